@@ -23,6 +23,11 @@ systemctl stop mullvad-daemon.service || true
 systemctl disable mullvad-daemon.service || true
 systemctl stop mullvad-early-boot-blocking.service || true
 systemctl disable mullvad-early-boot-blocking.service || true
+systemctl stop mullvad-early-boot-nftables.service || true
+systemctl disable mullvad-early-boot-nftables.service || true
+systemctl stop mullvad-early-boot-nftables-update.path || true
+systemctl disable mullvad-early-boot-nftables-update.path || true
+rm -f /var/cache/mullvad-vpn/early-boot-firewall.nft
 
 /opt/Mullvad\ VPN/resources/mullvad-setup reset-firewall || echo "Failed to reset firewall"
 /opt/Mullvad\ VPN/resources/mullvad-setup remove-device || echo "Failed to remove device from account"
@@ -189,6 +189,9 @@ const config = {
       distAssets('linux/before-remove.sh'),
       distAssets('linux/mullvad-daemon.service') +'=/usr/lib/systemd/system/mullvad-daemon.service',
       distAssets('linux/mullvad-early-boot-blocking.service') +'=/usr/lib/systemd/system/mullvad-early-boot-blocking.service',
+      distAssets('linux/mullvad-early-boot-nftables.service') +'=/usr/lib/systemd/system/mullvad-early-boot-nftables.service',
+      distAssets('linux/mullvad-early-boot-nftables-update.path') +'=/usr/lib/systemd/system/mullvad-early-boot-nftables-update.path',
+      distAssets('linux/mullvad-early-boot-nftables-update.service') +'=/usr/lib/systemd/system/mullvad-early-boot-nftables-update.service',
       distAssets(path.join(getLinuxTargetSubdir(), 'mullvad')) + '=/usr/bin/',
       distAssets(path.join(getLinuxTargetSubdir(), 'mullvad-daemon')) + '=/usr/bin/',
       distAssets(path.join(getLinuxTargetSubdir(), 'mullvad-exclude')) + '=/usr/bin/',
@@ -220,6 +223,9 @@ const config = {
       distAssets('linux/post-transaction.sh'),
       distAssets('linux/mullvad-daemon.service') +'=/usr/lib/systemd/system/mullvad-daemon.service',
       distAssets('linux/mullvad-early-boot-blocking.service') +'=/usr/lib/systemd/system/mullvad-early-boot-blocking.service',
+      distAssets('linux/mullvad-early-boot-nftables.service') +'=/usr/lib/systemd/system/mullvad-early-boot-nftables.service',
+      distAssets('linux/mullvad-early-boot-nftables-update.path') +'=/usr/lib/systemd/system/mullvad-early-boot-nftables-update.path',
+      distAssets('linux/mullvad-early-boot-nftables-update.service') +'=/usr/lib/systemd/system/mullvad-early-boot-nftables-update.service',
       distAssets(path.join(getLinuxTargetSubdir(), 'mullvad')) + '=/usr/bin/',
       distAssets(path.join(getLinuxTargetSubdir(), 'mullvad-daemon')) + '=/usr/bin/',
       distAssets(path.join(getLinuxTargetSubdir(), 'mullvad-exclude')) + '=/usr/bin/',
@@ -0,0 +1,272 @@
+//! Routes HTTP requests to the mock state.
+
+use crate::{
+    relay_list,
+    state::{ApiError, MockDevice, ProblemReport, RecordedRequest, State},
+    Endpoint,
+};
+use hyper::{header, Body, Method, Request, Response};
+use mullvad_api::StatusCode;
+use mullvad_types::account::AccountToken;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, Mutex};
+use talpid_types::net::wireguard::PublicKey;
+
+type Result<T> = std::result::Result<T, ApiError>;
+
+pub async fn handle(state: Arc<Mutex<State>>, request: Request<Body>) -> Response<Body> {
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            log::error!("Failed to read request body: {error}");
+            return empty(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let path = parts.uri.path().trim_start_matches('/');
+    let segments: Vec<&str> = path.split('/').collect();
+    let Some(endpoint) = Endpoint::from_request(&parts.method, &segments) else {
+        log::warn!("Mock API: unhandled request: {} {path}", parts.method);
+        return error_response(ApiError::new(StatusCode::NOT_FOUND, "NOT_FOUND"));
+    };
+    log::debug!("Mock API: {} {path}", parts.method);
+
+    let mut state = state.lock().unwrap();
+
+    let account = if endpoint.requires_auth() {
+        match bearer_token(&parts.headers).and_then(|token| state.authenticate(token)) {
+            Ok(account) => Some(account),
+            Err(error) => {
+                state.requests.push(RecordedRequest {
+                    endpoint,
+                    account: None,
+                });
+                return error_response(error);
+            }
+        }
+    } else if endpoint == Endpoint::AccessToken {
+        parse_body::<AccessTokenRequest>(&body)
+            .ok()
+            .map(|request| request.account_number)
+    } else {
+        None
+    };
+
+    state.requests.push(RecordedRequest {
+        endpoint,
+        account: account.clone(),
+    });
+
+    if let Some(error) = state.take_injected_error(endpoint, account.as_ref()) {
+        return error_response(error);
+    }
+
+    let result = handle_endpoint(
+        &mut state,
+        endpoint,
+        &segments,
+        account,
+        &parts.headers,
+        &body,
+    );
+    match result {
+        Ok(response) => response,
+        Err(error) => error_response(error),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct AccessTokenRequest {
+    account_number: AccountToken,
+}
+
+#[derive(serde::Deserialize)]
+struct DeviceSubmission {
+    pubkey: PublicKey,
+    #[serde(default)]
+    hijack_dns: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct PubkeySubmission {
+    pubkey: PublicKey,
+}
+
+#[derive(serde::Deserialize)]
+struct VoucherSubmission {
+    voucher_code: String,
+}
+
+fn handle_endpoint(
+    state: &mut State,
+    endpoint: Endpoint,
+    segments: &[&str],
+    account: Option<AccountToken>,
+    headers: &hyper::HeaderMap,
+    body: &[u8],
+) -> Result<Response<Body>> {
+    // Only set for endpoints that require authentication
+    let account = || account.clone().expect("missing account");
+
+    match endpoint {
+        Endpoint::AccessToken => {
+            let request: AccessTokenRequest = parse_body(body)?;
+            let (access_token, expiry) = state.issue_access_token(&request.account_number)?;
+            Ok(json(
+                StatusCode::OK,
+                serde_json::json!({ "access_token": access_token, "expiry": expiry }),
+            ))
+        }
+        Endpoint::CreateAccount => {
+            let token = state.create_account(chrono::Utc::now());
+            Ok(json(
+                StatusCode::CREATED,
+                serde_json::json!({ "number": token }),
+            ))
+        }
+        Endpoint::GetAccount => {
+            let account = account();
+            let data = state.account(&account)?;
+            Ok(json(
+                StatusCode::OK,
+                serde_json::json!({ "id": format!("id-{account}"), "expiry": data.expiry }),
+            ))
+        }
+        Endpoint::SubmitVoucher => {
+            let submission: VoucherSubmission = parse_body(body)?;
+            let (time_added, new_expiry) =
+                state.submit_voucher(&account(), &submission.voucher_code)?;
+            Ok(json(
+                StatusCode::OK,
+                serde_json::json!({ "time_added": time_added, "new_expiry": new_expiry }),
+            ))
+        }
+        Endpoint::WwwAuthToken => Ok(json(
+            StatusCode::OK,
+            serde_json::json!({ "auth_token": "mock-www-auth-token" }),
+        )),
+        Endpoint::CreateDevice => {
+            let submission: DeviceSubmission = parse_body(body)?;
+            let device =
+                state.create_device(&account(), submission.pubkey, submission.hijack_dns)?;
+            Ok(json(StatusCode::CREATED, device_to_value(&device)))
+        }
+        Endpoint::ListDevices => {
+            let devices: Vec<_> = state
+                .account(&account())?
+                .devices
+                .iter()
+                .map(device_to_value)
+                .collect();
+            Ok(json(StatusCode::OK, serde_json::Value::Array(devices)))
+        }
+        Endpoint::GetDevice => {
+            let device = state.device(&account(), &segments[3].to_owned())?;
+            Ok(json(StatusCode::OK, device_to_value(device)))
+        }
+        Endpoint::RemoveDevice => {
+            state.remove_device(&account(), &segments[3].to_owned())?;
+            Ok(empty(StatusCode::NO_CONTENT))
+        }
+        Endpoint::ReplaceKey => {
+            let submission: PubkeySubmission = parse_body(body)?;
+            let device =
+                state.replace_key(&account(), &segments[3].to_owned(), submission.pubkey)?;
+            Ok(json(StatusCode::OK, device_to_value(&device)))
+        }
+        Endpoint::RelayList => {
+            let Some((relay_list, etag)) = &state.relay_list else {
+                return Err(ApiError::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "NO_RELAY_LIST",
+                ));
+            };
+            let unchanged = headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|tag| tag.to_str().ok())
+                .map(|tag| tag.trim_start_matches("W/") == etag)
+                .unwrap_or(false);
+            if unchanged {
+                return Ok(empty(StatusCode::NOT_MODIFIED));
+            }
+            let mut response = json(StatusCode::OK, relay_list::to_server_format(relay_list));
+            response
+                .headers_mut()
+                .insert(header::ETAG, etag.parse().unwrap());
+            Ok(response)
+        }
+        Endpoint::AppVersion => {
+            let version = segments[4];
+            let response = match &state.app_version {
+                Some(response) => serde_json::json!({
+                    "supported": response.supported,
+                    "latest": response.latest,
+                    "latest_stable": response.latest_stable,
+                    "latest_beta": response.latest_beta,
+                }),
+                // By default, the running version is the latest one
+                None => serde_json::json!({
+                    "supported": true,
+                    "latest": version,
+                    "latest_stable": version,
+                    "latest_beta": version,
+                }),
+            };
+            Ok(json(StatusCode::OK, response))
+        }
+        Endpoint::ProblemReport => {
+            let report: ProblemReport = parse_body(body)?;
+            state.problem_reports.push(report);
+            Ok(empty(StatusCode::NO_CONTENT))
+        }
+        Endpoint::ApiAddrs => Ok(json(StatusCode::OK, serde_json::json!([]))),
+    }
+}
+
+fn bearer_token(headers: &hyper::HeaderMap) -> Result<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, mullvad_api::INVALID_ACCESS_TOKEN))
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "INVALID_REQUEST"))
+}
+
+fn device_to_value(device: &MockDevice) -> serde_json::Value {
+    serde_json::json!({
+        "id": device.device.id,
+        "name": device.device.name,
+        "pubkey": device.device.pubkey,
+        "ipv4_address": device.addresses.ipv4_address,
+        "ipv6_address": device.addresses.ipv6_address,
+        "hijack_dns": device.device.hijack_dns,
+        "created": device.device.created,
+    })
+}
+
+fn json(status: StatusCode, value: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap()
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn error_response(error: ApiError) -> Response<Body> {
+    json(
+        error.status,
+        serde_json::json!({ "code": error.code, "error": error.code }),
+    )
+}
@@ -0,0 +1,372 @@
+//! Scriptable mock of the Mullvad REST API. It runs an HTTP server in-process, keeps track of
+//! accounts and devices, and can be told to fail specific requests.
+//!
+//! `mullvad-api` reads its endpoint from a process-wide override. Use [`MockApi::shared`] to get a
+//! mock that `mullvad-api` is pointed at. Since it is shared by all tests in the process, tests
+//! should create their own accounts and scope injected errors to them using
+//! [`InjectedError::for_account`].
+//!
+//! ```ignore
+//! let mock = MockApi::shared();
+//! let account = mock.create_account();
+//! mock.inject_error(
+//!     InjectedError::new(Endpoint::CreateDevice, StatusCode::BAD_REQUEST, MAX_DEVICES_REACHED)
+//!         .for_account(&account)
+//!         .times(1),
+//! );
+//! ```
+
+use chrono::{DateTime, Utc};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Method, Server,
+};
+use mullvad_api::{ApiEndpoint, AppVersionResponse, StatusCode};
+use mullvad_types::{
+    account::AccountToken,
+    device::{Device, DeviceId},
+    relay_list::RelayList,
+};
+use once_cell::sync::OnceCell;
+use std::{
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use talpid_types::net::wireguard::PublicKey;
+use tokio::sync::oneshot;
+
+mod handler;
+mod relay_list;
+mod state;
+
+pub use state::{
+    MockDevice, ProblemReport, RecordedRequest, DEFAULT_ACCESS_TOKEN_LIFETIME, DEFAULT_MAX_DEVICES,
+};
+
+/// Number of days that new accounts have left by default.
+const DEFAULT_ACCOUNT_DAYS: i64 = 30;
+
+/// An API endpoint that the mock implements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// `POST auth/v1/token`
+    AccessToken,
+    /// `POST accounts/v1/accounts`
+    CreateAccount,
+    /// `GET accounts/v1/accounts/me`
+    GetAccount,
+    /// `POST app/v1/submit-voucher`
+    SubmitVoucher,
+    /// `POST app/v1/www-auth-token`
+    WwwAuthToken,
+    /// `POST accounts/v1/devices`
+    CreateDevice,
+    /// `GET accounts/v1/devices`
+    ListDevices,
+    /// `GET accounts/v1/devices/<id>`
+    GetDevice,
+    /// `DELETE accounts/v1/devices/<id>`
+    RemoveDevice,
+    /// `PUT accounts/v1/devices/<id>/pubkey`
+    ReplaceKey,
+    /// `GET app/v1/relays`
+    RelayList,
+    /// `GET app/v1/releases/<platform>/<version>`
+    AppVersion,
+    /// `POST app/v1/problem-report`
+    ProblemReport,
+    /// `GET` or `HEAD app/v1/api-addrs`
+    ApiAddrs,
+}
+
+impl Endpoint {
+    fn from_request(method: &Method, segments: &[&str]) -> Option<Self> {
+        let endpoint = match (method, segments) {
+            (&Method::POST, ["auth", "v1", "token"]) => Endpoint::AccessToken,
+            (&Method::POST, ["accounts", "v1", "accounts"]) => Endpoint::CreateAccount,
+            (&Method::GET, ["accounts", "v1", "accounts", "me"]) => Endpoint::GetAccount,
+            (&Method::POST, ["app", "v1", "submit-voucher"]) => Endpoint::SubmitVoucher,
+            (&Method::POST, ["app", "v1", "www-auth-token"]) => Endpoint::WwwAuthToken,
+            (&Method::POST, ["accounts", "v1", "devices"]) => Endpoint::CreateDevice,
+            (&Method::GET, ["accounts", "v1", "devices"]) => Endpoint::ListDevices,
+            (&Method::GET, ["accounts", "v1", "devices", _]) => Endpoint::GetDevice,
+            (&Method::DELETE, ["accounts", "v1", "devices", _]) => Endpoint::RemoveDevice,
+            (&Method::PUT, ["accounts", "v1", "devices", _, "pubkey"]) => Endpoint::ReplaceKey,
+            (&Method::GET, ["app", "v1", "relays"]) => Endpoint::RelayList,
+            (&Method::GET, ["app", "v1", "releases", _, _]) => Endpoint::AppVersion,
+            (&Method::POST, ["app", "v1", "problem-report"]) => Endpoint::ProblemReport,
+            (&Method::GET | &Method::HEAD, ["app", "v1", "api-addrs"]) => Endpoint::ApiAddrs,
+            _ => return None,
+        };
+        Some(endpoint)
+    }
+
+    /// Returns whether the endpoint needs an access token.
+    fn requires_auth(self) -> bool {
+        matches!(
+            self,
+            Endpoint::GetAccount
+                | Endpoint::SubmitVoucher
+                | Endpoint::WwwAuthToken
+                | Endpoint::CreateDevice
+                | Endpoint::ListDevices
+                | Endpoint::GetDevice
+                | Endpoint::RemoveDevice
+                | Endpoint::ReplaceKey
+        )
+    }
+}
+
+/// An error response to return instead of handling a request normally.
+#[derive(Debug, Clone)]
+pub struct InjectedError {
+    endpoint: Endpoint,
+    status: StatusCode,
+    code: String,
+    account: Option<AccountToken>,
+    remaining: Option<usize>,
+}
+
+impl InjectedError {
+    /// Fails all requests to `endpoint` with the given status and error code, e.g.
+    /// [`mullvad_api::MAX_DEVICES_REACHED`].
+    pub fn new(endpoint: Endpoint, status: StatusCode, code: &str) -> Self {
+        InjectedError {
+            endpoint,
+            status,
+            code: code.to_owned(),
+            account: None,
+            remaining: None,
+        }
+    }
+
+    /// Only fails requests made for `account`. For [`Endpoint::AccessToken`], this is the
+    /// account that a token is requested for.
+    pub fn for_account(mut self, account: &AccountToken) -> Self {
+        self.account = Some(account.clone());
+        self
+    }
+
+    /// Only fails the next `count` matching requests.
+    pub fn times(mut self, count: usize) -> Self {
+        self.remaining = Some(count);
+        self
+    }
+
+    fn matches(&self, endpoint: Endpoint, account: Option<&AccountToken>) -> bool {
+        self.endpoint == endpoint
+            && self.remaining != Some(0)
+            && self
+                .account
+                .as_ref()
+                .map(|expected| Some(expected) == account)
+                .unwrap_or(true)
+    }
+}
+
+/// A running mock API server. The server is stopped when this is dropped.
+pub struct MockApi {
+    addr: SocketAddr,
+    state: Arc<Mutex<state::State>>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl MockApi {
+    /// Starts a new mock server on a background thread, listening on localhost.
+    ///
+    /// Note that `mullvad-api` must be pointed at the server separately, see [`Self::shared`].
+    pub fn spawn() -> Self {
+        let listener =
+            TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("failed to bind mock API");
+        listener
+            .set_nonblocking(true)
+            .expect("failed to set mock API socket to non-blocking");
+        let addr = listener.local_addr().unwrap();
+
+        let state = Arc::new(Mutex::new(state::State::new()));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            // The server runs on its own runtime, so that it outlives the runtime of any single
+            // test
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to create mock API runtime");
+            runtime.block_on(async move {
+                let make_service = make_service_fn(move |_| {
+                    let state = server_state.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |request| {
+                            let state = state.clone();
+                            async move { Ok::<_, Infallible>(handler::handle(state, request).await) }
+                        }))
+                    }
+                });
+                let server = Server::from_tcp(listener)
+                    .expect("failed to start mock API")
+                    .serve(make_service)
+                    .with_graceful_shutdown(async {
+                        let _ = shutdown_rx.await;
+                    });
+                if let Err(error) = server.await {
+                    log::error!("Mock API stopped: {error}");
+                }
+            });
+        });
+
+        MockApi {
+            addr,
+            state,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    /// Returns a process-wide mock server, and points `mullvad-api` at it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `mullvad-api` endpoint has already been initialized to something else.
+    pub fn shared() -> &'static MockApi {
+        static SHARED: OnceCell<MockApi> = OnceCell::new();
+        SHARED.get_or_init(|| {
+            let mock = MockApi::spawn();
+            if mullvad_api::API.override_init(mock.api_endpoint()).is_err() {
+                panic!("The API endpoint was initialized before the mock API was started");
+            }
+            mock
+        })
+    }
+
+    /// Returns an endpoint that points at this server.
+    pub fn api_endpoint(&self) -> ApiEndpoint {
+        ApiEndpoint {
+            host: Some("api.mullvad.test".to_owned()),
+            address: Some(self.addr),
+            disable_address_cache: true,
+            disable_tls: true,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Creates an account with 30 days left.
+    pub fn create_account(&self) -> AccountToken {
+        self.create_account_with_expiry(Utc::now() + chrono::Duration::days(DEFAULT_ACCOUNT_DAYS))
+    }
+
+    pub fn create_account_with_expiry(&self, expiry: DateTime<Utc>) -> AccountToken {
+        self.state().create_account(expiry)
+    }
+
+    /// Changes the expiry of an existing account.
+    pub fn set_expiry(&self, account: &AccountToken, expiry: DateTime<Utc>) {
+        self.state()
+            .account_mut(account)
+            .expect("no such account")
+            .expiry = expiry;
+    }
+
+    /// Changes the number of devices that an existing account may have.
+    pub fn set_max_devices(&self, account: &AccountToken, max_devices: usize) {
+        self.state()
+            .account_mut(account)
+            .expect("no such account")
+            .max_devices = max_devices;
+    }
+
+    /// Registers a device directly, as if it had been added by another client.
+    pub fn add_device(&self, account: &AccountToken, pubkey: PublicKey) -> Device {
+        self.state()
+            .create_device(account, pubkey, false)
+            .expect("failed to add device")
+            .device
+    }
+
+    /// Removes a device directly, as if it had been removed by another client.
+    pub fn remove_device(&self, account: &AccountToken, id: &DeviceId) {
+        self.state()
+            .remove_device(account, id)
+            .expect("failed to remove device");
+    }
+
+    /// Returns the devices of an account.
+    pub fn devices(&self, account: &AccountToken) -> Vec<MockDevice> {
+        self.state()
+            .account(account)
+            .map(|account| account.devices.clone())
+            .unwrap_or_default()
+    }
+
+    /// Adds a voucher that adds `days` to the account it is redeemed on.
+    pub fn add_voucher(&self, code: &str, days: u32) {
+        self.state().add_voucher(code.to_owned(), days);
+    }
+
+    /// Invalidates all access tokens that have been issued for `account`, as if they had
+    /// expired.
+    pub fn revoke_access_tokens(&self, account: &AccountToken) {
+        self.state().revoke_access_tokens(account);
+    }
+
+    /// Sets how long new access tokens are valid.
+    pub fn set_access_token_lifetime(&self, lifetime: Duration) {
+        self.state().access_token_lifetime = lifetime;
+    }
+
+    /// Sets the relay list to serve, along with its ETag. Requests with a matching
+    /// `If-None-Match` header get `304 Not Modified`.
+    pub fn set_relay_list(&self, relay_list: RelayList, etag: &str) {
+        self.state().relay_list = Some((relay_list, format!("\"{etag}\"")));
+    }
+
+    /// Sets the version information to serve. By default, the requested version is reported as
+    /// the latest one.
+    pub fn set_app_version(&self, version: AppVersionResponse) {
+        self.state().app_version = Some(version);
+    }
+
+    /// Adds an error response. The first matching error is used for each request.
+    pub fn inject_error(&self, error: InjectedError) {
+        self.state().injected_errors.push(error);
+    }
+
+    /// Removes all injected errors for `account`.
+    pub fn clear_errors(&self, account: &AccountToken) {
+        self.state()
+            .injected_errors
+            .retain(|error| error.account.as_ref() != Some(account));
+    }
+
+    /// Returns the problem reports that have been submitted.
+    pub fn problem_reports(&self) -> Vec<ProblemReport> {
+        self.state().problem_reports.clone()
+    }
+
+    /// Returns the requests that have been made to `endpoint`.
+    pub fn requests(&self, endpoint: Endpoint) -> Vec<RecordedRequest> {
+        self.state()
+            .requests
+            .iter()
+            .filter(|request| request.endpoint == endpoint)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the number of requests that have been made to `endpoint` for `account`.
+    pub fn request_count(&self, endpoint: Endpoint, account: &AccountToken) -> usize {
+        self.requests(endpoint)
+            .iter()
+            .filter(|request| request.account.as_ref() == Some(account))
+            .count()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, state::State> {
+        self.state.lock().unwrap()
+    }
+}
@@ -0,0 +1,70 @@
+//! Converts relay lists into the format served by the API at `app/v1/relays`.
+
+use mullvad_types::relay_list::{Relay, RelayEndpointData, RelayList};
+use serde_json::{json, Map, Value};
+
+pub fn to_server_format(relay_list: &RelayList) -> Value {
+    let mut locations = Map::new();
+    let mut openvpn_relays = vec![];
+    let mut wireguard_relays = vec![];
+    let mut bridge_relays = vec![];
+
+    for country in &relay_list.countries {
+        for city in &country.cities {
+            let location = format!("{}-{}", country.code, city.code);
+            locations.insert(
+                location.clone(),
+                json!({
+                    "country": country.name,
+                    "city": city.name,
+                    "latitude": city.latitude,
+                    "longitude": city.longitude,
+                }),
+            );
+
+            for relay in &city.relays {
+                let mut value = relay_to_value(relay, &location);
+                match &relay.endpoint_data {
+                    RelayEndpointData::Openvpn => openvpn_relays.push(value),
+                    RelayEndpointData::Bridge => bridge_relays.push(value),
+                    RelayEndpointData::Wireguard(data) => {
+                        value["public_key"] = json!(data.public_key);
+                        wireguard_relays.push(value);
+                    }
+                }
+            }
+        }
+    }
+
+    json!({
+        "locations": locations,
+        "openvpn": {
+            "ports": relay_list.openvpn.ports,
+            "relays": openvpn_relays,
+        },
+        "wireguard": {
+            "port_ranges": relay_list.wireguard.port_ranges,
+            "ipv4_gateway": relay_list.wireguard.ipv4_gateway,
+            "ipv6_gateway": relay_list.wireguard.ipv6_gateway,
+            "relays": wireguard_relays,
+        },
+        "bridge": {
+            "shadowsocks": relay_list.bridge.shadowsocks,
+            "relays": bridge_relays,
+        },
+    })
+}
+
+fn relay_to_value(relay: &Relay, location: &str) -> Value {
+    json!({
+        "hostname": relay.hostname,
+        "active": relay.active,
+        "owned": relay.owned,
+        "location": location,
+        "provider": relay.provider,
+        "ipv4_addr_in": relay.ipv4_addr_in,
+        "ipv6_addr_in": relay.ipv6_addr_in,
+        "weight": relay.weight,
+        "include_in_country": relay.include_in_country,
+    })
+}
@@ -0,0 +1,340 @@
+//! Accounts, devices and other server-side state of the mock API.
+
+use crate::{Endpoint, InjectedError};
+use chrono::{DateTime, Duration, Utc};
+use mullvad_api::{AppVersionResponse, StatusCode};
+use mullvad_types::{
+    account::{AccessToken, AccountToken},
+    device::{Device, DeviceId},
+    relay_list::RelayList,
+    wireguard::AssociatedAddresses,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+use talpid_types::net::wireguard::PublicKey;
+
+/// Number of devices an account may have by default.
+pub const DEFAULT_MAX_DEVICES: usize = 5;
+/// How long access tokens are valid by default.
+pub const DEFAULT_ACCESS_TOKEN_LIFETIME: std::time::Duration =
+    std::time::Duration::from_secs(60 * 60);
+
+const DEVICE_NAME_ADJECTIVES: &[&str] = &["happy", "quiet", "brave", "eager", "lucky", "witty"];
+const DEVICE_NAME_NOUNS: &[&str] = &["seagull", "otter", "badger", "heron", "lynx", "marmot"];
+
+/// An error response from the API.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str) -> Self {
+        ApiError {
+            status,
+            code: code.to_owned(),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ApiError>;
+
+/// A device and the tunnel addresses assigned to it.
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    pub device: Device,
+    pub addresses: AssociatedAddresses,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Account {
+    pub expiry: DateTime<Utc>,
+    pub devices: Vec<MockDevice>,
+    pub max_devices: usize,
+}
+
+/// A submitted problem report.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProblemReport {
+    pub address: String,
+    pub message: String,
+    pub log: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// A request received by the mock.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub endpoint: Endpoint,
+    /// The account that the request was authenticated as, if any.
+    pub account: Option<AccountToken>,
+}
+
+pub(crate) struct Voucher {
+    days: u32,
+    used: bool,
+}
+
+pub(crate) struct State {
+    accounts: HashMap<AccountToken, Account>,
+    access_tokens: HashMap<AccessToken, (AccountToken, DateTime<Utc>)>,
+    vouchers: HashMap<String, Voucher>,
+    pub access_token_lifetime: std::time::Duration,
+    pub relay_list: Option<(RelayList, String)>,
+    pub app_version: Option<AppVersionResponse>,
+    pub problem_reports: Vec<ProblemReport>,
+    pub injected_errors: Vec<InjectedError>,
+    pub requests: Vec<RecordedRequest>,
+    next_id: u64,
+}
+
+impl State {
+    pub fn new() -> Self {
+        State {
+            accounts: HashMap::new(),
+            access_tokens: HashMap::new(),
+            vouchers: HashMap::new(),
+            access_token_lifetime: DEFAULT_ACCESS_TOKEN_LIFETIME,
+            relay_list: None,
+            app_version: None,
+            problem_reports: vec![],
+            injected_errors: vec![],
+            requests: vec![],
+            next_id: 1,
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Returns the error to respond with, if an injected error matches the request.
+    pub fn take_injected_error(
+        &mut self,
+        endpoint: Endpoint,
+        account: Option<&AccountToken>,
+    ) -> Option<ApiError> {
+        let index = self
+            .injected_errors
+            .iter()
+            .position(|error| error.matches(endpoint, account))?;
+        let error = &mut self.injected_errors[index];
+        let response = ApiError::new(error.status, &error.code);
+        if let Some(remaining) = &mut error.remaining {
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.injected_errors.remove(index);
+            }
+        }
+        Some(response)
+    }
+
+    pub fn create_account(&mut self, expiry: DateTime<Utc>) -> AccountToken {
+        let token = format!("{:016}", 1_000_000_000_000_000 + self.next_id());
+        self.accounts.insert(
+            token.clone(),
+            Account {
+                expiry,
+                devices: vec![],
+                max_devices: DEFAULT_MAX_DEVICES,
+            },
+        );
+        token
+    }
+
+    pub fn account(&self, token: &AccountToken) -> Result<&Account> {
+        self.accounts
+            .get(token)
+            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, mullvad_api::INVALID_ACCOUNT))
+    }
+
+    pub fn account_mut(&mut self, token: &AccountToken) -> Result<&mut Account> {
+        self.accounts
+            .get_mut(token)
+            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, mullvad_api::INVALID_ACCOUNT))
+    }
+
+    pub fn issue_access_token(
+        &mut self,
+        account: &AccountToken,
+    ) -> Result<(AccessToken, DateTime<Utc>)> {
+        if !self.accounts.contains_key(account) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                mullvad_api::INVALID_ACCOUNT,
+            ));
+        }
+        let token = format!("mock-access-token-{}", self.next_id());
+        let expiry = Utc::now() + Duration::from_std(self.access_token_lifetime).unwrap();
+        self.access_tokens
+            .insert(token.clone(), (account.clone(), expiry));
+        Ok((token, expiry))
+    }
+
+    /// Returns the account that `token` belongs to, if it is valid.
+    pub fn authenticate(&self, token: &str) -> Result<AccountToken> {
+        match self.access_tokens.get(token) {
+            Some((account, expiry)) if *expiry > Utc::now() => Ok(account.clone()),
+            _ => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                mullvad_api::INVALID_ACCESS_TOKEN,
+            )),
+        }
+    }
+
+    /// Invalidates all access tokens issued for `account`.
+    pub fn revoke_access_tokens(&mut self, account: &AccountToken) {
+        self.access_tokens.retain(|_, (owner, _)| owner != account);
+    }
+
+    pub fn create_device(
+        &mut self,
+        account: &AccountToken,
+        pubkey: PublicKey,
+        hijack_dns: bool,
+    ) -> Result<MockDevice> {
+        if self.device_with_pubkey(&pubkey).is_some() {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                mullvad_api::PUBKEY_IN_USE,
+            ));
+        }
+        let id = self.next_id();
+        let account = self.account_mut(account)?;
+        if account.devices.len() >= account.max_devices {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                mullvad_api::MAX_DEVICES_REACHED,
+            ));
+        }
+
+        let name = format!(
+            "{} {}",
+            DEVICE_NAME_ADJECTIVES[id as usize % DEVICE_NAME_ADJECTIVES.len()],
+            DEVICE_NAME_NOUNS
+                [(id as usize / DEVICE_NAME_ADJECTIVES.len()) % DEVICE_NAME_NOUNS.len()],
+        );
+        let device = MockDevice {
+            device: Device {
+                id: format!("{id:08x}-0000-4000-8000-000000000000"),
+                name,
+                pubkey,
+                hijack_dns,
+                created: Utc::now(),
+            },
+            addresses: addresses_for_id(id),
+        };
+        account.devices.push(device.clone());
+        Ok(device)
+    }
+
+    pub fn device(&self, account: &AccountToken, id: &DeviceId) -> Result<&MockDevice> {
+        self.account(account)?
+            .devices
+            .iter()
+            .find(|device| &device.device.id == id)
+            .ok_or_else(device_not_found)
+    }
+
+    pub fn remove_device(&mut self, account: &AccountToken, id: &DeviceId) -> Result<()> {
+        let devices = &mut self.account_mut(account)?.devices;
+        let index = devices
+            .iter()
+            .position(|device| &device.device.id == id)
+            .ok_or_else(device_not_found)?;
+        devices.remove(index);
+        Ok(())
+    }
+
+    pub fn replace_key(
+        &mut self,
+        account: &AccountToken,
+        id: &DeviceId,
+        pubkey: PublicKey,
+    ) -> Result<MockDevice> {
+        if let Some(other) = self.device_with_pubkey(&pubkey) {
+            if &other.device.id != id {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    mullvad_api::PUBKEY_IN_USE,
+                ));
+            }
+        }
+        let new_id = self.next_id();
+        let device = self
+            .account_mut(account)?
+            .devices
+            .iter_mut()
+            .find(|device| &device.device.id == id)
+            .ok_or_else(device_not_found)?;
+        device.device.pubkey = pubkey;
+        // Like the real API, a new key gets new addresses
+        device.addresses = addresses_for_id(new_id);
+        Ok(device.clone())
+    }
+
+    fn device_with_pubkey(&self, pubkey: &PublicKey) -> Option<&MockDevice> {
+        self.accounts
+            .values()
+            .flat_map(|account| account.devices.iter())
+            .find(|device| &device.device.pubkey == pubkey)
+    }
+
+    pub fn add_voucher(&mut self, code: String, days: u32) {
+        self.vouchers.insert(code, Voucher { days, used: false });
+    }
+
+    /// Redeems a voucher. Returns the number of seconds added and the new expiry.
+    pub fn submit_voucher(
+        &mut self,
+        account: &AccountToken,
+        code: &str,
+    ) -> Result<(u64, DateTime<Utc>)> {
+        let voucher = self
+            .vouchers
+            .get_mut(code)
+            .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, mullvad_api::INVALID_VOUCHER))?;
+        if voucher.used {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                mullvad_api::VOUCHER_USED,
+            ));
+        }
+        voucher.used = true;
+        let time_added = Duration::days(i64::from(voucher.days));
+
+        let account = self.account_mut(account)?;
+        account.expiry = account.expiry.max(Utc::now()) + time_added;
+        Ok((time_added.num_seconds() as u64, account.expiry))
+    }
+}
+
+fn device_not_found() -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, mullvad_api::DEVICE_NOT_FOUND)
+}
+
+fn addresses_for_id(id: u64) -> AssociatedAddresses {
+    let [.., a, b] = (id as u32).to_be_bytes();
+    AssociatedAddresses {
+        ipv4_address: ipnetwork::Ipv4Network::new(Ipv4Addr::new(10, 64, a, b), 32).unwrap(),
+        ipv6_address: ipnetwork::Ipv6Network::new(
+            Ipv6Addr::new(
+                0xfc00,
+                0xbbbb,
+                0xbbbb,
+                0xbb01,
+                0,
+                0,
+                u16::from(a),
+                u16::from(b),
+            ),
+            128,
+        )
+        .unwrap(),
+    }
+}
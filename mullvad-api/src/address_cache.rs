@@ -1,5 +1,11 @@
 use super::API;
-use std::{io, net::SocketAddr, path::Path, sync::Arc};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    sync::Arc,
+};
+use talpid_types::net::nat64::Nat64Prefix;
 use tokio::{
     fs,
     io::{AsyncReadExt, AsyncWriteExt},
@@ -60,9 +66,20 @@ impl AddressCache {
         }
     }
 
-    /// Returns the currently selected address.
+    /// Returns the currently selected address. If a NAT64 prefix is set, IPv4 addresses are
+    /// returned as the IPv6 addresses that they are reached at through NAT64.
     pub async fn get_address(&self) -> SocketAddr {
-        self.inner.lock().await.address
+        self.inner.lock().await.reachable_address()
+    }
+
+    /// Sets the NAT64 prefix to use for reaching IPv4 addresses over IPv6. This should only be
+    /// set on networks without IPv4 connectivity. Returns whether the address returned by
+    /// [`Self::get_address`] changed.
+    pub async fn set_nat64_prefix(&self, nat64_prefix: Option<Nat64Prefix>) -> bool {
+        let mut inner = self.inner.lock().await;
+        let old_address = inner.reachable_address();
+        inner.nat64_prefix = nat64_prefix;
+        old_address != inner.reachable_address()
     }
 
     pub async fn set_address(&self, address: SocketAddr) -> Result<(), Error> {
@@ -95,11 +112,24 @@ impl AddressCache {
 #[derive(Clone, PartialEq, Eq)]
 struct AddressCacheInner {
     address: SocketAddr,
+    nat64_prefix: Option<Nat64Prefix>,
 }
 
 impl AddressCacheInner {
     fn from_address(address: SocketAddr) -> Self {
-        Self { address }
+        Self {
+            address,
+            nat64_prefix: None,
+        }
+    }
+
+    fn reachable_address(&self) -> SocketAddr {
+        match (self.address.ip(), self.nat64_prefix) {
+            (IpAddr::V4(addr), Some(prefix)) => {
+                SocketAddr::new(prefix.synthesize(addr).into(), self.address.port())
+            }
+            _ => self.address,
+        }
     }
 }
 
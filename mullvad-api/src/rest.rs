@@ -361,38 +361,40 @@ impl Request {
         }
 
         // Make request to hyper client
-        let response = hyper_client
-            .request(self.request)
-            .await
-            .map_err(Error::from);
-
-        // Notify access token store of expired tokens
-        if let (Some(account), Some(store)) = (&self.account, &self.access_token_store) {
-            store.check_response(account, &response);
-        }
+        let response = hyper_client.request(self.request).await;
 
         // Parse unexpected responses and errors
-
-        let response = response?;
-
-        if !self.expected_status.contains(&response.status()) {
-            if !self.expected_status.is_empty() {
-                log::error!(
-                    "Unexpected HTTP status code {}, expected codes [{}]",
-                    response.status(),
-                    self.expected_status
-                        .iter()
-                        .map(ToString::to_string)
-                        .collect::<Vec<_>>()
-                        .join(",")
-                );
-            }
-            if !response.status().is_success() {
-                return handle_error_response(response).await;
+        let result = async {
+            let response = response?;
+
+            if !self.expected_status.contains(&response.status()) {
+                if !self.expected_status.is_empty() {
+                    log::error!(
+                        "Unexpected HTTP status code {}, expected codes [{}]",
+                        response.status(),
+                        self.expected_status
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    );
+                }
+                if !response.status().is_success() {
+                    return handle_error_response(response).await;
+                }
             }
+
+            Ok(Response::new(response))
+        }
+        .await;
+
+        // Notify access token store of expired tokens. This must happen after error responses
+        // have been parsed, since that is where the error code is known.
+        if let (Some(account), Some(store)) = (&self.account, &self.access_token_store) {
+            store.check_response(account, &result);
         }
 
-        Ok(Response::new(response))
+        result
     }
 
     /// Returns the URI of the request
@@ -3,7 +3,12 @@ use clap::Subcommand;
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{account::AccountToken, device::DeviceState};
-use std::io::{self, Write};
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::format;
 
 const NOT_LOGGED_IN_MESSAGE: &str = "Not logged in on any account";
 const REVOKED_MESSAGE: &str = "The current device has been revoked";
@@ -55,6 +60,14 @@ pub enum Account {
         /// Voucher code to submit
         voucher: String,
     },
+
+    /// Set how long before the account expires to show warnings. Warnings are disabled if no
+    /// times are given
+    SetExpiryWarnings {
+        /// Time before the account expires, in days, hours or minutes, e.g. '7d 1d 1h'
+        #[arg(value_parser = super::parse_duration)]
+        thresholds: Vec<Duration>,
+    },
 }
 
 impl Account {
@@ -78,6 +91,9 @@ impl Account {
                 Self::revoke_device(&mut rpc, device, account).await
             }
             Account::Redeem { voucher } => Self::redeem_voucher(&mut rpc, voucher).await,
+            Account::SetExpiryWarnings { thresholds } => {
+                Self::set_expiry_warnings(&mut rpc, thresholds).await
+            }
         }
     }
 
@@ -104,6 +120,21 @@ impl Account {
 
         let state = rpc.get_device().await?;
 
+        if format::json_output() {
+            let (account, expiry_warning) = match &state {
+                DeviceState::LoggedIn(device) => (
+                    Some(rpc.get_account_data(device.account_token.clone()).await?),
+                    rpc.get_expiry_warning().await?,
+                ),
+                DeviceState::LoggedOut | DeviceState::Revoked => (None, None),
+            };
+            return format::print_json(&serde_json::json!({
+                "device_state": state,
+                "account": account,
+                "expiry_warning": expiry_warning,
+            }));
+        }
+
         match state {
             DeviceState::LoggedIn(device) => {
                 println!("{:<20}{}", "Mullvad account:", device.account_token);
@@ -117,6 +148,9 @@ impl Account {
                 if verbose {
                     println!("{:<20}{}", "Account id:", data.id);
                 }
+                if let Some(warning) = rpc.get_expiry_warning().await? {
+                    format::print_expiry_warning(&warning);
+                }
 
                 println!("{:<20}{}", "Device name:", device.device.pretty_name());
                 if verbose {
@@ -146,9 +180,13 @@ impl Account {
     ) -> Result<()> {
         let token = account_else_current(rpc, account).await?;
         let mut device_list = rpc.list_devices(token).await?;
+        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
+
+        if format::json_output() {
+            return format::print_json(&device_list);
+        }
 
         println!("Devices on the account:");
-        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
         for device in device_list {
             if verbose {
                 println!();
@@ -192,6 +230,9 @@ impl Account {
         voucher.retain(|c| c.is_alphanumeric());
 
         let submission = rpc.submit_voucher(voucher).await?;
+        if format::json_output() {
+            return format::print_json(&submission);
+        }
         println!(
             "Added {} to the account",
             format_duration(submission.time_added)
@@ -202,6 +243,28 @@ impl Account {
         );
         Ok(())
     }
+
+    async fn set_expiry_warnings(
+        rpc: &mut MullvadProxyClient,
+        mut thresholds: Vec<Duration>,
+    ) -> Result<()> {
+        thresholds.sort_unstable_by(|a, b| b.cmp(a));
+        thresholds.dedup();
+        rpc.set_expiry_warning_thresholds(thresholds.clone())
+            .await?;
+        if thresholds.is_empty() {
+            println!("Disabled account expiry warnings");
+        } else {
+            println!(
+                "Will warn {} before the account expires",
+                thresholds
+                    .into_iter()
+                    .map(format::format_threshold)
+                    .join(", ")
+            );
+        }
+        Ok(())
+    }
 }
 
 async fn account_else_current(
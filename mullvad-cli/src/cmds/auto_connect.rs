@@ -3,6 +3,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::BooleanOption;
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum AutoConnect {
@@ -29,7 +30,11 @@ impl AutoConnect {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let auto_connect = BooleanOption::from(rpc.get_settings().await?.auto_connect);
+        let auto_connect = rpc.get_settings().await?.auto_connect;
+        if format::json_output() {
+            return format::print_json(&serde_json::json!({ "auto_connect": auto_connect }));
+        }
+        let auto_connect = BooleanOption::from(auto_connect);
         println!("Autoconnect: {auto_connect}");
         Ok(())
     }
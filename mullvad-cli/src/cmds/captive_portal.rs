@@ -0,0 +1,61 @@
+use crate::format;
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use std::time::Duration;
+
+#[derive(Subcommand, Debug)]
+pub enum CaptivePortal {
+    /// Temporarily allow DNS, HTTP and HTTPS traffic to the gateway of the network while the VPN
+    /// is blocking, so that you can log in to a captive portal. The login ends as soon as the
+    /// internet can be reached
+    Login {
+        /// Longest time to allow captive portal traffic, in seconds
+        #[arg(long, short = 't')]
+        timeout: Option<u64>,
+    },
+    /// Stop allowing captive portal traffic
+    Cancel,
+    /// Show the status of the current or most recent login, or whether a captive portal was
+    /// detected
+    Get,
+}
+
+impl CaptivePortal {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            CaptivePortal::Login { timeout } => Self::login(timeout.map(Duration::from_secs)).await,
+            CaptivePortal::Cancel => Self::cancel().await,
+            CaptivePortal::Get => Self::get().await,
+        }
+    }
+
+    async fn login(timeout: Option<Duration>) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.start_captive_portal_login(timeout).await?;
+        println!("Allowing captive portal traffic");
+        println!("Open any website that uses plain HTTP in a browser to reach the login page");
+        Ok(())
+    }
+
+    async fn cancel() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.cancel_captive_portal_login().await?;
+        println!("Stopped allowing captive portal traffic");
+        Ok(())
+    }
+
+    async fn get() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let state = rpc.get_tunnel_state().await?;
+        let captive_portal = state.captive_portal();
+        if format::json_output() {
+            return format::print_json(&serde_json::json!({ "captive_portal": captive_portal }));
+        }
+        if captive_portal.is_none() {
+            println!("No captive portal login");
+        }
+        format::print_captive_portal_login(captive_portal);
+        Ok(())
+    }
+}
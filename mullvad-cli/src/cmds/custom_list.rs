@@ -1,4 +1,5 @@
-use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs};
+use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs, BooleanOption};
+use crate::format;
 use anyhow::{anyhow, bail, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
@@ -32,6 +33,9 @@ pub enum CustomList {
         /// A custom list
         name: String,
     },
+
+    /// Automatically remove relays from custom lists when they are removed from the relay list
+    AutoPrune { state: BooleanOption },
 }
 
 #[derive(Subcommand, Debug)]
@@ -68,6 +72,7 @@ impl CustomList {
             CustomList::List { name: Some(name) } => Self::get(name).await,
             CustomList::New { name } => Self::create_list(name).await,
             CustomList::Delete { name } => Self::delete_list(name).await,
+            CustomList::AutoPrune { state } => Self::set_auto_prune(state).await,
             CustomList::Edit(cmd) => match cmd {
                 EditCommand::Add { name, location } => Self::add_location(name, location).await,
                 EditCommand::Rename { name, new_name } => Self::rename_list(name, new_name).await,
@@ -81,8 +86,12 @@ impl CustomList {
     /// Print all custom lists.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
+        let custom_lists = rpc.get_settings().await?.custom_lists;
+        if format::json_output() {
+            return format::print_json(&custom_lists);
+        }
         let cache = rpc.get_relay_locations().await?;
-        for custom_list in rpc.get_settings().await?.custom_lists {
+        for custom_list in custom_lists {
             Self::print_custom_list(&custom_list, &cache)
         }
         Ok(())
@@ -93,6 +102,9 @@ impl CustomList {
     async fn get(name: String) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let custom_list = find_list_by_name(&mut rpc, &name).await?;
+        if format::json_output() {
+            return format::print_json(&custom_list);
+        }
         let cache = rpc.get_relay_locations().await?;
         Self::print_custom_list_content(&custom_list, &cache);
         Ok(())
@@ -159,6 +171,13 @@ impl CustomList {
         Ok(())
     }
 
+    async fn set_auto_prune(state: BooleanOption) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.set_prune_custom_lists(*state).await?;
+        println!("Remove relays that no longer exist from custom lists: {state}");
+        Ok(())
+    }
+
     async fn rename_list(name: String, new_name: String) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
 
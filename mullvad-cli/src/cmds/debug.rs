@@ -1,11 +1,42 @@
+use crate::format;
 use anyhow::Result;
+use futures::StreamExt;
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::relay_constraints::{Constraint, RelayConstraints, RelaySettings};
+use mullvad_types::{
+    logging::LogFormat,
+    relay_constraints::{Constraint, RelayConstraints, RelaySettings},
+};
+use std::collections::BTreeMap;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum DebugCommands {
     /// Block all internet connection by setting an invalid relay constraint.
     BlockConnection,
+
+    /// Show or change the daemon log levels, and optionally follow the log. Changes last until
+    /// the daemon is restarted. Requires root, or membership of the management socket group on
+    /// Linux and macOS.
+    Log {
+        /// Log level to use: off, error, warn, info, debug or trace
+        #[arg(long)]
+        level: Option<log::LevelFilter>,
+
+        /// Only change the level of this module and its submodules, e.g. `talpid_core::firewall`
+        #[arg(long)]
+        module: Option<String>,
+
+        /// Make the module use the default log level again
+        #[arg(long, requires = "module", conflicts_with = "level")]
+        reset: bool,
+
+        /// Print log records as they are written, until interrupted
+        #[arg(long, short = 'f')]
+        follow: bool,
+
+        /// Print followed log records as JSON objects, one per line
+        #[arg(long, requires = "follow")]
+        json: bool,
+    },
 }
 
 impl DebugCommands {
@@ -35,6 +66,72 @@ impl DebugCommands {
                 eprintln!("WARNING: ENTERED BLOCKED MODE");
                 Ok(())
             }
+            DebugCommands::Log {
+                level,
+                module,
+                reset,
+                follow,
+                json,
+            } => handle_log(level, module, reset, follow, json).await,
+        }
+    }
+}
+
+async fn handle_log(
+    level: Option<log::LevelFilter>,
+    module: Option<String>,
+    reset: bool,
+    follow: bool,
+    json: bool,
+) -> Result<()> {
+    let mut rpc = MullvadProxyClient::new().await?;
+
+    match (module, level) {
+        (Some(module), Some(level)) => {
+            rpc.set_module_log_level(module.clone(), Some(level))
+                .await?;
+            eprintln!("Log level for {module} set to {level}");
+        }
+        (Some(module), None) if reset => {
+            rpc.set_module_log_level(module.clone(), None).await?;
+            eprintln!("Log level for {module} reset");
+        }
+        (None, Some(level)) => {
+            rpc.set_log_level(level).await?;
+            eprintln!("Log level set to {level}");
+        }
+        _ if !follow => {
+            let filter = rpc.get_log_filter().await?;
+            if format::json_output() {
+                let level = |level: log::LevelFilter| level.as_str().to_lowercase();
+                let modules: BTreeMap<_, _> = filter
+                    .modules
+                    .into_iter()
+                    .map(|(module, module_level)| (module, level(module_level)))
+                    .collect();
+                return format::print_json(&serde_json::json!({
+                    "level": level(filter.level),
+                    "modules": modules,
+                }));
+            }
+            println!("Default: {}", filter.level);
+            for (module, level) in filter.modules {
+                println!("{module}: {level}");
+            }
+        }
+        _ => (),
+    }
+
+    if follow {
+        let format = if json {
+            LogFormat::Json
+        } else {
+            LogFormat::Text
+        };
+        let mut lines = rpc.stream_logs(format).await?;
+        while let Some(line) = lines.next().await {
+            println!("{}", line?);
         }
     }
+    Ok(())
 }
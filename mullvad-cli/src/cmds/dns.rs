@@ -4,6 +4,8 @@ use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
 use std::net::IpAddr;
 
+use crate::format;
+
 #[derive(Subcommand, Debug)]
 pub enum Dns {
     /// Display the current DNS settings
@@ -89,6 +91,10 @@ impl Dns {
         let mut rpc = MullvadProxyClient::new().await?;
         let options = rpc.get_settings().await?.tunnel_options.dns_options;
 
+        if format::json_output() {
+            return format::print_json(&options);
+        }
+
         match options.state {
             DnsState::Default => {
                 println!("Custom DNS: no");
@@ -1,8 +1,18 @@
 use anyhow::Result;
+#[cfg(not(target_os = "windows"))]
+use clap::Args;
 use clap::Subcommand;
+#[cfg(not(target_os = "windows"))]
+use ipnetwork::IpNetwork;
 use mullvad_management_interface::MullvadProxyClient;
+#[cfg(not(target_os = "windows"))]
+use talpid_types::net::{
+    firewall_exception::{Direction, FirewallException},
+    TransportProtocol,
+};
 
 use super::BooleanOption;
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum Lan {
@@ -14,6 +24,55 @@ pub enum Lan {
         #[arg(value_parser = BooleanOption::custom_parser("allow", "block"))]
         policy: BooleanOption,
     },
+
+    /// Manage firewall exceptions, which allow traffic between this device and specific networks
+    /// outside the tunnel, in addition to local network sharing
+    #[cfg(not(target_os = "windows"))]
+    #[clap(subcommand)]
+    Exception(ExceptionCommands),
+}
+
+#[cfg(not(target_os = "windows"))]
+#[derive(Subcommand, Debug)]
+pub enum ExceptionCommands {
+    /// List firewall exceptions
+    List,
+    /// Add a firewall exception
+    Add(ExceptionArgs),
+    /// Remove a firewall exception. The arguments must be the same as when it was added
+    Remove(ExceptionArgs),
+}
+
+#[cfg(not(target_os = "windows"))]
+#[derive(Args, Debug)]
+pub struct ExceptionArgs {
+    /// Network or IP address to allow traffic with, e.g. '192.168.1.5' or '100.64.0.0/10'.
+    /// Networks that are not local must be at most /24 for IPv4 and /64 for IPv6, limited to a
+    /// port, a protocol or a single host, and either inbound or outbound
+    network: IpNetwork,
+    /// Only allow this transport protocol
+    #[arg(long)]
+    protocol: Option<TransportProtocol>,
+    /// Only allow connections to this port. This is the port of the remote host for outbound
+    /// connections, and the local port for inbound connections. Allows TCP and UDP unless
+    /// --protocol is given
+    #[arg(long)]
+    port: Option<u16>,
+    /// Which connections to allow: 'outbound', 'inbound' or 'both'. Replies are always allowed
+    #[arg(long, default_value_t = Direction::Both)]
+    direction: Direction,
+}
+
+#[cfg(not(target_os = "windows"))]
+impl From<ExceptionArgs> for FirewallException {
+    fn from(args: ExceptionArgs) -> Self {
+        FirewallException {
+            network: args.network,
+            protocol: args.protocol,
+            port: args.port,
+            direction: args.direction,
+        }
+    }
 }
 
 impl Lan {
@@ -21,6 +80,8 @@ impl Lan {
         match self {
             Lan::Get => Self::get().await,
             Lan::Set { policy } => Self::set(policy).await,
+            #[cfg(not(target_os = "windows"))]
+            Lan::Exception(subcmd) => Self::exception(subcmd).await,
         }
     }
 
@@ -33,9 +94,42 @@ impl Lan {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let allow_lan =
-            BooleanOption::with_labels(rpc.get_settings().await?.allow_lan, "allow", "block");
+        let allow_lan = rpc.get_settings().await?.allow_lan;
+        if format::json_output() {
+            return format::print_json(&serde_json::json!({ "allow_lan": allow_lan }));
+        }
+        let allow_lan = BooleanOption::with_labels(allow_lan, "allow", "block");
         println!("Local network sharing setting: {allow_lan}");
         Ok(())
     }
+
+    #[cfg(not(target_os = "windows"))]
+    async fn exception(subcmd: ExceptionCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match subcmd {
+            ExceptionCommands::List => {
+                let exceptions = rpc.get_settings().await?.firewall_exceptions;
+                if format::json_output() {
+                    return format::print_json(&exceptions);
+                }
+                if exceptions.is_empty() {
+                    println!("No firewall exceptions");
+                }
+                for exception in exceptions {
+                    println!("{exception}");
+                }
+            }
+            ExceptionCommands::Add(args) => {
+                let exception = FirewallException::from(args);
+                rpc.add_firewall_exception(exception.clone()).await?;
+                println!("Added firewall exception {exception}");
+            }
+            ExceptionCommands::Remove(args) => {
+                let exception = FirewallException::from(args);
+                rpc.remove_firewall_exception(exception.clone()).await?;
+                println!("Removed firewall exception {exception}");
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,11 +1,13 @@
 use clap::builder::{PossibleValuesParser, TypedValueParser, ValueParser};
-use std::{io::stdin, ops::Deref};
+use std::{io::stdin, ops::Deref, time::Duration};
 
 pub mod account;
 pub mod api_access;
 pub mod auto_connect;
 pub mod beta_program;
 pub mod bridge;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod captive_portal;
 pub mod custom_list;
 pub mod debug;
 pub mod dns;
@@ -13,12 +15,16 @@ pub mod lan;
 pub mod lockdown;
 pub mod obfuscation;
 pub mod patch;
+pub mod profile;
 pub mod proxies;
 pub mod relay;
 pub mod relay_constraints;
 pub mod reset;
+pub mod settings_history;
 pub mod split_tunnel;
 pub mod status;
+#[cfg(target_os = "linux")]
+pub mod trusted_interface;
 pub mod tunnel;
 pub mod tunnel_state;
 pub mod version;
@@ -84,6 +90,34 @@ impl std::fmt::Display for BooleanOption {
     }
 }
 
+/// Parses a positive duration in days, hours, minutes or seconds, e.g. '7d', '1h' or '10m'
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (amount, unit_seconds) = if let Some(days) = value.strip_suffix('d') {
+        (days, 24 * 60 * 60)
+    } else if let Some(hours) = value.strip_suffix('h') {
+        (hours, 60 * 60)
+    } else if let Some(minutes) = value.strip_suffix('m') {
+        (minutes, 60)
+    } else if let Some(seconds) = value.strip_suffix('s') {
+        (seconds, 1)
+    } else {
+        return Err(format!(
+            "'{value}' has no unit. Expected 'd', 'h', 'm', or 's'"
+        ));
+    };
+    let amount = amount
+        .parse::<u64>()
+        .map_err(|error| format!("invalid time '{value}': {error}"))?;
+    if amount == 0 {
+        return Err("the time must be positive".to_owned());
+    }
+    amount
+        .checked_mul(unit_seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("'{value}' is too large"))
+}
+
 async fn receive_confirmation(msg: &'static str, default: bool) -> bool {
     let helper_str = match default {
         true => "[Y/n]",
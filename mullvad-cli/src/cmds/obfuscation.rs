@@ -5,6 +5,8 @@ use mullvad_types::relay_constraints::{
     Constraint, ObfuscationSettings, SelectedObfuscation, Udp2TcpObfuscationSettings,
 };
 
+use crate::format;
+
 #[derive(Subcommand, Debug)]
 pub enum Obfuscation {
     /// Get current obfuscation settings
@@ -35,6 +37,9 @@ impl Obfuscation {
             Obfuscation::Get => {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let obfuscation_settings = rpc.get_settings().await?.obfuscation_settings;
+                if format::json_output() {
+                    return format::print_json(&obfuscation_settings);
+                }
                 println!(
                     "Obfuscation mode: {}",
                     obfuscation_settings.selected_obfuscation
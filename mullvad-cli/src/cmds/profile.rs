@@ -0,0 +1,98 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{relay_constraints::RelaySettingsFormatter, settings::DnsState};
+
+use crate::{format, print_option};
+
+#[derive(Subcommand, Debug)]
+pub enum Profile {
+    /// Save the current relay settings, obfuscation settings, tunnel options, DNS options, and
+    /// LAN setting as a new profile
+    Create {
+        /// A name for the new profile
+        name: String,
+    },
+
+    /// Show all profiles
+    List,
+
+    /// Replace the current settings with those saved in a profile
+    Apply {
+        /// Name of the profile
+        name: String,
+    },
+
+    /// Delete a profile
+    Delete {
+        /// Name of the profile
+        name: String,
+    },
+}
+
+impl Profile {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Profile::Create { name } => Self::create(name).await,
+            Profile::List => Self::list().await,
+            Profile::Apply { name } => Self::apply(name).await,
+            Profile::Delete { name } => Self::delete(name).await,
+        }
+    }
+
+    async fn create(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.create_profile(name.clone()).await?;
+        println!("Saved current settings as profile \"{name}\"");
+        Ok(())
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let profiles = rpc.get_profiles().await?;
+        if format::json_output() {
+            return format::print_json(&profiles);
+        }
+        let custom_lists = rpc.get_settings().await?.custom_lists;
+        for profile in profiles {
+            println!("{}", profile.name);
+            print_option!(
+                "Relay settings",
+                RelaySettingsFormatter {
+                    settings: &profile.relay_settings,
+                    custom_lists: &custom_lists,
+                },
+            );
+            print_option!(
+                "Obfuscation",
+                profile.obfuscation_settings.selected_obfuscation,
+            );
+            print_option!(
+                "DNS",
+                match profile.tunnel_options.dns_options.state {
+                    DnsState::Default => "default",
+                    DnsState::Custom => "custom",
+                },
+            );
+            print_option!(
+                "Local network sharing",
+                if profile.allow_lan { "allow" } else { "block" },
+            );
+        }
+        Ok(())
+    }
+
+    async fn apply(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.apply_profile(name.clone()).await?;
+        println!("Applied profile \"{name}\"");
+        Ok(())
+    }
+
+    async fn delete(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.delete_profile(name.clone()).await?;
+        println!("Deleted profile \"{name}\"");
+        Ok(())
+    }
+}
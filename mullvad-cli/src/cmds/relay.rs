@@ -1,28 +1,36 @@
 use anyhow::{anyhow, bail, Context, Result};
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
-    location::{CountryCode, Location},
+    connection_plan::AttemptOutcome,
+    location::{CityCode, CountryCode, Location},
+    openvpn_profile,
     relay_constraints::{
         Constraint, GeographicLocationConstraint, LocationConstraint, LocationConstraintFormatter,
         Match, OpenVpnConstraints, Ownership, Provider, Providers, RelayConstraints, RelayOverride,
         RelaySettings, TransportPort, WireguardConstraints,
     },
     relay_list::{RelayEndpointData, RelayListCountry},
+    relay_query::RelayQuery,
+    user_relay::UserRelay,
     ConnectionConfig, CustomTunnelEndpoint,
 };
 use std::{
     collections::HashMap,
     io::BufRead,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
 };
 use talpid_types::net::{
     all_of_the_internet, openvpn, wireguard, Endpoint, IpVersion, TransportProtocol, TunnelType,
 };
 
-use super::{relay_constraints::LocationArgs, BooleanOption};
-use crate::{cmds::receive_confirmation, print_option};
+use super::{
+    relay_constraints::{LocationArgs, RelayLocation, RelayLocationArgs},
+    BooleanOption,
+};
+use crate::{cmds::receive_confirmation, format, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Relay {
@@ -34,7 +42,12 @@ pub enum Relay {
     Set(SetCommands),
 
     /// List available relays
-    List,
+    List {
+        /// Instead list the relays referenced by the settings that were added, removed or
+        /// deactivated in the last relay list update
+        #[arg(long)]
+        changes: bool,
+    },
 
     /// Update the relay list
     Update,
@@ -42,15 +55,72 @@ pub enum Relay {
     /// Override options for individual relays/servers
     #[clap(subcommand)]
     Override(OverrideCommands),
+
+    /// Manage user-defined relays. These are WireGuard servers run by you, which are added to the
+    /// relay list. They are only selected when given by hostname, and never as a multihop exit
+    /// relay
+    #[clap(subcommand)]
+    User(UserRelayCommands),
+
+    /// Display the connection plan, which controls how the tunnel protocol, port, bridge and
+    /// obfuscation vary across connection attempts. The plan can be replaced using
+    /// 'mullvad import-settings'.
+    Plan {
+        /// Instead print what the relay selector would select on each of the first N connection
+        /// attempts, given the current settings and relay list
+        #[arg(long, value_name = "N")]
+        simulate: Option<u32>,
+    },
+
+    /// List the relays that may be selected for some constraints, and the probability of each
+    /// relay being selected. Constraints that are not given are taken from the current settings.
+    /// No settings are changed.
+    Query(QueryArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct QueryArgs {
+    /// Exit location, given as a country, city and hostname like in 'relay set location', or
+    /// 'any'
+    #[arg(long, num_args = 1..=3, value_names = ["COUNTRY", "CITY", "HOSTNAME"])]
+    location: Option<Vec<String>>,
+
+    /// Custom list to select exit relays from
+    #[arg(long, conflicts_with = "location")]
+    custom_list: Option<String>,
+
+    /// Tunnel protocol: 'any', 'wireguard', or 'openvpn'
+    #[arg(long)]
+    tunnel_protocol: Option<Constraint<TunnelType>>,
+
+    /// Hosting provider(s), or 'any'
+    #[arg(long, num_args = 1..)]
+    provider: Option<Vec<Provider>>,
+
+    /// Ownership: 'any', 'owned', or 'rented'
+    #[arg(long)]
+    ownership: Option<Constraint<Ownership>>,
+
+    /// Whether to use WireGuard multihop
+    #[arg(long)]
+    multihop: Option<BooleanOption>,
+
+    /// Multihop entry location, given like '--location'
+    #[arg(long, num_args = 1..=3, value_names = ["COUNTRY", "CITY", "HOSTNAME"])]
+    entry_location: Option<Vec<String>>,
+
+    /// Bridge location, given like '--location'
+    #[arg(long, num_args = 1..=3, value_names = ["COUNTRY", "CITY", "HOSTNAME"])]
+    bridge_location: Option<Vec<String>>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum SetCommands {
-    /// Select a relay using country, city or hostname.
+    /// Select a relay using country, city or hostname, or by distance.
     /// The 'mullvad relay list' command shows the available relays and their
     /// geographical location.
     #[command(
-        override_usage = "mullvad relay set location <COUNTRY> [CITY] [HOSTNAME] | <HOSTNAME>
+        override_usage = "mullvad relay set location <COUNTRY> [CITY] [HOSTNAME] | <HOSTNAME> | --radius <DISTANCE> [--near <LATITUDE,LONGITUDE>]
 
   Select relay using a country:
 
@@ -66,9 +136,17 @@ pub enum SetCommands {
 
   Select relay using only its hostname:
 
-\tmullvad relay set location se-got-wg-004"
+\tmullvad relay set location se-got-wg-004
+
+  Select relay within 800 km of some coordinates:
+
+\tmullvad relay set location --near 59.33,18.07 --radius 800km
+
+  Select relay within 800 km of the current location:
+
+\tmullvad relay set location --radius 800km"
     )]
-    Location(LocationArgs),
+    Location(RelayLocationArgs),
 
     /// Set custom list to select relays from. Use the 'custom-lists list'
     /// command to show available alternatives.
@@ -150,7 +228,7 @@ pub enum EntryArgs {
     /// Location of entry relay. This can be 'any' or any location that is valid with 'set
     /// location', such as 'se got'.
     #[command(
-        override_usage = "mullvad relay set tunnel wireguard entry-location <COUNTRY> [CITY] [HOSTNAME] | <HOSTNAME>
+        override_usage = "mullvad relay set tunnel wireguard entry-location <COUNTRY> [CITY] [HOSTNAME] | <HOSTNAME> | --radius <DISTANCE> [--near <LATITUDE,LONGITUDE>]
 
   Select entry location using a country:
 
@@ -166,11 +244,32 @@ pub enum EntryArgs {
 
   Select entry location using only its hostname:
 
-\tmullvad relay set tunnel wireguard entry-location se-got-wg-004"
+\tmullvad relay set tunnel wireguard entry-location se-got-wg-004
+
+  Select entry location within 800 km of some coordinates:
+
+\tmullvad relay set tunnel wireguard entry-location --near 59.33,18.07 --radius 800km"
     )]
-    Location(LocationArgs),
+    Location(RelayLocationArgs),
     /// Name of custom list to use to pick entry endpoint.
     CustomList { custom_list_name: String },
+    /// Ways in which the entry and exit relays must differ, in addition to being different
+    /// relays
+    #[clap(arg_required_else_help = true)]
+    Diversity {
+        /// Require the entry and exit relays to have different hosting providers
+        #[arg(long)]
+        different_provider: Option<BooleanOption>,
+        /// Require the entry and exit relays to be in different countries
+        #[arg(long)]
+        different_country: Option<BooleanOption>,
+        /// Require one of the entry and exit relays to be Mullvad-owned and the other rented
+        #[arg(long)]
+        different_ownership: Option<BooleanOption>,
+        /// Minimum distance between the entry and exit relays, such as '1000km', or 'any'
+        #[arg(long, value_parser = parse_min_distance)]
+        min_distance: Option<Constraint<u32>>,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -191,6 +290,17 @@ pub enum SetCustomCommands {
         transport_protocol: TransportProtocol,
     },
 
+    /// Use a custom OpenVPN relay described by an OpenVPN profile (.ovpn file).
+    /// If the profile does not contain any credentials, they are read from standard input
+    #[clap(arg_required_else_help = true)]
+    OpenvpnProfile {
+        /// Path to the profile. Files referenced by the profile are resolved relative to it
+        file: PathBuf,
+        /// Only show how the profile would be imported, without changing any settings
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Use a custom WireGuard relay
     #[clap(arg_required_else_help = true)]
     Wireguard {
@@ -249,6 +359,55 @@ pub enum OverrideSetCommands {
     },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum UserRelayCommands {
+    /// List user-defined relays
+    List,
+    /// Add a user-defined relay, replacing any user-defined relay with the same hostname
+    Add(UserRelayArgs),
+    /// Remove a user-defined relay
+    Remove {
+        /// Hostname of the relay to remove
+        hostname: String,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UserRelayArgs {
+    /// Unique name of the relay. Must not be the hostname of a Mullvad relay
+    hostname: String,
+    /// Country code of the location, e.g. 'se'
+    #[arg(long)]
+    country: CountryCode,
+    /// City code of the location, e.g. 'got'
+    #[arg(long)]
+    city: CityCode,
+    /// Name of the country. Defaults to the name in the relay list, if the country exists
+    #[arg(long)]
+    country_name: Option<String>,
+    /// Name of the city. Defaults to the name in the relay list, if the city exists
+    #[arg(long)]
+    city_name: Option<String>,
+    /// Latitude of the city. Required unless the city exists in the relay list
+    #[arg(long, requires = "longitude", allow_negative_numbers = true)]
+    latitude: Option<f64>,
+    /// Longitude of the city. Required unless the city exists in the relay list
+    #[arg(long, requires = "latitude", allow_negative_numbers = true)]
+    longitude: Option<f64>,
+    /// IPv4 address of the server
+    #[arg(long)]
+    ipv4: Ipv4Addr,
+    /// IPv6 address of the server
+    #[arg(long)]
+    ipv6: Option<Ipv6Addr>,
+    /// Port that the server listens on
+    #[arg(long, default_value_t = 51820)]
+    port: u16,
+    /// Base64 encoded public key of the server
+    #[arg(long, value_parser = wireguard::PublicKey::from_base64)]
+    public_key: wireguard::PublicKey,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum OverrideUnsetCommands {
     /// Remove overridden entry IPv4 address for the given server
@@ -261,10 +420,17 @@ impl Relay {
     pub async fn handle(self) -> Result<()> {
         match self {
             Relay::Get => Self::get().await,
-            Relay::List => Self::list().await,
+            Relay::List { changes: false } => Self::list().await,
+            Relay::List { changes: true } => Self::list_changes().await,
             Relay::Update => Self::update().await,
             Relay::Set(subcmd) => Self::set(subcmd).await,
             Relay::Override(subcmd) => Self::r#override(subcmd).await,
+            Relay::User(subcmd) => Self::user(subcmd).await,
+            Relay::Plan { simulate: None } => Self::plan().await,
+            Relay::Plan {
+                simulate: Some(attempts),
+            } => Self::simulate_plan(attempts).await,
+            Relay::Query(args) => Self::query(args).await,
         }
     }
 
@@ -273,6 +439,10 @@ impl Relay {
         let settings = rpc.get_settings().await?;
         let relay_settings = settings.relay_settings;
 
+        if format::json_output() {
+            return format::print_json(&relay_settings);
+        }
+
         match relay_settings {
             RelaySettings::CustomTunnelEndpoint(endpoint) => {
                 println!("Custom endpoint: {endpoint}")
@@ -335,6 +505,10 @@ impl Relay {
                             custom_lists: &settings.custom_lists
                         }),
                 );
+                print_option!(
+                    "Multihop diversity",
+                    constraints.wireguard_constraints.diversity,
+                );
             }
         }
 
@@ -344,14 +518,23 @@ impl Relay {
     async fn list() -> Result<()> {
         let mut countries = get_active_relays().await?;
         countries.sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-        for mut country in countries {
+        for country in &mut countries {
             country
                 .cities
                 .sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-            println!("{} ({})", country.name, country.code);
-            for mut city in country.cities {
+            for city in &mut country.cities {
                 city.relays
                     .sort_by(|r1, r2| natord::compare_ignore_case(&r1.hostname, &r2.hostname));
+            }
+        }
+
+        if format::json_output() {
+            return format::print_json(&countries);
+        }
+
+        for country in countries {
+            println!("{} ({})", country.name, country.code);
+            for city in country.cities {
                 println!(
                     "\t{} ({}) @ {:.5}°N, {:.5}°W",
                     city.name, city.code, city.latitude, city.longitude
@@ -362,7 +545,9 @@ impl Relay {
                         RelayEndpointData::Wireguard(_) => "WireGuard",
                         _ => unreachable!("Bug in relay filtering earlier on"),
                     };
-                    let ownership = if relay.owned {
+                    let ownership = if relay.is_user_defined() {
+                        "user-defined"
+                    } else if relay.owned {
                         "Mullvad-owned"
                     } else {
                         "rented"
@@ -385,6 +570,180 @@ impl Relay {
         Ok(())
     }
 
+    async fn list_changes() -> Result<()> {
+        let changes = MullvadProxyClient::new()
+            .await?
+            .get_relay_list_changes()
+            .await?;
+        if format::json_output() {
+            return format::print_json(&changes);
+        }
+        if changes.is_empty() {
+            println!("No relays referenced by the settings changed in the last relay list update");
+            return Ok(());
+        }
+        for (title, relays) in [
+            ("Removed", &changes.removed),
+            ("Deactivated", &changes.deactivated),
+            ("Added", &changes.added),
+        ] {
+            if relays.is_empty() {
+                continue;
+            }
+            println!("{title}");
+            for relay in relays {
+                println!("\t{relay}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn plan() -> Result<()> {
+        let simulation = MullvadProxyClient::new()
+            .await?
+            .simulate_connection_plan(0)
+            .await?;
+        if format::json_output() {
+            return format::print_json(&simulation);
+        }
+        let plan = simulation.plan;
+        if simulation.is_default {
+            println!("Connection plan (default)");
+        } else {
+            println!("Connection plan (custom)");
+        }
+        for (attempt, template) in plan.attempts().iter().enumerate() {
+            println!("\t{attempt:>2}: {template}");
+        }
+        println!(
+            "Later attempts repeat the plan from attempt {}",
+            plan.repeat_from()
+        );
+        Ok(())
+    }
+
+    async fn simulate_plan(attempts: u32) -> Result<()> {
+        let simulation = MullvadProxyClient::new()
+            .await?
+            .simulate_connection_plan(attempts)
+            .await?;
+        if format::json_output() {
+            return format::print_json(&simulation);
+        }
+        for attempt in simulation.attempts {
+            println!("Attempt {}: {}", attempt.attempt, attempt.template);
+            match attempt.outcome {
+                AttemptOutcome::Selected(selection) => println!("\tSelected: {selection}"),
+                AttemptOutcome::Failed(error) => println!("\tNo selection: {error}"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn query(args: QueryArgs) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let settings = rpc.get_settings().await?;
+
+        let mut constraints = match settings.relay_settings {
+            RelaySettings::Normal(constraints) => constraints,
+            RelaySettings::CustomTunnelEndpoint(_custom) => RelayConstraints::default(),
+        };
+        let mut bridge_constraints = settings.bridge_settings.normal;
+
+        if let Some(location) = args.location {
+            constraints.location = resolve_query_location(&mut rpc, location, |relay| {
+                relay.endpoint_data != RelayEndpointData::Bridge
+            })
+            .await?;
+        }
+        if let Some(name) = args.custom_list {
+            let list_id = super::custom_list::find_list_by_name(&mut rpc, &name)
+                .await?
+                .id;
+            constraints.location = Constraint::Only(LocationConstraint::CustomList { list_id });
+        }
+        if let Some(tunnel_protocol) = args.tunnel_protocol {
+            constraints.tunnel_protocol = tunnel_protocol;
+        }
+        if let Some(providers) = args.provider {
+            let providers = if providers[0].eq_ignore_ascii_case("any") {
+                Constraint::Any
+            } else {
+                Constraint::Only(Providers::new(providers.into_iter()).unwrap())
+            };
+            constraints.providers = providers.clone();
+            bridge_constraints.providers = providers;
+        }
+        if let Some(ownership) = args.ownership {
+            constraints.ownership = ownership;
+            bridge_constraints.ownership = ownership;
+        }
+        if let Some(multihop) = args.multihop {
+            constraints.wireguard_constraints.use_multihop = *multihop;
+        }
+        if let Some(location) = args.entry_location {
+            constraints.wireguard_constraints.entry_location =
+                resolve_query_location(&mut rpc, location, |relay| {
+                    matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
+                })
+                .await?;
+        }
+        if let Some(location) = args.bridge_location {
+            bridge_constraints.location = resolve_query_location(&mut rpc, location, |relay| {
+                relay.endpoint_data == RelayEndpointData::Bridge
+            })
+            .await?;
+        }
+
+        let mut result = rpc
+            .query_relays(RelayQuery {
+                constraints,
+                bridge_constraints,
+            })
+            .await?;
+        for candidates in [&mut result.exit, &mut result.entry, &mut result.bridge] {
+            candidates.sort_by(|a, b| {
+                b.probability
+                    .total_cmp(&a.probability)
+                    .then_with(|| natord::compare_ignore_case(&a.relay.hostname, &b.relay.hostname))
+            });
+        }
+
+        if format::json_output() {
+            return format::print_json(&result);
+        }
+
+        for (title, candidates) in [
+            ("Exit relays", result.exit),
+            ("Entry relays", result.entry),
+            ("Bridges", result.bridge),
+        ] {
+            if candidates.is_empty() {
+                continue;
+            }
+            println!("{title} ({})", candidates.len());
+            for candidate in candidates {
+                let relay = candidate.relay;
+                let location = relay
+                    .location
+                    .map(|location| format!("{}, {}", location.city, location.country))
+                    .unwrap_or_default();
+                let ownership = if relay.owned {
+                    "Mullvad-owned"
+                } else {
+                    "rented"
+                };
+                println!(
+                    "\t{:>6.2}% {} ({location}), hosted by {} ({ownership})",
+                    candidate.probability * 100.0,
+                    relay.hostname,
+                    relay.provider,
+                );
+            }
+        }
+        Ok(())
+    }
+
     async fn update() -> Result<()> {
         MullvadProxyClient::new()
             .await?
@@ -449,6 +808,9 @@ impl Relay {
 
     async fn set_custom(subcmd: SetCustomCommands) -> Result<()> {
         let custom_endpoint = match subcmd {
+            SetCustomCommands::OpenvpnProfile { file, dry_run } => {
+                return Self::import_openvpn_profile(file, dry_run).await;
+            }
             SetCustomCommands::Openvpn {
                 host,
                 port,
@@ -493,15 +855,60 @@ impl Relay {
     ) -> CustomTunnelEndpoint {
         CustomTunnelEndpoint {
             host,
-            config: ConnectionConfig::OpenVpn(openvpn::ConnectionConfig {
-                endpoint: Endpoint::from_socket_address(
+            config: ConnectionConfig::OpenVpn(openvpn::ConnectionConfig::new(
+                Endpoint::from_socket_address(
                     SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
                     protocol,
                 ),
                 username,
                 password,
-            }),
+            )),
+        }
+    }
+
+    async fn import_openvpn_profile(file: PathBuf, dry_run: bool) -> Result<()> {
+        let contents = tokio::task::spawn_blocking(move || -> Result<String> {
+            let profile = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read profile: {}", file.display()))?;
+            let base_dir = file.parent().unwrap_or_else(|| Path::new("."));
+            let mut profile = openvpn_profile::inline_file_references(&profile, base_dir)
+                .context("Failed to read file referenced by the profile")?;
+
+            if !profile.contains("<auth-user-pass>") {
+                println!("Reading username and password from standard input");
+                let mut lines = std::io::stdin().lock().lines();
+                let username = lines.next().transpose()?.unwrap_or_default();
+                let password = lines.next().transpose()?.unwrap_or_default();
+                profile.push_str(&format!(
+                    "<auth-user-pass>\n{}\n{}\n</auth-user-pass>\n",
+                    username.trim(),
+                    password.trim()
+                ));
+            }
+            Ok(profile)
+        })
+        .await
+        .unwrap()?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let import = rpc.import_openvpn_profile(contents, dry_run).await?;
+
+        println!("Custom endpoint: {}", import.endpoint);
+        if let Some(mssfix) = import.options.mssfix {
+            println!("OpenVPN mssfix: {mssfix}");
+        }
+        if !import.unsupported.is_empty() {
+            println!("The following directives are not supported and were ignored:");
+            for directive in &import.unsupported {
+                println!("\t{directive}");
+            }
         }
+        if dry_run {
+            println!("No settings were changed");
+        } else {
+            println!("Relay constraints updated");
+        }
+        Ok(())
     }
 
     async fn read_custom_wireguard_relay(
@@ -552,7 +959,17 @@ impl Relay {
         })
     }
 
-    async fn set_location(location_constraint_args: LocationArgs) -> Result<()> {
+    async fn set_location(location_args: RelayLocationArgs) -> Result<()> {
+        let location_constraint_args = match RelayLocation::from(location_args) {
+            RelayLocation::Location(location_constraint_args) => location_constraint_args,
+            RelayLocation::Radius(radius) => {
+                return Self::update_constraints(|constraints| {
+                    constraints.location = Constraint::Only(LocationConstraint::Radius(radius));
+                })
+                .await;
+            }
+        };
+
         let mut rpc = MullvadProxyClient::new().await?;
         let relay_settings = rpc.get_settings().await?.get_relay_settings();
         let constraints = match relay_settings {
@@ -682,16 +1099,23 @@ impl Relay {
             wireguard_constraints.use_multihop = *use_multihop;
         }
         match entry_location {
-            Some(EntryArgs::Location(location_args)) => {
-                let relay_filter = |relay: &mullvad_types::relay_list::Relay| {
-                    relay.active && matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
-                };
-                let location_constraint =
-                    resolve_location_constraint(&mut rpc, location_args, relay_filter).await?;
+            Some(EntryArgs::Location(location_args)) => match RelayLocation::from(location_args) {
+                RelayLocation::Location(location_args) => {
+                    let relay_filter = |relay: &mullvad_types::relay_list::Relay| {
+                        relay.active
+                            && matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
+                    };
+                    let location_constraint =
+                        resolve_location_constraint(&mut rpc, location_args, relay_filter).await?;
 
-                wireguard_constraints.entry_location =
-                    location_constraint.map(LocationConstraint::from);
-            }
+                    wireguard_constraints.entry_location =
+                        location_constraint.map(LocationConstraint::from);
+                }
+                RelayLocation::Radius(radius) => {
+                    wireguard_constraints.entry_location =
+                        Constraint::Only(LocationConstraint::Radius(radius));
+                }
+            },
             Some(EntryArgs::CustomList { custom_list_name }) => {
                 let list_id = super::custom_list::find_list_by_name(&mut rpc, &custom_list_name)
                     .await?
@@ -699,6 +1123,26 @@ impl Relay {
                 wireguard_constraints.entry_location =
                     Constraint::Only(LocationConstraint::CustomList { list_id });
             }
+            Some(EntryArgs::Diversity {
+                different_provider,
+                different_country,
+                different_ownership,
+                min_distance,
+            }) => {
+                let diversity = &mut wireguard_constraints.diversity;
+                if let Some(different_provider) = different_provider {
+                    diversity.different_provider = *different_provider;
+                }
+                if let Some(different_country) = different_country {
+                    diversity.different_country = *different_country;
+                }
+                if let Some(different_ownership) = different_ownership {
+                    diversity.different_ownership = *different_ownership;
+                }
+                if let Some(min_distance) = min_distance {
+                    diversity.min_distance_km = min_distance.option();
+                }
+            }
             None => (),
         }
 
@@ -765,6 +1209,10 @@ impl Relay {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?;
 
+                if format::json_output() {
+                    return format::print_json(&settings.relay_overrides);
+                }
+
                 let mut overrides = HashMap::new();
                 for relay_override in settings.relay_overrides {
                     overrides.insert(relay_override.hostname.clone(), relay_override);
@@ -888,6 +1336,82 @@ impl Relay {
         }
         Ok(())
     }
+
+    async fn user(subcmd: UserRelayCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match subcmd {
+            UserRelayCommands::List => {
+                let user_relays = rpc.get_settings().await?.user_relays;
+                if format::json_output() {
+                    return format::print_json(&user_relays);
+                }
+                for relay in user_relays {
+                    let mut addresses: Vec<IpAddr> = vec![relay.ipv4_addr_in.into()];
+                    if let Some(ipv6_addr) = relay.ipv6_addr_in {
+                        addresses.push(ipv6_addr.into());
+                    }
+                    println!(
+                        "{} ({}), port {}, in {}, {} ({}, {})",
+                        relay.hostname,
+                        addresses.iter().join(", "),
+                        relay.port,
+                        relay.location.city,
+                        relay.location.country,
+                        relay.location.city_code,
+                        relay.location.country_code,
+                    );
+                    println!("{:<4}Public key: {}", " ", relay.public_key);
+                }
+            }
+            UserRelayCommands::Add(args) => {
+                let relay_list = rpc.get_relay_locations().await?;
+                let country = relay_list
+                    .countries
+                    .iter()
+                    .find(|country| country.code == args.country);
+                let city = country
+                    .and_then(|country| country.cities.iter().find(|city| city.code == args.city));
+                let (latitude, longitude) = match (args.latitude, args.longitude, city) {
+                    (Some(latitude), Some(longitude), _) => (latitude, longitude),
+                    (_, _, Some(city)) => (city.latitude, city.longitude),
+                    _ => bail!(
+                        "--latitude and --longitude must be given for cities that are not in the \
+                         relay list"
+                    ),
+                };
+                let location = Location {
+                    country: args
+                        .country_name
+                        .or_else(|| country.map(|country| country.name.clone()))
+                        .unwrap_or_else(|| args.country.clone()),
+                    city: args
+                        .city_name
+                        .or_else(|| city.map(|city| city.name.clone()))
+                        .unwrap_or_else(|| args.city.clone()),
+                    country_code: args.country,
+                    city_code: args.city,
+                    latitude,
+                    longitude,
+                };
+                let hostname = args.hostname.clone();
+                rpc.add_user_relay(UserRelay {
+                    hostname: args.hostname,
+                    location,
+                    ipv4_addr_in: args.ipv4,
+                    ipv6_addr_in: args.ipv6,
+                    port: args.port,
+                    public_key: args.public_key,
+                })
+                .await?;
+                println!("Added user-defined relay {hostname}");
+            }
+            UserRelayCommands::Remove { hostname } => {
+                rpc.remove_user_relay(hostname.clone()).await?;
+                println!("Removed user-defined relay {hostname}");
+            }
+        }
+        Ok(())
+    }
 }
 
 fn parse_transport_port(
@@ -977,6 +1501,34 @@ pub async fn resolve_location_constraint(
     }
 }
 
+fn parse_min_distance(value: &str) -> Result<Constraint<u32>, String> {
+    if value.eq_ignore_ascii_case("any") {
+        return Ok(Constraint::Any);
+    }
+    let km = value.trim().trim_end_matches("km").trim_end();
+    km.parse::<u32>()
+        .map(Constraint::Only)
+        .map_err(|error| format!("invalid distance '{value}': {error}"))
+}
+
+/// Resolves a location given as up to three values, a country, city and hostname, or only a
+/// hostname, to a location constraint.
+async fn resolve_query_location(
+    rpc: &mut MullvadProxyClient,
+    location: Vec<String>,
+    relay_filter: impl FnOnce(&mullvad_types::relay_list::Relay) -> bool,
+) -> Result<Constraint<LocationConstraint>> {
+    let mut location = location.into_iter();
+    let args = LocationArgs {
+        country: location.next().context("Missing location")?,
+        city: location.next(),
+        hostname: location.next(),
+    };
+    Ok(resolve_location_constraint(rpc, args, relay_filter)
+        .await?
+        .map(LocationConstraint::from))
+}
+
 /// Return a list of all relays that are active and not bridges
 pub async fn get_active_relays() -> Result<Vec<RelayListCountry>> {
     let mut rpc = MullvadProxyClient::new().await?;
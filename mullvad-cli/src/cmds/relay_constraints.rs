@@ -1,7 +1,10 @@
 use clap::Args;
 use mullvad_types::{
-    location::{CityCode, CountryCode, Hostname},
-    relay_constraints::{Constraint, GeographicLocationConstraint, LocationConstraint},
+    location::{CityCode, Coordinates, CountryCode, Hostname},
+    relay_constraints::{
+        Constraint, GeographicLocationConstraint, GeographicRadius, LocationConstraint,
+        RadiusCenter,
+    },
 };
 
 #[derive(Args, Debug, Clone)]
@@ -49,3 +52,78 @@ impl From<LocationArgs> for Constraint<LocationConstraint> {
         Constraint::Only(LocationConstraint::Location(location))
     }
 }
+
+/// Relay location arguments that may also select relays by their distance from a point.
+#[derive(Args, Debug, Clone)]
+pub struct RelayLocationArgs {
+    /// A two-letter country code, or 'any'.
+    #[arg(required_unless_present = "radius")]
+    pub country: Option<CountryCode>,
+    /// A three-letter city code.
+    pub city: Option<CityCode>,
+    /// A host name, such as "se-got-wg-101".
+    pub hostname: Option<Hostname>,
+    /// Select relays near these coordinates, given as 'LATITUDE,LONGITUDE'. If omitted, relays
+    /// near the current location are selected.
+    #[arg(long, requires = "radius", value_parser = parse_coordinates)]
+    pub near: Option<Coordinates>,
+    /// Select relays within this distance, such as '800km'.
+    #[arg(long, conflicts_with = "country", value_parser = parse_distance)]
+    pub radius: Option<f64>,
+}
+
+pub enum RelayLocation {
+    Location(LocationArgs),
+    Radius(GeographicRadius),
+}
+
+impl From<RelayLocationArgs> for RelayLocation {
+    fn from(value: RelayLocationArgs) -> Self {
+        match (value.country, value.radius) {
+            (_, Some(radius_km)) => RelayLocation::Radius(GeographicRadius {
+                center: value
+                    .near
+                    .map(RadiusCenter::Coordinates)
+                    .unwrap_or(RadiusCenter::CurrentLocation),
+                radius_km,
+            }),
+            (Some(country), None) => RelayLocation::Location(LocationArgs {
+                country,
+                city: value.city,
+                hostname: value.hostname,
+            }),
+            (None, None) => unreachable!("invalid location arguments"),
+        }
+    }
+}
+
+fn parse_coordinates(value: &str) -> Result<Coordinates, String> {
+    let (latitude, longitude) = value
+        .split_once(',')
+        .ok_or("expected coordinates as 'LATITUDE,LONGITUDE'")?;
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<f64>()
+            .map_err(|error| format!("invalid coordinate '{value}': {error}"))
+    };
+    let coordinates = Coordinates {
+        latitude: parse(latitude)?,
+        longitude: parse(longitude)?,
+    };
+    if !coordinates.is_valid() {
+        return Err("latitude must be within ±90 and longitude within ±180 degrees".to_owned());
+    }
+    Ok(coordinates)
+}
+
+fn parse_distance(value: &str) -> Result<f64, String> {
+    let km = value.trim().trim_end_matches("km").trim_end();
+    let distance = km
+        .parse::<f64>()
+        .map_err(|error| format!("invalid distance '{value}': {error}"))?;
+    if !distance.is_finite() || distance <= 0.0 {
+        return Err("the distance must be positive".to_owned());
+    }
+    Ok(distance)
+}
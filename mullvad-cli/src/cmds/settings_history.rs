@@ -0,0 +1,76 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+
+use crate::format;
+
+#[derive(Subcommand, Debug)]
+pub enum SettingsHistory {
+    /// Show all saved snapshots of previous settings, oldest first
+    List,
+
+    /// Show what has changed in the settings since a snapshot was taken
+    Diff {
+        /// ID of the snapshot
+        id: u32,
+    },
+
+    /// Replace the current settings with those in a snapshot. The current settings are saved
+    /// as a new snapshot first
+    Restore {
+        /// ID of the snapshot
+        id: u32,
+    },
+}
+
+impl SettingsHistory {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            SettingsHistory::List => Self::list().await,
+            SettingsHistory::Diff { id } => Self::diff(id).await,
+            SettingsHistory::Restore { id } => Self::restore(id).await,
+        }
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let snapshots = rpc.get_settings_history().await?;
+        if format::json_output() {
+            return format::print_json(&snapshots);
+        }
+        if snapshots.is_empty() {
+            println!("No settings snapshots");
+        }
+        for snapshot in snapshots {
+            println!(
+                "{:>4}  {}  {}",
+                snapshot.id,
+                snapshot.created.with_timezone(&chrono::Local),
+                snapshot.cause
+            );
+        }
+        Ok(())
+    }
+
+    async fn diff(id: u32) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let changes = rpc.diff_settings_snapshot(id).await?;
+        if format::json_output() {
+            return format::print_json(&changes);
+        }
+        if changes.is_empty() {
+            println!("No changes since snapshot {id}");
+        }
+        for change in changes {
+            println!("{change}");
+        }
+        Ok(())
+    }
+
+    async fn restore(id: u32) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.restore_settings_snapshot(id).await?;
+        println!("Restored settings from snapshot {id}");
+        Ok(())
+    }
+}
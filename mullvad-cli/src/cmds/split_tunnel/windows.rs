@@ -8,6 +8,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::super::BooleanOption;
+use crate::format;
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -43,6 +44,18 @@ impl SplitTunnel {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?.split_tunnel;
 
+                if format::json_output() {
+                    let excluded_processes = if list_processes {
+                        Some(rpc.get_excluded_processes().await?)
+                    } else {
+                        None
+                    };
+                    return format::print_json(&serde_json::json!({
+                        "split_tunnel": settings,
+                        "excluded_processes": excluded_processes,
+                    }));
+                }
+
                 let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
 
                 println!("Split tunneling state: {enable_exclusions}");
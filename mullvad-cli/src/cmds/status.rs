@@ -21,6 +21,11 @@ pub struct StatusArgs {
     /// Enable debug output
     #[arg(long, short = 'd')]
     debug: bool,
+
+    /// Keep running and print changes, like 'listen'. With '--json', every daemon event is printed
+    /// as a line of JSON
+    #[arg(long, short = 'f')]
+    follow: bool,
 }
 
 impl Status {
@@ -28,6 +33,10 @@ impl Status {
         let mut previous_tunnel_state = None;
 
         while let Some(event) = rpc.events_listen().await?.next().await {
+            if format::json_output() {
+                print_event_json(event?)?;
+                continue;
+            }
             match event? {
                 DaemonEvent::TunnelState(new_state) => {
                     if args.debug {
@@ -45,13 +54,23 @@ impl Status {
                                 Some(TunnelState::Disconnected {
                                     location: _,
                                     locked_down: was_locked_down,
+                                    captive_portal: previous_captive_portal,
+                                    paused_until: previous_paused_until,
                                 }),
                                 TunnelState::Disconnected {
                                     location: _,
                                     locked_down,
+                                    captive_portal,
+                                    paused_until,
                                 },
-                                // Do print an updated state if the lockdown setting was changed
-                            ) if was_locked_down == locked_down => continue,
+                                // Do print an updated state if the lockdown setting, captive
+                                // portal login or pause was changed
+                            ) if was_locked_down == locked_down
+                                && previous_captive_portal == captive_portal
+                                && previous_paused_until == paused_until =>
+                            {
+                                continue
+                            }
                             (
                                 Some(TunnelState::Connected { .. }),
                                 TunnelState::Connected { .. },
@@ -92,6 +111,20 @@ impl Status {
                         println!("New access method: {access_method:#?}");
                     }
                 }
+                DaemonEvent::RelayListChanges(changes) => {
+                    if args.debug {
+                        println!("Relay list changes: {changes:#?}");
+                    }
+                }
+                DaemonEvent::ExpiryWarning(warning) => {
+                    if args.debug {
+                        println!("Expiry warning: {warning:#?}");
+                    } else if let Some(warning) = warning {
+                        format::print_expiry_warning(&warning);
+                    } else {
+                        println!("Account expiry warning cleared");
+                    }
+                }
             }
         }
         Ok(())
@@ -101,9 +134,22 @@ impl Status {
 pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
     let mut rpc = MullvadProxyClient::new().await?;
     let state = rpc.get_tunnel_state().await?;
+    let follow = args.follow || cmd == Some(Status::Listen);
+
+    if format::json_output() {
+        if follow {
+            print_event_json(DaemonEvent::TunnelState(state))?;
+            return Status::listen(rpc, args).await;
+        }
+        return format::print_json(&state);
+    }
+
     let device = rpc.get_device().await?;
 
     print_account_logged_out(&state, &device);
+    if let Some(warning) = rpc.get_expiry_warning().await? {
+        format::print_expiry_warning(&warning);
+    }
 
     if args.debug {
         println!("Tunnel state: {state:#?}");
@@ -112,12 +158,32 @@ pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
         format::print_location(&state);
     }
 
-    if cmd == Some(Status::Listen) {
+    if follow {
         Status::listen(rpc, args).await?;
     }
     Ok(())
 }
 
+/// Prints a daemon event as an object containing the name of the event and its data
+fn print_event_json(event: DaemonEvent) -> Result<()> {
+    let (name, data) = match event {
+        DaemonEvent::TunnelState(state) => ("tunnel_state", serde_json::to_value(state)?),
+        DaemonEvent::Settings(settings) => ("settings", serde_json::to_value(settings)?),
+        DaemonEvent::RelayList(relay_list) => ("relay_list", serde_json::to_value(relay_list)?),
+        DaemonEvent::AppVersionInfo(info) => ("app_version_info", serde_json::to_value(info)?),
+        DaemonEvent::Device(device) => ("device", serde_json::to_value(device)?),
+        DaemonEvent::RemoveDevice(device) => ("remove_device", serde_json::to_value(device)?),
+        DaemonEvent::NewAccessMethod(access_method) => {
+            ("new_access_method", serde_json::to_value(access_method)?)
+        }
+        DaemonEvent::RelayListChanges(changes) => {
+            ("relay_list_changes", serde_json::to_value(changes)?)
+        }
+        DaemonEvent::ExpiryWarning(warning) => ("expiry_warning", serde_json::to_value(warning)?),
+    };
+    format::print_json(&serde_json::json!({ "event": name, "data": data }))
+}
+
 fn print_account_logged_out(state: &TunnelState, device: &DeviceState) {
     match state {
         TunnelState::Connecting { .. } | TunnelState::Connected { .. } | TunnelState::Error(_) => {
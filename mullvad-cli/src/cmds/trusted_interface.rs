@@ -0,0 +1,54 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use talpid_types::net::trusted_interface::TrustedInterface;
+
+use crate::format;
+
+#[derive(Subcommand, Debug)]
+pub enum TrustedInterfaces {
+    /// List trusted interfaces
+    List,
+    /// Trust an interface, allowing its traffic outside the tunnel and copying its routes to the
+    /// tunnel routing table. Physical interfaces and the interface of the default route cannot be
+    /// trusted
+    Add {
+        /// Name of the interface, e.g. 'tailscale0'. A name ending with '*' matches every
+        /// interface starting with the rest of the name, e.g. 'zt*'
+        interface: TrustedInterface,
+    },
+    /// Stop trusting an interface
+    Remove {
+        /// Name of the interface, as it was added
+        interface: TrustedInterface,
+    },
+}
+
+impl TrustedInterfaces {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match self {
+            TrustedInterfaces::List => {
+                let interfaces = rpc.get_settings().await?.trusted_interfaces;
+                if format::json_output() {
+                    return format::print_json(&interfaces);
+                }
+                if interfaces.is_empty() {
+                    println!("No trusted interfaces");
+                }
+                for interface in interfaces {
+                    println!("{interface}");
+                }
+            }
+            TrustedInterfaces::Add { interface } => {
+                rpc.add_trusted_interface(interface.clone()).await?;
+                println!("Trusting interface {interface}");
+            }
+            TrustedInterfaces::Remove { interface } => {
+                rpc.remove_trusted_interface(interface.clone()).await?;
+                println!("No longer trusting interface {interface}");
+            }
+        }
+        Ok(())
+    }
+}
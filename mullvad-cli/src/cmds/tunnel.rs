@@ -1,13 +1,15 @@
 use anyhow::Result;
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     relay_constraints::Constraint,
     wireguard::{QuantumResistantState, RotationInterval, DEFAULT_ROTATION_INTERVAL},
 };
+use std::time::Duration;
+use talpid_types::net::wireguard::LivenessProbe;
 
 use super::BooleanOption;
-use crate::print_option;
+use crate::{format, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Tunnel {
@@ -35,12 +37,17 @@ pub enum TunnelOptions {
         /// Configure the tunnel MTU, or 'any'
         #[arg(long, short = 'm')]
         mtu: Option<Constraint<u16>>,
+        /// Probe the path MTU when connecting and lower the tunnel MTU to match it (Linux only)
+        #[arg(long)]
+        mtu_discovery: Option<BooleanOption>,
         /// Configure quantum-resistant key exchange
         #[arg(long)]
         quantum_resistant: Option<QuantumResistantState>,
         /// The key rotation interval. Number of hours, or 'any'
         #[arg(long)]
         rotation_interval: Option<Constraint<RotationInterval>>,
+        #[command(flatten)]
+        connectivity_check: ConnectivityCheckArgs,
         /// Rotate WireGuard key
         #[clap(subcommand)]
         rotate_key: Option<RotateKey>,
@@ -51,6 +58,36 @@ pub enum TunnelOptions {
     Ipv6 { state: BooleanOption },
 }
 
+/// Options for detecting whether a WireGuard tunnel works
+#[derive(Args, Debug, Clone)]
+pub struct ConnectivityCheckArgs {
+    /// Kind of probe sent when there is too little traffic to tell whether the tunnel works
+    #[arg(long)]
+    liveness_probe: Option<LivenessProbe>,
+    /// Seconds to wait for a response to outgoing traffic before probing
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    rx_timeout: Option<u64>,
+    /// Seconds without any traffic before probing
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    traffic_timeout: Option<u64>,
+    /// Seconds to keep probing without a response before the tunnel is considered broken
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    probe_timeout: Option<u64>,
+    /// Seconds to wait for the first response when connecting
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    establish_timeout: Option<u64>,
+}
+
+impl ConnectivityCheckArgs {
+    fn is_empty(&self) -> bool {
+        self.liveness_probe.is_none()
+            && self.rx_timeout.is_none()
+            && self.traffic_timeout.is_none()
+            && self.probe_timeout.is_none()
+            && self.establish_timeout.is_none()
+    }
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum RotateKey {
     /// Replace the WireGuard key with a new one
@@ -69,6 +106,14 @@ impl Tunnel {
         let mut rpc = MullvadProxyClient::new().await?;
         let tunnel_options = rpc.get_settings().await?.tunnel_options;
 
+        if format::json_output() {
+            let wireguard_key = rpc.get_wireguard_key().await.ok();
+            return format::print_json(&serde_json::json!({
+                "tunnel_options": tunnel_options,
+                "wireguard_key": wireguard_key,
+            }));
+        }
+
         println!("OpenVPN options");
 
         print_option!(
@@ -90,11 +135,32 @@ impl Tunnel {
                 .map(|val| val.to_string())
                 .unwrap_or("unset".to_string()),
         );
+        print_option!(
+            "MTU discovery",
+            if tunnel_options.wireguard.mtu_discovery {
+                "on"
+            } else {
+                "off"
+            }
+        );
         print_option!(
             "Quantum resistance",
             tunnel_options.wireguard.quantum_resistant,
         );
 
+        let connectivity_check = tunnel_options.wireguard.connectivity_check;
+        print_option!("Liveness probe", connectivity_check.probe);
+        print_option!(
+            "Timeouts",
+            format_args!(
+                "rx {}s, traffic {}s, probe {}s, establish {}s",
+                connectivity_check.rx_timeout.as_secs(),
+                connectivity_check.traffic_timeout.as_secs(),
+                connectivity_check.probe_timeout.as_secs(),
+                connectivity_check.establish_timeout.as_secs(),
+            )
+        );
+
         let key = rpc.get_wireguard_key().await?;
         print_option!("Public key", key.key,);
         print_option!(format_args!(
@@ -128,11 +194,21 @@ impl Tunnel {
             TunnelOptions::Openvpn { mssfix } => Self::handle_openvpn(mssfix).await,
             TunnelOptions::Wireguard {
                 mtu,
+                mtu_discovery,
                 quantum_resistant,
                 rotation_interval,
+                connectivity_check,
                 rotate_key,
             } => {
-                Self::handle_wireguard(mtu, quantum_resistant, rotation_interval, rotate_key).await
+                Self::handle_wireguard(
+                    mtu,
+                    mtu_discovery,
+                    quantum_resistant,
+                    rotation_interval,
+                    connectivity_check,
+                    rotate_key,
+                )
+                .await
             }
             TunnelOptions::Ipv6 { state } => Self::handle_ipv6(state).await,
         }
@@ -158,8 +234,10 @@ impl Tunnel {
 
     async fn handle_wireguard(
         mtu: Option<Constraint<u16>>,
+        mtu_discovery: Option<BooleanOption>,
         quantum_resistant: Option<QuantumResistantState>,
         rotation_interval: Option<Constraint<RotationInterval>>,
+        connectivity_check: ConnectivityCheckArgs,
         rotate_key: Option<RotateKey>,
     ) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
@@ -169,6 +247,11 @@ impl Tunnel {
             println!("MTU parameter has been updated");
         }
 
+        if let Some(mtu_discovery) = mtu_discovery {
+            rpc.set_wireguard_mtu_discovery(*mtu_discovery).await?;
+            println!("MTU discovery: {mtu_discovery}");
+        }
+
         if let Some(quantum_resistant) = quantum_resistant {
             rpc.set_quantum_resistant_tunnel(quantum_resistant).await?;
             println!("Quantum resistant setting has been updated");
@@ -190,6 +273,32 @@ impl Tunnel {
             }
         }
 
+        if !connectivity_check.is_empty() {
+            let mut options = rpc
+                .get_settings()
+                .await?
+                .tunnel_options
+                .wireguard
+                .connectivity_check;
+            if let Some(probe) = connectivity_check.liveness_probe {
+                options.probe = probe;
+            }
+            if let Some(timeout) = connectivity_check.rx_timeout {
+                options.rx_timeout = Duration::from_secs(timeout);
+            }
+            if let Some(timeout) = connectivity_check.traffic_timeout {
+                options.traffic_timeout = Duration::from_secs(timeout);
+            }
+            if let Some(timeout) = connectivity_check.probe_timeout {
+                options.probe_timeout = Duration::from_secs(timeout);
+            }
+            if let Some(timeout) = connectivity_check.establish_timeout {
+                options.establish_timeout = Duration::from_secs(timeout);
+            }
+            rpc.set_wireguard_connectivity_check(options).await?;
+            println!("Connectivity check settings have been updated");
+        }
+
         if matches!(rotate_key, Some(RotateKey::RotateKey)) {
             rpc.rotate_wireguard_key().await?;
             println!("Rotated WireGuard key");
@@ -3,6 +3,7 @@ use anyhow::{anyhow, Result};
 use futures::{Stream, StreamExt};
 use mullvad_management_interface::{client::DaemonEvent, MullvadProxyClient};
 use mullvad_types::{device::DeviceState, states::TunnelState};
+use std::time::Duration;
 
 pub async fn connect(wait: bool) -> Result<()> {
     let mut rpc = MullvadProxyClient::new().await?;
@@ -30,7 +31,7 @@ pub async fn connect(wait: bool) -> Result<()> {
     Ok(())
 }
 
-pub async fn disconnect(wait: bool) -> Result<()> {
+pub async fn disconnect(wait: bool, duration: Option<Duration>) -> Result<()> {
     let mut rpc = MullvadProxyClient::new().await?;
 
     let listener = if wait {
@@ -39,7 +40,25 @@ pub async fn disconnect(wait: bool) -> Result<()> {
         None
     };
 
-    if rpc.disconnect_tunnel().await? {
+    let changed = match duration {
+        Some(duration) => {
+            rpc.pause_tunnel(duration).await?;
+            let message = format!(
+                "Paused. Reconnecting in {}",
+                format::format_remaining(duration)
+            );
+            // Standard output only contains tunnel states when printing JSON
+            if format::json_output() {
+                eprintln!("{message}");
+            } else {
+                println!("{message}");
+            }
+            true
+        }
+        None => rpc.disconnect_tunnel().await?,
+    };
+
+    if changed {
         if let Some(receiver) = listener {
             wait_for_tunnel_state(receiver, |state| Ok(state.is_disconnected())).await?;
         }
@@ -81,7 +100,11 @@ async fn wait_for_tunnel_state(
 ) -> Result<()> {
     while let Some(state) = event_stream.next().await {
         if let DaemonEvent::TunnelState(new_state) = state? {
-            format::print_state(&new_state, false);
+            if format::json_output() {
+                format::print_json(&new_state)?;
+            } else {
+                format::print_state(&new_state, false);
+            }
             if matches_event(&new_state)? {
                 return Ok(());
             }
@@ -1,7 +1,13 @@
 use anyhow::{Context, Result};
 use mullvad_management_interface::MullvadProxyClient;
 
+use crate::format;
+
 pub async fn print() -> Result<()> {
+    if format::json_output() {
+        return print_json().await;
+    }
+
     println!("{:22}: {}", "Current version", mullvad_version::VERSION);
 
     let mut rpc = MullvadProxyClient::new()
@@ -46,3 +52,22 @@ pub async fn print() -> Result<()> {
 
     Ok(())
 }
+
+async fn print_json() -> Result<()> {
+    let mut rpc = MullvadProxyClient::new()
+        .await
+        .context("Failed to connect to mullvad-daemon")?;
+    let daemon_version = rpc
+        .get_current_version()
+        .await
+        .context("Failed to get current mullvad-daemon version")?;
+    let version_info = rpc
+        .get_version_info()
+        .await
+        .context("Failed to get version info")?;
+    format::print_json(&serde_json::json!({
+        "cli_version": mullvad_version::VERSION,
+        "daemon_version": daemon_version,
+        "version_info": version_info,
+    }))
+}
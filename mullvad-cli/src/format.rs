@@ -1,9 +1,39 @@
-use mullvad_types::{auth_failed::AuthFailed, location::GeoIpLocation, states::TunnelState};
+use mullvad_types::{
+    account::ExpiryWarning, auth_failed::AuthFailed, location::GeoIpLocation, states::TunnelState,
+};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 use talpid_types::{
     net::{Endpoint, TunnelEndpoint},
-    tunnel::ErrorState,
+    tunnel::{CaptivePortalLogin, ErrorState},
 };
 
+/// Whether commands should print JSON instead of text. Set by the global `--json` flag.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if the output of commands should be JSON. See `docs/cli-json-output.md`.
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Prints `value` as a single line of JSON. Each call prints one document, so that streams of
+/// values can be read as newline-delimited JSON.
+pub fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer(&mut stdout, value)?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
 #[macro_export]
 macro_rules! print_option {
     ($value:expr $(,)?) => {{
@@ -28,24 +58,38 @@ pub fn print_state(state: &TunnelState, verbose: bool) {
                 if let Some(tunnel_interface) = &endpoint.tunnel_interface {
                     println!("Tunnel interface: {tunnel_interface}")
                 }
+                if let Some(mtu) = endpoint.discovered_mtu {
+                    println!("Discovered MTU: {mtu}")
+                }
             }
         }
-        Connecting { endpoint, location } => {
+        Connecting {
+            endpoint,
+            location,
+            captive_portal,
+        } => {
             let ellipsis = if !verbose { "..." } else { "" };
             println!(
                 "Connecting to {}{ellipsis}",
                 format_relay_connection(endpoint, location.as_ref(), verbose)
             );
+            print_captive_portal_login(captive_portal.as_ref());
         }
         Disconnected {
             location: _,
             locked_down,
+            captive_portal,
+            paused_until: _,
         } => {
             if *locked_down {
                 println!("Disconnected (Internet access is blocked due to lockdown mode)");
             } else {
                 println!("Disconnected");
             }
+            if let Some(remaining) = state.pause_remaining() {
+                println!("Paused, reconnecting in {}", format_remaining(remaining));
+            }
+            print_captive_portal_login(captive_portal.as_ref());
         }
         Disconnecting(_) => println!("Disconnecting..."),
     }
@@ -53,10 +97,7 @@ pub fn print_state(state: &TunnelState, verbose: bool) {
 
 pub fn print_location(state: &TunnelState) {
     let location = match state {
-        TunnelState::Disconnected {
-            location,
-            locked_down: _,
-        } => location,
+        TunnelState::Disconnected { location, .. } => location,
         TunnelState::Connected { location, .. } => location,
         _ => return,
     };
@@ -75,6 +116,47 @@ pub fn print_location(state: &TunnelState) {
     }
 }
 
+pub fn print_expiry_warning(warning: &ExpiryWarning) {
+    if warning.is_expired() {
+        println!("Warning: The account is out of time");
+    } else {
+        println!(
+            "Warning: The account expires in less than {} ({})",
+            format_threshold(warning.threshold),
+            warning.expiry.with_timezone(&chrono::Local),
+        );
+    }
+}
+
+/// Formats the time left of a pause, e.g. "1h 5m" or "30s"
+pub fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, minutes) => format!("{minutes}m {seconds}s"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+pub fn format_threshold(threshold: Duration) -> String {
+    const MINUTES_PER_DAY: u64 = 24 * 60;
+
+    let minutes = threshold.as_secs() / 60;
+    let (amount, unit) = if minutes > 0 && minutes % MINUTES_PER_DAY == 0 {
+        (minutes / MINUTES_PER_DAY, "day")
+    } else if minutes > 0 && minutes % 60 == 0 {
+        (minutes / 60, "hour")
+    } else {
+        (minutes, "minute")
+    };
+    if amount == 1 {
+        format!("{amount} {unit}")
+    } else {
+        format!("{amount} {unit}s")
+    }
+}
+
 fn format_relay_connection(
     endpoint: &TunnelEndpoint,
     location: Option<&GeoIpLocation>,
@@ -222,6 +304,29 @@ fn print_error_state(error_state: &ErrorState) {
         }
         cause => println!("Blocked: {cause}"),
     }
+    print_captive_portal_login(error_state.captive_portal());
+}
+
+pub fn print_captive_portal_login(login: Option<&CaptivePortalLogin>) {
+    match login {
+        Some(
+            login @ CaptivePortalLogin::Active {
+                expires,
+                portal_detected: _,
+            },
+        ) => {
+            let expires = chrono::DateTime::<chrono::Local>::from(*expires);
+            println!(
+                "Captive portal login: {login} (until {})",
+                expires.format("%H:%M:%S")
+            );
+        }
+        Some(CaptivePortalLogin::Detected) => {
+            println!("Captive portal: detected, run `mullvad captive-portal login` to log in")
+        }
+        Some(login) => println!("Captive portal login: {login}"),
+        None => (),
+    }
 }
 
 const fn get_auth_failed_message(auth_failed: AuthFailed) -> &'static str {
@@ -1,5 +1,6 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::time::Duration;
 
 mod cmds;
 mod format;
@@ -10,6 +11,16 @@ pub const BIN_NAME: &str = env!("CARGO_BIN_NAME");
 #[derive(Debug, Parser)]
 #[command(author, version = mullvad_version::VERSION, about, long_about = None)]
 #[command(propagate_version = true)]
+struct Options {
+    /// Print output as JSON. See docs/cli-json-output.md for the schema
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    cli: Cli,
+}
+
+#[derive(Debug, Subcommand)]
 enum Cli {
     /// Control and display information about your Mullvad account
     #[clap(subcommand)]
@@ -23,6 +34,11 @@ enum Cli {
     #[clap(subcommand)]
     BetaProgram(beta_program::BetaProgram),
 
+    /// Temporarily allow traffic to a captive portal while the VPN is blocking
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[clap(subcommand)]
+    CaptivePortal(captive_portal::CaptivePortal),
+
     /// Control whether to block network access when disconnected from VPN
     #[clap(subcommand)]
     LockdownMode(lockdown::LockdownMode),
@@ -54,6 +70,11 @@ enum Cli {
         /// Wait until disconnected before exiting
         #[arg(long, short = 'w')]
         wait: bool,
+
+        /// Pause the VPN and reconnect automatically after the given time, e.g. '10m' or '1h'.
+        /// Not available while lockdown mode is enabled
+        #[arg(long = "for", value_parser = cmds::parse_duration)]
+        duration: Option<Duration>,
     },
 
     /// Reconnect to any matching VPN relay
@@ -100,6 +121,12 @@ enum Cli {
     #[clap(subcommand)]
     SplitTunnel(split_tunnel::SplitTunnel),
 
+    /// Exclude the traffic and routes of other VPN interfaces, such as Tailscale or ZeroTier,
+    /// from the tunnel
+    #[cfg(target_os = "linux")]
+    #[clap(subcommand)]
+    TrustedInterfaces(trusted_interface::TrustedInterfaces),
+
     /// Return the state of the VPN tunnel
     Status {
         #[clap(subcommand)]
@@ -136,6 +163,14 @@ enum Cli {
     #[clap(subcommand)]
     CustomList(custom_list::CustomList),
 
+    /// Manage named settings profiles
+    #[clap(subcommand)]
+    Profile(profile::Profile),
+
+    /// Inspect and restore previous settings
+    #[clap(subcommand)]
+    SettingsHistory(settings_history::SettingsHistory),
+
     /// Apply a JSON patch generated by 'export-settings'
     #[clap(arg_required_else_help = true)]
     ImportSettings {
@@ -153,15 +188,20 @@ enum Cli {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    match Cli::parse() {
+    let options = Options::parse();
+    format::set_json_output(options.json);
+
+    match options.cli {
         Cli::Account(cmd) => cmd.handle().await,
         Cli::Bridge(cmd) => cmd.handle().await,
         Cli::Connect { wait } => tunnel_state::connect(wait).await,
         Cli::Reconnect { wait } => tunnel_state::reconnect(wait).await,
         Cli::Debug(cmd) => cmd.handle().await,
-        Cli::Disconnect { wait } => tunnel_state::disconnect(wait).await,
+        Cli::Disconnect { wait, duration } => tunnel_state::disconnect(wait, duration).await,
         Cli::AutoConnect(cmd) => cmd.handle().await,
         Cli::BetaProgram(cmd) => cmd.handle().await,
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        Cli::CaptivePortal(cmd) => cmd.handle().await,
         Cli::LockdownMode(cmd) => cmd.handle().await,
         Cli::Dns(cmd) => cmd.handle().await,
         Cli::Lan(cmd) => cmd.handle().await,
@@ -173,8 +213,12 @@ async fn main() -> Result<()> {
         Cli::Tunnel(cmd) => cmd.handle().await,
         #[cfg(any(target_os = "windows", target_os = "linux"))]
         Cli::SplitTunnel(cmd) => cmd.handle().await,
+        #[cfg(target_os = "linux")]
+        Cli::TrustedInterfaces(cmd) => cmd.handle().await,
         Cli::Status { cmd, args } => status::handle(cmd, args).await,
         Cli::CustomList(cmd) => cmd.handle().await,
+        Cli::Profile(cmd) => cmd.handle().await,
+        Cli::SettingsHistory(cmd) => cmd.handle().await,
         Cli::ImportSettings { file } => patch::import(file).await,
         Cli::ExportSettings { file } => patch::export(file).await,
 
@@ -185,7 +229,7 @@ async fn main() -> Result<()> {
 
             // FIXME: The shell completions include hidden commands (including "shell-completions")
             println!("Generating shell completions to {}", dir.display());
-            clap_complete::generate_to(shell, &mut Cli::command(), BIN_NAME, dir)
+            clap_complete::generate_to(shell, &mut Options::command(), BIN_NAME, dir)
                 .context("Failed to generate shell completions")?;
             Ok(())
         }
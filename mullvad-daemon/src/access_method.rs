@@ -53,7 +53,9 @@ where
         let access_method_setting = AccessMethodSetting::new(name, enabled, access_method);
         let id = access_method_setting.get_id();
         self.settings
-            .update(|settings| settings.api_access_methods.append(access_method_setting))
+            .update("add_api_access_method", |settings| {
+                settings.api_access_methods.append(access_method_setting)
+            })
             .await
             .map(|did_change| self.notify_on_change(did_change))
             .map(|_| id)
@@ -70,10 +72,13 @@ where
     ) -> Result<(), Error> {
         let did_change = self
             .settings
-            .try_update(|settings| -> Result<(), Error> {
-                settings.api_access_methods.remove(&access_method)?;
-                Ok(())
-            })
+            .try_update(
+                "remove_api_access_method",
+                |settings| -> Result<(), Error> {
+                    settings.api_access_methods.remove(&access_method)?;
+                    Ok(())
+                },
+            )
             .await
             .map_err(Error::Settings)?;
 
@@ -186,7 +191,7 @@ where
         };
 
         self.settings
-            .update(settings_update)
+            .update("update_api_access_method", settings_update)
             .await
             .map(|did_change| self.notify_on_change(did_change))
             .map_err(Error::Settings)?;
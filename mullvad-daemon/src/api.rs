@@ -13,6 +13,7 @@ use futures::{
 use mullvad_api::{
     availability::ApiAvailabilityHandle,
     proxy::{ApiConnectionMode, ProxyConfig},
+    rest::RequestServiceHandle,
     AddressCache,
 };
 use mullvad_relay_selector::RelaySelector;
@@ -21,7 +22,9 @@ use mullvad_types::access_method::{
 };
 use std::{net::SocketAddr, path::PathBuf};
 use talpid_core::mpsc::Sender;
-use talpid_types::net::{AllowedClients, AllowedEndpoint, Endpoint, TransportProtocol};
+use talpid_types::net::{
+    AllowedClients, AllowedEndpoint, Connectivity, Endpoint, TransportProtocol,
+};
 
 pub enum Message {
     Get(ResponseTx<ResolvedConnectionMode>),
@@ -527,22 +530,79 @@ pub fn allowed_clients(connection_mode: &ApiConnectionMode) -> AllowedClients {
     }
 }
 
+/// Forwards the connectivity of the host to the API and the relay selector. On networks without
+/// IPv4 connectivity, the NAT64 prefix is discovered, so that IPv4-only relays, bridges, and API
+/// addresses can be reached over IPv6.
 pub(crate) fn forward_offline_state(
     api_availability: ApiAvailabilityHandle,
-    mut offline_state_rx: mpsc::UnboundedReceiver<bool>,
+    relay_selector: RelaySelector,
+    address_cache: AddressCache,
+    connection_modes_handler: AccessModeSelectorHandle,
+    api_service: RequestServiceHandle,
+    mut offline_state_rx: mpsc::UnboundedReceiver<Connectivity>,
 ) {
     tokio::spawn(async move {
-        let initial_state = offline_state_rx
+        let mut connectivity = offline_state_rx
             .next()
             .await
             .expect("missing initial offline state");
-        api_availability.set_offline(initial_state);
-        while let Some(is_offline) = offline_state_rx.next().await {
-            api_availability.set_offline(is_offline);
+        loop {
+            api_availability.set_offline(connectivity.is_offline());
+            relay_selector.set_connectivity(connectivity);
+
+            let nat64_prefix = if connectivity.has_ipv4() {
+                // NAT64 is not needed when IPv4 is available
+                Some(None)
+            } else if connectivity.has_ipv6() {
+                // Keep the current prefix if no prefix could be discovered, since the network
+                // may not have responded yet
+                crate::nat64::discover_prefix().await.map(Some)
+            } else {
+                // Keep the prefix while offline, since the network is likely to return
+                None
+            };
+            if let Some(nat64_prefix) = nat64_prefix {
+                relay_selector.set_nat64_prefix(nat64_prefix);
+                if address_cache.set_nat64_prefix(nat64_prefix).await {
+                    log::debug!(
+                        "API address changed to {}",
+                        address_cache.get_address().await
+                    );
+                    reset_api_endpoint(&connection_modes_handler, &api_service).await;
+                }
+            }
+
+            match offline_state_rx.next().await {
+                Some(new_connectivity) => connectivity = new_connectivity,
+                None => break,
+            }
         }
     });
 }
 
+/// Resolves the current API access method again, so that the allowed API endpoint follows the
+/// address returned by the address cache.
+async fn reset_api_endpoint(
+    connection_modes_handler: &AccessModeSelectorHandle,
+    api_service: &RequestServiceHandle,
+) {
+    let result = match connection_modes_handler.get_current().await {
+        Ok(current) => {
+            connection_modes_handler
+                .set_access_method(current.setting)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    if let Err(error) = result {
+        log::error!("Failed to reset the API access method: {error}");
+        return;
+    }
+    if let Err(error) = api_service.next_api_endpoint().await {
+        log::error!("Failed to rotate API endpoint: {error}");
+    }
+}
+
 #[cfg(target_os = "android")]
 pub(crate) fn create_bypass_tx(
     event_sender: &DaemonEventSender,
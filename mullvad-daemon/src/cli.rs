@@ -1,5 +1,7 @@
 use clap::Parser;
 use once_cell::sync::Lazy;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
 
 static ENV_DESC: Lazy<String> = Lazy::new(|| {
     format!(
@@ -49,6 +51,11 @@ struct Cli {
     #[cfg(target_os = "linux")]
     #[arg(long)]
     initialize_early_boot_firewall: bool,
+    /// Write the rules of the early boot firewall to a file that can be loaded using `nft -f`,
+    /// and exit
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "PATH")]
+    export_early_boot_firewall: Option<PathBuf>,
 
     /// Check the status of the launch daemon. The exit code represents the current status
     #[cfg(target_os = "macos")]
@@ -69,6 +76,8 @@ pub struct Config {
     pub launch_daemon_status: bool,
     #[cfg(target_os = "linux")]
     pub initialize_firewall_and_exit: bool,
+    #[cfg(target_os = "linux")]
+    pub export_firewall_and_exit: Option<PathBuf>,
 }
 
 pub fn get_config() -> &'static Config {
@@ -97,5 +106,7 @@ fn create_config() -> Config {
         launch_daemon_status: app.launch_daemon_status,
         #[cfg(target_os = "linux")]
         initialize_firewall_and_exit: app.initialize_early_boot_firewall,
+        #[cfg(target_os = "linux")]
+        export_firewall_and_exit: app.export_early_boot_firewall,
     }
 }
@@ -4,8 +4,10 @@ use mullvad_types::{
     relay_constraints::{
         BridgeState, Constraint, LocationConstraint, RelaySettings, ResolvedBridgeSettings,
     },
+    relay_list_changes::RelayListChanges,
 };
 use talpid_types::net::TunnelType;
+use talpid_types::ErrorExt;
 
 impl<L> Daemon<L>
 where
@@ -26,7 +28,7 @@ where
 
         let settings_changed = self
             .settings
-            .update(|settings| {
+            .update("create_custom_list", |settings| {
                 settings.custom_lists.add(new_list);
             })
             .await
@@ -54,7 +56,7 @@ where
         };
         let settings_changed = self
             .settings
-            .update(|settings| {
+            .update("delete_custom_list", |settings| {
                 // NOTE: Not using swap remove because it would make user output slightly
                 // more confusing and the cost is so small.
                 settings.custom_lists.remove(list_index);
@@ -78,6 +80,29 @@ where
         Ok(())
     }
 
+    /// Remove relays that no longer exist from all custom lists.
+    pub async fn prune_custom_lists(&mut self, changes: &RelayListChanges) {
+        let settings_changed = self
+            .settings
+            .update("prune_custom_lists", |settings| {
+                changes.prune_custom_lists(&mut settings.custom_lists);
+            })
+            .await;
+
+        match settings_changed {
+            Ok(true) => {
+                log::info!("Removed relays that no longer exist from custom lists");
+                self.event_listener
+                    .notify_settings(self.settings.to_settings());
+            }
+            Ok(false) => (),
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to prune custom lists")
+            ),
+        }
+    }
+
     pub async fn update_custom_list(&mut self, new_list: CustomList) -> Result<(), Error> {
         let Some((list_index, old_list)) = self
             .settings
@@ -102,7 +127,7 @@ where
 
         let settings_changed = self
             .settings
-            .update(|settings| {
+            .update("update_custom_list", |settings| {
                 settings.custom_lists[list_index] = new_list;
             })
             .await
@@ -135,10 +160,7 @@ where
                 need_to_reconnect |= list_id == &custom_list_id;
             }
 
-            if let TunnelState::Connecting {
-                endpoint,
-                location: _,
-            }
+            if let TunnelState::Connecting { endpoint, .. }
             | TunnelState::Connected {
                 endpoint,
                 location: _,
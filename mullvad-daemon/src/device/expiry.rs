@@ -0,0 +1,198 @@
+//! Warns about the account running out of time, and clears the warning when the account is
+//! renewed.
+
+use super::{AccountManagerHandle, Error};
+use chrono::{DateTime, Utc};
+use futures::{
+    channel::mpsc,
+    future::{BoxFuture, Fuse, FusedFuture, FutureExt},
+    stream::StreamExt,
+};
+use mullvad_types::account::ExpiryWarning;
+use std::time::{Duration, Instant};
+use talpid_core::mpsc::Sender;
+
+/// Longest time to wait before checking whether a threshold has been passed. Timers are not
+/// guaranteed to advance while the machine is suspended, so the wall clock must be checked
+/// regularly.
+const MAX_EVALUATION_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often to fetch the expiry while a warning is active, to detect renewals.
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// How often to fetch the expiry while no warning is active.
+const EXPIRY_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait before retrying a failed expiry check.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+enum ExpiryMonitorCommand {
+    SetExpiry(DateTime<Utc>),
+    SetThresholds(Vec<Duration>),
+    Reset,
+}
+
+#[derive(Clone)]
+pub(crate) struct ExpiryMonitorHandle {
+    cmd_tx: mpsc::UnboundedSender<ExpiryMonitorCommand>,
+}
+
+impl ExpiryMonitorHandle {
+    /// Use an expiry that was fetched elsewhere, for example after submitting a voucher.
+    pub fn set_expiry(&self, expiry: DateTime<Utc>) {
+        self.send_command(ExpiryMonitorCommand::SetExpiry(expiry));
+    }
+
+    pub fn set_thresholds(&self, thresholds: Vec<Duration>) {
+        self.send_command(ExpiryMonitorCommand::SetThresholds(thresholds));
+    }
+
+    /// Forget the current expiry and fetch it again. Should be called when the account changes.
+    pub fn reset(&self) {
+        self.send_command(ExpiryMonitorCommand::Reset);
+    }
+
+    fn send_command(&self, command: ExpiryMonitorCommand) {
+        if self.cmd_tx.unbounded_send(command).is_err() {
+            log::error!("Expiry monitor is down");
+        }
+    }
+}
+
+pub(crate) struct ExpiryMonitor<L> {
+    account_manager: AccountManagerHandle,
+    thresholds: Vec<Duration>,
+    expiry: Option<DateTime<Utc>>,
+    warning: Option<ExpiryWarning>,
+    /// When to fetch the expiry next. `None` if there is no device to fetch it for.
+    next_check: Option<Instant>,
+    listener: L,
+}
+
+impl<L: Sender<Option<ExpiryWarning>> + Send + 'static> ExpiryMonitor<L> {
+    /// Starts the expiry monitor. A new warning is sent to `listener` each time a threshold is
+    /// passed, and `None` is sent when a warning no longer applies.
+    pub fn spawn(
+        account_manager: AccountManagerHandle,
+        thresholds: Vec<Duration>,
+        listener: L,
+    ) -> ExpiryMonitorHandle {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded();
+        let monitor = ExpiryMonitor {
+            account_manager,
+            thresholds,
+            expiry: None,
+            warning: None,
+            next_check: Some(Instant::now()),
+            listener,
+        };
+        tokio::spawn(monitor.run(cmd_rx));
+        ExpiryMonitorHandle { cmd_tx }
+    }
+
+    async fn run(mut self, mut cmd_rx: mpsc::UnboundedReceiver<ExpiryMonitorCommand>) {
+        let mut expiry_check: Fuse<BoxFuture<'static, Result<DateTime<Utc>, Error>>> =
+            Fuse::terminated();
+
+        loop {
+            if self.update_warning().is_err() {
+                break;
+            }
+
+            let check_is_due = self
+                .next_check
+                .map(|next_check| next_check <= Instant::now())
+                .unwrap_or(false);
+            if check_is_due && expiry_check.is_terminated() {
+                self.next_check = None;
+                let account_manager = self.account_manager.clone();
+                expiry_check = async move { account_manager.check_expiry().await }
+                    .boxed()
+                    .fuse();
+            }
+
+            let mut timeout = Box::pin(tokio::time::sleep(self.time_until_next_event())).fuse();
+
+            futures::select! {
+                result = expiry_check => self.consume_expiry_check(result),
+                cmd = cmd_rx.next() => match cmd {
+                    Some(ExpiryMonitorCommand::SetExpiry(expiry)) => self.set_expiry(expiry),
+                    Some(ExpiryMonitorCommand::SetThresholds(thresholds)) => {
+                        self.thresholds = thresholds;
+                    }
+                    Some(ExpiryMonitorCommand::Reset) => {
+                        expiry_check = Fuse::terminated();
+                        self.expiry = None;
+                        self.next_check = Some(Instant::now());
+                    }
+                    None => break,
+                },
+                _ = timeout => (),
+            }
+        }
+        log::debug!("Expiry monitor has stopped");
+    }
+
+    /// Notifies the listener if the warning has changed. Fails if the listener is gone.
+    fn update_warning(&mut self) -> Result<(), talpid_core::mpsc::Error> {
+        let warning = self
+            .expiry
+            .and_then(|expiry| ExpiryWarning::new(expiry, &self.thresholds, Utc::now()));
+        if warning == self.warning {
+            return Ok(());
+        }
+        match &warning {
+            Some(warning) if warning.is_expired() => log::info!("The account has no time left"),
+            Some(warning) => log::info!("The account expires at {}", warning.expiry),
+            None => log::debug!("Clearing account expiry warning"),
+        }
+        self.warning = warning.clone();
+        self.listener.send(warning)
+    }
+
+    fn consume_expiry_check(&mut self, result: Result<DateTime<Utc>, Error>) {
+        match result {
+            Ok(expiry) => self.set_expiry(expiry),
+            Err(Error::NoDevice) => {
+                self.expiry = None;
+                self.next_check = None;
+            }
+            Err(_) => {
+                // The account manager logs the error
+                self.next_check = Some(Instant::now() + RETRY_INTERVAL);
+            }
+        }
+    }
+
+    fn set_expiry(&mut self, expiry: DateTime<Utc>) {
+        self.expiry = Some(expiry);
+        let has_warning = ExpiryWarning::new(expiry, &self.thresholds, Utc::now()).is_some();
+        let interval = if has_warning {
+            RENEWAL_CHECK_INTERVAL
+        } else {
+            EXPIRY_REFRESH_INTERVAL
+        };
+        self.next_check = Some(Instant::now() + interval);
+    }
+
+    /// Returns the time until the next threshold is passed or the next expiry check is due.
+    fn time_until_next_event(&self) -> Duration {
+        let mut next_event = MAX_EVALUATION_INTERVAL;
+        if let Some(expiry) = self.expiry {
+            let remaining = (expiry - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+            let until_thresholds = self
+                .thresholds
+                .iter()
+                .filter_map(|threshold| remaining.checked_sub(*threshold))
+                .chain(std::iter::once(remaining))
+                .filter(|until| !until.is_zero());
+            for until in until_thresholds {
+                next_event = next_event.min(until);
+            }
+        }
+        if let Some(next_check) = self.next_check {
+            next_event = next_event.min(next_check.saturating_duration_since(Instant::now()));
+        }
+        next_event
+    }
+}
@@ -32,7 +32,9 @@ use tokio::{
 };
 
 mod api;
+mod expiry;
 mod service;
+pub(crate) use expiry::{ExpiryMonitor, ExpiryMonitorHandle};
 pub(crate) use service::{AccountService, DeviceService};
 
 /// File that used to store account and device data.
@@ -1273,7 +1275,7 @@ impl TunnelStateChangeHandler {
     /// attempt, and zero the counter when leaving the connecting loop.
     fn next_retry_attempt(new_state: &TunnelStateTransition, retry_attempt: usize) -> usize {
         match new_state {
-            TunnelStateTransition::Connecting(endpoint) => {
+            TunnelStateTransition::Connecting(endpoint, _) => {
                 if endpoint.tunnel_type == TunnelType::Wireguard {
                     retry_attempt.wrapping_add(1)
                 } else {
@@ -1348,7 +1350,7 @@ impl TunnelStateChangeHandler {
 #[cfg(test)]
 mod test {
     use super::{Error, TunnelStateChangeHandler, WG_DEVICE_CHECK_THRESHOLD};
-    use mullvad_relay_selector::RelaySelector;
+    use mullvad_types::connection_plan::ConnectionPlan;
     use std::sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -1437,15 +1439,15 @@ mod test {
         );
     }
 
-    /// Test whether the relay selector selects wireguard often enough, given no special
+    /// Test whether the default connection plan selects wireguard often enough, given no special
     /// constraints, to verify that the device is valid
     #[test]
     fn test_validates_by_default() {
+        let plan = ConnectionPlan::default();
         for attempt in 0.. {
             let should_validate =
                 TunnelStateChangeHandler::should_check_validity_on_attempt(attempt);
-            let (_, _, tunnel_type) =
-                RelaySelector::preferred_tunnel_constraints(attempt.try_into().unwrap());
+            let tunnel_type = plan.attempt(attempt.try_into().unwrap()).tunnel_type;
             assert_eq!(
                 tunnel_type,
                 TunnelType::Wireguard,
@@ -1458,3 +1460,211 @@ mod test {
         }
     }
 }
+
+/// Tests that run the account manager against a mock API server.
+#[cfg(test)]
+mod api_test {
+    use super::{AccountEvent, AccountManager, AccountManagerHandle, Error, PrivateDeviceState};
+    use futures::channel::mpsc;
+    use mullvad_api::{
+        proxy::ApiConnectionMode, rest::StatusCode, MAX_DEVICES_REACHED, PUBKEY_IN_USE,
+    };
+    use mullvad_api_mock::{Endpoint, InjectedError, MockApi};
+    use mullvad_types::{account::AccountToken, wireguard::RotationInterval};
+    use talpid_types::net::wireguard::PrivateKey;
+
+    struct TestManager {
+        handle: AccountManagerHandle,
+        _events: mpsc::UnboundedReceiver<AccountEvent>,
+        _settings_dir: tempfile::TempDir,
+        _runtime: mullvad_api::Runtime,
+    }
+
+    /// Starts an account manager that talks to the shared mock API.
+    async fn spawn_manager() -> TestManager {
+        MockApi::shared();
+
+        let runtime = mullvad_api::Runtime::new(tokio::runtime::Handle::current()).unwrap();
+        let rest_handle = runtime
+            .mullvad_rest_handle(ApiConnectionMode::Direct.into_repeat())
+            .await;
+        let settings_dir = tempfile::tempdir().unwrap();
+        let (events_tx, events_rx) = mpsc::unbounded();
+        let (handle, _) = AccountManager::spawn(
+            rest_handle,
+            settings_dir.path(),
+            RotationInterval::default(),
+            events_tx,
+        )
+        .await
+        .unwrap();
+
+        TestManager {
+            handle,
+            _events: events_rx,
+            _settings_dir: settings_dir,
+            _runtime: runtime,
+        }
+    }
+
+    async fn logged_in_manager(account: &AccountToken) -> TestManager {
+        let manager = spawn_manager().await;
+        manager.handle.login(account.clone()).await.unwrap();
+        manager
+    }
+
+    /// Logging in should register the device with the API
+    #[tokio::test]
+    async fn test_login_creates_device() {
+        let mock = MockApi::shared();
+        let account = mock.create_account();
+
+        let manager = logged_in_manager(&account).await;
+
+        let state = manager.handle.data().await.unwrap();
+        let device = state.device().expect("expected to be logged in");
+        let devices = mock.devices(&account);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].device.id, device.device.id);
+        assert_eq!(
+            devices[0].device.pubkey,
+            device.device.wg_data.private_key.public_key()
+        );
+    }
+
+    /// A full account should be reported as such
+    #[tokio::test]
+    async fn test_login_max_devices_reached() {
+        let mock = MockApi::shared();
+        let account = mock.create_account();
+        mock.set_max_devices(&account, 1);
+        mock.add_device(&account, PrivateKey::new_from_random().public_key());
+
+        let manager = spawn_manager().await;
+        let result = manager.handle.login(account.clone()).await;
+
+        assert!(matches!(result, Err(Error::MaxDevicesReached)));
+        assert!(manager.handle.data().await.unwrap().logged_out());
+    }
+
+    /// An injected error should be mapped in the same way as a real one
+    #[tokio::test]
+    async fn test_login_injected_max_devices_reached() {
+        let mock = MockApi::shared();
+        let account = mock.create_account();
+        mock.inject_error(
+            InjectedError::new(
+                Endpoint::CreateDevice,
+                StatusCode::BAD_REQUEST,
+                MAX_DEVICES_REACHED,
+            )
+            .for_account(&account)
+            .times(1),
+        );
+
+        let manager = spawn_manager().await;
+        let result = manager.handle.login(account.clone()).await;
+        assert!(matches!(result, Err(Error::MaxDevicesReached)));
+
+        manager.handle.login(account.clone()).await.unwrap();
+        assert_eq!(mock.devices(&account).len(), 1);
+    }
+
+    /// A rejected key rotation should keep the old key
+    #[tokio::test]
+    async fn test_rotate_key_pubkey_in_use() {
+        let mock = MockApi::shared();
+        let account = mock.create_account();
+        let manager = logged_in_manager(&account).await;
+        let old_pubkey = mock.devices(&account)[0].device.pubkey.clone();
+
+        mock.inject_error(
+            InjectedError::new(Endpoint::ReplaceKey, StatusCode::BAD_REQUEST, PUBKEY_IN_USE)
+                .for_account(&account)
+                .times(1),
+        );
+        let result = manager.handle.rotate_key().await;
+        assert!(
+            matches!(result, Err(Error::OtherRestError(mullvad_api::rest::Error::ApiError(_, ref code))) if code == PUBKEY_IN_USE)
+        );
+
+        let state = manager.handle.data().await.unwrap();
+        let device = state.device().unwrap();
+        assert_eq!(device.device.wg_data.private_key.public_key(), old_pubkey);
+        assert_eq!(mock.devices(&account)[0].device.pubkey, old_pubkey);
+
+        manager.handle.rotate_key().await.unwrap();
+        let state = manager.handle.data().await.unwrap();
+        let new_pubkey = state
+            .device()
+            .unwrap()
+            .device
+            .wg_data
+            .private_key
+            .public_key();
+        assert_ne!(new_pubkey, old_pubkey);
+        assert_eq!(mock.devices(&account)[0].device.pubkey, new_pubkey);
+    }
+
+    /// An access token rejected by the API should be discarded, so that the next request uses a
+    /// new one
+    #[tokio::test]
+    async fn test_invalid_access_token_is_refreshed() {
+        let mock = MockApi::shared();
+        let account = mock.create_account();
+        let manager = logged_in_manager(&account).await;
+        assert_eq!(mock.request_count(Endpoint::AccessToken, &account), 1);
+
+        mock.revoke_access_tokens(&account);
+        assert!(manager.handle.check_expiry().await.is_err());
+
+        manager.handle.check_expiry().await.unwrap();
+        assert_eq!(mock.request_count(Endpoint::AccessToken, &account), 2);
+    }
+
+    /// A device removed by another client should be revoked when validated
+    #[tokio::test]
+    async fn test_removed_device_is_revoked() {
+        let mock = MockApi::shared();
+        let account = mock.create_account();
+        let manager = logged_in_manager(&account).await;
+        let device_id = mock.devices(&account)[0].device.id.clone();
+
+        mock.remove_device(&account, &device_id);
+
+        assert!(matches!(
+            manager.handle.validate_device().await,
+            Err(Error::InvalidDevice)
+        ));
+        assert_eq!(
+            manager.handle.data().await.unwrap(),
+            PrivateDeviceState::Revoked
+        );
+    }
+
+    /// Redeeming a voucher should extend the account, but only once
+    #[tokio::test]
+    async fn test_submit_voucher() {
+        let mock = MockApi::shared();
+        let account = mock.create_account_with_expiry(chrono::Utc::now());
+        let voucher = format!("VOUCHER-{account}");
+        mock.add_voucher(&voucher, 30);
+        let manager = logged_in_manager(&account).await;
+
+        let submission = manager
+            .handle
+            .submit_voucher(voucher.clone())
+            .await
+            .unwrap();
+        assert_eq!(submission.time_added, 30 * 24 * 60 * 60);
+        assert_eq!(
+            manager.handle.check_expiry().await.unwrap(),
+            submission.new_expiry
+        );
+
+        assert!(matches!(
+            manager.handle.submit_voucher(voucher).await,
+            Err(Error::UsedVoucher)
+        ));
+    }
+}
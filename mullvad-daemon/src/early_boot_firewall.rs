@@ -1,38 +1,267 @@
-use mullvad_daemon::settings::{self, SettingsPersister};
+use mullvad_api::proxy::ApiConnectionMode;
+use mullvad_daemon::settings::SettingsPersister;
+use mullvad_types::states::TargetState;
+use nix::sched::{unshare, CloneFlags};
+use std::{io, path::Path, process::Command, thread};
 use talpid_core::firewall::{self, Firewall, FirewallPolicy};
+use talpid_types::ErrorExt;
+
+/// Name of the nftables table that the firewall adds its rules to.
+const TABLE_NAME: &str = "mullvad";
 
 #[derive(err_derive::Error, Debug)]
+#[error(no_from)]
 pub enum Error {
     #[error(display = "Failed to initialize firewall")]
     Firewall(#[error(source)] firewall::Error),
 
-    #[error(display = "Failed to get settings path")]
+    #[error(display = "Failed to get settings or cache path")]
     Path(#[error(source)] mullvad_paths::Error),
 
-    #[error(display = "Failed to get settings")]
-    Settings(#[error(source)] settings::Error),
+    #[error(display = "Failed to load the cached API address")]
+    ApiRuntime(#[error(source)] mullvad_api::Error),
+
+    #[error(display = "Failed to create a network namespace")]
+    CreateNamespace(#[error(source)] nix::Error),
+
+    #[error(display = "Failed to run nft")]
+    RunNft(#[error(source)] io::Error),
+
+    #[error(display = "nft failed to list the firewall rules: {}", _0)]
+    ListRules(String),
+
+    #[error(display = "Failed to write the firewall rules to a file")]
+    WriteRules(#[error(source)] io::Error),
 }
 
 pub async fn initialize_firewall() -> Result<(), Error> {
-    let mut firewall = Firewall::new(mullvad_types::TUNNEL_FWMARK)?;
-    let allow_lan = get_allow_lan().await.unwrap_or_else(|err| {
-        log::info!(
-            "Not allowing LAN traffic due to failing to read settings: {}",
-            err
-        );
-        false
-    });
-    let policy = FirewallPolicy::Blocked {
-        allow_lan,
-        allowed_endpoint: None,
-    };
-    log::info!("Applying firewall policy {policy}");
-    firewall.apply_policy(policy)?;
+    let mut firewall = Firewall::new(mullvad_types::TUNNEL_FWMARK).map_err(Error::Firewall)?;
+    match early_boot_policy().await {
+        Some(policy) => {
+            log::info!("Applying firewall policy {policy}");
+            firewall.apply_policy(policy).map_err(Error::Firewall)
+        }
+        None => {
+            log::info!("Not blocking traffic, since the daemon will not block it when it starts");
+            firewall.reset_policy().map_err(Error::Firewall)
+        }
+    }
+}
+
+/// Writes the rules of the early boot firewall to `path` as an nftables file, which can be loaded
+/// using `nft -f` before the daemon binary can run. The rules are created in a new network
+/// namespace, so the firewall of the host is not affected.
+pub async fn export_firewall(path: &Path) -> Result<(), Error> {
+    let policy = early_boot_policy().await;
+    if let Some(ref policy) = policy {
+        log::info!("Exporting firewall policy {policy}");
+    }
+    // Network namespaces belong to threads, so a thread that is not reused must enter it.
+    let rules = thread::spawn(move || list_rules_in_new_namespace(policy))
+        .join()
+        .expect("Firewall export thread panicked")?;
+
+    let content = format!(
+        "# Early boot firewall rules generated by mullvad-daemon --export-early-boot-firewall.\n\
+         # The table is removed first, so that this file can be loaded more than once.\n\
+         table inet {TABLE_NAME}\n\
+         delete table inet {TABLE_NAME}\n\
+         {rules}"
+    );
+    tokio::fs::write(path, content)
+        .await
+        .map_err(Error::WriteRules)?;
+    log::info!("Wrote firewall rules to {}", path.display());
     Ok(())
 }
 
-async fn get_allow_lan() -> Result<bool, Error> {
-    let path = mullvad_paths::settings_dir()?;
-    let settings = SettingsPersister::load(&path).await;
-    Ok(settings.allow_lan)
+/// Applies `policy` in a new network namespace and returns the resulting table in the format of
+/// `nft list`. Returns an empty string if there is no policy.
+fn list_rules_in_new_namespace(policy: Option<FirewallPolicy>) -> Result<String, Error> {
+    let policy = match policy {
+        Some(policy) => policy,
+        None => return Ok(String::new()),
+    };
+    unshare(CloneFlags::CLONE_NEWNET).map_err(Error::CreateNamespace)?;
+
+    let mut firewall = Firewall::new(mullvad_types::TUNNEL_FWMARK).map_err(Error::Firewall)?;
+    firewall.apply_policy(policy).map_err(Error::Firewall)?;
+
+    let output = Command::new("nft")
+        .args(["list", "table", "inet", TABLE_NAME])
+        .output()
+        .map_err(Error::RunNft)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::ListRules(stderr.trim().to_owned()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the policy to apply before the daemon has started, or `None` if the daemon will not
+/// block traffic when it starts. All traffic is blocked if the settings cannot be read.
+async fn early_boot_policy() -> Option<FirewallPolicy> {
+    let policy = match (mullvad_paths::settings_dir(), mullvad_paths::cache_dir()) {
+        (Ok(settings_dir), Ok(cache_dir)) => persisted_policy(&settings_dir, &cache_dir).await,
+        (Err(error), _) | (_, Err(error)) => Err(Error::Path(error)),
+    };
+    policy.unwrap_or_else(|error| {
+        log::error!(
+            "{}",
+            error.display_chain_with_msg("Blocking all traffic due to failing to read settings")
+        );
+        Some(FirewallPolicy::Blocked {
+            allow_lan: false,
+            allowed_endpoint: None,
+            captive_portal: None,
+            firewall_exceptions: vec![],
+            trusted_interfaces: vec![],
+        })
+    })
+}
+
+/// Returns the blocking policy derived from the settings in `settings_dir` and the state cached in
+/// `cache_dir`, which is the policy that the daemon applies until it has started connecting. The
+/// daemon blocks traffic when it starts if lockdown mode or auto-connect is enabled, or if it was
+/// secured when it stopped.
+async fn persisted_policy(
+    settings_dir: &Path,
+    cache_dir: &Path,
+) -> Result<Option<FirewallPolicy>, Error> {
+    let settings = SettingsPersister::load(settings_dir).await;
+
+    if !settings.block_when_disconnected
+        && !settings.auto_connect
+        && mullvad_daemon::start_state(cache_dir).await != TargetState::Secured
+    {
+        return Ok(None);
+    }
+
+    // Allow the API, like the daemon does while it is blocking, using the access method and
+    // address it used last.
+    let connection_mode = ApiConnectionMode::try_from_cache(cache_dir).await;
+    let api_runtime = mullvad_api::Runtime::with_cache(cache_dir, false)
+        .await
+        .map_err(Error::ApiRuntime)?;
+    let allowed_endpoint = mullvad_daemon::resolve_allowed_endpoint(
+        &connection_mode,
+        api_runtime.address_cache.get_address().await,
+    );
+
+    Ok(Some(FirewallPolicy::Blocked {
+        allow_lan: settings.allow_lan,
+        allowed_endpoint: Some(allowed_endpoint),
+        captive_portal: None,
+        firewall_exceptions: settings.firewall_exceptions.clone(),
+        trusted_interfaces: settings.trusted_interfaces.clone(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::settings::Settings;
+
+    fn write_settings(settings_dir: &Path, settings: &Settings) {
+        std::fs::write(
+            settings_dir.join("settings.json"),
+            serde_json::to_vec(settings).unwrap(),
+        )
+        .unwrap();
+    }
+
+    async fn policy_with_settings(update: impl FnOnce(&mut Settings)) -> Option<FirewallPolicy> {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut settings = Settings::default();
+        update(&mut settings);
+        write_settings(settings_dir.path(), &settings);
+        persisted_policy(settings_dir.path(), cache_dir.path())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_no_policy_when_daemon_does_not_block() {
+        let policy = policy_with_settings(|settings| {
+            settings.block_when_disconnected = false;
+            settings.auto_connect = false;
+        })
+        .await;
+        assert_eq!(policy, None);
+    }
+
+    #[tokio::test]
+    async fn test_lockdown_mode_blocks() {
+        let policy = policy_with_settings(|settings| {
+            settings.block_when_disconnected = true;
+            settings.allow_lan = true;
+        })
+        .await;
+        assert!(matches!(
+            policy,
+            Some(FirewallPolicy::Blocked {
+                allow_lan: true,
+                allowed_endpoint: Some(_),
+                captive_portal: None,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_auto_connect_blocks() {
+        let policy = policy_with_settings(|settings| {
+            settings.block_when_disconnected = false;
+            settings.auto_connect = true;
+            settings.allow_lan = false;
+        })
+        .await;
+        assert!(matches!(
+            policy,
+            Some(FirewallPolicy::Blocked {
+                allow_lan: false,
+                allowed_endpoint: Some(_),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_secured_start_state_blocks() {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut settings = Settings::default();
+        settings.block_when_disconnected = false;
+        settings.auto_connect = false;
+        write_settings(settings_dir.path(), &settings);
+        std::fs::write(
+            cache_dir.path().join("target-start-state.json"),
+            serde_json::to_vec(&TargetState::Secured).unwrap(),
+        )
+        .unwrap();
+
+        let policy = persisted_policy(settings_dir.path(), cache_dir.path())
+            .await
+            .unwrap();
+        assert!(matches!(policy, Some(FirewallPolicy::Blocked { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_settings_block() {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::write(settings_dir.path().join("settings.json"), b"not settings").unwrap();
+
+        let policy = persisted_policy(settings_dir.path(), cache_dir.path())
+            .await
+            .unwrap();
+        assert!(matches!(
+            policy,
+            Some(FirewallPolicy::Blocked {
+                allow_lan: false,
+                ..
+            })
+        ));
+    }
 }
@@ -16,6 +16,7 @@ mod macos;
 #[cfg(not(target_os = "android"))]
 pub mod management_interface;
 mod migrations;
+mod nat64;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
 pub mod runtime;
@@ -23,11 +24,16 @@ pub mod settings;
 pub mod shutdown;
 mod target_state;
 mod tunnel;
+mod user_relay;
 pub mod version;
 mod version_check;
 
+pub use api::resolve_allowed_endpoint;
+pub use target_state::start_state;
+
 use crate::target_state::PersistentTargetState;
 use api::AccessMethodEvent;
+use chrono::{DateTime, Utc};
 use device::{AccountEvent, PrivateAccountAndDevice, PrivateDeviceEvent};
 use futures::{
     channel::{mpsc, oneshot},
@@ -43,21 +49,30 @@ use mullvad_relay_selector::{
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
 use mullvad_types::{
     access_method::{AccessMethod, AccessMethodSetting},
-    account::{AccountData, AccountToken, VoucherSubmission},
+    account::{AccountData, AccountToken, ExpiryWarning, VoucherSubmission},
     auth_failed::AuthFailed,
+    connection_plan::ConnectionPlanSimulation,
     custom_list::CustomList,
     device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
-    location::{GeoIpLocation, LocationEventData},
+    location::{Coordinates, GeoIpLocation, LocationEventData},
+    openvpn_profile::{self, ProfileImport},
+    profile::Profile,
     relay_constraints::{
         BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
     },
-    relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    relay_list::{RelayList, RelayListDiff},
+    relay_list_changes::RelayListChanges,
+    relay_query::{RelayQuery, RelayQueryResult},
+    settings::{
+        history::{SettingsChange, SettingsSnapshotInfo},
+        DnsOptions, Settings,
+    },
     states::{TargetState, TunnelState},
+    user_relay::UserRelay,
     version::{AppVersion, AppVersionInfo},
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
-use settings::SettingsPersister;
+use settings::{profiles::ProfileStore, ReplacedSetting, SettingsPersister};
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 #[cfg(target_os = "windows")]
@@ -78,10 +93,14 @@ use talpid_core::{
 };
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+#[cfg(not(target_os = "windows"))]
+use talpid_types::net::firewall_exception::FirewallException;
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
-    net::{IpVersion, TunnelEndpoint, TunnelType},
+    net::{wireguard::ConnectivityCheckOptions, IpVersion, TunnelEndpoint, TunnelType},
     tunnel::{ErrorStateCause, TunnelStateTransition},
     ErrorExt,
 };
@@ -92,6 +111,12 @@ use tokio::io;
 /// Delay between generating a new WireGuard key and reconnecting
 const WG_RECONNECT_DELAY: Duration = Duration::from_secs(4 * 60);
 
+/// The longest time that the tunnel can be paused for.
+const MAX_PAUSE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+/// Longest time between checks of the pause deadline against the wall clock. Timers use a
+/// monotonic clock, which does not advance while the computer is asleep.
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 pub type ResponseTx<T, E> = oneshot::Sender<Result<T, E>>;
 
 #[derive(err_derive::Error, Debug)]
@@ -177,9 +202,53 @@ pub enum Error {
     #[error(display = "A list with that name does not exist")]
     CustomListNotFound,
 
+    #[error(
+        display = "Cannot pause while lockdown mode is enabled since all traffic would be blocked"
+    )]
+    PauseInLockdown,
+
+    #[error(display = "The pause duration must be positive and at most 24 hours")]
+    InvalidPauseDuration,
+
+    #[error(
+        display = "Expiry warning thresholds must be positive, unique and in descending order"
+    )]
+    InvalidExpiryWarningThresholds,
+
+    #[error(display = "Invalid user-defined relay: {}", _0)]
+    InvalidUserRelay(mullvad_types::user_relay::Error),
+
+    #[error(display = "No user-defined relay has that hostname")]
+    UserRelayNotFound,
+
+    #[error(display = "Invalid connectivity check options: {}", _0)]
+    InvalidConnectivityCheck(talpid_types::net::wireguard::ConnectivityCheckError),
+
+    #[cfg(not(target_os = "windows"))]
+    #[error(display = "Invalid firewall exception: {}", _0)]
+    InvalidFirewallException(talpid_types::net::firewall_exception::Error),
+
+    #[cfg(not(target_os = "windows"))]
+    #[error(display = "No such firewall exception")]
+    FirewallExceptionNotFound,
+
+    #[cfg(target_os = "linux")]
+    #[error(display = "No such trusted interface")]
+    TrustedInterfaceNotFound,
+
+    #[cfg(target_os = "linux")]
+    #[error(
+        display = "The interface matches {}, whose traffic must go through the tunnel",
+        _0
+    )]
+    UntrustableInterface(String),
+
     #[error(display = "Access method error")]
     AccessMethodError(#[error(source)] access_method::Error),
 
+    #[error(display = "Invalid OpenVPN profile")]
+    OpenVpnProfile(#[error(source)] openvpn_profile::Error),
+
     #[error(display = "API connection mode error")]
     ApiConnectionModeError(#[error(source)] api::Error),
     #[error(display = "No custom bridge has been specified")]
@@ -204,8 +273,15 @@ pub enum DaemonCommand {
     SetTargetState(oneshot::Sender<bool>, TargetState),
     /// Reconnect the tunnel, if one is connecting/connected.
     Reconnect(oneshot::Sender<bool>),
+    /// Disconnect, and connect again once the given duration has passed. Fails if lockdown mode
+    /// is enabled.
+    PauseTunnel(ResponseTx<(), Error>, Duration),
     /// Request the current state.
     GetState(oneshot::Sender<TunnelState>),
+    /// Start a captive portal login that lasts for at most the given duration, or cancel the
+    /// ongoing login if `None` is given.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    SetCaptivePortalLogin(oneshot::Sender<()>, Option<Duration>),
     CreateNewAccount(ResponseTx<String, Error>),
     /// Request the metadata for an account.
     GetAccountData(
@@ -216,12 +292,22 @@ pub enum DaemonCommand {
     GetWwwAuthToken(ResponseTx<String, Error>),
     /// Submit voucher to add time to the current account. Returns time added in seconds
     SubmitVoucher(ResponseTx<VoucherSubmission, Error>, String),
+    /// Get the current warning about the account running out of time, if any
+    GetExpiryWarning(oneshot::Sender<Option<ExpiryWarning>>),
+    /// Set how long before the account expires to warn about it
+    SetExpiryWarningThresholds(ResponseTx<(), Error>, Vec<Duration>),
     /// Request account history
     GetAccountHistory(oneshot::Sender<Option<AccountToken>>),
     /// Remove the last used account, if there is one
     ClearAccountHistory(ResponseTx<(), Error>),
     /// Get the list of countries and cities where there are relays.
     GetRelayLocations(oneshot::Sender<RelayList>),
+    /// Get the changes to relays referenced by the settings in the last relay list update.
+    GetRelayListChanges(oneshot::Sender<RelayListChanges>),
+    /// Run the relay selector for a number of connection attempts, without connecting.
+    SimulateConnectionPlan(oneshot::Sender<ConnectionPlanSimulation>, u32),
+    /// List the relays matching the given constraints, without changing any settings.
+    QueryRelays(oneshot::Sender<RelayQueryResult>, RelayQuery),
     /// Trigger an asynchronous relay list update. This returns before the relay list is actually
     /// updated.
     UpdateRelayLocations,
@@ -241,6 +327,18 @@ pub enum DaemonCommand {
     SetRelaySettings(ResponseTx<(), settings::Error>, RelaySettings),
     /// Set the allow LAN setting.
     SetAllowLan(ResponseTx<(), settings::Error>, bool),
+    /// Add traffic that is allowed outside the tunnel
+    #[cfg(not(target_os = "windows"))]
+    AddFirewallException(ResponseTx<(), Error>, FirewallException),
+    /// Remove traffic that is allowed outside the tunnel
+    #[cfg(not(target_os = "windows"))]
+    RemoveFirewallException(ResponseTx<(), Error>, FirewallException),
+    /// Exclude the traffic and routes of an interface from the tunnel
+    #[cfg(target_os = "linux")]
+    AddTrustedInterface(ResponseTx<(), Error>, TrustedInterface),
+    /// Stop excluding the traffic and routes of an interface from the tunnel
+    #[cfg(target_os = "linux")]
+    RemoveTrustedInterface(ResponseTx<(), Error>, TrustedInterface),
     /// Set the beta program setting.
     SetShowBetaReleases(ResponseTx<(), settings::Error>, bool),
     /// Set the block_when_disconnected setting.
@@ -263,9 +361,19 @@ pub enum DaemonCommand {
     SetRelayOverride(ResponseTx<(), settings::Error>, RelayOverride),
     /// Remove all relay override options
     ClearAllRelayOverrides(ResponseTx<(), settings::Error>),
+    /// Add a user-defined relay, or replace the one with the same hostname
+    AddUserRelay(ResponseTx<(), Error>, UserRelay),
+    /// Remove the user-defined relay with the given hostname
+    RemoveUserRelay(ResponseTx<(), Error>, String),
+    /// Parse an OpenVPN profile and, unless it is a dry run, use it as a custom tunnel endpoint
+    ImportOpenVpnProfile(ResponseTx<ProfileImport, Error>, String, bool),
     /// Toggle macOS network check leak
     /// Set MTU for wireguard tunnels
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
+    /// Enable or disable path MTU discovery for wireguard tunnels
+    SetWireguardMtuDiscovery(ResponseTx<(), settings::Error>, bool),
+    /// Set the liveness probe and timeouts used to monitor wireguard tunnels
+    SetWireguardConnectivityCheck(ResponseTx<(), Error>, ConnectivityCheckOptions),
     /// Set automatic key rotation interval for wireguard tunnels
     SetWireguardRotationInterval(ResponseTx<(), settings::Error>, Option<RotationInterval>),
     /// Get the daemon settings
@@ -280,6 +388,8 @@ pub enum DaemonCommand {
     DeleteCustomList(ResponseTx<(), Error>, mullvad_types::custom_list::Id),
     /// Update a custom list with a given id
     UpdateCustomList(ResponseTx<(), Error>, CustomList),
+    /// Remove relays from custom lists when they are removed from the relay list
+    SetPruneCustomLists(ResponseTx<(), settings::Error>, bool),
     /// Add API access methods
     AddApiAccessMethod(
         ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -360,6 +470,20 @@ pub enum DaemonCommand {
     ApplyJsonSettings(ResponseTx<(), settings::patch::Error>, String),
     /// Return a JSON blob containing all overridable settings, if there are any
     ExportJsonSettings(ResponseTx<String, settings::patch::Error>),
+    /// Save the current settings as a new named profile
+    CreateProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Return all settings profiles
+    GetProfiles(oneshot::Sender<Vec<Profile>>),
+    /// Apply a settings profile, reconnecting at most once
+    ApplyProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Remove a settings profile
+    DeleteProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Return all snapshots in the settings history
+    GetSettingsHistory(oneshot::Sender<Vec<SettingsSnapshotInfo>>),
+    /// Return the changes made to the settings since a snapshot was taken
+    DiffSettingsSnapshot(ResponseTx<Vec<SettingsChange>, settings::Error>, u32),
+    /// Replace the settings with a snapshot from the settings history
+    RestoreSettingsSnapshot(ResponseTx<(), settings::Error>, u32),
 }
 
 /// All events that can happen in the daemon. Sent from various threads and exposed interfaces.
@@ -384,6 +508,12 @@ pub(crate) enum InternalDaemonEvent {
     DeviceMigrationEvent(Result<PrivateAccountAndDevice, device::Error>),
     /// A geographical location has has been received from am.i.mullvad.net
     LocationEvent(LocationEventData),
+    /// The relay list was updated, and some relays were added, removed or deactivated.
+    RelayListDiff(RelayListDiff),
+    /// An account expiry warning threshold was passed, or a warning was cleared.
+    ExpiryWarning(Option<ExpiryWarning>),
+    /// The deadline of a timed pause was reached.
+    PauseExpired,
     /// The split tunnel paths or state were updated.
     #[cfg(target_os = "windows")]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
@@ -413,6 +543,18 @@ impl From<AppVersionInfo> for InternalDaemonEvent {
     }
 }
 
+impl From<RelayListDiff> for InternalDaemonEvent {
+    fn from(diff: RelayListDiff) -> Self {
+        InternalDaemonEvent::RelayListDiff(diff)
+    }
+}
+
+impl From<Option<ExpiryWarning>> for InternalDaemonEvent {
+    fn from(warning: Option<ExpiryWarning>) -> Self {
+        InternalDaemonEvent::ExpiryWarning(warning)
+    }
+}
+
 impl From<AccountEvent> for InternalDaemonEvent {
     fn from(event: AccountEvent) -> Self {
         InternalDaemonEvent::DeviceEvent(event)
@@ -613,6 +755,12 @@ pub trait EventListener {
 
     /// Notify that the api access method changed.
     fn notify_new_access_method_event(&self, new_access_method: AccessMethodSetting);
+
+    /// Notify that relays referenced by the settings changed in a relay list update.
+    fn notify_relay_list_changes(&self, changes: RelayListChanges);
+
+    /// Notify that the account is about to run out of time, or that a warning no longer applies.
+    fn notify_expiry_warning(&self, warning: Option<ExpiryWarning>);
 }
 
 pub struct Daemon<L: EventListener> {
@@ -624,18 +772,23 @@ pub struct Daemon<L: EventListener> {
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
+    pause_job: Option<AbortHandle>,
     event_listener: L,
     migration_complete: migrations::MigrationComplete,
     settings: SettingsPersister,
+    profiles: ProfileStore,
     account_history: account_history::AccountHistory,
     device_checker: device::TunnelStateChangeHandler,
     account_manager: device::AccountManagerHandle,
+    expiry_monitor: device::ExpiryMonitorHandle,
+    expiry_warning: Option<ExpiryWarning>,
     connection_modes_handler: api::AccessModeSelectorHandle,
     api_runtime: mullvad_api::Runtime,
     api_handle: mullvad_api::rest::MullvadRestHandle,
     version_updater_handle: version_check::VersionUpdaterHandle,
     relay_selector: RelaySelector,
     relay_list_updater: RelayListUpdaterHandle,
+    relay_list_changes: RelayListChanges,
     parameters_generator: tunnel::ParametersGenerator,
     app_version_info: Option<AppVersionInfo>,
     shutdown_tasks: Vec<Pin<Box<dyn Future<Output = ()>>>>,
@@ -693,6 +846,7 @@ where
             // Notify management interface server of changes to the settings
             settings_event_listener.notify_settings(settings.to_owned());
         });
+        let profiles = ProfileStore::load(&settings_dir).await;
 
         let app_version_info = version_check::load_cache(&cache_dir).await;
 
@@ -744,6 +898,16 @@ where
         .await
         .map_err(Error::LoadAccountManager)?;
 
+        let expiry_monitor = device::ExpiryMonitor::spawn(
+            account_manager.clone(),
+            settings.expiry_warning_thresholds.clone(),
+            internal_event_tx.to_specialized_sender(),
+        );
+        let settings_expiry_monitor = expiry_monitor.clone();
+        settings.register_change_listener(move |settings| {
+            settings_expiry_monitor.set_thresholds(settings.expiry_warning_thresholds.clone());
+        });
+
         let account_history = account_history::AccountHistory::new(
             &settings_dir,
             data.device().map(|device| device.account_token.clone()),
@@ -798,6 +962,9 @@ where
         let tunnel_state_machine_handle = tunnel_state_machine::spawn(
             tunnel_state_machine::InitialTunnelState {
                 allow_lan: settings.allow_lan,
+                firewall_exceptions: settings.firewall_exceptions.clone(),
+                #[cfg(target_os = "linux")]
+                trusted_interfaces: settings.trusted_interfaces.clone(),
                 block_when_disconnected: settings.block_when_disconnected,
                 dns_servers: dns::addresses_from_options(&settings.tunnel_options.dns_options),
                 allowed_endpoint: initial_api_endpoint,
@@ -823,11 +990,22 @@ where
         .await
         .map_err(Error::TunnelError)?;
 
-        api::forward_offline_state(api_availability.clone(), offline_state_rx);
+        api::forward_offline_state(
+            api_availability.clone(),
+            relay_selector.clone(),
+            api_runtime.address_cache.clone(),
+            connection_modes_handler.clone(),
+            api_handle.service(),
+            offline_state_rx,
+        );
 
         let relay_list_listener = event_listener.clone();
-        let on_relay_list_update = move |relay_list: &RelayList| {
+        let relay_list_diff_tx = internal_event_tx.to_specialized_sender();
+        let on_relay_list_update = move |relay_list: &RelayList, diff: &RelayListDiff| {
             relay_list_listener.notify_relay_list(relay_list.clone());
+            if !diff.is_empty() {
+                let _ = relay_list_diff_tx.send(diff.clone());
+            }
         };
 
         let mut relay_list_updater = RelayListUpdater::spawn(
@@ -859,6 +1037,8 @@ where
             tunnel_state: TunnelState::Disconnected {
                 location: None,
                 locked_down: settings.block_when_disconnected,
+                captive_portal: None,
+                paused_until: target_state.paused_until(),
             },
             target_state,
             state: DaemonExecutionState::Running,
@@ -867,18 +1047,23 @@ where
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
+            pause_job: None,
             event_listener,
             migration_complete,
             settings,
+            profiles,
             account_history,
             device_checker: device::TunnelStateChangeHandler::new(account_manager.clone()),
             account_manager,
+            expiry_monitor,
+            expiry_warning: None,
             connection_modes_handler,
             api_runtime,
             api_handle,
             version_updater_handle,
             relay_selector,
             relay_list_updater,
+            relay_list_changes: RelayListChanges::default(),
             parameters_generator,
             app_version_info,
             shutdown_tasks: vec![],
@@ -896,6 +1081,10 @@ where
     /// Consume the `Daemon` and run the main event loop. Blocks until an error happens or a
     /// shutdown event is received.
     pub async fn run(mut self) -> Result<(), Error> {
+        if let Some(deadline) = self.target_state.paused_until() {
+            self.schedule_pause_end(deadline);
+        }
+
         match *self.target_state {
             TargetState::Secured => {
                 self.connect_tunnel();
@@ -988,6 +1177,9 @@ where
             } => self.handle_access_method_event(event, endpoint_active_tx),
             DeviceMigrationEvent(event) => self.handle_device_migration_event(event),
             LocationEvent(location_data) => self.handle_location_event(location_data),
+            RelayListDiff(diff) => self.handle_relay_list_diff(diff).await,
+            ExpiryWarning(warning) => self.handle_expiry_warning(warning),
+            PauseExpired => self.handle_pause_expired().await,
             #[cfg(windows)]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
         }
@@ -1002,16 +1194,34 @@ where
             .handle_state_transition(&tunnel_state_transition);
 
         let tunnel_state = match tunnel_state_transition {
-            TunnelStateTransition::Disconnected { locked_down } => TunnelState::Disconnected {
+            TunnelStateTransition::Disconnected {
+                locked_down,
+                captive_portal,
+            } => TunnelState::Disconnected {
                 location: None,
                 locked_down,
+                captive_portal,
+                paused_until: self.target_state.paused_until(),
             },
-            TunnelStateTransition::Connecting(endpoint) => TunnelState::Connecting {
-                endpoint,
-                location: self.parameters_generator.get_last_location().await,
-            },
+            TunnelStateTransition::Connecting(endpoint, captive_portal) => {
+                TunnelState::Connecting {
+                    endpoint,
+                    location: self.parameters_generator.get_last_location().await,
+                    captive_portal,
+                }
+            }
             TunnelStateTransition::Connected(endpoint) => {
                 let location = self.parameters_generator.get_last_location().await;
+                if let Some(location) = &location {
+                    // Fall back on the relay location for distance constraints until the
+                    // location of the device is known
+                    if self.relay_selector.current_location().is_none() {
+                        self.relay_selector.set_current_location(Coordinates {
+                            latitude: location.latitude,
+                            longitude: location.longitude,
+                        });
+                    }
+                }
                 TunnelState::Connected { endpoint, location }
             }
             TunnelStateTransition::Disconnecting(after_disconnect) => {
@@ -1104,6 +1314,38 @@ where
         self.location_handler.send_geo_location_request(use_ipv6);
     }
 
+    /// Logs and broadcasts the relays that were added, removed or deactivated by a relay list
+    /// update, and removes relays that no longer exist from the custom lists if enabled.
+    async fn handle_relay_list_diff(&mut self, diff: RelayListDiff) {
+        let changes = RelayListChanges::new(&diff, &self.settings);
+        if changes.is_empty() {
+            return;
+        }
+
+        for relay in &changes.removed {
+            log::warn!("Relay removed from the relay list: {relay}");
+        }
+        for relay in &changes.deactivated {
+            log::warn!("Relay deactivated: {relay}");
+        }
+        for relay in &changes.added {
+            log::info!("Relay added to the relay list: {relay}");
+        }
+
+        if self.settings.custom_lists.prune_removed_relays() {
+            self.prune_custom_lists(&changes).await;
+        }
+
+        self.event_listener
+            .notify_relay_list_changes(changes.clone());
+        self.relay_list_changes = changes;
+    }
+
+    fn handle_expiry_warning(&mut self, warning: Option<ExpiryWarning>) {
+        self.expiry_warning = warning.clone();
+        self.event_listener.notify_expiry_warning(warning);
+    }
+
     /// Receives and handles the geographical exit location received from am.i.mullvad.net, i.e. the
     /// [`InternalDaemonEvent::LocationEvent`] event.
     fn handle_location_event(&mut self, location_data: LocationEventData) {
@@ -1119,9 +1361,16 @@ where
 
         match self.tunnel_state {
             TunnelState::Disconnected {
-                ref mut location,
-                locked_down: _,
-            } => *location = Some(fetched_location),
+                ref mut location, ..
+            } => {
+                if !fetched_location.mullvad_exit_ip {
+                    self.relay_selector.set_current_location(Coordinates {
+                        latitude: fetched_location.latitude,
+                        longitude: fetched_location.longitude,
+                    });
+                }
+                *location = Some(fetched_location)
+            }
             TunnelState::Connected {
                 ref mut location, ..
             } => {
@@ -1174,6 +1423,50 @@ where
         }
     }
 
+    fn schedule_pause_end(&mut self, deadline: DateTime<Utc>) {
+        self.unschedule_pause_end();
+
+        let daemon_event_tx = self.tx.clone();
+        let (future, abort_handle) = abortable(Box::pin(async move {
+            // The deadline has passed once the remaining time is negative
+            while let Ok(remaining) = (deadline - Utc::now()).to_std() {
+                tokio::time::sleep(remaining.min(PAUSE_CHECK_INTERVAL)).await;
+            }
+            let _ = daemon_event_tx.send(InternalDaemonEvent::PauseExpired);
+        }));
+
+        tokio::spawn(future);
+        self.pause_job = Some(abort_handle);
+    }
+
+    fn unschedule_pause_end(&mut self) {
+        if let Some(job) = self.pause_job.take() {
+            job.abort();
+        }
+    }
+
+    async fn handle_pause_expired(&mut self) {
+        self.pause_job = None;
+        match self.target_state.paused_until() {
+            Some(deadline) if deadline <= Utc::now() => {
+                log::info!("Pause has ended. Reconnecting");
+                self.set_target_state(TargetState::Secured).await;
+            }
+            // The pause was cancelled or extended after the timer fired
+            _ => (),
+        }
+    }
+
+    /// Updates the pause deadline of the current tunnel state if disconnected. Other states are
+    /// updated on the next tunnel state transition.
+    fn notify_pause_changed(&mut self) {
+        if let TunnelState::Disconnected { paused_until, .. } = &mut self.tunnel_state {
+            *paused_until = self.target_state.paused_until();
+            self.event_listener
+                .notify_new_state(self.tunnel_state.clone());
+        }
+    }
+
     async fn handle_command(&mut self, command: DaemonCommand) {
         use self::DaemonCommand::*;
         if !self.state.is_running() {
@@ -1188,12 +1481,22 @@ where
         match command {
             SetTargetState(tx, state) => self.on_set_target_state(tx, state).await,
             Reconnect(tx) => self.on_reconnect(tx),
+            PauseTunnel(tx, duration) => self.on_pause_tunnel(tx, duration).await,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            SetCaptivePortalLogin(tx, timeout) => self.on_set_captive_portal_login(tx, timeout),
             GetState(tx) => self.on_get_state(tx),
             CreateNewAccount(tx) => self.on_create_new_account(tx),
             GetAccountData(tx, account_token) => self.on_get_account_data(tx, account_token),
             GetWwwAuthToken(tx) => self.on_get_www_auth_token(tx).await,
             SubmitVoucher(tx, voucher) => self.on_submit_voucher(tx, voucher),
+            GetExpiryWarning(tx) => self.on_get_expiry_warning(tx),
+            SetExpiryWarningThresholds(tx, thresholds) => {
+                self.on_set_expiry_warning_thresholds(tx, thresholds).await
+            }
             GetRelayLocations(tx) => self.on_get_relay_locations(tx),
+            GetRelayListChanges(tx) => self.on_get_relay_list_changes(tx),
+            SimulateConnectionPlan(tx, attempts) => self.on_simulate_connection_plan(tx, attempts),
+            QueryRelays(tx, query) => self.on_query_relays(tx, query),
             UpdateRelayLocations => self.on_update_relay_locations().await,
             LoginAccount(tx, account_token) => self.on_login_account(tx, account_token),
             LogoutAccount(tx) => self.on_logout_account(tx),
@@ -1207,6 +1510,18 @@ where
             ClearAccountHistory(tx) => self.on_clear_account_history(tx).await,
             SetRelaySettings(tx, update) => self.on_set_relay_settings(tx, update).await,
             SetAllowLan(tx, allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
+            #[cfg(not(target_os = "windows"))]
+            AddFirewallException(tx, exception) => {
+                self.on_add_firewall_exception(tx, exception).await
+            }
+            #[cfg(not(target_os = "windows"))]
+            RemoveFirewallException(tx, exception) => {
+                self.on_remove_firewall_exception(tx, exception).await
+            }
+            #[cfg(target_os = "linux")]
+            AddTrustedInterface(tx, iface) => self.on_add_trusted_interface(tx, iface).await,
+            #[cfg(target_os = "linux")]
+            RemoveTrustedInterface(tx, iface) => self.on_remove_trusted_interface(tx, iface).await,
             SetShowBetaReleases(tx, enabled) => self.on_set_show_beta_releases(tx, enabled).await,
             SetBlockWhenDisconnected(tx, block_when_disconnected) => {
                 self.on_set_block_when_disconnected(tx, block_when_disconnected)
@@ -1228,7 +1543,18 @@ where
                 self.on_set_relay_override(tx, relay_override).await
             }
             ClearAllRelayOverrides(tx) => self.on_clear_all_relay_overrides(tx).await,
+            AddUserRelay(tx, relay) => self.on_add_user_relay(tx, relay).await,
+            RemoveUserRelay(tx, hostname) => self.on_remove_user_relay(tx, hostname).await,
+            ImportOpenVpnProfile(tx, contents, dry_run) => {
+                self.on_import_openvpn_profile(tx, contents, dry_run).await
+            }
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
+            SetWireguardMtuDiscovery(tx, enabled) => {
+                self.on_set_wireguard_mtu_discovery(tx, enabled).await
+            }
+            SetWireguardConnectivityCheck(tx, options) => {
+                self.on_set_wireguard_connectivity_check(tx, options).await
+            }
             SetWireguardRotationInterval(tx, interval) => {
                 self.on_set_wireguard_rotation_interval(tx, interval).await
             }
@@ -1238,6 +1564,7 @@ where
             CreateCustomList(tx, name) => self.on_create_custom_list(tx, name).await,
             DeleteCustomList(tx, id) => self.on_delete_custom_list(tx, id).await,
             UpdateCustomList(tx, update) => self.on_update_custom_list(tx, update).await,
+            SetPruneCustomLists(tx, enabled) => self.on_set_prune_custom_lists(tx, enabled).await,
             GetVersionInfo(tx) => self.on_get_version_info(tx),
             AddApiAccessMethod(tx, name, enabled, access_method) => {
                 self.on_add_access_method(tx, name, enabled, access_method)
@@ -1289,6 +1616,13 @@ where
             }
             ApplyJsonSettings(tx, blob) => self.on_apply_json_settings(tx, blob).await,
             ExportJsonSettings(tx) => self.on_export_json_settings(tx),
+            CreateProfile(tx, name) => self.on_create_profile(tx, name).await,
+            GetProfiles(tx) => self.on_get_profiles(tx),
+            ApplyProfile(tx, name) => self.on_apply_profile(tx, name).await,
+            DeleteProfile(tx, name) => self.on_delete_profile(tx, name).await,
+            GetSettingsHistory(tx) => self.on_get_settings_history(tx),
+            DiffSettingsSnapshot(tx, id) => self.on_diff_settings_snapshot(tx, id).await,
+            RestoreSettingsSnapshot(tx, id) => self.on_restore_settings_snapshot(tx, id).await,
         }
     }
 
@@ -1298,6 +1632,15 @@ where
     }
 
     async fn handle_device_event(&mut self, event: AccountEvent) {
+        match &event {
+            AccountEvent::Device(
+                PrivateDeviceEvent::Login(_)
+                | PrivateDeviceEvent::Logout
+                | PrivateDeviceEvent::Revoked,
+            ) => self.expiry_monitor.reset(),
+            AccountEvent::Expiry(expiry) => self.expiry_monitor.set_expiry(*expiry),
+            _ => (),
+        }
         match &event {
             AccountEvent::Device(PrivateDeviceEvent::Login(device)) => {
                 if let Err(error) = self.account_history.set(device.account_token.clone()).await {
@@ -1435,12 +1778,16 @@ where
         let save_result = match update {
             ExcludedPathsUpdate::SetState(state) => self
                 .settings
-                .update(move |settings| settings.split_tunnel.enable_exclusions = state)
+                .update("set_split_tunnel_state", move |settings| {
+                    settings.split_tunnel.enable_exclusions = state
+                })
                 .await
                 .map_err(Error::SettingsError),
             ExcludedPathsUpdate::SetPaths(paths) => self
                 .settings
-                .update(move |settings| settings.split_tunnel.apps = paths)
+                .update("set_split_tunnel_apps", move |settings| {
+                    settings.split_tunnel.apps = paths
+                })
                 .await
                 .map_err(Error::SettingsError),
         };
@@ -1470,6 +1817,42 @@ where
         }
     }
 
+    async fn on_pause_tunnel(&mut self, tx: ResponseTx<(), Error>, duration: Duration) {
+        if self.settings.block_when_disconnected {
+            Self::oneshot_send(tx, Err(Error::PauseInLockdown), "pause_tunnel response");
+            return;
+        }
+        let deadline = match chrono::Duration::from_std(duration) {
+            Ok(pause) if !duration.is_zero() && duration <= MAX_PAUSE_DURATION => {
+                Utc::now() + pause
+            }
+            _ => {
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::InvalidPauseDuration),
+                    "pause_tunnel response",
+                );
+                return;
+            }
+        };
+
+        log::info!("Pausing the tunnel until {deadline}");
+        let was_secured =
+            *self.target_state == TargetState::Secured || self.tunnel_state.is_in_error_state();
+        self.target_state.pause(deadline).await;
+        self.schedule_pause_end(deadline);
+        if was_secured {
+            self.disconnect_tunnel();
+        }
+        self.notify_pause_changed();
+        Self::oneshot_send(tx, Ok(()), "pause_tunnel response");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn on_set_captive_portal_login(&self, tx: oneshot::Sender<()>, timeout: Option<Duration>) {
+        self.send_tunnel_command(TunnelCommand::CaptivePortalLogin(timeout, tx));
+    }
+
     fn on_get_state(&self, tx: oneshot::Sender<TunnelState>) {
         Self::oneshot_send(tx, self.tunnel_state.clone(), "current state");
     }
@@ -1557,10 +1940,68 @@ where
         });
     }
 
+    fn on_get_expiry_warning(&mut self, tx: oneshot::Sender<Option<ExpiryWarning>>) {
+        Self::oneshot_send(tx, self.expiry_warning.clone(), "expiry warning");
+    }
+
+    async fn on_set_expiry_warning_thresholds(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        thresholds: Vec<Duration>,
+    ) {
+        if !mullvad_types::account::is_valid_expiry_warning_thresholds(&thresholds) {
+            Self::oneshot_send(
+                tx,
+                Err(Error::InvalidExpiryWarningThresholds),
+                "set_expiry_warning_thresholds response",
+            );
+            return;
+        }
+        match self
+            .settings
+            .update("set_expiry_warning_thresholds", move |settings| {
+                settings.expiry_warning_thresholds = thresholds
+            })
+            .await
+        {
+            Ok(_) => Self::oneshot_send(tx, Ok(()), "set_expiry_warning_thresholds response"),
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(e)),
+                    "set_expiry_warning_thresholds response",
+                );
+            }
+        }
+    }
+
     fn on_get_relay_locations(&mut self, tx: oneshot::Sender<RelayList>) {
         Self::oneshot_send(tx, self.relay_selector.get_locations(), "relay locations");
     }
 
+    fn on_get_relay_list_changes(&mut self, tx: oneshot::Sender<RelayListChanges>) {
+        Self::oneshot_send(tx, self.relay_list_changes.clone(), "relay list changes");
+    }
+
+    fn on_simulate_connection_plan(
+        &mut self,
+        tx: oneshot::Sender<ConnectionPlanSimulation>,
+        attempts: u32,
+    ) {
+        let simulation = ConnectionPlanSimulation {
+            plan: self.settings.connection_plan.clone().unwrap_or_default(),
+            is_default: self.settings.connection_plan.is_none(),
+            attempts: self.relay_selector.simulate_connection_plan(attempts),
+        };
+        Self::oneshot_send(tx, simulation, "connection plan simulation");
+    }
+
+    fn on_query_relays(&mut self, tx: oneshot::Sender<RelayQueryResult>, query: RelayQuery) {
+        let result = self.relay_selector.query_relays(&query);
+        Self::oneshot_send(tx, result, "relay query result");
+    }
+
     async fn on_update_relay_locations(&mut self) {
         self.relay_list_updater.update().await;
     }
@@ -1750,6 +2191,16 @@ where
             last_error = Err(Error::FactoryResetError("Failed to reset settings"));
         }
 
+        if let Err(error) = self.profiles.clear().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to clear settings profiles")
+            );
+            last_error = Err(Error::FactoryResetError(
+                "Failed to clear settings profiles",
+            ));
+        }
+
         // Shut the daemon down.
         self.trigger_shutdown_event(false);
 
@@ -1955,7 +2406,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.set_relay_settings(update))
+            .update("set_relay_settings", move |settings| {
+                settings.set_relay_settings(update)
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -1975,7 +2428,9 @@ where
     async fn on_set_allow_lan(&mut self, tx: ResponseTx<(), settings::Error>, allow_lan: bool) {
         match self
             .settings
-            .update(move |settings| settings.allow_lan = allow_lan)
+            .update("set_allow_lan", move |settings| {
+                settings.allow_lan = allow_lan
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -1997,6 +2452,164 @@ where
         }
     }
 
+    #[cfg(not(target_os = "windows"))]
+    async fn on_add_firewall_exception(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        exception: FirewallException,
+    ) {
+        if let Err(error) = exception.validate() {
+            Self::oneshot_send(
+                tx,
+                Err(Error::InvalidFirewallException(error)),
+                "add_firewall_exception response",
+            );
+            return;
+        }
+        self.update_firewall_exceptions(tx, "add_firewall_exception", move |exceptions| {
+            if !exceptions.contains(&exception) {
+                exceptions.push(exception);
+            }
+        })
+        .await;
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    async fn on_remove_firewall_exception(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        exception: FirewallException,
+    ) {
+        if !self.settings.firewall_exceptions.contains(&exception) {
+            Self::oneshot_send(
+                tx,
+                Err(Error::FirewallExceptionNotFound),
+                "remove_firewall_exception response",
+            );
+            return;
+        }
+        self.update_firewall_exceptions(tx, "remove_firewall_exception", move |exceptions| {
+            exceptions.retain(|other| other != &exception)
+        })
+        .await;
+    }
+
+    /// Changes the firewall exceptions in the settings and passes them on to the tunnel state
+    /// machine. A response is sent once the firewall policy has been updated.
+    #[cfg(not(target_os = "windows"))]
+    async fn update_firewall_exceptions(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        cause: &str,
+        update: impl FnOnce(&mut Vec<FirewallException>),
+    ) {
+        const RESPONSE: &str = "firewall exceptions response";
+        match self
+            .settings
+            .update(cause, move |settings| {
+                update(&mut settings.firewall_exceptions)
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::FirewallExceptions(
+                        self.settings.firewall_exceptions.clone(),
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), RESPONSE);
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), RESPONSE);
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(Error::SettingsError(e)), RESPONSE);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_add_trusted_interface(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        iface: TrustedInterface,
+    ) {
+        // The firewall checks this again whenever it is applied, since interfaces come and go
+        let uplinks = talpid_core::firewall::uplink_interfaces();
+        if let Some(uplink) = uplinks.into_iter().find(|uplink| iface.matches(uplink)) {
+            Self::oneshot_send(
+                tx,
+                Err(Error::UntrustableInterface(uplink)),
+                "add_trusted_interface response",
+            );
+            return;
+        }
+        self.update_trusted_interfaces(tx, "add_trusted_interface", move |trusted_interfaces| {
+            if !trusted_interfaces.contains(&iface) {
+                trusted_interfaces.push(iface);
+            }
+        })
+        .await;
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_remove_trusted_interface(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        iface: TrustedInterface,
+    ) {
+        if !self.settings.trusted_interfaces.contains(&iface) {
+            Self::oneshot_send(
+                tx,
+                Err(Error::TrustedInterfaceNotFound),
+                "remove_trusted_interface response",
+            );
+            return;
+        }
+        self.update_trusted_interfaces(tx, "remove_trusted_interface", move |trusted_interfaces| {
+            trusted_interfaces.retain(|other| other != &iface)
+        })
+        .await;
+    }
+
+    /// Changes the trusted interfaces in the settings and passes them on to the tunnel state
+    /// machine. A response is sent once the firewall policy and routes have been updated.
+    #[cfg(target_os = "linux")]
+    async fn update_trusted_interfaces(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        cause: &str,
+        update: impl FnOnce(&mut Vec<TrustedInterface>),
+    ) {
+        const RESPONSE: &str = "trusted interfaces response";
+        match self
+            .settings
+            .update(cause, move |settings| {
+                update(&mut settings.trusted_interfaces)
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::TrustedInterfaces(
+                        self.settings.trusted_interfaces.clone(),
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), RESPONSE);
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), RESPONSE);
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(Error::SettingsError(e)), RESPONSE);
+            }
+        }
+    }
+
     async fn on_set_show_beta_releases(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2004,7 +2617,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.show_beta_releases = enabled)
+            .update("set_show_beta_releases", move |settings| {
+                settings.show_beta_releases = enabled
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2028,7 +2643,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.block_when_disconnected = block_when_disconnected)
+            .update("set_block_when_disconnected", move |settings| {
+                settings.block_when_disconnected = block_when_disconnected
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2057,7 +2674,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.auto_connect = auto_connect)
+            .update("set_auto_connect", move |settings| {
+                settings.auto_connect = auto_connect
+            })
             .await
         {
             Ok(_settings_changed) => {
@@ -2077,7 +2696,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.tunnel_options.openvpn.mssfix = mssfix)
+            .update("set_openvpn_mssfix", move |settings| {
+                settings.tunnel_options.openvpn.mssfix = mssfix
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2113,7 +2734,9 @@ where
 
         match self
             .settings
-            .update(move |settings| settings.bridge_settings = new_settings)
+            .update("set_bridge_settings", move |settings| {
+                settings.bridge_settings = new_settings
+            })
             .await
         {
             Ok(settings_changes) => {
@@ -2143,7 +2766,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.obfuscation_settings = new_settings)
+            .update("set_obfuscation_settings", move |settings| {
+                settings.obfuscation_settings = new_settings
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2169,7 +2794,9 @@ where
     ) {
         let result = match self
             .settings
-            .update(move |settings| settings.bridge_state = bridge_state)
+            .update("set_bridge_state", move |settings| {
+                settings.bridge_state = bridge_state
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2193,7 +2820,9 @@ where
     async fn on_set_enable_ipv6(&mut self, tx: ResponseTx<(), settings::Error>, enable_ipv6: bool) {
         match self
             .settings
-            .update(|settings| settings.tunnel_options.generic.enable_ipv6 = enable_ipv6)
+            .update("set_enable_ipv6", |settings| {
+                settings.tunnel_options.generic.enable_ipv6 = enable_ipv6
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2217,7 +2846,7 @@ where
     ) {
         match self
             .settings
-            .update(|settings| {
+            .update("set_quantum_resistant_tunnel", |settings| {
                 settings.tunnel_options.wireguard.quantum_resistant = quantum_resistant
             })
             .await
@@ -2244,7 +2873,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.tunnel_options.dns_options = dns_options)
+            .update("set_dns_options", move |settings| {
+                settings.tunnel_options.dns_options = dns_options
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2276,7 +2907,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.set_relay_override(relay_override))
+            .update("set_relay_override", move |settings| {
+                settings.set_relay_override(relay_override)
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2295,7 +2928,9 @@ where
     async fn on_clear_all_relay_overrides(&mut self, tx: ResponseTx<(), settings::Error>) {
         match self
             .settings
-            .update(move |settings| settings.relay_overrides.clear())
+            .update("clear_all_relay_overrides", move |settings| {
+                settings.relay_overrides.clear()
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2311,6 +2946,71 @@ where
         }
     }
 
+    async fn on_add_user_relay(&mut self, tx: ResponseTx<(), Error>, relay: UserRelay) {
+        let result = self.add_user_relay(relay).await;
+        Self::oneshot_send(tx, result, "add_user_relay response");
+    }
+
+    async fn on_remove_user_relay(&mut self, tx: ResponseTx<(), Error>, hostname: String) {
+        let result = self.remove_user_relay(hostname).await;
+        Self::oneshot_send(tx, result, "remove_user_relay response");
+    }
+
+    async fn on_import_openvpn_profile(
+        &mut self,
+        tx: ResponseTx<ProfileImport, Error>,
+        contents: String,
+        dry_run: bool,
+    ) {
+        let profile = match openvpn_profile::parse(&contents) {
+            Ok(profile) => profile,
+            Err(error) => {
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::OpenVpnProfile(error)),
+                    "import_openvpn_profile response",
+                );
+                return;
+            }
+        };
+        for directive in &profile.unsupported {
+            log::warn!("Ignoring OpenVPN profile directive: {directive}");
+        }
+        let import = ProfileImport::from(profile);
+        if dry_run {
+            Self::oneshot_send(tx, Ok(import), "import_openvpn_profile response");
+            return;
+        }
+
+        let applied_import = import.clone();
+        match self
+            .settings
+            .update("import_openvpn_profile", move |settings| {
+                settings.set_relay_settings(RelaySettings::CustomTunnelEndpoint(
+                    applied_import.endpoint.clone(),
+                ));
+                applied_import.apply_options(&mut settings.tunnel_options.openvpn);
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(import), "import_openvpn_profile response");
+                if settings_changed {
+                    log::info!("Initiating tunnel restart because an OpenVPN profile was imported");
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(e)),
+                    "import_openvpn_profile response",
+                );
+            }
+        }
+    }
+
     async fn on_set_wireguard_mtu(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2318,7 +3018,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.tunnel_options.wireguard.mtu = mtu)
+            .update("set_wireguard_mtu", move |settings| {
+                settings.tunnel_options.wireguard.mtu = mtu
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2339,6 +3041,78 @@ where
         }
     }
 
+    async fn on_set_wireguard_mtu_discovery(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        enabled: bool,
+    ) {
+        match self
+            .settings
+            .update("set_wireguard_mtu_discovery", move |settings| {
+                settings.tunnel_options.wireguard.mtu_discovery = enabled
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_mtu_discovery response");
+                if settings_changed {
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard MTU discovery setting changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_mtu_discovery response");
+            }
+        }
+    }
+
+    async fn on_set_wireguard_connectivity_check(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        options: ConnectivityCheckOptions,
+    ) {
+        if let Err(error) = options.validate() {
+            Self::oneshot_send(
+                tx,
+                Err(Error::InvalidConnectivityCheck(error)),
+                "set_wireguard_connectivity_check response",
+            );
+            return;
+        }
+        match self
+            .settings
+            .update("set_wireguard_connectivity_check", move |settings| {
+                settings.tunnel_options.wireguard.connectivity_check = options
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_connectivity_check response");
+                if settings_changed {
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard connectivity check settings changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(e)),
+                    "set_wireguard_connectivity_check response",
+                );
+            }
+        }
+    }
+
     async fn on_set_wireguard_rotation_interval(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2346,7 +3120,9 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.tunnel_options.wireguard.rotation_interval = interval)
+            .update("set_wireguard_rotation_interval", move |settings| {
+                settings.tunnel_options.wireguard.rotation_interval = interval
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2416,6 +3192,32 @@ where
         Self::oneshot_send(tx, result, "update_custom_list response");
     }
 
+    async fn on_set_prune_custom_lists(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        enabled: bool,
+    ) {
+        match self
+            .settings
+            .update("set_prune_custom_lists", move |settings| {
+                settings.custom_lists.set_prune_removed_relays(enabled)
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_prune_custom_lists response");
+                if settings_changed {
+                    self.event_listener
+                        .notify_settings(self.settings.to_settings());
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_prune_custom_lists response");
+            }
+        }
+    }
+
     async fn on_add_access_method(
         &mut self,
         tx: ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -2647,7 +3449,9 @@ where
         tx: ResponseTx<(), settings::patch::Error>,
         blob: String,
     ) {
-        let result = settings::patch::merge_validate_patch(&mut self.settings, &blob).await;
+        let result =
+            settings::patch::merge_validate_patch(&mut self.settings, &mut self.profiles, &blob)
+                .await;
         if result.is_ok() {
             self.reconnect_tunnel();
         }
@@ -2655,14 +3459,200 @@ where
     }
 
     fn on_export_json_settings(&mut self, tx: ResponseTx<String, settings::patch::Error>) {
-        let result = settings::patch::export_settings(&self.settings);
+        let result = settings::patch::export_settings(&self.settings, self.profiles.profiles());
         Self::oneshot_send(tx, result, "export_json_settings response");
     }
 
+    async fn on_create_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let profile = Profile::from_settings(name, &self.settings);
+        let result = self.profiles.create(profile).await;
+        if let Err(error) = &result {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to create profile")
+            );
+        }
+        Self::oneshot_send(tx, result, "create_profile response");
+    }
+
+    fn on_get_profiles(&mut self, tx: oneshot::Sender<Vec<Profile>>) {
+        Self::oneshot_send(
+            tx,
+            self.profiles.profiles().to_vec(),
+            "get_profiles response",
+        );
+    }
+
+    async fn on_apply_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let profile = match self.profiles.get(&name) {
+            Ok(profile) => profile.clone(),
+            Err(error) => {
+                Self::oneshot_send(tx, Err(error), "apply_profile response");
+                return;
+            }
+        };
+        let prev_settings = self.settings.to_settings();
+
+        // All settings in the profile are saved in a single update
+        match self
+            .settings
+            .update("apply_profile", move |settings| profile.apply_to(settings))
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.apply_replaced_settings(&prev_settings).await;
+                    log::info!("Initiating tunnel restart because profile \"{name}\" was applied");
+                    self.reconnect_tunnel();
+                }
+                Self::oneshot_send(tx, Ok(()), "apply_profile response");
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(
+                    tx,
+                    Err(settings::profiles::Error::Settings(e)),
+                    "apply_profile response",
+                );
+            }
+        }
+    }
+
+    async fn on_delete_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let result = self.profiles.remove(&name).await;
+        Self::oneshot_send(tx, result, "delete_profile response");
+    }
+
+    fn on_get_settings_history(&self, tx: oneshot::Sender<Vec<SettingsSnapshotInfo>>) {
+        Self::oneshot_send(
+            tx,
+            self.settings.snapshots(),
+            "get_settings_history response",
+        );
+    }
+
+    async fn on_diff_settings_snapshot(
+        &self,
+        tx: ResponseTx<Vec<SettingsChange>, settings::Error>,
+        id: u32,
+    ) {
+        let result = self.settings.diff_snapshot(id).await;
+        Self::oneshot_send(tx, result, "diff_settings_snapshot response");
+    }
+
+    async fn on_restore_settings_snapshot(&mut self, tx: ResponseTx<(), settings::Error>, id: u32) {
+        let prev_settings = self.settings.to_settings();
+        match self.settings.restore_snapshot(id).await {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.apply_replaced_settings(&prev_settings).await;
+                    log::info!(
+                        "Initiating tunnel restart because settings snapshot {id} was restored"
+                    );
+                    self.reconnect_tunnel();
+                }
+                Self::oneshot_send(tx, Ok(()), "restore_settings_snapshot response");
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to restore settings"));
+                Self::oneshot_send(tx, Err(e), "restore_settings_snapshot response");
+            }
+        }
+    }
+
+    /// Passes settings that other parts of the daemon keep track of to them, after several
+    /// settings were replaced at once. The tunnel still has to be reconnected to apply the rest.
+    async fn apply_replaced_settings(&mut self, prev_settings: &Settings) {
+        let settings = self.settings.to_settings();
+        for setting in settings::replaced_settings(prev_settings, &settings) {
+            match setting {
+                ReplacedSetting::AllowLan(allow_lan) => {
+                    let (lan_tx, _) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::AllowLan(allow_lan, lan_tx));
+                }
+                ReplacedSetting::FirewallExceptions(exceptions) => {
+                    let (exceptions_tx, _) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::FirewallExceptions(
+                        exceptions,
+                        exceptions_tx,
+                    ));
+                }
+                #[cfg(target_os = "linux")]
+                ReplacedSetting::TrustedInterfaces(interfaces) => {
+                    let (trusted_tx, _) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::TrustedInterfaces(
+                        interfaces, trusted_tx,
+                    ));
+                }
+                ReplacedSetting::BlockWhenDisconnected(block_when_disconnected) => {
+                    let (block_tx, _) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
+                        block_when_disconnected,
+                        block_tx,
+                    ));
+                }
+                ReplacedSetting::DnsOptions(dns_options) => {
+                    let resolvers = dns::addresses_from_options(&dns_options);
+                    let (dns_tx, _) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::Dns(resolvers, dns_tx));
+                }
+                ReplacedSetting::AccessMethods(access_methods) => {
+                    let handle = self.connection_modes_handler.clone();
+                    tokio::spawn(async move {
+                        let _ = handle.update_access_methods(access_methods).await;
+                    });
+                }
+                ReplacedSetting::RotationInterval(interval) => {
+                    if let Err(error) = self
+                        .account_manager
+                        .set_rotation_interval(interval.unwrap_or_default())
+                        .await
+                    {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to update rotation interval")
+                        );
+                    }
+                }
+                #[cfg(windows)]
+                ReplacedSetting::SplitTunnel(split_tunnel) => {
+                    let apps = if split_tunnel.enable_exclusions {
+                        split_tunnel.apps.into_iter().map(OsString::from).collect()
+                    } else {
+                        vec![]
+                    };
+                    let (result_tx, result_rx) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::SetExcludedApps(result_tx, apps));
+                    tokio::spawn(async move {
+                        if let Ok(Err(error)) = result_rx.await {
+                            log::error!(
+                                "{}",
+                                error.display_chain_with_msg("Failed to set excluded apps list")
+                            );
+                        }
+                    });
+                }
+            }
+        }
+    }
+
     /// Set the target state of the client. If it changed trigger the operations needed to
-    /// progress towards that state.
+    /// progress towards that state. This ends any ongoing pause.
     /// Returns a bool representing whether or not a state change was initiated.
     async fn set_target_state(&mut self, new_state: TargetState) -> bool {
+        self.unschedule_pause_end();
         if new_state != *self.target_state || self.tunnel_state.is_in_error_state() {
             log::debug!("Target state {:?} => {:?}", *self.target_state, new_state);
 
@@ -2674,6 +3664,10 @@ where
             }
             true
         } else {
+            if self.target_state.clear_pause().await {
+                log::info!("Pause cancelled");
+                self.notify_pause_changed();
+            }
             false
         }
     }
@@ -2754,6 +3748,8 @@ fn new_selector_config(settings: &Settings) -> SelectorConfig {
         obfuscation_settings: settings.obfuscation_settings.clone(),
         custom_lists: settings.custom_lists.clone(),
         relay_overrides: settings.relay_overrides.clone(),
+        user_relays: settings.user_relays.clone(),
+        connection_plan: settings.connection_plan.clone().unwrap_or_default(),
     }
 }
 
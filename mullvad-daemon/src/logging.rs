@@ -2,8 +2,11 @@ use fern::{
     colors::{Color, ColoredLevelConfig},
     Output,
 };
-use std::{fmt, io, path::PathBuf};
+use mullvad_types::logging::{LogFilter, LogFormat};
+use once_cell::sync::Lazy;
+use std::{fmt, io, path::PathBuf, sync::RwLock};
 use talpid_core::logging::rotate_log;
+use tokio::sync::{broadcast, mpsc};
 
 #[derive(err_derive::Error, Debug)]
 pub enum Error {
@@ -62,21 +65,28 @@ const LINE_SEPARATOR: &str = "\r\n";
 
 const DATE_TIME_FORMAT_STR: &str = "[%Y-%m-%d %H:%M:%S%.3f]";
 
+/// Number of log records that can be buffered for a streaming client before records are dropped.
+const LOG_STREAM_CAPACITY: usize = 1024;
+
+/// The log filter, which can be changed while the daemon is running.
+static LOG_FILTER: Lazy<RwLock<LogFilter>> =
+    Lazy::new(|| RwLock::new(LogFilter::new(log::LevelFilter::Info)));
+
+/// Broadcasts all log records that pass the filter to streaming clients.
+static LOG_STREAM: Lazy<broadcast::Sender<StreamedRecord>> =
+    Lazy::new(|| broadcast::channel(LOG_STREAM_CAPACITY).0);
+
 pub fn init_logger(
     log_level: log::LevelFilter,
     log_file: Option<&PathBuf>,
     output_timestamp: bool,
 ) -> Result<(), Error> {
-    let mut top_dispatcher = fern::Dispatch::new().level(log_level);
-    for silenced_crate in WARNING_SILENCED_CRATES {
-        top_dispatcher = top_dispatcher.level_for(*silenced_crate, log::LevelFilter::Error);
-    }
-    for silenced_crate in SILENCED_CRATES {
-        top_dispatcher = top_dispatcher.level_for(*silenced_crate, log::LevelFilter::Warn);
-    }
-    for silenced_crate in SLIGHTLY_SILENCED_CRATES {
-        top_dispatcher = top_dispatcher.level_for(*silenced_crate, one_level_quieter(log_level));
-    }
+    *LOG_FILTER.write().unwrap() = LogFilter::new(log_level);
+    let mut top_dispatcher =
+        fern::Dispatch::new().filter(|metadata| metadata.level() <= level_for(metadata.target()));
+
+    let stream_dispatcher = fern::Dispatch::new().chain(Output::call(stream_record));
+    top_dispatcher = top_dispatcher.chain(stream_dispatcher);
 
     let stdout_formatter = Formatter {
         output_timestamp,
@@ -111,9 +121,136 @@ pub fn init_logger(
         top_dispatcher = top_dispatcher.chain(logger);
     }
     top_dispatcher.apply().map_err(Error::SetLoggerError)?;
+    update_max_level();
     Ok(())
 }
 
+/// Returns the current log filter. Crates that are silenced by default are not included unless
+/// their level has been changed.
+pub fn log_filter() -> LogFilter {
+    LOG_FILTER.read().unwrap().clone()
+}
+
+/// Sets the level used for all modules that have no level of their own.
+pub fn set_log_level(level: log::LevelFilter) {
+    LOG_FILTER.write().unwrap().level = level;
+    update_max_level();
+    log::info!("Log level set to {level}");
+}
+
+/// Sets the level for a module and its submodules. If `level` is `None`, the module uses the
+/// default level again.
+pub fn set_module_log_level(module: String, level: Option<log::LevelFilter>) {
+    {
+        let mut filter = LOG_FILTER.write().unwrap();
+        match level {
+            Some(level) => filter.modules.insert(module.clone(), level),
+            None => filter.modules.remove(&module),
+        };
+    }
+    update_max_level();
+    match level {
+        Some(level) => log::info!("Log level for {module} set to {level}"),
+        None => log::info!("Log level for {module} reset"),
+    }
+}
+
+/// Returns the level to log at for the given target.
+fn level_for(target: &str) -> log::LevelFilter {
+    let filter = LOG_FILTER.read().unwrap();
+    if let Some(level) = filter.module_level(target) {
+        return level;
+    }
+    let crate_name = target.split("::").next().unwrap_or(target);
+    if WARNING_SILENCED_CRATES.contains(&crate_name) {
+        log::LevelFilter::Error
+    } else if SILENCED_CRATES.contains(&crate_name) {
+        log::LevelFilter::Warn
+    } else if SLIGHTLY_SILENCED_CRATES.contains(&crate_name) {
+        one_level_quieter(filter.level)
+    } else {
+        filter.level
+    }
+}
+
+/// Lets the `log` macros skip records that no module would log, without consulting the filter.
+fn update_max_level() {
+    let filter = LOG_FILTER.read().unwrap();
+    let max_level = filter
+        .modules
+        .values()
+        .copied()
+        .chain([filter.level, log::LevelFilter::Warn])
+        .max()
+        .unwrap_or(filter.level);
+    log::set_max_level(max_level);
+}
+
+/// A log record that is sent to streaming clients.
+#[derive(Clone)]
+struct StreamedRecord {
+    time: chrono::DateTime<chrono::Local>,
+    level: log::Level,
+    target: String,
+    message: String,
+}
+
+impl StreamedRecord {
+    fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => format!(
+                "{}[{}][{}] {}",
+                self.time.format(DATE_TIME_FORMAT_STR),
+                self.target,
+                self.level,
+                self.message,
+            ),
+            LogFormat::Json => serde_json::json!({
+                "time": self.time.to_rfc3339(),
+                "level": self.level.as_str(),
+                "target": self.target,
+                "message": self.message,
+            })
+            .to_string(),
+        }
+    }
+}
+
+fn stream_record(record: &log::Record<'_>) {
+    if LOG_STREAM.receiver_count() == 0 {
+        return;
+    }
+    let _ = LOG_STREAM.send(StreamedRecord {
+        time: chrono::Local::now(),
+        level: record.level(),
+        target: record.target().to_owned(),
+        message: record.args().to_string(),
+    });
+}
+
+/// Returns a channel that receives all log records from now on, formatted as lines. The records
+/// are forwarded until the receiver is dropped. If the receiver cannot keep up, records are
+/// dropped and a line saying so is sent instead.
+pub fn subscribe(format: LogFormat) -> mpsc::UnboundedReceiver<String> {
+    let mut records = LOG_STREAM.subscribe();
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let line = match records.recv().await {
+                Ok(record) => record.format(format),
+                Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                    format!("[{dropped} log records were dropped]")
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
 fn one_level_quieter(level: log::LevelFilter) -> log::LevelFilter {
     use log::LevelFilter::*;
     match level {
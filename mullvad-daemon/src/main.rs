@@ -48,7 +48,7 @@ fn main() {
 
 fn init_daemon_logging(config: &cli::Config) -> Result<Option<PathBuf>, String> {
     #[cfg(target_os = "linux")]
-    if config.initialize_firewall_and_exit {
+    if config.initialize_firewall_and_exit || config.export_firewall_and_exit.is_some() {
         init_early_boot_logging(config);
         return Ok(None);
     }
@@ -127,6 +127,11 @@ async fn run_platform(config: &cli::Config, log_dir: Option<PathBuf>) -> Result<
             .await
             .map_err(|err| format!("{err}"));
     }
+    if let Some(path) = &config.export_firewall_and_exit {
+        return crate::early_boot_firewall::export_firewall(path)
+            .await
+            .map_err(|err| err.display_chain());
+    }
     run_standalone(log_dir).await
 }
 
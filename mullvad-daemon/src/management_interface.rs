@@ -1,6 +1,7 @@
-use crate::{account_history, device, DaemonCommand, DaemonCommandSender, EventListener};
+use crate::{account_history, device, logging, DaemonCommand, DaemonCommandSender, EventListener};
 use futures::{
     channel::{mpsc, oneshot},
+    stream::BoxStream,
     StreamExt,
 };
 use mullvad_api::{rest::Error as RestError, StatusCode};
@@ -17,8 +18,10 @@ use mullvad_types::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
     },
     relay_list::RelayList,
+    relay_query::RelayQuery,
     settings::Settings,
     states::{TargetState, TunnelState},
+    user_relay::UserRelay,
     version,
     wireguard::{RotationInterval, RotationIntervalError},
 };
@@ -30,6 +33,10 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
+#[cfg(not(target_os = "windows"))]
+use talpid_types::net::firewall_exception::FirewallException;
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
 use talpid_types::ErrorExt;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
@@ -53,10 +60,14 @@ type EventsListenerSender = tokio::sync::mpsc::UnboundedSender<Result<types::Dae
 const INVALID_VOUCHER_MESSAGE: &str = "This voucher code is invalid";
 const USED_VOUCHER_MESSAGE: &str = "This voucher code has already been used";
 
+/// Upper limit on the number of attempts that can be simulated in a single request.
+const MAX_SIMULATED_ATTEMPTS: u32 = 1000;
+
 #[mullvad_management_interface::async_trait]
 impl ManagementService for ManagementServiceImpl {
     type GetSplitTunnelProcessesStream = UnboundedReceiverStream<Result<i32, Status>>;
     type EventsListenStream = EventsListenerReceiver;
+    type StreamLogsStream = BoxStream<'static, Result<String, Status>>;
 
     // Control and get the tunnel state
     //
@@ -87,6 +98,56 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(reconnect_issued))
     }
 
+    async fn pause_tunnel(&self, request: Request<types::Duration>) -> ServiceResult<()> {
+        let duration = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative pause duration"))?;
+        log::debug!("pause_tunnel({:?})", duration);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::PauseTunnel(tx, duration))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn start_captive_portal_login(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<()> {
+        let timeout = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative login duration"))?;
+        let timeout = if timeout.is_zero() {
+            talpid_core::tunnel_state_machine::DEFAULT_LOGIN_TIMEOUT
+        } else {
+            timeout
+        };
+        log::debug!("start_captive_portal_login({:?})", timeout);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetCaptivePortalLogin(tx, Some(timeout)))?;
+        self.wait_for_result(rx).await?;
+        Ok(Response::new(()))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    async fn start_captive_portal_login(&self, _: Request<types::Duration>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "captive portal logins are not supported on this platform",
+        ))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn cancel_captive_portal_login(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("cancel_captive_portal_login");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetCaptivePortalLogin(tx, None))?;
+        self.wait_for_result(rx).await?;
+        Ok(Response::new(()))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    async fn cancel_captive_portal_login(&self, _: Request<()>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "captive portal logins are not supported on this platform",
+        ))
+    }
+
     async fn get_tunnel_state(&self, _: Request<()>) -> ServiceResult<types::TunnelState> {
         log::debug!("get_tunnel_state");
         let (tx, rx) = oneshot::channel();
@@ -157,6 +218,51 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(self.wait_for_result(rx).await?))
     }
 
+    // Logging
+    //
+
+    async fn get_log_filter(&self, request: Request<()>) -> ServiceResult<types::LogFilter> {
+        log::debug!("get_log_filter");
+        mullvad_management_interface::authorize_privileged(&request)?;
+        Ok(Response::new(
+            types::LogFilter::from(&logging::log_filter()),
+        ))
+    }
+
+    async fn set_log_level(&self, request: Request<types::LogLevelUpdate>) -> ServiceResult<()> {
+        mullvad_management_interface::authorize_privileged(&request)?;
+        let update = request.into_inner();
+        log::debug!("set_log_level({:?})", update);
+        let level = update
+            .level
+            .map(types::try_log_level_from_i32)
+            .transpose()
+            .map_err(map_protobuf_type_err)?;
+        match (update.module, level) {
+            (Some(module), level) => logging::set_module_log_level(module, level),
+            (None, Some(level)) => logging::set_log_level(level),
+            (None, None) => {
+                return Err(Status::invalid_argument(
+                    "a level is required when no module is given",
+                ))
+            }
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn stream_logs(
+        &self,
+        request: Request<types::LogStreamOptions>,
+    ) -> ServiceResult<Self::StreamLogsStream> {
+        mullvad_management_interface::authorize_privileged(&request)?;
+        let options = request.into_inner();
+        log::debug!("stream_logs");
+        let format =
+            types::try_log_format_from_i32(options.format).map_err(map_protobuf_type_err)?;
+        let lines = UnboundedReceiverStream::new(logging::subscribe(format));
+        Ok(Response::new(lines.map(Ok).boxed()))
+    }
+
     // Relays and tunnel constraints
     //
 
@@ -191,6 +297,52 @@ impl ManagementService for ManagementServiceImpl {
             .map(|relays| Response::new(types::RelayList::from(relays)))
     }
 
+    async fn get_relay_list_changes(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::RelayListChanges> {
+        log::debug!("get_relay_list_changes");
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRelayListChanges(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|changes| Response::new(types::RelayListChanges::from(changes)))
+    }
+
+    async fn simulate_connection_plan(
+        &self,
+        request: Request<u32>,
+    ) -> ServiceResult<types::ConnectionPlanSimulation> {
+        let attempts = request.into_inner();
+        log::debug!("simulate_connection_plan({attempts})");
+        if attempts > MAX_SIMULATED_ATTEMPTS {
+            return Err(Status::invalid_argument(format!(
+                "cannot simulate more than {MAX_SIMULATED_ATTEMPTS} attempts"
+            )));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SimulateConnectionPlan(tx, attempts))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|simulation| Response::new(types::ConnectionPlanSimulation::from(simulation)))
+    }
+
+    async fn query_relays(
+        &self,
+        request: Request<types::RelayQuery>,
+    ) -> ServiceResult<types::RelayQueryResult> {
+        let query = RelayQuery::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("query_relays({:?})", query);
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::QueryRelays(tx, query))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|result| Response::new(types::RelayQueryResult::from(result)))
+    }
+
     async fn set_bridge_settings(
         &self,
         request: Request<types::BridgeSettings>,
@@ -251,6 +403,94 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    #[cfg(not(target_os = "windows"))]
+    async fn add_firewall_exception(
+        &self,
+        request: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        let exception =
+            FirewallException::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("add_firewall_exception({exception})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddFirewallException(tx, exception))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+    #[cfg(target_os = "windows")]
+    async fn add_firewall_exception(
+        &self,
+        _: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "firewall exceptions are not supported on this platform",
+        ))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    async fn remove_firewall_exception(
+        &self,
+        request: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        let exception =
+            FirewallException::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("remove_firewall_exception({exception})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveFirewallException(tx, exception))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+    #[cfg(target_os = "windows")]
+    async fn remove_firewall_exception(
+        &self,
+        _: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "firewall exceptions are not supported on this platform",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn add_trusted_interface(&self, request: Request<String>) -> ServiceResult<()> {
+        let iface = TrustedInterface::new(request.into_inner())
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
+        log::debug!("add_trusted_interface({iface})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddTrustedInterface(tx, iface))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn add_trusted_interface(&self, _: Request<String>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "trusted interfaces are not supported on this platform",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn remove_trusted_interface(&self, request: Request<String>) -> ServiceResult<()> {
+        let iface = TrustedInterface::new(request.into_inner())
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
+        log::debug!("remove_trusted_interface({iface})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveTrustedInterface(tx, iface))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn remove_trusted_interface(&self, _: Request<String>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "trusted interfaces are not supported on this platform",
+        ))
+    }
+
     async fn set_show_beta_releases(&self, request: Request<bool>) -> ServiceResult<()> {
         let enabled = request.into_inner();
         log::debug!("set_show_beta_releases({})", enabled);
@@ -305,6 +545,29 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_wireguard_mtu_discovery(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enabled = request.into_inner();
+        log::debug!("set_wireguard_mtu_discovery({})", enabled);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardMtuDiscovery(tx, enabled))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_wireguard_connectivity_check(
+        &self,
+        request: Request<types::ConnectivityCheckOptions>,
+    ) -> ServiceResult<()> {
+        let options =
+            talpid_types::net::wireguard::ConnectivityCheckOptions::try_from(request.into_inner())
+                .map_err(map_protobuf_type_err)?;
+        log::debug!("set_wireguard_connectivity_check({options:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardConnectivityCheck(tx, options))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
     async fn set_enable_ipv6(&self, request: Request<bool>) -> ServiceResult<()> {
         let enable_ipv6 = request.into_inner();
         log::debug!("set_enable_ipv6({})", enable_ipv6);
@@ -365,6 +628,47 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn add_user_relay(&self, request: Request<types::UserRelay>) -> ServiceResult<()> {
+        let relay = UserRelay::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("add_user_relay({})", relay.hostname);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddUserRelay(tx, relay))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn remove_user_relay(&self, request: Request<String>) -> ServiceResult<()> {
+        let hostname = request.into_inner();
+        log::debug!("remove_user_relay({hostname})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveUserRelay(tx, hostname))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn import_openvpn_profile(
+        &self,
+        request: Request<types::OpenvpnProfile>,
+    ) -> ServiceResult<types::OpenvpnProfileImport> {
+        log::debug!("import_openvpn_profile");
+        let profile = request.into_inner();
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportOpenVpnProfile(
+            tx,
+            profile.contents,
+            profile.dry_run,
+        ))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(types::OpenvpnProfileImport::from)
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     // Account management
     //
 
@@ -466,6 +770,27 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn get_expiry_warning(&self, _: Request<()>) -> ServiceResult<types::ExpiryWarningState> {
+        log::debug!("get_expiry_warning");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetExpiryWarning(tx))?;
+        let warning = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::ExpiryWarningState::from(warning)))
+    }
+
+    async fn set_expiry_warning_thresholds(
+        &self,
+        request: Request<types::ExpiryWarningThresholds>,
+    ) -> ServiceResult<()> {
+        let thresholds =
+            Vec::<Duration>::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_expiry_warning_thresholds({thresholds:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetExpiryWarningThresholds(tx, thresholds))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
     // Device management
     async fn get_device(&self, _: Request<()>) -> ServiceResult<types::DeviceState> {
         log::debug!("get_device");
@@ -601,6 +926,15 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn set_prune_custom_lists(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enabled = request.into_inner();
+        log::debug!("set_prune_custom_lists({})", enabled);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetPruneCustomLists(tx, enabled))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     // Access Methods
 
     async fn add_api_access_method(
@@ -920,6 +1254,82 @@ impl ManagementService for ManagementServiceImpl {
         let blob = self.wait_for_result(rx).await??;
         Ok(Response::new(blob))
     }
+
+    async fn create_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("create_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CreateProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_profiles(&self, _: Request<()>) -> ServiceResult<types::ProfileList> {
+        log::debug!("get_profiles");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetProfiles(tx))?;
+        let profiles = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::ProfileList {
+            profiles: profiles.iter().map(types::Profile::from).collect(),
+        }))
+    }
+
+    async fn apply_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("apply_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ApplyProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn delete_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("delete_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DeleteProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_settings_history(&self, _: Request<()>) -> ServiceResult<types::SettingsHistory> {
+        log::debug!("get_settings_history");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettingsHistory(tx))?;
+        let snapshots = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::SettingsHistory {
+            snapshots: snapshots
+                .iter()
+                .map(types::SettingsSnapshot::from)
+                .collect(),
+        }))
+    }
+
+    async fn diff_settings_snapshot(
+        &self,
+        request: Request<u32>,
+    ) -> ServiceResult<types::SettingsDiff> {
+        let id = request.into_inner();
+        log::debug!("diff_settings_snapshot({id})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DiffSettingsSnapshot(tx, id))?;
+        let changes = self.wait_for_result(rx).await??;
+        Ok(Response::new(types::SettingsDiff {
+            changes: changes
+                .into_iter()
+                .map(types::SettingsChange::from)
+                .collect(),
+        }))
+    }
+
+    async fn restore_settings_snapshot(&self, request: Request<u32>) -> ServiceResult<()> {
+        let id = request.into_inner();
+        log::debug!("restore_settings_snapshot({id})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RestoreSettingsSnapshot(tx, id))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
 }
 
 impl ManagementServiceImpl {
@@ -1049,6 +1459,27 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
             )),
         })
     }
+
+    fn notify_relay_list_changes(
+        &self,
+        changes: mullvad_types::relay_list_changes::RelayListChanges,
+    ) {
+        log::debug!("Broadcasting relay list changes");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::RelayListChanges(
+                types::RelayListChanges::from(changes),
+            )),
+        })
+    }
+
+    fn notify_expiry_warning(&self, warning: Option<mullvad_types::account::ExpiryWarning>) {
+        log::debug!("Broadcasting expiry warning");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::ExpiryWarning(
+                types::ExpiryWarningState::from(warning),
+            )),
+        })
+    }
 }
 
 impl ManagementInterfaceEventBroadcaster {
@@ -1079,6 +1510,24 @@ fn map_daemon_error(error: crate::Error) -> Status {
         DaemonError::NoAccountToken | DaemonError::NoAccountTokenHistory => {
             Status::unauthenticated(error.to_string())
         }
+        DaemonError::OpenVpnProfile(error) => Status::invalid_argument(error.to_string()),
+        DaemonError::PauseInLockdown => Status::failed_precondition(error.to_string()),
+        DaemonError::InvalidPauseDuration => Status::invalid_argument(error.to_string()),
+        DaemonError::InvalidExpiryWarningThresholds => Status::invalid_argument(error.to_string()),
+        DaemonError::InvalidUserRelay(mullvad_types::user_relay::Error::HostnameTaken(_)) => {
+            Status::already_exists(error.to_string())
+        }
+        DaemonError::InvalidUserRelay(_) => Status::invalid_argument(error.to_string()),
+        DaemonError::InvalidConnectivityCheck(_) => Status::invalid_argument(error.to_string()),
+        DaemonError::UserRelayNotFound => Status::not_found(error.to_string()),
+        #[cfg(not(target_os = "windows"))]
+        DaemonError::InvalidFirewallException(_) => Status::invalid_argument(error.to_string()),
+        #[cfg(not(target_os = "windows"))]
+        DaemonError::FirewallExceptionNotFound => Status::not_found(error.to_string()),
+        #[cfg(target_os = "linux")]
+        DaemonError::TrustedInterfaceNotFound => Status::not_found(error.to_string()),
+        #[cfg(target_os = "linux")]
+        DaemonError::UntrustableInterface(_) => Status::invalid_argument(error.to_string()),
         DaemonError::CustomListExists => Status::with_details(
             Code::AlreadyExists,
             error.to_string(),
@@ -31,6 +31,7 @@
 //! 1. Implement the migration and add adequate tests.
 //! 1. Add to the changelog: "Settings format updated to `vY`"
 
+use crate::settings::history::SettingsHistory;
 use std::{
     path::Path,
     sync::{
@@ -38,6 +39,7 @@ use std::{
         Arc,
     },
 };
+use talpid_types::ErrorExt;
 use tokio::{
     fs,
     io::{self, AsyncWriteExt},
@@ -142,6 +144,14 @@ pub async fn migrate_all(cache_dir: &Path, settings_dir: &Path) -> Result<Option
         return Ok(migration_data);
     }
 
+    let mut history = SettingsHistory::load(settings_dir).await;
+    if let Err(error) = history.record("migration", old_settings).await {
+        log::error!(
+            "{}",
+            error.display_chain_with_msg("Failed to save settings before migrating them")
+        );
+    }
+
     let buffer = serde_json::to_string_pretty(&settings).map_err(Error::Serialize)?;
 
     let mut file = fs::OpenOptions::new()
@@ -161,7 +171,7 @@ pub async fn migrate_all(cache_dir: &Path, settings_dir: &Path) -> Result<Option
     Ok(migration_data)
 }
 
-async fn migrate_settings(
+pub(crate) async fn migrate_settings(
     directories: Option<(&Path, &Path)>,
     settings: &mut serde_json::Value,
 ) -> Result<Option<MigrationData>> {
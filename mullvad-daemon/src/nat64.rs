@@ -0,0 +1,206 @@
+//! Discovery of the NAT64 prefix of the network, which lets IPv4-only relays, bridges, and API
+//! addresses be reached on IPv6-only networks.
+//!
+//! The prefix is primarily taken from the PREF64 option of router advertisements ([RFC 8781]),
+//! since the firewall allows NDP in every tunnel state. The DNS64 resolver of the network
+//! ([RFC 7050]) is only asked as a fallback: the firewall blocks DNS outside of the tunnel while
+//! blocking or connecting, and the resolver inside the tunnel does not do DNS64.
+//!
+//! [RFC 8781]: https://www.rfc-editor.org/rfc/rfc8781
+//! [RFC 7050]: https://www.rfc-editor.org/rfc/rfc7050
+
+use std::{future::Future, io, net::IpAddr, time::Duration};
+use talpid_types::net::nat64::{self, Nat64Prefix};
+
+/// How long to wait for router advertisements, and for the DNS64 resolver, when discovering the
+/// NAT64 prefix.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Discovers the NAT64 prefix of the network. Returns `None` if the prefix could not be
+/// discovered.
+pub async fn discover_prefix() -> Option<Nat64Prefix> {
+    discover_prefix_from(solicit_pref64(), query_dns64()).await
+}
+
+/// Discovers the NAT64 prefix using `pref64`, and then `dns64` if that fails.
+async fn discover_prefix_from(
+    pref64: impl Future<Output = io::Result<Nat64Prefix>>,
+    dns64: impl Future<Output = io::Result<Option<Nat64Prefix>>>,
+) -> Option<Nat64Prefix> {
+    match tokio::time::timeout(DISCOVERY_TIMEOUT, pref64).await {
+        Ok(Ok(prefix)) => {
+            log::info!("Discovered NAT64 prefix {prefix} in a router advertisement");
+            return Some(prefix);
+        }
+        Ok(Err(error)) => log::debug!("Failed to solicit router advertisements: {error}"),
+        Err(_) => log::debug!("No router advertisement contained a NAT64 prefix"),
+    }
+
+    match tokio::time::timeout(DISCOVERY_TIMEOUT, dns64).await {
+        Ok(Ok(Some(prefix))) => {
+            log::info!("Discovered NAT64 prefix {prefix} using DNS64");
+            Some(prefix)
+        }
+        Ok(Ok(None)) => {
+            log::debug!("The network does not provide a NAT64 prefix");
+            None
+        }
+        Ok(Err(error)) => {
+            log::debug!("Failed to discover NAT64 prefix: {error}");
+            None
+        }
+        Err(_) => {
+            log::debug!("Timed out discovering NAT64 prefix");
+            None
+        }
+    }
+}
+
+/// Asks the DNS64 resolver of the network for the NAT64 prefix, as described in RFC 7050.
+async fn query_dns64() -> io::Result<Option<Nat64Prefix>> {
+    let addresses = tokio::net::lookup_host((nat64::IPV4ONLY_ARPA, 0)).await?;
+    Ok(addresses
+        .into_iter()
+        .find_map(|address| match address.ip() {
+            IpAddr::V6(address) => Nat64Prefix::from_ipv4only_arpa(address),
+            IpAddr::V4(_) => None,
+        }))
+}
+
+/// Sends a router solicitation on every interface with a link-local IPv6 address, and returns the
+/// NAT64 prefix of the first router advertisement that contains one. Never returns if no such
+/// router advertisement is received.
+#[cfg(unix)]
+async fn solicit_pref64() -> io::Result<Nat64Prefix> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    use std::{
+        mem::MaybeUninit,
+        net::{Ipv6Addr, SocketAddrV6},
+    };
+    use tokio::io::unix::AsyncFd;
+
+    const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+    // Type, code, checksum, which is filled in by the kernel, and reserved bytes
+    const ROUTER_SOLICITATION: [u8; 8] = [133, 0, 0, 0, 0, 0, 0, 0];
+
+    let socket = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6))?;
+    socket.set_nonblocking(true)?;
+    // Routers discard NDP messages that may have been forwarded
+    socket.set_multicast_hops_v6(255)?;
+    let socket = AsyncFd::new(socket)?;
+
+    for interface in link_local_interfaces() {
+        let destination = SocketAddrV6::new(ALL_ROUTERS, 0, 0, interface);
+        if let Err(error) = socket
+            .get_ref()
+            .send_to(&ROUTER_SOLICITATION, &destination.into())
+        {
+            log::debug!("Failed to send router solicitation on interface {interface}: {error}");
+        }
+    }
+
+    let mut buffer = [MaybeUninit::<u8>::uninit(); 1500];
+    loop {
+        let mut guard = socket.readable().await?;
+        let Ok(result) = guard.try_io(|socket| socket.get_ref().recv_from(&mut buffer)) else {
+            continue;
+        };
+        let (len, source) = result?;
+        // SAFETY: `recv_from` initialized the first `len` bytes of the buffer
+        let message = unsafe { &*(&buffer[..len] as *const [MaybeUninit<u8>] as *const [u8]) };
+        // Router advertisements are only valid if they are sent from a link-local address
+        let from_link_local = source
+            .as_socket_ipv6()
+            .map(|source| is_link_local(source.ip()))
+            .unwrap_or(false);
+        if from_link_local {
+            if let Some(prefix) = Nat64Prefix::from_router_advertisement(message) {
+                return Ok(prefix);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn solicit_pref64() -> io::Result<Nat64Prefix> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "router solicitations are not supported on this platform",
+    ))
+}
+
+/// Returns the indices of all interfaces with a link-local IPv6 address.
+#[cfg(unix)]
+fn link_local_interfaces() -> Vec<u32> {
+    use nix::sys::socket::SockAddr;
+    use std::net::SocketAddr;
+
+    let addresses = match nix::ifaddrs::getifaddrs() {
+        Ok(addresses) => addresses,
+        Err(error) => {
+            log::debug!("Failed to list interface addresses: {error}");
+            return vec![];
+        }
+    };
+    let mut interfaces: Vec<u32> = addresses
+        .filter(|address| match address.address {
+            Some(SockAddr::Inet(address)) => {
+                matches!(address.to_std(), SocketAddr::V6(address) if is_link_local(address.ip()))
+            }
+            _ => false,
+        })
+        .filter_map(|address| nix::net::if_::if_nametoindex(address.interface_name.as_str()).ok())
+        .collect();
+    interfaces.sort_unstable();
+    interfaces.dedup();
+    interfaces
+}
+
+#[cfg(unix)]
+fn is_link_local(addr: &std::net::Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::future::ready;
+
+    #[tokio::test]
+    async fn test_discover_prefix_while_dns_is_blocked() {
+        let prefix = Nat64Prefix::new("2001:db8::".parse().unwrap(), 96).unwrap();
+        // The firewall rejects DNS outside of the tunnel
+        let blocked = || {
+            ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "DNS is blocked",
+            )))
+        };
+
+        assert_eq!(
+            discover_prefix_from(ready(Ok(prefix)), blocked()).await,
+            Some(prefix)
+        );
+        assert_eq!(
+            discover_prefix_from(
+                ready(Err(io::Error::from(io::ErrorKind::PermissionDenied))),
+                blocked()
+            )
+            .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discover_prefix_using_dns64() {
+        let prefix = Nat64Prefix::WELL_KNOWN;
+        assert_eq!(
+            discover_prefix_from(
+                ready(Err(io::Error::from(io::ErrorKind::PermissionDenied))),
+                ready(Ok(Some(prefix)))
+            )
+            .await,
+            Some(prefix)
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! A bounded history of previous settings. A snapshot is taken every time the settings are
+//! replaced, and before they are migrated, so that they can be restored later. A factory reset
+//! clears the history instead, so that no previous settings are kept.
+
+use chrono::{DateTime, Utc};
+use mullvad_types::settings::{
+    history::{SettingsChange, SettingsSnapshotInfo},
+    Settings,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+const HISTORY_FILE: &str = "settings-history.json";
+
+/// Maximum number of snapshots to keep. The oldest snapshots are removed first.
+const MAX_SNAPSHOTS: usize = 20;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "No settings snapshot with ID {}", _0)]
+    SnapshotNotFound(u32),
+
+    #[error(display = "Unable to migrate settings snapshot")]
+    MigrateError(#[error(source)] crate::migrations::Error),
+
+    #[error(display = "Unable to parse settings snapshot")]
+    ParseError(#[error(source)] serde_json::Error),
+
+    #[error(display = "Unable to serialize settings history to JSON")]
+    SerializeError(#[error(source)] serde_json::Error),
+
+    #[error(display = "Unable to write settings history to {}", _0)]
+    WriteError(String, #[error(source)] io::Error),
+
+    #[error(display = "Unable to delete settings history {}", _0)]
+    DeleteError(String, #[error(source)] io::Error),
+}
+
+/// Settings as they were before they were replaced. The settings are stored as JSON, since
+/// snapshots taken before a migration may not be valid in the current format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SettingsSnapshot {
+    id: u32,
+    created: DateTime<Utc>,
+    cause: String,
+    settings: serde_json::Value,
+}
+
+pub struct SettingsHistory {
+    snapshots: Vec<SettingsSnapshot>,
+    path: PathBuf,
+}
+
+impl SettingsHistory {
+    /// Loads the history from file. If it fails, the history starts out empty.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(HISTORY_FILE);
+        let snapshots = match fs::read(&path).await {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).unwrap_or_else(|error: serde_json::Error| {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to parse settings history")
+                    );
+                    vec![]
+                })
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read settings history")
+                );
+                vec![]
+            }
+        };
+        SettingsHistory { snapshots, path }
+    }
+
+    pub fn snapshots(&self) -> Vec<SettingsSnapshotInfo> {
+        self.snapshots
+            .iter()
+            .map(|snapshot| SettingsSnapshotInfo {
+                id: snapshot.id,
+                created: snapshot.created,
+                cause: snapshot.cause.clone(),
+            })
+            .collect()
+    }
+
+    /// Saves `settings` as a new snapshot, removing the oldest snapshots if there are too many.
+    pub async fn record(&mut self, cause: &str, settings: serde_json::Value) -> Result<(), Error> {
+        let id = self
+            .snapshots
+            .last()
+            .map(|snapshot| snapshot.id.wrapping_add(1))
+            .unwrap_or(1);
+        let mut snapshots = self.snapshots.clone();
+        snapshots.push(SettingsSnapshot {
+            id,
+            created: Utc::now(),
+            cause: cause.to_owned(),
+            settings,
+        });
+        let excess = snapshots.len().saturating_sub(MAX_SNAPSHOTS);
+        snapshots.drain(..excess);
+
+        log::debug!("Saving settings snapshot {id} ({cause})");
+        self.write(&snapshots).await?;
+        self.snapshots = snapshots;
+        Ok(())
+    }
+
+    /// Removes all snapshots, and the history file.
+    pub async fn clear(&mut self) -> Result<(), Error> {
+        match fs::remove_file(&self.path).await {
+            Ok(()) => (),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(Error::DeleteError(self.path.display().to_string(), error)),
+        }
+        self.snapshots.clear();
+        Ok(())
+    }
+
+    /// Returns the settings in a snapshot, migrated to the current format.
+    pub async fn settings(&self, id: u32) -> Result<Settings, Error> {
+        let mut settings = self
+            .snapshots
+            .iter()
+            .find(|snapshot| snapshot.id == id)
+            .ok_or(Error::SnapshotNotFound(id))?
+            .settings
+            .clone();
+        crate::migrations::migrate_settings(None, &mut settings)
+            .await
+            .map_err(Error::MigrateError)?;
+        serde_json::from_value(settings).map_err(Error::ParseError)
+    }
+
+    /// Returns the changes that were made to the settings since the snapshot was taken.
+    pub async fn diff(&self, id: u32, current: &Settings) -> Result<Vec<SettingsChange>, Error> {
+        let old = serde_json::to_value(self.settings(id).await?).map_err(Error::SerializeError)?;
+        let new = serde_json::to_value(current).map_err(Error::SerializeError)?;
+        let mut changes = vec![];
+        diff_values("", &old, &new, &mut changes);
+        Ok(changes)
+    }
+
+    async fn write(&self, snapshots: &[SettingsSnapshot]) -> Result<(), Error> {
+        let buffer = serde_json::to_string(snapshots).map_err(Error::SerializeError)?;
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
+        file.write_all(&buffer.into_bytes())
+            .await
+            .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
+        file.finalize()
+            .await
+            .map_err(|e| Error::WriteError(self.path.display().to_string(), e))
+    }
+}
+
+/// Collects the differences between two JSON values. Objects are compared key by key, and all
+/// other values, including arrays, are compared as a whole.
+fn diff_values(
+    path: &str,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    changes: &mut Vec<SettingsChange>,
+) {
+    use serde_json::Value;
+
+    if old == new {
+        return;
+    }
+    let (Value::Object(old_map), Value::Object(new_map)) = (old, new) else {
+        changes.push(SettingsChange {
+            path: path.to_owned(),
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+        });
+        return;
+    };
+
+    let subpath = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    for (key, old_value) in old_map {
+        match new_map.get(key) {
+            Some(new_value) => diff_values(&subpath(key), old_value, new_value, changes),
+            None => changes.push(SettingsChange {
+                path: subpath(key),
+                old: Some(old_value.to_string()),
+                new: None,
+            }),
+        }
+    }
+    for (key, new_value) in new_map {
+        if !old_map.contains_key(key) {
+            changes.push(SettingsChange {
+                path: subpath(key),
+                old: None,
+                new: Some(new_value.to_string()),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_values() {
+        let old = serde_json::json!({
+            "allow_lan": false,
+            "tunnel_options": { "wireguard": { "mtu": null, "mtu_discovery": false } },
+            "removed": 1,
+        });
+        let new = serde_json::json!({
+            "allow_lan": true,
+            "tunnel_options": { "wireguard": { "mtu": 1280, "mtu_discovery": false } },
+            "added": [1, 2],
+        });
+
+        let mut changes = vec![];
+        diff_values("", &old, &new, &mut changes);
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let change = |path: &str, old: Option<&str>, new: Option<&str>| SettingsChange {
+            path: path.to_owned(),
+            old: old.map(str::to_owned),
+            new: new.map(str::to_owned),
+        };
+        assert_eq!(
+            changes,
+            vec![
+                change("added", None, Some("[1,2]")),
+                change("allow_lan", Some("false"), Some("true")),
+                change("removed", Some("1"), None),
+                change("tunnel_options.wireguard.mtu", Some("null"), Some("1280")),
+            ]
+        );
+    }
+}
@@ -1,8 +1,16 @@
 #[cfg(not(target_os = "android"))]
 use futures::TryFutureExt;
+use history::SettingsHistory;
+#[cfg(windows)]
+use mullvad_types::settings::SplitTunnelSettings;
 use mullvad_types::{
+    access_method,
     relay_constraints::{RelayConstraints, RelaySettings, WireguardConstraints},
-    settings::{DnsState, Settings},
+    settings::{
+        history::{SettingsChange, SettingsSnapshotInfo},
+        DnsOptions, DnsState, Settings,
+    },
+    wireguard::RotationInterval,
 };
 use std::{
     fmt::{self, Display},
@@ -10,13 +18,17 @@ use std::{
     path::{Path, PathBuf},
 };
 use talpid_core::firewall::is_local_address;
-use talpid_types::ErrorExt;
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
+use talpid_types::{net::firewall_exception::FirewallException, ErrorExt};
 use tokio::{
     fs,
     io::{self, AsyncWriteExt},
 };
 
+pub mod history;
 pub mod patch;
+pub mod profiles;
 
 const SETTINGS_FILE: &str = "settings.json";
 
@@ -41,6 +53,9 @@ pub enum Error {
 
     #[error(display = "Failed to apply settings update")]
     UpdateFailed(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error(display = "Failed to load settings snapshot")]
+    History(#[error(source)] history::Error),
 }
 
 /// Converts an [Error] to a management interface status
@@ -56,6 +71,10 @@ impl From<Error> for mullvad_management_interface::Status {
             Error::SerializeError(..) | Error::ParseError(..) | Error::UpdateFailed(..) => {
                 Status::new(Code::Internal, error.to_string())
             }
+            Error::History(history::Error::SnapshotNotFound(..)) => {
+                Status::new(Code::NotFound, error.to_string())
+            }
+            Error::History(..) => Status::new(Code::Internal, error.to_string()),
         }
     }
 }
@@ -63,6 +82,7 @@ impl From<Error> for mullvad_management_interface::Status {
 pub struct SettingsPersister {
     settings: Settings,
     path: PathBuf,
+    history: SettingsHistory,
     #[allow(clippy::type_complexity)]
     on_change_listeners: Vec<Box<dyn Fn(&Settings)>>,
 }
@@ -87,14 +107,17 @@ impl SettingsPersister {
             should_save |= !settings.show_beta_releases;
             settings.show_beta_releases = true;
         }
+        should_save |= remove_invalid_firewall_exceptions(&mut settings);
 
         let mut persister = SettingsPersister {
             settings,
             path,
+            history: SettingsHistory::load(settings_dir).await,
             on_change_listeners: vec![],
         };
 
         if should_save {
+            persister.record_file_snapshot("load").await;
             if let Err(error) = persister.save().await {
                 log::error!(
                     "{}",
@@ -171,6 +194,39 @@ impl SettingsPersister {
         Self::save_inner(&self.path, &self.settings).await
     }
 
+    /// Saves the current settings to the history before they are replaced.
+    async fn record_snapshot(&mut self, cause: &str) {
+        let result = match serde_json::to_value(&self.settings) {
+            Ok(settings) => self.history.record(cause, settings).await,
+            Err(error) => Err(history::Error::SerializeError(error)),
+        };
+        if let Err(error) = result {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to save settings snapshot")
+            );
+        }
+    }
+
+    /// Saves the settings file to the history before it is replaced, as long as it contains
+    /// valid JSON. Unlike [`Self::record_snapshot`], this keeps settings that could not be
+    /// loaded.
+    async fn record_file_snapshot(&mut self, cause: &str) {
+        let Ok(bytes) = fs::read(&self.path).await else {
+            return;
+        };
+        let Ok(settings) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+            log::warn!("Not saving a snapshot of the settings since they are not valid JSON");
+            return;
+        };
+        if let Err(error) = self.history.record(cause, settings).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to save settings snapshot")
+            );
+        }
+    }
+
     /// Serializes the settings and saves them to the given file.
     async fn save_inner(path: &Path, settings: &Settings) -> Result<(), Error> {
         log::debug!("Writing settings to {}", path.display());
@@ -189,9 +245,11 @@ impl SettingsPersister {
         Ok(())
     }
 
-    /// Resets default settings
+    /// Resets default settings, and removes the settings history so that no previous settings are
+    /// kept.
     #[cfg(not(target_os = "android"))]
     pub async fn reset(&mut self) -> Result<(), Error> {
+        self.history.clear().await.map_err(Error::History)?;
         self.settings = Self::default_settings();
         let path = self.path.clone();
         self.save()
@@ -227,7 +285,8 @@ impl SettingsPersister {
         settings
     }
 
-    /// Edit the settings in a closure and write the changes to disk.
+    /// Edit the settings in a closure and write the changes to disk. `cause` describes what
+    /// changed the settings, usually the name of an RPC, and is saved in the settings history.
     ///
     /// # On success
     ///
@@ -243,16 +302,18 @@ impl SettingsPersister {
     /// If no settings were changed, no I/O will be performed.
     pub async fn update(
         &mut self,
+        cause: &str,
         update_fn: impl FnOnce(&mut Settings),
     ) -> Result<MadeChanges, Error> {
-        self.try_update(|settings| -> Result<(), Error> {
+        self.try_update(cause, |settings| -> Result<(), Error> {
             update_fn(settings);
             Ok(())
         })
         .await
     }
 
-    /// Edit the settings in a closure, and write the changes to disk.
+    /// Edit the settings in a closure, and write the changes to disk. The previous settings are
+    /// saved in the settings history, along with `cause`.
     ///
     /// # On success
     ///
@@ -281,7 +342,7 @@ impl SettingsPersister {
     /// }
     ///
     /// let settings = Settings::default_settings();
-    /// let err = settings.try_update(|settings| {
+    /// let err = settings.try_update("toggle_lan", |settings| {
     ///   // Perform some update on the settings
     ///   settings.allow_lan = !settings.allow_lan;
     ///   // Fail the update procedure due to some error
@@ -293,6 +354,7 @@ impl SettingsPersister {
     /// ```
     pub async fn try_update<E>(
         &mut self,
+        cause: &str,
         update_fn: impl FnOnce(&mut Settings) -> Result<(), E>,
     ) -> Result<MadeChanges, Error>
     where
@@ -303,11 +365,13 @@ impl SettingsPersister {
         update_fn(&mut new_settings)
             .map_err(Box::from)
             .map_err(Error::UpdateFailed)?;
+        remove_invalid_firewall_exceptions(&mut new_settings);
 
         if self.settings == new_settings {
             return Ok(false);
         }
 
+        self.record_snapshot(cause).await;
         Self::save_inner(&self.path, &new_settings).await?;
         self.settings = new_settings;
 
@@ -316,6 +380,30 @@ impl SettingsPersister {
         Ok(true)
     }
 
+    /// Returns all snapshots in the settings history, oldest first.
+    pub fn snapshots(&self) -> Vec<SettingsSnapshotInfo> {
+        self.history.snapshots()
+    }
+
+    /// Returns the changes that were made to the settings since a snapshot was taken.
+    pub async fn diff_snapshot(&self, id: u32) -> Result<Vec<SettingsChange>, Error> {
+        self.history
+            .diff(id, &self.settings)
+            .await
+            .map_err(Error::History)
+    }
+
+    /// Replaces the settings with those in a snapshot. Snapshots in an older format are migrated
+    /// before they are applied. The current settings are saved as a new snapshot, so a restore
+    /// can be undone.
+    pub async fn restore_snapshot(&mut self, id: u32) -> Result<MadeChanges, Error> {
+        let restored = self.history.settings(id).await.map_err(Error::History)?;
+        self.update(&format!("restore_settings({id})"), move |settings| {
+            *settings = restored
+        })
+        .await
+    }
+
     /// Return a compact summary of important settings
     pub fn summary(&self) -> SettingsSummary<'_> {
         SettingsSummary {
@@ -334,11 +422,100 @@ impl SettingsPersister {
     }
 }
 
+/// A setting that has to be passed on to another part of the daemon when it changes, since it
+/// is not only read from the settings when reconnecting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplacedSetting {
+    AllowLan(bool),
+    FirewallExceptions(Vec<FirewallException>),
+    #[cfg(target_os = "linux")]
+    TrustedInterfaces(Vec<TrustedInterface>),
+    BlockWhenDisconnected(bool),
+    DnsOptions(DnsOptions),
+    AccessMethods(access_method::Settings),
+    RotationInterval(Option<RotationInterval>),
+    #[cfg(windows)]
+    SplitTunnel(SplitTunnelSettings),
+}
+
+/// Returns the settings that differ between `prev_settings` and `settings`, after several
+/// settings were replaced at once, e.g. by restoring a snapshot or applying a profile.
+pub fn replaced_settings(prev_settings: &Settings, settings: &Settings) -> Vec<ReplacedSetting> {
+    let mut replaced = vec![];
+    if settings.allow_lan != prev_settings.allow_lan {
+        replaced.push(ReplacedSetting::AllowLan(settings.allow_lan));
+    }
+    if settings.firewall_exceptions != prev_settings.firewall_exceptions {
+        replaced.push(ReplacedSetting::FirewallExceptions(
+            settings.firewall_exceptions.clone(),
+        ));
+    }
+    #[cfg(target_os = "linux")]
+    if settings.trusted_interfaces != prev_settings.trusted_interfaces {
+        replaced.push(ReplacedSetting::TrustedInterfaces(
+            settings.trusted_interfaces.clone(),
+        ));
+    }
+    if settings.block_when_disconnected != prev_settings.block_when_disconnected {
+        replaced.push(ReplacedSetting::BlockWhenDisconnected(
+            settings.block_when_disconnected,
+        ));
+    }
+    if settings.tunnel_options.dns_options != prev_settings.tunnel_options.dns_options {
+        replaced.push(ReplacedSetting::DnsOptions(
+            settings.tunnel_options.dns_options.clone(),
+        ));
+    }
+    if settings.api_access_methods != prev_settings.api_access_methods {
+        replaced.push(ReplacedSetting::AccessMethods(
+            settings.api_access_methods.clone(),
+        ));
+    }
+    let rotation_interval = settings.tunnel_options.wireguard.rotation_interval;
+    if rotation_interval != prev_settings.tunnel_options.wireguard.rotation_interval {
+        replaced.push(ReplacedSetting::RotationInterval(rotation_interval));
+    }
+    #[cfg(windows)]
+    if settings.split_tunnel != prev_settings.split_tunnel {
+        replaced.push(ReplacedSetting::SplitTunnel(settings.split_tunnel.clone()));
+    }
+    replaced
+}
+
 struct LoadSettingsResult {
     settings: Settings,
     should_save: bool,
 }
 
+/// Removes firewall exceptions that would be refused if they were added now, e.g. because they
+/// were added by editing the settings file, restoring a snapshot or applying a profile. Returns
+/// whether any exception was removed.
+#[cfg(not(target_os = "windows"))]
+fn remove_invalid_firewall_exceptions(settings: &mut Settings) -> bool {
+    let count = settings.firewall_exceptions.len();
+    settings
+        .firewall_exceptions
+        .retain(|exception| match exception.validate() {
+            Ok(()) => true,
+            Err(error) => {
+                log::warn!("Removing invalid firewall exception {exception}: {error}");
+                false
+            }
+        });
+    count != settings.firewall_exceptions.len()
+}
+
+/// Firewall exceptions are not supported on Windows, so all of them are removed.
+#[cfg(target_os = "windows")]
+fn remove_invalid_firewall_exceptions(settings: &mut Settings) -> bool {
+    if settings.firewall_exceptions.is_empty() {
+        return false;
+    }
+    log::warn!("Removing firewall exceptions, since they are not supported on this platform");
+    settings.firewall_exceptions.clear();
+    true
+}
+
 impl Deref for SettingsPersister {
     type Target = Settings;
 
@@ -629,4 +806,114 @@ mod test {
             "The daemon should block the internet if settings are corrupt"
         );
     }
+
+    /// A factory reset must not keep any previous settings in the history.
+    #[cfg(not(target_os = "android"))]
+    #[tokio::test]
+    async fn test_reset_clears_history() {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let mut settings = SettingsPersister::load(settings_dir.path()).await;
+        settings
+            .update("test", |settings| settings.allow_lan = !settings.allow_lan)
+            .await
+            .unwrap();
+        assert!(!settings.snapshots().is_empty());
+
+        settings.reset().await.unwrap();
+        assert!(settings.snapshots().is_empty());
+        assert!(!settings_dir.path().join("settings-history.json").exists());
+
+        let settings = SettingsPersister::load(settings_dir.path()).await;
+        assert!(settings.snapshots().is_empty());
+    }
+
+    /// Restoring a snapshot must pass the restored access methods on, since the API connection
+    /// does not read them from the settings.
+    #[cfg(not(target_os = "android"))]
+    #[tokio::test]
+    async fn test_restore_snapshot_replaces_access_methods() {
+        use mullvad_types::access_method::{AccessMethod, AccessMethodSetting};
+        use talpid_types::net::proxy::Socks5Remote;
+
+        let settings_dir = tempfile::tempdir().unwrap();
+        let mut settings = SettingsPersister::load(settings_dir.path()).await;
+        let original = settings.api_access_methods.clone();
+
+        let proxy = Socks5Remote::new(([192, 0, 2, 1], 1080));
+        settings
+            .update("add_api_access_method", move |settings| {
+                settings.api_access_methods.append(AccessMethodSetting::new(
+                    "proxy".to_owned(),
+                    true,
+                    AccessMethod::from(proxy),
+                ))
+            })
+            .await
+            .unwrap();
+        let snapshot = settings.snapshots().last().unwrap().id;
+
+        let prev_settings = settings.to_settings();
+        assert!(settings.restore_snapshot(snapshot).await.unwrap());
+        assert_eq!(settings.api_access_methods, original);
+        assert!(replaced_settings(&prev_settings, &settings)
+            .contains(&ReplacedSetting::AccessMethods(original)));
+    }
+
+    /// Applying a profile replaces the tunnel options, which include the key rotation interval.
+    #[test]
+    fn test_apply_profile_replaces_rotation_interval() {
+        use mullvad_types::profile::Profile;
+        use std::time::Duration;
+
+        let mut office = SettingsPersister::default_settings();
+        let interval = RotationInterval::new(Duration::from_secs(2 * 24 * 60 * 60)).unwrap();
+        office.tunnel_options.wireguard.rotation_interval = Some(interval);
+        let profile = Profile::from_settings("office".to_owned(), &office);
+
+        let prev_settings = SettingsPersister::default_settings();
+        let mut settings = prev_settings.clone();
+        profile.apply_to(&mut settings);
+        assert!(replaced_settings(&prev_settings, &settings)
+            .contains(&ReplacedSetting::RotationInterval(Some(interval))));
+    }
+
+    /// Exceptions that the daemon would refuse to add must not be applied when they are found in
+    /// the settings file.
+    #[cfg(not(any(target_os = "android", target_os = "windows")))]
+    #[tokio::test]
+    async fn test_load_removes_invalid_firewall_exceptions() {
+        use talpid_types::net::firewall_exception::{Direction, FirewallException};
+
+        let exception = |network: &str, port| FirewallException {
+            network: network.parse().unwrap(),
+            protocol: None,
+            port,
+            direction: Direction::Both,
+        };
+        let valid = exception("100.64.0.0/10", None);
+
+        let settings_dir = tempfile::tempdir().unwrap();
+        let mut settings = SettingsPersister::default_settings();
+        settings.firewall_exceptions = vec![
+            exception("0.0.0.0/0", None),
+            valid.clone(),
+            exception("8.8.0.0/16", None),
+            exception("192.168.1.1/32", Some(53)),
+        ];
+        std::fs::write(
+            settings_dir.path().join(SETTINGS_FILE),
+            serde_json::to_vec(&settings).unwrap(),
+        )
+        .unwrap();
+
+        let settings = SettingsPersister::load(settings_dir.path()).await;
+        assert_eq!(settings.firewall_exceptions, vec![valid.clone()]);
+
+        let settings = SettingsPersister::load(settings_dir.path()).await;
+        assert_eq!(
+            settings.firewall_exceptions,
+            vec![valid],
+            "The invalid exceptions should be removed from the file"
+        );
+    }
 }
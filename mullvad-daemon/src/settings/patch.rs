@@ -12,11 +12,17 @@
 //!
 //! Permitted settings and merge strategies are defined in the [PERMITTED_SUBKEYS] constant.
 //!
+//! Settings profiles are not part of [Settings], but may be included in a patch using the
+//! [PROFILES_KEY] key. Imported profiles are stored, but never applied to the settings.
+//!
 //! This implementation must be kept in sync with the
 //! [spec](../../../docs/settings-patch-format.md).
 
-use super::SettingsPersister;
-use mullvad_types::settings::Settings;
+use super::{
+    profiles::{self, ProfileStore},
+    SettingsPersister,
+};
+use mullvad_types::{profile::Profile, settings::Settings};
 
 #[derive(err_derive::Error, Debug)]
 #[error(no_from)]
@@ -45,6 +51,9 @@ pub enum Error {
     /// Settings error
     #[error(display = "Settings error")]
     Settings(#[error(source)] super::Error),
+    /// Failed to store imported profiles
+    #[error(display = "Failed to import profiles")]
+    Profiles(#[error(source)] super::profiles::Error),
 }
 
 /// Converts an [Error] to a management interface status
@@ -60,6 +69,7 @@ impl From<Error> for mullvad_management_interface::Status {
             | Error::DeserializePatched(_)
             | Error::RecursionLimit => Status::invalid_argument(error.to_string()),
             Error::Settings(error) => Status::from(error),
+            Error::Profiles(error) => Status::from(error),
             Error::SerializeSettings(error) | Error::SerializeValue(error) => {
                 Status::internal(error.to_string())
             }
@@ -120,26 +130,36 @@ enum PermittedKeyValue {
     Any,
 }
 
-const PERMITTED_SUBKEYS: &PermittedKey = &PermittedKey::object(&[(
-    "relay_overrides",
-    PermittedKey::array(&PermittedKey::object(&[
-        ("hostname", PermittedKey::any()),
-        ("ipv4_addr_in", PermittedKey::any()),
-        ("ipv6_addr_in", PermittedKey::any()),
-    ]))
-    .merge_strategy(MergeStrategy::Custom(merge_relay_overrides)),
-)]);
+const PERMITTED_SUBKEYS: &PermittedKey = &PermittedKey::object(&[
+    (
+        "relay_overrides",
+        PermittedKey::array(&PermittedKey::object(&[
+            ("hostname", PermittedKey::any()),
+            ("ipv4_addr_in", PermittedKey::any()),
+            ("ipv6_addr_in", PermittedKey::any()),
+        ]))
+        .merge_strategy(MergeStrategy::Custom(merge_relay_overrides)),
+    ),
+    ("connection_plan", PermittedKey::any()),
+]);
+/// Key under which settings profiles are stored in a patch. This is handled separately from
+/// [PERMITTED_SUBKEYS] since profiles are not part of the settings.
+const PROFILES_KEY: &str = "profiles";
+
 /// Prohibit stack overflow via excessive recursion. It might be possible to forgo this when
 /// tail-call optimization can be enforced?
 const RECURSE_LIMIT: usize = 15;
 
-/// Export a patch containing all currently supported settings.
-pub fn export_settings(settings: &Settings) -> Result<String, Error> {
-    let patch = export_settings_inner(settings)?;
+/// Export a patch containing all currently supported settings and all profiles.
+pub fn export_settings(settings: &Settings, profiles: &[Profile]) -> Result<String, Error> {
+    let patch = export_settings_inner(settings, profiles)?;
     serde_json::to_string_pretty(&patch).map_err(Error::SerializeValue)
 }
 
-fn export_settings_inner(settings: &Settings) -> Result<serde_json::Value, Error> {
+fn export_settings_inner(
+    settings: &Settings,
+    profiles: &[Profile],
+) -> Result<serde_json::Value, Error> {
     let mut out = serde_json::Map::new();
     let mut overrides = vec![];
 
@@ -164,38 +184,74 @@ fn export_settings_inner(settings: &Settings) -> Result<serde_json::Value, Error
         );
     }
 
+    if let Some(connection_plan) = &settings.connection_plan {
+        out.insert(
+            "connection_plan".to_owned(),
+            serde_json::to_value(connection_plan).map_err(Error::SerializeValue)?,
+        );
+    }
+
+    if !profiles.is_empty() {
+        out.insert(
+            PROFILES_KEY.to_owned(),
+            serde_json::to_value(profiles).map_err(Error::SerializeValue)?,
+        );
+    }
+
     Ok(serde_json::Value::Object(out))
 }
 
 /// Update the settings with the supplied patch. Only settings specified in `PERMITTED_SUBKEYS` can
-/// be updated. All other changes are rejected
+/// be updated. All other changes are rejected. Profiles in the patch are added to `profiles`,
+/// replacing existing profiles with the same names. Both the settings and the profiles are
+/// validated before either is saved.
 pub async fn merge_validate_patch(
     settings: &mut SettingsPersister,
+    profiles: &mut ProfileStore,
     json_patch: &str,
 ) -> Result<(), Error> {
-    let new_settings = merge_validate_patch_inner(settings, json_patch)?;
+    let (new_settings, new_profiles) = merge_validate_patch_inner(settings, json_patch)?;
 
+    profiles
+        .import(new_profiles)
+        .await
+        .map_err(Error::Profiles)?;
     settings
-        .update(move |settings| *settings = new_settings)
+        .update("apply_json_settings", move |settings| {
+            *settings = new_settings
+        })
         .await
         .map_err(Error::Settings)?;
 
     Ok(())
 }
 
-fn merge_validate_patch_inner(settings: &Settings, json_patch: &str) -> Result<Settings, Error> {
+fn merge_validate_patch_inner(
+    settings: &Settings,
+    json_patch: &str,
+) -> Result<(Settings, Vec<Profile>), Error> {
     let mut settings_value: serde_json::Value =
         serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
-    let patch_value: serde_json::Value =
+    let mut patch_value: serde_json::Value =
         serde_json::from_str(json_patch).map_err(Error::ParsePatch)?;
 
+    let profiles: Vec<Profile> = match patch_value
+        .as_object_mut()
+        .and_then(|patch| patch.remove(PROFILES_KEY))
+    {
+        Some(profiles) => serde_json::from_value(profiles)
+            .map_err(|_| Error::InvalidOrMissingValue("profiles"))?,
+        None => vec![],
+    };
+    profiles::validate_imported(&profiles).map_err(Error::Profiles)?;
+
     validate_patch_value(PERMITTED_SUBKEYS, &patch_value, 0)?;
     merge_patch_to_value(PERMITTED_SUBKEYS, &mut settings_value, &patch_value, 0)?;
 
     let new_settings: Settings =
         serde_json::from_value(settings_value).map_err(Error::DeserializePatched)?;
 
-    Ok(new_settings)
+    Ok((new_settings, profiles))
 }
 
 /// Replace overrides for existing values in the array if there's a matching hostname. For hostnames
@@ -454,9 +510,64 @@ fn test_valid_patch_files() {
     const OVERRIDE_PATCH: &str =
         include_str!("../../../docs/patch-examples/override-relay-ips.json");
 
+    const CONNECTION_PLAN_PATCH: &str =
+        include_str!("../../../docs/patch-examples/connection-plan.json");
+
     let prev_settings = Settings::default();
     let _ = merge_validate_patch_inner(&prev_settings, OVERRIDE_PATCH)
         .expect("failed to apply relay overrides");
+    let (new_settings, _) = merge_validate_patch_inner(&prev_settings, CONNECTION_PLAN_PATCH)
+        .expect("failed to apply connection plan");
+    assert!(new_settings.connection_plan.is_some());
+}
+
+#[test]
+fn test_patch_connection_plan() {
+    let mut settings = Settings::default();
+    settings.connection_plan = Some(Default::default());
+
+    // Exported plans can be imported again
+    let exported = export_settings(&settings, &[]).unwrap();
+    let (new_settings, _) = merge_validate_patch_inner(&Settings::default(), &exported).unwrap();
+    assert_eq!(new_settings, settings);
+
+    // Setting the plan to null restores the default plan
+    let (new_settings, _) =
+        merge_validate_patch_inner(&settings, r#"{ "connection_plan": null }"#).unwrap();
+    assert_eq!(new_settings.connection_plan, None);
+
+    // Invalid plans are rejected
+    merge_validate_patch_inner(
+        &Settings::default(),
+        r#"{ "connection_plan": { "attempts": [] } }"#,
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn test_patch_profiles() {
+    let mut office = Settings::default();
+    office.allow_lan = true;
+    let profile = Profile::from_settings("office".to_owned(), &office);
+
+    let exported = export_settings(&Settings::default(), &[profile.clone()]).unwrap();
+    let (new_settings, profiles) =
+        merge_validate_patch_inner(&Settings::default(), &exported).unwrap();
+
+    // Importing a profile must not apply it
+    assert_eq!(new_settings, Settings::default());
+    assert_eq!(profiles, vec![profile]);
+
+    merge_validate_patch_inner(&Settings::default(), r#"{ "profiles": [ { "name": 1 } ] }"#)
+        .unwrap_err();
+
+    // Profiles must be valid before the settings in the same patch are saved
+    let unnamed = Profile::from_settings(String::new(), &office);
+    let exported = export_settings(&office, &[unnamed]).unwrap();
+    assert!(matches!(
+        merge_validate_patch_inner(&Settings::default(), &exported),
+        Err(Error::Profiles(profiles::Error::EmptyName))
+    ));
 }
 
 #[test]
@@ -470,7 +581,7 @@ fn test_patch_export() {
     relay_override.ipv6_addr_in = Some("::1".parse().unwrap());
     settings.relay_overrides.push(relay_override);
 
-    let exported = export_settings_inner(&settings).expect("patch export failed");
+    let exported = export_settings_inner(&settings, &[]).expect("patch export failed");
 
     let expected = r#"{ "relay_overrides": [ { "hostname": "test", "ipv4_addr_in": "1.2.3.4", "ipv6_addr_in": "::1" } ] }"#;
     let expected: serde_json::Value = serde_json::from_str(expected).unwrap();
@@ -0,0 +1,201 @@
+//! Named settings profiles. Profiles are stored in a separate file next to the settings file, and
+//! are applied to the settings through [`SettingsPersister::update`](super::SettingsPersister).
+
+use mullvad_types::profile::Profile;
+use std::path::{Path, PathBuf};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+const PROFILES_FILE: &str = "profiles.json";
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "A profile named \"{}\" already exists", _0)]
+    NameAlreadyExists(String),
+
+    #[error(display = "No profile named \"{}\" exists", _0)]
+    ProfileNotFound(String),
+
+    #[error(display = "Profile names must not be empty")]
+    EmptyName,
+
+    #[error(display = "Unable to serialize profiles to JSON")]
+    SerializeError(#[error(source)] serde_json::Error),
+
+    #[error(display = "Unable to write profiles to {}", _0)]
+    WriteError(String, #[error(source)] io::Error),
+
+    #[error(display = "Unable to remove profiles file {}", _0)]
+    DeleteError(String, #[error(source)] io::Error),
+
+    #[error(display = "Failed to apply profile")]
+    Settings(#[error(source)] super::Error),
+}
+
+/// Converts an [Error] to a management interface status
+#[cfg(not(target_os = "android"))]
+impl From<Error> for mullvad_management_interface::Status {
+    fn from(error: Error) -> mullvad_management_interface::Status {
+        use mullvad_management_interface::{Code, Status};
+
+        match error {
+            Error::NameAlreadyExists(..) => Status::new(Code::AlreadyExists, error.to_string()),
+            Error::ProfileNotFound(..) => Status::new(Code::NotFound, error.to_string()),
+            Error::EmptyName => Status::new(Code::InvalidArgument, error.to_string()),
+            Error::WriteError(..) | Error::DeleteError(..) => {
+                Status::new(Code::FailedPrecondition, error.to_string())
+            }
+            Error::SerializeError(..) => Status::new(Code::Internal, error.to_string()),
+            Error::Settings(error) => Status::from(error),
+        }
+    }
+}
+
+pub struct ProfileStore {
+    profiles: Vec<Profile>,
+    path: PathBuf,
+}
+
+impl ProfileStore {
+    /// Loads profiles from file. If it fails, no profiles are used.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(PROFILES_FILE);
+        let profiles = match fs::read(&path).await {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).unwrap_or_else(|error: serde_json::Error| {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to parse profiles. Ignoring them.")
+                    );
+                    vec![]
+                })
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read profiles. Ignoring them.")
+                );
+                vec![]
+            }
+        };
+        ProfileStore { profiles, path }
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Profile, Error> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| Error::ProfileNotFound(name.to_owned()))
+    }
+
+    /// Adds a new profile. Fails if a profile with the same name already exists.
+    pub async fn create(&mut self, profile: Profile) -> Result<(), Error> {
+        if profile.name.is_empty() {
+            return Err(Error::EmptyName);
+        }
+        if self.get(&profile.name).is_ok() {
+            return Err(Error::NameAlreadyExists(profile.name));
+        }
+        let mut profiles = self.profiles.clone();
+        profiles.push(profile);
+        self.replace(profiles).await
+    }
+
+    pub async fn remove(&mut self, name: &str) -> Result<(), Error> {
+        self.get(name)?;
+        let mut profiles = self.profiles.clone();
+        profiles.retain(|profile| profile.name != name);
+        self.replace(profiles).await
+    }
+
+    /// Adds the given profiles, replacing existing profiles that have the same names.
+    pub async fn import(&mut self, imported: Vec<Profile>) -> Result<(), Error> {
+        validate_imported(&imported)?;
+        let mut profiles = self.profiles.clone();
+        for profile in imported {
+            match profiles.iter_mut().find(|p| p.name == profile.name) {
+                Some(existing) => *existing = profile,
+                None => profiles.push(profile),
+            }
+        }
+        self.replace(profiles).await
+    }
+
+    /// Removes all profiles, and the profiles file.
+    pub async fn clear(&mut self) -> Result<(), Error> {
+        match fs::remove_file(&self.path).await {
+            Ok(()) => (),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(Error::DeleteError(self.path.display().to_string(), error)),
+        }
+        self.profiles.clear();
+        Ok(())
+    }
+
+    /// Saves `profiles` to disk, and only then replaces the profiles in memory.
+    async fn replace(&mut self, profiles: Vec<Profile>) -> Result<(), Error> {
+        if profiles == self.profiles {
+            return Ok(());
+        }
+        log::debug!("Writing profiles to {}", self.path.display());
+
+        let buffer = serde_json::to_string_pretty(&profiles).map_err(Error::SerializeError)?;
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
+        file.write_all(&buffer.into_bytes())
+            .await
+            .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
+        file.finalize()
+            .await
+            .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
+
+        self.profiles = profiles;
+        Ok(())
+    }
+}
+
+/// Checks that profiles can be imported, without importing them.
+pub fn validate_imported(profiles: &[Profile]) -> Result<(), Error> {
+    if profiles.iter().any(|profile| profile.name.is_empty()) {
+        return Err(Error::EmptyName);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::settings::Settings;
+
+    /// A factory reset must remove every profile, including those on disk.
+    #[tokio::test]
+    async fn test_clear_removes_profiles() {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let mut profiles = ProfileStore::load(settings_dir.path()).await;
+        profiles
+            .create(Profile::from_settings(
+                "office".to_owned(),
+                &Settings::default(),
+            ))
+            .await
+            .unwrap();
+        assert!(settings_dir.path().join(PROFILES_FILE).exists());
+
+        profiles.clear().await.unwrap();
+        assert!(profiles.profiles().is_empty());
+        assert!(!settings_dir.path().join(PROFILES_FILE).exists());
+
+        let profiles = ProfileStore::load(settings_dir.path()).await;
+        assert!(profiles.profiles().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use mullvad_types::states::TargetState;
 use std::{
     future::Future,
@@ -10,12 +11,17 @@ use tokio::{fs, io};
 /// State to use by default if there is no cache.
 const DEFAULT_TARGET_STATE: TargetState = TargetState::Unsecured;
 const TARGET_START_STATE_FILE: &str = "target-start-state.json";
+/// Deadline of a timed pause. Unlike the target state, this is kept across clean restarts.
+const PAUSE_DEADLINE_FILE: &str = "pause-deadline.json";
 
 /// Persists the target state to a file, which is only removed if the instance is dropped cleanly.
 pub struct PersistentTargetState {
     state: TargetState,
     cache_path: PathBuf,
     locked: bool,
+    /// Time at which the target state returns to `Secured`, if the tunnel is paused.
+    paused_until: Option<DateTime<Utc>>,
+    pause_path: PathBuf,
 }
 
 impl PersistentTargetState {
@@ -26,12 +32,14 @@ impl PersistentTargetState {
             state,
             update_cache,
         } = Self::read_target_state(&cache_path, fs::read_to_string).await;
-        let state = PersistentTargetState {
+        let mut state = PersistentTargetState {
             state,
             cache_path,
             locked: false,
+            paused_until: None,
+            pause_path: cache_dir.join(PAUSE_DEADLINE_FILE),
         };
-        if update_cache {
+        if state.load_pause().await || update_cache {
             state.save().await;
         }
         state
@@ -97,22 +105,117 @@ impl PersistentTargetState {
     /// Override the current target state, if there is one
     pub async fn force(cache_dir: &Path, state: TargetState) -> Self {
         let cache_path = cache_dir.join(TARGET_START_STATE_FILE);
-        let state = PersistentTargetState {
+        let mut state = PersistentTargetState {
             state,
             cache_path,
             locked: false,
+            paused_until: None,
+            pause_path: cache_dir.join(PAUSE_DEADLINE_FILE),
         };
+        state.load_pause().await;
         state.save().await;
         state
     }
 
+    /// Restores a pause from before the daemon was restarted. If it is still in effect, the
+    /// target state becomes `Unsecured`, and if it has expired, `Secured`. Returns whether the
+    /// target state was changed.
+    async fn load_pause(&mut self) -> bool {
+        let deadline = match fs::read_to_string(&self.pause_path).await {
+            Ok(content) => match serde_json::from_str::<DateTime<Utc>>(&content) {
+                Ok(deadline) => deadline,
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to parse cached pause deadline")
+                    );
+                    self.remove_pause_file().await;
+                    return false;
+                }
+            },
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read cached pause deadline")
+                    );
+                }
+                return false;
+            }
+        };
+
+        let new_state = if deadline > Utc::now() {
+            log::info!("Tunnel is paused until {deadline}");
+            self.paused_until = Some(deadline);
+            TargetState::Unsecured
+        } else {
+            log::info!("Pause expired at {deadline} while the daemon was not running");
+            self.remove_pause_file().await;
+            TargetState::Secured
+        };
+        let changed = new_state != self.state;
+        self.state = new_state;
+        changed
+    }
+
+    /// Sets the target state. This ends any ongoing pause.
     pub async fn set(&mut self, new_state: TargetState) {
+        self.clear_pause().await;
         if new_state != self.state {
             self.state = new_state;
             self.save().await;
         }
     }
 
+    /// Sets the target state to `Unsecured` until `deadline`. The caller is responsible for
+    /// setting it back to `Secured` when the deadline has passed.
+    pub async fn pause(&mut self, deadline: DateTime<Utc>) {
+        self.set(TargetState::Unsecured).await;
+        self.paused_until = Some(deadline);
+        match serde_json::to_string(&deadline) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.pause_path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write cached pause deadline")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize pause deadline")
+                )
+            }
+        }
+    }
+
+    /// Ends the pause, if there is one, without changing the target state. Returns whether there
+    /// was a pause.
+    pub async fn clear_pause(&mut self) -> bool {
+        if self.paused_until.take().is_none() {
+            return false;
+        }
+        self.remove_pause_file().await;
+        true
+    }
+
+    /// Returns the time at which the pause ends, if the tunnel is paused.
+    pub fn paused_until(&self) -> Option<DateTime<Utc>> {
+        self.paused_until
+    }
+
+    async fn remove_pause_file(&self) {
+        if let Err(error) = fs::remove_file(&self.pause_path).await {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Cannot delete cached pause deadline")
+                );
+            }
+        }
+    }
+
     /// Prevent the file from being removed when the instance is dropped.
     pub fn lock(&mut self) {
         self.locked = true;
@@ -183,6 +286,25 @@ impl Deref for PersistentTargetState {
     }
 }
 
+/// Returns the target state that the daemon starts in if auto-connect is off, without changing the
+/// cache.
+pub async fn start_state(cache_dir: &Path) -> TargetState {
+    let cached = PersistentTargetState::read_target_state(
+        &cache_dir.join(TARGET_START_STATE_FILE),
+        fs::read_to_string,
+    )
+    .await;
+    let pause_deadline = fs::read_to_string(cache_dir.join(PAUSE_DEADLINE_FILE))
+        .await
+        .ok()
+        .and_then(|content| serde_json::from_str::<DateTime<Utc>>(&content).ok());
+    match pause_deadline {
+        Some(deadline) if deadline > Utc::now() => TargetState::Unsecured,
+        Some(_) => TargetState::Secured,
+        None => cached.state,
+    }
+}
+
 /// The result of calling `read_target_state`.
 struct TargetStateInner {
     state: TargetState,
@@ -248,4 +370,57 @@ mod test {
         // Reading back a corrupt target state cache should yield `TargetState::Secured`.
         assert_eq!(*target_state, TargetState::Secured);
     }
+
+    /// A pause survives a restart, and overrides auto-connect while it is in effect. An expired
+    /// pause makes the target state `Secured`.
+    #[tokio::test]
+    async fn test_pause_across_restarts() {
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let mut target_state = PersistentTargetState::new(cache_dir.path()).await;
+        let deadline = Utc::now() + chrono::Duration::minutes(10);
+        target_state.pause(deadline).await;
+        target_state.finalize().await;
+
+        let target_state =
+            PersistentTargetState::force(cache_dir.path(), TargetState::Secured).await;
+        assert_eq!(*target_state, TargetState::Unsecured);
+        assert_eq!(target_state.paused_until(), Some(deadline));
+        target_state.finalize().await;
+
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        fs::write(
+            cache_dir.path().join(PAUSE_DEADLINE_FILE),
+            serde_json::to_string(&expired).unwrap(),
+        )
+        .await
+        .unwrap();
+        let target_state = PersistentTargetState::new(cache_dir.path()).await;
+        assert_eq!(*target_state, TargetState::Secured);
+        assert_eq!(target_state.paused_until(), None);
+        assert!(!cache_dir.path().join(PAUSE_DEADLINE_FILE).exists());
+    }
+
+    /// Reading the start state leaves the cache as it is.
+    #[tokio::test]
+    async fn test_start_state() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        assert_eq!(start_state(cache_dir.path()).await, DEFAULT_TARGET_STATE);
+
+        let target_state =
+            PersistentTargetState::force(cache_dir.path(), TargetState::Secured).await;
+        assert_eq!(start_state(cache_dir.path()).await, TargetState::Secured);
+        target_state.finalize().await;
+        assert_eq!(start_state(cache_dir.path()).await, TargetState::Unsecured);
+
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        fs::write(
+            cache_dir.path().join(PAUSE_DEADLINE_FILE),
+            serde_json::to_string(&expired).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(start_state(cache_dir.path()).await, TargetState::Secured);
+        assert!(cache_dir.path().join(PAUSE_DEADLINE_FILE).exists());
+    }
 }
@@ -0,0 +1,73 @@
+use crate::{Daemon, Error, EventListener};
+use mullvad_types::user_relay::{self, UserRelay};
+
+impl<L> Daemon<L>
+where
+    L: EventListener + Clone + Send + 'static,
+{
+    /// Adds a user-defined relay, replacing any user-defined relay with the same hostname.
+    pub async fn add_user_relay(&mut self, relay: UserRelay) -> Result<(), Error> {
+        relay.validate().map_err(Error::InvalidUserRelay)?;
+        if self
+            .relay_selector
+            .get_locations()
+            .relays()
+            .any(|other| !other.is_user_defined() && other.hostname == relay.hostname)
+        {
+            return Err(Error::InvalidUserRelay(user_relay::Error::HostnameTaken(
+                relay.hostname,
+            )));
+        }
+
+        let replaced = self
+            .settings
+            .user_relays
+            .iter()
+            .any(|other| other.hostname == relay.hostname);
+
+        let settings_changed = self
+            .settings
+            .update("add_user_relay", move |settings| {
+                settings
+                    .user_relays
+                    .retain(|other| other.hostname != relay.hostname);
+                settings.user_relays.push(relay);
+            })
+            .await
+            .map_err(Error::SettingsError)?;
+
+        if settings_changed {
+            self.event_listener
+                .notify_relay_list(self.relay_selector.get_locations());
+            if replaced {
+                self.reconnect_tunnel();
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn remove_user_relay(&mut self, hostname: String) -> Result<(), Error> {
+        if !self
+            .settings
+            .user_relays
+            .iter()
+            .any(|relay| relay.hostname == hostname)
+        {
+            return Err(Error::UserRelayNotFound);
+        }
+
+        self.settings
+            .update("remove_user_relay", move |settings| {
+                settings
+                    .user_relays
+                    .retain(|relay| relay.hostname != hostname);
+            })
+            .await
+            .map_err(Error::SettingsError)?;
+
+        self.event_listener
+            .notify_relay_list(self.relay_selector.get_locations());
+        self.reconnect_tunnel();
+        Ok(())
+    }
+}
@@ -8,8 +8,10 @@ use jnix::{
 use mullvad_daemon::EventListener;
 use mullvad_types::{
     access_method::AccessMethodSetting,
+    account::ExpiryWarning,
     device::{DeviceEvent, RemoveDeviceEvent},
     relay_list::RelayList,
+    relay_list_changes::RelayListChanges,
     settings::Settings,
     states::TunnelState,
     version::AppVersionInfo,
@@ -77,6 +79,14 @@ impl EventListener for JniEventListener {
     // the Android app.
     #[allow(dead_code, unused_variables)]
     fn notify_new_access_method_event(&self, access_method: AccessMethodSetting) {}
+
+    // TODO: Implement this function when relay list changes are shown in the Android app.
+    #[allow(dead_code, unused_variables)]
+    fn notify_relay_list_changes(&self, changes: RelayListChanges) {}
+
+    // TODO: Implement this function when expiry warnings are shown in the Android app.
+    #[allow(dead_code, unused_variables)]
+    fn notify_expiry_warning(&self, warning: Option<ExpiryWarning>) {}
 }
 
 struct JniEventHandler<'env> {
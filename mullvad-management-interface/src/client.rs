@@ -4,21 +4,31 @@ use crate::types;
 use futures::{Stream, StreamExt};
 use mullvad_types::{
     access_method::{self, AccessMethod, AccessMethodSetting},
-    account::{AccountData, AccountToken, VoucherSubmission},
+    account::{AccountData, AccountToken, ExpiryWarning, VoucherSubmission},
+    connection_plan::ConnectionPlanSimulation,
     custom_list::{CustomList, Id},
     device::{Device, DeviceEvent, DeviceId, DeviceState, RemoveDeviceEvent},
+    logging::{LogFilter, LogFormat},
+    openvpn_profile::ProfileImport,
+    profile::Profile,
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
     },
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    relay_list_changes::RelayListChanges,
+    relay_query::{RelayQuery, RelayQueryResult},
+    settings::{
+        history::{SettingsChange, SettingsSnapshotInfo},
+        DnsOptions, Settings,
+    },
     states::TunnelState,
+    user_relay::UserRelay,
     version::AppVersionInfo,
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
 #[cfg(target_os = "windows")]
 use std::path::Path;
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use tonic::{Code, Status};
@@ -38,6 +48,9 @@ pub enum DaemonEvent {
     Device(DeviceEvent),
     RemoveDevice(RemoveDeviceEvent),
     NewAccessMethod(AccessMethodSetting),
+    RelayListChanges(RelayListChanges),
+    /// The account is about to run out of time. `None` if an earlier warning no longer applies.
+    ExpiryWarning(Option<ExpiryWarning>),
 }
 
 impl TryFrom<types::daemon_event::Event> for DaemonEvent {
@@ -68,6 +81,16 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
                     .map(DaemonEvent::NewAccessMethod)
                     .map_err(Error::InvalidResponse)
             }
+            types::daemon_event::Event::RelayListChanges(changes) => {
+                RelayListChanges::try_from(changes)
+                    .map(DaemonEvent::RelayListChanges)
+                    .map_err(Error::InvalidResponse)
+            }
+            types::daemon_event::Event::ExpiryWarning(state) => {
+                Option::<ExpiryWarning>::try_from(state)
+                    .map(DaemonEvent::ExpiryWarning)
+                    .map_err(Error::InvalidResponse)
+            }
         }
     }
 }
@@ -109,6 +132,13 @@ impl MullvadProxyClient {
             .into_inner())
     }
 
+    /// Disconnect and reconnect automatically once `duration` has passed.
+    pub async fn pause_tunnel(&mut self, duration: Duration) -> Result<()> {
+        let duration = types::Duration::try_from(duration).map_err(|_| Error::DurationTooLarge)?;
+        self.0.pause_tunnel(duration).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn get_tunnel_state(&mut self) -> Result<TunnelState> {
         let state = self
             .0
@@ -119,6 +149,26 @@ impl MullvadProxyClient {
         TunnelState::try_from(state).map_err(Error::InvalidResponse)
     }
 
+    /// Allow captive portal traffic for at most `timeout`, or for the default duration if no
+    /// timeout is given.
+    pub async fn start_captive_portal_login(&mut self, timeout: Option<Duration>) -> Result<()> {
+        let duration = types::Duration::try_from(timeout.unwrap_or_default())
+            .map_err(|_| Error::DurationTooLarge)?;
+        self.0
+            .start_captive_portal_login(duration)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn cancel_captive_portal_login(&mut self) -> Result<()> {
+        self.0
+            .cancel_captive_portal_login(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn events_listen(&mut self) -> Result<impl Stream<Item = Result<DaemonEvent>>> {
         let listener = self
             .0
@@ -136,6 +186,61 @@ impl MullvadProxyClient {
         }))
     }
 
+    pub async fn get_log_filter(&mut self) -> Result<LogFilter> {
+        let filter = self
+            .0
+            .get_log_filter(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        LogFilter::try_from(filter).map_err(Error::InvalidResponse)
+    }
+
+    /// Sets the default log level, which is used for all modules that have no level of their own.
+    pub async fn set_log_level(&mut self, level: log::LevelFilter) -> Result<()> {
+        self.0
+            .set_log_level(types::LogLevelUpdate {
+                module: None,
+                level: Some(i32::from(types::LogLevel::from(level))),
+            })
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Sets the log level of a module and its submodules. If `level` is `None`, the module uses
+    /// the default log level again.
+    pub async fn set_module_log_level(
+        &mut self,
+        module: String,
+        level: Option<log::LevelFilter>,
+    ) -> Result<()> {
+        self.0
+            .set_log_level(types::LogLevelUpdate {
+                module: Some(module),
+                level: level.map(|level| i32::from(types::LogLevel::from(level))),
+            })
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Returns all log records written from now on, formatted as lines.
+    pub async fn stream_logs(
+        &mut self,
+        format: LogFormat,
+    ) -> Result<impl Stream<Item = Result<String>>> {
+        let lines = self
+            .0
+            .stream_logs(types::LogStreamOptions {
+                format: i32::from(types::log_stream_options::Format::from(format)),
+            })
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Ok(lines.map(|line| line.map_err(Error::Rpc)))
+    }
+
     pub async fn prepare_restart(&mut self) -> Result<()> {
         self.0.prepare_restart(()).await.map_err(Error::Rpc)?;
         Ok(())
@@ -175,6 +280,39 @@ impl MullvadProxyClient {
         mullvad_types::relay_list::RelayList::try_from(list).map_err(Error::InvalidResponse)
     }
 
+    pub async fn get_relay_list_changes(&mut self) -> Result<RelayListChanges> {
+        let changes = self
+            .0
+            .get_relay_list_changes(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        RelayListChanges::try_from(changes).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn simulate_connection_plan(
+        &mut self,
+        attempts: u32,
+    ) -> Result<ConnectionPlanSimulation> {
+        let simulation = self
+            .0
+            .simulate_connection_plan(attempts)
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        ConnectionPlanSimulation::try_from(simulation).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn query_relays(&mut self, query: RelayQuery) -> Result<RelayQueryResult> {
+        let result = self
+            .0
+            .query_relays(types::RelayQuery::from(query))
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        RelayQueryResult::try_from(result).map_err(Error::InvalidResponse)
+    }
+
     pub async fn get_api_access_methods(&mut self) -> Result<Vec<AccessMethodSetting>> {
         let access_method_settings = self
             .0
@@ -291,6 +429,57 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    /// Allow traffic outside the tunnel. Adding an exception that already exists has no effect.
+    #[cfg(not(target_os = "windows"))]
+    pub async fn add_firewall_exception(
+        &mut self,
+        exception: talpid_types::net::firewall_exception::FirewallException,
+    ) -> Result<()> {
+        self.0
+            .add_firewall_exception(types::FirewallException::from(exception))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub async fn remove_firewall_exception(
+        &mut self,
+        exception: talpid_types::net::firewall_exception::FirewallException,
+    ) -> Result<()> {
+        self.0
+            .remove_firewall_exception(types::FirewallException::from(exception))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Exclude the traffic and routes of an interface from the tunnel. Adding an interface that
+    /// is already trusted has no effect.
+    #[cfg(target_os = "linux")]
+    pub async fn add_trusted_interface(
+        &mut self,
+        iface: talpid_types::net::trusted_interface::TrustedInterface,
+    ) -> Result<()> {
+        self.0
+            .add_trusted_interface(iface.to_string())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn remove_trusted_interface(
+        &mut self,
+        iface: talpid_types::net::trusted_interface::TrustedInterface,
+    ) -> Result<()> {
+        self.0
+            .remove_trusted_interface(iface.to_string())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_show_beta_releases(&mut self, state: bool) -> Result<()> {
         self.0
             .set_show_beta_releases(state)
@@ -328,6 +517,25 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_wireguard_mtu_discovery(&mut self, enabled: bool) -> Result<()> {
+        self.0
+            .set_wireguard_mtu_discovery(enabled)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_wireguard_connectivity_check(
+        &mut self,
+        options: talpid_types::net::wireguard::ConnectivityCheckOptions,
+    ) -> Result<()> {
+        self.0
+            .set_wireguard_connectivity_check(types::ConnectivityCheckOptions::from(options))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_enable_ipv6(&mut self, state: bool) -> Result<()> {
         self.0.set_enable_ipv6(state).await.map_err(Error::Rpc)?;
         Ok(())
@@ -368,6 +576,39 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    /// Add a user-defined relay, replacing any user-defined relay with the same hostname.
+    pub async fn add_user_relay(&mut self, relay: UserRelay) -> Result<()> {
+        self.0
+            .add_user_relay(types::UserRelay::from(relay))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn remove_user_relay(&mut self, hostname: String) -> Result<()> {
+        self.0
+            .remove_user_relay(hostname)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Parse an OpenVPN profile and use it as a custom tunnel endpoint. Files referenced by the
+    /// profile must already be inlined. If `dry_run` is set, no settings are changed.
+    pub async fn import_openvpn_profile(
+        &mut self,
+        contents: String,
+        dry_run: bool,
+    ) -> Result<ProfileImport> {
+        let import = self
+            .0
+            .import_openvpn_profile(types::OpenvpnProfile { contents, dry_run })
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        ProfileImport::try_from(import).map_err(Error::InvalidResponse)
+    }
+
     pub async fn create_new_account(&mut self) -> Result<AccountToken> {
         Ok(self
             .0
@@ -431,6 +672,24 @@ impl MullvadProxyClient {
         VoucherSubmission::try_from(result).map_err(Error::InvalidResponse)
     }
 
+    pub async fn get_expiry_warning(&mut self) -> Result<Option<ExpiryWarning>> {
+        let state = self
+            .0
+            .get_expiry_warning(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Option::<ExpiryWarning>::try_from(state).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn set_expiry_warning_thresholds(&mut self, thresholds: Vec<Duration>) -> Result<()> {
+        self.0
+            .set_expiry_warning_thresholds(types::ExpiryWarningThresholds::from(thresholds))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn get_device(&mut self) -> Result<DeviceState> {
         let state = self
             .0
@@ -536,6 +795,14 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_prune_custom_lists(&mut self, enabled: bool) -> Result<()> {
+        self.0
+            .set_prune_custom_lists(enabled)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn add_access_method(
         &mut self,
         name: String,
@@ -696,6 +963,77 @@ impl MullvadProxyClient {
         let blob = self.0.export_json_settings(()).await.map_err(Error::Rpc)?;
         Ok(blob.into_inner())
     }
+
+    pub async fn create_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .create_profile(name)
+            .await
+            .map_err(map_profile_error)?;
+        Ok(())
+    }
+
+    pub async fn get_profiles(&mut self) -> Result<Vec<Profile>> {
+        let list = self
+            .0
+            .get_profiles(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        list.profiles
+            .into_iter()
+            .map(|profile| Profile::try_from(profile).map_err(Error::InvalidResponse))
+            .collect::<Result<_>>()
+    }
+
+    pub async fn apply_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .apply_profile(name)
+            .await
+            .map_err(map_profile_error)?;
+        Ok(())
+    }
+
+    pub async fn delete_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .delete_profile(name)
+            .await
+            .map_err(map_profile_error)?;
+        Ok(())
+    }
+
+    pub async fn get_settings_history(&mut self) -> Result<Vec<SettingsSnapshotInfo>> {
+        let history = self
+            .0
+            .get_settings_history(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        history
+            .snapshots
+            .into_iter()
+            .map(|snapshot| {
+                SettingsSnapshotInfo::try_from(snapshot).map_err(Error::InvalidResponse)
+            })
+            .collect::<Result<_>>()
+    }
+
+    pub async fn diff_settings_snapshot(&mut self, id: u32) -> Result<Vec<SettingsChange>> {
+        let diff = self
+            .0
+            .diff_settings_snapshot(id)
+            .await
+            .map_err(map_settings_snapshot_error)?
+            .into_inner();
+        Ok(diff.changes.into_iter().map(SettingsChange::from).collect())
+    }
+
+    pub async fn restore_settings_snapshot(&mut self, id: u32) -> Result<()> {
+        self.0
+            .restore_settings_snapshot(id)
+            .await
+            .map_err(map_settings_snapshot_error)?;
+        Ok(())
+    }
 }
 
 fn map_device_error(status: Status) -> Error {
@@ -727,3 +1065,18 @@ fn map_custom_list_error(status: Status) -> Error {
         _other => Error::Rpc(status),
     }
 }
+
+fn map_profile_error(status: Status) -> Error {
+    match status.code() {
+        Code::NotFound => Error::ProfileNotFound,
+        Code::AlreadyExists => Error::ProfileExists,
+        _other => Error::Rpc(status),
+    }
+}
+
+fn map_settings_snapshot_error(status: Status) -> Error {
+    match status.code() {
+        Code::NotFound => Error::SettingsSnapshotNotFound,
+        _other => Error::Rpc(status),
+    }
+}
@@ -1,17 +1,18 @@
 pub mod client;
 pub mod types;
+#[cfg(windows)]
+mod windows;
 
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
 #[cfg(unix)]
-use std::{env, fs, os::unix::fs::PermissionsExt};
 use std::{
-    future::Future,
-    io,
+    env, fs,
+    os::unix::fs::PermissionsExt,
     pin::Pin,
     task::{Context, Poll},
 };
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tonic::transport::{server::Connected, Endpoint, Server, Uri};
+use std::{future::Future, io};
+use tonic::transport::{Endpoint, Server, Uri};
 use tower::service_fn;
 
 pub use tonic::{async_trait, transport::Channel, Code, Request, Response, Status};
@@ -38,6 +39,7 @@ pub enum Error {
     #[error(display = "Failed to start IPC pipe/socket")]
     StartServerError(#[error(source)] io::Error),
 
+    #[cfg(windows)]
     #[error(display = "Failed to initialize pipe/socket security attributes")]
     SecurityAttributes(#[error(source)] io::Error),
 
@@ -109,6 +111,15 @@ pub enum Error {
 
     #[error(display = "An access method with that id does not exist")]
     ApiAccessMethodNotFound,
+
+    #[error(display = "A profile with that name already exists")]
+    ProfileExists,
+
+    #[error(display = "A profile with that name does not exist")]
+    ProfileNotFound,
+
+    #[error(display = "A settings snapshot with that ID does not exist")]
+    SettingsSnapshotNotFound,
 }
 
 #[deprecated(note = "Prefer MullvadProxyClient")]
@@ -134,71 +145,156 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
     service: T,
     abort_rx: F,
 ) -> std::result::Result<ServerJoinHandle, Error> {
-    use futures::stream::TryStreamExt;
-    use parity_tokio_ipc::SecurityAttributes;
-
     let socket_path = mullvad_paths::get_rpc_socket_path();
 
-    let mut endpoint = IpcEndpoint::new(socket_path.to_string_lossy().to_string());
-    endpoint.set_security_attributes(
-        SecurityAttributes::allow_everyone_create()
-            .map_err(Error::SecurityAttributes)?
-            .set_mode(0o766)
-            .map_err(Error::SecurityAttributes)?,
-    );
-    let incoming = endpoint.incoming().map_err(Error::StartServerError)?;
-
     #[cfg(unix)]
-    if let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
-        let group = nix::unistd::Group::from_name(group_name)
-            .map_err(Error::ObtainGidError)?
-            .ok_or(Error::NoGidError)?;
-        nix::unistd::chown(&socket_path, None, Some(group.gid)).map_err(Error::SetGidError)?;
-        fs::set_permissions(&socket_path, PermissionsExt::from_mode(0o760))
-            .map_err(Error::PermissionsError)?;
-    }
+    let incoming = SocketIncoming::bind(socket_path)?;
+
+    #[cfg(windows)]
+    let incoming = windows::incoming(socket_path.to_string_lossy().to_string())?;
 
     Ok(tokio::spawn(async move {
         Server::builder()
             .add_service(ManagementServiceServer::new(service))
-            .serve_with_incoming_shutdown(incoming.map_ok(StreamBox), abort_rx)
+            .serve_with_incoming_shutdown(incoming, abort_rx)
             .await
             .map_err(Error::GrpcTransportError)
     }))
 }
 
-#[derive(Debug)]
-struct StreamBox<T: AsyncRead + AsyncWrite>(pub T);
-impl<T: AsyncRead + AsyncWrite> Connected for StreamBox<T> {
-    type ConnectInfo = Option<()>;
+/// Returns an error unless the client that sent `request` runs as root or as a member of the
+/// group set by `MULLVAD_MANAGEMENT_SOCKET_GROUP`. Used for calls that expose or change more
+/// than the socket permissions alone should allow.
+#[cfg(unix)]
+pub fn authorize_privileged<T>(request: &Request<T>) -> Result<(), Status> {
+    use tonic::transport::server::UdsConnectInfo;
+
+    let credentials = request
+        .extensions()
+        .get::<UdsConnectInfo>()
+        .and_then(|info| info.peer_cred);
+    let Some(credentials) = credentials else {
+        return Err(Status::permission_denied(
+            "Failed to obtain the credentials of the client",
+        ));
+    };
+    if credentials.uid() == 0 || is_management_group_member(credentials.uid(), credentials.gid()) {
+        return Ok(());
+    }
+    Err(Status::permission_denied(
+        "Only root or members of the management socket group may do this",
+    ))
+}
+
+/// Returns an error unless the client that sent `request` runs as SYSTEM or as an elevated
+/// administrator.
+#[cfg(windows)]
+pub fn authorize_privileged<T>(request: &Request<T>) -> Result<(), Status> {
+    let Some(info) = request.extensions().get::<windows::PipeConnectInfo>() else {
+        return Err(Status::permission_denied(
+            "Failed to obtain the credentials of the client",
+        ));
+    };
+    if info.privileged {
+        return Ok(());
+    }
+    Err(Status::permission_denied(
+        "Only SYSTEM or elevated administrators may do this",
+    ))
+}
 
-    fn connect_info(&self) -> Self::ConnectInfo {
-        None
+#[cfg(unix)]
+fn is_management_group_member(uid: u32, gid: u32) -> bool {
+    use nix::unistd::{Group, Uid, User};
+
+    let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP else {
+        return false;
+    };
+    let Ok(Some(group)) = Group::from_name(group_name) else {
+        return false;
+    };
+    if group.gid.as_raw() == gid {
+        return true;
+    }
+    match User::from_uid(Uid::from_raw(uid)) {
+        Ok(Some(user)) => group.mem.contains(&user.name),
+        _ => false,
     }
 }
-impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for StreamBox<T> {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.0).poll_read(cx, buf)
+
+/// Accepts connections on the management socket. Unlike `parity_tokio_ipc`, this yields
+/// `UnixStream`s, which lets tonic attach the credentials of the client to each request. The
+/// socket file is removed when this is dropped.
+#[cfg(unix)]
+struct SocketIncoming {
+    listener: tokio::net::UnixListener,
+    path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl SocketIncoming {
+    fn bind(path: std::path::PathBuf) -> Result<Self, Error> {
+        let listener = tokio::net::UnixListener::bind(&path).map_err(Error::StartServerError)?;
+        let incoming = SocketIncoming { listener, path };
+
+        if let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
+            let group = nix::unistd::Group::from_name(group_name)
+                .map_err(Error::ObtainGidError)?
+                .ok_or(Error::NoGidError)?;
+            nix::unistd::chown(&incoming.path, None, Some(group.gid))
+                .map_err(Error::SetGidError)?;
+            fs::set_permissions(&incoming.path, PermissionsExt::from_mode(0o760))
+                .map_err(Error::PermissionsError)?;
+        } else {
+            fs::set_permissions(&incoming.path, PermissionsExt::from_mode(0o766))
+                .map_err(Error::PermissionsError)?;
+        }
+        Ok(incoming)
     }
 }
-impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for StreamBox<T> {
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.0).poll_write(cx, buf)
+
+#[cfg(unix)]
+impl futures::Stream for SocketIncoming {
+    type Item = io::Result<tokio::net::UnixStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|result| Some(result.map(|(stream, _addr)| stream)))
     }
+}
 
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.0).poll_flush(cx)
+#[cfg(unix)]
+impl Drop for SocketIncoming {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            log::warn!("Failed to remove IPC socket: {error}");
+        }
     }
+}
 
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.0).poll_shutdown(cx)
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_socket_incoming() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mullvad-vpn.sock");
+        let mut incoming = SocketIncoming::bind(path.clone()).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o766);
+
+        // Accepted connections carry the credentials of the client
+        let _client = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let stream = incoming.next().await.unwrap().unwrap();
+        let credentials = tonic::transport::server::Connected::connect_info(&stream)
+            .peer_cred
+            .unwrap();
+        assert_eq!(credentials.uid(), nix::unistd::getuid().as_raw());
+
+        drop(incoming);
+        assert!(!path.exists());
     }
 }
@@ -1,6 +1,7 @@
 use crate::types;
 use chrono::TimeZone;
-use mullvad_types::account::{AccountData, VoucherSubmission};
+use mullvad_types::account::{AccountData, ExpiryWarning, VoucherSubmission};
+use std::time::Duration;
 
 use super::FromProtobufTypeError;
 
@@ -62,3 +63,86 @@ impl TryFrom<types::AccountData> for AccountData {
         })
     }
 }
+
+impl From<ExpiryWarning> for types::ExpiryWarning {
+    fn from(warning: ExpiryWarning) -> Self {
+        types::ExpiryWarning {
+            expiry: Some(types::Timestamp {
+                seconds: warning.expiry.timestamp(),
+                nanos: 0,
+            }),
+            threshold: Some(
+                types::Duration::try_from(warning.threshold)
+                    .expect("Failed to convert std::time::Duration to prost_types::Duration"),
+            ),
+        }
+    }
+}
+
+impl TryFrom<types::ExpiryWarning> for ExpiryWarning {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(warning: types::ExpiryWarning) -> Result<Self, FromProtobufTypeError> {
+        let expiry = warning
+            .expiry
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing expiry"))?;
+        let ndt =
+            chrono::NaiveDateTime::from_timestamp_opt(expiry.seconds, expiry.nanos as u32).unwrap();
+        let threshold = warning
+            .threshold
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing threshold"))
+            .and_then(try_threshold_from_proto)?;
+
+        Ok(ExpiryWarning {
+            expiry: chrono::Utc.from_utc_datetime(&ndt),
+            threshold,
+        })
+    }
+}
+
+impl From<Option<ExpiryWarning>> for types::ExpiryWarningState {
+    fn from(warning: Option<ExpiryWarning>) -> Self {
+        types::ExpiryWarningState {
+            warning: warning.map(types::ExpiryWarning::from),
+        }
+    }
+}
+
+impl TryFrom<types::ExpiryWarningState> for Option<ExpiryWarning> {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(state: types::ExpiryWarningState) -> Result<Self, FromProtobufTypeError> {
+        state.warning.map(ExpiryWarning::try_from).transpose()
+    }
+}
+
+impl From<Vec<Duration>> for types::ExpiryWarningThresholds {
+    fn from(thresholds: Vec<Duration>) -> Self {
+        types::ExpiryWarningThresholds {
+            thresholds: thresholds
+                .into_iter()
+                .map(|threshold| {
+                    types::Duration::try_from(threshold)
+                        .expect("Failed to convert std::time::Duration to prost_types::Duration")
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<types::ExpiryWarningThresholds> for Vec<Duration> {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(thresholds: types::ExpiryWarningThresholds) -> Result<Self, FromProtobufTypeError> {
+        thresholds
+            .thresholds
+            .into_iter()
+            .map(try_threshold_from_proto)
+            .collect()
+    }
+}
+
+fn try_threshold_from_proto(threshold: types::Duration) -> Result<Duration, FromProtobufTypeError> {
+    Duration::try_from(threshold)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid expiry warning threshold"))
+}
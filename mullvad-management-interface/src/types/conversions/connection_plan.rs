@@ -0,0 +1,197 @@
+use crate::types::{
+    conversions::{
+        arg_from_str,
+        net::{try_transport_protocol_from_i32, try_tunnel_type_from_i32},
+    },
+    proto, FromProtobufTypeError,
+};
+use mullvad_types::{
+    connection_plan::{
+        AttemptOutcome, AttemptSelection, AttemptTemplate, ConnectionPlan,
+        ConnectionPlanSimulation, SimulatedAttempt,
+    },
+    relay_constraints::{Constraint, TransportPort},
+};
+use talpid_types::net::{Endpoint, TunnelType};
+
+fn tunnel_type_to_proto(tunnel_type: TunnelType) -> i32 {
+    match tunnel_type {
+        TunnelType::Wireguard => i32::from(proto::TunnelType::Wireguard),
+        TunnelType::OpenVpn => i32::from(proto::TunnelType::Openvpn),
+    }
+}
+
+impl From<AttemptTemplate> for proto::ConnectionAttemptTemplate {
+    fn from(template: AttemptTemplate) -> Self {
+        proto::ConnectionAttemptTemplate {
+            tunnel_type: tunnel_type_to_proto(template.tunnel_type),
+            wireguard_port: template.wireguard_port.map(u32::from).option(),
+            openvpn_port: Some(proto::TransportPort::from(template.openvpn_port)),
+            openvpn_only_port: Some(proto::TransportPort::from(template.openvpn_only_port)),
+            use_bridge: template.use_bridge,
+            auto_obfuscation: template.auto_obfuscation,
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionAttemptTemplate> for AttemptTemplate {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(template: proto::ConnectionAttemptTemplate) -> Result<Self, Self::Error> {
+        let port = |port: Option<proto::TransportPort>| {
+            port.ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing OpenVPN port in attempt template",
+            ))
+            .and_then(TransportPort::try_from)
+        };
+        Ok(AttemptTemplate {
+            tunnel_type: try_tunnel_type_from_i32(template.tunnel_type)?,
+            wireguard_port: Constraint::from(template.wireguard_port.map(|port| port as u16)),
+            openvpn_port: port(template.openvpn_port)?,
+            openvpn_only_port: port(template.openvpn_only_port)?,
+            use_bridge: template.use_bridge,
+            auto_obfuscation: template.auto_obfuscation,
+        })
+    }
+}
+
+impl From<&ConnectionPlan> for proto::ConnectionPlan {
+    fn from(plan: &ConnectionPlan) -> Self {
+        proto::ConnectionPlan {
+            attempts: plan
+                .attempts()
+                .iter()
+                .copied()
+                .map(proto::ConnectionAttemptTemplate::from)
+                .collect(),
+            repeat_from: u32::try_from(plan.repeat_from()).unwrap_or(u32::MAX),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionPlan> for ConnectionPlan {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(plan: proto::ConnectionPlan) -> Result<Self, Self::Error> {
+        let attempts = plan
+            .attempts
+            .into_iter()
+            .map(AttemptTemplate::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        ConnectionPlan::new(attempts, plan.repeat_from as usize)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid connection plan"))
+    }
+}
+
+impl From<ConnectionPlanSimulation> for proto::ConnectionPlanSimulation {
+    fn from(simulation: ConnectionPlanSimulation) -> Self {
+        proto::ConnectionPlanSimulation {
+            plan: Some(proto::ConnectionPlan::from(&simulation.plan)),
+            is_default: simulation.is_default,
+            attempts: simulation
+                .attempts
+                .into_iter()
+                .map(proto::connection_plan_simulation::Attempt::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<SimulatedAttempt> for proto::connection_plan_simulation::Attempt {
+    fn from(attempt: SimulatedAttempt) -> Self {
+        use proto::connection_plan_simulation::{attempt::Outcome, Selection};
+
+        let outcome = match attempt.outcome {
+            AttemptOutcome::Selected(selection) => Outcome::Selection(Selection {
+                tunnel_type: tunnel_type_to_proto(selection.tunnel_type),
+                exit: selection.exit,
+                entry: selection.entry,
+                endpoint: Some(proto::Endpoint {
+                    address: selection.endpoint.address.to_string(),
+                    protocol: i32::from(proto::TransportProtocol::from(
+                        selection.endpoint.protocol,
+                    )),
+                }),
+                bridge: selection.bridge,
+                obfuscator: selection.obfuscator.map(|address| address.to_string()),
+            }),
+            AttemptOutcome::Failed(error) => Outcome::Error(error),
+        };
+        proto::connection_plan_simulation::Attempt {
+            attempt: attempt.attempt,
+            template: Some(proto::ConnectionAttemptTemplate::from(attempt.template)),
+            outcome: Some(outcome),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionPlanSimulation> for ConnectionPlanSimulation {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(simulation: proto::ConnectionPlanSimulation) -> Result<Self, Self::Error> {
+        let plan = simulation
+            .plan
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing connection plan",
+            ))
+            .and_then(ConnectionPlan::try_from)?;
+        Ok(ConnectionPlanSimulation {
+            plan,
+            is_default: simulation.is_default,
+            attempts: simulation
+                .attempts
+                .into_iter()
+                .map(SimulatedAttempt::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<proto::connection_plan_simulation::Attempt> for SimulatedAttempt {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(attempt: proto::connection_plan_simulation::Attempt) -> Result<Self, Self::Error> {
+        use proto::connection_plan_simulation::attempt::Outcome;
+
+        let template = attempt
+            .template
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing attempt template",
+            ))
+            .and_then(AttemptTemplate::try_from)?;
+        let outcome = match attempt.outcome {
+            Some(Outcome::Selection(selection)) => {
+                let endpoint = selection
+                    .endpoint
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "missing selected endpoint",
+                    ))?;
+                AttemptOutcome::Selected(AttemptSelection {
+                    tunnel_type: try_tunnel_type_from_i32(selection.tunnel_type)?,
+                    exit: selection.exit,
+                    entry: selection.entry,
+                    endpoint: Endpoint {
+                        address: arg_from_str(&endpoint.address, "invalid endpoint address")?,
+                        protocol: try_transport_protocol_from_i32(endpoint.protocol)?,
+                    },
+                    bridge: selection.bridge,
+                    obfuscator: selection
+                        .obfuscator
+                        .map(|address| arg_from_str(&address, "invalid obfuscator address"))
+                        .transpose()?,
+                })
+            }
+            Some(Outcome::Error(error)) => AttemptOutcome::Failed(error),
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "missing attempt outcome",
+                ))
+            }
+        };
+        Ok(SimulatedAttempt {
+            attempt: attempt.attempt,
+            template,
+            outcome,
+        })
+    }
+}
@@ -9,6 +9,7 @@ use mullvad_types::{
 impl From<mullvad_types::custom_list::CustomListsSettings> for proto::CustomListSettings {
     fn from(settings: mullvad_types::custom_list::CustomListsSettings) -> Self {
         Self {
+            prune_removed_relays: settings.prune_removed_relays(),
             custom_lists: settings.into_iter().map(proto::CustomList::from).collect(),
         }
     }
@@ -18,13 +19,15 @@ impl TryFrom<proto::CustomListSettings> for mullvad_types::custom_list::CustomLi
     type Error = FromProtobufTypeError;
 
     fn try_from(settings: proto::CustomListSettings) -> Result<Self, Self::Error> {
-        Ok(Self::from(
+        let mut custom_lists = Self::from(
             settings
                 .custom_lists
                 .into_iter()
                 .map(mullvad_types::custom_list::CustomList::try_from)
                 .collect::<Result<Vec<CustomList>, _>>()?,
-        ))
+        );
+        custom_lists.set_prune_removed_relays(settings.prune_removed_relays);
+        Ok(custom_lists)
     }
 }
 
@@ -32,6 +32,11 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                         },
                         username: config.username,
                         password: config.password,
+                        key_material: config
+                            .key_material
+                            .map(openvpn::KeyMaterial::try_from)
+                            .transpose()?
+                            .unwrap_or_default(),
                     },
                 ))
             }
@@ -104,6 +109,49 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
     }
 }
 
+impl TryFrom<proto::connection_config::OpenvpnKeyMaterial>
+    for talpid_types::net::openvpn::KeyMaterial
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(
+        key_material: proto::connection_config::OpenvpnKeyMaterial,
+    ) -> Result<Self, Self::Error> {
+        let key_direction = key_material
+            .key_direction
+            .map(|direction| match direction {
+                0 | 1 => Ok(direction as u8),
+                _ => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid key direction",
+                )),
+            })
+            .transpose()?;
+        Ok(Self {
+            ca: key_material.ca,
+            cert: key_material.cert,
+            key: key_material.key,
+            tls_auth: key_material.tls_auth,
+            key_direction,
+            tls_crypt: key_material.tls_crypt,
+        })
+    }
+}
+
+impl From<talpid_types::net::openvpn::KeyMaterial>
+    for proto::connection_config::OpenvpnKeyMaterial
+{
+    fn from(key_material: talpid_types::net::openvpn::KeyMaterial) -> Self {
+        Self {
+            ca: key_material.ca,
+            cert: key_material.cert,
+            key: key_material.key,
+            tls_auth: key_material.tls_auth,
+            key_direction: key_material.key_direction.map(u32::from),
+            tls_crypt: key_material.tls_crypt,
+        }
+    }
+}
+
 impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
     fn from(config: mullvad_types::ConnectionConfig) -> Self {
         use proto::connection_config;
@@ -118,6 +166,9 @@ impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
                         )),
                         username: config.username,
                         password: config.password,
+                        key_material: Some(connection_config::OpenvpnKeyMaterial::from(
+                            config.key_material,
+                        )),
                     })
                 }
                 mullvad_types::ConnectionConfig::Wireguard(config) => {
@@ -152,3 +203,68 @@ impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
         }
     }
 }
+
+impl From<mullvad_types::openvpn_profile::ProfileImport> for proto::OpenvpnProfileImport {
+    fn from(import: mullvad_types::openvpn_profile::ProfileImport) -> Self {
+        use proto::openvpn_profile_import::UnsupportedDirective;
+
+        Self {
+            relay: Some(proto::CustomRelaySettings {
+                host: import.endpoint.host,
+                config: Some(proto::ConnectionConfig::from(import.endpoint.config)),
+            }),
+            options: Some(proto::tunnel_options::OpenvpnOptions {
+                mssfix: import.options.mssfix.map(u32::from),
+            }),
+            unsupported: import
+                .unsupported
+                .into_iter()
+                .map(|directive| UnsupportedDirective {
+                    line: directive.line as u64,
+                    directive: directive.directive,
+                    reason: directive.reason,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::OpenvpnProfileImport> for mullvad_types::openvpn_profile::ProfileImport {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(import: proto::OpenvpnProfileImport) -> Result<Self, Self::Error> {
+        use mullvad_types::openvpn_profile::UnsupportedDirective;
+        use talpid_types::net::openvpn;
+
+        let relay = import
+            .relay
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing relay"))?;
+        let config = relay.config.ok_or(FromProtobufTypeError::InvalidArgument(
+            "missing relay connection config",
+        ))?;
+        let options = import
+            .options
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing openvpn options",
+            ))?;
+
+        Ok(Self {
+            endpoint: mullvad_types::CustomTunnelEndpoint::new(
+                relay.host,
+                mullvad_types::ConnectionConfig::try_from(config)?,
+            ),
+            options: openvpn::TunnelOptions {
+                mssfix: options.mssfix.map(|mssfix| mssfix as u16),
+            },
+            unsupported: import
+                .unsupported
+                .into_iter()
+                .map(|directive| UnsupportedDirective {
+                    line: directive.line as usize,
+                    directive: directive.directive,
+                    reason: directive.reason,
+                })
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use crate::types::{proto, FromProtobufTypeError};
+use log::LevelFilter;
+use mullvad_types::logging::{LogFilter, LogFormat};
+
+impl From<LevelFilter> for proto::LogLevel {
+    fn from(level: LevelFilter) -> Self {
+        match level {
+            LevelFilter::Off => proto::LogLevel::Off,
+            LevelFilter::Error => proto::LogLevel::Error,
+            LevelFilter::Warn => proto::LogLevel::Warn,
+            LevelFilter::Info => proto::LogLevel::Info,
+            LevelFilter::Debug => proto::LogLevel::Debug,
+            LevelFilter::Trace => proto::LogLevel::Trace,
+        }
+    }
+}
+
+impl From<proto::LogLevel> for LevelFilter {
+    fn from(level: proto::LogLevel) -> Self {
+        match level {
+            proto::LogLevel::Off => LevelFilter::Off,
+            proto::LogLevel::Error => LevelFilter::Error,
+            proto::LogLevel::Warn => LevelFilter::Warn,
+            proto::LogLevel::Info => LevelFilter::Info,
+            proto::LogLevel::Debug => LevelFilter::Debug,
+            proto::LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+pub fn try_log_level_from_i32(level: i32) -> Result<LevelFilter, FromProtobufTypeError> {
+    Ok(proto::LogLevel::try_from(level)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid log level"))?
+        .into())
+}
+
+impl From<&LogFilter> for proto::LogFilter {
+    fn from(filter: &LogFilter) -> Self {
+        Self {
+            level: i32::from(proto::LogLevel::from(filter.level)),
+            modules: filter
+                .modules
+                .iter()
+                .map(|(module, level)| proto::ModuleLogLevel {
+                    module: module.clone(),
+                    level: i32::from(proto::LogLevel::from(*level)),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::LogFilter> for LogFilter {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(filter: proto::LogFilter) -> Result<Self, Self::Error> {
+        let mut log_filter = LogFilter::new(try_log_level_from_i32(filter.level)?);
+        for module in filter.modules {
+            log_filter
+                .modules
+                .insert(module.module, try_log_level_from_i32(module.level)?);
+        }
+        Ok(log_filter)
+    }
+}
+
+impl From<LogFormat> for proto::log_stream_options::Format {
+    fn from(format: LogFormat) -> Self {
+        match format {
+            LogFormat::Text => proto::log_stream_options::Format::Text,
+            LogFormat::Json => proto::log_stream_options::Format::Json,
+        }
+    }
+}
+
+pub fn try_log_format_from_i32(format: i32) -> Result<LogFormat, FromProtobufTypeError> {
+    match proto::log_stream_options::Format::try_from(format) {
+        Ok(proto::log_stream_options::Format::Text) => Ok(LogFormat::Text),
+        Ok(proto::log_stream_options::Format::Json) => Ok(LogFormat::Json),
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid log stream format",
+        )),
+    }
+}
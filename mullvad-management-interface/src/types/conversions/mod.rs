@@ -2,13 +2,16 @@ use std::str::FromStr;
 
 mod access_method;
 mod account;
+mod connection_plan;
 mod custom_list;
 mod custom_tunnel;
 mod device;
 mod location;
+mod logging;
 mod net;
 pub mod relay_constraints;
 mod relay_list;
+mod relay_query;
 mod settings;
 #[cfg(target_os = "windows")]
 mod split_tunnel;
@@ -16,6 +19,8 @@ mod states;
 mod version;
 mod wireguard;
 
+pub use logging::{try_log_format_from_i32, try_log_level_from_i32};
+
 #[derive(err_derive::Error, Debug)]
 pub enum FromProtobufTypeError {
     #[error(display = "Invalid argument for type conversion: {}", _0)]
@@ -37,9 +37,12 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                 address: entry.address.to_string(),
                 protocol: i32::from(proto::TransportProtocol::from(entry.protocol)),
             }),
-            tunnel_metadata: endpoint
-                .tunnel_interface
-                .map(|tunnel_interface| proto::TunnelMetadata { tunnel_interface }),
+            tunnel_metadata: endpoint.tunnel_interface.map(|tunnel_interface| {
+                proto::TunnelMetadata {
+                    tunnel_interface,
+                    discovered_mtu: endpoint.discovered_mtu.map(u32::from),
+                }
+            }),
         }
     }
 }
@@ -120,6 +123,11 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                     })
                 })
                 .transpose()?,
+            discovered_mtu: endpoint
+                .tunnel_metadata
+                .as_ref()
+                .and_then(|tunnel_metadata| tunnel_metadata.discovered_mtu)
+                .map(|mtu| mtu as u16),
             tunnel_interface: endpoint
                 .tunnel_metadata
                 .map(|tunnel_metadata| tunnel_metadata.tunnel_interface),
@@ -183,6 +191,63 @@ pub fn try_transport_protocol_from_i32(
         .into())
 }
 
+impl From<talpid_types::net::firewall_exception::FirewallException> for proto::FirewallException {
+    fn from(exception: talpid_types::net::firewall_exception::FirewallException) -> Self {
+        use talpid_types::net::firewall_exception::Direction;
+
+        let direction = match exception.direction {
+            Direction::Both => proto::firewall_exception::Direction::Both,
+            Direction::Outbound => proto::firewall_exception::Direction::Outbound,
+            Direction::Inbound => proto::firewall_exception::Direction::Inbound,
+        };
+        proto::FirewallException {
+            network: exception.network.to_string(),
+            protocol: exception
+                .protocol
+                .map(|protocol| i32::from(proto::TransportProtocol::from(protocol))),
+            port: exception.port.map(u32::from),
+            direction: i32::from(direction),
+        }
+    }
+}
+
+impl TryFrom<proto::FirewallException>
+    for talpid_types::net::firewall_exception::FirewallException
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(exception: proto::FirewallException) -> Result<Self, Self::Error> {
+        use talpid_types::net::firewall_exception::Direction;
+
+        let direction = match proto::firewall_exception::Direction::try_from(exception.direction) {
+            Ok(proto::firewall_exception::Direction::Both) => Direction::Both,
+            Ok(proto::firewall_exception::Direction::Outbound) => Direction::Outbound,
+            Ok(proto::firewall_exception::Direction::Inbound) => Direction::Inbound,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid firewall exception direction",
+                ))
+            }
+        };
+        Ok(Self {
+            network: arg_from_str(&exception.network, "invalid firewall exception network")?,
+            protocol: exception
+                .protocol
+                .map(try_transport_protocol_from_i32)
+                .transpose()?,
+            port: exception
+                .port
+                .map(|port| {
+                    u16::try_from(port).map_err(|_| {
+                        FromProtobufTypeError::InvalidArgument("invalid firewall exception port")
+                    })
+                })
+                .transpose()?,
+            direction,
+        })
+    }
+}
+
 mod proxy {
     use std::net::Ipv4Addr;
 
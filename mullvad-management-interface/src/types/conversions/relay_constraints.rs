@@ -1,7 +1,8 @@
 use crate::types::{conversions::net::try_tunnel_type_from_i32, proto, FromProtobufTypeError};
 use mullvad_types::{
     custom_list::Id,
-    relay_constraints::{Constraint, GeographicLocationConstraint},
+    location::Coordinates,
+    relay_constraints::{Constraint, GeographicLocationConstraint, GeographicRadius, RadiusCenter},
 };
 use std::str::FromStr;
 use talpid_types::net::proxy::CustomProxy;
@@ -40,10 +41,37 @@ impl TryFrom<&proto::WireguardConstraints>
                     .ok()
                 })
                 .unwrap_or(Constraint::Any),
+            diversity: constraints
+                .diversity
+                .clone()
+                .map(mullvad_constraints::MultihopDiversity::from)
+                .unwrap_or_default(),
         })
     }
 }
 
+impl From<proto::MultihopDiversity> for mullvad_types::relay_constraints::MultihopDiversity {
+    fn from(diversity: proto::MultihopDiversity) -> Self {
+        Self {
+            different_provider: diversity.different_provider,
+            different_country: diversity.different_country,
+            different_ownership: diversity.different_ownership,
+            min_distance_km: diversity.min_distance_km,
+        }
+    }
+}
+
+impl From<mullvad_types::relay_constraints::MultihopDiversity> for proto::MultihopDiversity {
+    fn from(diversity: mullvad_types::relay_constraints::MultihopDiversity) -> Self {
+        Self {
+            different_provider: diversity.different_provider,
+            different_country: diversity.different_country,
+            different_ownership: diversity.different_ownership,
+            min_distance_km: diversity.min_distance_km,
+        }
+    }
+}
+
 impl TryFrom<&proto::OpenvpnConstraints> for mullvad_types::relay_constraints::OpenVpnConstraints {
     type Error = FromProtobufTypeError;
 
@@ -92,45 +120,63 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
             }
 
             proto::relay_settings::Endpoint::Normal(settings) => {
-                let location = settings
-                    .location
-                    .and_then(|loc| Constraint::<mullvad_types::relay_constraints::LocationConstraint>::try_from(loc).ok())
-                    .unwrap_or(Constraint::Any);
-                let providers = try_providers_constraint_from_proto(&settings.providers)?;
-                let ownership = try_ownership_constraint_from_i32(settings.ownership)?;
-                let tunnel_protocol = Constraint::from(
-                    settings
-                        .tunnel_type
-                        .map(try_tunnel_type_from_i32)
-                        .transpose()?,
-                );
-                let openvpn_constraints =
-                    mullvad_constraints::OpenVpnConstraints::try_from(
-                        &settings.openvpn_constraints.ok_or(
-                            FromProtobufTypeError::InvalidArgument("missing openvpn constraints"),
-                        )?,
-                    )?;
-                let wireguard_constraints = mullvad_constraints::WireguardConstraints::try_from(
-                    &settings.wireguard_constraints.ok_or(
-                        FromProtobufTypeError::InvalidArgument("missing wireguard constraints"),
-                    )?,
-                )?;
-
                 Ok(mullvad_constraints::RelaySettings::Normal(
-                    mullvad_constraints::RelayConstraints {
-                        location,
-                        providers,
-                        ownership,
-                        tunnel_protocol,
-                        wireguard_constraints,
-                        openvpn_constraints,
-                    },
+                    mullvad_constraints::RelayConstraints::try_from(settings)?,
                 ))
             }
         }
     }
 }
 
+impl TryFrom<proto::NormalRelaySettings> for mullvad_types::relay_constraints::RelayConstraints {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(
+        settings: proto::NormalRelaySettings,
+    ) -> Result<mullvad_types::relay_constraints::RelayConstraints, Self::Error> {
+        use mullvad_types::relay_constraints as mullvad_constraints;
+
+        let location = settings
+            .location
+            .and_then(|loc| {
+                Constraint::<mullvad_types::relay_constraints::LocationConstraint>::try_from(loc)
+                    .ok()
+            })
+            .unwrap_or(Constraint::Any);
+        let providers = try_providers_constraint_from_proto(&settings.providers)?;
+        let ownership = try_ownership_constraint_from_i32(settings.ownership)?;
+        let tunnel_protocol = Constraint::from(
+            settings
+                .tunnel_type
+                .map(try_tunnel_type_from_i32)
+                .transpose()?,
+        );
+        let openvpn_constraints = mullvad_constraints::OpenVpnConstraints::try_from(
+            &settings
+                .openvpn_constraints
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing openvpn constraints",
+                ))?,
+        )?;
+        let wireguard_constraints = mullvad_constraints::WireguardConstraints::try_from(
+            &settings
+                .wireguard_constraints
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing wireguard constraints",
+                ))?,
+        )?;
+
+        Ok(mullvad_constraints::RelayConstraints {
+            location,
+            providers,
+            ownership,
+            tunnel_protocol,
+            wireguard_constraints,
+            openvpn_constraints,
+        })
+    }
+}
+
 impl From<mullvad_types::relay_constraints::BridgeState> for proto::BridgeState {
     fn from(state: mullvad_types::relay_constraints::BridgeState) -> Self {
         use mullvad_types::relay_constraints::BridgeState;
@@ -190,16 +236,7 @@ impl From<mullvad_types::relay_constraints::BridgeSettings> for proto::BridgeSet
             }
         };
 
-        let normal = bridge_settings::BridgeConstraints {
-            location: settings
-                .normal
-                .location
-                .clone()
-                .option()
-                .map(proto::LocationConstraint::from),
-            providers: convert_providers_constraint(&settings.normal.providers),
-            ownership: i32::from(convert_ownership_constraint(&settings.normal.ownership)),
-        };
+        let normal = bridge_settings::BridgeConstraints::from(settings.normal);
 
         let custom = settings.custom.map(proto::CustomProxy::from);
 
@@ -211,11 +248,25 @@ impl From<mullvad_types::relay_constraints::BridgeSettings> for proto::BridgeSet
     }
 }
 
+impl From<mullvad_types::relay_constraints::BridgeConstraints>
+    for proto::bridge_settings::BridgeConstraints
+{
+    fn from(constraints: mullvad_types::relay_constraints::BridgeConstraints) -> Self {
+        proto::bridge_settings::BridgeConstraints {
+            location: constraints
+                .location
+                .option()
+                .map(proto::LocationConstraint::from),
+            providers: convert_providers_constraint(&constraints.providers),
+            ownership: i32::from(convert_ownership_constraint(&constraints.ownership)),
+        }
+    }
+}
+
 impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySettings {
     fn from(settings: mullvad_types::relay_constraints::RelaySettings) -> Self {
         use mullvad_types::relay_constraints::RelaySettings as MullvadRelaySettings;
         use proto::relay_settings;
-        use talpid_types::net as talpid_net;
 
         let endpoint = match settings {
             MullvadRelaySettings::CustomTunnelEndpoint(endpoint) => {
@@ -225,51 +276,7 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                 })
             }
             MullvadRelaySettings::Normal(constraints) => {
-                relay_settings::Endpoint::Normal(proto::NormalRelaySettings {
-                    location: constraints
-                        .location
-                        .option()
-                        .map(proto::LocationConstraint::from),
-                    providers: convert_providers_constraint(&constraints.providers),
-                    ownership: convert_ownership_constraint(&constraints.ownership) as i32,
-                    tunnel_type: match constraints.tunnel_protocol {
-                        Constraint::Any => None,
-                        Constraint::Only(talpid_net::TunnelType::Wireguard) => {
-                            Some(proto::TunnelType::Wireguard)
-                        }
-                        Constraint::Only(talpid_net::TunnelType::OpenVpn) => {
-                            Some(proto::TunnelType::Openvpn)
-                        }
-                    }
-                    .map(i32::from),
-
-                    wireguard_constraints: Some(proto::WireguardConstraints {
-                        port: constraints
-                            .wireguard_constraints
-                            .port
-                            .map(u32::from)
-                            .option(),
-                        ip_version: constraints
-                            .wireguard_constraints
-                            .ip_version
-                            .option()
-                            .map(|ipv| i32::from(proto::IpVersion::from(ipv))),
-                        use_multihop: constraints.wireguard_constraints.use_multihop,
-                        entry_location: constraints
-                            .wireguard_constraints
-                            .entry_location
-                            .option()
-                            .map(proto::LocationConstraint::from),
-                    }),
-
-                    openvpn_constraints: Some(proto::OpenvpnConstraints {
-                        port: constraints
-                            .openvpn_constraints
-                            .port
-                            .option()
-                            .map(proto::TransportPort::from),
-                    }),
-                })
+                relay_settings::Endpoint::Normal(proto::NormalRelaySettings::from(constraints))
             }
         };
 
@@ -279,6 +286,61 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
     }
 }
 
+impl From<mullvad_types::relay_constraints::RelayConstraints> for proto::NormalRelaySettings {
+    fn from(constraints: mullvad_types::relay_constraints::RelayConstraints) -> Self {
+        use talpid_types::net as talpid_net;
+
+        proto::NormalRelaySettings {
+            location: constraints
+                .location
+                .option()
+                .map(proto::LocationConstraint::from),
+            providers: convert_providers_constraint(&constraints.providers),
+            ownership: convert_ownership_constraint(&constraints.ownership) as i32,
+            tunnel_type: match constraints.tunnel_protocol {
+                Constraint::Any => None,
+                Constraint::Only(talpid_net::TunnelType::Wireguard) => {
+                    Some(proto::TunnelType::Wireguard)
+                }
+                Constraint::Only(talpid_net::TunnelType::OpenVpn) => {
+                    Some(proto::TunnelType::Openvpn)
+                }
+            }
+            .map(i32::from),
+
+            wireguard_constraints: Some(proto::WireguardConstraints {
+                port: constraints
+                    .wireguard_constraints
+                    .port
+                    .map(u32::from)
+                    .option(),
+                ip_version: constraints
+                    .wireguard_constraints
+                    .ip_version
+                    .option()
+                    .map(|ipv| i32::from(proto::IpVersion::from(ipv))),
+                use_multihop: constraints.wireguard_constraints.use_multihop,
+                entry_location: constraints
+                    .wireguard_constraints
+                    .entry_location
+                    .option()
+                    .map(proto::LocationConstraint::from),
+                diversity: Some(proto::MultihopDiversity::from(
+                    constraints.wireguard_constraints.diversity,
+                )),
+            }),
+
+            openvpn_constraints: Some(proto::OpenvpnConstraints {
+                port: constraints
+                    .openvpn_constraints
+                    .port
+                    .option()
+                    .map(proto::TransportPort::from),
+            }),
+        }
+    }
+}
+
 impl From<mullvad_types::relay_constraints::TransportPort> for proto::TransportPort {
     fn from(port: mullvad_types::relay_constraints::TransportPort) -> Self {
         proto::TransportPort {
@@ -302,6 +364,11 @@ impl From<mullvad_types::relay_constraints::LocationConstraint> for proto::Locat
                     list_id.to_string(),
                 )),
             },
+            LocationConstraint::Radius(radius) => Self {
+                r#type: Some(proto::location_constraint::Type::Radius(
+                    proto::RadiusLocationConstraint::from(radius),
+                )),
+            },
         }
     }
 }
@@ -325,11 +392,53 @@ impl TryFrom<proto::LocationConstraint>
                 };
                 Ok(Constraint::Only(location))
             }
+            Some(proto::location_constraint::Type::Radius(radius)) => Ok(Constraint::Only(
+                LocationConstraint::Radius(GeographicRadius::try_from(radius)?),
+            )),
             None => Ok(Constraint::Any),
         }
     }
 }
 
+impl From<GeographicRadius> for proto::RadiusLocationConstraint {
+    fn from(radius: GeographicRadius) -> Self {
+        Self {
+            center: match radius.center {
+                RadiusCenter::Coordinates(center) => Some(proto::Coordinates {
+                    latitude: center.latitude,
+                    longitude: center.longitude,
+                }),
+                RadiusCenter::CurrentLocation => None,
+            },
+            radius_km: radius.radius_km,
+        }
+    }
+}
+
+impl TryFrom<proto::RadiusLocationConstraint> for GeographicRadius {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(radius: proto::RadiusLocationConstraint) -> Result<Self, Self::Error> {
+        let center = match radius.center {
+            Some(center) => RadiusCenter::Coordinates(Coordinates {
+                latitude: center.latitude,
+                longitude: center.longitude,
+            }),
+            None => RadiusCenter::CurrentLocation,
+        };
+        let radius = GeographicRadius {
+            center,
+            radius_km: radius.radius_km,
+        };
+        if !radius.is_valid() {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "invalid coordinates or radius",
+            ));
+        }
+        Ok(radius)
+    }
+}
+
 impl From<GeographicLocationConstraint> for proto::GeographicLocationConstraint {
     fn from(location: mullvad_types::relay_constraints::GeographicLocationConstraint) -> Self {
         match location {
@@ -398,11 +507,32 @@ impl TryFrom<proto::BridgeSettings> for mullvad_types::relay_constraints::Bridge
         use mullvad_types::relay_constraints::{BridgeConstraints, BridgeSettings};
 
         // convert normal bridge settings
-        let constraints = settings
+        let normal = settings
             .normal
             .ok_or(FromProtobufTypeError::InvalidArgument(
                 "missing normal bridge constraints",
-            ))?;
+            ))
+            .and_then(BridgeConstraints::try_from)?;
+
+        // convert custom bridge settings
+        let custom = settings.custom.map(CustomProxy::try_from).transpose()?;
+
+        Ok(BridgeSettings {
+            bridge_type: try_bridge_mode_from_i32(settings.bridge_type)?,
+            normal,
+            custom,
+        })
+    }
+}
+
+impl TryFrom<proto::bridge_settings::BridgeConstraints>
+    for mullvad_types::relay_constraints::BridgeConstraints
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(
+        constraints: proto::bridge_settings::BridgeConstraints,
+    ) -> Result<Self, Self::Error> {
         let location = match constraints.location {
             None => Constraint::Any,
             Some(location) => {
@@ -411,19 +541,10 @@ impl TryFrom<proto::BridgeSettings> for mullvad_types::relay_constraints::Bridge
                 )?
             }
         };
-        let normal = BridgeConstraints {
+        Ok(mullvad_types::relay_constraints::BridgeConstraints {
             location,
             providers: try_providers_constraint_from_proto(&constraints.providers)?,
             ownership: try_ownership_constraint_from_i32(constraints.ownership)?,
-        };
-
-        // convert custom bridge settings
-        let custom = settings.custom.map(CustomProxy::try_from).transpose()?;
-
-        Ok(BridgeSettings {
-            bridge_type: try_bridge_mode_from_i32(settings.bridge_type)?,
-            normal,
-            custom,
         })
     }
 }
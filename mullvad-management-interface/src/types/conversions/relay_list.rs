@@ -122,18 +122,12 @@ impl From<mullvad_types::relay_list::Relay> for proto::Relay {
                     "mullvad_daemon.management_interface/WireguardRelayEndpointData",
                     proto::WireguardRelayEndpointData {
                         public_key: data.public_key.as_bytes().to_vec(),
+                        port: data.port.map(u32::from),
                     },
                 )),
                 _ => None,
             },
-            location: relay.location.map(|location| proto::Location {
-                country: location.country,
-                country_code: location.country_code,
-                city: location.city,
-                city_code: location.city_code,
-                latitude: location.latitude,
-                longitude: location.longitude,
-            }),
+            location: relay.location.map(proto::Location::from),
         }
     }
 }
@@ -236,6 +230,11 @@ impl TryFrom<proto::Relay> for mullvad_types::relay_list::Relay {
                 MullvadEndpointData::Wireguard(
                     mullvad_types::relay_list::WireguardRelayEndpointData {
                         public_key: bytes_to_pubkey(&data.public_key)?,
+                        port: data
+                            .port
+                            .map(u16::try_from)
+                            .transpose()
+                            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))?,
                     },
                 )
             }
@@ -267,14 +266,7 @@ impl TryFrom<proto::Relay> for mullvad_types::relay_list::Relay {
             provider: relay.provider,
             weight: relay.weight,
             endpoint_data,
-            location: relay.location.map(|location| MullvadLocation {
-                country: location.country,
-                country_code: location.country_code,
-                city: location.city,
-                city_code: location.city_code,
-                latitude: location.latitude,
-                longitude: location.longitude,
-            }),
+            location: relay.location.map(MullvadLocation::from),
         })
     }
 }
@@ -375,3 +367,186 @@ impl TryFrom<proto::WireguardEndpointData> for mullvad_types::relay_list::Wiregu
         })
     }
 }
+
+impl From<mullvad_types::relay_list_changes::RelayListChanges> for proto::RelayListChanges {
+    fn from(changes: mullvad_types::relay_list_changes::RelayListChanges) -> Self {
+        let convert = |relays: Vec<mullvad_types::relay_list_changes::RelayChange>| {
+            relays
+                .into_iter()
+                .map(proto::relay_list_changes::RelayChange::from)
+                .collect()
+        };
+        proto::RelayListChanges {
+            added: convert(changes.added),
+            removed: convert(changes.removed),
+            deactivated: convert(changes.deactivated),
+        }
+    }
+}
+
+impl From<mullvad_types::relay_list_changes::RelayChange>
+    for proto::relay_list_changes::RelayChange
+{
+    fn from(change: mullvad_types::relay_list_changes::RelayChange) -> Self {
+        use mullvad_types::relay_list_changes::RelayReference;
+        use proto::relay_list_changes::relay_reference::Kind;
+
+        let referenced_by = change
+            .referenced_by
+            .into_iter()
+            .map(|reference| {
+                let (kind, custom_list_id, custom_list_name) = match reference {
+                    RelayReference::Location => (Kind::Location, None, None),
+                    RelayReference::EntryLocation => (Kind::EntryLocation, None, None),
+                    RelayReference::BridgeLocation => (Kind::BridgeLocation, None, None),
+                    RelayReference::CustomList { id, name } => {
+                        (Kind::CustomList, Some(id.to_string()), Some(name))
+                    }
+                };
+                proto::relay_list_changes::RelayReference {
+                    kind: i32::from(kind),
+                    custom_list_id,
+                    custom_list_name,
+                }
+            })
+            .collect();
+
+        proto::relay_list_changes::RelayChange {
+            hostname: change.hostname,
+            country_code: change.country_code,
+            city_code: change.city_code,
+            referenced_by,
+        }
+    }
+}
+
+impl TryFrom<proto::RelayListChanges> for mullvad_types::relay_list_changes::RelayListChanges {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(changes: proto::RelayListChanges) -> Result<Self, Self::Error> {
+        let convert = |relays: Vec<proto::relay_list_changes::RelayChange>| {
+            relays
+                .into_iter()
+                .map(mullvad_types::relay_list_changes::RelayChange::try_from)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(mullvad_types::relay_list_changes::RelayListChanges {
+            added: convert(changes.added)?,
+            removed: convert(changes.removed)?,
+            deactivated: convert(changes.deactivated)?,
+        })
+    }
+}
+
+impl TryFrom<proto::relay_list_changes::RelayChange>
+    for mullvad_types::relay_list_changes::RelayChange
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(change: proto::relay_list_changes::RelayChange) -> Result<Self, Self::Error> {
+        use mullvad_types::relay_list_changes::RelayReference;
+        use proto::relay_list_changes::relay_reference::Kind;
+
+        let referenced_by = change
+            .referenced_by
+            .into_iter()
+            .map(|reference| match Kind::try_from(reference.kind) {
+                Ok(Kind::Location) => Ok(RelayReference::Location),
+                Ok(Kind::EntryLocation) => Ok(RelayReference::EntryLocation),
+                Ok(Kind::BridgeLocation) => Ok(RelayReference::BridgeLocation),
+                Ok(Kind::CustomList) => {
+                    let id = reference
+                        .custom_list_id
+                        .as_deref()
+                        .and_then(|id| mullvad_types::custom_list::Id::from_str(id).ok())
+                        .ok_or(FromProtobufTypeError::InvalidArgument("invalid list ID"))?;
+                    Ok(RelayReference::CustomList {
+                        id,
+                        name: reference.custom_list_name.unwrap_or_default(),
+                    })
+                }
+                Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid relay reference",
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mullvad_types::relay_list_changes::RelayChange {
+            hostname: change.hostname,
+            country_code: change.country_code,
+            city_code: change.city_code,
+            referenced_by,
+        })
+    }
+}
+
+impl From<mullvad_types::location::Location> for proto::Location {
+    fn from(location: mullvad_types::location::Location) -> Self {
+        proto::Location {
+            country: location.country,
+            country_code: location.country_code,
+            city: location.city,
+            city_code: location.city_code,
+            latitude: location.latitude,
+            longitude: location.longitude,
+        }
+    }
+}
+
+impl From<proto::Location> for mullvad_types::location::Location {
+    fn from(location: proto::Location) -> Self {
+        mullvad_types::location::Location {
+            country: location.country,
+            country_code: location.country_code,
+            city: location.city,
+            city_code: location.city_code,
+            latitude: location.latitude,
+            longitude: location.longitude,
+        }
+    }
+}
+
+impl From<mullvad_types::user_relay::UserRelay> for proto::UserRelay {
+    fn from(relay: mullvad_types::user_relay::UserRelay) -> Self {
+        proto::UserRelay {
+            hostname: relay.hostname,
+            location: Some(proto::Location::from(relay.location)),
+            ipv4_addr_in: relay.ipv4_addr_in.to_string(),
+            ipv6_addr_in: relay.ipv6_addr_in.map(|addr| addr.to_string()),
+            port: u32::from(relay.port),
+            public_key: relay.public_key.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl TryFrom<proto::UserRelay> for mullvad_types::user_relay::UserRelay {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(relay: proto::UserRelay) -> Result<Self, Self::Error> {
+        let location = relay
+            .location
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing user relay location",
+            ))?;
+        let ipv6_addr_in = relay
+            .ipv6_addr_in
+            .map(|addr| {
+                addr.parse().map_err(|_err| {
+                    FromProtobufTypeError::InvalidArgument("invalid user relay IPv6 address")
+                })
+            })
+            .transpose()?;
+
+        Ok(mullvad_types::user_relay::UserRelay {
+            hostname: relay.hostname,
+            location: mullvad_types::location::Location::from(location),
+            ipv4_addr_in: relay.ipv4_addr_in.parse().map_err(|_err| {
+                FromProtobufTypeError::InvalidArgument("invalid user relay IPv4 address")
+            })?,
+            ipv6_addr_in,
+            port: u16::try_from(relay.port)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid user relay port"))?,
+            public_key: bytes_to_pubkey(&relay.public_key)?,
+        })
+    }
+}
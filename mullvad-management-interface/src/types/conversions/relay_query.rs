@@ -0,0 +1,97 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::{
+    relay_constraints::{BridgeConstraints, RelayConstraints},
+    relay_list::Relay,
+    relay_query::{RelayCandidate, RelayQuery, RelayQueryResult},
+};
+
+impl From<RelayQuery> for proto::RelayQuery {
+    fn from(query: RelayQuery) -> Self {
+        proto::RelayQuery {
+            constraints: Some(proto::NormalRelaySettings::from(query.constraints)),
+            bridge_constraints: Some(proto::bridge_settings::BridgeConstraints::from(
+                query.bridge_constraints,
+            )),
+        }
+    }
+}
+
+impl TryFrom<proto::RelayQuery> for RelayQuery {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(query: proto::RelayQuery) -> Result<Self, Self::Error> {
+        Ok(RelayQuery {
+            constraints: query
+                .constraints
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing relay constraints",
+                ))
+                .and_then(RelayConstraints::try_from)?,
+            bridge_constraints: query
+                .bridge_constraints
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing bridge constraints",
+                ))
+                .and_then(BridgeConstraints::try_from)?,
+        })
+    }
+}
+
+impl From<RelayQueryResult> for proto::RelayQueryResult {
+    fn from(result: RelayQueryResult) -> Self {
+        let candidates = |candidates: Vec<RelayCandidate>| {
+            candidates
+                .into_iter()
+                .map(proto::RelayCandidate::from)
+                .collect()
+        };
+        proto::RelayQueryResult {
+            exit: candidates(result.exit),
+            entry: candidates(result.entry),
+            bridge: candidates(result.bridge),
+        }
+    }
+}
+
+impl From<RelayCandidate> for proto::RelayCandidate {
+    fn from(candidate: RelayCandidate) -> Self {
+        proto::RelayCandidate {
+            relay: Some(proto::Relay::from(candidate.relay)),
+            weight: candidate.weight,
+            probability: candidate.probability,
+        }
+    }
+}
+
+impl TryFrom<proto::RelayQueryResult> for RelayQueryResult {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(result: proto::RelayQueryResult) -> Result<Self, Self::Error> {
+        let candidates = |candidates: Vec<proto::RelayCandidate>| {
+            candidates
+                .into_iter()
+                .map(RelayCandidate::try_from)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(RelayQueryResult {
+            exit: candidates(result.exit)?,
+            entry: candidates(result.entry)?,
+            bridge: candidates(result.bridge)?,
+        })
+    }
+}
+
+impl TryFrom<proto::RelayCandidate> for RelayCandidate {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(candidate: proto::RelayCandidate) -> Result<Self, Self::Error> {
+        Ok(RelayCandidate {
+            relay: candidate
+                .relay
+                .ok_or(FromProtobufTypeError::InvalidArgument("missing relay"))
+                .and_then(Relay::try_from)?,
+            weight: candidate.weight,
+            probability: candidate.probability,
+        })
+    }
+}
@@ -24,6 +24,15 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
         #[cfg(not(windows))]
         let split_tunnel = None;
 
+        #[cfg(target_os = "linux")]
+        let trusted_interfaces = settings
+            .trusted_interfaces
+            .iter()
+            .map(|iface| iface.to_string())
+            .collect();
+        #[cfg(not(target_os = "linux"))]
+        let trusted_interfaces = vec![];
+
         Self {
             relay_settings: Some(proto::RelaySettings::from(settings.get_relay_settings())),
             bridge_settings: Some(proto::BridgeSettings::from(
@@ -51,6 +60,27 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 .cloned()
                 .map(proto::RelayOverride::from)
                 .collect(),
+            user_relays: settings
+                .user_relays
+                .iter()
+                .cloned()
+                .map(proto::UserRelay::from)
+                .collect(),
+            firewall_exceptions: settings
+                .firewall_exceptions
+                .iter()
+                .cloned()
+                .map(proto::FirewallException::from)
+                .collect(),
+            trusted_interfaces,
+            connection_plan: settings
+                .connection_plan
+                .as_ref()
+                .map(proto::ConnectionPlan::from),
+            expiry_warning_thresholds: proto::ExpiryWarningThresholds::from(
+                settings.expiry_warning_thresholds.clone(),
+            )
+            .thresholds,
         }
     }
 }
@@ -92,6 +122,10 @@ impl From<&mullvad_types::settings::TunnelOptions> for proto::TunnelOptions {
             }),
             wireguard: Some(proto::tunnel_options::WireguardOptions {
                 mtu: options.wireguard.mtu.map(u32::from),
+                mtu_discovery: options.wireguard.mtu_discovery,
+                connectivity_check: Some(proto::ConnectivityCheckOptions::from(
+                    options.wireguard.connectivity_check,
+                )),
                 rotation_interval: options.wireguard.rotation_interval.map(|ivl| {
                     prost_types::Duration::try_from(std::time::Duration::from(ivl))
                         .expect("Failed to convert std::time::Duration to prost_types::Duration for tunnel_options.wireguard.rotation_interval")
@@ -179,7 +213,31 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .into_iter()
                 .map(mullvad_types::relay_constraints::RelayOverride::try_from)
                 .collect::<Result<Vec<_>, _>>()?,
+            user_relays: settings
+                .user_relays
+                .into_iter()
+                .map(mullvad_types::user_relay::UserRelay::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            firewall_exceptions: settings
+                .firewall_exceptions
+                .into_iter()
+                .map(talpid_types::net::firewall_exception::FirewallException::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            #[cfg(target_os = "linux")]
+            trusted_interfaces: settings
+                .trusted_interfaces
+                .into_iter()
+                .map(talpid_types::net::trusted_interface::TrustedInterface::new)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid trusted interface"))?,
+            connection_plan: settings
+                .connection_plan
+                .map(mullvad_types::connection_plan::ConnectionPlan::try_from)
+                .transpose()?,
             show_beta_releases: settings.show_beta_releases,
+            expiry_warning_thresholds: Vec::try_from(proto::ExpiryWarningThresholds {
+                thresholds: settings.expiry_warning_thresholds,
+            })?,
             #[cfg(windows)]
             split_tunnel: mullvad_types::settings::SplitTunnelSettings::from(split_tunnel),
             obfuscation_settings: mullvad_types::relay_constraints::ObfuscationSettings::try_from(
@@ -198,6 +256,57 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
     }
 }
 
+impl From<&mullvad_types::profile::Profile> for proto::Profile {
+    fn from(profile: &mullvad_types::profile::Profile) -> Self {
+        Self {
+            name: profile.name.clone(),
+            relay_settings: Some(proto::RelaySettings::from(profile.relay_settings.clone())),
+            obfuscation_settings: Some(proto::ObfuscationSettings::from(
+                &profile.obfuscation_settings,
+            )),
+            tunnel_options: Some(proto::TunnelOptions::from(&profile.tunnel_options)),
+            allow_lan: profile.allow_lan,
+        }
+    }
+}
+
+impl TryFrom<proto::Profile> for mullvad_types::profile::Profile {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(profile: proto::Profile) -> Result<Self, Self::Error> {
+        let relay_settings =
+            profile
+                .relay_settings
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing relay settings",
+                ))?;
+        let obfuscation_settings =
+            profile
+                .obfuscation_settings
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing obfuscation settings",
+                ))?;
+        let tunnel_options =
+            profile
+                .tunnel_options
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing tunnel options",
+                ))?;
+
+        Ok(Self {
+            name: profile.name,
+            relay_settings: mullvad_types::relay_constraints::RelaySettings::try_from(
+                relay_settings,
+            )?,
+            obfuscation_settings: mullvad_types::relay_constraints::ObfuscationSettings::try_from(
+                obfuscation_settings,
+            )?,
+            tunnel_options: mullvad_types::settings::TunnelOptions::try_from(tunnel_options)?,
+            allow_lan: profile.allow_lan,
+        })
+    }
+}
+
 pub fn try_bridge_state_from_i32(
     bridge_state: i32,
 ) -> Result<mullvad_types::relay_constraints::BridgeState, FromProtobufTypeError> {
@@ -262,6 +371,12 @@ impl TryFrom<proto::TunnelOptions> for mullvad_types::settings::TunnelOptions {
             },
             wireguard: mullvad_types::wireguard::TunnelOptions {
                 mtu: wireguard_options.mtu.map(|mtu| mtu as u16),
+                mtu_discovery: wireguard_options.mtu_discovery,
+                connectivity_check: wireguard_options
+                    .connectivity_check
+                    .map(talpid_types::net::wireguard::ConnectivityCheckOptions::try_from)
+                    .transpose()?
+                    .unwrap_or_default(),
                 rotation_interval: wireguard_options
                     .rotation_interval
                     .map(std::time::Duration::try_from)
@@ -349,3 +464,60 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
         })
     }
 }
+
+impl From<&mullvad_types::settings::history::SettingsSnapshotInfo> for proto::SettingsSnapshot {
+    fn from(snapshot: &mullvad_types::settings::history::SettingsSnapshotInfo) -> Self {
+        Self {
+            id: snapshot.id,
+            created: Some(crate::types::Timestamp {
+                seconds: snapshot.created.timestamp(),
+                nanos: 0,
+            }),
+            cause: snapshot.cause.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::SettingsSnapshot> for mullvad_types::settings::history::SettingsSnapshotInfo {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(snapshot: proto::SettingsSnapshot) -> Result<Self, Self::Error> {
+        use chrono::TimeZone;
+
+        let created = snapshot
+            .created
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing snapshot timestamp",
+            ))?;
+        let ndt = chrono::NaiveDateTime::from_timestamp_opt(created.seconds, created.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "invalid snapshot timestamp",
+            ))?;
+
+        Ok(Self {
+            id: snapshot.id,
+            created: chrono::Utc.from_utc_datetime(&ndt),
+            cause: snapshot.cause,
+        })
+    }
+}
+
+impl From<mullvad_types::settings::history::SettingsChange> for proto::SettingsChange {
+    fn from(change: mullvad_types::settings::history::SettingsChange) -> Self {
+        Self {
+            path: change.path,
+            old: change.old,
+            new: change.new,
+        }
+    }
+}
+
+impl From<proto::SettingsChange> for mullvad_types::settings::history::SettingsChange {
+    fn from(change: proto::SettingsChange) -> Self {
+        Self {
+            path: change.path,
+            old: change.old,
+            new: change.new,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::types::{proto, FromProtobufTypeError};
+use chrono::TimeZone;
+use prost_types::Timestamp;
 
 impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
     fn from(state: mullvad_types::states::TunnelState) -> Self {
@@ -35,18 +37,28 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
             MullvadTunnelState::Disconnected {
                 location: disconnected_location,
                 locked_down,
+                captive_portal,
+                paused_until,
             } => proto::tunnel_state::State::Disconnected(proto::tunnel_state::Disconnected {
                 disconnected_location: disconnected_location.map(proto::GeoIpLocation::from),
                 locked_down,
+                captive_portal: captive_portal.map(proto::CaptivePortalLogin::from),
+                paused_until: paused_until.map(|deadline| Timestamp {
+                    seconds: deadline.timestamp(),
+                    nanos: 0,
+                }),
+            }),
+            MullvadTunnelState::Connecting {
+                endpoint,
+                location,
+                captive_portal,
+            } => proto::tunnel_state::State::Connecting(proto::tunnel_state::Connecting {
+                relay_info: Some(proto::TunnelStateRelayInfo {
+                    tunnel_endpoint: Some(proto::TunnelEndpoint::from(endpoint)),
+                    location: location.map(proto::GeoIpLocation::from),
+                }),
+                captive_portal: captive_portal.map(proto::CaptivePortalLogin::from),
             }),
-            MullvadTunnelState::Connecting { endpoint, location } => {
-                proto::tunnel_state::State::Connecting(proto::tunnel_state::Connecting {
-                    relay_info: Some(proto::TunnelStateRelayInfo {
-                        tunnel_endpoint: Some(proto::TunnelEndpoint::from(endpoint)),
-                        location: location.map(proto::GeoIpLocation::from),
-                    }),
-                })
-            }
             MullvadTunnelState::Connected { endpoint, location } => {
                 proto::tunnel_state::State::Connected(proto::tunnel_state::Connected {
                     relay_info: Some(proto::TunnelStateRelayInfo {
@@ -155,6 +167,10 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                             }
                             _ => None,
                         },
+                        captive_portal: error_state
+                            .captive_portal()
+                            .cloned()
+                            .map(proto::CaptivePortalLogin::from),
                     }),
                 })
             }
@@ -164,6 +180,65 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
     }
 }
 
+impl From<talpid_types::tunnel::CaptivePortalLogin> for proto::CaptivePortalLogin {
+    fn from(login: talpid_types::tunnel::CaptivePortalLogin) -> Self {
+        use proto::captive_portal_login::State;
+        use talpid_types::tunnel::CaptivePortalLogin;
+
+        let state = match login {
+            CaptivePortalLogin::Active {
+                expires,
+                portal_detected,
+            } => {
+                return proto::CaptivePortalLogin {
+                    state: i32::from(State::Active),
+                    expires: Some(crate::types::Timestamp::from(expires)),
+                    portal_detected,
+                }
+            }
+            CaptivePortalLogin::Succeeded => State::Succeeded,
+            CaptivePortalLogin::Expired => State::Expired,
+            CaptivePortalLogin::Cancelled => State::Cancelled,
+            CaptivePortalLogin::Detected => State::Detected,
+        };
+        proto::CaptivePortalLogin {
+            state: i32::from(state),
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<proto::CaptivePortalLogin> for talpid_types::tunnel::CaptivePortalLogin {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(login: proto::CaptivePortalLogin) -> Result<Self, Self::Error> {
+        use proto::captive_portal_login::State;
+        use talpid_types::tunnel::CaptivePortalLogin;
+
+        match State::try_from(login.state) {
+            Ok(State::Active) => {
+                let expires = login
+                    .expires
+                    .and_then(|expires| std::time::SystemTime::try_from(expires).ok())
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "invalid captive portal login expiry",
+                    ))?;
+                Ok(CaptivePortalLogin::Active {
+                    expires,
+                    portal_detected: login.portal_detected,
+                })
+            }
+            Ok(State::Succeeded) => Ok(CaptivePortalLogin::Succeeded),
+            Ok(State::Expired) => Ok(CaptivePortalLogin::Expired),
+            Ok(State::Cancelled) => Ok(CaptivePortalLogin::Cancelled),
+            Ok(State::Detected) => Ok(CaptivePortalLogin::Detected),
+            Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid captive portal login state",
+            )),
+        }
+    }
+}
+
 impl From<mullvad_types::auth_failed::AuthFailed> for proto::error_state::AuthFailedError {
     fn from(auth_failed: mullvad_types::auth_failed::AuthFailed) -> Self {
         use mullvad_types::auth_failed::AuthFailed;
@@ -209,11 +284,26 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
             Some(proto::tunnel_state::State::Disconnected(proto::tunnel_state::Disconnected {
                 disconnected_location,
                 locked_down,
+                captive_portal,
+                paused_until,
             })) => MullvadState::Disconnected {
                 location: disconnected_location
                     .map(mullvad_types::location::GeoIpLocation::try_from)
                     .transpose()?,
                 locked_down,
+                captive_portal: captive_portal
+                    .map(talpid_tunnel::CaptivePortalLogin::try_from)
+                    .transpose()?,
+                paused_until: paused_until
+                    .map(|deadline| {
+                        chrono::Utc
+                            .timestamp_opt(deadline.seconds, 0)
+                            .single()
+                            .ok_or(FromProtobufTypeError::InvalidArgument(
+                                "invalid pause deadline",
+                            ))
+                    })
+                    .transpose()?,
             },
             Some(proto::tunnel_state::State::Connecting(proto::tunnel_state::Connecting {
                 relay_info:
@@ -221,11 +311,15 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         tunnel_endpoint: Some(tunnel_endpoint),
                         location,
                     }),
+                captive_portal,
             })) => MullvadState::Connecting {
                 endpoint: talpid_net::TunnelEndpoint::try_from(tunnel_endpoint)?,
                 location: location
                     .map(mullvad_types::location::GeoIpLocation::try_from)
                     .transpose()?,
+                captive_portal: captive_portal
+                    .map(talpid_tunnel::CaptivePortalLogin::try_from)
+                    .transpose()?,
             },
             Some(proto::tunnel_state::State::Connected(proto::tunnel_state::Connected {
                 relay_info:
@@ -268,6 +362,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         parameter_error,
                         policy_error,
                         create_tunnel_error,
+                        captive_portal,
                     }),
             })) => {
                 #[cfg(not(target_os = "windows"))]
@@ -346,7 +441,14 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                     })
                     .transpose()?;
 
-                MullvadState::Error(talpid_tunnel::ErrorState::new(cause, block_failure))
+                let captive_portal = captive_portal
+                    .map(talpid_tunnel::CaptivePortalLogin::try_from)
+                    .transpose()?;
+
+                MullvadState::Error(
+                    talpid_tunnel::ErrorState::new(cause, block_failure)
+                        .with_captive_portal(captive_portal),
+                )
             }
             _ => {
                 return Err(FromProtobufTypeError::InvalidArgument(
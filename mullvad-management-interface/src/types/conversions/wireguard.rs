@@ -71,3 +71,71 @@ impl TryFrom<proto::QuantumResistantState> for mullvad_types::wireguard::Quantum
         }
     }
 }
+
+impl From<talpid_types::net::wireguard::ConnectivityCheckOptions>
+    for proto::ConnectivityCheckOptions
+{
+    fn from(options: talpid_types::net::wireguard::ConnectivityCheckOptions) -> Self {
+        use proto::connectivity_check_options::LivenessProbe;
+        use talpid_types::net::wireguard;
+
+        let to_proto_duration = |duration: std::time::Duration| {
+            prost_types::Duration::try_from(duration)
+                .expect("Failed to convert std::time::Duration to prost_types::Duration")
+        };
+
+        proto::ConnectivityCheckOptions {
+            probe: i32::from(match options.probe {
+                wireguard::LivenessProbe::Icmp => LivenessProbe::Icmp,
+                wireguard::LivenessProbe::Tcp => LivenessProbe::Tcp,
+                wireguard::LivenessProbe::Dns => LivenessProbe::Dns,
+            }),
+            rx_timeout: Some(to_proto_duration(options.rx_timeout)),
+            traffic_timeout: Some(to_proto_duration(options.traffic_timeout)),
+            probe_timeout: Some(to_proto_duration(options.probe_timeout)),
+            establish_timeout: Some(to_proto_duration(options.establish_timeout)),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectivityCheckOptions>
+    for talpid_types::net::wireguard::ConnectivityCheckOptions
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(options: proto::ConnectivityCheckOptions) -> Result<Self, Self::Error> {
+        use proto::connectivity_check_options::LivenessProbe;
+        use talpid_types::net::wireguard;
+
+        let from_proto_duration = |duration: Option<prost_types::Duration>| {
+            let duration = duration.ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing connectivity check timeout",
+            ))?;
+            match std::time::Duration::try_from(duration) {
+                Ok(duration) if !duration.is_zero() => Ok(duration),
+                _ => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid connectivity check timeout",
+                )),
+            }
+        };
+
+        let probe = match LivenessProbe::try_from(options.probe) {
+            Ok(LivenessProbe::Icmp) => wireguard::LivenessProbe::Icmp,
+            Ok(LivenessProbe::Tcp) => wireguard::LivenessProbe::Tcp,
+            Ok(LivenessProbe::Dns) => wireguard::LivenessProbe::Dns,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid liveness probe",
+                ))
+            }
+        };
+
+        Ok(wireguard::ConnectivityCheckOptions {
+            probe,
+            rx_timeout: from_proto_duration(options.rx_timeout)?,
+            traffic_timeout: from_proto_duration(options.traffic_timeout)?,
+            probe_timeout: from_proto_duration(options.probe_timeout)?,
+            establish_timeout: from_proto_duration(options.establish_timeout)?,
+        })
+    }
+}
@@ -0,0 +1,220 @@
+//! Named pipe server for the management interface. Unlike `parity_tokio_ipc`, this keeps the
+//! pipe handle of each connection, which is needed to find out who the client is.
+
+use crate::Error;
+use futures::Stream;
+use std::{
+    ffi::{c_void, OsStr},
+    io, iter, mem,
+    os::windows::{ffi::OsStrExt, io::AsRawHandle},
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::windows::named_pipe::{NamedPipeServer, ServerOptions},
+};
+use tonic::transport::server::Connected;
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    Security::{
+        Authorization::{ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1},
+        GetTokenInformation, TokenElevation, SECURITY_ATTRIBUTES, TOKEN_ELEVATION, TOKEN_QUERY,
+    },
+    System::{
+        Memory::LocalFree,
+        Pipes::GetNamedPipeClientProcessId,
+        Threading::{OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION},
+    },
+};
+
+/// Lets everyone read from and write to the pipe.
+const PIPE_SECURITY_DESCRIPTOR: &str = "D:(A;;GRGW;;;WD)";
+
+const PIPE_BUFFER_SIZE: u32 = 65536;
+
+/// Information about the client of a connection, which tonic attaches to each request.
+#[derive(Debug, Clone, Copy)]
+pub struct PipeConnectInfo {
+    /// Whether the client runs as SYSTEM or as an elevated administrator.
+    pub privileged: bool,
+}
+
+/// Accepts connections on the named pipe at `path`.
+pub fn incoming(path: String) -> Result<impl Stream<Item = io::Result<PipeConnection>>, Error> {
+    let mut attributes = SecurityAttributes::allow_everyone().map_err(Error::SecurityAttributes)?;
+    let pipe = create_pipe(&path, true, &mut attributes).map_err(Error::StartServerError)?;
+
+    Ok(futures::stream::try_unfold(
+        (pipe, path, attributes),
+        |(pipe, path, mut attributes)| async move {
+            pipe.connect().await?;
+            let next_pipe = create_pipe(&path, false, &mut attributes)?;
+            Ok(Some((
+                PipeConnection::new(pipe),
+                (next_pipe, path, attributes),
+            )))
+        },
+    ))
+}
+
+fn create_pipe(
+    path: &str,
+    first_instance: bool,
+    attributes: &mut SecurityAttributes,
+) -> io::Result<NamedPipeServer> {
+    // SAFETY: The security attributes and the descriptor they point to outlive the call
+    unsafe {
+        ServerOptions::new()
+            .first_pipe_instance(first_instance)
+            .reject_remote_clients(true)
+            .access_inbound(true)
+            .access_outbound(true)
+            .in_buffer_size(PIPE_BUFFER_SIZE)
+            .out_buffer_size(PIPE_BUFFER_SIZE)
+            .create_with_security_attributes_raw(
+                path,
+                &mut attributes.0 as *mut SECURITY_ATTRIBUTES as *mut c_void,
+            )
+    }
+}
+
+struct SecurityAttributes(SECURITY_ATTRIBUTES);
+
+// SAFETY: The security descriptor is owned by `SecurityAttributes`, and is never mutated
+unsafe impl Send for SecurityAttributes {}
+
+impl SecurityAttributes {
+    fn allow_everyone() -> io::Result<Self> {
+        let descriptor_string: Vec<u16> = OsStr::new(PIPE_SECURITY_DESCRIPTOR)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let mut descriptor = ptr::null_mut();
+        if unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                descriptor_string.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut(),
+            )
+        } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor,
+            bInheritHandle: 0,
+        }))
+    }
+}
+
+impl Drop for SecurityAttributes {
+    fn drop(&mut self) {
+        unsafe { LocalFree(self.0.lpSecurityDescriptor as isize) };
+    }
+}
+
+/// A connection to a client of the named pipe.
+#[derive(Debug)]
+pub struct PipeConnection {
+    pipe: NamedPipeServer,
+    info: PipeConnectInfo,
+}
+
+impl PipeConnection {
+    fn new(pipe: NamedPipeServer) -> Self {
+        let privileged = is_privileged_client(&pipe).unwrap_or_else(|error| {
+            log::warn!("Failed to check the privileges of a management interface client: {error}");
+            false
+        });
+        Self {
+            pipe,
+            info: PipeConnectInfo { privileged },
+        }
+    }
+}
+
+/// Returns whether the process at the other end of `pipe` runs as SYSTEM or as an elevated
+/// administrator, which are the only tokens that are elevated.
+fn is_privileged_client(pipe: &NamedPipeServer) -> io::Result<bool> {
+    let mut process_id = 0;
+    if unsafe { GetNamedPipeClientProcessId(pipe.as_raw_handle() as HANDLE, &mut process_id) } == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id) };
+    if process == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let process = Handle(process);
+
+    let mut token = 0;
+    if unsafe { OpenProcessToken(process.0, TOKEN_QUERY, &mut token) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let token = Handle(token);
+
+    let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+    let mut returned_len = 0;
+    if unsafe {
+        GetTokenInformation(
+            token.0,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut c_void,
+            mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned_len,
+        )
+    } == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(elevation.TokenIsElevated != 0)
+}
+
+struct Handle(HANDLE);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+impl Connected for PipeConnection {
+    type ConnectInfo = PipeConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.info
+    }
+}
+
+impl AsyncRead for PipeConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.pipe).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PipeConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.pipe).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.pipe).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.pipe).poll_shutdown(cx)
+    }
+}
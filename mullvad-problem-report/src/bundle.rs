@@ -0,0 +1,475 @@
+//! Collects a local diagnostic bundle. Unlike the problem report, which is a size-limited text
+//! file meant to be sent to the API, the bundle is a tar archive that contains one file per
+//! source and a manifest. It is never sent anywhere by this tool.
+
+use crate::{
+    frontend_log_dir, list_logs, read_file_lossy,
+    redact::{RedactionSummary, Redactor},
+    Error, LogError, ProblemReport, DEVICE_FILENAME,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+
+/// Maximum number of bytes to read from each log file. This is larger than for problem reports,
+/// since the bundle is not uploaded.
+const BUNDLE_LOG_MAX_READ_BYTES: usize = 8 * 1024 * 1024;
+/// Maximum number of bytes to read from other files.
+const BUNDLE_FILE_MAX_READ_BYTES: usize = 1024 * 1024;
+
+/// Version of the bundle layout. Increase this when the manifest format changes.
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "manifest.json";
+
+const SETTINGS_FILENAME: &str = "settings.json";
+
+/// Object keys whose values are removed from the settings and device state. These include the
+/// credentials of proxies and custom API access methods.
+const SECRET_KEYS: &[&str] = &["private_key", "psk", "username", "password"];
+
+/// Log line that the daemon prints on every tunnel state change.
+const TUNNEL_STATE_LOG_MARKER: &str = "New tunnel state:";
+
+/// A string that was removed from a file in the bundle.
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    /// Path of the file in the bundle.
+    pub file: String,
+    /// Name of the rule that redacted the string.
+    pub rule: String,
+    /// The redacted string.
+    pub original: String,
+}
+
+#[derive(Debug)]
+struct BundleEntry {
+    /// Path of the file in the bundle.
+    path: String,
+    /// Where the content came from.
+    source: String,
+    content: String,
+    /// Set if the content could not be collected. `content` then contains the error.
+    error: Option<String>,
+    redactions: Vec<Redaction>,
+}
+
+/// An in-memory diagnostic bundle. Collect it using [`DiagnosticBundle::collect`], optionally
+/// inspect the redactions, and write it using [`DiagnosticBundle::write`].
+#[derive(Debug)]
+pub struct DiagnosticBundle {
+    report: ProblemReport,
+    entries: Vec<BundleEntry>,
+}
+
+impl DiagnosticBundle {
+    /// Collects logs, settings, device state and network configuration. All content is
+    /// redacted using `redactor`.
+    pub fn collect<P: AsRef<Path>>(extra_logs: &[P], redactor: Redactor) -> Self {
+        let mut bundle = DiagnosticBundle {
+            report: ProblemReport::new(redactor),
+            entries: vec![],
+        };
+
+        let mut daemon_log_content = String::new();
+        match mullvad_paths::get_log_dir()
+            .map_err(LogError::GetLogDir)
+            .and_then(list_logs)
+        {
+            Ok(logs) => {
+                for log in logs {
+                    match log {
+                        Ok(path) => {
+                            if let Ok(content) = read_file_lossy(&path, BUNDLE_LOG_MAX_READ_BYTES) {
+                                daemon_log_content.push_str(&content);
+                            }
+                            bundle.add_log("logs/daemon", &path);
+                        }
+                        Err(error) => {
+                            bundle.add_error("logs/daemon-error.txt", "log directory", &error)
+                        }
+                    }
+                }
+            }
+            Err(error) => bundle.add_error("logs/daemon-error.txt", "log directory", &error),
+        }
+        match frontend_log_dir().map(|dir| dir.and_then(list_logs)) {
+            Some(Ok(logs)) => {
+                for log in logs.flatten() {
+                    bundle.add_log("logs/frontend", &log);
+                }
+            }
+            Some(Err(error)) => {
+                bundle.add_error("logs/frontend-error.txt", "frontend log directory", &error)
+            }
+            None => {}
+        }
+        for log in extra_logs {
+            bundle.add_log("logs/extra", log.as_ref());
+        }
+
+        bundle.add_tunnel_state_history(&daemon_log_content);
+
+        match mullvad_paths::settings_dir() {
+            Ok(settings_dir) => {
+                bundle.add_json_file("settings.json", &settings_dir.join(SETTINGS_FILENAME));
+                bundle.add_json_file("device.json", &settings_dir.join(DEVICE_FILENAME));
+            }
+            Err(error) => bundle.add_error("settings-error.txt", "settings directory", &error),
+        }
+
+        #[cfg(target_os = "linux")]
+        bundle.add_linux_network_state();
+
+        bundle
+    }
+
+    /// Returns every string that was redacted from the bundle.
+    pub fn redactions(&self) -> impl Iterator<Item = &Redaction> {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.redactions.iter())
+    }
+
+    /// Returns the number of strings that were redacted by each rule.
+    pub fn summary(&self) -> &RedactionSummary {
+        &self.report.redaction_summary
+    }
+
+    /// Writes the bundle as a tar archive to `path`.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        self.write_inner(path)
+            .map_err(|source| Error::WriteReportError {
+                path: path.display().to_string(),
+                source,
+            })
+    }
+
+    fn write_inner(&self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        let mut archive = tar::Builder::new(BufWriter::new(file));
+
+        let manifest = serde_json::to_vec_pretty(&self.manifest())?;
+        append_file(&mut archive, MANIFEST_PATH, &manifest)?;
+        for entry in &self.entries {
+            append_file(&mut archive, &entry.path, entry.content.as_bytes())?;
+        }
+
+        archive.into_inner()?.into_inner()?.sync_all()
+    }
+
+    fn manifest(&self) -> serde_json::Value {
+        let files: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut redaction_counts = BTreeMap::<&str, usize>::new();
+                for redaction in &entry.redactions {
+                    *redaction_counts.entry(&redaction.rule).or_default() += 1;
+                }
+                serde_json::json!({
+                    "path": entry.path,
+                    "source": entry.source,
+                    "error": entry.error,
+                    "redactions": redaction_counts,
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "format_version": BUNDLE_FORMAT_VERSION,
+            "metadata": self.report.metadata,
+            "files": files,
+        })
+    }
+
+    fn add_log(&mut self, dir: &str, path: &Path) {
+        let expanded_path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let file_name = expanded_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "unknown.log".to_owned());
+        let bundle_path = self.unique_path(&format!("{dir}/{file_name}"));
+        let source = self.report.redact(&expanded_path.to_string_lossy());
+
+        match read_file_lossy(path, BUNDLE_LOG_MAX_READ_BYTES) {
+            Ok(content) => self.push(bundle_path, source, content, None),
+            Err(error) => self.push_error(bundle_path, source, &error),
+        }
+    }
+
+    fn add_file(&mut self, bundle_path: &str, path: &Path) {
+        let source = self.report.redact(&path.to_string_lossy());
+        match read_file_lossy(path, BUNDLE_FILE_MAX_READ_BYTES) {
+            Ok(content) => self.push(bundle_path.to_owned(), source, content, None),
+            Err(error) => self.push_error(bundle_path.to_owned(), source, &error),
+        }
+    }
+
+    fn add_error(&mut self, bundle_path: &str, source: &str, error: &impl ErrorExt) {
+        self.push(
+            self.unique_path(bundle_path),
+            source.to_owned(),
+            error.display_chain(),
+            Some(error.display_chain()),
+        );
+    }
+
+    /// Adds a JSON file with the values of all [`SECRET_KEYS`] removed. The file is left out if it
+    /// cannot be parsed, since the secrets could not be removed then.
+    fn add_json_file(&mut self, bundle_path: &str, path: &Path) {
+        let source = self.report.redact(&path.to_string_lossy());
+        let content = read_file_lossy(path, BUNDLE_FILE_MAX_READ_BYTES).and_then(|content| {
+            let mut value: serde_json::Value = serde_json::from_str(&content)?;
+            remove_secrets(&mut value);
+            Ok(serde_json::to_string_pretty(&value)?)
+        });
+        match content {
+            Ok(content) => self.push(bundle_path.to_owned(), source, content, None),
+            Err(error) => self.push_error(bundle_path.to_owned(), source, &error),
+        }
+    }
+
+    /// Adds all tunnel state transitions found in the daemon logs.
+    fn add_tunnel_state_history(&mut self, daemon_log: &str) {
+        let mut history: Vec<_> = daemon_log
+            .lines()
+            .filter(|line| line.contains(TUNNEL_STATE_LOG_MARKER))
+            .collect();
+        // Lines start with a timestamp, and rotated logs may have been read in any order
+        history.sort();
+        let mut history = history.join("\n");
+        history.push('\n');
+        self.push(
+            "tunnel-state-history.txt".to_owned(),
+            "daemon logs".to_owned(),
+            history,
+            None,
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    fn add_linux_network_state(&mut self) {
+        const COMMANDS: &[(&str, &str, &[&str])] = &[
+            ("network/nft-ruleset.txt", "nft", &["list", "ruleset"]),
+            ("network/ip-rule.txt", "ip", &["rule", "list"]),
+            ("network/ip6-rule.txt", "ip", &["-6", "rule", "list"]),
+            (
+                "network/ip-route.txt",
+                "ip",
+                &["route", "show", "table", "all"],
+            ),
+            (
+                "network/ip6-route.txt",
+                "ip",
+                &["-6", "route", "show", "table", "all"],
+            ),
+            ("network/ip-link.txt", "ip", &["link", "show"]),
+            ("network/resolvectl-status.txt", "resolvectl", &["status"]),
+            (
+                "network/networkmanager-connections.txt",
+                "nmcli",
+                &["--terse", "connection", "show", "--active"],
+            ),
+            (
+                "network/networkmanager-devices.txt",
+                "nmcli",
+                &["--terse", "device", "status"],
+            ),
+        ];
+        for (bundle_path, program, args) in COMMANDS {
+            self.add_command_output(bundle_path, program, args);
+        }
+        self.add_file("network/resolv.conf", Path::new("/etc/resolv.conf"));
+    }
+
+    #[cfg(target_os = "linux")]
+    fn add_command_output(&mut self, bundle_path: &str, program: &str, args: &[&str]) {
+        let source = format!("{program} {}", args.join(" "));
+        match std::process::Command::new(program).args(args).output() {
+            Ok(output) if output.status.success() => {
+                let content = String::from_utf8_lossy(&output.stdout).into_owned();
+                self.push(bundle_path.to_owned(), source, content, None);
+            }
+            Ok(output) => {
+                let error = format!(
+                    "{source} failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                self.push(bundle_path.to_owned(), source, error.clone(), Some(error));
+            }
+            Err(error) => self.push_error(bundle_path.to_owned(), source, &error),
+        }
+    }
+
+    fn push_error(&mut self, path: String, source: String, error: &io::Error) {
+        let error = error.display_chain();
+        self.push(path, source, error.clone(), Some(error));
+    }
+
+    fn push(&mut self, path: String, source: String, content: String, error: Option<String>) {
+        let mut redactions = vec![];
+        let report = &mut self.report;
+        let content = report
+            .redactor
+            .redact_and_record(&content, &mut |rule, original| {
+                report.redaction_summary.record(rule);
+                redactions.push(Redaction {
+                    file: path.clone(),
+                    rule: rule.to_owned(),
+                    original: original.to_owned(),
+                })
+            });
+        let error = error.map(|error| self.report.redact(&error));
+        self.entries.push(BundleEntry {
+            path,
+            source,
+            content,
+            error,
+            redactions,
+        });
+    }
+
+    /// Returns `path`, or `path` with a numeric suffix if it is already used.
+    fn unique_path(&self, path: &str) -> String {
+        let is_used = |path: &str| self.entries.iter().any(|entry| entry.path == path);
+        if !is_used(path) {
+            return path.to_owned();
+        }
+        (1..)
+            .map(|i| format!("{path}.{i}"))
+            .find(|path| !is_used(path))
+            .unwrap()
+    }
+}
+
+fn append_file<W: io::Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    content: &[u8],
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0),
+    );
+    archive.append_data(&mut header, PathBuf::from(path), content)
+}
+
+/// Replaces the values of all keys in [`SECRET_KEYS`] in a JSON value.
+fn remove_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) {
+                    *value = serde_json::Value::String("[REMOVED]".to_owned());
+                } else {
+                    remove_secrets(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_secrets),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn removes_nested_private_keys() {
+        let mut device = serde_json::json!({
+            "logged_in": {
+                "account_token": "1234123412341234",
+                "device": {
+                    "wg_data": {
+                        "private_key": "secret",
+                        "addresses": {},
+                    },
+                },
+            },
+        });
+        remove_secrets(&mut device);
+        assert_eq!(
+            device["logged_in"]["device"]["wg_data"]["private_key"],
+            "[REMOVED]"
+        );
+        assert_eq!(device["logged_in"]["account_token"], "1234123412341234");
+    }
+
+    #[test]
+    fn removes_proxy_credentials() {
+        use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Remote, SocksAuth};
+
+        let auth = SocksAuth::new("user".to_owned(), "hunter2".to_owned()).unwrap();
+        let socks5 = Socks5Remote::new_with_authentication(([192, 0, 2, 1], 1080), auth);
+        let shadowsocks = Shadowsocks::new(
+            ([192, 0, 2, 2], 443),
+            "aes-256-gcm".to_owned(),
+            "hunter3".to_owned(),
+        );
+        let mut settings = serde_json::json!({
+            "api_access_methods": {
+                "custom": [CustomProxy::Socks5Remote(socks5)],
+            },
+            "bridge_settings": {
+                "custom": CustomProxy::Shadowsocks(shadowsocks),
+            },
+        });
+        remove_secrets(&mut settings);
+
+        let settings = settings.to_string();
+        for secret in ["user", "hunter2", "hunter3"] {
+            assert!(!settings.contains(&format!("\"{secret}\"")), "{settings}");
+        }
+        assert!(settings.contains("aes-256-gcm"));
+    }
+
+    #[test]
+    fn records_redactions() {
+        let mut bundle = DiagnosticBundle {
+            report: ProblemReport::new(
+                Redactor::from_local_info(
+                    Default::default(),
+                    vec!["hostname".to_owned()],
+                    &Default::default(),
+                )
+                .unwrap(),
+            ),
+            entries: vec![],
+        };
+        bundle.push(
+            "test.txt".to_owned(),
+            "test".to_owned(),
+            "1234123412341234 at 10.0.0.1 on hostname".to_owned(),
+            None,
+        );
+
+        assert_eq!(
+            bundle.entries[0].content,
+            "[REDACTED ACCOUNT NUMBER] at [REDACTED] on [REDACTED]"
+        );
+        let redactions: Vec<_> = bundle
+            .redactions()
+            .map(|redaction| (redaction.rule.as_str(), redaction.original.as_str()))
+            .collect();
+        assert_eq!(
+            redactions,
+            [
+                ("account-number", "1234123412341234"),
+                ("network-address", "10.0.0.1"),
+                ("custom-strings", "hostname"),
+            ]
+        );
+    }
+}
@@ -1,8 +1,5 @@
 use mullvad_api::proxy::ApiConnectionMode;
-use once_cell::sync::Lazy;
-use regex::Regex;
 use std::{
-    borrow::Cow,
     cmp::min,
     collections::{BTreeMap, HashSet},
     ffi::OsStr,
@@ -12,7 +9,11 @@ use std::{
 };
 use talpid_types::ErrorExt;
 
+pub mod bundle;
 pub mod metadata;
+pub mod redact;
+
+use redact::{RedactionSummary, Redactor};
 
 /// Maximum number of bytes to read from each log file
 const LOG_MAX_READ_BYTES: usize = 128 * 1024;
@@ -23,6 +24,9 @@ const REPORT_MAX_SIZE: usize = (5 * LOG_MAX_READ_BYTES) + EXTRA_BYTES;
 /// Field delimiter in generated problem report
 const LOG_DELIMITER: &str = "====================";
 
+/// File in the settings directory that contains the device state.
+const DEVICE_FILENAME: &str = "device.json";
+
 /// Line separator character sequence
 #[cfg(not(windows))]
 const LINE_SEPARATOR: &str = "\n";
@@ -75,6 +79,37 @@ pub enum Error {
 
     #[error(display = "Unable to find cache directory")]
     ObtainCacheDirectory(#[error(source)] mullvad_paths::Error),
+
+    #[error(display = "Failed to read {}", path)]
+    ReadFileError {
+        path: String,
+        #[error(source)]
+        source: io::Error,
+    },
+
+    #[error(display = "Failed to read the redaction config at {}", path)]
+    ReadRedactionConfig {
+        path: String,
+        #[error(source)]
+        source: io::Error,
+    },
+
+    #[error(display = "Failed to parse the redaction config at {}", path)]
+    ParseRedactionConfig {
+        path: String,
+        #[error(source)]
+        source: serde_json::Error,
+    },
+
+    #[error(display = "Invalid pattern in redaction rule \"{}\"", name)]
+    InvalidRedactionRule {
+        name: String,
+        #[error(source)]
+        source: regex::Error,
+    },
+
+    #[error(display = "Unknown redaction rule \"{}\"", _0)]
+    UnknownRedactionRule(String),
 }
 
 /// These are errors that can happen during problem report collection.
@@ -104,13 +139,15 @@ pub enum LogError {
     NoLocalAppDataDir,
 }
 
+/// Collects a problem report to `output_path`. Returns the number of strings that were redacted
+/// by each rule.
 pub fn collect_report<P: AsRef<Path>>(
     extra_logs: &[P],
     output_path: &Path,
-    redact_custom_strings: Vec<String>,
+    redactor: Redactor,
     #[cfg(target_os = "android")] android_log_dir: &Path,
-) -> Result<(), Error> {
-    let mut problem_report = ProblemReport::new(redact_custom_strings);
+) -> Result<RedactionSummary, Error> {
+    let mut problem_report = ProblemReport::new(redactor);
 
     let daemon_logs_dir = {
         #[cfg(target_os = "android")]
@@ -169,10 +206,13 @@ pub fn collect_report<P: AsRef<Path>>(
 
     problem_report.add_logs(extra_logs);
 
-    write_problem_report(output_path, &problem_report).map_err(|source| Error::WriteReportError {
-        path: output_path.display().to_string(),
-        source,
-    })
+    write_problem_report(output_path, &problem_report).map_err(|source| {
+        Error::WriteReportError {
+            path: output_path.display().to_string(),
+            source,
+        }
+    })?;
+    Ok(problem_report.redaction_summary)
 }
 
 /// Returns an iterator over all files in the given directory that has the `.log` extension.
@@ -347,20 +387,20 @@ struct ProblemReport {
     metadata: BTreeMap<String, String>,
     logs: Vec<(String, String)>,
     log_paths: HashSet<PathBuf>,
-    redact_custom_strings: Vec<String>,
+    redactor: Redactor,
+    redaction_summary: RedactionSummary,
 }
 
 impl ProblemReport {
     /// Creates a new problem report with system information. Logs can be added with `add_log`.
-    /// Logs will have all strings in `redact_custom_strings` removed from them.
-    pub fn new(mut redact_custom_strings: Vec<String>) -> Self {
-        redact_custom_strings.retain(|redact| !redact.is_empty());
-
+    /// Logs will be redacted using `redactor`.
+    pub fn new(redactor: Redactor) -> Self {
         ProblemReport {
             metadata: metadata::collect(),
             logs: Vec::new(),
             log_paths: HashSet::new(),
-            redact_custom_strings,
+            redactor,
+            redaction_summary: RedactionSummary::default(),
         }
     }
 
@@ -401,53 +441,9 @@ impl ProblemReport {
         self.logs.push((message.to_string(), redacted_error));
     }
 
-    fn redact(&self, input: &str) -> String {
-        let out1 = Self::redact_account_number(input);
-        let out2 = Self::redact_home_dir(&out1);
-        let out3 = Self::redact_network_info(&out2);
-        let out4 = Self::redact_guids(&out3);
-        self.redact_custom_strings(&out4).to_string()
-    }
-
-    fn redact_account_number(input: &str) -> Cow<'_, str> {
-        static RE: Lazy<Regex> = Lazy::new(|| Regex::new("\\d{16}").unwrap());
-        RE.replace_all(input, "[REDACTED ACCOUNT NUMBER]")
-    }
-
-    fn redact_home_dir(input: &str) -> Cow<'_, str> {
-        redact_home_dir_inner(input, dirs::home_dir())
-    }
-
-    fn redact_network_info(input: &str) -> Cow<'_, str> {
-        static RE: Lazy<Regex> = Lazy::new(|| {
-            let boundary = "[^0-9a-zA-Z.:]";
-            let combined_pattern = format!(
-                "(?P<start>^|{})(?:{}|{}|{})",
-                boundary,
-                build_ipv4_regex(),
-                build_ipv6_regex(),
-                build_mac_regex(),
-            );
-            Regex::new(&combined_pattern).unwrap()
-        });
-        RE.replace_all(input, "$start[REDACTED]")
-    }
-
-    fn redact_guids(input: &str) -> Cow<'_, str> {
-        static RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"(?i)\{?[A-F0-9]{8}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{12}\}?")
-                .unwrap()
-        });
-        RE.replace_all(input, "[REDACTED]")
-    }
-
-    fn redact_custom_strings<'a>(&self, input: &'a str) -> Cow<'a, str> {
-        // Can probably me made a lot faster with aho-corasick if optimization is ever needed.
-        let mut out = Cow::from(input);
-        for redact in &self.redact_custom_strings {
-            out = out.replace(redact, "[REDACTED]").into()
-        }
-        out
+    fn redact(&mut self, input: &str) -> String {
+        self.redactor
+            .redact_with_summary(input, &mut self.redaction_summary)
     }
 
     fn write_to<W: Write>(&self, mut output: W) -> io::Result<()> {
@@ -493,87 +489,6 @@ impl ProblemReport {
     }
 }
 
-fn redact_home_dir_inner(input: &str, home_dir: Option<PathBuf>) -> Cow<'_, str> {
-    match home_dir {
-        Some(home) => {
-            let out = input.replace(home.to_string_lossy().as_ref(), "~");
-
-            // On Windows, redact the prefix of any path that contains \Users\{user}.
-            #[cfg(target_os = "windows")]
-            {
-                let mut home = home;
-                let prefix = home.components().next();
-                if let Some(prefix @ std::path::Component::Prefix(_)) = prefix.as_ref() {
-                    home = home.strip_prefix(prefix).unwrap().to_path_buf();
-                }
-                let expr = format!(r"[\w\\]+{}", regex::escape(&home.display().to_string()));
-                let regex = Regex::new(&expr).unwrap();
-
-                Cow::Owned(regex.replace_all(&out, "~").to_string())
-            }
-
-            #[cfg(not(target_os = "windows"))]
-            Cow::from(out)
-        }
-        None => Cow::from(input),
-    }
-}
-
-fn build_mac_regex() -> String {
-    let octet = "[[:xdigit:]]{2}"; // 0 - ff
-
-    // five pairs of two hexadecimal chars followed by colon or dash
-    // followed by a pair of hexadecimal chars
-    format!("(?:{octet}[:-]){{5}}({octet})")
-}
-
-fn build_ipv4_regex() -> String {
-    // regex adapted from  https://www.regular-expressions.info/ip.html
-
-    let above_250 = "25[0-5]";
-    let above_200 = "2[0-4][0-9]";
-    let above_100 = "1[0-9][0-9]";
-
-    // 100-119 | 120-126 | 128-129 | 130 - 199
-    let above_100_not_127 = "1(?:[01][0-9]|2[0-6]|2[89]|[3-9][0-9])";
-
-    let above_0 = "0?[0-9][0-9]?";
-
-    // matches 0-255, except 127
-    let first_octet = format!("(?:{above_250}|{above_200}|{above_100_not_127}|{above_0})");
-
-    // matches 0-255
-    let ip_octet = format!("(?:{above_250}|{above_200}|{above_100}|{above_0})");
-
-    format!("(?:{first_octet}\\.{ip_octet}\\.{ip_octet}\\.{ip_octet})")
-}
-
-fn build_ipv6_regex() -> String {
-    // Regular expression obtained from:
-    // https://stackoverflow.com/a/17871737
-    let ipv4_segment = "(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])";
-    let ipv4_address = format!("({ipv4_segment}\\.){{3,3}}{ipv4_segment}");
-
-    let ipv6_segment = "[0-9a-fA-F]{1,4}";
-
-    let long = format!("({ipv6_segment}:){{7,7}}{ipv6_segment}");
-    let compressed_1 = format!("({ipv6_segment}:){{1,7}}:");
-    let compressed_2 = format!("({ipv6_segment}:){{1,6}}:{ipv6_segment}");
-    let compressed_3 = format!("({ipv6_segment}:){{1,5}}(:{ipv6_segment}){{1,2}}");
-    let compressed_4 = format!("({ipv6_segment}:){{1,4}}(:{ipv6_segment}){{1,3}}");
-    let compressed_5 = format!("({ipv6_segment}:){{1,3}}(:{ipv6_segment}){{1,4}}");
-    let compressed_6 = format!("({ipv6_segment}:){{1,2}}(:{ipv6_segment}){{1,5}}");
-    let compressed_7 = format!("{ipv6_segment}:((:{ipv6_segment}){{1,6}})");
-    let compressed_8 = format!(":((:{ipv6_segment}){{1,7}}|:)");
-    let link_local = "[Ff][Ee]80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}";
-    let ipv4_mapped = format!("::([fF]{{4}}(:0{{1,4}}){{0,1}}:){{0,1}}{ipv4_address}");
-    let ipv4_embedded = format!("({ipv6_segment}:){{1,4}}:{ipv4_address}");
-
-    format!(
-        "{long}|{link_local}|{ipv4_mapped}|{ipv4_embedded}|{compressed_8}|{compressed_7}|{compressed_6}|{compressed_5}|{compressed_4}|{compressed_3}|{compressed_2}|{compressed_1}",
-    )
-}
-
 /// Helper to lossily read a file to a `String`. If the file size exceeds the given `max_bytes`,
 /// only the last `max_bytes` bytes of the file are read.
 fn read_file_lossy(path: &Path, max_bytes: usize) -> io::Result<String> {
@@ -649,21 +564,6 @@ mod tests {
         assert_redacts("{123123ab-12ab-89cd-45ef-012345678901}");
     }
 
-    #[test]
-    #[cfg(windows)]
-    fn redacts_home_dir() {
-        let assert_redacts_home_dir = |home_dir, test_str| {
-            let input = format!(r"pre {}\remaining\path post", test_str);
-            let actual = redact_home_dir_inner(&input, Some(PathBuf::from(home_dir)));
-            assert_eq!(r"pre ~\remaining\path post", actual);
-        };
-
-        let home_dir = r"C:\Users\user";
-
-        assert_redacts_home_dir(home_dir, r"\Device\HarddiskVolume1\Users\user");
-        assert_redacts_home_dir(home_dir, r"C:\Users\user");
-    }
-
     #[test]
     fn doesnt_redact_not_guid() {
         assert_does_not_redact("23123ab-12ab-89cd-45ef-012345678901");
@@ -676,20 +576,20 @@ mod tests {
     }
 
     fn assert_redacts(input: &str) {
-        let report = ProblemReport::new(vec![]);
+        let mut report = ProblemReport::new(Redactor::new(vec![]));
         let actual = report.redact(&format!("pre {input} post"));
         assert_eq!("pre [REDACTED] post", actual);
     }
 
     fn assert_does_not_redact(input: &str) {
-        let report = ProblemReport::new(vec![]);
+        let mut report = ProblemReport::new(Redactor::new(vec![]));
         let res = report.redact(input);
         assert_eq!(input, res);
     }
 
     #[test]
     fn parse_metadata() {
-        let report = ProblemReport::new(Vec::new());
+        let report = ProblemReport::new(Redactor::new(Vec::new()));
         let mut report_data = Vec::new();
         report
             .write_to(&mut report_data)
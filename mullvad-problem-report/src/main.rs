@@ -1,7 +1,13 @@
 use clap::Parser;
-use mullvad_problem_report::{collect_report, Error};
+use mullvad_problem_report::{
+    bundle::DiagnosticBundle,
+    collect_report,
+    redact::{RedactionConfig, RedactionSummary, Redactor},
+    Error,
+};
 use std::{
-    env,
+    env, fs,
+    io::{self, BufRead, Write},
     path::{Path, PathBuf},
     process,
 };
@@ -35,6 +41,43 @@ enum Cli {
         /// List of strings to remove from the report
         #[arg(long)]
         redact: Vec<String>,
+        /// Path to a JSON file that disables or adds redaction rules
+        #[arg(long)]
+        redaction_config: Option<PathBuf>,
+    },
+
+    /// Collect a local diagnostic bundle. The bundle is a tar archive with logs, settings, device
+    /// state and network configuration. It is never sent anywhere
+    Bundle {
+        /// The destination path for saving the bundle
+        #[arg(required = true, long, short = 'o')]
+        output: PathBuf,
+        /// Paths to additional log files to be included
+        extra_logs: Vec<PathBuf>,
+        /// List of strings to remove from the bundle
+        #[arg(long)]
+        redact: Vec<String>,
+        /// Path to a JSON file that disables or adds redaction rules
+        #[arg(long)]
+        redaction_config: Option<PathBuf>,
+        /// Show every redacted string and ask for confirmation before writing the bundle
+        #[arg(long)]
+        preview: bool,
+    },
+
+    /// Redact a file using the same rules as problem reports, and list the rules that matched
+    Redact {
+        /// Path to the file to redact
+        file: PathBuf,
+        /// The destination path for the redacted file. Defaults to stdout
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+        /// List of strings to remove from the file
+        #[arg(long)]
+        redact: Vec<String>,
+        /// Path to a JSON file that disables or adds redaction rules
+        #[arg(long)]
+        redaction_config: Option<PathBuf>,
     },
 
     /// Send collected problem report
@@ -59,14 +102,65 @@ fn run() -> Result<(), Error> {
             output,
             extra_logs,
             redact,
+            redaction_config,
         } => {
-            collect_report(&extra_logs, &output, redact)?;
+            let redactor = create_redactor(redact, redaction_config.as_deref())?;
+            let summary = collect_report(&extra_logs, &output, redactor)?;
 
             println!("Problem report written to {}", output.display());
+            print_summary(&summary);
             println!();
             println!("Send the problem report to support via the send subcommand. See:");
             println!(" $ {} send --help", env::args().next().unwrap());
         }
+        Cli::Bundle {
+            output,
+            extra_logs,
+            redact,
+            redaction_config,
+            preview,
+        } => {
+            let redactor = create_redactor(redact, redaction_config.as_deref())?;
+            let bundle = DiagnosticBundle::collect(&extra_logs, redactor);
+
+            if preview && !confirm_redactions(&bundle) {
+                println!("No bundle was written");
+                return Ok(());
+            }
+
+            bundle.write(&output)?;
+            println!("Diagnostic bundle written to {}", output.display());
+            print_summary(bundle.summary());
+        }
+        Cli::Redact {
+            file,
+            output,
+            redact,
+            redaction_config,
+        } => {
+            let redactor = create_redactor(redact, redaction_config.as_deref())?;
+            let content = fs::read(&file).map_err(|source| Error::ReadFileError {
+                path: file.display().to_string(),
+                source,
+            })?;
+
+            let mut summary = RedactionSummary::default();
+            let redacted =
+                redactor.redact_with_summary(&String::from_utf8_lossy(&content), &mut summary);
+
+            match output {
+                Some(output) => {
+                    fs::write(&output, redacted).map_err(|source| Error::WriteReportError {
+                        path: output.display().to_string(),
+                        source,
+                    })?
+                }
+                None => print!("{redacted}"),
+            }
+            // Print the summary to stderr so that it does not end up in redirected output
+            eprintln!("Matched redaction rules:");
+            eprint!("{summary}");
+        }
         Cli::Send {
             report,
             email,
@@ -83,6 +177,47 @@ fn run() -> Result<(), Error> {
     Ok(())
 }
 
+fn create_redactor(
+    custom_strings: Vec<String>,
+    config_path: Option<&Path>,
+) -> Result<Redactor, Error> {
+    let config = match config_path {
+        Some(path) => RedactionConfig::load(path)?,
+        None => RedactionConfig::default(),
+    };
+    Redactor::with_config(custom_strings, &config)
+}
+
+fn print_summary(summary: &RedactionSummary) {
+    println!();
+    println!("Matched redaction rules:");
+    print!("{summary}");
+}
+
+/// Prints all redactions in the bundle and asks whether to write it.
+fn confirm_redactions(bundle: &DiagnosticBundle) -> bool {
+    let mut current_file = None;
+    let mut count = 0;
+    for redaction in bundle.redactions() {
+        if current_file != Some(&redaction.file) {
+            println!("{}:", redaction.file);
+            current_file = Some(&redaction.file);
+        }
+        println!("    {}: {}", redaction.rule, redaction.original);
+        count += 1;
+    }
+    println!();
+    println!("{count} strings will be redacted");
+
+    print!("Write the bundle? [y/N] ");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
 fn send_problem_report(
     user_email: &str,
     user_message: &str,
@@ -0,0 +1,681 @@
+//! Removes identifying information from problem reports. Redaction is done by a pipeline of
+//! named rules. Each rule is a regular expression. If the expression contains a group named
+//! `redact`, only that group is replaced, otherwise the whole match is.
+//!
+//! The built-in rules can be disabled and extended with a JSON config file:
+//!
+//! ```json
+//! {
+//!     "disabled_rules": ["guid"],
+//!     "rules": [{ "name": "ticket-id", "pattern": "TICKET-\\d+" }],
+//!     "strings": ["my-laptop"]
+//! }
+//! ```
+
+use crate::Error;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_REPLACEMENT: &str = "[REDACTED]";
+
+/// Names of the built-in rules, in the order that they are applied. Custom rules from the config
+/// and custom strings are applied after these.
+pub const BUILTIN_RULES: &[&str] = &[
+    WIREGUARD_KEY_RULE,
+    ACCOUNT_NUMBER_RULE,
+    HOME_DIR_RULE,
+    NETWORK_ADDRESS_RULE,
+    GUID_RULE,
+    WIFI_SSID_RULE,
+    HOSTNAME_RULE,
+    USERNAME_RULE,
+    DEVICE_NAME_RULE,
+];
+
+const WIREGUARD_KEY_RULE: &str = "wireguard-public-key";
+const ACCOUNT_NUMBER_RULE: &str = "account-number";
+const HOME_DIR_RULE: &str = "home-dir";
+const NETWORK_ADDRESS_RULE: &str = "network-address";
+const GUID_RULE: &str = "guid";
+const WIFI_SSID_RULE: &str = "wifi-ssid";
+const HOSTNAME_RULE: &str = "hostname";
+const USERNAME_RULE: &str = "username";
+const DEVICE_NAME_RULE: &str = "device-name";
+/// Name of the rule that removes strings given on the command line or in the config.
+pub const CUSTOM_STRINGS_RULE: &str = "custom-strings";
+
+/// Usernames that do not identify anyone, and which are common words in logs.
+const IGNORED_USERNAMES: &[&str] = &["root", "SYSTEM"];
+
+/// User configuration of the redaction pipeline.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactionConfig {
+    /// Names of built-in rules that should not be applied.
+    pub disabled_rules: Vec<String>,
+    /// Rules to apply in addition to the built-in ones.
+    pub rules: Vec<CustomRule>,
+    /// Strings to remove in addition to those given on the command line.
+    pub strings: Vec<String>,
+}
+
+impl RedactionConfig {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|source| Error::ReadRedactionConfig {
+            path: path.display().to_string(),
+            source,
+        })?;
+        serde_json::from_str(&content).map_err(|source| Error::ParseRedactionConfig {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+}
+
+/// A user-defined redaction rule.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRule {
+    pub name: String,
+    /// Regular expression to match. May contain a group named `redact`.
+    pub pattern: String,
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+fn default_replacement() -> String {
+    DEFAULT_REPLACEMENT.to_owned()
+}
+
+/// Number of matches of each rule that fired.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RedactionSummary {
+    matches: BTreeMap<String, usize>,
+}
+
+impl RedactionSummary {
+    pub fn record(&mut self, rule: &str) {
+        *self.matches.entry(rule.to_owned()).or_default() += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Returns the number of matches of `rule`.
+    pub fn count(&self, rule: &str) -> usize {
+        self.matches.get(rule).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.matches
+            .iter()
+            .map(|(rule, count)| (rule.as_str(), *count))
+    }
+}
+
+impl fmt::Display for RedactionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No redaction rules matched");
+        }
+        for (rule, count) in self.iter() {
+            writeln!(f, "{rule}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    regex: Regex,
+    replacement: String,
+}
+
+impl Rule {
+    fn new(name: &str, regex: Regex, replacement: &str) -> Self {
+        Rule {
+            name: name.to_owned(),
+            regex,
+            replacement: replacement.to_owned(),
+        }
+    }
+
+    /// Returns a rule that matches any of `strings`, or `None` if there are no strings.
+    fn literals(name: &str, strings: &[String], whole_words: bool) -> Option<Self> {
+        let mut strings: Vec<_> = strings.iter().filter(|s| !s.is_empty()).collect();
+        if strings.is_empty() {
+            return None;
+        }
+        // Prefer the longest match when strings overlap
+        strings.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        let alternatives = strings
+            .iter()
+            .map(|s| regex::escape(s))
+            .collect::<Vec<_>>()
+            .join("|");
+        let pattern = if whole_words {
+            format!(r"(?i)\b(?:{alternatives})\b")
+        } else {
+            format!("(?:{alternatives})")
+        };
+        Some(Rule::new(
+            name,
+            Regex::new(&pattern).unwrap(),
+            DEFAULT_REPLACEMENT,
+        ))
+    }
+
+    fn apply<'a>(&self, input: &'a str, on_redact: &mut dyn FnMut(&str, &str)) -> Cow<'a, str> {
+        self.regex.replace_all(input, |caps: &Captures<'_>| {
+            let whole = caps.get(0).unwrap();
+            match caps.name("redact") {
+                Some(redacted) => {
+                    on_redact(&self.name, redacted.as_str());
+                    let start = redacted.start() - whole.start();
+                    let end = redacted.end() - whole.start();
+                    format!(
+                        "{}{}{}",
+                        &whole.as_str()[..start],
+                        self.replacement,
+                        &whole.as_str()[end..]
+                    )
+                }
+                None => {
+                    on_redact(&self.name, whole.as_str());
+                    self.replacement.clone()
+                }
+            }
+        })
+    }
+}
+
+/// Identifying information about the local machine and user.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct LocalInfo {
+    pub home_dir: Option<PathBuf>,
+    pub hostname: Option<String>,
+    pub username: Option<String>,
+    pub device_name: Option<String>,
+}
+
+impl LocalInfo {
+    fn collect() -> Self {
+        LocalInfo {
+            home_dir: dirs::home_dir(),
+            hostname: local_hostname(),
+            username: local_username(),
+            device_name: mullvad_device_name(),
+        }
+    }
+}
+
+/// Applies redaction rules to text.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<Rule>,
+}
+
+impl Redactor {
+    /// Returns a redactor with all built-in rules, which also removes `custom_strings`.
+    pub fn new(custom_strings: Vec<String>) -> Self {
+        Self::with_config(custom_strings, &RedactionConfig::default())
+            .expect("the default config is valid")
+    }
+
+    /// Returns a redactor with the built-in rules, adjusted by `config`, which also removes
+    /// `custom_strings`.
+    pub fn with_config(
+        custom_strings: Vec<String>,
+        config: &RedactionConfig,
+    ) -> Result<Self, Error> {
+        Self::from_local_info(LocalInfo::collect(), custom_strings, config)
+    }
+
+    pub(crate) fn from_local_info(
+        info: LocalInfo,
+        mut custom_strings: Vec<String>,
+        config: &RedactionConfig,
+    ) -> Result<Self, Error> {
+        if let Some(unknown) = config
+            .disabled_rules
+            .iter()
+            .find(|rule| !BUILTIN_RULES.contains(&rule.as_str()))
+        {
+            return Err(Error::UnknownRedactionRule(unknown.clone()));
+        }
+
+        let mut rules: Vec<_> = builtin_rules(info)
+            .into_iter()
+            .filter(|rule| !config.disabled_rules.contains(&rule.name))
+            .collect();
+
+        for rule in &config.rules {
+            let regex =
+                Regex::new(&rule.pattern).map_err(|source| Error::InvalidRedactionRule {
+                    name: rule.name.clone(),
+                    source,
+                })?;
+            rules.push(Rule::new(&rule.name, regex, &rule.replacement));
+        }
+
+        custom_strings.extend(config.strings.iter().cloned());
+        rules.extend(Rule::literals(CUSTOM_STRINGS_RULE, &custom_strings, false));
+
+        Ok(Redactor { rules })
+    }
+
+    /// Returns the names of the rules that are applied, in order.
+    pub fn rule_names(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.name.as_str())
+    }
+
+    pub fn redact(&self, input: &str) -> String {
+        self.redact_and_record(input, &mut |_, _| ())
+    }
+
+    /// Redacts `input`, and adds the number of matches of each rule to `summary`.
+    pub fn redact_with_summary(&self, input: &str, summary: &mut RedactionSummary) -> String {
+        self.redact_and_record(input, &mut |rule, _| summary.record(rule))
+    }
+
+    /// Redacts `input`, and calls `on_redact` with the rule name and original value of every
+    /// redacted string.
+    pub fn redact_and_record(&self, input: &str, on_redact: &mut dyn FnMut(&str, &str)) -> String {
+        let mut out = Cow::from(input);
+        for rule in &self.rules {
+            if let Cow::Owned(redacted) = rule.apply(&out, on_redact) {
+                out = Cow::Owned(redacted);
+            }
+        }
+        out.into_owned()
+    }
+}
+
+fn builtin_rules(info: LocalInfo) -> Vec<Rule> {
+    let mut rules = vec![
+        Rule::new(
+            WIREGUARD_KEY_RULE,
+            WIREGUARD_KEY_RE.clone(),
+            DEFAULT_REPLACEMENT,
+        ),
+        Rule::new(
+            ACCOUNT_NUMBER_RULE,
+            ACCOUNT_NUMBER_RE.clone(),
+            "[REDACTED ACCOUNT NUMBER]",
+        ),
+    ];
+    rules.extend(home_dir_rule(info.home_dir));
+    rules.extend([
+        Rule::new(
+            NETWORK_ADDRESS_RULE,
+            NETWORK_ADDRESS_RE.clone(),
+            DEFAULT_REPLACEMENT,
+        ),
+        Rule::new(GUID_RULE, GUID_RE.clone(), DEFAULT_REPLACEMENT),
+        Rule::new(WIFI_SSID_RULE, WIFI_SSID_RE.clone(), DEFAULT_REPLACEMENT),
+    ]);
+    let literal_rules = [
+        (HOSTNAME_RULE, info.hostname),
+        (USERNAME_RULE, info.username),
+        (DEVICE_NAME_RULE, info.device_name),
+    ];
+    for (name, value) in literal_rules {
+        rules.extend(Rule::literals(name, &Vec::from_iter(value), true));
+    }
+
+    debug_assert!(rules
+        .iter()
+        .all(|rule| BUILTIN_RULES.contains(&rule.name.as_str())));
+    rules
+}
+
+static ACCOUNT_NUMBER_RE: Lazy<Regex> = Lazy::new(|| Regex::new("\\d{16}").unwrap());
+
+static NETWORK_ADDRESS_RE: Lazy<Regex> = Lazy::new(|| {
+    let boundary = "[^0-9a-zA-Z.:]";
+    let combined_pattern = format!(
+        "(?:^|{})(?P<redact>{}|{}|{})",
+        boundary,
+        build_ipv4_regex(),
+        build_ipv6_regex(),
+        build_mac_regex(),
+    );
+    Regex::new(&combined_pattern).unwrap()
+});
+
+static GUID_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\{?[A-F0-9]{8}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{12}\}?").unwrap()
+});
+
+/// Matches base64 encoded 32-byte keys. The last character before the padding only has two
+/// significant bits, so it is one of a few values.
+static WIREGUARD_KEY_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:^|[^A-Za-z0-9+/])(?P<redact>[A-Za-z0-9+/]{42}[AEIMQUYcgkosw048]=)").unwrap()
+});
+
+/// Matches SSIDs in `key: value` and `key=value` pairs, such as the ones printed by
+/// NetworkManager and wpa_supplicant. Quoted values may contain spaces.
+static WIFI_SSID_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\bssid\b["']?\s*[:=]\s*(?P<redact>"[^"\r\n]*"|'[^'\r\n]*'|[^\s,;"']+)"#)
+        .unwrap()
+});
+
+fn home_dir_rule(home_dir: Option<PathBuf>) -> Option<Rule> {
+    let home = home_dir?;
+    let home_str = home.to_string_lossy();
+    if home_str.is_empty() {
+        return None;
+    }
+    #[cfg_attr(not(target_os = "windows"), allow(unused_mut))]
+    let mut pattern = regex::escape(&home_str);
+
+    // On Windows, also redact the prefix of any path that contains \Users\{user}.
+    #[cfg(target_os = "windows")]
+    {
+        let mut home = home.clone();
+        let prefix = home.components().next();
+        if let Some(prefix @ std::path::Component::Prefix(_)) = prefix.as_ref() {
+            home = home.strip_prefix(prefix).unwrap().to_path_buf();
+        }
+        pattern = format!(
+            r"{pattern}|[\w\\]+{}",
+            regex::escape(&home.display().to_string())
+        );
+    }
+
+    Some(Rule::new(HOME_DIR_RULE, Regex::new(&pattern).unwrap(), "~"))
+}
+
+#[cfg(all(unix, not(target_os = "android")))]
+fn local_hostname() -> Option<String> {
+    let output = std::process::Command::new("hostname").output().ok()?;
+    let hostname = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (!hostname.is_empty()).then_some(hostname)
+}
+
+#[cfg(windows)]
+fn local_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(target_os = "android")]
+fn local_hostname() -> Option<String> {
+    None
+}
+
+fn local_username() -> Option<String> {
+    // When run using sudo, the invoking user is the interesting one
+    let username = ["SUDO_USER", "USER", "USERNAME"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok())?;
+    (!username.is_empty() && !IGNORED_USERNAMES.contains(&username.as_str())).then_some(username)
+}
+
+/// Returns the name of the Mullvad device that is logged in, if any.
+fn mullvad_device_name() -> Option<String> {
+    let settings_dir = mullvad_paths::settings_dir().ok()?;
+    let device = fs::read_to_string(settings_dir.join(crate::DEVICE_FILENAME)).ok()?;
+    let device: serde_json::Value = serde_json::from_str(&device).ok()?;
+    device["logged_in"]["device"]["name"]
+        .as_str()
+        .map(str::to_owned)
+}
+
+fn build_mac_regex() -> String {
+    let octet = "[[:xdigit:]]{2}"; // 0 - ff
+
+    // five pairs of two hexadecimal chars followed by colon or dash
+    // followed by a pair of hexadecimal chars
+    format!("(?:{octet}[:-]){{5}}({octet})")
+}
+
+fn build_ipv4_regex() -> String {
+    // regex adapted from  https://www.regular-expressions.info/ip.html
+
+    let above_250 = "25[0-5]";
+    let above_200 = "2[0-4][0-9]";
+    let above_100 = "1[0-9][0-9]";
+
+    // 100-119 | 120-126 | 128-129 | 130 - 199
+    let above_100_not_127 = "1(?:[01][0-9]|2[0-6]|2[89]|[3-9][0-9])";
+
+    let above_0 = "0?[0-9][0-9]?";
+
+    // matches 0-255, except 127
+    let first_octet = format!("(?:{above_250}|{above_200}|{above_100_not_127}|{above_0})");
+
+    // matches 0-255
+    let ip_octet = format!("(?:{above_250}|{above_200}|{above_100}|{above_0})");
+
+    format!("(?:{first_octet}\\.{ip_octet}\\.{ip_octet}\\.{ip_octet})")
+}
+
+fn build_ipv6_regex() -> String {
+    // Regular expression obtained from:
+    // https://stackoverflow.com/a/17871737
+    let ipv4_segment = "(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])";
+    let ipv4_address = format!("({ipv4_segment}\\.){{3,3}}{ipv4_segment}");
+
+    let ipv6_segment = "[0-9a-fA-F]{1,4}";
+
+    let long = format!("({ipv6_segment}:){{7,7}}{ipv6_segment}");
+    let compressed_1 = format!("({ipv6_segment}:){{1,7}}:");
+    let compressed_2 = format!("({ipv6_segment}:){{1,6}}:{ipv6_segment}");
+    let compressed_3 = format!("({ipv6_segment}:){{1,5}}(:{ipv6_segment}){{1,2}}");
+    let compressed_4 = format!("({ipv6_segment}:){{1,4}}(:{ipv6_segment}){{1,3}}");
+    let compressed_5 = format!("({ipv6_segment}:){{1,3}}(:{ipv6_segment}){{1,4}}");
+    let compressed_6 = format!("({ipv6_segment}:){{1,2}}(:{ipv6_segment}){{1,5}}");
+    let compressed_7 = format!("{ipv6_segment}:((:{ipv6_segment}){{1,6}})");
+    let compressed_8 = format!(":((:{ipv6_segment}){{1,7}}|:)");
+    let link_local = "[Ff][Ee]80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}";
+    let ipv4_mapped = format!("::([fF]{{4}}(:0{{1,4}}){{0,1}}:){{0,1}}{ipv4_address}");
+    let ipv4_embedded = format!("({ipv6_segment}:){{1,4}}:{ipv4_address}");
+
+    format!(
+        "{long}|{link_local}|{ipv4_mapped}|{ipv4_embedded}|{compressed_8}|{compressed_7}|{compressed_6}|{compressed_5}|{compressed_4}|{compressed_3}|{compressed_2}|{compressed_1}",
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An example that each rule should redact, and what it should be replaced with.
+    const SAMPLES: &[(&str, &str, &str)] = &[
+        (
+            WIREGUARD_KEY_RULE,
+            "pubkey: yIRjWuG4xFjMbnZzZ1Q8X7L8VhgPyDhm9PnKhGCzEhU=",
+            "pubkey: [REDACTED]",
+        ),
+        (
+            ACCOUNT_NUMBER_RULE,
+            "account 1234567890123456",
+            "account [REDACTED ACCOUNT NUMBER]",
+        ),
+        (HOME_DIR_RULE, "path /home/alice/logs", "path ~/logs"),
+        (
+            NETWORK_ADDRESS_RULE,
+            "addr 10.0.0.1 up",
+            "addr [REDACTED] up",
+        ),
+        (
+            GUID_RULE,
+            "guid {123123ab-12ab-89cd-45ef-012345678901}",
+            "guid [REDACTED]",
+        ),
+        (
+            WIFI_SSID_RULE,
+            "connected to SSID: \"Home Network\" at",
+            "connected to SSID: [REDACTED] at",
+        ),
+        (HOSTNAME_RULE, "host alices-laptop.", "host [REDACTED]."),
+        (
+            USERNAME_RULE,
+            "user Alice logged in",
+            "user [REDACTED] logged in",
+        ),
+        (
+            DEVICE_NAME_RULE,
+            "device Happy Seagull created",
+            "device [REDACTED] created",
+        ),
+    ];
+
+    fn test_info() -> LocalInfo {
+        LocalInfo {
+            home_dir: Some(PathBuf::from("/home/alice")),
+            hostname: Some("alices-laptop".to_owned()),
+            username: Some("alice".to_owned()),
+            device_name: Some("happy seagull".to_owned()),
+        }
+    }
+
+    fn test_redactor(custom_strings: Vec<String>, config: &RedactionConfig) -> Redactor {
+        Redactor::from_local_info(test_info(), custom_strings, config).unwrap()
+    }
+
+    /// Every built-in rule should be covered by `SAMPLES`
+    #[test]
+    fn samples_cover_all_rules() {
+        for rule in BUILTIN_RULES {
+            assert!(
+                SAMPLES.iter().any(|(name, _, _)| name == rule),
+                "missing sample for rule '{rule}'"
+            );
+        }
+        let redactor = test_redactor(vec![], &RedactionConfig::default());
+        assert_eq!(redactor.rule_names().collect::<Vec<_>>(), BUILTIN_RULES);
+    }
+
+    #[test]
+    fn each_rule_redacts_its_sample() {
+        let redactor = test_redactor(vec![], &RedactionConfig::default());
+        for (rule, input, expected) in SAMPLES {
+            let mut summary = RedactionSummary::default();
+            let actual = redactor.redact_with_summary(input, &mut summary);
+            assert_eq!(&actual, expected, "rule '{rule}'");
+            assert_eq!(summary.count(rule), 1, "rule '{rule}' did not fire once");
+            assert_eq!(
+                summary.iter().count(),
+                1,
+                "other rules fired for '{rule}': {summary}"
+            );
+        }
+    }
+
+    #[test]
+    fn disabled_rules_do_not_fire() {
+        for (rule, input, _) in SAMPLES {
+            let config = RedactionConfig {
+                disabled_rules: vec![rule.to_string()],
+                ..Default::default()
+            };
+            let redactor = test_redactor(vec![], &config);
+            assert!(!redactor.rule_names().any(|name| name == *rule));
+
+            let mut summary = RedactionSummary::default();
+            redactor.redact_with_summary(input, &mut summary);
+            assert_eq!(summary.count(rule), 0, "rule '{rule}' fired");
+        }
+    }
+
+    #[test]
+    fn unknown_disabled_rule_is_rejected() {
+        let config = RedactionConfig {
+            disabled_rules: vec!["no-such-rule".to_owned()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            Redactor::from_local_info(test_info(), vec![], &config),
+            Err(Error::UnknownRedactionRule(_))
+        ));
+    }
+
+    #[test]
+    fn custom_rules_and_strings() {
+        let config: RedactionConfig = serde_json::from_str(
+            r#"{
+                "rules": [
+                    { "name": "ticket", "pattern": "TICKET-\\d+" },
+                    { "name": "token", "pattern": "token=(?P<redact>\\w+)", "replacement": "***" }
+                ],
+                "strings": ["secret"]
+            }"#,
+        )
+        .unwrap();
+        let redactor = test_redactor(vec!["hidden".to_owned()], &config);
+
+        let mut summary = RedactionSummary::default();
+        let actual =
+            redactor.redact_with_summary("TICKET-42 token=abc secret hidden secret", &mut summary);
+        assert_eq!(
+            actual,
+            "[REDACTED] token=*** [REDACTED] [REDACTED] [REDACTED]"
+        );
+        assert_eq!(summary.count("ticket"), 1);
+        assert_eq!(summary.count("token"), 1);
+        assert_eq!(summary.count(CUSTOM_STRINGS_RULE), 3);
+    }
+
+    #[test]
+    fn invalid_custom_rule_is_rejected() {
+        let config = RedactionConfig {
+            rules: vec![CustomRule {
+                name: "broken".to_owned(),
+                pattern: "(".to_owned(),
+                replacement: default_replacement(),
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            Redactor::from_local_info(test_info(), vec![], &config),
+            Err(Error::InvalidRedactionRule { .. })
+        ));
+    }
+
+    #[test]
+    fn literal_rules_match_whole_words_only() {
+        let redactor = test_redactor(vec![], &RedactionConfig::default());
+        assert_eq!(redactor.redact("malice"), "malice");
+        assert_eq!(redactor.redact("ALICE"), "[REDACTED]");
+    }
+
+    #[test]
+    fn ssid_without_quotes() {
+        let redactor = test_redactor(vec![], &RedactionConfig::default());
+        assert_eq!(redactor.redact("ssid=CoffeeShop,"), "ssid=[REDACTED],");
+        assert_eq!(redactor.redact("\"ssid\": 'Cafe'"), "\"ssid\": [REDACTED]");
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn redacts_home_dir() {
+        let assert_redacts_home_dir = |home_dir, test_str| {
+            let input = format!(r"pre {}\remaining\path post", test_str);
+            let rule = home_dir_rule(Some(PathBuf::from(home_dir))).unwrap();
+            let actual = rule.apply(&input, &mut |_, _| ());
+            assert_eq!(r"pre ~\remaining\path post", actual);
+        };
+
+        let home_dir = r"C:\Users\user";
+
+        assert_redacts_home_dir(home_dir, r"\Device\HarddiskVolume1\Users\user");
+        assert_redacts_home_dir(home_dir, r"C:\Users\user");
+    }
+
+    #[test]
+    fn does_not_redact_non_key_base64() {
+        let redactor = test_redactor(vec![], &RedactionConfig::default());
+        // Too short to be a key
+        let input = "dGhpcyBpcyBub3QgYSBrZXk=";
+        assert_eq!(redactor.redact(input), input);
+    }
+}
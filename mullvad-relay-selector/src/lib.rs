@@ -4,25 +4,32 @@
 use chrono::{DateTime, Local};
 use ipnetwork::IpNetwork;
 use mullvad_types::{
+    connection_plan::{
+        AttemptOutcome, AttemptSelection, AttemptTemplate, ConnectionPlan, SimulatedAttempt,
+    },
     custom_list::CustomListsSettings,
     endpoint::{MullvadEndpoint, MullvadWireguardEndpoint},
     location::{Coordinates, Location},
     relay_constraints::{
-        BridgeSettings, BridgeState, Constraint, InternalBridgeConstraints, LocationConstraint,
-        Match, MissingCustomBridgeSettings, ObfuscationSettings, OpenVpnConstraints, Ownership,
-        Providers, RelayConstraints, RelayConstraintsFormatter, RelayOverride, RelaySettings,
-        ResolvedBridgeSettings, ResolvedLocationConstraint, SelectedObfuscation, Set,
-        TransportPort, Udp2TcpObfuscationSettings,
+        BridgeSettings, BridgeState, Constraint, DiversityRule, InternalBridgeConstraints,
+        LocationConstraint, Match, MissingCustomBridgeSettings, ObfuscationSettings,
+        OpenVpnConstraints, Ownership, Providers, RelayConstraints, RelayConstraintsFormatter,
+        RelayOverride, RelaySettings, ResolvedBridgeSettings, ResolvedLocationConstraint,
+        SelectedObfuscation, Set, TransportPort, Udp2TcpObfuscationSettings,
+    },
+    relay_list::{
+        BridgeEndpointData, Relay, RelayEndpointData, RelayList, RelayListCity, RelayListCountry,
     },
-    relay_list::{BridgeEndpointData, Relay, RelayEndpointData, RelayList},
+    relay_query::{RelayCandidate, RelayQuery, RelayQueryResult},
     settings::Settings,
+    user_relay::UserRelay,
     CustomTunnelEndpoint,
 };
 use parking_lot::{Mutex, MutexGuard};
 use rand::{seq::SliceRandom, Rng};
 use std::{
     collections::HashMap,
-    io,
+    fmt, io,
     net::{IpAddr, SocketAddr},
     path::Path,
     sync::Arc,
@@ -30,8 +37,8 @@ use std::{
 };
 use talpid_types::{
     net::{
-        obfuscation::ObfuscatorConfig, proxy::CustomProxy, wireguard, IpVersion, TransportProtocol,
-        TunnelType,
+        nat64::Nat64Prefix, obfuscation::ObfuscatorConfig, proxy::CustomProxy, wireguard,
+        Connectivity, IpVersion, TransportProtocol, TunnelType,
     },
     ErrorExt,
 };
@@ -81,6 +88,32 @@ pub enum Error {
 
     #[error(display = "Invalid bridge settings")]
     InvalidBridgeSettings(#[error(source)] MissingCustomBridgeSettings),
+
+    #[error(
+        display = "No multihop entry and exit relays satisfy the diversity constraints: {}",
+        _0
+    )]
+    NoDiverseMultihopPair(UnsatisfiedDiversity),
+}
+
+/// The multihop diversity constraints that no pair of entry and exit relays satisfies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsatisfiedDiversity(pub Vec<DiversityRule>);
+
+impl fmt::Display for UnsatisfiedDiversity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "each can be satisfied, but not all at once");
+        }
+        write!(f, "no pair has ")?;
+        for (i, rule) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " or ")?;
+            }
+            write!(f, "{rule}")?;
+        }
+        Ok(())
+    }
 }
 
 struct ParsedRelays {
@@ -88,6 +121,8 @@ struct ParsedRelays {
     parsed_list: RelayList,
     original_list: RelayList,
     overrides: Vec<RelayOverride>,
+    user_relays: Vec<UserRelay>,
+    nat64_prefix: Option<Nat64Prefix>,
 }
 
 impl ParsedRelays {
@@ -97,7 +132,13 @@ impl ParsedRelays {
     }
 
     pub fn update(&mut self, new_relays: RelayList) {
-        *self = Self::from_relay_list(new_relays, SystemTime::now(), &self.overrides);
+        *self = Self::from_relay_list(
+            new_relays,
+            SystemTime::now(),
+            &self.overrides,
+            &self.user_relays,
+            self.nat64_prefix,
+        );
 
         log::info!(
             "Updated relay inventory has {} relays",
@@ -109,9 +150,35 @@ impl ParsedRelays {
         self.last_updated
     }
 
-    fn set_overrides(&mut self, new_overrides: &[RelayOverride]) {
-        self.parsed_list = Self::parse_relay_list(&self.original_list, new_overrides);
+    fn set_user_data(&mut self, new_overrides: &[RelayOverride], new_user_relays: &[UserRelay]) {
+        self.parsed_list = Self::parse_relay_list(
+            &self.original_list,
+            new_overrides,
+            new_user_relays,
+            self.nat64_prefix,
+        );
         self.overrides = new_overrides.to_vec();
+        self.user_relays = new_user_relays.to_vec();
+    }
+
+    fn set_nat64_prefix(&mut self, nat64_prefix: Option<Nat64Prefix>) {
+        if self.nat64_prefix == nat64_prefix {
+            return;
+        }
+        self.parsed_list = Self::parse_relay_list(
+            &self.original_list,
+            &self.overrides,
+            &self.user_relays,
+            nat64_prefix,
+        );
+        self.nat64_prefix = nat64_prefix;
+    }
+
+    /// Returns the relay list without overrides, but with user-defined relays.
+    fn locations(&self) -> RelayList {
+        let mut relay_list = self.original_list.clone();
+        Self::add_user_relays(&mut relay_list, &self.user_relays);
+        relay_list
     }
 
     fn empty() -> Self {
@@ -120,6 +187,8 @@ impl ParsedRelays {
             parsed_list: RelayList::empty(),
             original_list: RelayList::empty(),
             overrides: vec![],
+            user_relays: vec![],
+            nat64_prefix: None,
         }
     }
 
@@ -128,11 +197,12 @@ impl ParsedRelays {
         cache_path: &Path,
         resource_path: &Path,
         overrides: &[RelayOverride],
+        user_relays: &[UserRelay],
     ) -> Result<Self, Error> {
         // prefer the resource path's relay list if the cached one doesn't exist or was modified
         // before the resource one was created.
-        let cached_relays = Self::from_file(cache_path, overrides);
-        let bundled_relays = match Self::from_file(resource_path, overrides) {
+        let cached_relays = Self::from_file(cache_path, overrides, user_relays);
+        let bundled_relays = match Self::from_file(resource_path, overrides, user_relays) {
             Ok(bundled_relays) => bundled_relays,
             Err(e) => {
                 log::error!("Failed to load bundled relays: {}", e);
@@ -151,14 +221,24 @@ impl ParsedRelays {
         }
     }
 
-    fn from_file(path: impl AsRef<Path>, overrides: &[RelayOverride]) -> Result<Self, Error> {
+    fn from_file(
+        path: impl AsRef<Path>,
+        overrides: &[RelayOverride],
+        user_relays: &[UserRelay],
+    ) -> Result<Self, Error> {
         log::debug!("Reading relays from {}", path.as_ref().display());
         let (last_modified, file) =
             Self::open_file(path.as_ref()).map_err(Error::OpenRelayCache)?;
         let relay_list =
             serde_json::from_reader(io::BufReader::new(file)).map_err(Error::Serialize)?;
 
-        Ok(Self::from_relay_list(relay_list, last_modified, overrides))
+        Ok(Self::from_relay_list(
+            relay_list,
+            last_modified,
+            overrides,
+            user_relays,
+            None,
+        ))
     }
 
     fn open_file(path: &Path) -> io::Result<(SystemTime, std::fs::File)> {
@@ -171,16 +251,25 @@ impl ParsedRelays {
         relay_list: RelayList,
         last_updated: SystemTime,
         overrides: &[RelayOverride],
+        user_relays: &[UserRelay],
+        nat64_prefix: Option<Nat64Prefix>,
     ) -> Self {
         ParsedRelays {
             last_updated,
-            parsed_list: Self::parse_relay_list(&relay_list, overrides),
+            parsed_list: Self::parse_relay_list(&relay_list, overrides, user_relays, nat64_prefix),
             original_list: relay_list,
             overrides: overrides.to_vec(),
+            user_relays: user_relays.to_vec(),
+            nat64_prefix,
         }
     }
 
-    fn parse_relay_list(relay_list: &RelayList, overrides: &[RelayOverride]) -> RelayList {
+    fn parse_relay_list(
+        relay_list: &RelayList,
+        overrides: &[RelayOverride],
+        user_relays: &[UserRelay],
+        nat64_prefix: Option<Nat64Prefix>,
+    ) -> RelayList {
         let mut remaining_overrides = HashMap::new();
         for relay_override in overrides {
             remaining_overrides.insert(
@@ -218,8 +307,76 @@ impl ParsedRelays {
             }
         }
 
+        Self::add_user_relays(&mut parsed_list, user_relays);
+
+        // Relays without IPv6 addresses can still be reached over IPv6 through NAT64
+        if let Some(prefix) = nat64_prefix {
+            for country in &mut parsed_list.countries {
+                for city in &mut country.cities {
+                    for relay in &mut city.relays {
+                        relay
+                            .ipv6_addr_in
+                            .get_or_insert_with(|| prefix.synthesize(relay.ipv4_addr_in));
+                    }
+                }
+            }
+        }
+
         parsed_list
     }
+
+    /// Adds user-defined relays to the cities given by their locations, creating the countries
+    /// and cities that are not in the list. Relays whose hostnames are used by Mullvad relays are
+    /// skipped, since the relay list may have gained such a relay after the user relay was added.
+    fn add_user_relays(relay_list: &mut RelayList, user_relays: &[UserRelay]) {
+        for user_relay in user_relays {
+            if relay_list
+                .relays()
+                .any(|relay| relay.hostname == user_relay.hostname)
+            {
+                log::warn!(
+                    "Ignoring user-defined relay {} since a Mullvad relay has the same hostname",
+                    user_relay.hostname
+                );
+                continue;
+            }
+
+            let location = &user_relay.location;
+            let country = match relay_list
+                .countries
+                .iter()
+                .position(|country| country.code == location.country_code)
+            {
+                Some(index) => &mut relay_list.countries[index],
+                None => {
+                    relay_list.countries.push(RelayListCountry {
+                        name: location.country.clone(),
+                        code: location.country_code.clone(),
+                        cities: vec![],
+                    });
+                    relay_list.countries.last_mut().unwrap()
+                }
+            };
+            let city = match country
+                .cities
+                .iter()
+                .position(|city| city.code == location.city_code)
+            {
+                Some(index) => &mut country.cities[index],
+                None => {
+                    country.cities.push(RelayListCity {
+                        name: location.city.clone(),
+                        code: location.city_code.clone(),
+                        latitude: location.latitude,
+                        longitude: location.longitude,
+                        relays: vec![],
+                    });
+                    country.cities.last_mut().unwrap()
+                }
+            };
+            city.relays.push(user_relay.to_relay());
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -230,6 +387,8 @@ pub struct SelectorConfig {
     pub obfuscation_settings: ObfuscationSettings,
     pub custom_lists: CustomListsSettings,
     pub relay_overrides: Vec<RelayOverride>,
+    pub user_relays: Vec<UserRelay>,
+    pub connection_plan: ConnectionPlan,
 }
 
 impl Default for SelectorConfig {
@@ -242,6 +401,8 @@ impl Default for SelectorConfig {
             bridge_state: default_settings.bridge_state,
             custom_lists: default_settings.custom_lists,
             relay_overrides: default_settings.relay_overrides,
+            user_relays: default_settings.user_relays,
+            connection_plan: default_settings.connection_plan.unwrap_or_default(),
         }
     }
 }
@@ -250,6 +411,10 @@ impl Default for SelectorConfig {
 pub struct RelaySelector {
     config: Arc<Mutex<SelectorConfig>>,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
+    /// Last known location of the device. Used by distance constraints.
+    current_location: Arc<Mutex<Option<Coordinates>>>,
+    /// Address families that the device can reach relays over.
+    connectivity: Arc<Mutex<Connectivity>>,
 }
 
 impl RelaySelector {
@@ -257,15 +422,19 @@ impl RelaySelector {
     pub fn new(config: SelectorConfig, resource_dir: &Path, cache_dir: &Path) -> Self {
         let cache_path = cache_dir.join(RELAYS_FILENAME);
         let resource_path = resource_dir.join(RELAYS_FILENAME);
-        let unsynchronized_parsed_relays =
-            ParsedRelays::from_dir(&cache_path, &resource_path, &config.relay_overrides)
-                .unwrap_or_else(|error| {
-                    log::error!(
-                        "{}",
-                        error.display_chain_with_msg("Unable to load cached and bundled relays")
-                    );
-                    ParsedRelays::empty()
-                });
+        let unsynchronized_parsed_relays = ParsedRelays::from_dir(
+            &cache_path,
+            &resource_path,
+            &config.relay_overrides,
+            &config.user_relays,
+        )
+        .unwrap_or_else(|error| {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to load cached and bundled relays")
+            );
+            ParsedRelays::empty()
+        });
         log::info!(
             "Initialized with {} cached relays from {}",
             unsynchronized_parsed_relays.relays().count(),
@@ -276,6 +445,8 @@ impl RelaySelector {
         RelaySelector {
             config: Arc::new(Mutex::new(config)),
             parsed_relays: Arc::new(Mutex::new(unsynchronized_parsed_relays)),
+            current_location: Arc::new(Mutex::new(None)),
+            connectivity: Arc::new(Mutex::new(Connectivity::PresumeOnline)),
         }
     }
 
@@ -285,21 +456,47 @@ impl RelaySelector {
                 relay_list,
                 SystemTime::now(),
                 &config.relay_overrides,
+                &config.user_relays,
+                None,
             ))),
             config: Arc::new(Mutex::new(config)),
+            current_location: Arc::new(Mutex::new(None)),
+            connectivity: Arc::new(Mutex::new(Connectivity::PresumeOnline)),
         }
     }
 
     pub fn set_config(&mut self, config: SelectorConfig) {
         let mut parsed_relays = self.parsed_relays.lock();
-        parsed_relays.set_overrides(&config.relay_overrides);
+        parsed_relays.set_user_data(&config.relay_overrides, &config.user_relays);
         *self.config.lock() = config;
     }
 
+    /// Sets the location that distance constraints centered on the current location refer to.
+    pub fn set_current_location(&self, location: Coordinates) {
+        *self.current_location.lock() = Some(location);
+    }
+
+    pub fn current_location(&self) -> Option<Coordinates> {
+        self.current_location.lock().clone()
+    }
+
+    /// Sets the address families that the device can reach relays over. When only IPv6 is
+    /// available, relays and bridges are connected to over IPv6 unless an IP version is
+    /// explicitly selected.
+    pub fn set_connectivity(&self, connectivity: Connectivity) {
+        *self.connectivity.lock() = connectivity;
+    }
+
+    /// Sets the NAT64 prefix of the network, which is used to reach relays that lack IPv6
+    /// addresses over IPv6. This should only be set when the device lacks IPv4 connectivity.
+    pub fn set_nat64_prefix(&self, nat64_prefix: Option<Nat64Prefix>) {
+        self.parsed_relays.lock().set_nat64_prefix(nat64_prefix);
+    }
+
     /// Returns all countries and cities. The cities in the object returned does not have any
     /// relays in them.
     pub fn get_locations(&mut self) -> RelayList {
-        self.parsed_relays.lock().original_list.clone()
+        self.parsed_relays.lock().locations()
     }
 
     /// Returns a random relay and relay endpoint matching the current constraints.
@@ -320,10 +517,13 @@ impl RelaySelector {
                 Ok((SelectedRelay::Custom(custom_relay.clone()), None, None))
             }
             RelaySettings::Normal(constraints) => {
+                let attempt = *config.connection_plan.attempt(retry_attempt);
+                let constraints =
+                    self.with_host_connectivity(self.with_current_location(constraints));
                 let relay = self.get_tunnel_endpoint(
-                    constraints,
+                    &constraints,
                     config.bridge_state,
-                    retry_attempt,
+                    &attempt,
                     &config.custom_lists,
                 )?;
                 let bridge = match relay.endpoint {
@@ -335,7 +535,7 @@ impl RelaySelector {
                             .location
                             .as_ref()
                             .expect("Relay has no location set");
-                        self.get_bridge_for(&config, location, retry_attempt, &config.custom_lists)?
+                        self.get_bridge_for(&config, location, &attempt, &config.custom_lists)?
                     }
                     _ => None,
                 };
@@ -348,6 +548,7 @@ impl RelaySelector {
                             obfuscator_relay,
                             endpoint,
                             retry_attempt,
+                            &attempt,
                         )?
                     }
                     _ => None,
@@ -357,6 +558,202 @@ impl RelaySelector {
         }
     }
 
+    /// Runs the selector for the first `attempts` connection attempts, and returns the attempt
+    /// template and the selected relays for each attempt.
+    pub fn simulate_connection_plan(&self, attempts: u32) -> Vec<SimulatedAttempt> {
+        let plan = self.config.lock().connection_plan.clone();
+        (0..attempts)
+            .map(|retry_attempt| SimulatedAttempt {
+                attempt: retry_attempt,
+                template: *plan.attempt(retry_attempt),
+                outcome: match self.get_relay(retry_attempt) {
+                    Ok((relay, bridge, obfuscator)) => {
+                        AttemptOutcome::Selected(Self::attempt_selection(relay, bridge, obfuscator))
+                    }
+                    Err(error) => AttemptOutcome::Failed(error.display_chain()),
+                },
+            })
+            .collect()
+    }
+
+    fn attempt_selection(
+        relay: SelectedRelay,
+        bridge: Option<SelectedBridge>,
+        obfuscator: Option<SelectedObfuscator>,
+    ) -> AttemptSelection {
+        let (exit, entry, endpoint) = match relay {
+            SelectedRelay::Normal(relay) => (
+                relay.exit_relay.hostname,
+                relay.entry_relay.map(|entry| entry.hostname),
+                relay.endpoint,
+            ),
+            SelectedRelay::Custom(custom) => {
+                let endpoint = custom.endpoint();
+                let tunnel_type = match custom.config {
+                    mullvad_types::ConnectionConfig::OpenVpn(_) => TunnelType::OpenVpn,
+                    mullvad_types::ConnectionConfig::Wireguard(_) => TunnelType::Wireguard,
+                };
+                return AttemptSelection {
+                    tunnel_type,
+                    exit: custom.host,
+                    entry: None,
+                    endpoint,
+                    bridge: None,
+                    obfuscator: None,
+                };
+            }
+        };
+        AttemptSelection {
+            tunnel_type: match endpoint {
+                MullvadEndpoint::OpenVpn(_) => TunnelType::OpenVpn,
+                MullvadEndpoint::Wireguard(_) => TunnelType::Wireguard,
+            },
+            exit,
+            entry,
+            endpoint: endpoint.to_endpoint(),
+            bridge: bridge.map(|bridge| match bridge {
+                SelectedBridge::Normal(bridge) => bridge.relay.hostname,
+                SelectedBridge::Custom(proxy) => {
+                    format!("custom ({})", proxy.get_remote_endpoint().endpoint)
+                }
+            }),
+            obfuscator: obfuscator.map(|obfuscator| match obfuscator.config {
+                ObfuscatorConfig::Udp2Tcp { endpoint } => endpoint,
+            }),
+        }
+    }
+
+    /// Returns the relays matching `query` for each role, along with the probability of each relay
+    /// being selected. This uses the current custom lists and location, but ignores the current
+    /// relay and bridge settings.
+    ///
+    /// A relay is never used as both entry and exit, and the multihop diversity constraints
+    /// apply. The role that is picked first only lists relays that can be paired with some relay
+    /// in the other role. If it only has a single candidate, relays that cannot be paired with it
+    /// are excluded from the other role.
+    pub fn query_relays(&self, query: &RelayQuery) -> RelayQueryResult {
+        let custom_lists = self.config.lock().custom_lists.clone();
+        let constraints =
+            self.with_host_connectivity(self.with_current_location(&query.constraints));
+
+        let (openvpn_data, wireguard_data) = {
+            let relays = self.parsed_relays.lock();
+            (
+                relays.parsed_list.openvpn.clone(),
+                relays.parsed_list.wireguard.clone(),
+            )
+        };
+        let mut exit_matcher = RelayMatcher::new(
+            constraints.clone(),
+            openvpn_data,
+            wireguard_data,
+            &custom_lists,
+        );
+
+        let use_multihop = constraints.wireguard_constraints.use_multihop
+            && constraints.tunnel_protocol != Constraint::Only(TunnelType::OpenVpn);
+        let (exit, entry) = if use_multihop {
+            let mut entry_matcher = RelayMatcher {
+                locations: ResolvedLocationConstraint::from_constraint(
+                    constraints.wireguard_constraints.entry_location.clone(),
+                    &custom_lists,
+                ),
+                providers: constraints.providers.clone(),
+                ownership: constraints.ownership,
+                endpoint_matcher: exit_matcher.endpoint_matcher.clone(),
+            }
+            .into_wireguard_matcher();
+            exit_matcher.endpoint_matcher.wireguard = WireguardMatcher {
+                diversity: entry_matcher.endpoint_matcher.diversity,
+                ..self.wireguard_exit_matcher()
+            };
+
+            if entry_matcher.locations.is_subset(&exit_matcher.locations) {
+                let wireguard_exit_matcher = exit_matcher.clone().into_wireguard_matcher();
+                let entry = self
+                    .multihop_candidates(&entry_matcher, &wireguard_exit_matcher)
+                    .unwrap_or_default();
+                if let [entry_relay] = &entry[..] {
+                    exit_matcher.endpoint_matcher.wireguard.peer = Some(entry_relay.clone());
+                }
+                let exit =
+                    exit_matcher.filter_matching_relay_list(self.parsed_relays.lock().relays());
+                (exit, entry)
+            } else {
+                let exit = self
+                    .multihop_candidates(&exit_matcher, &entry_matcher)
+                    .unwrap_or_default();
+                if let [exit_relay] = &exit[..] {
+                    entry_matcher.set_peer(exit_relay.clone());
+                }
+                let entry =
+                    entry_matcher.filter_matching_relay_list(self.parsed_relays.lock().relays());
+                (exit, entry)
+            }
+        } else {
+            let exit = exit_matcher.filter_matching_relay_list(self.parsed_relays.lock().relays());
+            (exit, vec![])
+        };
+
+        let bridge_constraints = InternalBridgeConstraints {
+            location: query.bridge_constraints.location.clone(),
+            providers: query.bridge_constraints.providers.clone(),
+            ownership: query.bridge_constraints.ownership,
+            transport_protocol: Constraint::Only(TransportProtocol::Tcp),
+        };
+        let near_location = self.get_relay_midpoint(&constraints, &custom_lists);
+        let bridge = self.bridge_candidates(&bridge_constraints, near_location, &custom_lists);
+
+        let with_relay_weight = |relays: Vec<Relay>| {
+            RelayCandidate::from_weighted(
+                relays
+                    .into_iter()
+                    .map(|relay| {
+                        let weight = relay.weight;
+                        (relay, weight)
+                    })
+                    .collect(),
+            )
+        };
+        RelayQueryResult {
+            exit: with_relay_weight(exit),
+            entry: with_relay_weight(entry),
+            bridge: RelayCandidate::from_weighted(bridge),
+        }
+    }
+
+    /// Centers the exit and entry distance constraints that refer to the current location on the
+    /// last known location of the device.
+    fn with_current_location(&self, constraints: &RelayConstraints) -> RelayConstraints {
+        let current_location = self.current_location.lock();
+        let mut constraints = constraints.clone();
+        constraints.location = constraints
+            .location
+            .map(|location| location.with_current_location(current_location.as_ref()));
+        constraints.wireguard_constraints.entry_location = constraints
+            .wireguard_constraints
+            .entry_location
+            .map(|location| location.with_current_location(current_location.as_ref()));
+        constraints
+    }
+
+    /// Adapts constraints that do not specify an IP version to the connectivity of the device.
+    /// On IPv6-only networks, relays are connected to over IPv6, and WireGuard is used unless
+    /// another tunnel protocol is selected, since OpenVPN is only used over IPv4.
+    fn with_host_connectivity(&self, mut constraints: RelayConstraints) -> RelayConstraints {
+        if !self.connectivity.lock().is_ipv6_only() {
+            return constraints;
+        }
+        if constraints.tunnel_protocol.is_any() {
+            constraints.tunnel_protocol = Constraint::Only(TunnelType::Wireguard);
+        }
+        let wireguard_constraints = &mut constraints.wireguard_constraints;
+        if wireguard_constraints.ip_version.is_any() {
+            wireguard_constraints.ip_version = Constraint::Only(IpVersion::V6);
+        }
+        constraints
+    }
+
     /// Returns a random relay and relay endpoint matching the given constraints and with
     /// preferences applied.
     #[cfg_attr(target_os = "android", allow(unused_variables))]
@@ -364,32 +761,26 @@ impl RelaySelector {
         &self,
         relay_constraints: &RelayConstraints,
         bridge_state: BridgeState,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         custom_lists: &CustomListsSettings,
     ) -> Result<NormalSelectedRelay, Error> {
         #[cfg(target_os = "android")]
         {
-            self.get_wireguard_endpoint(relay_constraints, retry_attempt, custom_lists)
+            self.get_wireguard_endpoint(relay_constraints, attempt, custom_lists)
         }
 
         #[cfg(not(target_os = "android"))]
         match relay_constraints.tunnel_protocol {
-            Constraint::Only(TunnelType::OpenVpn) => self.get_openvpn_endpoint(
-                relay_constraints,
-                bridge_state,
-                retry_attempt,
-                custom_lists,
-            ),
+            Constraint::Only(TunnelType::OpenVpn) => {
+                self.get_openvpn_endpoint(relay_constraints, bridge_state, attempt, custom_lists)
+            }
 
             Constraint::Only(TunnelType::Wireguard) => {
-                self.get_wireguard_endpoint(relay_constraints, retry_attempt, custom_lists)
+                self.get_wireguard_endpoint(relay_constraints, attempt, custom_lists)
+            }
+            Constraint::Any => {
+                self.get_any_tunnel_endpoint(relay_constraints, bridge_state, attempt, custom_lists)
             }
-            Constraint::Any => self.get_any_tunnel_endpoint(
-                relay_constraints,
-                bridge_state,
-                retry_attempt,
-                custom_lists,
-            ),
         }
     }
 
@@ -440,7 +831,7 @@ impl RelaySelector {
         &self,
         relay_constraints: &RelayConstraints,
         bridge_state: BridgeState,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         custom_lists: &CustomListsSettings,
     ) -> Result<NormalSelectedRelay, Error> {
         let mut relay_matcher = RelayMatcher {
@@ -469,8 +860,10 @@ impl RelaySelector {
 
         let mut preferred_relay_matcher = relay_matcher.clone();
 
-        let (preferred_port, preferred_protocol) =
-            Self::preferred_openvpn_constraints(retry_attempt);
+        let TransportPort {
+            protocol: preferred_protocol,
+            port: preferred_port,
+        } = attempt.openvpn_only_port;
         let should_try_preferred =
             match &mut preferred_relay_matcher.endpoint_matcher.constraints.port {
                 any @ Constraint::Any => {
@@ -508,12 +901,17 @@ impl RelaySelector {
             locations: ResolvedLocationConstraint::from_constraint(exit_locations, custom_lists),
             providers: entry_matcher.providers.clone(),
             ownership: entry_matcher.ownership,
-            endpoint_matcher: self.wireguard_exit_matcher(),
+            endpoint_matcher: WireguardMatcher {
+                diversity: entry_matcher.endpoint_matcher.diversity,
+                ..self.wireguard_exit_matcher()
+            },
         };
 
         let (exit_relay, entry_relay, exit_endpoint, mut entry_endpoint) =
             if entry_matcher.locations.is_subset(&exit_matcher.locations) {
-                let (entry_relay, entry_endpoint) = self.get_entry_endpoint(&entry_matcher)?;
+                let entry_relays = self.multihop_candidates(&entry_matcher, &exit_matcher)?;
+                let (entry_relay, entry_endpoint) =
+                    self.pick_entry_endpoint(&entry_matcher, &entry_relays)?;
                 exit_matcher.set_peer(entry_relay.clone());
                 let exit_result = self.get_tunnel_endpoint_internal(&exit_matcher)?;
                 (
@@ -523,7 +921,8 @@ impl RelaySelector {
                     entry_endpoint,
                 )
             } else {
-                let exit_result = self.get_tunnel_endpoint_internal(&exit_matcher)?;
+                let exit_relays = self.multihop_candidates(&exit_matcher, &entry_matcher)?;
+                let exit_result = self.pick_tunnel_endpoint(&exit_matcher, &exit_relays)?;
 
                 entry_matcher.set_peer(exit_result.exit_relay.clone());
                 let (entry_relay, entry_endpoint) = self.get_entry_endpoint(&entry_matcher)?;
@@ -557,7 +956,7 @@ impl RelaySelector {
     fn get_wireguard_endpoint(
         &self,
         relay_constraints: &RelayConstraints,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         custom_lists: &CustomListsSettings,
     ) -> Result<NormalSelectedRelay, Error> {
         let wg_endpoint_data = self.parsed_relays.lock().parsed_list.wireguard.clone();
@@ -585,7 +984,7 @@ impl RelaySelector {
             preferred_matcher.endpoint_matcher.port = preferred_matcher
                 .endpoint_matcher
                 .port
-                .or(Self::preferred_wireguard_port(retry_attempt));
+                .or(attempt.wireguard_port);
 
             self.get_tunnel_endpoint_internal(&preferred_matcher)
                 .or_else(|_| self.get_tunnel_endpoint_internal(&relay_matcher))
@@ -608,7 +1007,7 @@ impl RelaySelector {
             entry_relay_matcher.endpoint_matcher.port = entry_relay_matcher
                 .endpoint_matcher
                 .port
-                .or(Self::preferred_wireguard_port(retry_attempt));
+                .or(attempt.wireguard_port);
 
             self.get_wireguard_multi_hop_endpoint(
                 entry_relay_matcher,
@@ -656,18 +1055,31 @@ impl RelaySelector {
         .into_wireguard_matcher();
 
         // Pick the entry relay first if its location constraint is a subset of the exit location.
+        let mut exit_relays = None;
         if relay_constraints.wireguard_constraints.use_multihop {
-            matcher.endpoint_matcher.wireguard = self.wireguard_exit_matcher();
+            matcher.endpoint_matcher.wireguard = WireguardMatcher {
+                diversity: entry_matcher.endpoint_matcher.diversity,
+                ..self.wireguard_exit_matcher()
+            };
             if entry_matcher.locations.is_subset(&matcher.locations) {
-                if let Ok((entry_relay, entry_endpoint)) = self.get_entry_endpoint(&entry_matcher) {
+                let exit_matcher = matcher.clone().into_wireguard_matcher();
+                let entry_relays = self.multihop_candidates(&entry_matcher, &exit_matcher)?;
+                if let Ok((entry_relay, entry_endpoint)) =
+                    self.pick_entry_endpoint(&entry_matcher, &entry_relays)
+                {
                     matcher.endpoint_matcher.wireguard.peer = Some(entry_relay.clone());
                     selected_entry_relay = Some(entry_relay);
                     selected_entry_endpoint = Some(entry_endpoint);
                 }
+            } else {
+                exit_relays = Some(self.multihop_candidates(&matcher, &entry_matcher)?);
             }
         }
 
-        let mut selected_relay = self.get_tunnel_endpoint_internal(&matcher)?;
+        let mut selected_relay = match exit_relays {
+            Some(exit_relays) => self.pick_tunnel_endpoint(&matcher, &exit_relays)?,
+            None => self.get_tunnel_endpoint_internal(&matcher)?,
+        };
 
         // Pick the entry relay last if its location constraint is NOT a subset of the exit
         // location.
@@ -714,31 +1126,21 @@ impl RelaySelector {
         &self,
         relay_constraints: &RelayConstraints,
         bridge_state: BridgeState,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         custom_lists: &CustomListsSettings,
     ) -> Result<NormalSelectedRelay, Error> {
-        let preferred_constraints = self.preferred_constraints(
-            relay_constraints,
-            bridge_state,
-            retry_attempt,
-            custom_lists,
-        );
+        let preferred_constraints =
+            self.preferred_constraints(relay_constraints, bridge_state, attempt, custom_lists);
 
         if let Ok(result) =
             self.get_multihop_tunnel_endpoint_internal(&preferred_constraints, custom_lists)
         {
-            log::debug!(
-                "Relay matched on highest preference for retry attempt {}",
-                retry_attempt
-            );
+            log::debug!("Relay matched on highest preference for attempt: {attempt}");
             Ok(result)
         } else if let Ok(result) =
             self.get_multihop_tunnel_endpoint_internal(relay_constraints, custom_lists)
         {
-            log::debug!(
-                "Relay matched on second preference for retry attempt {}",
-                retry_attempt
-            );
+            log::debug!("Relay matched on second preference for attempt: {attempt}");
             Ok(result)
         } else {
             log::warn!(
@@ -758,16 +1160,16 @@ impl RelaySelector {
         &self,
         original_constraints: &RelayConstraints,
         bridge_state: BridgeState,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         custom_lists: &CustomListsSettings,
     ) -> RelayConstraints {
         let location = ResolvedLocationConstraint::from_constraint(
             original_constraints.location.clone(),
             custom_lists,
         );
-        let (preferred_port, preferred_protocol, preferred_tunnel) = self
+        let (preferred_tunnel, preferred_openvpn_port) = self
             .preferred_tunnel_constraints_for_location(
-                retry_attempt,
+                attempt,
                 &location,
                 &original_constraints.providers,
                 original_constraints.ownership,
@@ -790,10 +1192,7 @@ impl RelaySelector {
                     };
                 } else if original_constraints.openvpn_constraints.port.is_any() {
                     relay_constraints.openvpn_constraints = OpenVpnConstraints {
-                        port: Constraint::Only(TransportPort {
-                            protocol: preferred_protocol,
-                            port: preferred_port,
-                        }),
+                        port: Constraint::Only(preferred_openvpn_port),
                     };
                 } else {
                     relay_constraints.openvpn_constraints =
@@ -801,7 +1200,7 @@ impl RelaySelector {
                 }
 
                 if relay_constraints.wireguard_constraints.port.is_any() {
-                    relay_constraints.wireguard_constraints.port = preferred_port;
+                    relay_constraints.wireguard_constraints.port = attempt.wireguard_port;
                 }
 
                 relay_constraints.tunnel_protocol = Constraint::Only(preferred_tunnel);
@@ -815,20 +1214,14 @@ impl RelaySelector {
                         port: Constraint::Any,
                     });
                 } else if openvpn_constraints.port.is_any() {
-                    let (preferred_port, preferred_protocol) =
-                        Self::preferred_openvpn_constraints(retry_attempt);
-                    openvpn_constraints.port = Constraint::Only(TransportPort {
-                        protocol: preferred_protocol,
-                        port: preferred_port,
-                    });
+                    openvpn_constraints.port = Constraint::Only(attempt.openvpn_only_port);
                 }
             }
             Constraint::Only(TunnelType::Wireguard) => {
                 relay_constraints.wireguard_constraints =
                     original_constraints.wireguard_constraints.clone();
                 if relay_constraints.wireguard_constraints.port.is_any() {
-                    relay_constraints.wireguard_constraints.port =
-                        Self::preferred_wireguard_port(retry_attempt);
+                    relay_constraints.wireguard_constraints.port = attempt.wireguard_port;
                 }
             }
         };
@@ -836,6 +1229,56 @@ impl RelaySelector {
         relay_constraints
     }
 
+    /// Returns the relays matching `first` that can be paired with at least one relay matching
+    /// `second`, given the multihop diversity constraints of `second`. This is used for picking
+    /// the first relay of a multihop pair, so that a matching second relay always exists.
+    fn multihop_candidates<T: EndpointMatcher>(
+        &self,
+        first: &RelayMatcher<T>,
+        second: &RelayMatcher<WireguardMatcher>,
+    ) -> Result<Vec<Relay>, Error> {
+        let parsed_relays = self.parsed_relays.lock();
+        let first_relays = first.filter_matching_relay_list(parsed_relays.relays());
+        let diversity = second.endpoint_matcher.diversity;
+        if !diversity.is_enabled() {
+            return Ok(first_relays);
+        }
+        let second_relays = second.filter_matching_relay_list(parsed_relays.relays());
+        if first_relays.is_empty() || second_relays.is_empty() {
+            return Ok(first_relays);
+        }
+
+        let has_peer = |relay: &Relay, allows: &dyn Fn(&Relay, &Relay) -> bool| {
+            second_relays
+                .iter()
+                .any(|peer| peer.hostname != relay.hostname && allows(relay, peer))
+        };
+        let candidates: Vec<Relay> = first_relays
+            .iter()
+            .filter(|relay| {
+                !matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
+                    || has_peer(relay, &|relay, peer| diversity.allows(relay, peer))
+            })
+            .cloned()
+            .collect();
+        if !candidates.is_empty() {
+            return Ok(candidates);
+        }
+
+        let unsatisfiable = diversity
+            .rules()
+            .into_iter()
+            .filter(|rule| {
+                !first_relays
+                    .iter()
+                    .any(|relay| has_peer(relay, &|relay, peer| rule.allows(relay, peer)))
+            })
+            .collect();
+        Err(Error::NoDiverseMultihopPair(UnsatisfiedDiversity(
+            unsatisfiable,
+        )))
+    }
+
     fn get_entry_endpoint(
         &self,
         matcher: &RelayMatcher<WireguardMatcher>,
@@ -844,9 +1287,17 @@ impl RelaySelector {
             .filter_matching_relay_list(self.parsed_relays.lock().relays())
             .into_iter()
             .collect();
+        self.pick_entry_endpoint(matcher, &matching_relays)
+    }
 
+    /// Like [Self::get_entry_endpoint], but picks from relays that have already been matched.
+    fn pick_entry_endpoint(
+        &self,
+        matcher: &RelayMatcher<WireguardMatcher>,
+        matching_relays: &[Relay],
+    ) -> Result<(Relay, MullvadWireguardEndpoint), Error> {
         let relay = self
-            .pick_random_relay(&matching_relays)
+            .pick_random_relay(matching_relays)
             .cloned()
             .ok_or(Error::NoRelay)?;
         let endpoint = matcher
@@ -870,7 +1321,7 @@ impl RelaySelector {
         &self,
         config: &MutexGuard<'_, SelectorConfig>,
         location: &mullvad_types::location::Location,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         custom_lists: &CustomListsSettings,
     ) -> Result<Option<SelectedBridge>, Error> {
         match config
@@ -896,7 +1347,7 @@ impl RelaySelector {
                             relay,
                         })))
                     }
-                    BridgeState::Auto if Self::should_use_bridge(retry_attempt) => Ok(self
+                    BridgeState::Auto if attempt.use_bridge => Ok(self
                         .get_proxy_settings(&bridge_constraints, Some(location), custom_lists)
                         .map(|(settings, relay)| {
                             SelectedBridge::Normal(NormalSelectedBridge { settings, relay })
@@ -906,7 +1357,7 @@ impl RelaySelector {
             }
             ResolvedBridgeSettings::Custom(bridge_settings) => match config.bridge_state {
                 BridgeState::On => Ok(Some(SelectedBridge::Custom(bridge_settings.clone()))),
-                BridgeState::Auto if Self::should_use_bridge(retry_attempt) => {
+                BridgeState::Auto if attempt.use_bridge => {
                     Ok(Some(SelectedBridge::Custom(bridge_settings.clone())))
                 }
                 BridgeState::Auto | BridgeState::Off => Ok(None),
@@ -945,22 +1396,31 @@ impl RelaySelector {
             .map(|(settings, _relay)| settings)
     }
 
-    fn should_use_bridge(retry_attempt: u32) -> bool {
-        // shouldn't use a bridge for the first 3 times
-        retry_attempt > 3 &&
-            // i.e. 4th and 5th with bridge, 6th & 7th without
-            // The test is to see whether the current _couple of connections_ is even or not.
-            // | retry_attempt                | 4 | 5 | 6 | 7 | 8 | 9 |
-            // | (retry_attempt % 4) < 2      | t | t | f | f | t | t |
-            (retry_attempt % 4) < 2
-    }
-
     fn get_proxy_settings<T: Into<Coordinates>>(
         &self,
         constraints: &InternalBridgeConstraints,
         location: Option<T>,
         custom_lists: &CustomListsSettings,
     ) -> Option<(CustomProxy, Relay)> {
+        let candidates = self.bridge_candidates(constraints, location, custom_lists);
+        let relay = self
+            .pick_random_relay_fn(&candidates, |(_relay, weight)| *weight)
+            .map(|(relay, _weight)| relay.clone());
+        relay.and_then(|relay| {
+            self.pick_random_bridge(&self.parsed_relays.lock().parsed_list.bridge, &relay)
+                .map(|bridge| (bridge, relay.clone()))
+        })
+    }
+
+    /// Returns the bridges matching `constraints` along with the weight to select them by. If a
+    /// location is given, only the bridges closest to it are returned, and closer bridges are
+    /// given a higher weight.
+    fn bridge_candidates<T: Into<Coordinates>>(
+        &self,
+        constraints: &InternalBridgeConstraints,
+        location: Option<T>,
+        custom_lists: &CustomListsSettings,
+    ) -> Vec<(Relay, u64)> {
         let matcher = RelayMatcher {
             locations: ResolvedLocationConstraint::from_constraint(
                 constraints.location.clone(),
@@ -973,57 +1433,45 @@ impl RelaySelector {
         let matching_relays: Vec<Relay> =
             matcher.filter_matching_relay_list(self.parsed_relays.lock().relays());
 
-        if matching_relays.is_empty() {
-            return None;
-        }
-
-        let relay = if let Some(location) = location {
-            let location = location.into();
-
-            #[derive(Debug, Clone)]
-            struct RelayWithDistance {
-                relay: Relay,
-                distance: f64,
-            }
-
-            let mut matching_relays: Vec<RelayWithDistance> = matching_relays
+        let Some(location) = location else {
+            return matching_relays
                 .into_iter()
-                .map(|relay| RelayWithDistance {
-                    distance: relay.location.as_ref().unwrap().distance_from(&location),
-                    relay,
+                .map(|relay| {
+                    let weight = relay.weight;
+                    (relay, weight)
                 })
                 .collect();
-            matching_relays
-                .sort_unstable_by_key(|relay: &RelayWithDistance| relay.distance as usize);
+        };
+        let location = location.into();
 
-            let mut greatest_distance = 0f64;
-            matching_relays = matching_relays
-                .into_iter()
-                .enumerate()
-                .filter_map(|(i, relay)| {
-                    if i < MIN_BRIDGE_COUNT || relay.distance <= MAX_BRIDGE_DISTANCE {
-                        if relay.distance > greatest_distance {
-                            greatest_distance = relay.distance;
-                        }
-                        return Some(relay);
-                    }
-                    None
-                })
-                .collect();
+        let mut matching_relays: Vec<(Relay, f64)> = matching_relays
+            .into_iter()
+            .map(|relay| {
+                let distance = relay.location.as_ref().unwrap().distance_from(&location);
+                (relay, distance)
+            })
+            .collect();
+        matching_relays.sort_unstable_by_key(|(_relay, distance)| *distance as usize);
 
-            let weight_fn =
-                |relay: &RelayWithDistance| 1 + (greatest_distance - relay.distance) as u64;
+        let mut greatest_distance = 0f64;
+        let matching_relays: Vec<(Relay, f64)> = matching_relays
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (relay, distance))| {
+                if i < MIN_BRIDGE_COUNT || distance <= MAX_BRIDGE_DISTANCE {
+                    if distance > greatest_distance {
+                        greatest_distance = distance;
+                    }
+                    return Some((relay, distance));
+                }
+                None
+            })
+            .collect();
 
-            self.pick_random_relay_fn(&matching_relays, weight_fn)
-                .cloned()
-                .map(|relay_with_distance| relay_with_distance.relay)
-        } else {
-            self.pick_random_relay(&matching_relays).cloned()
-        };
-        relay.and_then(|relay| {
-            self.pick_random_bridge(&self.parsed_relays.lock().parsed_list.bridge, &relay)
-                .map(|bridge| (bridge, relay.clone()))
-        })
+        matching_relays
+            .into_iter()
+            .map(|(relay, distance)| (relay, 1 + (greatest_distance - distance) as u64))
+            .collect()
     }
 
     fn get_obfuscator_inner(
@@ -1032,13 +1480,14 @@ impl RelaySelector {
         relay: &Relay,
         endpoint: &MullvadWireguardEndpoint,
         retry_attempt: u32,
+        attempt: &AttemptTemplate,
     ) -> Result<Option<SelectedObfuscator>, Error> {
         match &config.obfuscation_settings.selected_obfuscation {
             SelectedObfuscation::Auto => Ok(self.get_auto_obfuscator(
                 &config.obfuscation_settings,
                 relay,
                 endpoint,
-                retry_attempt,
+                attempt,
             )),
             SelectedObfuscation::Off => Ok(None),
             SelectedObfuscation::Udp2Tcp => Ok(Some(
@@ -1058,23 +1507,10 @@ impl RelaySelector {
         obfuscation_settings: &ObfuscationSettings,
         relay: &Relay,
         endpoint: &MullvadWireguardEndpoint,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
     ) -> Option<SelectedObfuscator> {
-        let obfuscation_attempt = Self::get_auto_obfuscator_retry_attempt(retry_attempt)?;
-        self.get_udp2tcp_obfuscator(
-            &obfuscation_settings.udp2tcp,
-            relay,
-            endpoint,
-            obfuscation_attempt,
-        )
-    }
-
-    const fn get_auto_obfuscator_retry_attempt(retry_attempt: u32) -> Option<u32> {
-        match retry_attempt % 4 {
-            0 | 1 => None,
-            // when the retry attempt is 2-3, 6-7, 10-11 ... obfuscation will be used
-            filtered_retry => Some(retry_attempt / 4 + filtered_retry - 2),
-        }
+        let port_index = attempt.auto_obfuscation?;
+        self.get_udp2tcp_obfuscator(&obfuscation_settings.udp2tcp, relay, endpoint, port_index)
     }
 
     fn get_udp2tcp_obfuscator(
@@ -1084,6 +1520,10 @@ impl RelaySelector {
         endpoint: &MullvadWireguardEndpoint,
         retry_attempt: u32,
     ) -> Option<SelectedObfuscator> {
+        // User-defined relays are not known to run udp2tcp
+        if relay.is_user_defined() {
+            return None;
+        }
         let udp2tcp_ports = &self
             .parsed_relays
             .lock()
@@ -1107,14 +1547,15 @@ impl RelaySelector {
             })
     }
 
-    /// Return the preferred constraints, on attempt `retry_attempt`, for matching locations
+    /// Return the preferred tunnel type and OpenVPN transport for an attempt, given the
+    /// protocols supported by matching locations
     fn preferred_tunnel_constraints_for_location(
         &self,
-        retry_attempt: u32,
+        attempt: &AttemptTemplate,
         location: &Constraint<ResolvedLocationConstraint>,
         providers: &Constraint<Providers>,
         ownership: Constraint<Ownership>,
-    ) -> (Constraint<u16>, TransportProtocol, TunnelType) {
+    ) -> (TunnelType, TransportPort) {
         let parsed_relays = self.parsed_relays.lock();
         let mut active_location_relays = parsed_relays.relays().filter(|relay| {
             relay.active
@@ -1128,61 +1569,9 @@ impl RelaySelector {
         let location_supports_openvpn = active_location_relays
             .any(|relay| matches!(relay.endpoint_data, RelayEndpointData::Openvpn));
         match (location_supports_wg, location_supports_openvpn) {
-            (true, true) | (false, false) => Self::preferred_tunnel_constraints(retry_attempt),
-            (true, false) => {
-                let port = Self::preferred_wireguard_port(retry_attempt);
-                (port, TransportProtocol::Udp, TunnelType::Wireguard)
-            }
-            (false, true) => {
-                let (port, transport) = Self::preferred_openvpn_constraints(retry_attempt);
-                (port, transport, TunnelType::OpenVpn)
-            }
-        }
-    }
-
-    /// Return the preferred constraints, on attempt `retry_attempt`, given no other constraints
-    pub const fn preferred_tunnel_constraints(
-        retry_attempt: u32,
-    ) -> (Constraint<u16>, TransportProtocol, TunnelType) {
-        // Use WireGuard on the first three attempts, then OpenVPN
-        match retry_attempt {
-            0..=2 => (
-                Self::preferred_wireguard_port(retry_attempt),
-                TransportProtocol::Udp,
-                TunnelType::Wireguard,
-            ),
-            _ => {
-                let (preferred_port, preferred_protocol) =
-                    Self::preferred_openvpn_constraints(retry_attempt - 2);
-                (preferred_port, preferred_protocol, TunnelType::OpenVpn)
-            }
-        }
-    }
-
-    const fn preferred_wireguard_port(retry_attempt: u32) -> Constraint<u16> {
-        // Alternate between using a random port and port 53
-        if retry_attempt % 2 == 0 {
-            Constraint::Any
-        } else {
-            Constraint::Only(53)
-        }
-    }
-
-    const fn preferred_openvpn_constraints(
-        retry_attempt: u32,
-    ) -> (Constraint<u16>, TransportProtocol) {
-        // Prefer UDP by default. But if that has failed a couple of times, then try TCP port
-        // 443, which works for many with UDP problems. After that, just alternate
-        // between protocols.
-        // If the tunnel type constraint is set OpenVpn, from the 4th attempt onwards, the first
-        // two retry attempts OpenVpn constraints should be set to TCP as a bridge will be used,
-        // and to UDP or TCP for the next two attempts.
-        match retry_attempt {
-            0 | 1 => (Constraint::Any, TransportProtocol::Udp),
-            2 | 3 => (Constraint::Only(443), TransportProtocol::Tcp),
-            attempt if attempt % 4 < 2 => (Constraint::Any, TransportProtocol::Tcp),
-            attempt if attempt % 4 == 2 => (Constraint::Any, TransportProtocol::Udp),
-            _ => (Constraint::Any, TransportProtocol::Tcp),
+            (true, true) | (false, false) => (attempt.tunnel_type, attempt.openvpn_port),
+            (true, false) => (TunnelType::Wireguard, attempt.openvpn_port),
+            (false, true) => (TunnelType::OpenVpn, attempt.openvpn_only_port),
         }
     }
 
@@ -1195,8 +1584,17 @@ impl RelaySelector {
             .filter_matching_relay_list(self.parsed_relays.lock().relays())
             .into_iter()
             .collect();
+        self.pick_tunnel_endpoint(matcher, &matching_relays)
+    }
 
-        self.pick_random_relay(&matching_relays)
+    /// Like [Self::get_tunnel_endpoint_internal], but picks from relays that have already been
+    /// matched.
+    fn pick_tunnel_endpoint<T: EndpointMatcher>(
+        &self,
+        matcher: &RelayMatcher<T>,
+        matching_relays: &[Relay],
+    ) -> Result<NormalSelectedRelay, Error> {
+        self.pick_random_relay(matching_relays)
             .and_then(|selected_relay| {
                 let endpoint = matcher.mullvad_endpoint(selected_relay);
                 let addr_in = endpoint
@@ -1252,23 +1650,34 @@ impl RelaySelector {
         data.shadowsocks
             .choose(&mut rand::thread_rng())
             .map(|shadowsocks_endpoint| {
+                let addr_in = self.bridge_address(relay);
                 log::info!(
                     "Selected Shadowsocks bridge {} at {}:{}/{}",
                     relay.hostname,
-                    relay.ipv4_addr_in,
+                    addr_in,
                     shadowsocks_endpoint.port,
                     shadowsocks_endpoint.protocol
                 );
-                shadowsocks_endpoint.to_proxy_settings(relay.ipv4_addr_in.into())
+                shadowsocks_endpoint.to_proxy_settings(addr_in)
             })
     }
 
+    /// Returns the address to connect to a bridge at. Bridges are connected to over IPv6 if the
+    /// device can only reach the internet over IPv6.
+    fn bridge_address(&self, relay: &Relay) -> IpAddr {
+        match relay.ipv6_addr_in {
+            Some(ipv6_addr_in) if self.connectivity.lock().is_ipv6_only() => ipv6_addr_in.into(),
+            _ => relay.ipv4_addr_in.into(),
+        }
+    }
+
     fn wireguard_exit_matcher(&self) -> WireguardMatcher {
         let mut tunnel = WireguardMatcher::from_endpoint(
             self.parsed_relays.lock().parsed_list.wireguard.clone(),
         );
         tunnel.ip_version = WIREGUARD_EXIT_IP_VERSION;
         tunnel.port = WIREGUARD_EXIT_PORT;
+        tunnel.multihop_exit = true;
         tunnel
     }
 }
@@ -1332,7 +1741,8 @@ mod test {
     use mullvad_types::{
         custom_list::CustomListsSettings,
         relay_constraints::{
-            GeographicLocationConstraint, RelayConstraints, RelaySettings, WireguardConstraints,
+            GeographicLocationConstraint, MultihopDiversity, RelayConstraints, RelaySettings,
+            WireguardConstraints,
         },
         relay_list::{
             OpenVpnEndpoint, OpenVpnEndpointData, Relay, RelayListCity, RelayListCountry,
@@ -1343,6 +1753,10 @@ mod test {
     use std::collections::HashSet;
     use talpid_types::net::{wireguard::PublicKey, Endpoint};
 
+    fn default_attempt(retry_attempt: u32) -> AttemptTemplate {
+        *ConnectionPlan::default().attempt(retry_attempt)
+    }
+
     impl RelaySelector {
         fn get_obfuscator(
             &self,
@@ -1350,7 +1764,9 @@ mod test {
             endpoint: &MullvadWireguardEndpoint,
             retry_attempt: u32,
         ) -> Result<Option<SelectedObfuscator>, Error> {
-            self.get_obfuscator_inner(&self.config.lock(), relay, endpoint, retry_attempt)
+            let config = self.config.lock();
+            let attempt = *config.connection_plan.attempt(retry_attempt);
+            self.get_obfuscator_inner(&config, relay, endpoint, retry_attempt, &attempt)
         }
     }
 
@@ -1379,6 +1795,7 @@ mod test {
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
                             )
                             .unwrap(),
+                            port: None,
                         }),
                         location: None,
                     },
@@ -1396,6 +1813,7 @@ mod test {
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
                             )
                             .unwrap(),
+                            port: None,
                         }),
                         location: None,
                     },
@@ -1503,7 +1921,7 @@ mod test {
         let preferred = relay_selector.preferred_constraints(
             &relay_constraints,
             BridgeState::Off,
-            0,
+            &default_attempt(0),
             &CustomListsSettings::default(),
         );
         assert_eq!(
@@ -1516,7 +1934,7 @@ mod test {
                 .get_any_tunnel_endpoint(
                     &relay_constraints,
                     BridgeState::Off,
-                    attempt,
+                    &default_attempt(attempt),
                     &CustomListsSettings::default()
                 )
                 .is_ok());
@@ -1537,7 +1955,7 @@ mod test {
         let preferred = relay_selector.preferred_constraints(
             &relay_constraints,
             BridgeState::Off,
-            0,
+            &default_attempt(0),
             &CustomListsSettings::default(),
         );
         assert_eq!(
@@ -1550,7 +1968,7 @@ mod test {
                 .get_any_tunnel_endpoint(
                     &relay_constraints,
                     BridgeState::Off,
-                    attempt,
+                    &default_attempt(attempt),
                     &CustomListsSettings::default()
                 )
                 .is_ok());
@@ -1587,7 +2005,7 @@ mod test {
             .get_tunnel_endpoint(
                 &relay_constraints,
                 BridgeState::Off,
-                0,
+                &default_attempt(0),
                 &CustomListsSettings::default()
             )
             .is_err());
@@ -1600,12 +2018,74 @@ mod test {
             .get_tunnel_endpoint(
                 &relay_constraints,
                 BridgeState::Off,
-                0,
+                &default_attempt(0),
                 &CustomListsSettings::default()
             )
             .is_ok());
     }
 
+    #[test]
+    fn test_multihop_diversity() {
+        let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+        let mut relay_constraints = WIREGUARD_MULTIHOP_CONSTRAINTS.clone();
+
+        // se9-wireguard and se10-wireguard differ in provider and ownership
+        relay_constraints.wireguard_constraints.diversity = MultihopDiversity {
+            different_provider: true,
+            different_ownership: true,
+            ..MultihopDiversity::default()
+        };
+        for _ in 0..10 {
+            let result = relay_selector
+                .get_tunnel_endpoint(
+                    &relay_constraints,
+                    BridgeState::Off,
+                    &default_attempt(0),
+                    &CustomListsSettings::default(),
+                )
+                .expect("expected diverse entry and exit relays");
+            let entry = result.entry_relay.expect("expected entry relay");
+            assert_ne!(entry.provider, result.exit_relay.provider);
+            assert_ne!(entry.owned, result.exit_relay.owned);
+        }
+
+        // All relays are in the same country and city
+        relay_constraints.wireguard_constraints.diversity = MultihopDiversity {
+            different_provider: true,
+            different_country: true,
+            min_distance_km: Some(100),
+            ..MultihopDiversity::default()
+        };
+        let error = relay_selector
+            .get_tunnel_endpoint(
+                &relay_constraints,
+                BridgeState::Off,
+                &default_attempt(0),
+                &CustomListsSettings::default(),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::NoDiverseMultihopPair(UnsatisfiedDiversity(rules))
+                if rules == [DiversityRule::Country, DiversityRule::Distance(100)]
+        ));
+
+        // The exit relay is picked first when the entry location is not a subset of the exit
+        // location
+        relay_constraints.location = Constraint::Only(LocationConstraint::from(
+            GeographicLocationConstraint::Country("se".to_string()),
+        ));
+        assert!(matches!(
+            relay_selector.get_tunnel_endpoint(
+                &relay_constraints,
+                BridgeState::Off,
+                &default_attempt(0),
+                &CustomListsSettings::default(),
+            ),
+            Err(Error::NoDiverseMultihopPair(_))
+        ));
+    }
+
     #[test]
     fn test_wg_entry_filter() -> Result<(), String> {
         let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
@@ -1637,7 +2117,7 @@ mod test {
             .get_tunnel_endpoint(
                 &relay_constraints,
                 BridgeState::Off,
-                0,
+                &default_attempt(0),
                 &CustomListsSettings::default(),
             )
             .map_err(|error| error.to_string())?
@@ -1657,7 +2137,7 @@ mod test {
             .get_tunnel_endpoint(
                 &relay_constraints,
                 BridgeState::Off,
-                0,
+                &default_attempt(0),
                 &CustomListsSettings::default(),
             )
             .map_err(|error| error.to_string())?;
@@ -1773,7 +2253,7 @@ mod test {
                 let relay = relay_selector.get_tunnel_endpoint(
                     &relay_constraints,
                     BridgeState::Auto,
-                    retry_attempt,
+                    &default_attempt(retry_attempt),
                     &CustomListsSettings::default(),
                 );
 
@@ -1821,7 +2301,7 @@ mod test {
         let preferred = relay_selector.preferred_constraints(
             &relay_constraints,
             BridgeState::On,
-            0,
+            &default_attempt(0),
             &CustomListsSettings::default(),
         );
         assert_eq!(
@@ -1851,7 +2331,7 @@ mod test {
         let preferred = relay_selector.preferred_constraints(
             &relay_constraints,
             BridgeState::On,
-            0,
+            &default_attempt(0),
             &CustomListsSettings::default(),
         );
         assert_eq!(
@@ -1872,7 +2352,7 @@ mod test {
         let preferred = relay_selector.preferred_constraints(
             &relay_constraints,
             BridgeState::On,
-            0,
+            &default_attempt(0),
             &CustomListsSettings::default(),
         );
         assert_eq!(
@@ -1882,7 +2362,7 @@ mod test {
         let preferred = relay_selector.preferred_constraints(
             &relay_constraints,
             BridgeState::On,
-            3,
+            &default_attempt(3),
             &CustomListsSettings::default(),
         );
         assert_eq!(
@@ -1915,7 +2395,7 @@ mod test {
 
         let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
 
-        let result = relay_selector.get_tunnel_endpoint(&relay_constraints, BridgeState::Off, 0, &CustomListsSettings::default())
+        let result = relay_selector.get_tunnel_endpoint(&relay_constraints, BridgeState::Off, &default_attempt(0), &CustomListsSettings::default())
             .expect("Failed to get relay when tunnel constraints are set to Any and retrying the selection");
 
         assert!(
@@ -1933,6 +2413,12 @@ mod test {
             port: Constraint::Any,
             ip_version: Constraint::Any,
             entry_location: Constraint::Any,
+            diversity: MultihopDiversity {
+                different_provider: false,
+                different_country: false,
+                different_ownership: false,
+                min_distance_km: None,
+            },
         },
         tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
         openvpn_constraints: OpenVpnConstraints {
@@ -1949,6 +2435,12 @@ mod test {
             port: Constraint::Any,
             ip_version: Constraint::Any,
             entry_location: Constraint::Any,
+            diversity: MultihopDiversity {
+                different_provider: false,
+                different_country: false,
+                different_ownership: false,
+                min_distance_km: None,
+            },
         },
         tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
         openvpn_constraints: OpenVpnConstraints {
@@ -1960,7 +2452,7 @@ mod test {
     fn test_selecting_wireguard_location_will_consider_multihop() {
         let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
 
-        let result = relay_selector.get_tunnel_endpoint(&WIREGUARD_MULTIHOP_CONSTRAINTS, BridgeState::Off, 0, &CustomListsSettings::default())
+        let result = relay_selector.get_tunnel_endpoint(&WIREGUARD_MULTIHOP_CONSTRAINTS, BridgeState::Off, &default_attempt(0), &CustomListsSettings::default())
             .expect("Failed to get relay when tunnel constraints are set to default WireGuard multihop constraints");
 
         assert!(result.entry_relay.is_some());
@@ -1971,7 +2463,7 @@ mod test {
     fn test_selecting_wg_endpoint_with_udp2tcp_obfuscation() {
         let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
 
-        let result = relay_selector.get_tunnel_endpoint(&WIREGUARD_SINGLEHOP_CONSTRAINTS, BridgeState::Off, 0, &CustomListsSettings::default())
+        let result = relay_selector.get_tunnel_endpoint(&WIREGUARD_SINGLEHOP_CONSTRAINTS, BridgeState::Off, &default_attempt(0), &CustomListsSettings::default())
             .expect("Failed to get relay when tunnel constraints are set to default WireGuard constraints");
 
         assert!(result.entry_relay.is_none());
@@ -2000,7 +2492,7 @@ mod test {
     fn test_selecting_wg_endpoint_with_auto_obfuscation() {
         let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
 
-        let result = relay_selector.get_tunnel_endpoint(&WIREGUARD_SINGLEHOP_CONSTRAINTS, BridgeState::Off, 0, &CustomListsSettings::default())
+        let result = relay_selector.get_tunnel_endpoint(&WIREGUARD_SINGLEHOP_CONSTRAINTS, BridgeState::Off, &default_attempt(0), &CustomListsSettings::default())
             .expect("Failed to get relay when tunnel constraints are set to default WireGuard constraints");
 
         assert!(result.entry_relay.is_none());
@@ -2043,7 +2535,7 @@ mod test {
                 .get_tunnel_endpoint(
                     &WIREGUARD_SINGLEHOP_CONSTRAINTS,
                     BridgeState::Off,
-                    attempt,
+                    &default_attempt(attempt),
                     &CustomListsSettings::default(),
                 )
                 .expect("Failed to select a WireGuard relay");
@@ -2084,7 +2576,7 @@ mod test {
                 .get_tunnel_endpoint(
                     &constraints,
                     BridgeState::Auto,
-                    i,
+                    &default_attempt(i),
                     &CustomListsSettings::default(),
                 )
                 .unwrap();
@@ -2101,7 +2593,7 @@ mod test {
                 .get_tunnel_endpoint(
                     &constraints,
                     BridgeState::Auto,
-                    i,
+                    &default_attempt(i),
                     &CustomListsSettings::default(),
                 )
                 .unwrap();
@@ -2115,6 +2607,52 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_radius_constraint() {
+        use mullvad_types::relay_constraints::{GeographicRadius, RadiusCenter};
+
+        const GOTHENBURG: Coordinates = Coordinates {
+            latitude: 57.70887,
+            longitude: 11.97456,
+        };
+        const STOCKHOLM: Coordinates = Coordinates {
+            latitude: 59.3293,
+            longitude: 18.0686,
+        };
+
+        let near = |center, radius_km| {
+            let constraints = RelayConstraints {
+                location: Constraint::Only(LocationConstraint::Radius(GeographicRadius {
+                    center,
+                    radius_km,
+                })),
+                ..RelayConstraints::default()
+            };
+            RelaySelector::from_list(
+                SelectorConfig {
+                    relay_settings: RelaySettings::Normal(constraints),
+                    ..SelectorConfig::default()
+                },
+                RELAYS.clone(),
+            )
+        };
+
+        assert!(near(RadiusCenter::Coordinates(STOCKHOLM), 800.0)
+            .get_relay(0)
+            .is_ok());
+        assert!(near(RadiusCenter::Coordinates(STOCKHOLM), 100.0)
+            .get_relay(0)
+            .is_err());
+
+        // The constraint is ignored until the current location is known
+        let relay_selector = near(RadiusCenter::CurrentLocation, 100.0);
+        assert!(relay_selector.get_relay(0).is_ok());
+        relay_selector.set_current_location(STOCKHOLM);
+        assert!(relay_selector.get_relay(0).is_err());
+        relay_selector.set_current_location(GOTHENBURG);
+        assert!(relay_selector.get_relay(0).is_ok());
+    }
+
     // Make sure server and port selection varies between retry attempts.
     #[test]
     fn test_load_balancing() {
@@ -2177,7 +2715,7 @@ mod test {
                 .get_tunnel_endpoint(
                     &constraints,
                     BridgeState::Auto,
-                    i,
+                    &default_attempt(i),
                     &CustomListsSettings::default(),
                 )
                 .unwrap();
@@ -2222,6 +2760,349 @@ mod test {
         }
     }
 
+    /// Verify that the default connection plan retains the preferences described in
+    /// `docs/relay-selector.md` when no tunnel protocol or ports are specified.
+    #[test]
+    fn test_default_connection_plan() {
+        let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+        let relay_constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::from(
+                GeographicLocationConstraint::Country("se".to_string()),
+            )),
+            ..RelayConstraints::default()
+        };
+
+        let preferred_constraints = |retry_attempt: u32| {
+            relay_selector.preferred_constraints(
+                &relay_constraints,
+                BridgeState::Auto,
+                &default_attempt(retry_attempt),
+                &CustomListsSettings::default(),
+            )
+        };
+
+        // WireGuard is used on the first three attempts, alternating between a random port and
+        // port 53
+        let wireguard_ports = [Constraint::Any, Constraint::Only(53), Constraint::Any];
+        for (retry_attempt, port) in wireguard_ports.into_iter().enumerate() {
+            let preferred = preferred_constraints(retry_attempt as u32);
+            assert_eq!(
+                preferred.tunnel_protocol,
+                Constraint::Only(TunnelType::Wireguard),
+                "attempt {retry_attempt}"
+            );
+            assert_eq!(preferred.wireguard_constraints.port, port);
+        }
+
+        // Then OpenVPN, first over UDP, then TCP port 443, and then alternating between protocols
+        let openvpn_ports = [
+            (TransportProtocol::Udp, Constraint::Any),
+            (TransportProtocol::Tcp, Constraint::Only(443)),
+            (TransportProtocol::Tcp, Constraint::Only(443)),
+            (TransportProtocol::Tcp, Constraint::Any),
+            (TransportProtocol::Tcp, Constraint::Any),
+            (TransportProtocol::Udp, Constraint::Any),
+        ];
+        for (index, (protocol, port)) in openvpn_ports.into_iter().enumerate() {
+            let retry_attempt = wireguard_ports.len() + index;
+            let preferred = preferred_constraints(retry_attempt as u32);
+            assert_eq!(
+                preferred.tunnel_protocol,
+                Constraint::Only(TunnelType::OpenVpn),
+                "attempt {retry_attempt}"
+            );
+            assert_eq!(
+                preferred.openvpn_constraints.port,
+                Constraint::Only(TransportPort { protocol, port })
+            );
+        }
+    }
+
+    /// Verify that a connection plan set in the config is followed, and that simulating it
+    /// reports what was selected.
+    #[test]
+    fn test_simulate_custom_connection_plan() {
+        let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+        let template = AttemptTemplate {
+            wireguard_port: Constraint::Only(53),
+            ..default_attempt(0)
+        };
+        relay_selector.config.lock().connection_plan =
+            ConnectionPlan::new(vec![template], 0).unwrap();
+
+        let simulation = relay_selector.simulate_connection_plan(5);
+        assert_eq!(simulation.len(), 5);
+        for (retry_attempt, attempt) in simulation.into_iter().enumerate() {
+            assert_eq!(attempt.attempt, retry_attempt as u32);
+            assert_eq!(attempt.template, template);
+            let AttemptOutcome::Selected(selection) = attempt.outcome else {
+                panic!("nothing was selected on attempt {retry_attempt}");
+            };
+            assert_eq!(selection.tunnel_type, TunnelType::Wireguard);
+            assert_eq!(selection.endpoint.address.port(), 53);
+            assert!(selection.bridge.is_none());
+        }
+    }
+
+    #[test]
+    fn test_query_relays() {
+        let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+        let hostnames = |candidates: &[RelayCandidate]| -> Vec<String> {
+            candidates
+                .iter()
+                .map(|candidate| candidate.relay.hostname.clone())
+                .collect()
+        };
+
+        let result = relay_selector.query_relays(&RelayQuery::default());
+        assert_eq!(
+            hostnames(&result.exit),
+            [
+                "se9-wireguard",
+                "se10-wireguard",
+                "se-got-001",
+                "se-got-002"
+            ]
+        );
+        assert!(result
+            .exit
+            .iter()
+            .all(|candidate| candidate.probability == 0.25));
+        assert!(result.entry.is_empty());
+        assert_eq!(hostnames(&result.bridge), ["se-got-br-001"]);
+        assert_eq!(result.bridge[0].probability, 1.0);
+
+        // The only exit relay must not be used as entry
+        let mut query = RelayQuery::default();
+        query.constraints.tunnel_protocol = Constraint::Only(TunnelType::Wireguard);
+        query.constraints.location = Constraint::Only(LocationConstraint::from(
+            GeographicLocationConstraint::Hostname(
+                "se".to_string(),
+                "got".to_string(),
+                "se9-wireguard".to_string(),
+            ),
+        ));
+        query.constraints.wireguard_constraints.use_multihop = true;
+        let result = relay_selector.query_relays(&query);
+        assert_eq!(hostnames(&result.exit), ["se9-wireguard"]);
+        assert_eq!(hostnames(&result.entry), ["se10-wireguard"]);
+        assert_eq!(result.entry[0].probability, 1.0);
+
+        // Querying must not change the settings used for selection
+        assert_eq!(
+            relay_selector.config.lock().relay_settings,
+            SelectorConfig::default().relay_settings
+        );
+    }
+
+    #[test]
+    fn test_user_relays() {
+        let user_relay = |hostname: &str, country_code: &str, city_code: &str, port| UserRelay {
+            hostname: hostname.to_string(),
+            location: Location {
+                country: country_code.to_uppercase(),
+                country_code: country_code.to_string(),
+                city: city_code.to_uppercase(),
+                city_code: city_code.to_string(),
+                latitude: 64.1,
+                longitude: -21.9,
+            },
+            ipv4_addr_in: "192.0.2.1".parse().unwrap(),
+            ipv6_addr_in: None,
+            port,
+            public_key: PublicKey::from_base64("BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=")
+                .unwrap(),
+        };
+        let config = SelectorConfig {
+            user_relays: vec![
+                user_relay("home-got", "se", "got", 51900),
+                user_relay("home-rey", "is", "rey", 4444),
+                // Collides with a Mullvad relay and must be ignored
+                user_relay("se9-wireguard", "is", "rey", 4444),
+            ],
+            ..SelectorConfig::default()
+        };
+        let mut relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+
+        // User relays are merged into existing cities, or into new countries and cities
+        let locations = relay_selector.get_locations();
+        let got = locations.lookup_country("se".to_string()).unwrap();
+        assert!(got.cities[0]
+            .relays
+            .iter()
+            .any(|relay| relay.hostname == "home-got" && relay.is_user_defined()));
+        let rey = locations.lookup_country("is".to_string()).unwrap();
+        assert_eq!(rey.cities.len(), 1);
+        assert_eq!(rey.cities[0].relays.len(), 1);
+        let se9 = locations
+            .relays()
+            .find(|relay| relay.hostname == "se9-wireguard")
+            .unwrap();
+        assert!(!se9.is_user_defined());
+
+        // User relays are selected using their own port
+        let rey_location = LocationConstraint::from(GeographicLocationConstraint::Hostname(
+            "is".to_string(),
+            "rey".to_string(),
+            "home-rey".to_string(),
+        ));
+        let mut relay_constraints = RelayConstraints {
+            location: Constraint::Only(rey_location.clone()),
+            ..WIREGUARD_SINGLEHOP_CONSTRAINTS
+        };
+        for attempt in 0..4 {
+            let result = relay_selector
+                .get_tunnel_endpoint(
+                    &relay_constraints,
+                    BridgeState::Off,
+                    &default_attempt(attempt),
+                    &CustomListsSettings::default(),
+                )
+                .expect("expected user relay to be selected");
+            assert_eq!(result.exit_relay.hostname, "home-rey");
+            assert_eq!(
+                result.endpoint.to_endpoint().address,
+                "192.0.2.1:4444".parse().unwrap()
+            );
+        }
+
+        // Port constraints for other ports exclude user relays
+        relay_constraints.wireguard_constraints.port = Constraint::Only(53);
+        assert!(relay_selector
+            .get_tunnel_endpoint(
+                &relay_constraints,
+                BridgeState::Off,
+                &default_attempt(0),
+                &CustomListsSettings::default(),
+            )
+            .is_err());
+
+        // User relays are not selected by locations that contain them without naming them
+        relay_constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::from(
+                GeographicLocationConstraint::City("is".to_string(), "rey".to_string()),
+            )),
+            ..WIREGUARD_SINGLEHOP_CONSTRAINTS
+        };
+        assert!(relay_selector
+            .get_tunnel_endpoint(
+                &relay_constraints,
+                BridgeState::Off,
+                &default_attempt(0),
+                &CustomListsSettings::default(),
+            )
+            .is_err());
+
+        relay_constraints.location = Constraint::Any;
+        for attempt in 0..4 {
+            let result = relay_selector
+                .get_tunnel_endpoint(
+                    &relay_constraints,
+                    BridgeState::Off,
+                    &default_attempt(attempt),
+                    &CustomListsSettings::default(),
+                )
+                .expect("expected a Mullvad relay to be selected");
+            assert!(!result.exit_relay.is_user_defined());
+        }
+
+        // User relays can be used as entry relays, but not as exit relays
+        relay_constraints = RelayConstraints {
+            location: Constraint::Only(rey_location.clone()),
+            ..WIREGUARD_MULTIHOP_CONSTRAINTS
+        };
+        assert!(relay_selector
+            .get_tunnel_endpoint(
+                &relay_constraints,
+                BridgeState::Off,
+                &default_attempt(0),
+                &CustomListsSettings::default(),
+            )
+            .is_err());
+
+        relay_constraints.location = Constraint::Any;
+        relay_constraints.wireguard_constraints.entry_location = Constraint::Only(rey_location);
+        let result = relay_selector
+            .get_tunnel_endpoint(
+                &relay_constraints,
+                BridgeState::Off,
+                &default_attempt(0),
+                &CustomListsSettings::default(),
+            )
+            .expect("expected user relay to be used as entry");
+        assert_eq!(result.entry_relay.unwrap().hostname, "home-rey");
+        assert!(!result.exit_relay.is_user_defined());
+    }
+
+    /// Relays and bridges must be connected to over IPv6 when only IPv6 is available, and
+    /// addresses must be synthesized from the NAT64 prefix for those that lack IPv6 addresses.
+    #[test]
+    fn test_ipv6_only_connectivity() {
+        let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+        relay_selector.set_connectivity(Connectivity::Status {
+            ipv4: false,
+            ipv6: true,
+        });
+
+        for attempt in 0..4 {
+            let (relay, _bridge, obfuscator) = relay_selector
+                .get_relay(attempt)
+                .expect("expected a relay to be selected");
+            let SelectedRelay::Normal(relay) = relay else {
+                panic!("expected a normal relay");
+            };
+            assert!(matches!(relay.endpoint, MullvadEndpoint::Wireguard(_)));
+            assert!(relay.endpoint.to_endpoint().address.is_ipv6());
+            if let Some(SelectedObfuscator {
+                config: ObfuscatorConfig::Udp2Tcp { endpoint },
+                ..
+            }) = obfuscator
+            {
+                assert!(endpoint.is_ipv6());
+            }
+        }
+
+        // The bridge lacks an IPv6 address, so IPv4 is used until a NAT64 prefix is known
+        let bridge_address = |relay_selector: &RelaySelector| match relay_selector
+            .get_bridge_forced()
+            .expect("expected a bridge to be selected")
+        {
+            CustomProxy::Shadowsocks(shadowsocks) => shadowsocks.endpoint.ip(),
+            bridge => panic!("unexpected bridge: {bridge:?}"),
+        };
+        assert_eq!(
+            bridge_address(&relay_selector),
+            "1.3.3.7".parse::<IpAddr>().unwrap()
+        );
+
+        relay_selector.set_nat64_prefix(Some(Nat64Prefix::WELL_KNOWN));
+        assert_eq!(
+            bridge_address(&relay_selector),
+            "64:ff9b::103:307".parse::<IpAddr>().unwrap()
+        );
+
+        // Native IPv6 addresses are preferred over synthesized ones
+        let se9 = relay_selector
+            .parsed_relays
+            .lock()
+            .relays()
+            .find(|relay| relay.hostname == "se9-wireguard")
+            .cloned()
+            .unwrap();
+        assert_eq!(
+            se9.ipv6_addr_in,
+            Some("2a03:1b20:5:f011::a09f".parse().unwrap())
+        );
+
+        // IPv4 is used again once it is available
+        relay_selector.set_nat64_prefix(None);
+        relay_selector.set_connectivity(Connectivity::PresumeOnline);
+        assert_eq!(
+            bridge_address(&relay_selector),
+            "1.3.3.7".parse::<IpAddr>().unwrap()
+        );
+    }
+
     /// Ensure that `include_in_country` is ignored if all relays have it set to false (i.e., some
     /// relay is returned). Also ensure that `include_in_country` is respected if some relays
     /// have it set to true (i.e., that relay is never returned)
@@ -2253,6 +3134,7 @@ mod test {
                                         "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
                                     )
                                     .unwrap(),
+                                    port: None,
                                 },
                             ),
                             location: None,
@@ -2272,6 +3154,7 @@ mod test {
                                         "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
                                     )
                                     .unwrap(),
+                                    port: None,
                                 },
                             ),
                             location: None,
@@ -2,8 +2,8 @@ use crate::CustomListsSettings;
 use mullvad_types::{
     endpoint::{MullvadEndpoint, MullvadWireguardEndpoint},
     relay_constraints::{
-        Constraint, Match, OpenVpnConstraints, Ownership, Providers, RelayConstraints,
-        ResolvedLocationConstraint, WireguardConstraints,
+        Constraint, Match, MultihopDiversity, OpenVpnConstraints, Ownership, Providers,
+        RelayConstraints, ResolvedLocationConstraint, WireguardConstraints,
     },
     relay_list::{
         OpenVpnEndpoint, OpenVpnEndpointData, Relay, RelayEndpointData, WireguardEndpointData,
@@ -85,6 +85,8 @@ impl<T: EndpointMatcher> RelayMatcher<T> {
             && self.providers.matches(relay)
             && self.ownership.matches(relay)
             && self.locations.matches_with_opts(relay, true)
+            // User-defined relays are only used when they are selected explicitly
+            && (!relay.is_user_defined() || self.locations.names_relay(relay))
             && self.endpoint_matcher.is_matching_relay(relay)
     }
 
@@ -216,6 +218,11 @@ pub struct WireguardMatcher {
     /// The peer is an already selected peer relay to be used with multihop.
     /// It's stored here so we can exclude it from further selections being made.
     pub peer: Option<Relay>,
+    /// Ways in which relays must differ from `peer`.
+    pub diversity: MultihopDiversity,
+    /// Whether this matcher selects the exit relay of a multihop circuit. User-defined relays can
+    /// only be used as entry relays.
+    pub multihop_exit: bool,
     pub port: Constraint<u16>,
     pub ip_version: Constraint<IpVersion>,
 
@@ -226,6 +233,8 @@ impl WireguardMatcher {
     pub fn new(constraints: WireguardConstraints, data: WireguardEndpointData) -> Self {
         Self {
             peer: None,
+            diversity: constraints.diversity,
+            multihop_exit: false,
             port: constraints.port,
             ip_version: constraints.ip_version,
             data,
@@ -244,14 +253,14 @@ impl WireguardMatcher {
         relay: &Relay,
         data: &WireguardEndpointData,
     ) -> Option<MullvadEndpoint> {
+        let relay_data = relay.endpoint_data.unwrap_wireguard_ref();
         let host = self.get_address_for_wireguard_relay(relay)?;
-        let port = self.get_port_for_wireguard_relay(data)?;
+        let port = match relay_data.port {
+            Some(port) => port,
+            None => self.get_port_for_wireguard_relay(data)?,
+        };
         let peer_config = wireguard::PeerConfig {
-            public_key: relay
-                .endpoint_data
-                .unwrap_wireguard_ref()
-                .public_key
-                .clone(),
+            public_key: relay_data.public_key.clone(),
             endpoint: SocketAddr::new(host, port),
             allowed_ips: all_of_the_internet(),
             psk: None,
@@ -311,12 +320,20 @@ impl WireguardMatcher {
 
 impl EndpointMatcher for WireguardMatcher {
     fn is_matching_relay(&self, relay: &Relay) -> bool {
-        !self
-            .peer
+        self.peer
             .as_ref()
-            .map(|peer_relay| peer_relay.hostname == relay.hostname)
-            .unwrap_or(false)
-            && matches!(relay.endpoint_data, RelayEndpointData::Wireguard(..))
+            .map(|peer_relay| {
+                peer_relay.hostname != relay.hostname && self.diversity.allows(peer_relay, relay)
+            })
+            .unwrap_or(true)
+            && !(self.multihop_exit && relay.is_user_defined())
+            && match &relay.endpoint_data {
+                // Relays with their own port only match port constraints for that port
+                RelayEndpointData::Wireguard(data) => {
+                    data.port.map_or(true, |port| self.port.matches_eq(&port))
+                }
+                _ => false,
+            }
     }
 
     fn mullvad_endpoint(&self, relay: &Relay) -> Option<MullvadEndpoint> {
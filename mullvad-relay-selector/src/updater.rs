@@ -5,7 +5,7 @@ use futures::{
     Future, FutureExt, SinkExt, StreamExt,
 };
 use mullvad_api::{availability::ApiAvailabilityHandle, rest::MullvadRestHandle, RelayListProxy};
-use mullvad_types::relay_list::RelayList;
+use mullvad_types::relay_list::{RelayList, RelayListDiff};
 use parking_lot::Mutex;
 use std::{
     path::{Path, PathBuf},
@@ -53,7 +53,7 @@ pub struct RelayListUpdater {
     api_client: RelayListProxy,
     cache_path: PathBuf,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
-    on_update: Box<dyn Fn(&RelayList) + Send + 'static>,
+    on_update: Box<dyn Fn(&RelayList, &RelayListDiff) + Send + 'static>,
     last_check: SystemTime,
     api_availability: ApiAvailabilityHandle,
 }
@@ -63,7 +63,7 @@ impl RelayListUpdater {
         selector: super::RelaySelector,
         api_handle: MullvadRestHandle,
         cache_dir: &Path,
-        on_update: impl Fn(&RelayList) + Send + 'static,
+        on_update: impl Fn(&RelayList, &RelayListDiff) + Send + 'static,
     ) -> RelayListUpdaterHandle {
         let (tx, cmd_rx) = mpsc::channel(1);
         let api_availability = api_handle.availability.clone();
@@ -179,8 +179,10 @@ impl RelayListUpdater {
         }
 
         let mut parsed_relays = self.parsed_relays.lock();
+        let old_relay_list = parsed_relays.parsed_list.clone();
         parsed_relays.update(new_relay_list);
-        (self.on_update)(&parsed_relays.original_list);
+        let diff = old_relay_list.diff(&parsed_relays.parsed_list);
+        (self.on_update)(&parsed_relays.locations(), &diff);
         Ok(())
     }
 
@@ -2,6 +2,7 @@ use chrono::{offset::Utc, DateTime};
 #[cfg(target_os = "android")]
 use jnix::{FromJava, IntoJava};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Account identifier used for authentication.
 pub type AccountToken = String;
@@ -35,6 +36,53 @@ impl AccountData {
     }
 }
 
+/// How long before the account expires to warn about it, by default.
+pub const DEFAULT_EXPIRY_WARNING_THRESHOLDS: [Duration; 3] = [
+    Duration::from_secs(7 * 24 * 60 * 60),
+    Duration::from_secs(24 * 60 * 60),
+    Duration::from_secs(60 * 60),
+];
+
+/// Returns whether `thresholds` can be used as expiry warning thresholds. They must be positive,
+/// unique and in descending order.
+pub fn is_valid_expiry_warning_thresholds(thresholds: &[Duration]) -> bool {
+    !thresholds.iter().any(Duration::is_zero) && thresholds.windows(2).all(|pair| pair[0] > pair[1])
+}
+
+/// Warning that the account is about to run out of time, or has run out of time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiryWarning {
+    pub expiry: DateTime<Utc>,
+    /// The smallest warning threshold that has been passed. Less than this remains on the account.
+    /// Zero once the account has run out of time.
+    pub threshold: Duration,
+}
+
+impl ExpiryWarning {
+    /// Returns the warning that applies at `now`, or `None` if more time than the largest of the
+    /// `thresholds` remains. A final warning with a zero threshold applies once the account has
+    /// expired, unless there are no thresholds.
+    pub fn new(expiry: DateTime<Utc>, thresholds: &[Duration], now: DateTime<Utc>) -> Option<Self> {
+        let remaining = (expiry - now).to_std().unwrap_or(Duration::ZERO);
+        let threshold = thresholds
+            .iter()
+            .copied()
+            .filter(|threshold| remaining <= *threshold)
+            .min()?;
+        let threshold = if remaining.is_zero() {
+            Duration::ZERO
+        } else {
+            threshold
+        };
+        Some(ExpiryWarning { expiry, threshold })
+    }
+
+    /// Return true if the account has no time left.
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expiry
+    }
+}
+
 /// Data structure that's returned from successful invocation of the mullvad API's
 /// `/v1/submit-voucher` RPC.
 #[derive(Deserialize, Serialize, Debug)]
@@ -73,3 +121,55 @@ impl AccessTokenData {
         Utc::now() >= self.expiry
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expiry_warning_threshold() {
+        let now = Utc::now();
+        let hours = |hours: u64| Duration::from_secs(hours * 60 * 60);
+        let expires_in = |hours: i64| now + chrono::Duration::hours(hours);
+        let thresholds = DEFAULT_EXPIRY_WARNING_THRESHOLDS;
+
+        assert_eq!(
+            ExpiryWarning::new(expires_in(24 * 8), &thresholds, now),
+            None
+        );
+        assert_eq!(
+            ExpiryWarning::new(expires_in(24 * 3), &thresholds, now).map(|w| w.threshold),
+            Some(hours(24 * 7))
+        );
+        assert_eq!(
+            ExpiryWarning::new(expires_in(24), &thresholds, now).map(|w| w.threshold),
+            Some(hours(24))
+        );
+        // An expired account gets a final warning
+        assert_eq!(
+            ExpiryWarning::new(expires_in(-1), &thresholds, now).map(|w| w.threshold),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            ExpiryWarning::new(now, &thresholds, now).map(|w| w.threshold),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(ExpiryWarning::new(expires_in(-1), &[], now), None);
+    }
+
+    #[test]
+    fn test_valid_expiry_warning_thresholds() {
+        let hours = |hours: u64| Duration::from_secs(hours * 60 * 60);
+
+        assert!(is_valid_expiry_warning_thresholds(
+            &DEFAULT_EXPIRY_WARNING_THRESHOLDS
+        ));
+        assert!(is_valid_expiry_warning_thresholds(&[]));
+        assert!(!is_valid_expiry_warning_thresholds(&[
+            hours(1),
+            Duration::ZERO
+        ]));
+        assert!(!is_valid_expiry_warning_thresholds(&[hours(24), hours(24)]));
+        assert!(!is_valid_expiry_warning_thresholds(&[hours(1), hours(24)]));
+    }
+}
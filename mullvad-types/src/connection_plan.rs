@@ -0,0 +1,326 @@
+//! A connection plan describes how the relay selector varies the tunnel protocol, port, bridge
+//! usage and obfuscation across consecutive connection attempts.
+
+use crate::relay_constraints::{Constraint, TransportPort};
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::SocketAddr};
+use talpid_types::net::{Endpoint, TransportProtocol, TunnelType};
+
+/// Number of attempts in the default plan. Attempts past the end restart at
+/// [DEFAULT_PLAN_REPEAT_FROM].
+const DEFAULT_PLAN_LENGTH: u32 = 22;
+/// The default plan is periodic from this attempt onward. The period is 16 attempts so that the
+/// obfuscation port index cycles through up to four ports before the plan repeats.
+const DEFAULT_PLAN_REPEAT_FROM: usize = 6;
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error(display = "A connection plan must contain at least one attempt")]
+    NoAttempts,
+
+    #[error(
+        display = "Cannot repeat from attempt {} in a plan with {} attempts",
+        repeat_from,
+        attempts
+    )]
+    InvalidRepeatFrom { repeat_from: usize, attempts: usize },
+}
+
+/// Preferences applied by the relay selector on a single connection attempt. These never override
+/// explicit constraints: if no relay matches the preferences, the selector falls back on the
+/// constraints alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttemptTemplate {
+    /// Tunnel protocol to use if the constraints and the selected location allow for both.
+    pub tunnel_type: TunnelType,
+    /// WireGuard port to use unless a port is specified in the constraints.
+    pub wireguard_port: Constraint<u16>,
+    /// OpenVPN transport to use if this template selected OpenVPN.
+    pub openvpn_port: TransportPort,
+    /// OpenVPN transport to use if OpenVPN is the only option, because of the tunnel protocol
+    /// constraint or because the location only has OpenVPN relays.
+    pub openvpn_only_port: TransportPort,
+    /// Whether to use a bridge if the bridge state is "auto".
+    pub use_bridge: bool,
+    /// Whether to use udp2tcp if obfuscation is set to "auto". The value selects one of the
+    /// available udp2tcp ports, modulo the number of ports.
+    #[serde(default)]
+    pub auto_obfuscation: Option<u32>,
+}
+
+impl AttemptTemplate {
+    /// Returns the template used on attempt `retry_attempt` by the default plan.
+    const fn default_for_attempt(retry_attempt: u32) -> Self {
+        AttemptTemplate {
+            tunnel_type: if retry_attempt <= 2 {
+                TunnelType::Wireguard
+            } else {
+                TunnelType::OpenVpn
+            },
+            wireguard_port: default_wireguard_port(retry_attempt),
+            openvpn_port: default_openvpn_port(retry_attempt.saturating_sub(2)),
+            openvpn_only_port: default_openvpn_port(retry_attempt),
+            // Don't use a bridge for the first four attempts. After that, use a bridge on every
+            // other pair of attempts.
+            use_bridge: retry_attempt > 3 && retry_attempt % 4 < 2,
+            // Use obfuscation on attempts 2-3, 6-7, 10-11, ..., moving on to the next port for
+            // every set of four attempts.
+            auto_obfuscation: match retry_attempt % 4 {
+                0 | 1 => None,
+                filtered_retry => Some(retry_attempt / 4 + filtered_retry - 2),
+            },
+        }
+    }
+}
+
+const fn default_wireguard_port(retry_attempt: u32) -> Constraint<u16> {
+    // Alternate between using a random port and port 53
+    if retry_attempt % 2 == 0 {
+        Constraint::Any
+    } else {
+        Constraint::Only(53)
+    }
+}
+
+const fn default_openvpn_port(retry_attempt: u32) -> TransportPort {
+    // Prefer UDP by default. But if that has failed a couple of times, then try TCP port
+    // 443, which works for many with UDP problems. After that, just alternate
+    // between protocols.
+    // If the tunnel type constraint is set OpenVpn, from the 4th attempt onwards, the first
+    // two retry attempts OpenVpn constraints should be set to TCP as a bridge will be used,
+    // and to UDP or TCP for the next two attempts.
+    let (port, protocol) = match retry_attempt {
+        0 | 1 => (Constraint::Any, TransportProtocol::Udp),
+        2 | 3 => (Constraint::Only(443), TransportProtocol::Tcp),
+        attempt if attempt % 4 < 2 => (Constraint::Any, TransportProtocol::Tcp),
+        attempt if attempt % 4 == 2 => (Constraint::Any, TransportProtocol::Udp),
+        _ => (Constraint::Any, TransportProtocol::Tcp),
+    };
+    TransportPort { protocol, port }
+}
+
+impl fmt::Display for AttemptTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, WireGuard port {}, OpenVPN {} (OpenVPN only: {})",
+            self.tunnel_type,
+            self.wireguard_port,
+            TransportPortFormatter(&self.openvpn_port),
+            TransportPortFormatter(&self.openvpn_only_port),
+        )?;
+        if self.use_bridge {
+            write!(f, ", bridge")?;
+        }
+        if let Some(index) = self.auto_obfuscation {
+            write!(f, ", udp2tcp port #{index}")?;
+        }
+        Ok(())
+    }
+}
+
+struct TransportPortFormatter<'a>(&'a TransportPort);
+
+impl fmt::Display for TransportPortFormatter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} port {}", self.0.protocol, self.0.port)
+    }
+}
+
+/// An ordered list of attempt templates. Attempt `n` uses the `n`th template. Once the end of the
+/// list has been reached, the templates starting at `repeat_from` are used over again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedConnectionPlan")]
+pub struct ConnectionPlan {
+    attempts: Vec<AttemptTemplate>,
+    repeat_from: usize,
+}
+
+#[derive(Deserialize)]
+struct UncheckedConnectionPlan {
+    attempts: Vec<AttemptTemplate>,
+    #[serde(default)]
+    repeat_from: usize,
+}
+
+impl TryFrom<UncheckedConnectionPlan> for ConnectionPlan {
+    type Error = Error;
+
+    fn try_from(plan: UncheckedConnectionPlan) -> Result<Self, Self::Error> {
+        ConnectionPlan::new(plan.attempts, plan.repeat_from)
+    }
+}
+
+impl ConnectionPlan {
+    pub fn new(attempts: Vec<AttemptTemplate>, repeat_from: usize) -> Result<Self, Error> {
+        if attempts.is_empty() {
+            return Err(Error::NoAttempts);
+        }
+        if repeat_from >= attempts.len() {
+            return Err(Error::InvalidRepeatFrom {
+                repeat_from,
+                attempts: attempts.len(),
+            });
+        }
+        Ok(ConnectionPlan {
+            attempts,
+            repeat_from,
+        })
+    }
+
+    pub fn attempts(&self) -> &[AttemptTemplate] {
+        &self.attempts
+    }
+
+    pub fn repeat_from(&self) -> usize {
+        self.repeat_from
+    }
+
+    /// Returns the template to use on attempt `retry_attempt`.
+    pub fn attempt(&self, retry_attempt: u32) -> &AttemptTemplate {
+        let retry_attempt = retry_attempt as usize;
+        let index = if retry_attempt < self.attempts.len() {
+            retry_attempt
+        } else {
+            let period = self.attempts.len() - self.repeat_from;
+            self.repeat_from + (retry_attempt - self.repeat_from) % period
+        };
+        &self.attempts[index]
+    }
+}
+
+impl Default for ConnectionPlan {
+    /// Use WireGuard on the first three attempts, alternating between a random port and port 53.
+    /// After that, use OpenVPN, first over UDP and then over TCP port 443, while periodically
+    /// trying bridges and obfuscation.
+    fn default() -> Self {
+        ConnectionPlan {
+            attempts: (0..DEFAULT_PLAN_LENGTH)
+                .map(AttemptTemplate::default_for_attempt)
+                .collect(),
+            repeat_from: DEFAULT_PLAN_REPEAT_FROM,
+        }
+    }
+}
+
+/// The result of running the relay selector against a connection plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionPlanSimulation {
+    pub plan: ConnectionPlan,
+    /// Whether `plan` is the default plan, as opposed to one set in the settings.
+    pub is_default: bool,
+    pub attempts: Vec<SimulatedAttempt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulatedAttempt {
+    pub attempt: u32,
+    pub template: AttemptTemplate,
+    pub outcome: AttemptOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    Selected(AttemptSelection),
+    /// Nothing could be selected. Contains the reason.
+    Failed(String),
+}
+
+/// What the relay selector picked on a simulated attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttemptSelection {
+    pub tunnel_type: TunnelType,
+    /// Hostname of the exit relay, or the host of a custom tunnel endpoint.
+    pub exit: String,
+    /// Hostname of the entry relay, if multihop is used.
+    pub entry: Option<String>,
+    /// The endpoint the tunnel connects to.
+    pub endpoint: Endpoint,
+    /// Hostname of the bridge, or a description of a custom bridge.
+    pub bridge: Option<String>,
+    /// Address of the obfuscation endpoint.
+    pub obfuscator: Option<SocketAddr>,
+}
+
+impl fmt::Display for AttemptSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.tunnel_type, self.exit)?;
+        if let Some(entry) = &self.entry {
+            write!(f, " via {entry}")?;
+        }
+        write!(f, " at {}", self.endpoint)?;
+        if let Some(bridge) = &self.bridge {
+            write!(f, ", bridge {bridge}")?;
+        }
+        if let Some(obfuscator) = &self.obfuscator {
+            write!(f, ", udp2tcp at {obfuscator}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_attempt_cycles_from_repeat_from() {
+        let templates: Vec<_> = (0..4).map(AttemptTemplate::default_for_attempt).collect();
+        let plan = ConnectionPlan::new(templates.clone(), 1).unwrap();
+
+        let selected: Vec<_> = (0..10).map(|attempt| *plan.attempt(attempt)).collect();
+        let expected: Vec<_> = [0, 1, 2, 3, 1, 2, 3, 1, 2, 3]
+            .into_iter()
+            .map(|index| templates[index])
+            .collect();
+        assert_eq!(selected, expected);
+    }
+
+    #[test]
+    fn test_default_plan_is_periodic() {
+        let plan = ConnectionPlan::default();
+        for attempt in 0..200 {
+            let expected = AttemptTemplate::default_for_attempt(attempt);
+            let actual = plan.attempt(attempt);
+            assert_eq!(
+                actual.tunnel_type, expected.tunnel_type,
+                "attempt {attempt}"
+            );
+            assert_eq!(actual.wireguard_port, expected.wireguard_port);
+            assert_eq!(actual.openvpn_port, expected.openvpn_port);
+            assert_eq!(actual.openvpn_only_port, expected.openvpn_only_port);
+            assert_eq!(actual.use_bridge, expected.use_bridge);
+            // The obfuscation port index keeps growing in the unbounded version, but is only
+            // used modulo the number of ports.
+            assert_eq!(
+                actual.auto_obfuscation.map(|index| index % 4),
+                expected.auto_obfuscation.map(|index| index % 4),
+                "attempt {attempt}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_plans() {
+        assert_eq!(ConnectionPlan::new(vec![], 0), Err(Error::NoAttempts));
+        assert_eq!(
+            ConnectionPlan::new(vec![AttemptTemplate::default_for_attempt(0)], 1),
+            Err(Error::InvalidRepeatFrom {
+                repeat_from: 1,
+                attempts: 1
+            })
+        );
+        serde_json::from_str::<ConnectionPlan>(r#"{ "attempts": [] }"#).unwrap_err();
+    }
+
+    #[test]
+    fn test_serialization_roundtrip() {
+        let plan = ConnectionPlan::default();
+        let serialized = serde_json::to_string(&plan).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ConnectionPlan>(&serialized).unwrap(),
+            plan
+        );
+    }
+}
@@ -80,11 +80,17 @@ impl<'borrow, 'env: 'borrow> IntoJava<'borrow, 'env> for Id {
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CustomListsSettings {
     custom_lists: Vec<CustomList>,
+    /// Remove relays from custom lists when they are removed from the relay list
+    #[serde(default)]
+    prune_removed_relays: bool,
 }
 
 impl From<Vec<CustomList>> for CustomListsSettings {
     fn from(custom_lists: Vec<CustomList>) -> Self {
-        Self { custom_lists }
+        Self {
+            custom_lists,
+            prune_removed_relays: false,
+        }
     }
 }
 
@@ -96,6 +102,14 @@ impl CustomListsSettings {
     pub fn remove(&mut self, index: usize) {
         self.custom_lists.remove(index);
     }
+
+    pub fn prune_removed_relays(&self) -> bool {
+        self.prune_removed_relays
+    }
+
+    pub fn set_prune_removed_relays(&mut self, enabled: bool) {
+        self.prune_removed_relays = enabled;
+    }
 }
 
 impl IntoIterator for CustomListsSettings {
@@ -1,14 +1,21 @@
 pub mod access_method;
 pub mod account;
 pub mod auth_failed;
+pub mod connection_plan;
 pub mod custom_list;
 pub mod device;
 pub mod endpoint;
 pub mod location;
+pub mod logging;
+pub mod openvpn_profile;
+pub mod profile;
 pub mod relay_constraints;
 pub mod relay_list;
+pub mod relay_list_changes;
+pub mod relay_query;
 pub mod settings;
 pub mod states;
+pub mod user_relay;
 pub mod version;
 pub mod wireguard;
 
@@ -8,7 +8,7 @@ pub type CityCode = String;
 pub type Hostname = String;
 
 /// Describes the physical location of a [`crate::relay_list::Relay`] as returned by the API.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub country: String,
     pub country_code: CountryCode,
@@ -35,7 +35,7 @@ impl Location {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Coordinates {
     pub latitude: f64,
     pub longitude: f64,
@@ -57,6 +57,21 @@ impl From<Location> for Coordinates {
 }
 
 impl Coordinates {
+    /// Returns the distance to `other` in kilometers.
+    pub fn distance_from(&self, other: &Coordinates) -> f64 {
+        haversine_dist_deg(
+            self.latitude,
+            self.longitude,
+            other.latitude,
+            other.longitude,
+        )
+    }
+
+    /// Returns whether the coordinates are within the valid ranges of latitude and longitude.
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.latitude) && (-180.0..=180.0).contains(&self.longitude)
+    }
+
     /// Computes the approximate midpoint of a set of locations.
     ///
     /// This works by calculating the mean Cartesian coordinates, and converting them
@@ -0,0 +1,74 @@
+//! Types for controlling the daemon log at runtime.
+
+use log::LevelFilter;
+use std::collections::BTreeMap;
+
+/// Log levels currently used by the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Level used for all modules that have no level of their own
+    pub level: LevelFilter,
+    /// Levels set for individual modules, keyed by module path, e.g. `talpid_core::firewall`.
+    /// A level applies to the module and all of its submodules.
+    pub modules: BTreeMap<String, LevelFilter>,
+}
+
+impl LogFilter {
+    pub fn new(level: LevelFilter) -> Self {
+        LogFilter {
+            level,
+            modules: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the level set for the module that most closely matches `target`, if any.
+    pub fn module_level(&self, target: &str) -> Option<LevelFilter> {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .map(|rest| rest.is_empty() || rest.starts_with("::"))
+                    .unwrap_or(false)
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+    }
+}
+
+/// Format of streamed log records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The same format as the log file
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_module_level() {
+        let mut filter = LogFilter::new(LevelFilter::Info);
+        filter
+            .modules
+            .insert("talpid_core".to_owned(), LevelFilter::Debug);
+        filter
+            .modules
+            .insert("talpid_core::firewall".to_owned(), LevelFilter::Trace);
+
+        assert_eq!(filter.module_level("talpid_core"), Some(LevelFilter::Debug));
+        assert_eq!(
+            filter.module_level("talpid_core::dns"),
+            Some(LevelFilter::Debug)
+        );
+        assert_eq!(
+            filter.module_level("talpid_core::firewall::linux"),
+            Some(LevelFilter::Trace)
+        );
+        assert_eq!(filter.module_level("talpid_core_extra"), None);
+        assert_eq!(filter.module_level("mullvad_daemon"), None);
+    }
+}
@@ -0,0 +1,531 @@
+//! Parser for OpenVPN client profiles (`.ovpn` files).
+//!
+//! Only a small subset of the OpenVPN configuration language can be expressed using
+//! [`CustomTunnelEndpoint`]: the remote endpoint, the transport protocol, credentials and a few
+//! tunnel options. Everything else is either managed by the daemon itself (device, routes, DNS,
+//! ciphers, ...) or not supported at all. Directives that cannot be mapped are never dropped
+//! silently but returned as [`UnsupportedDirective`]s so that the user can decide whether the
+//! imported profile is usable. Certificates and keys are passed on to OpenVPN, so that servers
+//! other than Mullvad relays can be reached.
+//!
+//! Files referenced by a profile (`ca ca.crt`, `auth-user-pass creds.txt`, ...) are not read by
+//! the parser. Use [`inline_file_references`] to turn them into inline blocks before parsing. This
+//! way, the daemon never has to touch files owned by the user.
+
+use crate::{ConnectionConfig, CustomTunnelEndpoint};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::Path,
+};
+use talpid_types::net::{openvpn, Endpoint, TransportProtocol};
+
+/// The port used by OpenVPN if none is specified.
+const DEFAULT_PORT: u16 = 1194;
+
+/// Directives whose argument is a file that may also be given as an inline block.
+const FILE_DIRECTIVES: &[&str] = &[
+    "ca",
+    "cert",
+    "key",
+    "tls-auth",
+    "tls-crypt",
+    "auth-user-pass",
+];
+
+/// Directives that are always set by the daemon, and are therefore ignored without a warning.
+const MANAGED_DIRECTIVES: &[&str] = &[
+    "client",
+    "dev",
+    "dev-type",
+    "nobind",
+    "persist-key",
+    "persist-tun",
+    "pull",
+    "remote-cert-tls",
+    "resolv-retry",
+    "tls-client",
+    "verb",
+    "mute",
+    "mute-replay-warnings",
+    "auth-nocache",
+];
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error(display = "The profile does not contain a 'remote' directive")]
+    NoRemote,
+
+    #[error(display = "Invalid or missing argument for '{}' on line {}", _1, _0)]
+    InvalidArgument(usize, String),
+
+    #[error(display = "Unsupported transport protocol on line {}: {}", _0, _1)]
+    UnsupportedProtocol(usize, String),
+
+    #[error(display = "Inline block <{}> on line {} is never closed", _1, _0)]
+    UnterminatedBlock(usize, String),
+
+    #[error(display = "Unexpected closing tag </{}> on line {}", _1, _0)]
+    UnexpectedClosingTag(usize, String),
+
+    #[error(display = "The profile does not contain a username and password")]
+    MissingCredentials,
+
+    #[error(display = "The profile contains <{}> but not <{}>", _0, _1)]
+    IncompleteKeyMaterial(&'static str, &'static str),
+
+    #[error(display = "The profile contains both <tls-auth> and <tls-crypt>")]
+    ConflictingTlsKeys,
+
+    #[error(display = "File referenced by '{}' was not inlined: {}", _0, _1)]
+    FileReference(String, String),
+}
+
+/// The result of parsing an OpenVPN profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenVpnProfile {
+    /// Hostname or IP address of the first `remote`.
+    pub host: String,
+    pub config: openvpn::ConnectionConfig,
+    pub options: openvpn::TunnelOptions,
+    /// Directives that were found in the profile but could not be applied.
+    pub unsupported: Vec<UnsupportedDirective>,
+}
+
+impl OpenVpnProfile {
+    /// Returns a custom tunnel endpoint that connects to the server described by the profile.
+    pub fn to_custom_tunnel_endpoint(&self) -> CustomTunnelEndpoint {
+        CustomTunnelEndpoint::new(
+            self.host.clone(),
+            ConnectionConfig::OpenVpn(self.config.clone()),
+        )
+    }
+}
+
+/// Summary of a profile that has been imported by the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileImport {
+    pub endpoint: CustomTunnelEndpoint,
+    /// Tunnel options set by the profile. Options that are not set are left unchanged by
+    /// [`ProfileImport::apply_options`].
+    pub options: openvpn::TunnelOptions,
+    pub unsupported: Vec<UnsupportedDirective>,
+}
+
+impl ProfileImport {
+    /// Applies the tunnel options that are set by the profile to `options`.
+    pub fn apply_options(&self, options: &mut openvpn::TunnelOptions) {
+        if let Some(mssfix) = self.options.mssfix {
+            options.mssfix = Some(mssfix);
+        }
+    }
+}
+
+impl From<OpenVpnProfile> for ProfileImport {
+    fn from(profile: OpenVpnProfile) -> Self {
+        Self {
+            endpoint: profile.to_custom_tunnel_endpoint(),
+            options: profile.options,
+            unsupported: profile.unsupported,
+        }
+    }
+}
+
+/// A directive that was not mapped onto the custom tunnel endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsupportedDirective {
+    /// 1-based line number of the directive
+    pub line: usize,
+    pub directive: String,
+    pub reason: String,
+}
+
+impl fmt::Display for UnsupportedDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: '{}': {}",
+            self.line, self.directive, self.reason
+        )
+    }
+}
+
+/// Parses the contents of an `.ovpn` file. Files must be inlined beforehand using
+/// [`inline_file_references`].
+pub fn parse(profile: &str) -> Result<OpenVpnProfile, Error> {
+    let mut remote: Option<(String, Option<u16>, Option<TransportProtocol>)> = None;
+    let mut port = None;
+    let mut protocol = None;
+    let mut credentials = None;
+    let mut options = openvpn::TunnelOptions::default();
+    let mut key_material = openvpn::KeyMaterial::default();
+    let mut unsupported = vec![];
+
+    let mut lines = profile
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+
+    while let Some((line_nr, line)) = lines.next() {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(tag) = line
+            .strip_prefix("</")
+            .and_then(|tag| tag.strip_suffix('>'))
+        {
+            return Err(Error::UnexpectedClosingTag(line_nr, tag.to_owned()));
+        }
+
+        if let Some(tag) = line.strip_prefix('<').and_then(|tag| tag.strip_suffix('>')) {
+            let closing_tag = format!("</{tag}>");
+            let mut block = String::new();
+            loop {
+                match lines.next() {
+                    Some((_, line)) if line == closing_tag => break,
+                    Some((_, line)) => {
+                        block.push_str(line);
+                        block.push('\n');
+                    }
+                    None => return Err(Error::UnterminatedBlock(line_nr, tag.to_owned())),
+                }
+            }
+            match tag {
+                "auth-user-pass" => credentials = Some(parse_credentials(line_nr, &block)?),
+                "ca" => key_material.ca = Some(block),
+                "cert" => key_material.cert = Some(block),
+                "key" => key_material.key = Some(block),
+                "tls-auth" => key_material.tls_auth = Some(block),
+                "tls-crypt" => key_material.tls_crypt = Some(block),
+                tag => unsupported.push(UnsupportedDirective {
+                    line: line_nr,
+                    directive: format!("<{tag}>"),
+                    reason: "unknown inline block".to_owned(),
+                }),
+            }
+            continue;
+        }
+
+        let mut args = line.split_whitespace();
+        let directive = args.next().unwrap_or_default();
+        let args: Vec<&str> = args.collect();
+
+        match directive {
+            "remote" => {
+                if remote.is_some() {
+                    unsupported.push(UnsupportedDirective {
+                        line: line_nr,
+                        directive: line.to_owned(),
+                        reason: "only the first remote is used".to_owned(),
+                    });
+                    continue;
+                }
+                let host = args
+                    .first()
+                    .ok_or_else(|| Error::InvalidArgument(line_nr, directive.to_owned()))?;
+                let remote_port = args
+                    .get(1)
+                    .map(|port| parse_port(line_nr, directive, port))
+                    .transpose()?;
+                let remote_protocol = args
+                    .get(2)
+                    .map(|proto| parse_protocol(line_nr, proto))
+                    .transpose()?;
+                remote = Some((host.to_string(), remote_port, remote_protocol));
+            }
+            "port" | "rport" => {
+                let arg = args
+                    .first()
+                    .ok_or_else(|| Error::InvalidArgument(line_nr, directive.to_owned()))?;
+                port = Some(parse_port(line_nr, directive, arg)?);
+            }
+            "proto" => {
+                let arg = args
+                    .first()
+                    .ok_or_else(|| Error::InvalidArgument(line_nr, directive.to_owned()))?;
+                protocol = Some(parse_protocol(line_nr, arg)?);
+            }
+            "mssfix" => {
+                let mssfix = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| Error::InvalidArgument(line_nr, directive.to_owned()))?;
+                options.mssfix = Some(mssfix);
+            }
+            "key-direction" => {
+                key_material.key_direction = Some(parse_key_direction(line_nr, directive, &args)?);
+            }
+            directive if FILE_DIRECTIVES.contains(&directive) => {
+                // Without an argument, OpenVPN prompts for the credentials. An inline block may
+                // still follow.
+                if let Some(path) = args.first() {
+                    if *path != "[inline]" {
+                        return Err(Error::FileReference(
+                            directive.to_owned(),
+                            (*path).to_owned(),
+                        ));
+                    }
+                }
+                if directive == "tls-auth" && args.len() > 1 {
+                    key_material.key_direction =
+                        Some(parse_key_direction(line_nr, directive, &args[1..])?);
+                }
+            }
+            directive if MANAGED_DIRECTIVES.contains(&directive) => (),
+            directive => unsupported.push(UnsupportedDirective {
+                line: line_nr,
+                directive: directive.to_owned(),
+                reason: "not supported by custom tunnel endpoints".to_owned(),
+            }),
+        }
+    }
+
+    let (host, remote_port, remote_protocol) = remote.ok_or(Error::NoRemote)?;
+    let (username, password) = credentials.ok_or(Error::MissingCredentials)?;
+    match &key_material {
+        openvpn::KeyMaterial {
+            cert: Some(_),
+            key: None,
+            ..
+        } => return Err(Error::IncompleteKeyMaterial("cert", "key")),
+        openvpn::KeyMaterial {
+            cert: None,
+            key: Some(_),
+            ..
+        } => return Err(Error::IncompleteKeyMaterial("key", "cert")),
+        openvpn::KeyMaterial {
+            tls_auth: Some(_),
+            tls_crypt: Some(_),
+            ..
+        } => return Err(Error::ConflictingTlsKeys),
+        _ => (),
+    }
+
+    let address = match host.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        // The hostname is resolved when connecting
+        Err(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+    let endpoint = Endpoint::from_socket_address(
+        SocketAddr::new(address, remote_port.or(port).unwrap_or(DEFAULT_PORT)),
+        remote_protocol
+            .or(protocol)
+            .unwrap_or(TransportProtocol::Udp),
+    );
+
+    let mut config = openvpn::ConnectionConfig::new(endpoint, username, password);
+    config.key_material = key_material;
+
+    Ok(OpenVpnProfile {
+        host,
+        config,
+        options,
+        unsupported,
+    })
+}
+
+/// Replaces every directive in [`FILE_DIRECTIVES`] that references a file with an inline block
+/// containing the file. Relative paths are resolved against `base_dir`.
+pub fn inline_file_references(profile: &str, base_dir: &Path) -> io::Result<String> {
+    let mut output = String::with_capacity(profile.len());
+    for line in profile.lines() {
+        let mut args = line.split_whitespace();
+        let directive = args.next().unwrap_or_default();
+        match (args.next(), FILE_DIRECTIVES.contains(&directive)) {
+            (Some(path), true) if path != "[inline]" => {
+                let contents = std::fs::read_to_string(base_dir.join(path))?;
+                output.push_str(&format!(
+                    "<{directive}>\n{}\n</{directive}>\n",
+                    contents.trim()
+                ));
+                // The key direction is the second argument of `tls-auth`
+                if let Some(key_direction) = args.next() {
+                    output.push_str(&format!("key-direction {key_direction}\n"));
+                }
+            }
+            _ => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn parse_credentials(line_nr: usize, block: &str) -> Result<(String, String), Error> {
+    let mut lines = block.lines();
+    match (lines.next(), lines.next()) {
+        (Some(username), Some(password)) if !username.is_empty() => {
+            Ok((username.to_owned(), password.to_owned()))
+        }
+        _ => Err(Error::InvalidArgument(line_nr, "auth-user-pass".to_owned())),
+    }
+}
+
+fn parse_key_direction(line_nr: usize, directive: &str, args: &[&str]) -> Result<u8, Error> {
+    match args.first() {
+        Some(&"0") => Ok(0),
+        Some(&"1") => Ok(1),
+        _ => Err(Error::InvalidArgument(line_nr, directive.to_owned())),
+    }
+}
+
+fn parse_port(line_nr: usize, directive: &str, port: &str) -> Result<u16, Error> {
+    port.parse()
+        .map_err(|_| Error::InvalidArgument(line_nr, directive.to_owned()))
+}
+
+fn parse_protocol(line_nr: usize, protocol: &str) -> Result<TransportProtocol, Error> {
+    match protocol {
+        "udp" | "udp4" | "udp6" => Ok(TransportProtocol::Udp),
+        "tcp" | "tcp4" | "tcp6" | "tcp-client" | "tcp4-client" | "tcp6-client" => {
+            Ok(TransportProtocol::Tcp)
+        }
+        other => Err(Error::UnsupportedProtocol(line_nr, other.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+client
+dev tun
+proto udp
+remote se-got-ovpn-001.relays.mullvad.net 1196
+remote se-got-ovpn-002.relays.mullvad.net 1196
+cipher AES-256-GCM
+mssfix 1300
+<auth-user-pass>
+1234123412341234
+m
+</auth-user-pass>
+<ca>
+-----BEGIN CERTIFICATE-----
+MIIE
+-----END CERTIFICATE-----
+</ca>
+"#;
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = parse(PROFILE).unwrap();
+
+        assert_eq!(profile.host, "se-got-ovpn-001.relays.mullvad.net");
+        assert_eq!(profile.config.endpoint.address.port(), 1196);
+        assert_eq!(profile.config.endpoint.protocol, TransportProtocol::Udp);
+        assert_eq!(profile.config.username, "1234123412341234");
+        assert_eq!(profile.config.password, "m");
+        assert_eq!(profile.options.mssfix, Some(1300));
+        assert_eq!(
+            profile.config.key_material.ca.as_deref(),
+            Some("-----BEGIN CERTIFICATE-----\nMIIE\n-----END CERTIFICATE-----\n")
+        );
+
+        let unsupported: Vec<_> = profile
+            .unsupported
+            .iter()
+            .map(|directive| directive.directive.as_str())
+            .collect();
+        assert_eq!(
+            unsupported,
+            ["remote se-got-ovpn-002.relays.mullvad.net 1196", "cipher"]
+        );
+    }
+
+    #[test]
+    fn test_remote_overrides_proto_and_port() {
+        let profile = parse(
+            "proto udp\nport 1300\nremote 10.0.0.1 443 tcp-client\n<auth-user-pass>\na\nb\n</auth-user-pass>",
+        )
+        .unwrap();
+        assert_eq!(
+            profile.config.endpoint.address,
+            "10.0.0.1:443".parse().unwrap()
+        );
+        assert_eq!(profile.config.endpoint.protocol, TransportProtocol::Tcp);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("client\n"), Err(Error::NoRemote));
+        assert_eq!(parse("remote 10.0.0.1"), Err(Error::MissingCredentials));
+        assert_eq!(
+            parse("remote 10.0.0.1\n<ca>\nabc"),
+            Err(Error::UnterminatedBlock(2, "ca".to_owned()))
+        );
+        assert_eq!(
+            parse("remote 10.0.0.1 1194 tcp-server"),
+            Err(Error::UnsupportedProtocol(1, "tcp-server".to_owned()))
+        );
+        assert_eq!(
+            parse("remote 10.0.0.1\nca ca.crt"),
+            Err(Error::FileReference("ca".to_owned(), "ca.crt".to_owned()))
+        );
+        assert_eq!(
+            parse("remote 10.0.0.1\nkey-direction 2"),
+            Err(Error::InvalidArgument(2, "key-direction".to_owned()))
+        );
+
+        const CREDENTIALS: &str = "<auth-user-pass>\na\nb\n</auth-user-pass>\n";
+        assert_eq!(
+            parse(&format!(
+                "remote 10.0.0.1\n{CREDENTIALS}<cert>\nabc\n</cert>"
+            )),
+            Err(Error::IncompleteKeyMaterial("cert", "key"))
+        );
+        assert_eq!(
+            parse(&format!(
+                "remote 10.0.0.1\n{CREDENTIALS}<tls-auth>\na\n</tls-auth>\n\
+                <tls-crypt>\nb\n</tls-crypt>"
+            )),
+            Err(Error::ConflictingTlsKeys)
+        );
+    }
+
+    #[test]
+    fn test_parse_key_material() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("client.crt"), "CERT\n").unwrap();
+        std::fs::write(dir.path().join("client.key"), "KEY\n").unwrap();
+        std::fs::write(dir.path().join("ta.key"), "TLS AUTH\n").unwrap();
+        let profile = inline_file_references(
+            "remote 10.0.0.1\n<auth-user-pass>\na\nb\n</auth-user-pass>\n\
+            cert client.crt\nkey client.key\ntls-auth ta.key 1\n",
+            dir.path(),
+        )
+        .unwrap();
+
+        let key_material = parse(&profile).unwrap().config.key_material;
+        assert_eq!(
+            key_material,
+            openvpn::KeyMaterial {
+                ca: None,
+                cert: Some("CERT\n".to_owned()),
+                key: Some("KEY\n".to_owned()),
+                tls_auth: Some("TLS AUTH\n".to_owned()),
+                key_direction: Some(1),
+                tls_crypt: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_options() {
+        let mut options = openvpn::TunnelOptions { mssfix: Some(1400) };
+
+        // Options that are not set by the profile are kept
+        let import = ProfileImport::from(
+            parse("remote 10.0.0.1\n<auth-user-pass>\na\nb\n</auth-user-pass>").unwrap(),
+        );
+        import.apply_options(&mut options);
+        assert_eq!(options.mssfix, Some(1400));
+
+        let import = ProfileImport::from(parse(PROFILE).unwrap());
+        import.apply_options(&mut options);
+        assert_eq!(options.mssfix, Some(1300));
+    }
+}
@@ -0,0 +1,77 @@
+//! Named subsets of the settings that can be switched between.
+
+use crate::{
+    relay_constraints::{ObfuscationSettings, RelaySettings},
+    settings::{Settings, TunnelOptions},
+};
+use serde::{Deserialize, Serialize};
+
+/// A named snapshot of the settings that affect how the tunnel is established. Applying a profile
+/// replaces the corresponding settings, leaving all other settings untouched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub relay_settings: RelaySettings,
+    pub obfuscation_settings: ObfuscationSettings,
+    /// Tunnel options, including DNS options.
+    pub tunnel_options: TunnelOptions,
+    pub allow_lan: bool,
+}
+
+impl Profile {
+    /// Creates a profile from the current values of `settings`.
+    pub fn from_settings(name: String, settings: &Settings) -> Self {
+        Self {
+            name,
+            relay_settings: settings.relay_settings.clone(),
+            obfuscation_settings: settings.obfuscation_settings.clone(),
+            tunnel_options: settings.tunnel_options.clone(),
+            allow_lan: settings.allow_lan,
+        }
+    }
+
+    /// Replaces the settings covered by this profile.
+    pub fn apply_to(&self, settings: &mut Settings) {
+        settings.set_relay_settings(self.relay_settings.clone());
+        settings.obfuscation_settings = self.obfuscation_settings.clone();
+        settings.tunnel_options = self.tunnel_options.clone();
+        settings.allow_lan = self.allow_lan;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        relay_constraints::{
+            Constraint, GeographicLocationConstraint, LocationConstraint, RelayConstraints,
+        },
+        settings::DnsState,
+    };
+
+    #[test]
+    fn test_apply_profile() {
+        let mut office = Settings::default();
+        office.allow_lan = true;
+        office.tunnel_options.dns_options.state = DnsState::Custom;
+        office.relay_settings = RelaySettings::Normal(RelayConstraints {
+            location: Constraint::Only(LocationConstraint::from(
+                GeographicLocationConstraint::Country("se".to_owned()),
+            )),
+            ..Default::default()
+        });
+        let profile = Profile::from_settings("office".to_owned(), &office);
+
+        let mut settings = Settings {
+            auto_connect: true,
+            ..Default::default()
+        };
+        profile.apply_to(&mut settings);
+
+        assert_eq!(settings.relay_settings, office.relay_settings);
+        assert_eq!(settings.tunnel_options, office.tunnel_options);
+        assert!(settings.allow_lan);
+        // Settings outside of the profile are kept
+        assert!(settings.auto_connect);
+    }
+}
@@ -3,7 +3,7 @@
 
 use crate::{
     custom_list::{CustomListsSettings, Id},
-    location::{CityCode, CountryCode, Hostname},
+    location::{CityCode, Coordinates, CountryCode, Hostname},
     relay_list::Relay,
     CustomTunnelEndpoint,
 };
@@ -261,13 +261,102 @@ impl<'a> fmt::Display for RelaySettingsFormatter<'a> {
 #[cfg_attr(target_os = "android", jnix(package = "net.mullvad.mullvadvpn.model"))]
 pub enum LocationConstraint {
     Location(GeographicLocationConstraint),
-    CustomList { list_id: Id },
+    CustomList {
+        list_id: Id,
+    },
+    #[cfg(not(target_os = "android"))]
+    Radius(GeographicRadius),
+}
+
+impl LocationConstraint {
+    /// Centers radius constraints that refer to the current location on `current_location`. If
+    /// the current location is unknown, the constraint is left unchanged.
+    #[cfg_attr(target_os = "android", allow(clippy::match_single_binding))]
+    pub fn with_current_location(self, current_location: Option<&Coordinates>) -> Self {
+        match (self, current_location) {
+            #[cfg(not(target_os = "android"))]
+            (
+                LocationConstraint::Radius(GeographicRadius {
+                    center: RadiusCenter::CurrentLocation,
+                    radius_km,
+                }),
+                Some(location),
+            ) => LocationConstraint::Radius(GeographicRadius {
+                center: RadiusCenter::Coordinates(location.clone()),
+                radius_km,
+            }),
+            (constraint, _) => constraint,
+        }
+    }
+}
+
+/// Limits the set of relays to those within a distance of a point.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GeographicRadius {
+    pub center: RadiusCenter,
+    /// Maximum distance from the center, in kilometers
+    pub radius_km: f64,
+}
+
+// Floats are compared by their bits, so that equality is reflexive even for NaN
+impl PartialEq for GeographicRadius {
+    fn eq(&self, other: &Self) -> bool {
+        self.center == other.center && self.radius_km.to_bits() == other.radius_km.to_bits()
+    }
+}
+
+impl Eq for GeographicRadius {}
+
+impl GeographicRadius {
+    pub fn is_valid(&self) -> bool {
+        let center_is_valid = match &self.center {
+            RadiusCenter::Coordinates(coordinates) => coordinates.is_valid(),
+            RadiusCenter::CurrentLocation => true,
+        };
+        center_is_valid && self.radius_km.is_finite() && self.radius_km > 0.0
+    }
+}
+
+impl fmt::Display for GeographicRadius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "within {} km of ", self.radius_km)?;
+        match &self.center {
+            RadiusCenter::Coordinates(coordinates) => {
+                write!(f, "{}, {}", coordinates.latitude, coordinates.longitude)
+            }
+            RadiusCenter::CurrentLocation => write!(f, "the current location"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RadiusCenter {
+    Coordinates(Coordinates),
+    /// The last known location of the device
+    CurrentLocation,
+}
+
+impl PartialEq for RadiusCenter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RadiusCenter::Coordinates(coordinates), RadiusCenter::Coordinates(other)) => {
+                coordinates.latitude.to_bits() == other.latitude.to_bits()
+                    && coordinates.longitude.to_bits() == other.longitude.to_bits()
+            }
+            (RadiusCenter::CurrentLocation, RadiusCenter::CurrentLocation) => true,
+            _ => false,
+        }
+    }
 }
 
+impl Eq for RadiusCenter {}
+
 #[derive(Debug, Clone)]
 pub enum ResolvedLocationConstraint {
     Location(GeographicLocationConstraint),
     Locations(Vec<GeographicLocationConstraint>),
+    Radius { center: Coordinates, radius_km: f64 },
 }
 
 impl ResolvedLocationConstraint {
@@ -292,6 +381,17 @@ impl ResolvedLocationConstraint {
                     log::warn!("Resolved non-existent custom list");
                     Constraint::Only(ResolvedLocationConstraint::Locations(vec![]))
                 }),
+            #[cfg(not(target_os = "android"))]
+            Constraint::Only(LocationConstraint::Radius(radius)) => match radius.center {
+                RadiusCenter::Coordinates(center) => Constraint::Only(Self::Radius {
+                    center,
+                    radius_km: radius.radius_km,
+                }),
+                RadiusCenter::CurrentLocation => {
+                    log::warn!("The current location is unknown. Ignoring distance constraint");
+                    Constraint::Any
+                }
+            },
         }
     }
 }
@@ -304,36 +404,38 @@ impl From<GeographicLocationConstraint> for LocationConstraint {
 
 impl Set<Constraint<ResolvedLocationConstraint>> for Constraint<ResolvedLocationConstraint> {
     fn is_subset(&self, other: &Self) -> bool {
-        match self {
-            Constraint::Any => other.is_any(),
-            Constraint::Only(ResolvedLocationConstraint::Location(location)) => match other {
-                Constraint::Any => true,
-                Constraint::Only(ResolvedLocationConstraint::Location(other_location)) => {
-                    location.is_subset(other_location)
-                }
-                Constraint::Only(ResolvedLocationConstraint::Locations(other_locations)) => {
+        use ResolvedLocationConstraint::*;
+
+        let Constraint::Only(location) = self else {
+            return other.is_any();
+        };
+        let Constraint::Only(other_location) = other else {
+            return true;
+        };
+        match (location, other_location) {
+            (Location(location), Location(other_location)) => location.is_subset(other_location),
+            (Location(location), Locations(other_locations)) => other_locations
+                .iter()
+                .any(|other_location| location.is_subset(other_location)),
+            (Locations(locations), Location(other_location)) => locations
+                .iter()
+                .all(|location| location.is_subset(other_location)),
+            (Locations(locations), Locations(other_locations)) => {
+                locations.iter().all(|location| {
                     other_locations
                         .iter()
                         .any(|other_location| location.is_subset(other_location))
-                }
-            },
-            Constraint::Only(ResolvedLocationConstraint::Locations(locations)) => match other {
-                Constraint::Any => true,
-                Constraint::Only(ResolvedLocationConstraint::Location(other_location)) => locations
-                    .iter()
-                    .all(|location| location.is_subset(other_location)),
-                Constraint::Only(ResolvedLocationConstraint::Locations(other_locations)) => {
-                    for location in locations {
-                        if !other_locations
-                            .iter()
-                            .any(|other_location| location.is_subset(other_location))
-                        {
-                            return false;
-                        }
-                    }
-                    true
-                }
-            },
+                })
+            }
+            (
+                Radius { center, radius_km },
+                Radius {
+                    center: other_center,
+                    radius_km: other_radius_km,
+                },
+            ) => center.distance_from(other_center) + radius_km <= *other_radius_km,
+            // Whether a radius overlaps a named location depends on the relay list
+            (Radius { .. }, _) | (_, Radius { .. }) => false,
         }
     }
 }
@@ -348,6 +450,29 @@ impl Constraint<ResolvedLocationConstraint> {
             Constraint::Only(ResolvedLocationConstraint::Locations(locations)) => locations
                 .iter()
                 .any(|loc| loc.matches_with_opts(relay, ignore_include_in_country)),
+            Constraint::Only(ResolvedLocationConstraint::Radius { center, radius_km }) => relay
+                .location
+                .as_ref()
+                .map(|location| location.distance_from(center) <= *radius_km)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Returns whether the constraint names `relay` by its hostname, rather than only matching a
+    /// country, city or area that contains it.
+    pub fn names_relay(&self, relay: &Relay) -> bool {
+        let names_relay = |location: &GeographicLocationConstraint| {
+            matches!(location, GeographicLocationConstraint::Hostname(..))
+                && location.matches_with_opts(relay, true)
+        };
+        match self {
+            Constraint::Only(ResolvedLocationConstraint::Location(location)) => {
+                names_relay(location)
+            }
+            Constraint::Only(ResolvedLocationConstraint::Locations(locations)) => {
+                locations.iter().any(names_relay)
+            }
+            Constraint::Any | Constraint::Only(ResolvedLocationConstraint::Radius { .. }) => false,
         }
     }
 }
@@ -367,6 +492,8 @@ impl<'a> fmt::Display for LocationConstraintFormatter<'a> {
                 .find(|list| &list.id == list_id)
                 .map(|custom_list| write!(f, "{}", custom_list.name))
                 .unwrap_or_else(|| write!(f, "invalid custom list")),
+            #[cfg(not(target_os = "android"))]
+            LocationConstraint::Radius(radius) => write!(f, "{}", radius),
         }
     }
 }
@@ -619,7 +746,8 @@ impl Match<Relay> for Ownership {
     fn matches(&self, relay: &Relay) -> bool {
         match self {
             Ownership::MullvadOwned => relay.owned,
-            Ownership::Rented => !relay.owned,
+            // User-defined relays are neither owned nor rented by Mullvad
+            Ownership::Rented => !relay.owned && !relay.is_user_defined(),
         }
     }
 }
@@ -766,6 +894,108 @@ pub struct WireguardConstraints {
     pub use_multihop: bool,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub entry_location: Constraint<LocationConstraint>,
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub diversity: MultihopDiversity,
+}
+
+/// Ways in which the entry and exit relays must differ when using multihop, in addition to being
+/// different relays.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MultihopDiversity {
+    pub different_provider: bool,
+    pub different_country: bool,
+    pub different_ownership: bool,
+    /// Minimum distance between the entry and exit relays, in kilometers.
+    pub min_distance_km: Option<u32>,
+}
+
+impl MultihopDiversity {
+    /// Returns whether any diversity constraint is enabled.
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Returns the enabled rules.
+    pub fn rules(&self) -> Vec<DiversityRule> {
+        let mut rules = vec![];
+        if self.different_provider {
+            rules.push(DiversityRule::Provider);
+        }
+        if self.different_country {
+            rules.push(DiversityRule::Country);
+        }
+        if self.different_ownership {
+            rules.push(DiversityRule::Ownership);
+        }
+        if let Some(km) = self.min_distance_km {
+            rules.push(DiversityRule::Distance(km));
+        }
+        rules
+    }
+
+    /// Returns whether `entry` and `exit` may be used together.
+    pub fn allows(&self, entry: &Relay, exit: &Relay) -> bool {
+        self.rules().iter().all(|rule| rule.allows(entry, exit))
+    }
+}
+
+impl fmt::Display for MultihopDiversity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = self.rules();
+        if rules.is_empty() {
+            return write!(f, "any");
+        }
+        for (i, rule) in rules.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{rule}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single multihop diversity constraint.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DiversityRule {
+    Provider,
+    Country,
+    Ownership,
+    /// Minimum distance in kilometers.
+    Distance(u32),
+}
+
+impl DiversityRule {
+    /// Returns whether `entry` and `exit` satisfy this rule. Relays without a location never
+    /// satisfy location based rules.
+    pub fn allows(&self, entry: &Relay, exit: &Relay) -> bool {
+        match self {
+            DiversityRule::Provider => entry.provider != exit.provider,
+            DiversityRule::Ownership => entry.owned != exit.owned,
+            DiversityRule::Country => match (&entry.location, &exit.location) {
+                (Some(entry), Some(exit)) => entry.country_code != exit.country_code,
+                _ => false,
+            },
+            DiversityRule::Distance(km) => match (&entry.location, &exit.location) {
+                (Some(entry), Some(exit)) => {
+                    entry.distance_from(&Coordinates::from(exit)) >= f64::from(*km)
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DiversityRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiversityRule::Provider => write!(f, "different providers"),
+            DiversityRule::Country => write!(f, "different countries"),
+            DiversityRule::Ownership => write!(f, "different ownership"),
+            DiversityRule::Distance(km) => write!(f, "at least {km} km apart"),
+        }
+    }
 }
 
 pub struct WireguardConstraintsFormatter<'a> {
@@ -790,6 +1020,9 @@ impl<'a> fmt::Display for WireguardConstraintsFormatter<'a> {
                 }
             });
             write!(f, ", multihop entry {}", location)?;
+            if self.constraints.diversity.is_enabled() {
+                write!(f, " ({})", self.constraints.diversity)?;
+            }
         }
         Ok(())
     }
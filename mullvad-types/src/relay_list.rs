@@ -1,8 +1,14 @@
-use crate::location::{CityCode, CountryCode, Location};
+use crate::{
+    location::{CityCode, CountryCode, Location},
+    user_relay::USER_RELAY_PROVIDER,
+};
 #[cfg(target_os = "android")]
 use jnix::IntoJava;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
 use talpid_types::net::{
     proxy::{CustomProxy, Shadowsocks},
     wireguard, TransportProtocol,
@@ -51,6 +57,59 @@ impl RelayList {
             .flat_map(|country| country.cities)
             .flat_map(|city| city.relays)
     }
+
+    /// Returns the relays that were added, removed or deactivated in `new` compared to this list.
+    /// Relays are identified by their hostnames.
+    pub fn diff(&self, new: &RelayList) -> RelayListDiff {
+        let old_relays: HashMap<&str, &Relay> = self
+            .relays()
+            .map(|relay| (relay.hostname.as_str(), relay))
+            .collect();
+        let new_relays: HashMap<&str, &Relay> = new
+            .relays()
+            .map(|relay| (relay.hostname.as_str(), relay))
+            .collect();
+
+        RelayListDiff {
+            added: new
+                .relays()
+                .filter(|relay| !old_relays.contains_key(relay.hostname.as_str()))
+                .cloned()
+                .collect(),
+            removed: self
+                .relays()
+                .filter(|relay| !new_relays.contains_key(relay.hostname.as_str()))
+                .cloned()
+                .collect(),
+            deactivated: new
+                .relays()
+                .filter(|relay| {
+                    !relay.active
+                        && old_relays
+                            .get(relay.hostname.as_str())
+                            .map_or(false, |old_relay| old_relay.active)
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Relays that differ between two versions of a [`RelayList`]. See [`RelayList::diff`].
+#[derive(Default, Debug, Clone)]
+pub struct RelayListDiff {
+    /// Relays that are only in the new list
+    pub added: Vec<Relay>,
+    /// Relays that are only in the old list
+    pub removed: Vec<Relay>,
+    /// Relays that were active in the old list but are inactive in the new list
+    pub deactivated: Vec<Relay>,
+}
+
+impl RelayListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.deactivated.is_empty()
+    }
 }
 
 /// A list of [`RelayListCity`]s within a country. Used by [`RelayList`].
@@ -106,6 +165,13 @@ pub struct Relay {
     pub location: Option<Location>,
 }
 
+impl Relay {
+    /// Returns whether this relay was added by the user. See [`crate::user_relay::UserRelay`].
+    pub fn is_user_defined(&self) -> bool {
+        self.provider == USER_RELAY_PROVIDER
+    }
+}
+
 /// Specifies the type of a relay or relay-specific endpoint data.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -190,6 +256,9 @@ struct PortRange {
 pub struct WireguardRelayEndpointData {
     /// Public key used by the relay peer
     pub public_key: wireguard::PublicKey,
+    /// Port used instead of the common port ranges. Only set for user-defined relays.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
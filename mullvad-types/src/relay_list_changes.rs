@@ -0,0 +1,309 @@
+//! Changes to the relay list that affect relays referenced by the settings.
+
+use crate::{
+    custom_list::{CustomListsSettings, Id},
+    location::{CityCode, CountryCode},
+    relay_constraints::{
+        Constraint, GeographicLocationConstraint, LocationConstraint, RelaySettings,
+    },
+    relay_list::{Relay, RelayEndpointData, RelayListDiff},
+    settings::Settings,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Relays referenced by the settings that were added, removed or deactivated when the relay list
+/// was updated.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayListChanges {
+    pub added: Vec<RelayChange>,
+    pub removed: Vec<RelayChange>,
+    pub deactivated: Vec<RelayChange>,
+}
+
+impl RelayListChanges {
+    /// Returns the relays in `diff` that are referenced by `settings`.
+    pub fn new(diff: &RelayListDiff, settings: &Settings) -> Self {
+        let referenced = |relays: &[Relay]| {
+            relays
+                .iter()
+                .filter_map(|relay| RelayChange::new(relay, settings))
+                .collect()
+        };
+        Self {
+            added: referenced(&diff.added),
+            removed: referenced(&diff.removed),
+            deactivated: referenced(&diff.deactivated),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.deactivated.is_empty()
+    }
+
+    /// Removes relays that no longer exist from the custom lists that contain them. Returns whether
+    /// any custom list was changed.
+    pub fn prune_custom_lists(&self, custom_lists: &mut CustomListsSettings) -> bool {
+        let mut changed = false;
+        for list in custom_lists.iter_mut() {
+            let num_locations = list.locations.len();
+            list.locations.retain(|location| match location {
+                GeographicLocationConstraint::Hostname(_, _, hostname) => {
+                    !self.removed.iter().any(|relay| relay.hostname == *hostname)
+                }
+                _ => true,
+            });
+            changed |= list.locations.len() != num_locations;
+        }
+        changed
+    }
+}
+
+/// A relay that changed, and the settings that reference it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayChange {
+    pub hostname: String,
+    pub country_code: CountryCode,
+    pub city_code: CityCode,
+    pub referenced_by: Vec<RelayReference>,
+}
+
+impl RelayChange {
+    /// Returns `None` if the relay is not referenced by `settings`.
+    fn new(relay: &Relay, settings: &Settings) -> Option<Self> {
+        let location = relay.location.as_ref()?;
+        let is_bridge = relay.endpoint_data == RelayEndpointData::Bridge;
+
+        let mut referenced_by = vec![];
+        if let RelaySettings::Normal(constraints) = &settings.relay_settings {
+            if !is_bridge && references(&constraints.location, relay) {
+                referenced_by.push(RelayReference::Location);
+            }
+            if !is_bridge
+                && constraints.wireguard_constraints.use_multihop
+                && references(&constraints.wireguard_constraints.entry_location, relay)
+            {
+                referenced_by.push(RelayReference::EntryLocation);
+            }
+        }
+        if is_bridge && references(&settings.bridge_settings.normal.location, relay) {
+            referenced_by.push(RelayReference::BridgeLocation);
+        }
+        for list in settings.custom_lists.iter() {
+            if list
+                .locations
+                .iter()
+                .any(|location| location.matches_with_opts(relay, false))
+            {
+                referenced_by.push(RelayReference::CustomList {
+                    id: list.id,
+                    name: list.name.clone(),
+                });
+            }
+        }
+
+        if referenced_by.is_empty() {
+            return None;
+        }
+        Some(RelayChange {
+            hostname: relay.hostname.clone(),
+            country_code: location.country_code.clone(),
+            city_code: location.city_code.clone(),
+            referenced_by,
+        })
+    }
+}
+
+impl fmt::Display for RelayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {}), referenced by ",
+            self.hostname, self.city_code, self.country_code
+        )?;
+        for (i, reference) in self.referenced_by.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            reference.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A setting that refers to a relay.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelayReference {
+    /// The location constraint of the relay settings
+    Location,
+    /// The entry location used for multihop
+    EntryLocation,
+    /// The location constraint of the bridge settings
+    BridgeLocation,
+    /// A custom list
+    CustomList { id: Id, name: String },
+}
+
+impl fmt::Display for RelayReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayReference::Location => f.write_str("location"),
+            RelayReference::EntryLocation => f.write_str("entry location"),
+            RelayReference::BridgeLocation => f.write_str("bridge location"),
+            RelayReference::CustomList { name, .. } => write!(f, "custom list '{name}'"),
+        }
+    }
+}
+
+fn references(constraint: &Constraint<LocationConstraint>, relay: &Relay) -> bool {
+    match constraint {
+        Constraint::Only(LocationConstraint::Location(location)) => {
+            location.matches_with_opts(relay, false)
+        }
+        // Relays in custom lists are reported as referenced by the list itself
+        Constraint::Only(LocationConstraint::CustomList { .. }) | Constraint::Any => false,
+        // A radius does not name any relays
+        #[cfg(not(target_os = "android"))]
+        Constraint::Only(LocationConstraint::Radius(_)) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        custom_list::CustomList,
+        location::Location,
+        relay_list::{RelayList, RelayListCity, RelayListCountry},
+    };
+
+    fn relay(hostname: &str, city_code: &str, active: bool) -> Relay {
+        Relay {
+            hostname: hostname.to_owned(),
+            ipv4_addr_in: "10.0.0.1".parse().unwrap(),
+            ipv6_addr_in: None,
+            include_in_country: true,
+            active,
+            owned: true,
+            provider: "provider".to_owned(),
+            weight: 1,
+            endpoint_data: RelayEndpointData::Openvpn,
+            location: Some(Location {
+                country: "Sweden".to_owned(),
+                country_code: "se".to_owned(),
+                city: city_code.to_owned(),
+                city_code: city_code.to_owned(),
+                latitude: 0.0,
+                longitude: 0.0,
+            }),
+        }
+    }
+
+    fn relay_list(relays: Vec<Relay>) -> RelayList {
+        RelayList {
+            countries: vec![RelayListCountry {
+                name: "Sweden".to_owned(),
+                code: "se".to_owned(),
+                cities: vec![RelayListCity {
+                    name: "Gothenburg".to_owned(),
+                    code: "got".to_owned(),
+                    latitude: 0.0,
+                    longitude: 0.0,
+                    relays,
+                }],
+            }],
+            ..RelayList::empty()
+        }
+    }
+
+    /// Returns settings where relays are only referenced by custom lists
+    fn settings_with_list(list: CustomList) -> Settings {
+        let mut settings = Settings {
+            relay_settings: RelaySettings::Normal(Default::default()),
+            ..Default::default()
+        };
+        settings.custom_lists.add(list);
+        settings
+    }
+
+    fn pinned(hostname: &str) -> GeographicLocationConstraint {
+        GeographicLocationConstraint::Hostname("se".to_owned(), "got".to_owned(), hostname.into())
+    }
+
+    #[test]
+    fn test_relay_list_diff() {
+        let old = relay_list(vec![
+            relay("se-got-001", "got", true),
+            relay("se-got-002", "got", true),
+            relay("se-got-003", "got", false),
+        ]);
+        let new = relay_list(vec![
+            relay("se-got-002", "got", false),
+            relay("se-got-003", "got", false),
+            relay("se-got-004", "got", true),
+        ]);
+
+        let diff = old.diff(&new);
+        let hostnames = |relays: &[Relay]| {
+            relays
+                .iter()
+                .map(|relay| relay.hostname.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hostnames(&diff.added), vec!["se-got-004"]);
+        assert_eq!(hostnames(&diff.removed), vec!["se-got-001"]);
+        assert_eq!(hostnames(&diff.deactivated), vec!["se-got-002"]);
+    }
+
+    #[test]
+    fn test_only_referenced_relays_are_reported() {
+        let old = relay_list(vec![
+            relay("se-got-001", "got", true),
+            relay("se-got-002", "got", true),
+        ]);
+        let new = relay_list(vec![]);
+
+        let mut list = CustomList::new("pinned".to_owned());
+        list.locations.insert(pinned("se-got-001"));
+        let settings = settings_with_list(list.clone());
+
+        let changes = RelayListChanges::new(&old.diff(&new), &settings);
+        assert_eq!(changes.removed.len(), 1);
+        assert_eq!(changes.removed[0].hostname, "se-got-001");
+        assert_eq!(
+            changes.removed[0].referenced_by,
+            vec![RelayReference::CustomList {
+                id: list.id,
+                name: list.name,
+            }]
+        );
+        assert!(changes.added.is_empty());
+        assert!(changes.deactivated.is_empty());
+    }
+
+    #[test]
+    fn test_prune_custom_lists() {
+        let old = relay_list(vec![
+            relay("se-got-001", "got", true),
+            relay("se-got-002", "got", true),
+        ]);
+        let new = relay_list(vec![relay("se-got-002", "got", false)]);
+
+        let mut list = CustomList::new("pinned".to_owned());
+        list.locations.insert(pinned("se-got-001"));
+        list.locations.insert(pinned("se-got-002"));
+        let mut settings = settings_with_list(list);
+
+        let changes = RelayListChanges::new(&old.diff(&new), &settings);
+        assert!(changes.prune_custom_lists(&mut settings.custom_lists));
+        // Deactivated relays may come back and are kept
+        assert_eq!(
+            settings.custom_lists[0]
+                .locations
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![&pinned("se-got-002")]
+        );
+        assert!(!changes.prune_custom_lists(&mut settings.custom_lists));
+    }
+}
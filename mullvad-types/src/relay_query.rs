@@ -0,0 +1,57 @@
+//! Listing the relays that the relay selector would consider for a set of constraints.
+
+use crate::{
+    relay_constraints::{BridgeConstraints, RelayConstraints},
+    relay_list::Relay,
+};
+use serde::{Deserialize, Serialize};
+
+/// Constraints to match relays against. Querying relays never changes any settings.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayQuery {
+    pub constraints: RelayConstraints,
+    pub bridge_constraints: BridgeConstraints,
+}
+
+/// The relays matching a [`RelayQuery`], for each role a relay can be selected for.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RelayQueryResult {
+    /// Relays that may be used as the exit relay, or as the only relay if multihop is disabled.
+    pub exit: Vec<RelayCandidate>,
+    /// Relays that may be used as the WireGuard entry relay. Empty unless multihop is enabled.
+    pub entry: Vec<RelayCandidate>,
+    /// Bridges that may be used for OpenVPN over TCP.
+    pub bridge: Vec<RelayCandidate>,
+}
+
+/// A relay matching a query, and the probability of it being picked among the other relays
+/// for the same role.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayCandidate {
+    pub relay: Relay,
+    /// The weight used by the relay selector. This may differ from the weight of the relay,
+    /// e.g. bridges are weighted by their distance to the tunnel relays.
+    pub weight: u64,
+    pub probability: f64,
+}
+
+impl RelayCandidate {
+    /// Computes the selection probability of each relay given its weight. Relays are picked
+    /// uniformly if all weights are zero.
+    pub fn from_weighted(relays: Vec<(Relay, u64)>) -> Vec<Self> {
+        let total_weight: u64 = relays.iter().map(|(_, weight)| weight).sum();
+        let count = relays.len();
+        relays
+            .into_iter()
+            .map(|(relay, weight)| RelayCandidate {
+                relay,
+                weight,
+                probability: if total_weight == 0 {
+                    1.0 / count as f64
+                } else {
+                    weight as f64 / total_weight as f64
+                },
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,36 @@
+//! Previous versions of the settings.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Describes a snapshot of the settings, taken before they were changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsSnapshotInfo {
+    pub id: u32,
+    pub created: DateTime<Utc>,
+    /// What replaced the settings in the snapshot, e.g. the name of an RPC or "migration"
+    pub cause: String,
+}
+
+/// A single difference between two versions of the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsChange {
+    /// Path to the changed value, e.g. `tunnel_options.wireguard.mtu`
+    pub path: String,
+    /// Old value as JSON, or `None` if the value was added
+    pub old: Option<String>,
+    /// New value as JSON, or `None` if the value was removed
+    pub new: Option<String>,
+}
+
+impl fmt::Display for SettingsChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, old, new),
+            (Some(old), None) => write!(f, "{}: {} (removed)", self.path, old),
+            (None, Some(new)) => write!(f, "{}: {} (added)", self.path, new),
+            (None, None) => write!(f, "{}", self.path),
+        }
+    }
+}
@@ -1,21 +1,28 @@
 use crate::{
     access_method,
+    account::DEFAULT_EXPIRY_WARNING_THRESHOLDS,
+    connection_plan::ConnectionPlan,
     custom_list::CustomListsSettings,
     relay_constraints::{
         BridgeSettings, BridgeState, Constraint, GeographicLocationConstraint, LocationConstraint,
         ObfuscationSettings, RelayConstraints, RelayOverride, RelaySettings,
         RelaySettingsFormatter, SelectedObfuscation, WireguardConstraints,
     },
+    user_relay::UserRelay,
     wireguard,
 };
 #[cfg(target_os = "android")]
 use jnix::IntoJava;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
 #[cfg(target_os = "windows")]
 use std::{collections::HashSet, path::PathBuf};
-use talpid_types::net::{openvpn, GenericTunnelOptions};
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
+use talpid_types::net::{firewall_exception::FirewallException, openvpn, GenericTunnelOptions};
 
 mod dns;
+pub mod history;
 
 /// The version used by the current version of the code. Should always be the
 /// latest version that exists in `SettingsVersion`.
@@ -84,6 +91,12 @@ pub struct Settings {
     pub api_access_methods: access_method::Settings,
     /// If the daemon should allow communication with private (LAN) networks.
     pub allow_lan: bool,
+    /// Traffic that is allowed outside the tunnel in addition to LAN traffic.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub firewall_exceptions: Vec<FirewallException>,
+    /// Interfaces whose traffic and routes should not go through the tunnel.
+    #[cfg(target_os = "linux")]
+    pub trusted_interfaces: Vec<TrustedInterface>,
     /// Extra level of kill switch. When this setting is on, the disconnected state will block
     /// the firewall to not allow any traffic in or out.
     #[cfg_attr(target_os = "android", jnix(skip))]
@@ -96,8 +109,19 @@ pub struct Settings {
     /// Overrides for relays
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub relay_overrides: Vec<RelayOverride>,
+    /// WireGuard servers added to the relay list by the user
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub user_relays: Vec<UserRelay>,
+    /// How the relay selector varies its preferences across connection attempts. The default
+    /// plan is used if this is not set.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_plan: Option<ConnectionPlan>,
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
+    /// How long before the account expires to warn about it.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub expiry_warning_thresholds: Vec<Duration>,
     /// Split tunneling settings
     #[cfg(windows)]
     pub split_tunnel: SplitTunnelSettings,
@@ -139,11 +163,17 @@ impl Default for Settings {
             custom_lists: CustomListsSettings::default(),
             api_access_methods: access_method::Settings::default(),
             allow_lan: false,
+            firewall_exceptions: vec![],
+            #[cfg(target_os = "linux")]
+            trusted_interfaces: vec![],
             block_when_disconnected: false,
             auto_connect: false,
             tunnel_options: TunnelOptions::default(),
             relay_overrides: vec![],
+            user_relays: vec![],
+            connection_plan: None,
             show_beta_releases: false,
+            expiry_warning_thresholds: DEFAULT_EXPIRY_WARNING_THRESHOLDS.to_vec(),
             #[cfg(windows)]
             split_tunnel: SplitTunnelSettings::default(),
             settings_version: CURRENT_SETTINGS_VERSION,
@@ -1,9 +1,10 @@
 use crate::location::GeoIpLocation;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{fmt, time::Duration};
 use talpid_types::{
     net::TunnelEndpoint,
-    tunnel::{ActionAfterDisconnect, ErrorState},
+    tunnel::{ActionAfterDisconnect, CaptivePortalLogin, ErrorState},
 };
 
 /// Represents the state the client strives towards.
@@ -34,10 +35,19 @@ pub enum TunnelState {
         location: Option<GeoIpLocation>,
         /// Whether internet access is blocked due to lockdown mode
         locked_down: bool,
+        /// Status of the current or most recent captive portal login
+        #[serde(default)]
+        captive_portal: Option<CaptivePortalLogin>,
+        /// Time at which the daemon reconnects, if the tunnel has been paused
+        #[serde(default)]
+        paused_until: Option<DateTime<Utc>>,
     },
     Connecting {
         endpoint: TunnelEndpoint,
         location: Option<GeoIpLocation>,
+        /// Status of the current or most recent captive portal login
+        #[serde(default)]
+        captive_portal: Option<CaptivePortalLogin>,
     },
     Connected {
         endpoint: TunnelEndpoint,
@@ -62,13 +72,35 @@ impl TunnelState {
     pub fn is_disconnected(&self) -> bool {
         matches!(self, TunnelState::Disconnected { .. })
     }
+
+    /// Returns the time left until the daemon reconnects, if the tunnel has been paused.
+    pub fn pause_remaining(&self) -> Option<Duration> {
+        match self {
+            TunnelState::Disconnected {
+                paused_until: Some(deadline),
+                ..
+            } => Some((*deadline - Utc::now()).to_std().unwrap_or_default()),
+            _ => None,
+        }
+    }
+
+    /// Returns the status of the current or most recent captive portal login, or a detected
+    /// captive portal.
+    pub fn captive_portal(&self) -> Option<&CaptivePortalLogin> {
+        match self {
+            TunnelState::Disconnected { captive_portal, .. }
+            | TunnelState::Connecting { captive_portal, .. } => captive_portal.as_ref(),
+            TunnelState::Error(error_state) => error_state.captive_portal(),
+            TunnelState::Connected { .. } | TunnelState::Disconnecting(_) => None,
+        }
+    }
 }
 
 #[cfg(target_os = "android")]
-// Here we manually implement the `IntoJava` trait of jnix to skip the `locked_down` field of
-// `TunnelState::Disconnected`. The derive macro currently does not support skipping fields in
-// struct variants of enums. It was decided that this solution is the preferred to updating the
-// macro since the jnix crate will be dropped once android implements gRPC.
+// Here we manually implement the `IntoJava` trait of jnix to skip the `locked_down`,
+// `captive_portal` and `paused_until` fields. The derive macro currently does not support
+// skipping fields in struct variants of enums. It was decided that this solution is the preferred
+// to updating the macro since the jnix crate will be dropped once android implements gRPC.
 impl<'borrow, 'env> jnix::IntoJava<'borrow, 'env> for TunnelState
 where
     'env: 'borrow,
@@ -81,6 +113,8 @@ where
             Self::Disconnected {
                 location,
                 locked_down: _,
+                captive_portal: _,
+                paused_until: _,
             } => {
                 let constructor_signature = format!("({})V", location.jni_signature());
 
@@ -96,7 +130,11 @@ where
                     );
                 env.auto_local(object)
             }
-            Self::Connecting { endpoint, location } => {
+            Self::Connecting {
+                endpoint,
+                location,
+                captive_portal: _,
+            } => {
                 let constructor_signature = format!(
                     "({}{})V",
                     endpoint.jni_signature(),
@@ -0,0 +1,86 @@
+//! WireGuard servers run by the user, which are added to the relay list and selected like any
+//! other relay.
+
+use crate::{
+    location::{Hostname, Location},
+    relay_list::{Relay, RelayEndpointData, WireguardRelayEndpointData},
+};
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use talpid_types::net::wireguard;
+
+/// Provider of all user-defined relays. This can be used in provider constraints to select only
+/// user-defined relays.
+pub const USER_RELAY_PROVIDER: &str = "user";
+
+/// Weight of user-defined relays, which is in the same range as the weights of Mullvad relays.
+const USER_RELAY_WEIGHT: u64 = 100;
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error(display = "The hostname must be non-empty and must not contain whitespace")]
+    InvalidHostname,
+
+    #[error(display = "The country and city codes must be non-empty")]
+    InvalidLocation,
+
+    #[error(display = "The port must be non-zero")]
+    InvalidPort,
+
+    #[error(display = "A Mullvad relay with the hostname {} already exists", _0)]
+    HostnameTaken(Hostname),
+}
+
+/// A WireGuard server that is not run by Mullvad. It is merged into the relay list at the given
+/// location, but is only selected by location constraints and custom lists that name it by its
+/// hostname. It may be used as a single-hop relay or a multihop entry relay, but never as a
+/// multihop exit relay.
+///
+/// The server must accept the device's WireGuard key and tunnel addresses, and must answer at the
+/// gateway addresses of the Mullvad relay list, since these are used for DNS and connectivity
+/// checks.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UserRelay {
+    /// Unique name of the relay. Must not be the hostname of a Mullvad relay.
+    pub hostname: Hostname,
+    pub location: Location,
+    pub ipv4_addr_in: Ipv4Addr,
+    pub ipv6_addr_in: Option<Ipv6Addr>,
+    /// The port that the server listens on. Port constraints only match this port.
+    pub port: u16,
+    pub public_key: wireguard::PublicKey,
+}
+
+impl UserRelay {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.hostname.is_empty() || self.hostname.contains(char::is_whitespace) {
+            return Err(Error::InvalidHostname);
+        }
+        if self.location.country_code.is_empty() || self.location.city_code.is_empty() {
+            return Err(Error::InvalidLocation);
+        }
+        if self.port == 0 {
+            return Err(Error::InvalidPort);
+        }
+        Ok(())
+    }
+
+    /// Returns the relay list entry for this relay.
+    pub fn to_relay(&self) -> Relay {
+        Relay {
+            hostname: self.hostname.clone(),
+            ipv4_addr_in: self.ipv4_addr_in,
+            ipv6_addr_in: self.ipv6_addr_in,
+            include_in_country: true,
+            active: true,
+            owned: false,
+            provider: USER_RELAY_PROVIDER.to_owned(),
+            weight: USER_RELAY_WEIGHT,
+            endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                public_key: self.public_key.clone(),
+                port: Some(self.port),
+            }),
+            location: Some(self.location.clone()),
+        }
+    }
+}
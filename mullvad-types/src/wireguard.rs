@@ -199,11 +199,18 @@ pub struct TunnelOptions {
         jnix(map = "|maybe_mtu| maybe_mtu.map(|mtu| mtu as i32)")
     )]
     pub mtu: Option<u16>,
+    /// Probe the path MTU once the tunnel is up and lower the tunnel MTU to match it.
+    /// Only supported on Linux.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub mtu_discovery: bool,
     /// Obtain a PSK using the relay config client.
     pub quantum_resistant: QuantumResistantState,
     /// Interval used for automatic key rotation
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub rotation_interval: Option<RotationInterval>,
+    /// How the connectivity monitor verifies that the tunnel works
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub connectivity_check: wireguard::ConnectivityCheckOptions,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -211,8 +218,10 @@ impl Default for TunnelOptions {
     fn default() -> Self {
         TunnelOptions {
             mtu: None,
+            mtu_discovery: false,
             quantum_resistant: QuantumResistantState::Auto,
             rotation_interval: None,
+            connectivity_check: wireguard::ConnectivityCheckOptions::default(),
         }
     }
 }
@@ -221,11 +230,13 @@ impl TunnelOptions {
     pub fn into_talpid_tunnel_options(self) -> wireguard::TunnelOptions {
         wireguard::TunnelOptions {
             mtu: self.mtu,
+            mtu_discovery: self.mtu_discovery,
             quantum_resistant: match self.quantum_resistant {
                 QuantumResistantState::Auto => QUANTUM_RESISTANT_AUTO_STATE,
                 QuantumResistantState::On => true,
                 QuantumResistantState::Off => false,
             },
+            connectivity_check: self.connectivity_check,
         }
     }
 }
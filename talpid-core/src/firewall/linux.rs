@@ -1,4 +1,4 @@
-use super::{FirewallArguments, FirewallPolicy};
+use super::{CaptivePortalPolicy, FirewallArguments, FirewallPolicy};
 use crate::{split_tunnel, tunnel};
 use ipnetwork::IpNetwork;
 use libc;
@@ -14,12 +14,17 @@ use std::{
     fs, io,
     net::{IpAddr, Ipv4Addr},
 };
-use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic, Endpoint, TransportProtocol};
+use talpid_types::net::{
+    firewall_exception::FirewallException, trusted_interface::TrustedInterface, AllowedEndpoint,
+    AllowedTunnelTraffic, Endpoint, TransportProtocol,
+};
 
 /// Priority for rules that tag split tunneling packets. Equals NF_IP_PRI_MANGLE.
 const MANGLE_CHAIN_PRIORITY: i32 = libc::NF_IP_PRI_MANGLE;
 const PREROUTING_CHAIN_PRIORITY: i32 = libc::NF_IP_PRI_CONNTRACK + 1;
 const PROC_SYS_NET_IPV4_CONF_SRC_VALID_MARK: &str = "/proc/sys/net/ipv4/conf/all/src_valid_mark";
+const PROC_NET_ROUTE: &str = "/proc/net/route";
+const SYS_CLASS_NET: &str = "/sys/class/net";
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -108,7 +113,8 @@ impl Firewall {
 
     pub fn apply_policy(&mut self, policy: FirewallPolicy) -> Result<()> {
         let table = Table::new(&*TABLE_NAME, ProtoFamily::Inet);
-        let batch = PolicyBatch::new(&table).finalize(&policy, self.fwmark)?;
+        let uplinks = uplink_interfaces();
+        let batch = PolicyBatch::new(&table).finalize(&policy, self.fwmark, &uplinks)?;
         Self::send_and_process(&batch)?;
         Self::apply_kernel_config(&policy);
         self.verify_tables(&[&TABLE_NAME])
@@ -282,13 +288,19 @@ impl<'a> PolicyBatch<'a> {
     }
 
     /// Finalize the nftnl message batch by adding every firewall rule needed to satisfy the given
-    /// policy.
-    pub fn finalize(mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<FinalizedBatch> {
+    /// policy. `uplinks` are the interfaces that must never be trusted, as returned by
+    /// [`uplink_interfaces`].
+    pub fn finalize(
+        mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        uplinks: &[String],
+    ) -> Result<FinalizedBatch> {
         self.add_loopback_rules()?;
         self.add_split_tunneling_rules(policy, fwmark)?;
         self.add_dhcp_client_rules();
         self.add_ndp_rules();
-        self.add_policy_specific_rules(policy, fwmark)?;
+        self.add_policy_specific_rules(policy, fwmark, uplinks)?;
 
         Ok(self.batch.finalize())
     }
@@ -522,17 +534,34 @@ impl<'a> PolicyBatch<'a> {
         }
     }
 
-    fn add_policy_specific_rules(&mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<()> {
-        let allow_lan = match policy {
+    fn add_policy_specific_rules(
+        &mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        uplinks: &[String],
+    ) -> Result<()> {
+        let (allow_lan, firewall_exceptions, trusted_interfaces, tunnel_iface) = match policy {
             FirewallPolicy::Connecting {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                captive_portal,
+                allow_gateway_dns,
+                firewall_exceptions,
+                trusted_interfaces,
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
                 self.add_allow_endpoint_rules(allowed_endpoint);
+                if let Some(captive_portal) = captive_portal {
+                    self.add_allow_captive_portal_rules(captive_portal);
+                }
+                if let (true, Some(tunnel), AllowedTunnelTraffic::All) =
+                    (*allow_gateway_dns, tunnel, allowed_tunnel_traffic)
+                {
+                    self.add_allow_gateway_dns_rule(tunnel)?;
+                }
 
                 // Important to block DNS after allow relay rule (so the relay can operate
                 // over port 53) but before allow LAN (so DNS does not leak to the LAN)
@@ -556,17 +585,29 @@ impl<'a> PolicyBatch<'a> {
                         self.add_block_cve_2019_14899(tunnel);
                     }
                 }
-                *allow_lan
+                let tunnel_iface = tunnel.as_ref().map(|tunnel| tunnel.interface.as_str());
+                (
+                    *allow_lan,
+                    firewall_exceptions,
+                    trusted_interfaces,
+                    tunnel_iface,
+                )
             }
             FirewallPolicy::Connected {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
                 dns_servers,
+                allow_gateway_dns,
+                firewall_exceptions,
+                trusted_interfaces,
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
                 self.add_allow_dns_rules(tunnel, dns_servers, TransportProtocol::Udp)?;
                 self.add_allow_dns_rules(tunnel, dns_servers, TransportProtocol::Tcp)?;
+                if *allow_gateway_dns {
+                    self.add_allow_gateway_dns_rule(tunnel)?;
+                }
 
                 // Important to block DNS *before* we allow the tunnel and allow LAN. So DNS
                 // can't leak to the wrong IPs in the tunnel or on the LAN.
@@ -575,25 +616,44 @@ impl<'a> PolicyBatch<'a> {
                 if *allow_lan {
                     self.add_block_cve_2019_14899(tunnel);
                 }
-                *allow_lan
+                let tunnel_iface = Some(tunnel.interface.as_str());
+                (
+                    *allow_lan,
+                    firewall_exceptions,
+                    trusted_interfaces,
+                    tunnel_iface,
+                )
             }
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
+                firewall_exceptions,
+                trusted_interfaces,
             } => {
                 if let Some(endpoint) = allowed_endpoint {
                     self.add_allow_endpoint_rules(endpoint);
                 }
+                if let Some(captive_portal) = captive_portal {
+                    self.add_allow_captive_portal_rules(captive_portal);
+                }
 
                 // Important to drop DNS before allowing LAN (to stop DNS leaking to the LAN)
                 self.add_drop_dns_rule();
-                *allow_lan
+                (*allow_lan, firewall_exceptions, trusted_interfaces, None)
             }
         };
 
         if allow_lan {
             self.add_allow_lan_rules();
         }
+        self.add_firewall_exception_rules(firewall_exceptions);
+        let untrusted = uplinks
+            .iter()
+            .map(String::as_str)
+            .chain(tunnel_iface)
+            .collect::<Vec<_>>();
+        self.add_trusted_interface_rules(trusted_interfaces, &untrusted);
 
         // Reject any remaining outgoing traffic
         for chain in &[&self.out_chain, &self.forward_chain] {
@@ -688,6 +748,33 @@ impl<'a> PolicyBatch<'a> {
         self.batch.add(&out_rule, nftnl::MsgType::Add);
     }
 
+    /// Allows outgoing DNS traffic to the gateways, HTTP and HTTPS traffic to the gateways and the
+    /// captive portal probe, and responses to it, so that captive portals can be detected and
+    /// used.
+    fn add_allow_captive_portal_rules(&mut self, policy: &CaptivePortalPolicy) {
+        for (host, protocol, port) in policy.allowed_destinations() {
+            let mut out_rule = Rule::new(&self.out_chain);
+            check_ip(&mut out_rule, End::Dst, host);
+            check_port(&mut out_rule, protocol, End::Dst, port);
+            if policy.daemon_only {
+                out_rule.add_expr(&nft_expr!(meta skuid));
+                out_rule.add_expr(&nft_expr!(cmp == super::ROOT_UID));
+            }
+            add_verdict(&mut out_rule, &Verdict::Accept);
+            self.batch.add(&out_rule, nftnl::MsgType::Add);
+
+            let mut in_rule = Rule::new(&self.in_chain);
+            check_ip(&mut in_rule, End::Src, host);
+            check_port(&mut in_rule, protocol, End::Src, port);
+            let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+            in_rule.add_expr(&nft_expr!(ct state));
+            in_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+            in_rule.add_expr(&nft_expr!(cmp != 0u32));
+            add_verdict(&mut in_rule, &Verdict::Accept);
+            self.batch.add(&in_rule, nftnl::MsgType::Add);
+        }
+    }
+
     fn add_allow_dns_rules(
         &mut self,
         tunnel: &tunnel::TunnelMetadata,
@@ -722,6 +809,15 @@ impl<'a> PolicyBatch<'a> {
         Ok(())
     }
 
+    /// Allows the DNS liveness probe, which queries the resolver on the tunnel gateway over UDP.
+    fn add_allow_gateway_dns_rule(&mut self, tunnel: &tunnel::TunnelMetadata) -> Result<()> {
+        self.add_allow_tunnel_dns_rule(
+            &tunnel.interface,
+            TransportProtocol::Udp,
+            IpAddr::V4(tunnel.ipv4_gateway),
+        )
+    }
+
     fn add_allow_local_dns_rule(
         &mut self,
         tunnel_interface: &str,
@@ -873,6 +969,93 @@ impl<'a> PolicyBatch<'a> {
         self.add_dhcp_server_rules();
     }
 
+    /// Adds rules allowing the user-defined firewall exceptions. Connections may only be initiated
+    /// in the direction of an exception, but replies to them are allowed. Must come after the rule
+    /// dropping DNS, so that DNS does not leak to the excepted networks.
+    fn add_firewall_exception_rules(&mut self, exceptions: &[FirewallException]) {
+        for exception in exceptions {
+            let protocols = match exception.transport_protocols() {
+                Some(protocols) => protocols.into_iter().map(Some).collect(),
+                None => vec![None],
+            };
+            for protocol in protocols {
+                if exception.direction.allows_outbound() {
+                    for chain in &[&self.out_chain, &self.forward_chain] {
+                        let rule =
+                            exception_rule(chain, exception, protocol, End::Dst, End::Dst, false);
+                        self.batch.add(&rule, nftnl::MsgType::Add);
+                    }
+                    for chain in &[&self.in_chain, &self.forward_chain] {
+                        let rule =
+                            exception_rule(chain, exception, protocol, End::Src, End::Src, true);
+                        self.batch.add(&rule, nftnl::MsgType::Add);
+                    }
+                }
+                if exception.direction.allows_inbound() {
+                    let in_rule = exception_rule(
+                        &self.in_chain,
+                        exception,
+                        protocol,
+                        End::Src,
+                        End::Dst,
+                        false,
+                    );
+                    self.batch.add(&in_rule, nftnl::MsgType::Add);
+                    let out_rule = exception_rule(
+                        &self.out_chain,
+                        exception,
+                        protocol,
+                        End::Dst,
+                        End::Src,
+                        true,
+                    );
+                    self.batch.add(&out_rule, nftnl::MsgType::Add);
+                }
+            }
+        }
+    }
+
+    /// Adds rules allowing traffic on the trusted interfaces, which are matched by name since they
+    /// may come and go. Like for the tunnel interface, only established connections are forwarded
+    /// from them. Must come after the rule dropping DNS, so that DNS does not leak to them.
+    ///
+    /// Trusted interfaces matching any interface in `untrusted` are skipped, since trusting the
+    /// tunnel or an uplink would let all traffic bypass the tunnel.
+    fn add_trusted_interface_rules(
+        &mut self,
+        trusted_interfaces: &[TrustedInterface],
+        untrusted: &[&str],
+    ) {
+        for iface in trusted_interfaces {
+            if let Some(untrusted) = untrusted.iter().find(|name| iface.matches(name)) {
+                log::error!(
+                    "Not trusting interface {iface}, since it matches {untrusted}, whose traffic \
+                     must go through the tunnel"
+                );
+                continue;
+            }
+            for (chain, direction) in [
+                (&self.out_chain, Direction::Out),
+                (&self.forward_chain, Direction::Out),
+                (&self.in_chain, Direction::In),
+            ] {
+                let mut rule = Rule::new(chain);
+                check_iface_name(&mut rule, direction, iface);
+                add_verdict(&mut rule, &Verdict::Accept);
+                self.batch.add(&rule, nftnl::MsgType::Add);
+            }
+
+            let mut forward_rule = Rule::new(&self.forward_chain);
+            check_iface_name(&mut forward_rule, Direction::In, iface);
+            forward_rule.add_expr(&nft_expr!(ct state));
+            let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+            forward_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+            forward_rule.add_expr(&nft_expr!(cmp != 0u32));
+            add_verdict(&mut forward_rule, &Verdict::Accept);
+            self.batch.add(&forward_rule, nftnl::MsgType::Add);
+        }
+    }
+
     fn add_dhcp_server_rules(&mut self) {
         use TransportProtocol::Udp;
         // Outgoing DHCPv4 response
@@ -929,6 +1112,37 @@ fn allow_tunnel_dns_rule<'a>(
     Ok(rule)
 }
 
+/// Returns a rule accepting traffic to or from the network of a firewall exception. `net_end` is
+/// the end that is in the network, and `port_end` the end that has the port of the exception, if
+/// any. If `established_only` is set, only packets of established connections are accepted.
+fn exception_rule<'a>(
+    chain: &'a Chain<'_>,
+    exception: &FirewallException,
+    protocol: Option<TransportProtocol>,
+    net_end: End,
+    port_end: End,
+    established_only: bool,
+) -> Rule<'a> {
+    let mut rule = Rule::new(chain);
+    // Any host bits must be cleared, since only the network part of addresses is compared
+    let network = IpNetwork::new(exception.network.network(), exception.network.prefix())
+        .expect("prefix of existing network is valid");
+    check_net(&mut rule, net_end, network);
+    match (protocol, exception.port) {
+        (Some(protocol), Some(port)) => check_port(&mut rule, protocol, port_end, port),
+        (Some(protocol), None) => check_l4proto(&mut rule, protocol),
+        (None, _) => (),
+    }
+    if established_only {
+        let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+        rule.add_expr(&nft_expr!(ct state));
+        rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+        rule.add_expr(&nft_expr!(cmp != 0u32));
+    }
+    add_verdict(&mut rule, &Verdict::Accept);
+    rule
+}
+
 fn allow_interface_rule<'a>(
     chain: &'a Chain<'_>,
     direction: Direction,
@@ -952,6 +1166,22 @@ fn check_iface(rule: &mut Rule<'_>, direction: Direction, iface: &str) -> Result
     Ok(())
 }
 
+/// Checks the name of the interface, rather than its index, so that the rule also applies to
+/// interfaces that do not exist yet.
+fn check_iface_name(rule: &mut Rule<'_>, direction: Direction, iface: &TrustedInterface) {
+    rule.add_expr(&match direction {
+        Direction::In => nft_expr!(meta iifname),
+        Direction::Out => nft_expr!(meta oifname),
+    });
+    // Comparing only the bytes of a prefix makes the rule match any name starting with it, so
+    // the nul terminator must be included for exact names
+    let name = match iface.wildcard_prefix() {
+        Some(prefix) => prefix.as_bytes().to_vec(),
+        None => [iface.as_str().as_bytes(), &[0]].concat(),
+    };
+    rule.add_expr(&nft_expr!(cmp == &name[..]));
+}
+
 fn check_not_iface(rule: &mut Rule<'_>, direction: Direction, iface: &str) -> Result<()> {
     let iface_index = crate::linux::iface_index(iface)
         .map_err(|e| Error::LookupIfaceIndexError(iface.to_owned(), e))?;
@@ -1066,6 +1296,44 @@ fn set_src_valid_mark_sysctl() -> io::Result<()> {
     fs::write(PROC_SYS_NET_IPV4_CONF_SRC_VALID_MARK, b"1")
 }
 
+/// Returns the names of the interfaces that must never be trusted: physical interfaces, and the
+/// interfaces of the IPv4 default routes in the main routing table.
+pub fn uplink_interfaces() -> Vec<String> {
+    let mut uplinks = vec![];
+    match fs::read_dir(SYS_CLASS_NET) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                // Only interfaces backed by a device have a `device` link
+                if entry.path().join("device").exists() {
+                    uplinks.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        Err(error) => log::error!("Failed to list physical interfaces: {error}"),
+    }
+    match fs::read_to_string(PROC_NET_ROUTE) {
+        Ok(routes) => uplinks.extend(default_route_interfaces(&routes)),
+        Err(error) => log::error!("Failed to read the default routes: {error}"),
+    }
+    uplinks
+}
+
+/// Returns the interfaces of the default routes in `routes`, which has the format of
+/// `/proc/net/route`.
+fn default_route_interfaces(routes: &str) -> Vec<String> {
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            // Iface, Destination, Gateway, Flags, RefCnt, Use, Metric, Mask, ...
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [iface, "00000000", _, _, _, _, _, "00000000", ..] => Some(iface.to_owned()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 /// Tables that are no longer used but need to be deleted due to upgrades.
 /// This can be removed when upgrades from 2023.3 are no longer supported.
 fn batch_deprecated_tables(batch: &mut Batch) {
@@ -1082,3 +1350,243 @@ fn batch_deprecated_tables(batch: &mut Batch) {
         batch.add(table, nftnl::MsgType::Del);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::AllowedClients;
+
+    const FWMARK: u32 = 0x6d6f6c65;
+
+    fn policies(trusted_interfaces: Vec<TrustedInterface>) -> [FirewallPolicy; 3] {
+        let peer_endpoint = AllowedEndpoint {
+            endpoint: Endpoint::new(Ipv4Addr::new(192, 0, 2, 1), 51820, TransportProtocol::Udp),
+            clients: AllowedClients::Root,
+        };
+        let api_endpoint = AllowedEndpoint {
+            endpoint: Endpoint::new(Ipv4Addr::new(192, 0, 2, 2), 443, TransportProtocol::Tcp),
+            clients: AllowedClients::Root,
+        };
+        // The loopback interface stands in for the tunnel interface, since it always exists
+        let tunnel = tunnel::TunnelMetadata {
+            interface: "lo".to_owned(),
+            ips: vec![Ipv4Addr::new(10, 64, 0, 2).into()],
+            ipv4_gateway: Ipv4Addr::new(10, 64, 0, 1),
+            ipv6_gateway: None,
+            discovered_mtu: None,
+        };
+        [
+            FirewallPolicy::Connecting {
+                peer_endpoint: peer_endpoint.clone(),
+                tunnel: Some(tunnel.clone()),
+                allow_lan: true,
+                allowed_endpoint: api_endpoint.clone(),
+                allowed_tunnel_traffic: AllowedTunnelTraffic::All,
+                captive_portal: None,
+                allow_gateway_dns: false,
+                firewall_exceptions: vec![],
+                trusted_interfaces: trusted_interfaces.clone(),
+            },
+            FirewallPolicy::Connected {
+                peer_endpoint,
+                tunnel,
+                allow_lan: true,
+                dns_servers: vec![Ipv4Addr::new(10, 64, 0, 1).into()],
+                allow_gateway_dns: false,
+                firewall_exceptions: vec![],
+                trusted_interfaces: trusted_interfaces.clone(),
+            },
+            FirewallPolicy::Blocked {
+                allow_lan: true,
+                allowed_endpoint: Some(api_endpoint),
+                captive_portal: None,
+                firewall_exceptions: vec![],
+                trusted_interfaces,
+            },
+        ]
+    }
+
+    fn batch_bytes(policy: &FirewallPolicy) -> Vec<u8> {
+        batch_bytes_with_uplinks(policy, &["eth0".to_owned()])
+    }
+
+    fn batch_bytes_with_uplinks(policy: &FirewallPolicy, uplinks: &[String]) -> Vec<u8> {
+        let table = Table::new(&*TABLE_NAME, ProtoFamily::Inet);
+        let batch = PolicyBatch::new(&table)
+            .finalize(policy, FWMARK, uplinks)
+            .unwrap();
+        let mut bytes = vec![];
+        for page in &batch {
+            bytes.extend_from_slice(page);
+        }
+        bytes
+    }
+
+    /// Returns the offsets of every occurrence of `pattern` in `bytes`.
+    fn positions(bytes: &[u8], pattern: &[u8]) -> Vec<usize> {
+        bytes
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| *window == pattern)
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    #[test]
+    fn test_trusted_interface_rules() {
+        let trusted_interfaces = vec!["tailscale0".parse().unwrap(), "ztnet*".parse().unwrap()];
+        for policy in policies(trusted_interfaces) {
+            let bytes = batch_bytes(&policy);
+
+            // One rule each for the output, input and forward chains, and one for forwarding
+            // established connections from the interface
+            let exact = positions(&bytes, b"tailscale0\0");
+            let wildcard = positions(&bytes, b"ztnet");
+            assert_eq!(exact.len(), 4, "{policy}");
+            assert_eq!(wildcard.len(), 4, "{policy}");
+
+            // The rules must come after the ones rejecting DNS in the output and forward chains,
+            // but before the ones rejecting all remaining traffic
+            let rejects = positions(&bytes, b"reject\0");
+            assert_eq!(rejects.len(), 6, "{policy}");
+            for position in exact.iter().chain(&wildcard) {
+                assert!(rejects[3] < *position && *position < rejects[4], "{policy}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_rules_per_trusted_interface() {
+        // Each rule matching a trusted interface loads its name with a meta expression
+        let meta_exprs = |policy| positions(&batch_bytes(policy), b"meta\0").len();
+        let trusted = policies(vec!["tailscale0".parse().unwrap()]);
+        for (untrusted, trusted) in policies(vec![]).iter().zip(&trusted) {
+            assert_eq!(meta_exprs(trusted) - meta_exprs(untrusted), 4, "{trusted}");
+        }
+    }
+
+    #[test]
+    fn test_uplink_not_trusted() {
+        // Names of uplinks that pass the validation of `TrustedInterface`, since they do not
+        // follow the usual naming of physical interfaces
+        let trusted = policies(vec!["uplink0".parse().unwrap(), "br*".parse().unwrap()]);
+        let uplinks = ["uplink0".to_owned(), "br-lan".to_owned()];
+        for (untrusted, trusted) in policies(vec![]).iter().zip(&trusted) {
+            assert_eq!(
+                batch_bytes_with_uplinks(trusted, &uplinks),
+                batch_bytes_with_uplinks(untrusted, &uplinks),
+                "{trusted}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tunnel_not_trusted() {
+        // The loopback interface is the tunnel interface of the policies
+        let connecting_and_connected = &policies(vec!["lo".parse().unwrap()])[..2];
+        for (untrusted, trusted) in policies(vec![]).iter().zip(connecting_and_connected) {
+            assert_eq!(batch_bytes(trusted), batch_bytes(untrusted), "{trusted}");
+        }
+    }
+
+    #[test]
+    fn test_gateway_dns_allowed_before_dns_is_rejected() {
+        let gateway = Ipv4Addr::new(10, 64, 0, 1).octets();
+        let [mut connecting, mut connected, _] = policies(vec![]);
+        // Use a custom resolver, so that the gateway is not allowed as a DNS server
+        if let FirewallPolicy::Connected { dns_servers, .. } = &mut connected {
+            *dns_servers = vec![Ipv4Addr::new(192, 0, 2, 53).into()];
+        }
+        for policy in [&connecting, &connected] {
+            assert!(
+                positions(&batch_bytes(policy), &gateway).is_empty(),
+                "{policy}"
+            );
+        }
+
+        for policy in [&mut connecting, &mut connected] {
+            match policy {
+                FirewallPolicy::Connecting {
+                    allow_gateway_dns, ..
+                }
+                | FirewallPolicy::Connected {
+                    allow_gateway_dns, ..
+                } => *allow_gateway_dns = true,
+                FirewallPolicy::Blocked { .. } => unreachable!(),
+            }
+        }
+        for policy in [&connecting, &connected] {
+            let bytes = batch_bytes(policy);
+            // One rule each for the output and forward chains, ahead of the ones rejecting DNS
+            let allowed = positions(&bytes, &gateway);
+            let rejects = positions(&bytes, b"reject\0");
+            assert_eq!(allowed.len(), 2, "{policy}");
+            assert!(
+                allowed.iter().all(|position| *position < rejects[0]),
+                "{policy}"
+            );
+        }
+
+        // Nothing but the ephemeral peer exchange may pass the tunnel before it is established
+        if let FirewallPolicy::Connecting {
+            allowed_tunnel_traffic,
+            ..
+        } = &mut connecting
+        {
+            *allowed_tunnel_traffic = AllowedTunnelTraffic::None;
+        }
+        assert!(positions(&batch_bytes(&connecting), &gateway).is_empty());
+    }
+
+    #[test]
+    fn test_captive_portal_rules_only_allow_gateway_and_probe() {
+        let gateway = Ipv4Addr::new(192, 168, 1, 1);
+        let probe = Ipv4Addr::new(198, 51, 100, 7);
+        let [_, _, mut blocked] = policies(vec![]);
+        let without_portal = batch_bytes(&blocked);
+        if let FirewallPolicy::Blocked { captive_portal, .. } = &mut blocked {
+            *captive_portal = Some(CaptivePortalPolicy {
+                gateways: vec![gateway.into()],
+                probe_hosts: vec![probe.into()],
+                daemon_only: false,
+            });
+        }
+        let bytes = batch_bytes(&blocked);
+
+        // An output and an input rule for DNS over UDP and TCP, HTTP and HTTPS to the gateway,
+        // but only for HTTP and HTTPS to the probe
+        assert_eq!(positions(&bytes, &gateway.octets()).len(), 8);
+        assert_eq!(positions(&bytes, &probe.octets()).len(), 4);
+        assert_eq!(
+            positions(&bytes, b"meta\0").len() - positions(&without_portal, b"meta\0").len(),
+            12 * 2
+        );
+
+        // While only detecting a portal, the output rules are restricted to the daemon
+        let daemon_only_bytes = match &mut blocked {
+            FirewallPolicy::Blocked {
+                captive_portal: Some(captive_portal),
+                ..
+            } => {
+                captive_portal.daemon_only = true;
+                batch_bytes(&blocked)
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            positions(&daemon_only_bytes, b"meta\0").len() - positions(&bytes, b"meta\0").len(),
+            6
+        );
+    }
+
+    #[test]
+    fn test_default_route_interfaces() {
+        let routes = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+ppp0\t00000000\t0100000A\t0003\t0\t0\t0\t00000000\t0\t0\t0
+ppp0\t0000000A\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0
+tailscale0\t00004064\t00000000\t0001\t0\t0\t0\t0000C0FF\t0\t0\t0
+";
+        assert_eq!(default_route_interfaces(routes), vec!["ppp0".to_owned()]);
+    }
+}
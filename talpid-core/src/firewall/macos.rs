@@ -1,12 +1,14 @@
-use super::{FirewallArguments, FirewallPolicy};
+use super::{CaptivePortalPolicy, FirewallArguments, FirewallPolicy};
 use ipnetwork::IpNetwork;
 use pfctl::{DropAction, FilterRuleAction, Uid};
 use std::{
     env,
-    net::{IpAddr, Ipv4Addr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
 };
 use subslice::SubsliceExt;
-use talpid_types::net::{self, AllowedEndpoint, AllowedTunnelTraffic};
+use talpid_types::net::{
+    self, firewall_exception::FirewallException, AllowedEndpoint, AllowedTunnelTraffic,
+};
 
 pub use pfctl::Error;
 
@@ -93,9 +95,16 @@ impl Firewall {
         policy: &FirewallPolicy,
     ) -> Result<Vec<pfctl::RedirectRule>> {
         let redirect_rules = match policy {
+            // DNS must reach the resolver handed out by the network to log in to a captive
+            // portal. Detecting a portal only requires the daemon to reach it.
             FirewallPolicy::Blocked {
-                dns_redirect_port, ..
-            } => {
+                dns_redirect_port,
+                captive_portal,
+                ..
+            } if captive_portal
+                .as_ref()
+                .map_or(true, |portal| portal.daemon_only) =>
+            {
                 vec![pfctl::RedirectRuleBuilder::default()
                     .action(pfctl::RedirectRuleAction::Redirect)
                     .interface("lo0")
@@ -120,10 +129,22 @@ impl Firewall {
                 allow_lan,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                captive_portal,
+                allow_gateway_dns,
+                firewall_exceptions,
             } => {
                 let mut rules = vec![self.get_allow_relay_rule(peer_endpoint)?];
                 rules.push(self.get_allowed_endpoint_rule(allowed_endpoint)?);
 
+                if let Some(captive_portal) = captive_portal {
+                    rules.append(&mut self.get_allow_captive_portal_rules(captive_portal)?);
+                }
+                if let (true, Some(tunnel), AllowedTunnelTraffic::All) =
+                    (*allow_gateway_dns, tunnel, allowed_tunnel_traffic)
+                {
+                    rules.push(self.get_allow_gateway_dns_rule(tunnel)?);
+                }
+
                 // Important to block DNS after allow relay rule (so the relay can operate
                 // over port 53) but before allow LAN (so DNS does not leak to the LAN)
                 rules.append(&mut self.get_block_dns_rules()?);
@@ -137,6 +158,7 @@ impl Firewall {
                 if *allow_lan {
                     rules.append(&mut self.get_allow_lan_rules()?);
                 }
+                rules.append(&mut self.get_firewall_exception_rules(firewall_exceptions)?);
                 Ok(rules)
             }
             FirewallPolicy::Connected {
@@ -144,12 +166,17 @@ impl Firewall {
                 tunnel,
                 allow_lan,
                 dns_servers,
+                allow_gateway_dns,
+                firewall_exceptions,
             } => {
                 let mut rules = vec![];
 
                 for server in dns_servers.iter() {
                     rules.append(&mut self.get_allow_dns_rules_when_connected(tunnel, *server)?);
                 }
+                if *allow_gateway_dns {
+                    rules.push(self.get_allow_gateway_dns_rule(tunnel)?);
+                }
 
                 rules.push(self.get_allow_relay_rule(peer_endpoint)?);
 
@@ -165,12 +192,15 @@ impl Firewall {
                 if *allow_lan {
                     rules.append(&mut self.get_allow_lan_rules()?);
                 }
+                rules.append(&mut self.get_firewall_exception_rules(firewall_exceptions)?);
 
                 Ok(rules)
             }
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
+                firewall_exceptions,
                 ..
             } => {
                 let mut rules = Vec::new();
@@ -178,11 +208,18 @@ impl Firewall {
                     rules.push(self.get_allowed_endpoint_rule(allowed_endpoint)?);
                 }
 
-                if *allow_lan {
+                if let Some(captive_portal) = captive_portal {
+                    rules.append(&mut self.get_allow_captive_portal_rules(captive_portal)?);
+                }
+
+                if *allow_lan || !firewall_exceptions.is_empty() {
                     // Important to block DNS before allow LAN (so DNS does not leak to the LAN)
                     rules.append(&mut self.get_block_dns_rules()?);
+                }
+                if *allow_lan {
                     rules.append(&mut self.get_allow_lan_rules()?);
                 }
+                rules.append(&mut self.get_firewall_exception_rules(firewall_exceptions)?);
 
                 Ok(rules)
             }
@@ -273,6 +310,21 @@ impl Firewall {
         Ok(rules)
     }
 
+    /// Allows the DNS liveness probe, which queries the resolver on the tunnel gateway over UDP.
+    fn get_allow_gateway_dns_rule(
+        &self,
+        tunnel: &crate::tunnel::TunnelMetadata,
+    ) -> Result<pfctl::FilterRule> {
+        Ok(self
+            .create_rule_builder(FilterRuleAction::Pass)
+            .direction(pfctl::Direction::Out)
+            .quick(true)
+            .interface(&tunnel.interface)
+            .proto(pfctl::Proto::Udp)
+            .to(pfctl::Endpoint::new(IpAddr::V4(tunnel.ipv4_gateway), 53))
+            .build()?)
+    }
+
     fn get_allow_relay_rule(
         &self,
         relay_endpoint: &net::AllowedEndpoint,
@@ -316,6 +368,29 @@ impl Firewall {
         rule.build()
     }
 
+    /// Allows outgoing DNS traffic to the gateways, and HTTP and HTTPS traffic to the gateways and
+    /// the captive portal probe, so that captive portals can be detected and used.
+    fn get_allow_captive_portal_rules(
+        &self,
+        policy: &CaptivePortalPolicy,
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = Vec::new();
+        for (host, protocol, port) in policy.allowed_destinations() {
+            let mut builder = self.create_rule_builder(FilterRuleAction::Pass);
+            builder
+                .direction(pfctl::Direction::Out)
+                .quick(true)
+                .proto(as_pfctl_proto(protocol))
+                .to(SocketAddr::new(host, port))
+                .keep_state(pfctl::StatePolicy::Keep);
+            if policy.daemon_only {
+                builder.user(Uid::from(super::ROOT_UID));
+            }
+            rules.push(builder.build()?);
+        }
+        Ok(rules)
+    }
+
     fn get_block_dns_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
         let block_tcp_dns_rule = self
             .create_rule_builder(FilterRuleAction::Drop(DropAction::Return))
@@ -450,6 +525,51 @@ impl Firewall {
         Ok(rules)
     }
 
+    /// Allows connections to be initiated in the direction of each user-defined firewall
+    /// exception. Replies are allowed by the state that the rules keep.
+    fn get_firewall_exception_rules(
+        &self,
+        exceptions: &[FirewallException],
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = vec![];
+        for exception in exceptions {
+            let port = || {
+                exception
+                    .port
+                    .map(pfctl::Port::from)
+                    .unwrap_or(pfctl::Port::Any)
+            };
+            let protocols = match exception.transport_protocols() {
+                Some(protocols) => protocols.into_iter().map(as_pfctl_proto).collect(),
+                None => vec![pfctl::Proto::Any],
+            };
+            for protocol in protocols {
+                let mut rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
+                rule_builder
+                    .quick(true)
+                    .proto(protocol)
+                    .keep_state(pfctl::StatePolicy::Keep);
+                if exception.direction.allows_outbound() {
+                    let allow_out = rule_builder
+                        .direction(pfctl::Direction::Out)
+                        .from(pfctl::Ip::Any)
+                        .to(pfctl::Endpoint::new(exception.network, port()))
+                        .build()?;
+                    rules.push(allow_out);
+                }
+                if exception.direction.allows_inbound() {
+                    let allow_in = rule_builder
+                        .direction(pfctl::Direction::In)
+                        .from(pfctl::Ip::from(exception.network))
+                        .to(port())
+                        .build()?;
+                    rules.push(allow_in);
+                }
+            }
+        }
+        Ok(rules)
+    }
+
     fn get_allow_dhcp_client_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
         let mut dhcp_rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
         dhcp_rule_builder.quick(true).proto(pfctl::Proto::Udp);
@@ -4,7 +4,13 @@ use std::{
     fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
-use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic};
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use talpid_types::net::TransportProtocol;
+use talpid_types::net::{
+    firewall_exception::FirewallException, AllowedEndpoint, AllowedTunnelTraffic,
+};
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -22,6 +28,8 @@ mod imp;
 #[path = "android.rs"]
 mod imp;
 
+#[cfg(target_os = "linux")]
+pub use self::imp::uplink_interfaces;
 pub use self::imp::Error;
 
 /// When "allow local network" is enabled the app will allow traffic to and from these networks.
@@ -74,6 +82,16 @@ static ROUTER_SOLICITATION_OUT_DST_ADDR: Lazy<Ipv6Addr> =
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 static SOLICITED_NODE_MULTICAST: Lazy<Ipv6Network> =
     Lazy::new(|| Ipv6Network::new(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 1, 0xFF00, 0), 104).unwrap());
+/// DNS traffic that is allowed to the gateways of the network while a captive portal is detected
+/// or logged in to.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const CAPTIVE_PORTAL_DNS_PORTS: [(TransportProtocol, u16); 2] =
+    [(TransportProtocol::Udp, 53), (TransportProtocol::Tcp, 53)];
+/// HTTP and HTTPS traffic that is allowed to the gateways of the network and to the captive
+/// portal probe.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const CAPTIVE_PORTAL_WEB_PORTS: [(TransportProtocol, u16); 2] =
+    [(TransportProtocol::Tcp, 80), (TransportProtocol::Tcp, 443)];
 static LOOPBACK_NETS: Lazy<[IpNetwork; 2]> = Lazy::new(|| {
     [
         IpNetwork::V4(ipnetwork::Ipv4Network::new(Ipv4Addr::new(127, 0, 0, 0), 8).unwrap()),
@@ -123,6 +141,17 @@ pub enum FirewallPolicy {
         allowed_endpoint: AllowedEndpoint,
         /// Networks for which to permit in-tunnel traffic.
         allowed_tunnel_traffic: AllowedTunnelTraffic,
+        /// Captive portal traffic that should be allowed, if a portal is being detected or logged
+        /// in to.
+        captive_portal: Option<CaptivePortalPolicy>,
+        /// Flag setting if DNS queries to the tunnel gateway should be allowed once all in-tunnel
+        /// traffic is, so that the DNS liveness probe works.
+        allow_gateway_dns: bool,
+        /// User-defined traffic that should be allowed outside the tunnel.
+        firewall_exceptions: Vec<FirewallException>,
+        /// Interfaces whose traffic should be allowed outside the tunnel.
+        #[cfg(target_os = "linux")]
+        trusted_interfaces: Vec<TrustedInterface>,
     },
 
     /// Allow traffic only to server and over tunnel interface
@@ -136,6 +165,14 @@ pub enum FirewallPolicy {
         /// Servers that are allowed to respond to DNS requests.
         #[cfg(not(target_os = "android"))]
         dns_servers: Vec<IpAddr>,
+        /// Flag setting if DNS queries to the tunnel gateway should be allowed, so that the DNS
+        /// liveness probe works.
+        allow_gateway_dns: bool,
+        /// User-defined traffic that should be allowed outside the tunnel.
+        firewall_exceptions: Vec<FirewallException>,
+        /// Interfaces whose traffic should be allowed outside the tunnel.
+        #[cfg(target_os = "linux")]
+        trusted_interfaces: Vec<TrustedInterface>,
     },
 
     /// Block all network traffic in and out from the computer.
@@ -144,6 +181,14 @@ pub enum FirewallPolicy {
         allow_lan: bool,
         /// Host that should be reachable while in the blocked state.
         allowed_endpoint: Option<AllowedEndpoint>,
+        /// Captive portal traffic that should be allowed, if a portal is being detected or logged
+        /// in to.
+        captive_portal: Option<CaptivePortalPolicy>,
+        /// User-defined traffic that should be allowed outside the tunnel.
+        firewall_exceptions: Vec<FirewallException>,
+        /// Interfaces whose traffic should be allowed outside the tunnel.
+        #[cfg(target_os = "linux")]
+        trusted_interfaces: Vec<TrustedInterface>,
         /// Destination port for DNS traffic redirection. Traffic destined to `127.0.0.1:53` will
         /// be redirected to `127.0.0.1:$dns_redirect_port`.
         #[cfg(target_os = "macos")]
@@ -160,7 +205,11 @@ impl fmt::Display for FirewallPolicy {
                 allow_lan,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
-                ..
+                captive_portal,
+                allow_gateway_dns,
+                firewall_exceptions,
+                #[cfg(target_os = "linux")]
+                trusted_interfaces,
             } => {
                 if let Some(tunnel) = tunnel {
                     write!(
@@ -179,7 +228,7 @@ impl fmt::Display for FirewallPolicy {
                         allowed_tunnel_traffic,
                         if *allow_lan { "Allowing" } else { "Blocking" },
                         allowed_endpoint,
-                    )
+                    )?;
                 } else {
                     write!(
                         f,
@@ -187,46 +236,170 @@ impl fmt::Display for FirewallPolicy {
                         peer_endpoint,
                         if *allow_lan { "Allowing" } else { "Blocking" },
                         allowed_endpoint,
-                    )
+                    )?;
+                }
+                if let Some(captive_portal) = captive_portal {
+                    write!(f, ". {captive_portal}")?;
+                }
+                if *allow_gateway_dns {
+                    write!(f, ". Allowing DNS to the gateway")?;
                 }
+                #[cfg(target_os = "linux")]
+                fmt_trusted_interfaces(f, trusted_interfaces)?;
+                fmt_firewall_exceptions(f, firewall_exceptions)
             }
             FirewallPolicy::Connected {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allow_gateway_dns,
+                firewall_exceptions,
+                #[cfg(target_os = "linux")]
+                trusted_interfaces,
                 ..
-            } => write!(
-                f,
-                "Connected to {} over \"{}\" (ip: {}, v4 gw: {}, v6 gw: {:?}), {} LAN",
-                peer_endpoint,
-                tunnel.interface,
-                tunnel
-                    .ips
-                    .iter()
-                    .map(|ip| ip.to_string())
-                    .collect::<Vec<_>>()
-                    .join(","),
-                tunnel.ipv4_gateway,
-                tunnel.ipv6_gateway,
-                if *allow_lan { "Allowing" } else { "Blocking" }
-            ),
+            } => {
+                write!(
+                    f,
+                    "Connected to {} over \"{}\" (ip: {}, v4 gw: {}, v6 gw: {:?}), {} LAN",
+                    peer_endpoint,
+                    tunnel.interface,
+                    tunnel
+                        .ips
+                        .iter()
+                        .map(|ip| ip.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    tunnel.ipv4_gateway,
+                    tunnel.ipv6_gateway,
+                    if *allow_lan { "Allowing" } else { "Blocking" }
+                )?;
+                if *allow_gateway_dns {
+                    write!(f, ". Allowing DNS to the gateway")?;
+                }
+                #[cfg(target_os = "linux")]
+                fmt_trusted_interfaces(f, trusted_interfaces)?;
+                fmt_firewall_exceptions(f, firewall_exceptions)
+            }
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
+                firewall_exceptions,
+                #[cfg(target_os = "linux")]
+                trusted_interfaces,
                 ..
-            } => write!(
-                f,
-                "Blocked. {} LAN. Allowing endpoint: {}",
-                if *allow_lan { "Allowing" } else { "Blocking" },
-                allowed_endpoint
-                    .as_ref()
-                    .map(|endpoint| -> &dyn std::fmt::Display { endpoint })
-                    .unwrap_or(&"none"),
-            ),
+            } => {
+                write!(
+                    f,
+                    "Blocked. {} LAN. Allowing endpoint: {}",
+                    if *allow_lan { "Allowing" } else { "Blocking" },
+                    allowed_endpoint
+                        .as_ref()
+                        .map(|endpoint| -> &dyn std::fmt::Display { endpoint })
+                        .unwrap_or(&"none"),
+                )?;
+                if let Some(captive_portal) = captive_portal {
+                    write!(f, ". {captive_portal}")?;
+                }
+                #[cfg(target_os = "linux")]
+                fmt_trusted_interfaces(f, trusted_interfaces)?;
+                fmt_firewall_exceptions(f, firewall_exceptions)
+            }
         }
     }
 }
 
+/// Captive portal traffic that a policy allows outside the tunnel. DNS is only allowed to the
+/// gateways of the network, and HTTP and HTTPS to the gateways and the captive portal probe.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CaptivePortalPolicy {
+    /// Default gateways of the network, which usually serve the portal and resolve its name.
+    pub gateways: Vec<IpAddr>,
+    /// Addresses of the host that is probed to find out whether a portal is in the way.
+    pub probe_hosts: Vec<IpAddr>,
+    /// Whether only the daemon may send the traffic, which is the case while a portal is only
+    /// being detected. Anyone may during a login.
+    pub daemon_only: bool,
+}
+
+impl CaptivePortalPolicy {
+    /// Returns every destination address, transport protocol and port that is allowed.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn allowed_destinations(&self) -> impl Iterator<Item = (IpAddr, TransportProtocol, u16)> + '_ {
+        let dns = self.gateways.iter().flat_map(|gateway| {
+            CAPTIVE_PORTAL_DNS_PORTS.map(|(protocol, port)| (*gateway, protocol, port))
+        });
+        let web = self
+            .gateways
+            .iter()
+            .chain(&self.probe_hosts)
+            .flat_map(|host| {
+                CAPTIVE_PORTAL_WEB_PORTS.map(|(protocol, port)| (*host, protocol, port))
+            });
+        dns.chain(web)
+    }
+}
+
+impl fmt::Display for CaptivePortalPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |addresses: &[IpAddr]| {
+            addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        write!(
+            f,
+            "Allowing captive portal traffic{} (gateways: {}, probe: {})",
+            if self.daemon_only {
+                " from the daemon"
+            } else {
+                ""
+            },
+            join(&self.gateways),
+            join(&self.probe_hosts),
+        )
+    }
+}
+
+fn fmt_firewall_exceptions(
+    f: &mut fmt::Formatter<'_>,
+    firewall_exceptions: &[FirewallException],
+) -> fmt::Result {
+    if firewall_exceptions.is_empty() {
+        return Ok(());
+    }
+    write!(
+        f,
+        ". Allowing exceptions: {}",
+        firewall_exceptions
+            .iter()
+            .map(|exception| exception.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn fmt_trusted_interfaces(
+    f: &mut fmt::Formatter<'_>,
+    trusted_interfaces: &[TrustedInterface],
+) -> fmt::Result {
+    if trusted_interfaces.is_empty() {
+        return Ok(());
+    }
+    write!(
+        f,
+        ". Allowing interfaces: {}",
+        trusted_interfaces
+            .iter()
+            .map(|iface| iface.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
 /// Manages network security of the computer/device. Can apply and enforce firewall policies
 /// by manipulating the OS firewall and DNS settings.
 pub struct Firewall {
@@ -239,6 +412,8 @@ pub struct FirewallArguments {
     pub initial_state: InitialFirewallState,
     /// This argument is required for the blocked state to configure the firewall correctly.
     pub allow_lan: bool,
+    /// User-defined traffic to allow in the blocked state.
+    pub firewall_exceptions: Vec<FirewallException>,
     /// Specifies the firewall mark used to identify traffic that is allowed to be excluded from
     /// the tunnel and _leaked_ during blocked states.
     #[cfg(target_os = "linux")]
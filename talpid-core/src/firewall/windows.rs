@@ -99,7 +99,19 @@ impl Firewall {
                 allow_lan,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                captive_portal,
+                allow_gateway_dns,
+                firewall_exceptions,
             } => {
+                if captive_portal.is_some() {
+                    log::warn!("Captive portal traffic is not allowed by the Windows firewall");
+                }
+                if allow_gateway_dns {
+                    log::warn!("DNS probes are blocked by the Windows firewall while connecting");
+                }
+                if !firewall_exceptions.is_empty() {
+                    log::warn!("Firewall exceptions are not applied by the Windows firewall");
+                }
                 let cfg = &WinFwSettings::new(allow_lan);
 
                 self.set_connecting_state(
@@ -114,15 +126,34 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
-                dns_servers,
+                mut dns_servers,
+                allow_gateway_dns,
+                firewall_exceptions,
             } => {
+                if !firewall_exceptions.is_empty() {
+                    log::warn!("Firewall exceptions are not applied by the Windows firewall");
+                }
+                // The gateway is reached through the tunnel, so allowing it as a DNS server only
+                // permits the DNS liveness probe
+                let gateway = IpAddr::V4(tunnel.ipv4_gateway);
+                if allow_gateway_dns && !dns_servers.contains(&gateway) {
+                    dns_servers.push(gateway);
+                }
                 let cfg = &WinFwSettings::new(allow_lan);
                 self.set_connected_state(&peer_endpoint, cfg, &tunnel, &dns_servers)
             }
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
+                firewall_exceptions,
             } => {
+                if captive_portal.is_some() {
+                    log::warn!("Captive portal traffic is not allowed by the Windows firewall");
+                }
+                if !firewall_exceptions.is_empty() {
+                    log::warn!("Firewall exceptions are not applied by the Windows firewall");
+                }
                 let cfg = &WinFwSettings::new(allow_lan);
                 self.set_blocked_state(
                     cfg,
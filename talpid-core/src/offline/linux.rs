@@ -4,7 +4,7 @@ use std::{
     sync::Arc,
 };
 use talpid_routing::{self, RouteManagerHandle};
-use talpid_types::ErrorExt;
+use talpid_types::{net::Connectivity, ErrorExt};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -18,34 +18,34 @@ pub enum Error {
 pub struct MonitorHandle {
     route_manager: RouteManagerHandle,
     fwmark: Option<u32>,
-    _notify_tx: Arc<UnboundedSender<bool>>,
+    _notify_tx: Arc<UnboundedSender<Connectivity>>,
 }
 
-const PUBLIC_INTERNET_ADDRESS_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(193, 138, 218, 78));
-const PUBLIC_INTERNET_ADDRESS_V6: IpAddr =
+pub(crate) const PUBLIC_INTERNET_ADDRESS_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(193, 138, 218, 78));
+pub(crate) const PUBLIC_INTERNET_ADDRESS_V6: IpAddr =
     IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6));
 
 impl MonitorHandle {
-    pub async fn host_is_offline(&self) -> bool {
-        match public_ip_unreachable(&self.route_manager, self.fwmark).await {
-            Ok(is_offline) => is_offline,
+    pub async fn connectivity(&self) -> Connectivity {
+        match check_connectivity(&self.route_manager, self.fwmark).await {
+            Ok(connectivity) => connectivity,
             Err(err) => {
                 log::error!(
                     "Failed to verify offline state: {}. Presuming connectivity",
                     err
                 );
-                false
+                Connectivity::PresumeOnline
             }
         }
     }
 }
 
 pub async fn spawn_monitor(
-    notify_tx: UnboundedSender<bool>,
+    notify_tx: UnboundedSender<Connectivity>,
     route_manager: RouteManagerHandle,
     fwmark: Option<u32>,
 ) -> Result<MonitorHandle> {
-    let mut is_offline = public_ip_unreachable(&route_manager, fwmark).await?;
+    let mut connectivity = check_connectivity(&route_manager, fwmark).await?;
 
     let mut listener = route_manager
         .change_listener()
@@ -64,18 +64,18 @@ pub async fn spawn_monitor(
         while let Some(_event) = listener.next().await {
             match sender.upgrade() {
                 Some(sender) => {
-                    let new_offline_state = public_ip_unreachable(&route_manager, fwmark)
+                    let new_connectivity = check_connectivity(&route_manager, fwmark)
                         .await
                         .unwrap_or_else(|err| {
                             log::error!(
                                 "{}",
                                 err.display_chain_with_msg("Failed to infer offline state")
                             );
-                            false
+                            Connectivity::PresumeOnline
                         });
-                    if new_offline_state != is_offline {
-                        is_offline = new_offline_state;
-                        let _ = sender.unbounded_send(is_offline);
+                    if new_connectivity != connectivity {
+                        connectivity = new_connectivity;
+                        let _ = sender.unbounded_send(connectivity);
                     }
                 }
                 None => return,
@@ -86,15 +86,20 @@ pub async fn spawn_monitor(
     Ok(monitor_handle)
 }
 
-async fn public_ip_unreachable(handle: &RouteManagerHandle, fwmark: Option<u32>) -> Result<bool> {
-    Ok(handle
+/// Returns the address families that have a route to a public address.
+async fn check_connectivity(
+    handle: &RouteManagerHandle,
+    fwmark: Option<u32>,
+) -> Result<Connectivity> {
+    let ipv4 = handle
         .get_destination_route(PUBLIC_INTERNET_ADDRESS_V4, fwmark)
         .await
         .map_err(Error::RouteManagerError)?
-        .is_none()
-        && handle
-            .get_destination_route(PUBLIC_INTERNET_ADDRESS_V6, fwmark)
-            .await
-            .unwrap_or(None)
-            .is_none())
+        .is_some();
+    let ipv6 = handle
+        .get_destination_route(PUBLIC_INTERNET_ADDRESS_V6, fwmark)
+        .await
+        .unwrap_or(None)
+        .is_some();
+    Ok(Connectivity::Status { ipv4, ipv6 })
 }
@@ -18,6 +18,7 @@ use std::{
     time::Duration,
 };
 use talpid_routing::{DefaultRouteEvent, RouteManagerHandle};
+use talpid_types::net::Connectivity;
 
 const SYNTHETIC_OFFLINE_DURATION: Duration = Duration::from_secs(1);
 
@@ -29,7 +30,7 @@ pub enum Error {
 
 pub struct MonitorHandle {
     state: Arc<Mutex<ConnectivityState>>,
-    _notify_tx: Arc<UnboundedSender<bool>>,
+    _notify_tx: Arc<UnboundedSender<Connectivity>>,
 }
 
 #[derive(Clone)]
@@ -42,19 +43,26 @@ impl ConnectivityState {
     fn get_connectivity(&self) -> bool {
         self.v4_connectivity || self.v6_connectivity
     }
+
+    fn as_connectivity(&self) -> Connectivity {
+        Connectivity::Status {
+            ipv4: self.v4_connectivity,
+            ipv6: self.v6_connectivity,
+        }
+    }
 }
 
 impl MonitorHandle {
-    /// Return whether the host is offline
+    /// Return the address families that the host is connected with
     #[allow(clippy::unused_async)]
-    pub async fn host_is_offline(&self) -> bool {
+    pub async fn connectivity(&self) -> Connectivity {
         let state = self.state.lock().unwrap();
-        !state.get_connectivity()
+        state.as_connectivity()
     }
 }
 
 pub async fn spawn_monitor(
-    notify_tx: UnboundedSender<bool>,
+    notify_tx: UnboundedSender<Connectivity>,
     route_manager_handle: RouteManagerHandle,
 ) -> Result<MonitorHandle, Error> {
     let notify_tx = Arc::new(notify_tx);
@@ -103,7 +111,7 @@ pub async fn spawn_monitor(
                         let Some(tx) = weak_notify_tx.upgrade() else {
                             break;
                         };
-                        let _ = tx.unbounded_send(false);
+                        let _ = tx.unbounded_send(state.as_connectivity());
                     }
                 }
 
@@ -142,7 +150,7 @@ pub async fn spawn_monitor(
                         let Some(tx) = weak_notify_tx.upgrade() else {
                             break;
                         };
-                        let _ = tx.unbounded_send(true);
+                        let _ = tx.unbounded_send(state.as_connectivity());
                         log::info!("Connectivity changed: Offline");
                     }
                     if real_state.get_connectivity() {
@@ -4,6 +4,7 @@ use once_cell::sync::Lazy;
 use talpid_routing::RouteManagerHandle;
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+use talpid_types::net::Connectivity;
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -29,20 +30,23 @@ static FORCE_DISABLE_OFFLINE_MONITOR: Lazy<bool> = Lazy::new(|| {
 });
 
 pub use self::imp::Error;
+#[cfg(target_os = "linux")]
+pub(crate) use self::imp::{PUBLIC_INTERNET_ADDRESS_V4, PUBLIC_INTERNET_ADDRESS_V6};
 
 pub struct MonitorHandle(Option<imp::MonitorHandle>);
 
 impl MonitorHandle {
-    pub async fn host_is_offline(&self) -> bool {
+    /// Returns the address families that the host can currently reach the internet over.
+    pub async fn connectivity(&self) -> Connectivity {
         match self.0.as_ref() {
-            Some(monitor) => monitor.host_is_offline().await,
-            None => false,
+            Some(monitor) => monitor.connectivity().await,
+            None => Connectivity::PresumeOnline,
         }
     }
 }
 
 pub async fn spawn_monitor(
-    sender: UnboundedSender<bool>,
+    sender: UnboundedSender<Connectivity>,
     #[cfg(not(target_os = "android"))] route_manager: RouteManagerHandle,
     #[cfg(target_os = "linux")] fwmark: Option<u32>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
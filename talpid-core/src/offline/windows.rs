@@ -8,7 +8,7 @@ use std::{
     sync::{Arc, Weak},
     time::Duration,
 };
-use talpid_types::ErrorExt;
+use talpid_types::{net::Connectivity, ErrorExt};
 use talpid_windows::net::AddressFamily;
 
 #[derive(err_derive::Error, Debug)]
@@ -22,14 +22,14 @@ pub enum Error {
 pub struct BroadcastListener {
     system_state: Arc<Mutex<SystemState>>,
     _callback_handle: CallbackHandle,
-    _notify_tx: Arc<UnboundedSender<bool>>,
+    _notify_tx: Arc<UnboundedSender<Connectivity>>,
 }
 
 unsafe impl Send for BroadcastListener {}
 
 impl BroadcastListener {
     pub async fn start(
-        notify_tx: UnboundedSender<bool>,
+        notify_tx: UnboundedSender<Connectivity>,
         route_manager_handle: RouteManagerHandle,
         mut power_mgmt_rx: PowerManagementListener,
     ) -> Result<Self, Error> {
@@ -139,9 +139,9 @@ impl BroadcastListener {
     }
 
     #[allow(clippy::unused_async)]
-    pub async fn host_is_offline(&self) -> bool {
+    pub async fn connectivity(&self) -> Connectivity {
         let state = self.system_state.lock();
-        state.is_offline_currently()
+        state.connectivity()
     }
 }
 
@@ -156,12 +156,12 @@ struct SystemState {
     v4_connectivity: bool,
     v6_connectivity: bool,
     suspended: bool,
-    notify_tx: Weak<UnboundedSender<bool>>,
+    notify_tx: Weak<UnboundedSender<Connectivity>>,
 }
 
 impl SystemState {
     fn apply_change(&mut self, change: StateChange) {
-        let old_state = self.is_offline_currently();
+        let old_state = self.connectivity();
         match change {
             StateChange::NetworkV4Connectivity(connectivity) => {
                 self.v4_connectivity = connectivity;
@@ -176,9 +176,14 @@ impl SystemState {
             }
         };
 
-        let new_state = self.is_offline_currently();
+        let new_state = self.connectivity();
         if old_state != new_state {
-            log::info!("Connectivity changed: {}", is_offline_str(new_state));
+            if old_state.is_offline() != new_state.is_offline() {
+                log::info!(
+                    "Connectivity changed: {}",
+                    is_offline_str(new_state.is_offline())
+                );
+            }
             if let Some(notify_tx) = self.notify_tx.upgrade() {
                 if let Err(e) = notify_tx.unbounded_send(new_state) {
                     log::error!("Failed to send new offline state to daemon: {}", e);
@@ -187,8 +192,11 @@ impl SystemState {
         }
     }
 
-    fn is_offline_currently(&self) -> bool {
-        (!self.v4_connectivity && !self.v6_connectivity) || self.suspended
+    fn connectivity(&self) -> Connectivity {
+        Connectivity::Status {
+            ipv4: self.v4_connectivity && !self.suspended,
+            ipv6: self.v6_connectivity && !self.suspended,
+        }
     }
 }
 
@@ -204,7 +212,7 @@ fn is_offline_str(offline: bool) -> &'static str {
 pub type MonitorHandle = BroadcastListener;
 
 pub async fn spawn_monitor(
-    sender: UnboundedSender<bool>,
+    sender: UnboundedSender<Connectivity>,
     route_manager_handle: RouteManagerHandle,
 ) -> Result<MonitorHandle, Error> {
     let power_mgmt_rx = crate::window::PowerManagementListener::new();
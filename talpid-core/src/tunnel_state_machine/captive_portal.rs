@@ -0,0 +1,637 @@
+//! Captive portal detection and temporary captive portal logins. While blocking, the daemon looks
+//! for a captive portal and reports it. While a login is active, the firewall lets DNS traffic to
+//! the gateways of the network, and HTTP and HTTPS traffic to the gateways and the probe host,
+//! through in the blocking states, so that the user can log in to the network. The login ends when
+//! a probe can reach the internet, when it times out, or when it is cancelled.
+
+use super::TunnelCommand;
+use crate::firewall::CaptivePortalPolicy;
+use futures::{channel::mpsc, future::BoxFuture};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use talpid_routing::RouteManagerHandle;
+use talpid_types::tunnel::CaptivePortalLogin;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinHandle,
+};
+
+/// Host that serves [`PROBE_CONTENT`] at [`PROBE_PATH`] over plain HTTP.
+const PROBE_HOST: &str = "detectportal.firefox.com";
+const PROBE_PATH: &str = "/success.txt";
+const PROBE_CONTENT: &str = "success";
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+/// How often to probe while only detecting captive portals.
+const DETECTION_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_RESPONSE_SIZE: u64 = 16 * 1024;
+
+/// Default length of a login, if no timeout is given.
+pub const DEFAULT_LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Longest allowed login.
+pub const MAX_LOGIN_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Looks up the default gateways of the network, which are the only hosts that captive portal DNS
+/// requests may be sent to.
+pub type GatewayLookup = Arc<dyn Fn() -> BoxFuture<'static, Vec<IpAddr>> + Send + Sync>;
+
+/// Returns a [`GatewayLookup`] that finds the gateways of the routes to the internet that bypass
+/// the tunnel.
+#[cfg(target_os = "linux")]
+pub fn gateway_lookup(route_manager: RouteManagerHandle, fwmark: u32) -> GatewayLookup {
+    use crate::offline::{PUBLIC_INTERNET_ADDRESS_V4, PUBLIC_INTERNET_ADDRESS_V6};
+
+    Arc::new(move || {
+        let route_manager = route_manager.clone();
+        Box::pin(async move {
+            let mut gateways = vec![];
+            for destination in [PUBLIC_INTERNET_ADDRESS_V4, PUBLIC_INTERNET_ADDRESS_V6] {
+                match route_manager
+                    .get_destination_route(destination, Some(fwmark))
+                    .await
+                {
+                    Ok(route) => {
+                        gateways.extend(route.and_then(|route| route.get_node().get_address()))
+                    }
+                    Err(error) => log::trace!("Failed to look up route to {destination}: {error}"),
+                }
+            }
+            gateways
+        })
+    })
+}
+
+/// Returns a [`GatewayLookup`] that finds the gateways of the default routes.
+#[cfg(target_os = "macos")]
+pub fn gateway_lookup(route_manager: RouteManagerHandle) -> GatewayLookup {
+    Arc::new(move || {
+        let route_manager = route_manager.clone();
+        Box::pin(async move {
+            match route_manager.get_default_routes().await {
+                Ok((v4_route, v6_route)) => [v4_route, v6_route]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|route| route.get_node().get_address())
+                    .collect(),
+                Err(error) => {
+                    log::trace!("Failed to look up default routes: {error}");
+                    vec![]
+                }
+            }
+        })
+    })
+}
+
+/// Returns a [`GatewayLookup`] that finds no gateways, since the firewall does not allow captive
+/// portal traffic on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn gateway_lookup() -> GatewayLookup {
+    Arc::new(|| Box::pin(async { vec![] }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeResult {
+    /// The expected content was received, so nothing is in the way.
+    Open,
+    /// Something other than the probe host answered, most likely a captive portal.
+    Intercepted,
+    /// No answer was received.
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// The user is logging in, so anyone may reach the portal.
+    Login,
+    /// The daemon is only looking for a portal to report.
+    Detection,
+}
+
+/// Event sent from a login or detection task to the tunnel state machine.
+#[derive(Debug)]
+pub struct CaptivePortalEvent {
+    task_id: u32,
+    kind: EventKind,
+}
+
+#[derive(Debug)]
+enum EventKind {
+    Gateways(Vec<IpAddr>),
+    ProbeHosts(Vec<IpAddr>),
+    Probed(ProbeResult),
+    Expired,
+}
+
+struct Task {
+    id: u32,
+    mode: Mode,
+    handle: JoinHandle<()>,
+    gateways: Vec<IpAddr>,
+    probe_hosts: Vec<IpAddr>,
+}
+
+/// Keeps track of the current captive portal login or detection, if any.
+pub(super) struct CaptivePortalLogins {
+    runtime: tokio::runtime::Handle,
+    command_tx: Weak<mpsc::UnboundedSender<TunnelCommand>>,
+    gateway_lookup: GatewayLookup,
+    next_id: u32,
+    task: Option<Task>,
+    status: Option<CaptivePortalLogin>,
+}
+
+impl CaptivePortalLogins {
+    pub fn new(
+        runtime: tokio::runtime::Handle,
+        command_tx: Weak<mpsc::UnboundedSender<TunnelCommand>>,
+        gateway_lookup: GatewayLookup,
+    ) -> Self {
+        Self {
+            runtime,
+            command_tx,
+            gateway_lookup,
+            next_id: 0,
+            task: None,
+            status: None,
+        }
+    }
+
+    /// Returns whether a login is active.
+    pub fn is_active(&self) -> bool {
+        matches!(&self.task, Some(task) if task.mode == Mode::Login)
+    }
+
+    /// Returns the captive portal traffic that the firewall should currently allow.
+    pub fn firewall_policy(&self) -> Option<CaptivePortalPolicy> {
+        let task = self.task.as_ref()?;
+        if task.gateways.is_empty() {
+            return None;
+        }
+        Some(CaptivePortalPolicy {
+            gateways: task.gateways.clone(),
+            probe_hosts: task.probe_hosts.clone(),
+            daemon_only: task.mode == Mode::Detection,
+        })
+    }
+
+    /// Starts a new login, replacing any ongoing login or detection.
+    pub fn start(&mut self, timeout: Duration) {
+        let timeout = timeout.min(MAX_LOGIN_TIMEOUT);
+        log::info!(
+            "Allowing captive portal traffic for {} seconds",
+            timeout.as_secs()
+        );
+
+        self.spawn_task(Mode::Login, Some(timeout));
+        self.status = Some(CaptivePortalLogin::Active {
+            expires: SystemTime::now() + timeout,
+            portal_detected: false,
+        });
+    }
+
+    /// Starts or stops looking for a captive portal. Does nothing while a login is active, and on
+    /// platforms whose firewall cannot allow captive portal traffic.
+    pub fn set_detecting(&mut self, detecting: bool) {
+        if !cfg!(any(target_os = "linux", target_os = "macos")) {
+            return;
+        }
+        match &self.task {
+            Some(task) if task.mode == Mode::Login => (),
+            Some(_) if detecting => (),
+            Some(_) => {
+                self.abort_task();
+                if self.status == Some(CaptivePortalLogin::Detected) {
+                    self.status = None;
+                }
+            }
+            None if detecting => self.spawn_task(Mode::Detection, None),
+            None => (),
+        }
+    }
+
+    /// Cancels the ongoing login. Returns whether there was one.
+    pub fn cancel(&mut self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        log::info!("Cancelling captive portal login");
+        self.end(CaptivePortalLogin::Cancelled);
+        true
+    }
+
+    /// Updates the login or detection with an event from its task. Returns whether the status or
+    /// the firewall policy changed.
+    pub fn handle_event(&mut self, event: CaptivePortalEvent) -> bool {
+        let task = match &mut self.task {
+            Some(task) if task.id == event.task_id => task,
+            // The event belongs to a task that has already ended
+            _ => return false,
+        };
+
+        match (event.kind, task.mode) {
+            (EventKind::Gateways(gateways), _) => {
+                let changed = task.gateways != gateways;
+                task.gateways = gateways;
+                changed
+            }
+            (EventKind::ProbeHosts(probe_hosts), _) => {
+                let changed = task.probe_hosts != probe_hosts;
+                task.probe_hosts = probe_hosts;
+                changed
+            }
+            (EventKind::Probed(ProbeResult::Open), Mode::Login) => {
+                log::info!("Captive portal probe succeeded, ending login");
+                self.end(CaptivePortalLogin::Succeeded);
+                true
+            }
+            (EventKind::Probed(result), Mode::Login) => {
+                let detected = result == ProbeResult::Intercepted;
+                match &mut self.status {
+                    Some(CaptivePortalLogin::Active {
+                        portal_detected, ..
+                    }) if *portal_detected != detected => {
+                        if detected {
+                            log::debug!("Captive portal probe was intercepted");
+                        }
+                        *portal_detected = detected;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            (EventKind::Probed(result), Mode::Detection) => {
+                let detected = match result {
+                    ProbeResult::Intercepted => true,
+                    ProbeResult::Open => false,
+                    ProbeResult::Failed => return false,
+                };
+                let was_detected = self.status == Some(CaptivePortalLogin::Detected);
+                if detected == was_detected {
+                    return false;
+                }
+                if detected {
+                    log::info!("Detected a captive portal");
+                    self.status = Some(CaptivePortalLogin::Detected);
+                } else {
+                    log::info!("Captive portal is no longer in the way");
+                    self.status = None;
+                }
+                true
+            }
+            (EventKind::Expired, _) => {
+                log::info!("Captive portal login timed out");
+                self.end(CaptivePortalLogin::Expired);
+                true
+            }
+        }
+    }
+
+    pub fn status(&self) -> Option<&CaptivePortalLogin> {
+        self.status.as_ref()
+    }
+
+    /// Returns the status to report in a state transition. A status for a login that has ended is
+    /// only reported once, while a detected portal is reported until it is gone.
+    pub fn take_status(&mut self) -> Option<CaptivePortalLogin> {
+        match &self.status {
+            Some(status @ (CaptivePortalLogin::Active { .. } | CaptivePortalLogin::Detected)) => {
+                Some(status.clone())
+            }
+            _ => self.status.take(),
+        }
+    }
+
+    fn spawn_task(&mut self, mode: Mode, timeout: Option<Duration>) {
+        self.abort_task();
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let handle = self.runtime.spawn(run_task(
+            id,
+            mode,
+            timeout,
+            self.gateway_lookup.clone(),
+            self.command_tx.clone(),
+        ));
+        self.task = Some(Task {
+            id,
+            mode,
+            handle,
+            gateways: vec![],
+            probe_hosts: vec![],
+        });
+    }
+
+    fn end(&mut self, status: CaptivePortalLogin) {
+        self.abort_task();
+        self.status = Some(status);
+    }
+
+    fn abort_task(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.handle.abort();
+        }
+    }
+}
+
+impl Drop for CaptivePortalLogins {
+    fn drop(&mut self) {
+        self.abort_task();
+    }
+}
+
+/// Looks up the gateways and the probe host, and probes the connectivity regularly. A login ends
+/// when the probe gets through or when it times out, while a detection runs until it is aborted.
+async fn run_task(
+    task_id: u32,
+    mode: Mode,
+    timeout: Option<Duration>,
+    gateway_lookup: GatewayLookup,
+    command_tx: Weak<mpsc::UnboundedSender<TunnelCommand>>,
+) {
+    let send = |kind| match command_tx.upgrade() {
+        Some(tx) => tx
+            .unbounded_send(TunnelCommand::CaptivePortalEvent(CaptivePortalEvent {
+                task_id,
+                kind,
+            }))
+            .is_ok(),
+        None => false,
+    };
+
+    let probe_loop = async {
+        loop {
+            let gateways = gateway_lookup().await;
+            if !send(EventKind::Gateways(gateways.clone())) {
+                return;
+            }
+            // The firewall only allows DNS requests to the gateways once it has been updated, so
+            // failures are retried soon
+            let probe_hosts = match resolve_probe_host(&gateways).await {
+                Ok(probe_hosts) => probe_hosts,
+                Err(error) => {
+                    log::trace!("Failed to resolve captive portal probe host: {error}");
+                    tokio::time::sleep(PROBE_INTERVAL).await;
+                    continue;
+                }
+            };
+            if !send(EventKind::ProbeHosts(probe_hosts.clone())) {
+                return;
+            }
+
+            let result = probe(&probe_hosts).await;
+            if !send(EventKind::Probed(result)) {
+                return;
+            }
+            match (result, mode) {
+                (ProbeResult::Open, Mode::Login) => return,
+                (ProbeResult::Failed, _) | (_, Mode::Login) => {
+                    tokio::time::sleep(PROBE_INTERVAL).await
+                }
+                (_, Mode::Detection) => tokio::time::sleep(DETECTION_INTERVAL).await,
+            }
+        }
+    };
+
+    match timeout {
+        Some(timeout) => {
+            if tokio::time::timeout(timeout, probe_loop).await.is_err() {
+                send(EventKind::Expired);
+            }
+        }
+        None => probe_loop.await,
+    }
+}
+
+/// Resolves the probe host using the gateways, which usually resolve names for the network.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+async fn resolve_probe_host(gateways: &[IpAddr]) -> io::Result<Vec<IpAddr>> {
+    use trust_dns_resolver::{
+        config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+        TokioAsyncResolver,
+    };
+
+    if gateways.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no gateway"));
+    }
+    let config = ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(gateways, 53, true),
+    );
+    let resolver = TokioAsyncResolver::tokio(config, ResolverOpts::default());
+    let lookup = tokio::time::timeout(PROBE_TIMEOUT, resolver.lookup_ip(PROBE_HOST))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "lookup timed out"))?
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    Ok(lookup.iter().collect())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+async fn resolve_probe_host(_gateways: &[IpAddr]) -> io::Result<Vec<IpAddr>> {
+    Ok(tokio::net::lookup_host((PROBE_HOST, 0))
+        .await?
+        .map(|addr| addr.ip())
+        .collect())
+}
+
+async fn probe(probe_hosts: &[IpAddr]) -> ProbeResult {
+    match tokio::time::timeout(PROBE_TIMEOUT, fetch_probe(probe_hosts)).await {
+        Ok(Ok(response)) if is_expected_response(&response) => ProbeResult::Open,
+        Ok(Ok(_)) => ProbeResult::Intercepted,
+        Ok(Err(error)) => {
+            log::trace!("Captive portal probe failed: {error}");
+            ProbeResult::Failed
+        }
+        Err(_) => {
+            log::trace!("Captive portal probe timed out");
+            ProbeResult::Failed
+        }
+    }
+}
+
+async fn fetch_probe(probe_hosts: &[IpAddr]) -> io::Result<Vec<u8>> {
+    let addrs: Vec<SocketAddr> = probe_hosts
+        .iter()
+        .map(|host| SocketAddr::new(*host, 80))
+        .collect();
+    let mut stream = TcpStream::connect(&addrs[..]).await?;
+
+    let request = format!(
+        "GET {PROBE_PATH} HTTP/1.0\r\nHost: {PROBE_HOST}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = vec![];
+    stream
+        .take(MAX_RESPONSE_SIZE)
+        .read_to_end(&mut response)
+        .await?;
+    Ok(response)
+}
+
+/// Returns whether `response` is a successful response containing the expected content. Portals
+/// typically answer with a redirect or with their own login page instead.
+fn is_expected_response(response: &[u8]) -> bool {
+    let response = String::from_utf8_lossy(response);
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        return false;
+    };
+    let status_ok = head
+        .lines()
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        == Some("200");
+    status_ok && body.trim() == PROBE_CONTENT
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+
+    /// Returns a gateway lookup that never finishes, so that the tasks never reach the network.
+    fn pending_gateway_lookup() -> GatewayLookup {
+        Arc::new(|| Box::pin(futures::future::pending()))
+    }
+
+    fn new_logins() -> (
+        CaptivePortalLogins,
+        Arc<mpsc::UnboundedSender<TunnelCommand>>,
+        mpsc::UnboundedReceiver<TunnelCommand>,
+    ) {
+        let (command_tx, command_rx) = mpsc::unbounded();
+        let command_tx = Arc::new(command_tx);
+        let logins = CaptivePortalLogins::new(
+            tokio::runtime::Handle::current(),
+            Arc::downgrade(&command_tx),
+            pending_gateway_lookup(),
+        );
+        (logins, command_tx, command_rx)
+    }
+
+    async fn next_event(
+        command_rx: &mut mpsc::UnboundedReceiver<TunnelCommand>,
+    ) -> CaptivePortalEvent {
+        match command_rx.next().await {
+            Some(TunnelCommand::CaptivePortalEvent(event)) => event,
+            _ => panic!("expected a captive portal event"),
+        }
+    }
+
+    fn gateways_event(logins: &CaptivePortalLogins, gateways: Vec<IpAddr>) -> CaptivePortalEvent {
+        CaptivePortalEvent {
+            task_id: logins.task.as_ref().unwrap().id,
+            kind: EventKind::Gateways(gateways),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_login_timeout() {
+        let (mut logins, _command_tx, mut command_rx) = new_logins();
+        let timeout = Duration::from_secs(60);
+        let started = tokio::time::Instant::now();
+        logins.start(timeout);
+        assert!(logins.is_active());
+
+        let event = next_event(&mut command_rx).await;
+        assert_eq!(tokio::time::Instant::now() - started, timeout);
+        assert!(matches!(event.kind, EventKind::Expired));
+        assert!(logins.handle_event(event));
+
+        assert_eq!(logins.take_status(), Some(CaptivePortalLogin::Expired));
+        assert_eq!(logins.take_status(), None);
+        assert!(!logins.is_active());
+        assert_eq!(logins.firewall_policy(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_login_cancel() {
+        let (mut logins, _command_tx, mut command_rx) = new_logins();
+        logins.start(DEFAULT_LOGIN_TIMEOUT);
+
+        let gateway = IpAddr::from([192, 168, 1, 1]);
+        let event = gateways_event(&logins, vec![gateway]);
+        let task_id = event.task_id;
+        assert!(logins.handle_event(event));
+        assert_eq!(
+            logins.firewall_policy(),
+            Some(CaptivePortalPolicy {
+                gateways: vec![gateway],
+                probe_hosts: vec![],
+                daemon_only: false,
+            })
+        );
+
+        assert!(logins.cancel());
+        assert!(!logins.cancel());
+        assert_eq!(logins.status(), Some(&CaptivePortalLogin::Cancelled));
+        assert!(!logins.is_active());
+        assert_eq!(logins.firewall_policy(), None);
+
+        // Events of the cancelled login are ignored, and the login does not expire
+        assert!(!logins.handle_event(CaptivePortalEvent {
+            task_id,
+            kind: EventKind::Expired,
+        }));
+        tokio::time::sleep(MAX_LOGIN_TIMEOUT).await;
+        assert!(command_rx.try_next().is_err());
+        assert_eq!(logins.status(), Some(&CaptivePortalLogin::Cancelled));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_detection_only_reports_portal() {
+        let (mut logins, _command_tx, _command_rx) = new_logins();
+        logins.set_detecting(true);
+        assert!(!logins.is_active());
+
+        let gateway = IpAddr::from([192, 168, 1, 1]);
+        assert!(logins.handle_event(gateways_event(&logins, vec![gateway])));
+        assert_eq!(
+            logins.firewall_policy().map(|policy| policy.daemon_only),
+            Some(true)
+        );
+
+        let task_id = logins.task.as_ref().unwrap().id;
+        let probed = |result| CaptivePortalEvent {
+            task_id,
+            kind: EventKind::Probed(result),
+        };
+        assert!(logins.handle_event(probed(ProbeResult::Intercepted)));
+        assert_eq!(logins.take_status(), Some(CaptivePortalLogin::Detected));
+        assert_eq!(logins.take_status(), Some(CaptivePortalLogin::Detected));
+        assert!(!logins.is_active());
+
+        // A failed probe does not tell whether the portal is gone
+        assert!(!logins.handle_event(probed(ProbeResult::Failed)));
+        assert!(logins.handle_event(probed(ProbeResult::Open)));
+        assert_eq!(logins.status(), None);
+
+        // Leaving the blocking states stops the detection
+        logins.set_detecting(false);
+        assert_eq!(logins.firewall_policy(), None);
+    }
+
+    #[test]
+    fn test_probe_response() {
+        assert!(is_expected_response(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nsuccess\n"
+        ));
+        assert!(!is_expected_response(
+            b"HTTP/1.1 302 Found\r\nLocation: http://portal.example/login\r\n\r\n"
+        ));
+        assert!(!is_expected_response(
+            b"HTTP/1.1 200 OK\r\n\r\n<html>Log in to continue</html>"
+        ));
+        assert!(!is_expected_response(b"HTTP/1.1 200 OK\r\n"));
+    }
+}
@@ -57,6 +57,7 @@ impl ConnectedState {
         let tunnel_interface = Some(connected_state.metadata.interface.clone());
         let tunnel_endpoint = talpid_types::net::TunnelEndpoint {
             tunnel_interface,
+            discovered_mtu: connected_state.metadata.discovered_mtu,
             ..connected_state.tunnel_parameters.get_tunnel_endpoint()
         };
 
@@ -158,6 +159,10 @@ impl ConnectedState {
             allow_lan: shared_values.allow_lan,
             #[cfg(not(target_os = "android"))]
             dns_servers: self.get_dns_servers(shared_values),
+            allow_gateway_dns: self.tunnel_parameters.probes_gateway_dns(),
+            firewall_exceptions: shared_values.firewall_exceptions.clone(),
+            #[cfg(target_os = "linux")]
+            trusted_interfaces: shared_values.trusted_interfaces.clone(),
         }
     }
 
@@ -251,6 +256,38 @@ impl ConnectedState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                let consequence = if shared_values.firewall_exceptions != firewall_exceptions {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                let consequence = if shared_values.set_trusted_interfaces(trusted_interfaces) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 shared_values.allowed_endpoint = endpoint;
                 let _ = tx.send(());
@@ -309,6 +346,17 @@ impl ConnectedState {
                     SameState(self)
                 }
             }
+            Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                // Captive portal traffic only needs to be allowed in the blocking states, so the
+                // login is reported once one of them is entered.
+                shared_values.set_captive_portal_login(timeout);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                shared_values.captive_portal.handle_event(event);
+                SameState(self)
+            }
             Some(TunnelCommand::Connect) => {
                 self.disconnect(shared_values, AfterDisconnect::Reconnect(0))
             }
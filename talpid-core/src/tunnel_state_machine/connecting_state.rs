@@ -22,7 +22,7 @@ use talpid_routing::RouteManager;
 use talpid_tunnel::{tun_provider::TunProvider, TunnelArgs, TunnelEvent, TunnelMetadata};
 use talpid_types::{
     net::{AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, TunnelParameters},
-    tunnel::{ErrorStateCause, FirewallPolicyError},
+    tunnel::{CaptivePortalLogin, ErrorStateCause, FirewallPolicyError},
     ErrorExt,
 };
 
@@ -87,6 +87,8 @@ impl ConnectingState {
                     return ErrorState::enter(shared_values, ErrorStateCause::SplitTunnelError);
                 }
 
+                // Captive portals are only looked for while blocking
+                shared_values.captive_portal.set_detecting(false);
                 if let Err(error) = Self::set_firewall_policy(
                     shared_values,
                     &tunnel_parameters,
@@ -124,7 +126,10 @@ impl ConnectingState {
                     let params = connecting_state.tunnel_parameters.clone();
                     (
                         Box::new(connecting_state),
-                        TunnelStateTransition::Connecting(params.get_tunnel_endpoint()),
+                        TunnelStateTransition::Connecting(
+                            params.get_tunnel_endpoint(),
+                            shared_values.captive_portal.take_status(),
+                        ),
                     )
                 }
             }
@@ -164,6 +169,11 @@ impl ConnectingState {
             allow_lan: shared_values.allow_lan,
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
             allowed_tunnel_traffic,
+            captive_portal: shared_values.captive_portal.firewall_policy(),
+            allow_gateway_dns: params.probes_gateway_dns(),
+            firewall_exceptions: shared_values.firewall_exceptions.clone(),
+            #[cfg(target_os = "linux")]
+            trusted_interfaces: shared_values.trusted_interfaces.clone(),
         };
         shared_values
             .firewall
@@ -406,6 +416,36 @@ impl ConnectingState {
         }
     }
 
+    /// Applies a change to the captive portal login. The tunnel is restarted once the login has
+    /// succeeded, since the current attempt is likely to have been blocked by the portal.
+    fn update_captive_portal(
+        self: Box<Self>,
+        shared_values: &mut SharedTunnelStateValues,
+    ) -> EventConsequence {
+        if matches!(
+            shared_values.captive_portal.status(),
+            Some(CaptivePortalLogin::Succeeded)
+        ) {
+            return self.disconnect(shared_values, AfterDisconnect::Reconnect(0));
+        }
+        if let Err(error) = Self::set_firewall_policy(
+            shared_values,
+            &self.tunnel_parameters,
+            &self.tunnel_metadata,
+            self.allowed_tunnel_traffic.clone(),
+        ) {
+            return self.disconnect(
+                shared_values,
+                AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+            );
+        }
+        let transition = TunnelStateTransition::Connecting(
+            self.tunnel_parameters.get_tunnel_endpoint(),
+            shared_values.captive_portal.take_status(),
+        );
+        EventConsequence::NewState((self as Box<dyn TunnelState>, transition))
+    }
+
     fn handle_commands(
         self: Box<Self>,
         command: Option<TunnelCommand>,
@@ -423,6 +463,44 @@ impl ConnectingState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                if shared_values.firewall_exceptions != firewall_exceptions {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    if let Err(error) = Self::set_firewall_policy(
+                        shared_values,
+                        &self.tunnel_parameters,
+                        &self.tunnel_metadata,
+                        self.allowed_tunnel_traffic.clone(),
+                    ) {
+                        let _ = complete_tx.send(());
+                        return self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        );
+                    }
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                if shared_values.set_trusted_interfaces(trusted_interfaces) {
+                    if let Err(error) = Self::set_firewall_policy(
+                        shared_values,
+                        &self.tunnel_parameters,
+                        &self.tunnel_metadata,
+                        self.allowed_tunnel_traffic.clone(),
+                    ) {
+                        let _ = complete_tx.send(());
+                        return self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        );
+                    }
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -468,6 +546,22 @@ impl ConnectingState {
                     SameState(self)
                 }
             }
+            Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                let consequence = if shared_values.set_captive_portal_login(timeout) {
+                    self.update_captive_portal(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                if shared_values.captive_portal.handle_event(event) {
+                    self.update_captive_portal(shared_values)
+                } else {
+                    SameState(self)
+                }
+            }
             Some(TunnelCommand::Connect) => {
                 self.disconnect(shared_values, AfterDisconnect::Reconnect(0))
             }
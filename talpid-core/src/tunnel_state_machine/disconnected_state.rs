@@ -20,8 +20,13 @@ impl DisconnectedState {
         shared_values: &mut SharedTunnelStateValues,
         should_reset_firewall: bool,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
+        shared_values
+            .captive_portal
+            .set_detecting(shared_values.block_when_disconnected);
+
+        // The captive portal must be reached using the DNS servers handed out by the network
         #[cfg(target_os = "macos")]
-        if shared_values.block_when_disconnected {
+        if shared_values.block_when_disconnected && !shared_values.captive_portal.is_active() {
             if let Err(err) = Self::setup_local_dns_config(shared_values) {
                 log::error!(
                     "{}",
@@ -54,6 +59,7 @@ impl DisconnectedState {
                 // Being disconnected and having lockdown mode enabled implies that your internet
                 // access is locked down
                 locked_down: shared_values.block_when_disconnected,
+                captive_portal: shared_values.captive_portal.take_status(),
             },
         )
     }
@@ -66,6 +72,10 @@ impl DisconnectedState {
             let policy = FirewallPolicy::Blocked {
                 allow_lan: shared_values.allow_lan,
                 allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
+                captive_portal: shared_values.captive_portal.firewall_policy(),
+                firewall_exceptions: shared_values.firewall_exceptions.clone(),
+                #[cfg(target_os = "linux")]
+                trusted_interfaces: shared_values.trusted_interfaces.clone(),
                 #[cfg(target_os = "macos")]
                 dns_redirect_port: shared_values.filtering_resolver.listening_port(),
             };
@@ -150,6 +160,22 @@ impl TunnelState for DisconnectedState {
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                if shared_values.firewall_exceptions != firewall_exceptions {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                if shared_values.set_trusted_interfaces(trusted_interfaces) {
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -175,6 +201,9 @@ impl TunnelState for DisconnectedState {
                     // The logic for updating the firewall in `DisconnectedState::enter` is
                     // identical but it does not enter the error state if setting the local DNS
                     // fails.
+                    shared_values
+                        .captive_portal
+                        .set_detecting(block_when_disconnected);
                     Self::set_firewall_policy(shared_values, true);
                     #[cfg(windows)]
                     Self::register_split_tunnel_addresses(shared_values, true);
@@ -204,6 +233,22 @@ impl TunnelState for DisconnectedState {
                 shared_values.is_offline = is_offline;
                 SameState(self)
             }
+            Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                let consequence = if shared_values.set_captive_portal_login(timeout) {
+                    NewState(Self::enter(shared_values, false))
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                if shared_values.captive_portal.handle_event(event) {
+                    NewState(Self::enter(shared_values, false))
+                } else {
+                    SameState(self)
+                }
+            }
             Some(TunnelCommand::Connect) => NewState(ConnectingState::enter(shared_values, 0)),
             Some(TunnelCommand::Block(reason)) => {
                 Self::reset_dns(shared_values);
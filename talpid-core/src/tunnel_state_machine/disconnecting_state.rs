@@ -45,6 +45,17 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                    shared_values.set_trusted_interfaces(trusted_interfaces);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
                     let _ = tx.send(());
@@ -67,6 +78,15 @@ impl DisconnectingState {
                     shared_values.is_offline = is_offline;
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                    shared_values.set_captive_portal_login(timeout);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                    shared_values.captive_portal.handle_event(event);
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::Connect) => AfterDisconnect::Reconnect(0),
                 Some(TunnelCommand::Disconnect) | None => AfterDisconnect::Nothing,
                 Some(TunnelCommand::Block(reason)) => AfterDisconnect::Block(reason),
@@ -87,6 +107,17 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Block(reason)
                 }
+                Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                    shared_values.set_trusted_interfaces(trusted_interfaces);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
                     let _ = tx.send(());
@@ -113,6 +144,15 @@ impl DisconnectingState {
                         AfterDisconnect::Block(reason)
                     }
                 }
+                Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                    shared_values.set_captive_portal_login(timeout);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                    shared_values.captive_portal.handle_event(event);
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::Connect) => AfterDisconnect::Reconnect(0),
                 Some(TunnelCommand::Disconnect) => AfterDisconnect::Nothing,
                 Some(TunnelCommand::Block(new_reason)) => AfterDisconnect::Block(new_reason),
@@ -134,6 +174,17 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                    shared_values.set_trusted_interfaces(trusted_interfaces);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
                     let _ = tx.send(());
@@ -160,6 +211,15 @@ impl DisconnectingState {
                         AfterDisconnect::Reconnect(retry_attempt)
                     }
                 }
+                Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                    shared_values.set_captive_portal_login(timeout);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                    shared_values.captive_portal.handle_event(event);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::Connect) => AfterDisconnect::Reconnect(retry_attempt),
                 Some(TunnelCommand::Disconnect) | None => AfterDisconnect::Nothing,
                 Some(TunnelCommand::Block(reason)) => AfterDisconnect::Block(reason),
@@ -21,6 +21,8 @@ impl ErrorState {
         shared_values: &mut SharedTunnelStateValues,
         block_reason: ErrorStateCause,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
+        shared_values.captive_portal.set_detecting(true);
+
         #[cfg(windows)]
         if let Err(error) = shared_values.split_tunnel.set_tunnel_addresses(None) {
             log::error!(
@@ -31,8 +33,11 @@ impl ErrorState {
             );
         }
 
+        // The captive portal must be reached using the DNS servers handed out by the network
         #[cfg(target_os = "macos")]
-        if !block_reason.prevents_filtering_resolver() {
+        if shared_values.captive_portal.is_active() {
+            Self::reset_dns(shared_values);
+        } else if !block_reason.prevents_filtering_resolver() {
             if let Err(err) = shared_values
                 .dns_monitor
                 .set("lo", &[Ipv4Addr::LOCALHOST.into()])
@@ -60,10 +65,10 @@ impl ErrorState {
             Box::new(ErrorState {
                 block_reason: block_reason.clone(),
             }),
-            TunnelStateTransition::Error(talpid_tunnel::ErrorState::new(
-                block_reason,
-                block_failure,
-            )),
+            TunnelStateTransition::Error(
+                talpid_tunnel::ErrorState::new(block_reason, block_failure)
+                    .with_captive_portal(shared_values.captive_portal.take_status()),
+            ),
         )
     }
 
@@ -73,6 +78,10 @@ impl ErrorState {
         let policy = FirewallPolicy::Blocked {
             allow_lan: shared_values.allow_lan,
             allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
+            captive_portal: shared_values.captive_portal.firewall_policy(),
+            firewall_exceptions: shared_values.firewall_exceptions.clone(),
+            #[cfg(target_os = "linux")]
+            trusted_interfaces: shared_values.trusted_interfaces.clone(),
             #[cfg(target_os = "macos")]
             dns_redirect_port: shared_values.filtering_resolver.listening_port(),
         };
@@ -149,6 +158,22 @@ impl TunnelState for ErrorState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            Some(TunnelCommand::FirewallExceptions(firewall_exceptions, complete_tx)) => {
+                if shared_values.firewall_exceptions != firewall_exceptions {
+                    shared_values.firewall_exceptions = firewall_exceptions;
+                    let _ = Self::set_firewall_policy(shared_values);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::TrustedInterfaces(trusted_interfaces, complete_tx)) => {
+                if shared_values.set_trusted_interfaces(trusted_interfaces) {
+                    let _ = Self::set_firewall_policy(shared_values);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -190,6 +215,22 @@ impl TunnelState for ErrorState {
                     SameState(self)
                 }
             }
+            Some(TunnelCommand::CaptivePortalLogin(timeout, complete_tx)) => {
+                let consequence = if shared_values.set_captive_portal_login(timeout) {
+                    NewState(Self::enter(shared_values, self.block_reason.clone()))
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            Some(TunnelCommand::CaptivePortalEvent(event)) => {
+                if shared_values.captive_portal.handle_event(event) {
+                    NewState(Self::enter(shared_values, self.block_reason.clone()))
+                } else {
+                    SameState(self)
+                }
+            }
             Some(TunnelCommand::Connect) => {
                 Self::reset_dns(shared_values);
 
@@ -1,10 +1,13 @@
+mod captive_portal;
 mod connected_state;
 mod connecting_state;
 mod disconnected_state;
 mod disconnecting_state;
 mod error_state;
 
+pub use self::captive_portal::{CaptivePortalEvent, DEFAULT_LOGIN_TIMEOUT};
 use self::{
+    captive_portal::CaptivePortalLogins,
     connected_state::ConnectedState,
     connecting_state::ConnectingState,
     disconnected_state::DisconnectedState,
@@ -40,9 +43,13 @@ use std::{
     time::Duration,
 };
 #[cfg(target_os = "android")]
-use talpid_types::{android::AndroidContext, ErrorExt};
+use talpid_types::android::AndroidContext;
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use talpid_types::ErrorExt;
 use talpid_types::{
-    net::{AllowedEndpoint, TunnelParameters},
+    net::{firewall_exception::FirewallException, AllowedEndpoint, Connectivity, TunnelParameters},
     tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition},
 };
 
@@ -90,6 +97,12 @@ pub enum Error {
 pub struct InitialTunnelState {
     /// Whether to allow LAN traffic when not in the (non-blocking) disconnected state.
     pub allow_lan: bool,
+    /// User-defined traffic to allow outside the tunnel when not in the (non-blocking)
+    /// disconnected state.
+    pub firewall_exceptions: Vec<FirewallException>,
+    /// Interfaces whose traffic and routes should not go through the tunnel.
+    #[cfg(target_os = "linux")]
+    pub trusted_interfaces: Vec<TrustedInterface>,
     /// Block traffic unless connected to the VPN.
     pub block_when_disconnected: bool,
     /// DNS servers to use. If `None`, the tunnel gateway is used.
@@ -122,7 +135,7 @@ pub async fn spawn(
     log_dir: Option<PathBuf>,
     resource_dir: PathBuf,
     state_change_listener: impl Sender<TunnelStateTransition> + Send + 'static,
-    offline_state_listener: mpsc::UnboundedSender<bool>,
+    offline_state_listener: mpsc::UnboundedSender<Connectivity>,
     #[cfg(target_os = "windows")] volume_update_rx: mpsc::UnboundedReceiver<()>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
     #[cfg(target_os = "linux")] linux_ids: LinuxNetworkingIdentifiers,
@@ -190,6 +203,11 @@ pub async fn spawn(
 pub enum TunnelCommand {
     /// Enable or disable LAN access in the firewall.
     AllowLan(bool, oneshot::Sender<()>),
+    /// Set the user-defined traffic that the firewall allows outside the tunnel.
+    FirewallExceptions(Vec<FirewallException>, oneshot::Sender<()>),
+    /// Set the interfaces whose traffic and routes should not go through the tunnel.
+    #[cfg(target_os = "linux")]
+    TrustedInterfaces(Vec<TrustedInterface>, oneshot::Sender<()>),
     /// Endpoint that should never be blocked. `()` is sent to the
     /// channel after attempting to set the firewall policy, regardless
     /// of whether it succeeded.
@@ -206,6 +224,12 @@ pub enum TunnelCommand {
     Disconnect,
     /// Disconnect any open tunnel and block all network access
     Block(ErrorStateCause),
+    /// Start a captive portal login that lasts for at most the given duration, or cancel the
+    /// ongoing login if `None` is given. `()` is sent to the channel once the firewall policy has
+    /// been updated. Logins are refused on Windows.
+    CaptivePortalLogin(Option<Duration>, oneshot::Sender<()>),
+    /// Result of a captive portal probe. Sent by the login task.
+    CaptivePortalEvent(CaptivePortalEvent),
     /// Bypass a socket, allowing traffic to flow through outside the tunnel.
     #[cfg(target_os = "android")]
     BypassSocket(RawFd, oneshot::Sender<()>),
@@ -241,7 +265,7 @@ struct TunnelStateMachine {
 struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     settings: InitialTunnelState,
     command_tx: std::sync::Weak<mpsc::UnboundedSender<TunnelCommand>>,
-    offline_state_tx: mpsc::UnboundedSender<bool>,
+    offline_state_tx: mpsc::UnboundedSender<Connectivity>,
     tunnel_parameters_generator: G,
     tun_provider: TunProvider,
     log_dir: Option<PathBuf>,
@@ -278,6 +302,17 @@ impl TunnelStateMachine {
         .await
         .map_err(Error::InitRouteManagerError)?;
 
+        #[cfg(target_os = "linux")]
+        if let Err(error) = route_manager
+            .set_trusted_interfaces(args.settings.trusted_interfaces.clone())
+            .await
+        {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to add routes of trusted interfaces")
+            );
+        }
+
         #[cfg(windows)]
         let split_tunnel = split_tunnel::SplitTunnel::new(
             runtime.clone(),
@@ -298,6 +333,7 @@ impl TunnelStateMachine {
                 InitialFirewallState::None
             },
             allow_lan: args.settings.allow_lan,
+            firewall_exceptions: args.settings.firewall_exceptions.clone(),
             #[cfg(target_os = "linux")]
             fwmark: args.linux_ids.fwmark,
         };
@@ -319,13 +355,13 @@ impl TunnelStateMachine {
         let (offline_tx, mut offline_rx) = mpsc::unbounded();
         let initial_offline_state_tx = args.offline_state_tx.clone();
         tokio::spawn(async move {
-            while let Some(offline) = offline_rx.next().await {
+            while let Some(connectivity) = offline_rx.next().await {
                 if let Some(tx) = args.command_tx.upgrade() {
-                    let _ = tx.unbounded_send(TunnelCommand::IsOffline(offline));
+                    let _ = tx.unbounded_send(TunnelCommand::IsOffline(connectivity.is_offline()));
                 } else {
                     break;
                 }
-                let _ = args.offline_state_tx.unbounded_send(offline);
+                let _ = args.offline_state_tx.unbounded_send(connectivity);
             }
         });
         let offline_monitor = offline::spawn_monitor(
@@ -339,14 +375,26 @@ impl TunnelStateMachine {
         )
         .await
         .map_err(Error::OfflineMonitorError)?;
-        let is_offline = offline_monitor.host_is_offline().await;
-        let _ = initial_offline_state_tx.unbounded_send(is_offline);
+        let connectivity = offline_monitor.connectivity().await;
+        let is_offline = connectivity.is_offline();
+        let _ = initial_offline_state_tx.unbounded_send(connectivity);
 
         #[cfg(windows)]
         split_tunnel
             .set_paths_sync(&args.settings.exclude_paths)
             .map_err(Error::InitSplitTunneling)?;
 
+        let captive_portal = CaptivePortalLogins::new(
+            runtime.clone(),
+            args.command_tx.clone(),
+            #[cfg(target_os = "linux")]
+            captive_portal::gateway_lookup(route_manager.handle()?, args.linux_ids.fwmark),
+            #[cfg(target_os = "macos")]
+            captive_portal::gateway_lookup(route_manager.handle()?),
+            #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+            captive_portal::gateway_lookup(),
+        );
+
         let mut shared_values = SharedTunnelStateValues {
             #[cfg(windows)]
             split_tunnel,
@@ -356,10 +404,14 @@ impl TunnelStateMachine {
             route_manager,
             _offline_monitor: offline_monitor,
             allow_lan: args.settings.allow_lan,
+            firewall_exceptions: args.settings.firewall_exceptions,
+            #[cfg(target_os = "linux")]
+            trusted_interfaces: args.settings.trusted_interfaces,
             block_when_disconnected: args.settings.block_when_disconnected,
             is_offline,
             dns_servers: args.settings.dns_servers,
             allowed_endpoint: args.settings.allowed_endpoint,
+            captive_portal,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
             log_dir: args.log_dir,
@@ -438,6 +490,11 @@ struct SharedTunnelStateValues {
     _offline_monitor: offline::MonitorHandle,
     /// Should LAN access be allowed outside the tunnel.
     allow_lan: bool,
+    /// User-defined traffic that should be allowed outside the tunnel.
+    firewall_exceptions: Vec<FirewallException>,
+    /// Interfaces whose traffic and routes should not go through the tunnel.
+    #[cfg(target_os = "linux")]
+    trusted_interfaces: Vec<TrustedInterface>,
     /// Should network access be allowed when in the disconnected state.
     block_when_disconnected: bool,
     /// True when the computer is known to be offline.
@@ -446,6 +503,8 @@ struct SharedTunnelStateValues {
     dns_servers: Option<Vec<IpAddr>>,
     /// Endpoint that should not be blocked by the firewall.
     allowed_endpoint: AllowedEndpoint,
+    /// Captive portal login, if any.
+    captive_portal: CaptivePortalLogins,
     /// The generator of new `TunnelParameter`s
     tunnel_parameters_generator: Box<dyn TunnelParametersGenerator>,
     /// The provider of tunnel devices.
@@ -487,6 +546,26 @@ impl SharedTunnelStateValues {
         Ok(())
     }
 
+    /// Sets the trusted interfaces and updates the routes copied from them. Returns whether the
+    /// interfaces changed, in which case the firewall policy must be updated.
+    #[cfg(target_os = "linux")]
+    pub fn set_trusted_interfaces(&mut self, trusted_interfaces: Vec<TrustedInterface>) -> bool {
+        if self.trusted_interfaces == trusted_interfaces {
+            return false;
+        }
+        self.trusted_interfaces = trusted_interfaces;
+        if let Err(error) = self.runtime.block_on(
+            self.route_manager
+                .set_trusted_interfaces(self.trusted_interfaces.clone()),
+        ) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to update routes of trusted interfaces")
+            );
+        }
+        true
+    }
+
     pub fn set_dns_servers(
         &mut self,
         dns_servers: Option<Vec<IpAddr>>,
@@ -544,6 +623,22 @@ impl SharedTunnelStateValues {
         }
     }
 
+    /// Starts or cancels a captive portal login. Returns whether the login status changed. Logins
+    /// are refused on Windows, since its firewall cannot allow captive portal traffic.
+    pub fn set_captive_portal_login(&mut self, timeout: Option<Duration>) -> bool {
+        match timeout {
+            Some(_) if cfg!(target_os = "windows") => {
+                log::error!("Captive portal logins are not supported on Windows");
+                false
+            }
+            Some(timeout) => {
+                self.captive_portal.start(timeout);
+                true
+            }
+            None => self.captive_portal.cancel(),
+        }
+    }
+
     #[cfg(target_os = "android")]
     pub fn bypass_socket(&mut self, fd: RawFd, tx: oneshot::Sender<()>) {
         if let Err(err) = self.tun_provider.lock().unwrap().bypass(fd) {
@@ -174,6 +174,8 @@ pub struct OpenVpnMonitor<C: OpenVpnBuilder = OpenVpnCommand> {
     _user_pass_file: mktemp::TempFile,
     /// Keep the 'TempFile' for the proxy user-pass file in the struct, so it's removed on drop.
     _proxy_auth_file: Option<mktemp::TempFile>,
+    /// Keep the `TempFile`s for the key material in the struct, so they're removed on drop.
+    _key_material_files: KeyMaterialFiles,
 
     event_server_abort_tx: triggered::Trigger,
     server_join_handle: task::JoinHandle<std::result::Result<(), event_server::Error>>,
@@ -263,6 +265,8 @@ impl OpenVpnMonitor<OpenVpnCommand> {
                 .map_err(Error::CredentialsWriteError)?;
         let proxy_auth_file =
             Self::create_proxy_auth_file(&params.proxy).map_err(Error::CredentialsWriteError)?;
+        let key_material_files = Self::create_key_material_files(&params.config.key_material)
+            .map_err(Error::CredentialsWriteError)?;
         let user_pass_file_path = user_pass_file.to_path_buf();
         let proxy_auth_file_path = proxy_auth_file.as_ref().map(|file| file.to_path_buf());
 
@@ -280,6 +284,7 @@ impl OpenVpnMonitor<OpenVpnCommand> {
             params,
             user_pass_file.as_ref(),
             proxy_auth_file.as_ref().map(AsRef::as_ref),
+            &key_material_files,
             resource_dir,
             &proxy_monitor,
             #[cfg(windows)]
@@ -300,6 +305,7 @@ impl OpenVpnMonitor<OpenVpnCommand> {
             log_path,
             user_pass_file,
             proxy_auth_file,
+            key_material_files,
             proxy_monitor,
             #[cfg(target_os = "linux")]
             fwmark: params.fwmark,
@@ -364,11 +370,22 @@ struct OpenVpnTunnelInitArgs {
     log_path: Option<PathBuf>,
     user_pass_file: mktemp::TempFile,
     proxy_auth_file: Option<mktemp::TempFile>,
+    key_material_files: KeyMaterialFiles,
     proxy_monitor: Option<Box<dyn ProxyMonitor>>,
     #[cfg(target_os = "linux")]
     fwmark: u32,
 }
 
+/// Temporary files containing the certificates and keys of a [`openvpn::KeyMaterial`].
+#[derive(Debug, Default)]
+struct KeyMaterialFiles {
+    ca: Option<mktemp::TempFile>,
+    cert: Option<mktemp::TempFile>,
+    key: Option<mktemp::TempFile>,
+    tls_auth: Option<mktemp::TempFile>,
+    tls_crypt: Option<mktemp::TempFile>,
+}
+
 impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
     fn new_internal<L>(
         mut cmd: C,
@@ -385,6 +402,7 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         let log_path = init_args.log_path;
         let user_pass_file = init_args.user_pass_file;
         let proxy_auth_file = init_args.proxy_auth_file;
+        let key_material_files = init_args.key_material_files;
         let proxy_monitor = init_args.proxy_monitor;
 
         let (server_join_handle, ipc_path) = event_server::start(on_event, event_server_abort_rx)
@@ -411,6 +429,7 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
             proxy_monitor,
             _user_pass_file: user_pass_file,
             _proxy_auth_file: proxy_auth_file,
+            _key_material_files: key_material_files,
 
             event_server_abort_tx,
             server_join_handle,
@@ -585,6 +604,29 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         Ok(temp_file)
     }
 
+    fn create_key_material_files(
+        key_material: &openvpn::KeyMaterial,
+    ) -> io::Result<KeyMaterialFiles> {
+        let create = |contents: &Option<String>| -> io::Result<Option<mktemp::TempFile>> {
+            let Some(contents) = contents else {
+                return Ok(None);
+            };
+            let temp_file = mktemp::TempFile::new();
+            log::debug!("Writing key material to {}", temp_file.as_ref().display());
+            let mut file = fs::File::create(&temp_file)?;
+            Self::set_user_pass_file_permissions(&file)?;
+            file.write_all(contents.as_bytes())?;
+            Ok(Some(temp_file))
+        };
+        Ok(KeyMaterialFiles {
+            ca: create(&key_material.ca)?,
+            cert: create(&key_material.cert)?,
+            key: create(&key_material.key)?,
+            tls_auth: create(&key_material.tls_auth)?,
+            tls_crypt: create(&key_material.tls_crypt)?,
+        })
+    }
+
     #[cfg(unix)]
     fn set_user_pass_file_permissions(file: &fs::File) -> io::Result<()> {
         use std::os::unix::fs::PermissionsExt;
@@ -611,6 +653,7 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         params: &openvpn::TunnelParameters,
         user_pass_file: &Path,
         proxy_auth_file: Option<&Path>,
+        key_material_files: &KeyMaterialFiles,
         resource_dir: &Path,
         proxy_monitor: &Option<Box<dyn ProxyMonitor>>,
         #[cfg(windows)] alias: OsString,
@@ -622,8 +665,23 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         cmd.remote(params.config.endpoint)
             .user_pass(user_pass_file)
             .tunnel_options(&params.options)
-            .enable_ipv6(params.generic_options.enable_ipv6)
-            .ca(resource_dir.join("ca.crt"));
+            .enable_ipv6(params.generic_options.enable_ipv6);
+        match &key_material_files.ca {
+            Some(ca) => cmd.ca(ca),
+            None => cmd.ca(resource_dir.join("ca.crt")),
+        };
+        if let Some(cert) = &key_material_files.cert {
+            cmd.cert(cert);
+        }
+        if let Some(key) = &key_material_files.key {
+            cmd.key(key);
+        }
+        if let Some(tls_auth) = &key_material_files.tls_auth {
+            cmd.tls_auth(tls_auth, params.config.key_material.key_direction);
+        }
+        if let Some(tls_crypt) = &key_material_files.tls_crypt {
+            cmd.tls_crypt(tls_crypt);
+        }
         #[cfg(windows)]
         cmd.tunnel_alias(Some(alias));
         if let Some(proxy_settings) = params.proxy.clone().take() {
@@ -962,6 +1020,7 @@ mod event_server {
                 ips,
                 ipv4_gateway,
                 ipv6_gateway,
+                discovered_mtu: None,
             })
         }
     }
@@ -1206,6 +1265,7 @@ mod tests {
             log_path,
             user_pass_file: TempFile::new(),
             proxy_auth_file: None,
+            key_material_files: KeyMaterialFiles::default(),
             proxy_monitor: None,
             #[cfg(target_os = "linux")]
             fwmark: 0,
@@ -63,6 +63,10 @@ pub struct OpenVpnCommand {
     proxy_auth_path: Option<PathBuf>,
     ca: Option<PathBuf>,
     crl: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    tls_auth: Option<(PathBuf, Option<u8>)>,
+    tls_crypt: Option<PathBuf>,
     plugin: Option<(PathBuf, Vec<String>)>,
     log: Option<PathBuf>,
     tunnel_options: net::openvpn::TunnelOptions,
@@ -86,6 +90,10 @@ impl OpenVpnCommand {
             proxy_auth_path: None,
             ca: None,
             crl: None,
+            cert: None,
+            key: None,
+            tls_auth: None,
+            tls_crypt: None,
             plugin: None,
             log: None,
             tunnel_options: net::openvpn::TunnelOptions::default(),
@@ -143,6 +151,30 @@ impl OpenVpnCommand {
         self
     }
 
+    /// Sets the path to the client certificate file.
+    pub fn cert(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.cert = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the path to the private key file of the client certificate.
+    pub fn key(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.key = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the path to the static key file used for `--tls-auth`, and its key direction.
+    pub fn tls_auth(&mut self, path: impl AsRef<Path>, key_direction: Option<u8>) -> &mut Self {
+        self.tls_auth = Some((path.as_ref().to_path_buf(), key_direction));
+        self
+    }
+
+    /// Sets the path to the static key file used for `--tls-crypt`.
+    pub fn tls_crypt(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.tls_crypt = Some(path.as_ref().to_path_buf());
+        self
+    }
+
     /// Sets a plugin and its arguments that OpenVPN will be started with.
     pub fn plugin(&mut self, path: impl AsRef<Path>, args: Vec<String>) -> &mut Self {
         self.plugin = Some((path.as_ref().to_path_buf(), args));
@@ -215,6 +247,25 @@ impl OpenVpnCommand {
             args.push(OsString::from("--crl-verify"));
             args.push(OsString::from(crl.as_os_str()));
         }
+        if let Some(ref cert) = self.cert {
+            args.push(OsString::from("--cert"));
+            args.push(OsString::from(cert.as_os_str()));
+        }
+        if let Some(ref key) = self.key {
+            args.push(OsString::from("--key"));
+            args.push(OsString::from(key.as_os_str()));
+        }
+        if let Some((ref tls_auth, key_direction)) = self.tls_auth {
+            args.push(OsString::from("--tls-auth"));
+            args.push(OsString::from(tls_auth.as_os_str()));
+            if let Some(key_direction) = key_direction {
+                args.push(OsString::from(key_direction.to_string()));
+            }
+        }
+        if let Some(ref tls_crypt) = self.tls_crypt {
+            args.push(OsString::from("--tls-crypt"));
+            args.push(OsString::from(tls_crypt.as_os_str()));
+        }
 
         if let Some((ref path, ref plugin_args)) = self.plugin {
             args.push(OsString::from("--plugin"));
@@ -485,4 +536,22 @@ mod tests {
         assert!(testee_args.contains(&OsString::from("123")));
         assert!(testee_args.contains(&OsString::from("cde")));
     }
+
+    #[test]
+    fn passes_key_material() {
+        let testee_args = OpenVpnCommand::new("")
+            .cert("./client.crt")
+            .key("./client.key")
+            .tls_auth("./ta.key", Some(1))
+            .get_arguments();
+        let expected = ["--cert", "./client.crt", "--key", "./client.key"]
+            .into_iter()
+            .chain(["--tls-auth", "./ta.key", "1"])
+            .map(OsString::from)
+            .collect::<Vec<_>>();
+        assert!(testee_args
+            .windows(expected.len())
+            .any(|window| window == expected));
+        assert!(!testee_args.contains(&OsString::from("--tls-crypt")));
+    }
 }
@@ -8,7 +8,7 @@ use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
-use talpid_types::ErrorExt;
+use talpid_types::{net::trusted_interface::TrustedInterface, ErrorExt};
 
 use futures::{
     channel::mpsc::{UnboundedReceiver, UnboundedSender},
@@ -24,7 +24,7 @@ use netlink_packet_route::{
     rtnl::{
         constants::{
             RTN_UNSPEC, RTPROT_UNSPEC, RT_SCOPE_LINK, RT_SCOPE_UNIVERSE, RT_TABLE_COMPAT,
-            RT_TABLE_MAIN,
+            RT_TABLE_LOCAL, RT_TABLE_MAIN,
         },
         RouteFlags,
     },
@@ -38,6 +38,11 @@ use rtnetlink::{
     Handle, IpVersion,
 };
 
+/// Shortest prefix of an IPv4 route that is copied from a trusted interface.
+const MIN_TRUSTED_IPV4_PREFIX: u8 = 8;
+/// Shortest prefix of an IPv6 route that is copied from a trusted interface.
+const MIN_TRUSTED_IPV6_PREFIX: u8 = 16;
+
 static SUPPRESS_RULE_V4: Lazy<RuleMessage> = Lazy::new(|| RuleMessage {
     header: RuleHeader {
         family: AF_INET as u8,
@@ -143,6 +148,11 @@ pub struct RouteManagerImpl {
     /// Firewall mark identifies traffic which shouldn't be routed via the tunnel routing table. It
     /// is used to construct a routing rule.
     fwmark: u32,
+
+    /// Interfaces whose routes are copied to the tunnel routing table.
+    trusted_interfaces: Vec<TrustedInterface>,
+    /// Routes that have been copied to the tunnel routing table from the trusted interfaces.
+    trusted_routes: HashSet<Route>,
 }
 
 impl RouteManagerImpl {
@@ -170,6 +180,8 @@ impl RouteManagerImpl {
             added_routes: HashSet::new(),
             table_id,
             fwmark,
+            trusted_interfaces: vec![],
+            trusted_routes: HashSet::new(),
         };
 
         monitor.clear_routing_rules().await?;
@@ -307,6 +319,71 @@ impl RouteManagerImpl {
         Ok(())
     }
 
+    /// Copies the routes of the trusted interfaces to the tunnel routing table, and removes the
+    /// copies of routes that are no longer trusted.
+    async fn set_trusted_interfaces(
+        &mut self,
+        trusted_interfaces: Vec<TrustedInterface>,
+    ) -> Result<()> {
+        self.trusted_interfaces = trusted_interfaces;
+
+        let trusted_routes: HashSet<Route> = self
+            .get_routes()
+            .await?
+            .iter()
+            .filter_map(|route| trusted_route_copy(route, &self.trusted_interfaces, self.table_id))
+            .collect();
+
+        for route in self.trusted_routes.difference(&trusted_routes) {
+            if let Err(error) = self.delete_route_if_exists(route).await {
+                log::error!("Failed to remove route: {}: {}", route, error);
+            }
+        }
+        let new_routes: Vec<_> = trusted_routes
+            .difference(&self.trusted_routes)
+            .cloned()
+            .collect();
+        self.trusted_routes = trusted_routes;
+        for route in new_routes {
+            log::debug!("Adding route of trusted interface: {}", route);
+            self.add_route_direct(route).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the routes of every table.
+    async fn get_routes(&self) -> Result<Vec<Route>> {
+        let mut routes = vec![];
+        for ip_version in [IpVersion::V4, IpVersion::V6] {
+            let mut route_request = self.handle.route().get(ip_version).execute();
+            while let Some(msg) = route_request.try_next().await.map_err(Error::Netlink)? {
+                // Routes without a device, such as unreachable routes, are not of interest
+                if let Ok(Some(route)) = self.parse_route_message(msg) {
+                    routes.push(route);
+                }
+            }
+        }
+        Ok(routes)
+    }
+
+    async fn add_trusted_route_copy(&mut self, route: &Route) -> Result<()> {
+        if let Some(copy) = trusted_route_copy(route, &self.trusted_interfaces, self.table_id) {
+            log::debug!("Adding route of trusted interface: {}", copy);
+            self.add_route_direct(copy.clone()).await?;
+            self.trusted_routes.insert(copy);
+        }
+        Ok(())
+    }
+
+    async fn remove_trusted_route_copy(&mut self, route: &Route) -> Result<()> {
+        if let Some(copy) = trusted_route_copy(route, &self.trusted_interfaces, self.table_id) {
+            if self.trusted_routes.remove(&copy) {
+                self.delete_route_if_exists(&copy).await?;
+            }
+        }
+        Ok(())
+    }
+
     async fn initialize_link_map(
         handle: &rtnetlink::Handle,
     ) -> Result<BTreeMap<u32, NetworkInterface>> {
@@ -341,6 +418,14 @@ impl RouteManagerImpl {
         }
     }
 
+    async fn cleanup_trusted_routes(&mut self) {
+        for route in self.trusted_routes.drain().collect::<Vec<_>>().iter() {
+            if let Err(e) = self.delete_route_if_exists(route).await {
+                log::error!("Failed to remove route: {}: {}", route, e);
+            }
+        }
+    }
+
     pub(crate) async fn run(
         mut self,
         manage_rx: UnboundedReceiver<RouteManagerCommand>,
@@ -352,7 +437,7 @@ impl RouteManagerImpl {
                     self.process_command(command).await?;
                 },
                 (route_change, _socket) = self.messages.select_next_some().fuse() => {
-                    if let Err(error) = self.process_netlink_message(route_change) {
+                    if let Err(error) = self.process_netlink_message(route_change).await {
                         log::error!("{}", error.display_chain_with_msg("Failed to process netlink message"));
                     }
                 }
@@ -379,6 +464,10 @@ impl RouteManagerImpl {
             RouteManagerCommand::ClearRoutingRules(result_tx) => {
                 let _ = result_tx.send(self.clear_routing_rules().await);
             }
+            RouteManagerCommand::SetTrustedInterfaces(trusted_interfaces, result_tx) => {
+                log::debug!("Setting trusted interfaces: {:?}", trusted_interfaces);
+                let _ = result_tx.send(self.set_trusted_interfaces(trusted_interfaces).await);
+            }
             RouteManagerCommand::NewChangeListener(result_tx) => {
                 let _ = result_tx.send(self.listen());
             }
@@ -396,7 +485,7 @@ impl RouteManagerImpl {
         Ok(())
     }
 
-    fn process_netlink_message(&mut self, msg: NetlinkMessage<RtnlMessage>) -> Result<()> {
+    async fn process_netlink_message(&mut self, msg: NetlinkMessage<RtnlMessage>) -> Result<()> {
         match msg.payload {
             NetlinkPayload::InnerMessage(RtnlMessage::NewLink(new_link)) => {
                 if let Some((idx, name)) = Self::map_interface(new_link) {
@@ -404,18 +493,37 @@ impl RouteManagerImpl {
                 }
             }
             NetlinkPayload::InnerMessage(RtnlMessage::DelLink(old_link)) => {
-                if let Some((idx, _)) = Self::map_interface(old_link) {
+                if let Some((idx, iface)) = Self::map_interface(old_link) {
                     self.iface_map.remove(&idx);
+                    // The kernel removes the routes of an interface along with it
+                    self.trusted_routes
+                        .retain(|route| route.node.get_device() != Some(iface.name.as_str()));
                 }
             }
             NetlinkPayload::InnerMessage(RtnlMessage::NewRoute(new_route)) => {
                 if let Some(addition) = self.parse_route_message(new_route)? {
+                    if let Err(error) = self.add_trusted_route_copy(&addition).await {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg(
+                                "Failed to copy route of trusted interface"
+                            )
+                        );
+                    }
                     self.notify_change_listeners(CallbackMessage::NewRoute(addition));
                 }
             }
             NetlinkPayload::InnerMessage(RtnlMessage::DelRoute(old_route)) => {
                 if let Some(deletion) = self.parse_route_message(old_route)? {
                     self.process_deleted_route(&deletion)?;
+                    if let Err(error) = self.remove_trusted_route_copy(&deletion).await {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg(
+                                "Failed to remove route of trusted interface"
+                            )
+                        );
+                    }
                     self.notify_change_listeners(CallbackMessage::DelRoute(deletion));
                 }
             }
@@ -744,6 +852,7 @@ impl RouteManagerImpl {
 
     async fn destructor(&mut self) {
         self.cleanup_routes().await;
+        self.cleanup_trusted_routes().await;
 
         if let Err(error) = self.clear_routing_rules().await {
             log::error!(
@@ -846,6 +955,34 @@ impl RouteManagerImpl {
     }
 }
 
+/// Returns the route to add to the tunnel routing table for `route`, if it goes through one of the
+/// trusted interfaces. Routes wider than [`MIN_TRUSTED_IPV4_PREFIX`] and
+/// [`MIN_TRUSTED_IPV6_PREFIX`] are never copied, so that a trusted interface cannot capture all
+/// traffic using default routes or split default routes such as `0.0.0.0/1` and `128.0.0.0/1`.
+/// Neither are routes of the local table, which the kernel manages.
+fn trusted_route_copy(
+    route: &Route,
+    trusted_interfaces: &[TrustedInterface],
+    table_id: u32,
+) -> Option<Route> {
+    let device = route.node.get_device()?;
+    if route.table_id == table_id
+        || route.table_id == u32::from(RT_TABLE_LOCAL)
+        || route.prefix.prefix() < min_trusted_prefix(&route.prefix)
+        || !trusted_interfaces.iter().any(|iface| iface.matches(device))
+    {
+        return None;
+    }
+    Some(Route::new(route.node.clone(), route.prefix).table(table_id))
+}
+
+fn min_trusted_prefix(prefix: &IpNetwork) -> u8 {
+    match prefix {
+        IpNetwork::V4(_) => MIN_TRUSTED_IPV4_PREFIX,
+        IpNetwork::V6(_) => MIN_TRUSTED_IPV6_PREFIX,
+    }
+}
+
 fn ip_to_bytes(addr: IpAddr) -> Vec<u8> {
     match addr {
         IpAddr::V4(addr) => addr.octets().to_vec(),
@@ -934,4 +1071,51 @@ mod test {
         });
         std::mem::drop(manager);
     }
+
+    #[test]
+    fn test_trusted_route_copy() {
+        const TABLE_ID: u32 = 1000;
+        let trusted_interfaces = [
+            "tailscale0".parse().unwrap(),
+            "zt*".parse().unwrap(),
+            "vpn1".parse().unwrap(),
+        ];
+        let route = |device: &str, prefix: &str, table_id: u32| {
+            Route::new(Node::device(device.to_owned()), prefix.parse().unwrap()).table(table_id)
+        };
+
+        // Routes are copied from any table but the tunnel and local tables
+        for original in [
+            route("tailscale0", "100.64.0.0/10", 52),
+            route("ztc3qtnr5b", "10.147.17.0/24", RT_TABLE_MAIN.into()),
+            route("tailscale0", "fd7a:115c:a1e0::/48", 52),
+            route("vpn1", "10.0.0.0/8", RT_TABLE_MAIN.into()),
+        ] {
+            let mut copy = original.clone();
+            copy.table_id = TABLE_ID;
+            assert_eq!(
+                trusted_route_copy(&original, &trusted_interfaces, TABLE_ID),
+                Some(copy)
+            );
+        }
+        for original in [
+            route("eth0", "192.168.1.0/24", RT_TABLE_MAIN.into()),
+            route("tailscale0", "0.0.0.0/0", 52),
+            route("tailscale0", "::/0", 52),
+            route("vpn1", "0.0.0.0/1", RT_TABLE_MAIN.into()),
+            route("vpn1", "128.0.0.0/1", RT_TABLE_MAIN.into()),
+            route("vpn1", "::/1", RT_TABLE_MAIN.into()),
+            route("vpn1", "8000::/1", RT_TABLE_MAIN.into()),
+            route("vpn1", "2000::/3", RT_TABLE_MAIN.into()),
+            route("vpn1", "64.0.0.0/2", RT_TABLE_MAIN.into()),
+            route("tailscale0", "100.64.0.0/10", TABLE_ID),
+            route("tailscale0", "100.101.102.103/32", RT_TABLE_LOCAL.into()),
+        ] {
+            assert_eq!(
+                trusted_route_copy(&original, &trusted_interfaces, TABLE_ID),
+                None,
+                "{original}"
+            );
+        }
+    }
 }
@@ -14,6 +14,8 @@ use futures::stream::Stream;
 
 #[cfg(target_os = "linux")]
 use std::net::IpAddr;
+#[cfg(target_os = "linux")]
+use talpid_types::net::trusted_interface::TrustedInterface;
 
 #[allow(clippy::module_inception)]
 #[cfg(target_os = "macos")]
@@ -149,6 +151,26 @@ impl RouteManagerHandle {
             .map_err(Error::PlatformError)
     }
 
+    /// Copy the routes of the given interfaces to the tunnel routing table, so that traffic to
+    /// their subnets is not routed through the tunnel. Replaces any previously trusted interfaces.
+    #[cfg(target_os = "linux")]
+    pub async fn set_trusted_interfaces(
+        &self,
+        trusted_interfaces: Vec<TrustedInterface>,
+    ) -> Result<(), Error> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.tx
+            .unbounded_send(RouteManagerCommand::SetTrustedInterfaces(
+                trusted_interfaces,
+                response_tx,
+            ))
+            .map_err(|_| Error::RouteManagerDown)?;
+        response_rx
+            .await
+            .map_err(|_| Error::ManagerChannelDown)?
+            .map_err(Error::PlatformError)
+    }
+
     /// Listen for route changes.
     #[cfg(target_os = "linux")]
     pub async fn change_listener(&self) -> Result<impl Stream<Item = CallbackMessage>, Error> {
@@ -218,6 +240,11 @@ pub(crate) enum RouteManagerCommand {
     #[cfg(target_os = "linux")]
     ClearRoutingRules(oneshot::Sender<Result<(), PlatformError>>),
     #[cfg(target_os = "linux")]
+    SetTrustedInterfaces(
+        Vec<TrustedInterface>,
+        oneshot::Sender<Result<(), PlatformError>>,
+    ),
+    #[cfg(target_os = "linux")]
     NewChangeListener(oneshot::Sender<mpsc::UnboundedReceiver<CallbackMessage>>),
     #[cfg(target_os = "linux")]
     GetMtuForRoute(IpAddr, oneshot::Sender<Result<u16, PlatformError>>),
@@ -348,6 +375,18 @@ impl RouteManager {
         self.handle()?.clear_routing_rules().await
     }
 
+    /// Copy the routes of the given interfaces to the tunnel routing table. See
+    /// [RouteManagerHandle::set_trusted_interfaces].
+    #[cfg(target_os = "linux")]
+    pub async fn set_trusted_interfaces(
+        &self,
+        trusted_interfaces: Vec<TrustedInterface>,
+    ) -> Result<(), Error> {
+        self.handle()?
+            .set_trusted_interfaces(trusted_interfaces)
+            .await
+    }
+
     /// Retrieve a sender directly to the command channel.
     pub fn handle(&self) -> Result<RouteManagerHandle, Error> {
         if let Some(tx) = &self.manage_tx {
@@ -46,6 +46,8 @@ pub struct TunnelMetadata {
     pub ipv4_gateway: Ipv4Addr,
     /// The IP to the IPv6 default gateway on the tunnel interface.
     pub ipv6_gateway: Option<Ipv6Addr>,
+    /// The tunnel MTU found by path MTU discovery, if it was performed.
+    pub discovered_mtu: Option<u16>,
 }
 
 /// Possible events from the VPN tunnel and the child process managing it.
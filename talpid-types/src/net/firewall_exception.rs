@@ -0,0 +1,301 @@
+//! User-defined traffic that the firewall allows outside the tunnel, in addition to LAN traffic.
+
+use super::TransportProtocol;
+use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// The narrowest prefix length allowed for IPv4 networks that are not local.
+const MIN_PUBLIC_IPV4_PREFIX: u8 = 24;
+/// The narrowest prefix length allowed for IPv6 networks that are not local.
+const MIN_PUBLIC_IPV6_PREFIX: u8 = 64;
+
+/// Port that is never allowed, since DNS must not leak outside the tunnel.
+const DNS_PORT: u16 = 53;
+
+/// Returns the networks that are not reachable through the internet, and so can be excepted in
+/// their entirety. Unlike the LAN networks, these include the shared address space used by CGNAT
+/// and many overlay networks.
+fn local_nets() -> [IpNetwork; 9] {
+    let v4 = |addr, prefix| IpNetwork::V4(Ipv4Network::new(addr, prefix).unwrap());
+    let v6 = |addr, prefix| IpNetwork::V6(Ipv6Network::new(addr, prefix).unwrap());
+    [
+        v4(Ipv4Addr::new(10, 0, 0, 0), 8),
+        v4(Ipv4Addr::new(172, 16, 0, 0), 12),
+        v4(Ipv4Addr::new(192, 168, 0, 0), 16),
+        v4(Ipv4Addr::new(169, 254, 0, 0), 16),
+        v4(Ipv4Addr::new(100, 64, 0, 0), 10),
+        v4(Ipv4Addr::new(224, 0, 0, 0), 4),
+        v6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10),
+        v6(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7),
+        v6(Ipv6Addr::new(0xff00, 0, 0, 0, 0, 0, 0, 0), 8),
+    ]
+}
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error(display = "The network must not contain every address")]
+    AllAddresses,
+
+    #[error(
+        display = "Networks that are not local must be at most /{} for IPv4 and /{} for IPv6",
+        MIN_PUBLIC_IPV4_PREFIX,
+        MIN_PUBLIC_IPV6_PREFIX
+    )]
+    PublicNetworkTooLarge,
+
+    #[error(
+        display = "Exceptions for networks that are not local must be limited to a port, a \
+                   protocol, or a single host"
+    )]
+    UnrestrictedPublicNetwork,
+
+    #[error(
+        display = "Exceptions for networks that are not local must be either inbound or outbound"
+    )]
+    PublicNetworkBothDirections,
+
+    #[error(display = "Port 0 is not a valid port")]
+    InvalidPort,
+
+    #[error(display = "DNS traffic cannot be excepted, since it would leak outside the tunnel")]
+    DnsPort,
+}
+
+/// Which connections an exception applies to, as seen from this device.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Connections from this device to the network.
+    Outbound,
+    /// Connections from the network to this device.
+    Inbound,
+    /// Connections in either direction.
+    #[default]
+    Both,
+}
+
+impl Direction {
+    pub fn allows_outbound(&self) -> bool {
+        matches!(self, Direction::Outbound | Direction::Both)
+    }
+
+    pub fn allows_inbound(&self) -> bool {
+        matches!(self, Direction::Inbound | Direction::Both)
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Outbound => "outbound".fmt(f),
+            Direction::Inbound => "inbound".fmt(f),
+            Direction::Both => "both".fmt(f),
+        }
+    }
+}
+
+/// Returned when `Direction::from_str` fails to convert a string into a [`Direction`].
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+#[error(display = "Not a valid direction")]
+pub struct DirectionParseError;
+
+impl FromStr for Direction {
+    type Err = DirectionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "outbound" | "out" => Ok(Direction::Outbound),
+            "inbound" | "in" => Ok(Direction::Inbound),
+            "both" => Ok(Direction::Both),
+            _ => Err(DirectionParseError),
+        }
+    }
+}
+
+/// Traffic between this device and a network that is allowed outside the tunnel in every firewall
+/// policy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FirewallException {
+    pub network: IpNetwork,
+    /// Only allow this transport protocol. If `None`, any protocol is allowed, unless `port` is
+    /// set, in which case TCP and UDP are allowed.
+    pub protocol: Option<TransportProtocol>,
+    /// Only allow connections to this port. That is the port of the remote host for outbound
+    /// connections, and the local port for inbound connections.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+impl FirewallException {
+    /// Checks that the exception cannot be used to send arbitrary traffic outside the tunnel.
+    /// Exceptions for networks that are not local must also be limited to a port, a protocol or
+    /// a single host, and to one direction.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.network.prefix() == 0 {
+            return Err(Error::AllAddresses);
+        }
+        if !self.is_local() {
+            let (min_prefix, host_prefix) = match self.network {
+                IpNetwork::V4(_) => (MIN_PUBLIC_IPV4_PREFIX, 32),
+                IpNetwork::V6(_) => (MIN_PUBLIC_IPV6_PREFIX, 128),
+            };
+            if self.network.prefix() < min_prefix {
+                return Err(Error::PublicNetworkTooLarge);
+            }
+            if self.port.is_none()
+                && self.protocol.is_none()
+                && self.network.prefix() != host_prefix
+            {
+                return Err(Error::UnrestrictedPublicNetwork);
+            }
+            if self.direction == Direction::Both {
+                return Err(Error::PublicNetworkBothDirections);
+            }
+        }
+        match self.port {
+            Some(0) => Err(Error::InvalidPort),
+            Some(DNS_PORT) => Err(Error::DnsPort),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the transport protocols that are allowed, or `None` if any protocol is allowed.
+    pub fn transport_protocols(&self) -> Option<Vec<TransportProtocol>> {
+        match (self.protocol, self.port) {
+            (Some(protocol), _) => Some(vec![protocol]),
+            (None, Some(_)) => Some(vec![TransportProtocol::Tcp, TransportProtocol::Udp]),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns whether the network is entirely within a network that is not reachable through
+    /// the internet.
+    fn is_local(&self) -> bool {
+        local_nets().iter().any(|local| {
+            local.contains(self.network.network()) && local.prefix() <= self.network.prefix()
+        })
+    }
+}
+
+impl fmt::Display for FirewallException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.network)?;
+        match (self.protocol, self.port) {
+            (Some(protocol), Some(port)) => write!(f, " {protocol} port {port}")?,
+            (Some(protocol), None) => write!(f, " {protocol}")?,
+            (None, Some(port)) => write!(f, " port {port}")?,
+            (None, None) => (),
+        }
+        write!(f, " ({})", self.direction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn exception(network: &str, port: Option<u16>) -> FirewallException {
+        FirewallException {
+            network: network.parse().unwrap(),
+            protocol: None,
+            port,
+            direction: Direction::Both,
+        }
+    }
+
+    fn outbound(network: &str, port: Option<u16>) -> FirewallException {
+        FirewallException {
+            direction: Direction::Outbound,
+            ..exception(network, port)
+        }
+    }
+
+    #[test]
+    fn test_validate_network() {
+        assert_eq!(
+            exception("0.0.0.0/0", None).validate(),
+            Err(Error::AllAddresses)
+        );
+        assert_eq!(exception("::/0", None).validate(), Err(Error::AllAddresses));
+        assert_eq!(
+            exception("1.0.0.0/8", None).validate(),
+            Err(Error::PublicNetworkTooLarge)
+        );
+        assert_eq!(
+            exception("2001:db8::/32", None).validate(),
+            Err(Error::PublicNetworkTooLarge)
+        );
+        // A local network must not be wider than the local range it is in
+        assert_eq!(
+            exception("100.0.0.0/9", None).validate(),
+            Err(Error::PublicNetworkTooLarge)
+        );
+
+        for network in ["203.0.113.0/24", "203.0.113.7/32", "2001:db8::/64"] {
+            assert_eq!(outbound(network, Some(22)).validate(), Ok(()), "{network}");
+        }
+        for network in [
+            "10.0.0.0/8",
+            "100.64.0.0/10",
+            "192.168.1.5/32",
+            "fd7a:115c:a1e0::/48",
+            "fe80::/10",
+            "239.255.255.250/32",
+        ] {
+            assert_eq!(exception(network, None).validate(), Ok(()), "{network}");
+        }
+    }
+
+    #[test]
+    fn test_validate_port() {
+        assert_eq!(
+            exception("192.168.1.5/32", Some(0)).validate(),
+            Err(Error::InvalidPort)
+        );
+        assert_eq!(
+            exception("192.168.1.5/32", Some(53)).validate(),
+            Err(Error::DnsPort)
+        );
+        assert_eq!(exception("192.168.1.5/32", Some(631)).validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_public_network_restrictions() {
+        assert_eq!(
+            outbound("203.0.113.0/24", None).validate(),
+            Err(Error::UnrestrictedPublicNetwork)
+        );
+        assert_eq!(
+            outbound("2001:db8::/64", None).validate(),
+            Err(Error::UnrestrictedPublicNetwork)
+        );
+        assert_eq!(
+            exception("203.0.113.0/24", Some(22)).validate(),
+            Err(Error::PublicNetworkBothDirections)
+        );
+        assert_eq!(
+            exception("203.0.113.7/32", None).validate(),
+            Err(Error::PublicNetworkBothDirections)
+        );
+
+        // A single host, a port or a protocol is enough
+        assert_eq!(outbound("203.0.113.7/32", None).validate(), Ok(()));
+        assert_eq!(outbound("2001:db8::7/128", None).validate(), Ok(()));
+        let udp = FirewallException {
+            protocol: Some(TransportProtocol::Udp),
+            ..outbound("203.0.113.0/24", None)
+        };
+        assert_eq!(udp.validate(), Ok(()));
+        let inbound = FirewallException {
+            direction: Direction::Inbound,
+            ..exception("2001:db8::/64", Some(8080))
+        };
+        assert_eq!(inbound.validate(), Ok(()));
+    }
+}
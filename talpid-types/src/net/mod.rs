@@ -12,9 +12,12 @@ use std::{
 
 use self::proxy::{CustomProxy, Socks5Local};
 
+pub mod firewall_exception;
+pub mod nat64;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
+pub mod trusted_interface;
 pub mod wireguard;
 
 /// TunnelParameters are used to encapsulate all the data needed to start a tunnel. This is enum
@@ -40,6 +43,7 @@ impl TunnelParameters {
                 obfuscation: None,
                 entry_endpoint: None,
                 tunnel_interface: None,
+                discovered_mtu: None,
             },
             TunnelParameters::Wireguard(params) => TunnelEndpoint {
                 tunnel_type: TunnelType::Wireguard,
@@ -55,6 +59,7 @@ impl TunnelParameters {
                     .get_exit_endpoint()
                     .map(|_| params.connection.get_endpoint()),
                 tunnel_interface: None,
+                discovered_mtu: None,
             },
         }
     }
@@ -99,6 +104,17 @@ impl TunnelParameters {
         }
     }
 
+    /// Returns whether the connectivity monitor probes the tunnel with DNS queries to the
+    /// gateway.
+    pub fn probes_gateway_dns(&self) -> bool {
+        match self {
+            TunnelParameters::OpenVpn(_) => false,
+            TunnelParameters::Wireguard(params) => {
+                params.options.connectivity_check.probe == wireguard::LivenessProbe::Dns
+            }
+        }
+    }
+
     pub fn get_openvpn_local_proxy_settings(&self) -> Option<&Socks5Local> {
         match &self {
             TunnelParameters::OpenVpn(params) => {
@@ -183,6 +199,9 @@ pub struct TunnelEndpoint {
     pub entry_endpoint: Option<Endpoint>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub tunnel_interface: Option<String>,
+    /// Tunnel MTU found by path MTU discovery, if it was performed
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub discovered_mtu: Option<u16>,
 }
 
 impl fmt::Display for TunnelEndpoint {
@@ -454,6 +473,47 @@ impl FromStr for IpVersion {
 #[error(display = "Not a valid IP protocol")]
 pub struct IpVersionParseError;
 
+/// The address families that the host can reach the internet over, as inferred from its default
+/// routes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Connectivity {
+    Status {
+        /// Whether there is a default route for IPv4.
+        ipv4: bool,
+        /// Whether there is a default route for IPv6.
+        ipv6: bool,
+    },
+    /// The available address families are unknown. The host is presumed to be online with both
+    /// IPv4 and IPv6.
+    PresumeOnline,
+}
+
+impl Connectivity {
+    /// Returns whether the host has no route to the internet at all.
+    pub fn is_offline(&self) -> bool {
+        !self.has_ipv4() && !self.has_ipv6()
+    }
+
+    pub fn has_ipv4(&self) -> bool {
+        match self {
+            Connectivity::Status { ipv4, .. } => *ipv4,
+            Connectivity::PresumeOnline => true,
+        }
+    }
+
+    pub fn has_ipv6(&self) -> bool {
+        match self {
+            Connectivity::Status { ipv6, .. } => *ipv6,
+            Connectivity::PresumeOnline => true,
+        }
+    }
+
+    /// Returns whether the internet can only be reached over IPv6.
+    pub fn is_ipv6_only(&self) -> bool {
+        self.has_ipv6() && !self.has_ipv4()
+    }
+}
+
 /// Representation of a transport protocol, either UDP or TCP.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -0,0 +1,300 @@
+//! NAT64 prefixes ([RFC 6052]), which let hosts on IPv6-only networks reach IPv4 addresses
+//! through a NAT64 gateway.
+//!
+//! [RFC 6052]: https://www.rfc-editor.org/rfc/rfc6052
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// Hostname that only has A records. A DNS64 resolver synthesizes AAAA records for it using the
+/// NAT64 prefix of the network, which is how the prefix is discovered ([RFC 7050]).
+///
+/// [RFC 7050]: https://www.rfc-editor.org/rfc/rfc7050
+pub const IPV4ONLY_ARPA: &str = "ipv4only.arpa";
+
+/// The well-known IPv4 addresses of [`IPV4ONLY_ARPA`].
+const IPV4ONLY_ARPA_ADDRESSES: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// Prefix lengths allowed by RFC 6052, in the order they are tried when discovering a prefix.
+/// The index of a length is also its prefix length code in a PREF64 option ([RFC 8781]).
+///
+/// [RFC 8781]: https://www.rfc-editor.org/rfc/rfc8781
+const PREFIX_LENGTHS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+
+/// ICMPv6 type of router advertisements.
+const ROUTER_ADVERTISEMENT: u8 = 134;
+
+/// Length of the fixed part of a router advertisement, which precedes its options.
+const ROUTER_ADVERTISEMENT_LEN: usize = 16;
+
+/// NDP option type of PREF64 options.
+const PREF64_OPTION: u8 = 38;
+
+/// Length of a PREF64 option.
+const PREF64_OPTION_LEN: usize = 16;
+
+/// Index of the octet containing bits 64 to 71 of the address, which must be zero.
+const U_OCTET: usize = 8;
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+#[error(display = "Invalid NAT64 prefix length: {}", _0)]
+pub struct InvalidPrefixLength(pub u8);
+
+/// An IPv6 prefix that IPv4 addresses are embedded in to be reached through NAT64.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Nat64Prefix {
+    prefix: Ipv6Addr,
+    prefix_len: u8,
+}
+
+impl Nat64Prefix {
+    /// The well-known prefix `64:ff9b::/96`.
+    pub const WELL_KNOWN: Nat64Prefix = Nat64Prefix {
+        prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+        prefix_len: 96,
+    };
+
+    /// Creates a prefix of the given length. Any bits of `prefix` after the first `prefix_len`
+    /// bits are ignored.
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> Result<Self, InvalidPrefixLength> {
+        if !PREFIX_LENGTHS.contains(&prefix_len) {
+            return Err(InvalidPrefixLength(prefix_len));
+        }
+        let mask = u128::MAX << (128 - u32::from(prefix_len));
+        Ok(Nat64Prefix {
+            prefix: Ipv6Addr::from(u128::from(prefix) & mask),
+            prefix_len,
+        })
+    }
+
+    /// Returns the NAT64 prefix that a DNS64 resolver used to synthesize `addr` from one of the
+    /// addresses of [`IPV4ONLY_ARPA`], or `None` if `addr` was not synthesized.
+    pub fn from_ipv4only_arpa(addr: Ipv6Addr) -> Option<Self> {
+        PREFIX_LENGTHS.iter().find_map(|&prefix_len| {
+            let embedded = embedded_ipv4(addr, prefix_len)?;
+            if IPV4ONLY_ARPA_ADDRESSES.contains(&embedded) {
+                Self::new(addr, prefix_len).ok()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the NAT64 prefix of the first valid PREF64 option ([RFC 8781]) in `message`, which
+    /// must be an ICMPv6 router advertisement. Returns `None` if `message` is not a router
+    /// advertisement, or if it has no PREF64 option with a nonzero lifetime.
+    ///
+    /// [RFC 8781]: https://www.rfc-editor.org/rfc/rfc8781
+    pub fn from_router_advertisement(message: &[u8]) -> Option<Self> {
+        if message.len() < ROUTER_ADVERTISEMENT_LEN || message[..2] != [ROUTER_ADVERTISEMENT, 0] {
+            return None;
+        }
+        let mut options = &message[ROUTER_ADVERTISEMENT_LEN..];
+        // Each option starts with its type and its length in units of 8 octets
+        while let [option_type, len, ..] = *options {
+            let len = usize::from(len) * 8;
+            if len == 0 || len > options.len() {
+                return None;
+            }
+            let (option, rest) = options.split_at(len);
+            if option_type == PREF64_OPTION {
+                if let Some(prefix) = Self::from_pref64_option(option) {
+                    return Some(prefix);
+                }
+            }
+            options = rest;
+        }
+        None
+    }
+
+    fn from_pref64_option(option: &[u8]) -> Option<Self> {
+        if option.len() != PREF64_OPTION_LEN {
+            return None;
+        }
+        // The scaled lifetime takes up the first 13 bits, and the prefix length code the rest
+        let lifetime_and_plc = u16::from_be_bytes([option[2], option[3]]);
+        if lifetime_and_plc >> 3 == 0 {
+            return None;
+        }
+        let prefix_len = *PREFIX_LENGTHS.get(usize::from(lifetime_and_plc & 0b111))?;
+        // Only the first 96 bits of the prefix are included
+        let mut prefix = [0u8; 16];
+        prefix[..12].copy_from_slice(&option[4..]);
+        Self::new(Ipv6Addr::from(prefix), prefix_len).ok()
+    }
+
+    pub fn prefix(&self) -> Ipv6Addr {
+        self.prefix
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns the IPv6 address that `addr` is reached at through NAT64.
+    pub fn synthesize(&self, addr: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.prefix.octets();
+        for (index, octet) in ipv4_octet_indices(self.prefix_len).zip(addr.octets()) {
+            octets[index] = octet;
+        }
+        Ipv6Addr::from(octets)
+    }
+}
+
+impl fmt::Display for Nat64Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.prefix, self.prefix_len)
+    }
+}
+
+/// Returns the IPv4 address embedded in `addr` for a prefix of the given length, or `None` if the
+/// address is not in the format of RFC 6052.
+fn embedded_ipv4(addr: Ipv6Addr, prefix_len: u8) -> Option<Ipv4Addr> {
+    let octets = addr.octets();
+    if prefix_len < 96 && octets[U_OCTET] != 0 {
+        return None;
+    }
+    let mut embedded = [0u8; 4];
+    for (octet, index) in embedded.iter_mut().zip(ipv4_octet_indices(prefix_len)) {
+        *octet = octets[index];
+    }
+    Some(Ipv4Addr::from(embedded))
+}
+
+/// Returns the indices of the octets that an IPv4 address is embedded in, skipping the u-octet.
+fn ipv4_octet_indices(prefix_len: u8) -> impl Iterator<Item = usize> {
+    (usize::from(prefix_len / 8)..16)
+        .filter(|&index| index != U_OCTET)
+        .take(4)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Examples from section 2.4 of RFC 6052.
+    const RFC_6052_EXAMPLES: [(&str, u8, &str); 6] = [
+        ("2001:db8::", 32, "2001:db8:c000:221::"),
+        ("2001:db8:100::", 40, "2001:db8:1c0:2:21::"),
+        ("2001:db8:122::", 48, "2001:db8:122:c000:2:2100::"),
+        ("2001:db8:122:300::", 56, "2001:db8:122:3c0:0:221::"),
+        ("2001:db8:122:344::", 64, "2001:db8:122:344:c0:2:2100:0"),
+        ("2001:db8:122:344::", 96, "2001:db8:122:344::192.0.2.33"),
+    ];
+
+    #[test]
+    fn test_synthesize() {
+        let addr = Ipv4Addr::new(192, 0, 2, 33);
+        for (prefix, prefix_len, expected) in RFC_6052_EXAMPLES {
+            let prefix = Nat64Prefix::new(prefix.parse().unwrap(), prefix_len).unwrap();
+            assert_eq!(
+                prefix.synthesize(addr),
+                expected.parse::<Ipv6Addr>().unwrap()
+            );
+        }
+        assert_eq!(
+            Nat64Prefix::WELL_KNOWN.synthesize(addr),
+            "64:ff9b::c000:221".parse::<Ipv6Addr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_prefix_length() {
+        assert_eq!(
+            Nat64Prefix::new(Ipv6Addr::UNSPECIFIED, 33),
+            Err(InvalidPrefixLength(33))
+        );
+    }
+
+    #[test]
+    fn test_from_ipv4only_arpa() {
+        for (prefix, prefix_len, _) in RFC_6052_EXAMPLES {
+            let prefix = Nat64Prefix::new(prefix.parse().unwrap(), prefix_len).unwrap();
+            for addr in IPV4ONLY_ARPA_ADDRESSES {
+                assert_eq!(
+                    Nat64Prefix::from_ipv4only_arpa(prefix.synthesize(addr)),
+                    Some(prefix)
+                );
+            }
+        }
+        assert_eq!(
+            Nat64Prefix::from_ipv4only_arpa("2001:db8::1".parse().unwrap()),
+            None
+        );
+    }
+
+    /// Returns a router advertisement with an MTU option followed by the given options.
+    fn router_advertisement(options: &[[u8; PREF64_OPTION_LEN]]) -> Vec<u8> {
+        let mut message = vec![ROUTER_ADVERTISEMENT, 0, 0, 0, 64, 0, 0x07, 0x08];
+        message.extend_from_slice(&[0; 8]);
+        message.extend_from_slice(&[5, 1, 0, 0, 0, 0, 0x05, 0xdc]);
+        for option in options {
+            message.extend_from_slice(option);
+        }
+        message
+    }
+
+    /// Returns a PREF64 option with a lifetime of 600 seconds.
+    fn pref64_option(prefix: Ipv6Addr, plc: u8) -> [u8; PREF64_OPTION_LEN] {
+        let lifetime_and_plc = (75u16 << 3) | u16::from(plc);
+        let mut option = [0; PREF64_OPTION_LEN];
+        option[..2].copy_from_slice(&[PREF64_OPTION, 2]);
+        option[2..4].copy_from_slice(&lifetime_and_plc.to_be_bytes());
+        option[4..].copy_from_slice(&prefix.octets()[..12]);
+        option
+    }
+
+    #[test]
+    fn test_from_router_advertisement() {
+        for (prefix, prefix_len, _) in RFC_6052_EXAMPLES {
+            let prefix = Nat64Prefix::new(prefix.parse().unwrap(), prefix_len).unwrap();
+            let plc = PREFIX_LENGTHS
+                .iter()
+                .position(|len| *len == prefix_len)
+                .unwrap();
+            let message = router_advertisement(&[pref64_option(prefix.prefix(), plc as u8)]);
+            assert_eq!(
+                Nat64Prefix::from_router_advertisement(&message),
+                Some(prefix)
+            );
+        }
+
+        let mut expired = pref64_option(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 0);
+        expired[2..4].copy_from_slice(&[0, 0]);
+        let valid = pref64_option(Nat64Prefix::WELL_KNOWN.prefix(), 0);
+        assert_eq!(
+            Nat64Prefix::from_router_advertisement(&router_advertisement(&[expired, valid])),
+            Some(Nat64Prefix::WELL_KNOWN)
+        );
+    }
+
+    #[test]
+    fn test_from_invalid_router_advertisement() {
+        assert_eq!(
+            Nat64Prefix::from_router_advertisement(&router_advertisement(&[])),
+            None
+        );
+
+        let option = pref64_option(Nat64Prefix::WELL_KNOWN.prefix(), 0);
+        // Invalid prefix length code
+        let mut invalid_plc = option;
+        invalid_plc[3] |= 0b111;
+        // Truncated option
+        let mut truncated = router_advertisement(&[option]);
+        truncated.pop();
+        // Router solicitation
+        let mut solicitation = router_advertisement(&[option]);
+        solicitation[0] = 133;
+
+        for message in [
+            router_advertisement(&[invalid_plc]),
+            truncated,
+            solicitation,
+        ] {
+            assert_eq!(Nat64Prefix::from_router_advertisement(&message), None);
+        }
+    }
+}
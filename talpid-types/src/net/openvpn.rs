@@ -21,6 +21,9 @@ pub struct ConnectionConfig {
     pub endpoint: Endpoint,
     pub username: String,
     pub password: String,
+    /// Certificates and keys used instead of the ones bundled with the app.
+    #[serde(default)]
+    pub key_material: KeyMaterial,
 }
 
 impl ConnectionConfig {
@@ -29,10 +32,31 @@ impl ConnectionConfig {
             endpoint,
             username,
             password,
+            key_material: KeyMaterial::default(),
         }
     }
 }
 
+/// PEM-encoded certificates and keys for connecting to a server that is not a Mullvad relay.
+/// Stored in [`ConnectionConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KeyMaterial {
+    /// CA certificates that the server is verified against. The CA bundled with the app is used
+    /// if this is not set.
+    pub ca: Option<String>,
+    /// Client certificate.
+    pub cert: Option<String>,
+    /// Private key of the client certificate.
+    pub key: Option<String>,
+    /// Static key that authenticates the TLS control channel.
+    pub tls_auth: Option<String>,
+    /// Direction in which `tls_auth` is used.
+    pub key_direction: Option<u8>,
+    /// Static key that authenticates and encrypts the TLS control channel.
+    pub tls_crypt: Option<String>,
+}
+
 /// `TunnelOptions` contains options for an OpenVPN tunnel that should be applied
 /// irrespective of the relay parameters - i.e. have nothing to do with the particular
 /// OpenVPN server, but do affect the connection.
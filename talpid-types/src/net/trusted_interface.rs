@@ -0,0 +1,180 @@
+//! Network interfaces of other VPNs, such as overlay networks, whose traffic is allowed outside the
+//! tunnel.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Longest interface name allowed by Linux, excluding the terminating nul byte.
+const MAX_NAME_LEN: usize = 15;
+
+/// Suffix that makes a trusted interface match every interface whose name starts with the rest of
+/// the name.
+const WILDCARD: char = '*';
+
+/// Prefixes of the names of tunnel interfaces created by the app, and of the names that Linux and
+/// udev give to physical interfaces. Trusting any of these would let traffic bypass the tunnel.
+const RESERVED_PREFIXES: [&str; 8] = ["wg0-mullvad", "tun", "eth", "en", "wl", "ww", "em", "ib"];
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error(display = "The interface name must not be empty")]
+    Empty,
+
+    #[error(
+        display = "Interface names are at most {} characters long",
+        MAX_NAME_LEN
+    )]
+    TooLong,
+
+    #[error(display = "Interface names must not contain '/', ':' or whitespace")]
+    InvalidCharacter,
+
+    #[error(display = "A '{}' is only allowed at the end of the name", WILDCARD)]
+    MisplacedWildcard,
+
+    #[error(display = "The name must not match every interface")]
+    AllInterfaces,
+
+    #[error(display = "The name must not match the tunnel interface or a physical interface")]
+    Reserved,
+}
+
+/// The name of an interface whose traffic, and routes, should not go through the tunnel. A name
+/// ending with `*` matches every interface that starts with the rest of the name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TrustedInterface(String);
+
+impl TrustedInterface {
+    pub fn new(name: String) -> Result<Self, Error> {
+        if name.is_empty() {
+            return Err(Error::Empty);
+        }
+        if name.len() > MAX_NAME_LEN {
+            return Err(Error::TooLong);
+        }
+        if name == "." || name == ".." || name.contains(['/', ':']) {
+            return Err(Error::InvalidCharacter);
+        }
+        if name.chars().any(char::is_whitespace) {
+            return Err(Error::InvalidCharacter);
+        }
+        match name.find(WILDCARD) {
+            Some(0) => return Err(Error::AllInterfaces),
+            Some(index) if index != name.len() - 1 => return Err(Error::MisplacedWildcard),
+            _ => (),
+        }
+        let iface = TrustedInterface(name);
+        if RESERVED_PREFIXES
+            .iter()
+            .any(|reserved| iface.overlaps_prefix(reserved))
+        {
+            return Err(Error::Reserved);
+        }
+        Ok(iface)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the prefix that interface names must start with if this is a wildcard, or `None`
+    /// if the name must match exactly.
+    pub fn wildcard_prefix(&self) -> Option<&str> {
+        self.0.strip_suffix(WILDCARD)
+    }
+
+    /// Returns whether the interface named `iface` is trusted.
+    pub fn matches(&self, iface: &str) -> bool {
+        match self.wildcard_prefix() {
+            Some(prefix) => iface.starts_with(prefix),
+            None => iface == self.0,
+        }
+    }
+
+    /// Returns whether this matches any interface whose name starts with `prefix`.
+    fn overlaps_prefix(&self, prefix: &str) -> bool {
+        match self.wildcard_prefix() {
+            Some(own_prefix) => own_prefix.starts_with(prefix) || prefix.starts_with(own_prefix),
+            None => self.0.starts_with(prefix),
+        }
+    }
+}
+
+impl FromStr for TrustedInterface {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.to_owned())
+    }
+}
+
+impl fmt::Display for TrustedInterface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(TrustedInterface::new("".to_owned()), Err(Error::Empty));
+        assert_eq!(
+            TrustedInterface::new("averylongifname0".to_owned()),
+            Err(Error::TooLong)
+        );
+        assert_eq!(
+            TrustedInterface::new("wg 0".to_owned()),
+            Err(Error::InvalidCharacter)
+        );
+        assert_eq!(
+            TrustedInterface::new("eth0:1".to_owned()),
+            Err(Error::InvalidCharacter)
+        );
+        assert_eq!(
+            TrustedInterface::new("*".to_owned()),
+            Err(Error::AllInterfaces)
+        );
+        assert_eq!(
+            TrustedInterface::new("zt*0".to_owned()),
+            Err(Error::MisplacedWildcard)
+        );
+        for name in [
+            "eth0",
+            "wlan0",
+            "enp3s0",
+            "tun0",
+            "wg0-mullvad",
+            "e*",
+            "w*",
+            "wg*",
+            "t*",
+        ] {
+            assert_eq!(
+                TrustedInterface::new(name.to_owned()),
+                Err(Error::Reserved),
+                "{name}"
+            );
+        }
+        for name in ["tailscale0", "zt*", "averylongifname", "wg1", "nebula1"] {
+            assert!(TrustedInterface::new(name.to_owned()).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let exact = TrustedInterface::new("tailscale0".to_owned()).unwrap();
+        assert!(exact.matches("tailscale0"));
+        assert!(!exact.matches("tailscale01"));
+        assert_eq!(exact.wildcard_prefix(), None);
+
+        let wildcard = TrustedInterface::new("zt*".to_owned()).unwrap();
+        assert!(wildcard.matches("ztc3qtnr5b"));
+        assert!(wildcard.matches("zt"));
+        assert!(!wildcard.matches("wg0"));
+        assert_eq!(wildcard.wildcard_prefix(), Some("zt"));
+    }
+}
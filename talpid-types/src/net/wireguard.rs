@@ -5,6 +5,8 @@ use std::{
     cmp, fmt,
     hash::{Hash, Hasher},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
 };
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -74,10 +76,132 @@ pub struct TunnelConfig {
 pub struct TunnelOptions {
     /// MTU for the wireguard tunnel
     pub mtu: Option<u16>,
+    /// Probe the path MTU once the tunnel is up and lower the tunnel MTU to match it
+    pub mtu_discovery: bool,
     /// Perform PQ-safe PSK exchange when connecting
     pub quantum_resistant: bool,
+    /// How the connectivity monitor verifies that the tunnel works
+    pub connectivity_check: ConnectivityCheckOptions,
 }
 
+/// Options for the connectivity monitor, which infers whether the tunnel works from the traffic
+/// flowing through it and sends probes when there is too little traffic to tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectivityCheckOptions {
+    /// Kind of traffic sent to the gateway to provoke a response
+    pub probe: LivenessProbe,
+    /// Time to wait for incoming traffic after sending traffic, before probing
+    pub rx_timeout: Duration,
+    /// Time without any traffic before probing
+    pub traffic_timeout: Duration,
+    /// Time to wait for incoming traffic after the first probe, before the tunnel is considered
+    /// broken
+    pub probe_timeout: Duration,
+    /// Time to wait for incoming traffic when establishing the tunnel. It is doubled for every
+    /// retry, up to `probe_timeout`.
+    pub establish_timeout: Duration,
+}
+
+impl Default for ConnectivityCheckOptions {
+    fn default() -> Self {
+        Self {
+            probe: LivenessProbe::default(),
+            rx_timeout: Duration::from_secs(5),
+            traffic_timeout: Duration::from_secs(120),
+            probe_timeout: Duration::from_secs(15),
+            establish_timeout: Duration::from_secs(4),
+        }
+    }
+}
+
+impl ConnectivityCheckOptions {
+    /// Returns an error if the timeouts cannot be used by the connectivity monitor, either because
+    /// one of them is zero or because they contradict each other.
+    pub fn validate(&self) -> Result<(), ConnectivityCheckError> {
+        if [
+            self.rx_timeout,
+            self.traffic_timeout,
+            self.probe_timeout,
+            self.establish_timeout,
+        ]
+        .contains(&Duration::ZERO)
+        {
+            return Err(ConnectivityCheckError::ZeroTimeout);
+        }
+        if self.rx_timeout > self.traffic_timeout {
+            return Err(ConnectivityCheckError::RxTimeoutExceedsTrafficTimeout);
+        }
+        if self.establish_timeout < PROBE_INTERVAL {
+            return Err(ConnectivityCheckError::EstablishTimeoutTooShort);
+        }
+        if self.establish_timeout > self.probe_timeout {
+            return Err(ConnectivityCheckError::EstablishTimeoutExceedsProbeTimeout);
+        }
+        Ok(())
+    }
+}
+
+/// Time between probes sent by the connectivity monitor while it waits for a response
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Returned by [`ConnectivityCheckOptions::validate`].
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConnectivityCheckError {
+    #[error(display = "Connectivity check timeouts must not be zero")]
+    ZeroTimeout,
+
+    #[error(display = "The RX timeout must not be longer than the traffic timeout")]
+    RxTimeoutExceedsTrafficTimeout,
+
+    #[error(display = "The establish timeout must not be shorter than the probe interval")]
+    EstablishTimeoutTooShort,
+
+    #[error(display = "The establish timeout must not be longer than the probe timeout")]
+    EstablishTimeoutExceedsProbeTimeout,
+}
+
+/// Kind of traffic used to check that a tunnel is alive
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LivenessProbe {
+    /// ICMP echo requests
+    #[default]
+    Icmp,
+    /// TCP connection attempts
+    Tcp,
+    /// DNS queries
+    Dns,
+}
+
+impl fmt::Display for LivenessProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LivenessProbe::Icmp => f.write_str("icmp"),
+            LivenessProbe::Tcp => f.write_str("tcp"),
+            LivenessProbe::Dns => f.write_str("dns"),
+        }
+    }
+}
+
+impl FromStr for LivenessProbe {
+    type Err = LivenessProbeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "icmp" => Ok(LivenessProbe::Icmp),
+            "tcp" => Ok(LivenessProbe::Tcp),
+            "dns" => Ok(LivenessProbe::Dns),
+            _ => Err(LivenessProbeParseError),
+        }
+    }
+}
+
+/// Returned when `LivenessProbe::from_str` fails to convert a string into a [`LivenessProbe`].
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+#[error(display = "Not a valid liveness probe")]
+pub struct LivenessProbeParseError;
+
 /// Wireguard x25519 private key
 #[derive(Clone, Zeroize, ZeroizeOnDrop)]
 pub struct PrivateKey(x25519_dalek::StaticSecret);
@@ -296,3 +420,66 @@ fn key_from_base64<K: From<[u8; 32]>>(key: &str) -> Result<K, InvalidKey> {
     key.copy_from_slice(&bytes);
     Ok(From::from(key))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_connectivity_check() {
+        let default = ConnectivityCheckOptions::default();
+        assert_eq!(default.validate(), Ok(()));
+
+        let invalid = [
+            (
+                ConnectivityCheckOptions {
+                    rx_timeout: Duration::ZERO,
+                    ..default
+                },
+                ConnectivityCheckError::ZeroTimeout,
+            ),
+            (
+                ConnectivityCheckOptions {
+                    probe_timeout: Duration::ZERO,
+                    ..default
+                },
+                ConnectivityCheckError::ZeroTimeout,
+            ),
+            (
+                ConnectivityCheckOptions {
+                    rx_timeout: Duration::from_secs(10),
+                    traffic_timeout: Duration::from_secs(5),
+                    ..default
+                },
+                ConnectivityCheckError::RxTimeoutExceedsTrafficTimeout,
+            ),
+            (
+                ConnectivityCheckOptions {
+                    establish_timeout: PROBE_INTERVAL - Duration::from_millis(1),
+                    ..default
+                },
+                ConnectivityCheckError::EstablishTimeoutTooShort,
+            ),
+            (
+                ConnectivityCheckOptions {
+                    establish_timeout: Duration::from_secs(20),
+                    probe_timeout: Duration::from_secs(10),
+                    ..default
+                },
+                ConnectivityCheckError::EstablishTimeoutExceedsProbeTimeout,
+            ),
+        ];
+        for (options, error) in invalid {
+            assert_eq!(options.validate(), Err(error), "{options:?}");
+        }
+
+        let shortest = ConnectivityCheckOptions {
+            rx_timeout: Duration::from_secs(1),
+            traffic_timeout: Duration::from_secs(1),
+            probe_timeout: PROBE_INTERVAL,
+            establish_timeout: PROBE_INTERVAL,
+            ..default
+        };
+        assert_eq!(shortest.validate(), Ok(()));
+    }
+}
@@ -2,9 +2,9 @@ use crate::net::TunnelEndpoint;
 #[cfg(target_os = "android")]
 use jnix::IntoJava;
 use serde::{Deserialize, Serialize};
-use std::fmt;
 #[cfg(target_os = "android")]
 use std::net::IpAddr;
+use std::{fmt, time::SystemTime};
 
 /// Event emitted from the states in `talpid_core::tunnel_state_machine` when the tunnel state
 /// machine enters a new state.
@@ -14,9 +14,12 @@ pub enum TunnelStateTransition {
     Disconnected {
         /// Whether internet access is blocked due to lockdown mode
         locked_down: bool,
+        /// Progress of the captive portal login, if one is in progress or has just ended
+        captive_portal: Option<CaptivePortalLogin>,
     },
-    /// Network is secured but tunnel is still connecting.
-    Connecting(TunnelEndpoint),
+    /// Network is secured but tunnel is still connecting. Also contains the progress of the
+    /// captive portal login, if one is in progress or has just ended.
+    Connecting(TunnelEndpoint, Option<CaptivePortalLogin>),
     /// Tunnel is connected.
     Connected(TunnelEndpoint),
     /// Disconnecting tunnel.
@@ -54,6 +57,10 @@ pub struct ErrorState {
         jnix(map = "|block_failure| block_failure.is_none()")
     )]
     block_failure: Option<FirewallPolicyError>,
+    /// Progress of the captive portal login, if one is in progress or has just ended
+    #[serde(default)]
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    captive_portal: Option<CaptivePortalLogin>,
 }
 
 impl ErrorState {
@@ -61,9 +68,15 @@ impl ErrorState {
         Self {
             cause,
             block_failure,
+            captive_portal: None,
         }
     }
 
+    pub fn with_captive_portal(mut self, captive_portal: Option<CaptivePortalLogin>) -> Self {
+        self.captive_portal = captive_portal;
+        self
+    }
+
     pub fn is_blocking(&self) -> bool {
         self.block_failure.is_none()
     }
@@ -75,6 +88,59 @@ impl ErrorState {
     pub fn block_failure(&self) -> Option<&FirewallPolicyError> {
         self.block_failure.as_ref()
     }
+
+    pub fn captive_portal(&self) -> Option<&CaptivePortalLogin> {
+        self.captive_portal.as_ref()
+    }
+}
+
+/// Progress of a user-initiated captive portal login. While a login is active, the firewall lets
+/// through DNS traffic to the gateways of the network, and HTTP and HTTPS traffic to the gateways
+/// and the captive portal probe, in the blocking states, so that a captive portal can be used to
+/// get online. Without a login, a portal is only detected while blocking.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "state", content = "details")]
+pub enum CaptivePortalLogin {
+    /// Captive portal traffic is allowed until `expires`, or until the probe gets through.
+    Active {
+        expires: SystemTime,
+        /// Whether the latest probe was intercepted by a captive portal
+        portal_detected: bool,
+    },
+    /// The probe got through, so the login ended.
+    Succeeded,
+    /// The login ended before the probe got through.
+    Expired,
+    /// The login was cancelled.
+    Cancelled,
+    /// No login is active, but a captive portal was detected while blocking.
+    Detected,
+}
+
+impl CaptivePortalLogin {
+    pub fn is_active(&self) -> bool {
+        matches!(self, CaptivePortalLogin::Active { .. })
+    }
+}
+
+impl fmt::Display for CaptivePortalLogin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptivePortalLogin::Active {
+                portal_detected: true,
+                ..
+            } => f.write_str("captive portal detected, waiting for login"),
+            CaptivePortalLogin::Active {
+                portal_detected: false,
+                ..
+            } => f.write_str("waiting for captive portal"),
+            CaptivePortalLogin::Succeeded => f.write_str("logged in to captive portal"),
+            CaptivePortalLogin::Expired => f.write_str("captive portal login timed out"),
+            CaptivePortalLogin::Cancelled => f.write_str("captive portal login cancelled"),
+            CaptivePortalLogin::Detected => f.write_str("captive portal detected"),
+        }
+    }
 }
 
 /// Reason for the tunnel state machine entering an [`ErrorState`].
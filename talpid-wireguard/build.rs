@@ -19,6 +19,13 @@ fn main() {
         _ => panic!("Unsupported platform: {target_os}"),
     };
 
+    // wireguard-go may be left out on Linux, where other implementations are available
+    let build_wireguard_go =
+        target_os != "linux" || env::var_os("CARGO_FEATURE_WIREGUARD_GO").is_some();
+    if !build_wireguard_go {
+        return;
+    }
+
     println!("cargo:rustc-link-lib{link_type}=wg");
 
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
@@ -3,7 +3,11 @@ use std::{
     ffi::CString,
     net::{Ipv4Addr, Ipv6Addr},
 };
-use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, GenericTunnelOptions};
+use talpid_types::net::{
+    obfuscation::ObfuscatorConfig,
+    wireguard::{self, ConnectivityCheckOptions},
+    GenericTunnelOptions,
+};
 
 /// Config required to set up a single WireGuard tunnel
 #[derive(Debug, Clone)]
@@ -20,6 +24,9 @@ pub struct Config {
     pub ipv6_gateway: Option<Ipv6Addr>,
     /// Maximum transmission unit for the tunnel
     pub mtu: u16,
+    /// Probe the path MTU once the tunnel is up and lower `mtu` to match it
+    #[cfg(target_os = "linux")]
+    pub mtu_discovery: bool,
     /// Firewall mark
     #[cfg(target_os = "linux")]
     pub fwmark: Option<u32>,
@@ -28,6 +35,8 @@ pub struct Config {
     pub enable_ipv6: bool,
     /// Obfuscator config to be used for reaching the relay.
     pub obfuscator_config: Option<ObfuscatorConfig>,
+    /// Options for the connectivity monitor
+    pub connectivity_check: ConnectivityCheckOptions,
 }
 
 /// Set the MTU to the lowest possible whilst still allowing for IPv6 to help with wireless
@@ -90,10 +99,13 @@ impl Config {
             ipv6_gateway,
             mtu,
             #[cfg(target_os = "linux")]
+            mtu_discovery: wg_options.mtu_discovery,
+            #[cfg(target_os = "linux")]
             fwmark: connection.fwmark,
             #[cfg(target_os = "linux")]
             enable_ipv6: generic_options.enable_ipv6,
             obfuscator_config: obfuscator_config.to_owned(),
+            connectivity_check: wg_options.connectivity_check,
         };
 
         for peer in config.peers_mut() {
@@ -8,6 +8,7 @@ use std::{
     sync::{mpsc, Mutex, Weak},
     time::{Duration, Instant},
 };
+use talpid_types::net::wireguard::{ConnectivityCheckOptions, LivenessProbe, PROBE_INTERVAL};
 
 use super::{Tunnel, TunnelError};
 
@@ -16,24 +17,9 @@ const DELAY_ON_INITIAL_SETUP: Duration = Duration::from_millis(50);
 /// Sleep time used when checking if an established connection is still working.
 const REGULAR_LOOP_SLEEP: Duration = Duration::from_secs(1);
 
-/// Timeout for waiting on receiving traffic after sending outgoing traffic.  Once this timeout is
-/// hit, a ping will be sent every `SECONDS_PER_PING` until `PING_TIMEOUT` is reached, or traffic
-/// is received.
-const BYTES_RX_TIMEOUT: Duration = Duration::from_secs(5);
-/// Timeout for waiting on receiving or sending any traffic.  Once this timeout is hit, a ping will
-/// be sent every `SECONDS_PER_PING` until `PING_TIMEOUT` is reached or traffic is received.
-const TRAFFIC_TIMEOUT: Duration = Duration::from_secs(120);
-/// Timeout for waiting on receiving traffic after sending the first ICMP packet.  Once this
-/// timeout is reached, it is assumed that the connection is lost.
-const PING_TIMEOUT: Duration = Duration::from_secs(15);
-/// Timeout for receiving traffic when establishing a connection.
-const ESTABLISH_TIMEOUT: Duration = Duration::from_secs(4);
-/// `ESTABLISH_TIMEOUT` is multiplied by this after each failed connection attempt.
+/// `ConnectivityCheckOptions::establish_timeout` is multiplied by this after each failed
+/// connection attempt. The timeout never exceeds `ConnectivityCheckOptions::probe_timeout`.
 const ESTABLISH_TIMEOUT_MULTIPLIER: u32 = 2;
-/// Maximum timeout for establishing a connection.
-const MAX_ESTABLISH_TIMEOUT: Duration = PING_TIMEOUT;
-/// Number of seconds to wait between sending ICMP packets
-const SECONDS_PER_PING: Duration = Duration::from_secs(3);
 
 /// Connectivity monitor errors
 #[derive(err_derive::Error, Debug)]
@@ -60,17 +46,19 @@ pub enum Error {
 /// timeout. A connection is considered to be established the first time an increase in incoming
 /// traffic is observed.
 ///
-/// The connectivity monitor will start sending pings and start the countdown to `PING_TIMEOUT` in
-/// the following cases:
+/// The connectivity monitor will start sending probes and start the countdown to `probe_timeout`
+/// in the following cases:
 /// - In case that we have observed a bump in the outgoing traffic but no coressponding incoming
-/// traffic for longer than `BYTES_RX_TIMEOUT`, then the monitor will start pinging.
+/// traffic for longer than `rx_timeout`, then the monitor will start probing.
 /// - In case that no increase in outgoing or incoming traffic has been observed for longer than
-/// `TRAFFIC_TIMEOUT`, then the monitor will start pinging as well.
+/// `traffic_timeout`, then the monitor will start probing as well.
 ///
 /// Once a connection established, a connection is only considered broken once the connectivity
-/// monitor has started pinging and no traffic has been received for a duration of `PING_TIMEOUT`.
+/// monitor has started probing and no traffic has been received for a duration of
+/// `probe_timeout`. The kind of probe and all timeouts are given by [`ConnectivityCheckOptions`].
 pub struct ConnectivityMonitor {
     tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+    options: ConnectivityCheckOptions,
     conn_state: ConnState,
     initial_ping_timestamp: Option<Instant>,
     num_pings_sent: u32,
@@ -83,19 +71,25 @@ impl ConnectivityMonitor {
         addr: Ipv4Addr,
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
         tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+        options: ConnectivityCheckOptions,
         close_receiver: mpsc::Receiver<()>,
     ) -> Result<Self, Error> {
         let pinger = new_pinger(
             addr,
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             interface,
+            options.probe,
         )
         .map_err(Error::PingError)?;
+        if options.probe != LivenessProbe::Icmp {
+            log::debug!("Using {} liveness probes", options.probe);
+        }
 
         let now = Instant::now();
 
         Ok(Self {
             tunnel_handle,
+            options,
             conn_state: ConnState::new(now, Default::default()),
             initial_ping_timestamp: None,
             num_pings_sent: 0,
@@ -108,12 +102,12 @@ impl ConnectivityMonitor {
     // successful at the start of a connection.
     pub(super) fn establish_connectivity(&mut self, retry_attempt: u32) -> Result<bool, Error> {
         // Send initial ping to prod WireGuard into connecting.
-        self.pinger.send_icmp().map_err(Error::PingError)?;
+        self.pinger.send_probe().map_err(Error::PingError)?;
         self.establish_connectivity_inner(
             retry_attempt,
-            ESTABLISH_TIMEOUT,
+            self.options.establish_timeout,
             ESTABLISH_TIMEOUT_MULTIPLIER,
-            MAX_ESTABLISH_TIMEOUT,
+            self.options.probe_timeout,
         )
     }
 
@@ -128,9 +122,11 @@ impl ConnectivityMonitor {
             return Ok(true);
         }
 
-        let check_timeout = cmp::min(
+        let check_timeout = establish_timeout(
+            retry_attempt,
+            timeout_initial,
+            timeout_multiplier,
             max_timeout,
-            timeout_initial.saturating_mul(timeout_multiplier.saturating_pow(retry_attempt)),
         );
 
         let start = Instant::now();
@@ -184,7 +180,7 @@ impl ConnectivityMonitor {
 
     /// Returns true if connection is established
     fn check_connectivity(&mut self, now: Instant) -> Result<bool, Error> {
-        self.check_connectivity_interval(now, PING_TIMEOUT)
+        self.check_connectivity_interval(now, self.options.probe_timeout)
     }
 
     /// Returns true if connection is established
@@ -204,7 +200,7 @@ impl ConnectivityMonitor {
                 }
 
                 self.maybe_send_ping(now)?;
-                Ok(!self.ping_timed_out(timeout) && self.conn_state.connected())
+                Ok(!self.ping_timed_out(now, timeout) && self.conn_state.connected())
             }
         }
     }
@@ -221,18 +217,20 @@ impl ConnectivityMonitor {
     }
 
     fn maybe_send_ping(&mut self, now: Instant) -> Result<(), Error> {
-        // Only send out a ping if we haven't received a byte in a while or no traffic has flowed
-        // in the last 2 minutes, but if a ping already has been sent out, only send one out every
-        // 3 seconds.
-        if (self.conn_state.rx_timed_out() || self.conn_state.traffic_timed_out())
+        // Only send out a probe if we haven't received a byte in a while or no traffic has flowed
+        // in a while, but if a probe already has been sent out, only send one out every 3
+        // seconds.
+        if (self.conn_state.rx_timed_out(now, &self.options)
+            || self.conn_state.traffic_timed_out(now, &self.options))
             && self
                 .initial_ping_timestamp
                 .map(|initial_ping_timestamp| {
-                    initial_ping_timestamp.elapsed() / self.num_pings_sent < SECONDS_PER_PING
+                    now.saturating_duration_since(initial_ping_timestamp) / self.num_pings_sent
+                        < PROBE_INTERVAL
                 })
                 .unwrap_or(true)
         {
-            self.pinger.send_icmp().map_err(Error::PingError)?;
+            self.pinger.send_probe().map_err(Error::PingError)?;
             if self.initial_ping_timestamp.is_none() {
                 self.initial_ping_timestamp = Some(now);
             }
@@ -241,9 +239,11 @@ impl ConnectivityMonitor {
         Ok(())
     }
 
-    fn ping_timed_out(&self, timeout: Duration) -> bool {
+    fn ping_timed_out(&self, now: Instant, timeout: Duration) -> bool {
         self.initial_ping_timestamp
-            .map(|initial_ping_timestamp| initial_ping_timestamp.elapsed() > timeout)
+            .map(|initial_ping_timestamp| {
+                now.saturating_duration_since(initial_ping_timestamp) > timeout
+            })
             .unwrap_or(false)
     }
 
@@ -255,6 +255,21 @@ impl ConnectivityMonitor {
     }
 }
 
+/// Returns how long to wait for a connection on attempt `retry_attempt`. The timeout starts at
+/// `timeout_initial`, is multiplied by `timeout_multiplier` for each attempt and never exceeds
+/// `max_timeout`.
+fn establish_timeout(
+    retry_attempt: u32,
+    timeout_initial: Duration,
+    timeout_multiplier: u32,
+    max_timeout: Duration,
+) -> Duration {
+    cmp::min(
+        max_timeout,
+        timeout_initial.saturating_mul(timeout_multiplier.saturating_pow(retry_attempt)),
+    )
+}
+
 enum ConnState {
     Connecting {
         start: Instant,
@@ -350,9 +365,11 @@ impl ConnState {
     }
 
     // check if last time data was received is too long ago
-    pub fn rx_timed_out(&self) -> bool {
+    pub fn rx_timed_out(&self, now: Instant, options: &ConnectivityCheckOptions) -> bool {
         match self {
-            ConnState::Connecting { start, .. } => start.elapsed() >= BYTES_RX_TIMEOUT,
+            ConnState::Connecting { start, .. } => {
+                now.saturating_duration_since(*start) >= options.rx_timeout
+            }
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
@@ -360,23 +377,23 @@ impl ConnState {
             } => {
                 // if last sent bytes were sent after or at the same time as last received bytes
                 tx_timestamp >= rx_timestamp &&
-                    // and the response hasn't been seen for BYTES_RX_TIMEOUT
-                    rx_timestamp.elapsed() >= BYTES_RX_TIMEOUT
+                    // and the response hasn't been seen for `rx_timeout`
+                    now.saturating_duration_since(*rx_timestamp) >= options.rx_timeout
             }
         }
     }
 
     // check if no bytes have been sent or received in a while
-    pub fn traffic_timed_out(&self) -> bool {
+    pub fn traffic_timed_out(&self, now: Instant, options: &ConnectivityCheckOptions) -> bool {
         match self {
-            ConnState::Connecting { .. } => self.rx_timed_out(),
+            ConnState::Connecting { .. } => self.rx_timed_out(now, options),
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
                 ..
             } => {
-                rx_timestamp.elapsed() >= TRAFFIC_TIMEOUT
-                    || tx_timestamp.elapsed() >= TRAFFIC_TIMEOUT
+                now.saturating_duration_since(*rx_timestamp) >= options.traffic_timeout
+                    || now.saturating_duration_since(*tx_timestamp) >= options.traffic_timeout
             }
         }
     }
@@ -397,9 +414,10 @@ mod test {
         TunnelError,
     };
     use std::{
+        collections::VecDeque,
         pin::Pin,
         sync::{
-            atomic::{AtomicBool, Ordering},
+            atomic::{AtomicBool, AtomicU32, Ordering},
             Arc, Mutex,
         },
         time::{Duration, Instant},
@@ -408,30 +426,33 @@ mod test {
     /// Test if a newly created ConnState won't have timed out or consider itself connected
     #[test]
     fn test_conn_state_no_timeout_on_start() {
+        let options = ConnectivityCheckOptions::default();
         let now = Instant::now();
         let conn_state = ConnState::new(now, Default::default());
 
         assert!(!conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(Instant::now(), &options));
+        assert!(!conn_state.traffic_timed_out(Instant::now(), &options));
     }
 
     /// Test if ConnState::Connecting will timeout after not receiving any traffic after
-    /// BYTES_RX_TIMEOUT
+    /// `rx_timeout`
     #[test]
     fn test_conn_state_timeout_after_rx_timeout() {
-        let now = Instant::now().checked_sub(BYTES_RX_TIMEOUT).unwrap();
+        let options = ConnectivityCheckOptions::default();
+        let now = Instant::now().checked_sub(options.rx_timeout).unwrap();
         let conn_state = ConnState::new(now, Default::default());
 
         assert!(!conn_state.connected());
-        assert!(conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(conn_state.rx_timed_out(Instant::now(), &options));
+        assert!(conn_state.traffic_timed_out(Instant::now(), &options));
     }
 
     /// Test if ConnState::Connecting correctly transitions into ConnState::Connected if traffic is
     /// received
     #[test]
     fn test_conn_state_connects() {
+        let options = ConnectivityCheckOptions::default();
         let start = Instant::now().checked_sub(Duration::from_secs(2)).unwrap();
         let mut conn_state = ConnState::new(start, Default::default());
         let mut stats = StatsMap::new();
@@ -445,20 +466,21 @@ mod test {
         conn_state.update(Instant::now(), stats);
 
         assert!(conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(Instant::now(), &options));
+        assert!(!conn_state.traffic_timed_out(Instant::now(), &options));
     }
 
-    /// Test if ConnState::Connected correctly times out after TRAFFIC_TIMEOUT when no traffic is
+    /// Test if ConnState::Connected correctly times out after `traffic_timeout` when no traffic is
     /// observed
     #[test]
     fn test_conn_state_traffic_times_out_after_connecting() {
+        let options = ConnectivityCheckOptions::default();
         let start = Instant::now()
-            .checked_sub(TRAFFIC_TIMEOUT + Duration::from_secs(1))
+            .checked_sub(options.traffic_timeout + Duration::from_secs(1))
             .unwrap();
         let mut conn_state = ConnState::new(start, Default::default());
 
-        let connect_time = Instant::now().checked_sub(TRAFFIC_TIMEOUT).unwrap();
+        let connect_time = Instant::now().checked_sub(options.traffic_timeout).unwrap();
         let mut stats = StatsMap::new();
         stats.insert(
             [0u8; 32],
@@ -470,16 +492,17 @@ mod test {
         conn_state.update(connect_time, stats);
 
         assert!(conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(Instant::now(), &options));
+        assert!(conn_state.traffic_timed_out(Instant::now(), &options));
     }
 
-    /// Test if ConnState::Connected correctly times out after BYTES_RX_TIMEOUT when no incoming
+    /// Test if ConnState::Connected correctly times out after `rx_timeout` when no incoming
     /// traffic is observed
     #[test]
     fn test_conn_state_rx_times_out_after_connecting() {
+        let options = ConnectivityCheckOptions::default();
         let start = Instant::now()
-            .checked_sub(BYTES_RX_TIMEOUT + Duration::from_secs(1))
+            .checked_sub(options.rx_timeout + Duration::from_secs(1))
             .unwrap();
         let mut conn_state = ConnState::new(start, Default::default());
 
@@ -493,7 +516,7 @@ mod test {
         );
         conn_state.update(start, stats);
 
-        let update_time = Instant::now().checked_sub(BYTES_RX_TIMEOUT).unwrap();
+        let update_time = Instant::now().checked_sub(options.rx_timeout).unwrap();
         let mut stats = StatsMap::new();
         stats.insert(
             [0u8; 32],
@@ -505,8 +528,8 @@ mod test {
         conn_state.update(update_time, stats);
 
         assert!(conn_state.connected());
-        assert!(conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(conn_state.rx_timed_out(Instant::now(), &options));
+        assert!(!conn_state.traffic_timed_out(Instant::now(), &options));
     }
 
     #[derive(Default)]
@@ -515,7 +538,7 @@ mod test {
     }
 
     impl Pinger for MockPinger {
-        fn send_icmp(&mut self) -> Result<(), crate::ping_monitor::Error> {
+        fn send_probe(&mut self) -> Result<(), crate::ping_monitor::Error> {
             if let Some(callback) = self.on_send_ping.as_mut() {
                 (callback)();
             }
@@ -558,6 +581,30 @@ mod test {
             }
         }
 
+        /// Returns the traffic counters in `script` as `(rx_bytes, tx_bytes)`, one entry per read.
+        /// The last entry is repeated once the script has been exhausted.
+        fn scripted(script: &[(u64, u64)]) -> Self {
+            let script: VecDeque<_> = script
+                .iter()
+                .map(|&(rx_bytes, tx_bytes)| {
+                    let mut map = stats::StatsMap::new();
+                    map.insert(Self::PEER, stats::Stats { tx_bytes, rx_bytes });
+                    map
+                })
+                .collect();
+            let script = Mutex::new(script);
+            Self {
+                on_get_stats: Box::new(move || {
+                    let mut script = script.lock().unwrap();
+                    if script.len() > 1 {
+                        Ok(script.pop_front().unwrap())
+                    } else {
+                        Ok(script.front().cloned().unwrap_or_default())
+                    }
+                }),
+            }
+        }
+
         fn never_incrementing() -> Self {
             Self {
                 on_get_stats: Box::new(|| {
@@ -614,8 +661,25 @@ mod test {
         pinger: Box<dyn Pinger>,
         tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
         close_receiver: mpsc::Receiver<()>,
+    ) -> ConnectivityMonitor {
+        mock_monitor_with_options(
+            now,
+            pinger,
+            tunnel_handle,
+            close_receiver,
+            ConnectivityCheckOptions::default(),
+        )
+    }
+
+    fn mock_monitor_with_options(
+        now: Instant,
+        pinger: Box<dyn Pinger>,
+        tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+        close_receiver: mpsc::Receiver<()>,
+        options: ConnectivityCheckOptions,
     ) -> ConnectivityMonitor {
         ConnectivityMonitor {
+            options,
             conn_state: ConnState::new(now, Default::default()),
             initial_ping_timestamp: None,
             num_pings_sent: 0,
@@ -644,21 +708,22 @@ mod test {
 
     #[test]
     /// Verify that `check_connectivity()` returns `false` if the tunnel is connected and traffic is
-    /// not flowing after `BYTES_RX_TIMEOUT` and `PING_TIMEOUT`.
+    /// not flowing after `rx_timeout` and `probe_timeout`.
     fn test_ping_times_out() {
+        let options = ConnectivityCheckOptions::default();
         let (_tunnel_anchor, tunnel) = MockTunnel::never_incrementing().into_locked();
         let (_tx, rx) = mpsc::channel();
         let pinger = MockPinger::default();
         let now = Instant::now();
         let start = now
-            .checked_sub(BYTES_RX_TIMEOUT + PING_TIMEOUT + Duration::from_secs(10))
+            .checked_sub(options.rx_timeout + options.probe_timeout + Duration::from_secs(10))
             .unwrap();
         let mut monitor = mock_monitor(start, Box::new(pinger), tunnel, rx);
 
         // Mock the state - connectivity has been established
         monitor.conn_state = connected_state(start);
         // A ping was sent to verify connectivity
-        monitor.maybe_send_ping(start).unwrap();
+        monitor.maybe_send_ping(start + options.rx_timeout).unwrap();
         assert!(!monitor.check_connectivity(now).unwrap())
     }
 
@@ -722,8 +787,9 @@ mod test {
 
     #[test]
     /// Verify that the connectivity monitor detects the tunnel timing out after no longer than
-    /// `BYTES_RX_TIMEOUT` and `PING_TIMEOUT` combined.
+    /// `rx_timeout` and `probe_timeout` combined.
     fn test_wait_loop_timeout() {
+        let options = ConnectivityCheckOptions::default();
         let should_stop = Arc::new(AtomicBool::new(false));
         let should_stop_inner = should_stop.clone();
 
@@ -770,7 +836,7 @@ mod test {
             .unwrap());
         should_stop.store(true, Ordering::SeqCst);
         assert!(result_rx
-            .recv_timeout(BYTES_RX_TIMEOUT + PING_TIMEOUT + Duration::from_secs(2))
+            .recv_timeout(options.rx_timeout + options.probe_timeout + Duration::from_secs(2))
             .unwrap()
             .is_ok());
     }
@@ -823,4 +889,142 @@ mod test {
         assert_rx(Duration::from_secs(2));
         assert_rx(Duration::from_secs(2));
     }
+
+    /// Returns options with timeouts short enough to be exercised by tests.
+    fn short_timeouts() -> ConnectivityCheckOptions {
+        ConnectivityCheckOptions {
+            rx_timeout: Duration::from_secs(1),
+            traffic_timeout: Duration::from_secs(2),
+            probe_timeout: Duration::from_secs(1),
+            establish_timeout: Duration::from_millis(500),
+            ..ConnectivityCheckOptions::default()
+        }
+    }
+
+    fn counting_pinger() -> (MockPinger, Arc<AtomicU32>) {
+        let probes_sent = Arc::new(AtomicU32::new(0));
+        let counter = probes_sent.clone();
+        let pinger = MockPinger {
+            on_send_ping: Some(Box::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })),
+        };
+        (pinger, probes_sent)
+    }
+
+    #[test]
+    /// Verify that custom timeouts are respected when outgoing traffic stops being answered.
+    fn test_custom_timeouts_detect_stall() {
+        let options = short_timeouts();
+        // Connect, then keep sending without receiving anything.
+        let mut script = vec![(0, 0), (1, 1)];
+        script.extend((2..100).map(|tx_bytes| (1, tx_bytes)));
+        let (_tunnel_anchor, tunnel) = MockTunnel::scripted(&script).into_locked();
+        let (pinger, probes_sent) = counting_pinger();
+
+        let (result_tx, result_rx) = mpsc::channel();
+        let (_stop_tx, stop_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut monitor = mock_monitor_with_options(
+                Instant::now(),
+                Box::new(pinger),
+                tunnel,
+                stop_rx,
+                options,
+            );
+            result_tx.send(monitor.establish_connectivity(0)).unwrap();
+            result_tx.send(monitor.run().map(|_| true)).unwrap();
+        });
+
+        assert!(result_rx
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap()
+            .unwrap());
+        let stall_deadline = options.rx_timeout + options.probe_timeout + REGULAR_LOOP_SLEEP * 3;
+        assert!(result_rx.recv_timeout(stall_deadline).unwrap().is_ok());
+        // One probe when connecting, and at least one when the stall was noticed
+        assert!(probes_sent.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[test]
+    /// Verify that an idle tunnel is probed after `traffic_timeout` and kept up as long as the
+    /// probes are answered.
+    fn test_probes_keep_idle_tunnel_up() {
+        let options = ConnectivityCheckOptions::default();
+        let answered = Arc::new(AtomicBool::new(false));
+        let answered_inner = answered.clone();
+
+        let mut map = stats::StatsMap::new();
+        map.insert(
+            MockTunnel::PEER,
+            stats::Stats {
+                tx_bytes: 0,
+                rx_bytes: 0,
+            },
+        );
+        let tunnel_stats = Mutex::new(map);
+        // Traffic only flows when a probe has been sent
+        let (_tunnel_anchor, tunnel) = MockTunnel::new(move || {
+            let mut tunnel_stats = tunnel_stats.lock().unwrap();
+            if answered_inner.swap(false, Ordering::SeqCst) {
+                for traffic in tunnel_stats.values_mut() {
+                    traffic.tx_bytes += 1;
+                    traffic.rx_bytes += 1;
+                }
+            }
+            Ok(tunnel_stats.clone())
+        })
+        .into_locked();
+
+        let probes_sent = Arc::new(AtomicU32::new(0));
+        let counter = probes_sent.clone();
+        let pinger = MockPinger {
+            on_send_ping: Some(Box::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                answered.store(true, Ordering::SeqCst);
+            })),
+        };
+
+        let (_tx, rx) = mpsc::channel();
+        let start = Instant::now();
+        let mut monitor = mock_monitor_with_options(start, Box::new(pinger), tunnel, rx, options);
+        monitor.conn_state = connected_state(start);
+
+        // Step through the idle period one loop iteration at a time, instead of sleeping
+        let idle_period = options.traffic_timeout * 2 + options.probe_timeout;
+        let mut elapsed = Duration::ZERO;
+        while elapsed <= idle_period {
+            // The monitor must not give up while probes are answered
+            assert!(
+                monitor.check_connectivity(start + elapsed).unwrap(),
+                "tunnel considered broken after {elapsed:?}"
+            );
+            elapsed += REGULAR_LOOP_SLEEP;
+        }
+        // At least once for every time the tunnel went idle
+        assert!(probes_sent.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[test]
+    /// Verify that the establish timeout is taken from the options and capped by the probe
+    /// timeout.
+    fn test_custom_establish_timeout() {
+        let options = short_timeouts();
+        for (attempt, expected) in [
+            (0, options.establish_timeout),
+            (1, options.establish_timeout * ESTABLISH_TIMEOUT_MULTIPLIER),
+            (2, options.probe_timeout),
+            (u32::MAX, options.probe_timeout),
+        ] {
+            assert_eq!(
+                establish_timeout(
+                    attempt,
+                    options.establish_timeout,
+                    ESTABLISH_TIMEOUT_MULTIPLIER,
+                    options.probe_timeout,
+                ),
+                expected
+            );
+        }
+    }
 }
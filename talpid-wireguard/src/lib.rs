@@ -12,7 +12,7 @@ use once_cell::sync::Lazy;
 use std::borrow::Cow;
 #[cfg(target_os = "linux")]
 use std::env;
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "linux"))]
 use std::io;
 use std::{
     convert::Infallible,
@@ -46,8 +46,12 @@ use tunnel_obfuscation::{
 pub mod config;
 mod connectivity_check;
 mod logging;
+#[cfg(target_os = "linux")]
+mod mtu_detection;
 mod ping_monitor;
 mod stats;
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+mod wireguard_boringtun;
 #[cfg(wireguard_go)]
 mod wireguard_go;
 #[cfg(target_os = "linux")]
@@ -195,6 +199,16 @@ static FORCE_USERSPACE_WIREGUARD: Lazy<bool> = Lazy::new(|| {
         .unwrap_or(false)
 });
 
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+/// Selects boringtun as the userspace implementation of WireGuard. It is always used when
+/// wireguard-go is not built.
+static USE_BORINGTUN: Lazy<bool> = Lazy::new(|| {
+    !cfg!(wireguard_go)
+        || env::var("TALPID_USERSPACE_WIREGUARD")
+            .map(|v| v == "boringtun")
+            .unwrap_or(false)
+});
+
 async fn maybe_create_obfuscator(
     config: &mut Config,
     close_msg_sender: sync_mpsc::Sender<CloseMsg>,
@@ -284,6 +298,8 @@ impl WireguardMonitor {
             setup_done_tx,
             #[cfg(target_os = "android")]
             psk_negotiation,
+            #[cfg(all(target_os = "linux", feature = "boringtun"))]
+            close_obfs_sender.clone(),
         )?;
         let iface_name = tunnel.get_interface_name();
 
@@ -315,6 +331,7 @@ impl WireguardMonitor {
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             iface_name.clone(),
             Arc::downgrade(&monitor.tunnel),
+            config.connectivity_check,
             pinger_rx,
         )
         .map_err(Error::ConnectivityMonitorError)?;
@@ -395,6 +412,13 @@ impl WireguardMonitor {
             .await
             .unwrap()?;
 
+            #[cfg(target_os = "linux")]
+            let discovered_mtu = if config.mtu_discovery {
+                Self::discover_mtu(&iface_name, &mut config).await
+            } else {
+                None
+            };
+
             // Add any default route(s) that may exist.
             args.route_manager
                 .add_routes(Self::get_post_tunnel_routes(&iface_name, &config).collect())
@@ -402,7 +426,12 @@ impl WireguardMonitor {
                 .map_err(Error::SetupRoutingError)
                 .map_err(CloseMsg::SetupError)?;
 
-            let metadata = Self::tunnel_metadata(&iface_name, &config);
+            #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+            let mut metadata = Self::tunnel_metadata(&iface_name, &config);
+            #[cfg(target_os = "linux")]
+            {
+                metadata.discovered_mtu = discovered_mtu;
+            }
             (on_event)(TunnelEvent::Up(metadata)).await;
 
             tokio::task::spawn_blocking(move || {
@@ -436,6 +465,45 @@ impl WireguardMonitor {
         Ok(monitor)
     }
 
+    /// Probes the path MTU through the tunnel and lowers the MTU of the tunnel interface if the
+    /// path cannot carry packets of the configured size. Returns the discovered MTU.
+    #[cfg(target_os = "linux")]
+    async fn discover_mtu(iface_name: &str, config: &mut Config) -> Option<u16> {
+        let gateway = config.ipv4_gateway;
+        let max_mtu = config.mtu;
+        let enable_ipv6 = config.enable_ipv6;
+        let moved_iface_name = iface_name.to_owned();
+        let result = tokio::task::spawn_blocking(move || {
+            mtu_detection::detect_mtu(gateway, moved_iface_name, max_mtu, enable_ipv6)
+        })
+        .await
+        .unwrap();
+
+        let mtu = match result {
+            Ok(mtu) => mtu,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to discover tunnel MTU")
+                );
+                return None;
+            }
+        };
+        log::info!("Discovered tunnel MTU: {mtu}");
+
+        if mtu < max_mtu {
+            if let Err(error) = set_mtu(iface_name, mtu) {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to set tunnel MTU")
+                );
+                return None;
+            }
+            config.mtu = mtu;
+        }
+        Some(mtu)
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn psk_negotiation<F>(
         tunnel: &Arc<Mutex<Option<Box<dyn Tunnel>>>>,
@@ -690,13 +758,16 @@ impl WireguardMonitor {
         #[cfg(target_os = "android")] psk_negotiation: bool,
         #[cfg(windows)] route_manager_handle: crate::routing::RouteManagerHandle,
         #[cfg(windows)] setup_done_tx: mpsc::Sender<std::result::Result<(), BoxedError>>,
+        #[cfg(all(target_os = "linux", feature = "boringtun"))] close_msg_sender: sync_mpsc::Sender<
+            CloseMsg,
+        >,
     ) -> Result<Box<dyn Tunnel>> {
         log::debug!("Tunnel MTU: {}", config.mtu);
 
         #[cfg(target_os = "linux")]
         if !*FORCE_USERSPACE_WIREGUARD {
             if will_nm_manage_dns() {
-                match wireguard_kernel::NetworkManagerTunnel::new(runtime, config) {
+                match wireguard_kernel::NetworkManagerTunnel::new(runtime.clone(), config) {
                     Ok(tunnel) => {
                         log::debug!("Using NetworkManager to use kernel WireGuard implementation");
                         return Ok(Box::new(tunnel));
@@ -711,7 +782,7 @@ impl WireguardMonitor {
                     }
                 };
             } else {
-                match wireguard_kernel::NetlinkTunnel::new(runtime, config) {
+                match wireguard_kernel::NetlinkTunnel::new(runtime.clone(), config) {
                     Ok(tunnel) => {
                         log::debug!("Using kernel WireGuard implementation");
                         return Ok(Box::new(tunnel));
@@ -728,6 +799,30 @@ impl WireguardMonitor {
             }
         }
 
+        #[cfg(all(target_os = "linux", feature = "boringtun"))]
+        if *USE_BORINGTUN {
+            let routes =
+                Self::get_tunnel_destinations(config).flat_map(Self::replace_default_prefixes);
+
+            log::debug!("Using boringtun userspace WireGuard implementation");
+            return Ok(Box::new(
+                wireguard_boringtun::BoringTunTunnel::start_tunnel(
+                    runtime,
+                    config,
+                    tun_provider,
+                    routes,
+                    close_msg_sender,
+                )
+                .map_err(Error::TunnelError)?,
+            ));
+        }
+
+        #[cfg(all(target_os = "linux", not(wireguard_go)))]
+        {
+            log::error!("No userspace WireGuard implementation is available");
+            Err(Error::TunnelError(TunnelError::FatalStartWireguardError))
+        }
+
         #[cfg(target_os = "windows")]
         {
             wireguard_nt::WgNtTunnel::start_tunnel(config, log_path, resource_dir, setup_done_tx)
@@ -765,6 +860,7 @@ impl WireguardMonitor {
             Ok(CloseMsg::Stop) | Ok(CloseMsg::ObfuscatorExpired) => Ok(()),
             Ok(CloseMsg::SetupError(error)) => Err(error),
             Ok(CloseMsg::ObfuscatorFailed(error)) => Err(error),
+            Ok(CloseMsg::TunnelFailed(error)) => Err(error),
             Err(_) => Ok(()),
         };
 
@@ -945,6 +1041,7 @@ impl WireguardMonitor {
             ips: config.tunnel.addresses.clone(),
             ipv4_gateway: config.ipv4_gateway,
             ipv6_gateway: config.ipv6_gateway,
+            discovered_mtu: None,
         }
     }
 }
@@ -957,6 +1054,7 @@ enum CloseMsg {
     SetupError(Error),
     ObfuscatorExpired,
     ObfuscatorFailed(Error),
+    TunnelFailed(Error),
 }
 
 pub(crate) trait Tunnel: Send {
@@ -1012,6 +1110,16 @@ pub enum TunnelError {
     #[error(display = "Failed to duplicate tunnel file descriptor for wireguard-go")]
     FdDuplicationError(#[error(source)] nix::Error),
 
+    /// Failed to set up a socket or device for the boringtun tunnel.
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    #[error(display = "Failed to set up boringtun tunnel")]
+    SetupSocket(#[error(source)] std::io::Error),
+
+    /// The tunnel device of the boringtun tunnel could no longer be polled.
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    #[error(display = "Failed to poll boringtun tunnel device")]
+    PollTunnelDevice(#[error(source)] std::io::Error),
+
     /// Failed to setup a tunnel device.
     #[cfg(not(windows))]
     #[error(display = "Failed to create tunnel device")]
@@ -1046,6 +1154,31 @@ pub enum TunnelError {
     LoggingError(#[error(source)] logging::Error),
 }
 
+/// Sets the MTU of a network interface.
+#[cfg(target_os = "linux")]
+fn set_mtu(interface_name: &str, mtu: u16) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, None)?;
+
+    // SAFETY: `ifreq` is a plain C struct for which all zeroes is a valid value
+    let mut request: libc::ifreq = unsafe { std::mem::zeroed() };
+    let name = interface_name.as_bytes();
+    if name.len() >= request.ifr_name.len() {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+    for (dst, src) in request.ifr_name.iter_mut().zip(name) {
+        *dst = *src as libc::c_char;
+    }
+    request.ifr_ifru.ifru_mtu = libc::c_int::from(mtu);
+
+    // SAFETY: The request is a valid `ifreq` that outlives the call
+    if unsafe { libc::ioctl(socket.as_raw_fd(), libc::SIOCSIFMTU, &request) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 #[cfg(target_os = "linux")]
 fn will_nm_manage_dns() -> bool {
     use talpid_dbus::network_manager::NetworkManager;
@@ -0,0 +1,100 @@
+//! Path MTU discovery through an established tunnel.
+//!
+//! Echo requests of decreasing size are sent to the gateway with the don't-fragment bit set. The
+//! largest request that is answered determines the MTU of the tunnel.
+
+use crate::ping_monitor::IcmpPinger;
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+
+/// The minimum MTU allowed for IPv4.
+const MIN_IPV4_MTU: u16 = 576;
+/// The minimum MTU allowed for IPv6.
+const MIN_IPV6_MTU: u16 = 1280;
+/// Difference in size between two consecutive probes.
+const PROBE_STEP: u16 = 20;
+/// How long to wait for replies after the last probe has been sent.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Errors that can occur while discovering the MTU
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    /// Failed to set up the pinger
+    #[error(display = "Failed to set up pinger")]
+    Pinger(#[error(source)] crate::ping_monitor::Error),
+
+    /// Failed to read replies
+    #[error(display = "Failed to receive replies")]
+    Receive(#[error(source)] crate::ping_monitor::Error),
+
+    /// None of the probes were answered
+    #[error(display = "No probes were answered")]
+    NoReplies,
+}
+
+/// Returns the largest MTU, no larger than `max_mtu`, for which echo requests to `gateway` are
+/// answered. This blocks until all probes have been answered or timed out.
+pub fn detect_mtu(
+    gateway: Ipv4Addr,
+    interface_name: String,
+    max_mtu: u16,
+    ipv6: bool,
+) -> Result<u16, Error> {
+    let min_mtu = if ipv6 { MIN_IPV6_MTU } else { MIN_IPV4_MTU };
+
+    let mut pinger = IcmpPinger::new(gateway, interface_name).map_err(Error::Pinger)?;
+    pinger.set_dont_fragment().map_err(Error::Pinger)?;
+
+    let mut probes = HashMap::new();
+    for size in probe_sizes(max_mtu, min_mtu) {
+        match pinger.send_sized_icmp(usize::from(size)) {
+            Ok(seq) => {
+                probes.insert(seq, size);
+            }
+            Err(error) => log::trace!("Failed to send MTU probe of size {size}: {error}"),
+        }
+    }
+
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    let mut largest_answered = None;
+    while let Some(seq) = pinger.receive_reply(deadline).map_err(Error::Receive)? {
+        let Some(size) = probes.remove(&seq) else {
+            continue;
+        };
+        largest_answered = largest_answered.max(Some(size));
+        if size == max_mtu || probes.is_empty() {
+            break;
+        }
+    }
+    largest_answered.ok_or(Error::NoReplies)
+}
+
+/// Returns the sizes to probe, from `max_mtu` down to `min_mtu`.
+fn probe_sizes(max_mtu: u16, min_mtu: u16) -> Vec<u16> {
+    let mut sizes: Vec<u16> = (min_mtu..=max_mtu)
+        .rev()
+        .step_by(usize::from(PROBE_STEP))
+        .collect();
+    if max_mtu >= min_mtu && sizes.last() != Some(&min_mtu) {
+        sizes.push(min_mtu);
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_probe_sizes() {
+        assert_eq!(probe_sizes(1380, 1320), vec![1380, 1360, 1340, 1320]);
+        assert_eq!(probe_sizes(1300, 1280), vec![1300, 1280]);
+        assert_eq!(probe_sizes(1290, 1280), vec![1290, 1280]);
+        assert_eq!(probe_sizes(1280, 1280), vec![1280]);
+        assert!(probe_sizes(1200, 1280).is_empty());
+    }
+}
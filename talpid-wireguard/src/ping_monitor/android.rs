@@ -40,7 +40,7 @@ impl Pinger {
 
 impl super::Pinger for Pinger {
     // Send an ICMP packet without waiting for a reply
-    fn send_icmp(&mut self) -> Result<(), Error> {
+    fn send_probe(&mut self) -> Result<(), Error> {
         self.try_deplete_process_list();
 
         let cmd = ping_cmd(self.addr, 1);
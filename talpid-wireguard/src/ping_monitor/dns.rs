@@ -0,0 +1,73 @@
+#[cfg(not(target_os = "windows"))]
+use super::imp::bind_to_interface;
+use super::imp::Error;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    mem::MaybeUninit,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+const DNS_PORT: u16 = 53;
+
+/// A pinger that sends DNS queries to the resolver on the gateway without waiting for responses
+pub struct Pinger {
+    sock: Socket,
+}
+
+impl Pinger {
+    pub fn new(
+        addr: Ipv4Addr,
+        #[cfg(not(target_os = "windows"))] interface_name: String,
+    ) -> Result<Self, Error> {
+        let sock =
+            Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).map_err(Error::Open)?;
+        sock.set_nonblocking(true).map_err(Error::Open)?;
+
+        #[cfg(not(target_os = "windows"))]
+        bind_to_interface(&sock, &interface_name)?;
+
+        sock.connect(&SocketAddr::new(addr.into(), DNS_PORT).into())
+            .map_err(Error::SocketOp)?;
+
+        Ok(Self { sock })
+    }
+
+    /// Discard responses to earlier queries. Only the traffic counters of the tunnel matter.
+    fn drain_responses(&self) {
+        let mut buffer = [MaybeUninit::<u8>::uninit(); 512];
+        while self.sock.recv(&mut buffer).is_ok() {}
+    }
+}
+
+impl super::Pinger for Pinger {
+    fn send_probe(&mut self) -> Result<(), Error> {
+        self.drain_responses();
+        match self.sock.send(&dns_query(rand::random())) {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(Error::Write(error)),
+        }
+    }
+}
+
+/// Returns a query for the name servers of the root zone.
+fn dns_query(id: u16) -> [u8; 17] {
+    let [id_high, id_low] = id.to_be_bytes();
+    [
+        // ID
+        id_high, id_low, //
+        // Flags - recursion desired
+        0x01, 0x00, //
+        // Question count
+        0x00, 0x01, //
+        // Answer, authority and additional record counts
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        // Name - the root
+        0x00, //
+        // Type - NS
+        0x00, 0x02, //
+        // Class - IN
+        0x00, 0x01,
+    ]
+}
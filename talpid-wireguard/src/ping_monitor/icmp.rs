@@ -1,6 +1,8 @@
 use byteorder::{NetworkEndian, WriteBytesExt};
 use rand::Rng;
 use socket2::{Domain, Protocol, Socket, Type};
+#[cfg(target_os = "linux")]
+use std::{io::Read, os::unix::io::AsRawFd, time::Instant};
 use std::{
     io::{self, Write},
     net::{Ipv4Addr, SocketAddr},
@@ -9,6 +11,10 @@ use std::{
 };
 
 const SEND_RETRY_ATTEMPTS: u32 = 10;
+#[cfg(target_os = "linux")]
+const IPV4_HEADER_SIZE: usize = 20;
+#[cfg(target_os = "linux")]
+const RECEIVE_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 /// Pinger errors
 #[derive(err_derive::Error, Debug)]
@@ -68,12 +74,8 @@ impl Pinger {
             Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).map_err(Error::Open)?;
         sock.set_nonblocking(true).map_err(Error::Open)?;
 
-        #[cfg(target_os = "linux")]
-        sock.bind_device(Some(interface_name.as_bytes()))
-            .map_err(Error::SocketOp)?;
-
-        #[cfg(target_os = "macos")]
-        Self::set_device_index(&sock, &interface_name)?;
+        #[cfg(not(target_os = "windows"))]
+        bind_to_interface(&sock, &interface_name)?;
 
         Ok(Self {
             sock,
@@ -83,18 +85,6 @@ impl Pinger {
         })
     }
 
-    #[cfg(target_os = "macos")]
-    fn set_device_index(socket: &Socket, interface_name: &str) -> Result<()> {
-        let index = nix::net::if_::if_nametoindex(interface_name).map_err(Error::DeviceIdx)?;
-        // Asserting that `index` is non-zero since otherwise `if_nametoindex` would have return
-        // an error
-        socket
-            .bind_device_by_index(std::num::NonZeroU32::new(index))
-            .map_err(Error::BindSocketByDevice)?;
-
-        Ok(())
-    }
-
     fn send_ping_request(&mut self, message: &[u8], destination: SocketAddr) -> Result<()> {
         let mut tries = 0;
         let mut result = Ok(());
@@ -122,10 +112,101 @@ impl Pinger {
         }
         Ok(())
     }
+
+    /// Set the don't-fragment bit on all requests and ignore the cached path MTU, so that requests
+    /// larger than the path MTU are dropped instead of fragmented.
+    #[cfg(target_os = "linux")]
+    pub fn set_dont_fragment(&self) -> Result<()> {
+        let value: libc::c_int = libc::IP_PMTUDISC_PROBE;
+        // SAFETY: `value` is a valid `c_int` that outlives the call
+        let result = unsafe {
+            libc::setsockopt(
+                self.sock.as_raw_fd(),
+                libc::IPPROTO_IP,
+                libc::IP_MTU_DISCOVER,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of_val(&value) as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(Error::SocketOp(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Send an echo request whose IP packet is `packet_size` bytes long. Returns the sequence
+    /// number of the request.
+    #[cfg(target_os = "linux")]
+    pub fn send_sized_icmp(&mut self, packet_size: usize) -> Result<u16> {
+        let mut message = vec![0u8; packet_size.saturating_sub(IPV4_HEADER_SIZE)];
+        let seq = self.seq;
+        self.construct_icmpv4_packet(&mut message)?;
+        self.send_ping_request(&message, self.addr)?;
+        Ok(seq)
+    }
+
+    /// Wait for an echo reply to a request sent by this pinger. Returns the sequence number of the
+    /// reply, or `None` if no reply arrived before `deadline`.
+    #[cfg(target_os = "linux")]
+    pub fn receive_reply(&mut self, deadline: Instant) -> Result<Option<u16>> {
+        let mut buffer = vec![0u8; usize::from(u16::MAX)];
+        loop {
+            match (&self.sock).read(&mut buffer) {
+                Ok(len) => {
+                    if let Some(seq) = parse_echo_reply(&buffer[..len], self.id) {
+                        return Ok(Some(seq));
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Ok(None);
+                    }
+                    thread::sleep(RECEIVE_POLL_INTERVAL);
+                }
+                Err(error) => return Err(Error::Read(error)),
+            }
+        }
+    }
+}
+
+/// Returns the sequence number of an IPv4 packet containing an echo reply with the given ID.
+#[cfg(target_os = "linux")]
+fn parse_echo_reply(packet: &[u8], id: u16) -> Option<u16> {
+    const ICMP_ECHO_REPLY: u8 = 0;
+
+    let header_len = usize::from(packet.first()? & 0x0f) * 4;
+    let message = packet.get(header_len..)?;
+    if *message.first()? != ICMP_ECHO_REPLY {
+        return None;
+    }
+    let reply_id = u16::from_be_bytes(message.get(4..6)?.try_into().ok()?);
+    let seq = u16::from_be_bytes(message.get(6..8)?.try_into().ok()?);
+    (reply_id == id).then_some(seq)
+}
+
+/// Make `socket` send its traffic through the interface named `interface_name`.
+#[cfg(not(target_os = "windows"))]
+pub(super) fn bind_to_interface(socket: &Socket, interface_name: &str) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    socket
+        .bind_device(Some(interface_name.as_bytes()))
+        .map_err(Error::SocketOp)?;
+
+    #[cfg(target_os = "macos")]
+    {
+        let index = nix::net::if_::if_nametoindex(interface_name).map_err(Error::DeviceIdx)?;
+        // Asserting that `index` is non-zero since otherwise `if_nametoindex` would have return
+        // an error
+        socket
+            .bind_device_by_index(std::num::NonZeroU32::new(index))
+            .map_err(Error::BindSocketByDevice)?;
+    }
+
+    Ok(())
 }
 
 impl super::Pinger for Pinger {
-    fn send_icmp(&mut self) -> Result<()> {
+    fn send_probe(&mut self) -> Result<()> {
         let mut message = [0u8; 50];
         self.construct_icmpv4_packet(&mut message)?;
         self.send_ping_request(&message, self.addr)
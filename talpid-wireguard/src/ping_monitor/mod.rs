@@ -7,20 +7,54 @@ mod imp;
 mod imp;
 
 pub use imp::Error;
+#[cfg(target_os = "linux")]
+pub use imp::Pinger as IcmpPinger;
 
-/// Trait for sending ICMP requests to get some traffic from a remote server
+#[cfg(not(target_os = "android"))]
+mod dns;
+#[cfg(not(target_os = "android"))]
+mod tcp;
+
+use talpid_types::net::wireguard::LivenessProbe;
+
+/// Trait for sending probes to get some traffic from a remote server
 pub trait Pinger: Send {
-    /// Sends an ICMP packet
-    fn send_icmp(&mut self) -> Result<(), Error>;
+    /// Sends a probe
+    fn send_probe(&mut self) -> Result<(), Error>;
     /// Clears all resources used by the pinger.
     fn reset(&mut self) {}
 }
 
-/// Create a new pinger
+/// Create a new pinger that sends the given kind of probe
 pub fn new_pinger(
     addr: std::net::Ipv4Addr,
     #[cfg(any(target_os = "linux", target_os = "macos"))] interface_name: String,
+    probe: LivenessProbe,
 ) -> Result<Box<dyn Pinger>, Error> {
+    #[cfg(not(target_os = "android"))]
+    match probe {
+        LivenessProbe::Icmp => (),
+        LivenessProbe::Tcp => {
+            return Ok(Box::new(tcp::Pinger::new(
+                addr,
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                interface_name,
+            )?))
+        }
+        LivenessProbe::Dns => {
+            return Ok(Box::new(dns::Pinger::new(
+                addr,
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                interface_name,
+            )?))
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    if probe != LivenessProbe::Icmp {
+        log::warn!("Only ICMP liveness probes are supported. Ignoring probe: {probe}");
+    }
+
     Ok(Box::new(imp::Pinger::new(
         addr,
         #[cfg(any(target_os = "linux", target_os = "macos"))]
@@ -0,0 +1,70 @@
+#[cfg(not(target_os = "windows"))]
+use super::imp::bind_to_interface;
+use super::imp::Error;
+use socket2::{Domain, Socket, Type};
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+/// Port on the gateway that connections are attempted to. Any response, including a reset, is
+/// incoming traffic that shows that the tunnel works.
+const PROBE_PORT: u16 = talpid_tunnel_config_client::CONFIG_SERVICE_PORT;
+/// Maximum number of connection attempts that are kept open at a time.
+const MAX_PENDING_CONNECTIONS: usize = 4;
+
+/// A pinger that starts TCP connections without waiting for them to complete
+pub struct Pinger {
+    addr: SocketAddr,
+    #[cfg(not(target_os = "windows"))]
+    interface_name: String,
+    pending: Vec<Socket>,
+}
+
+impl Pinger {
+    pub fn new(
+        addr: Ipv4Addr,
+        #[cfg(not(target_os = "windows"))] interface_name: String,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            addr: SocketAddr::new(addr.into(), PROBE_PORT),
+            #[cfg(not(target_os = "windows"))]
+            interface_name,
+            pending: vec![],
+        })
+    }
+}
+
+impl super::Pinger for Pinger {
+    fn send_probe(&mut self) -> Result<(), Error> {
+        let socket = Socket::new(Domain::IPV4, Type::STREAM, None).map_err(Error::Open)?;
+        socket.set_nonblocking(true).map_err(Error::Open)?;
+
+        #[cfg(not(target_os = "windows"))]
+        bind_to_interface(&socket, &self.interface_name)?;
+
+        match socket.connect(&self.addr.into()) {
+            Ok(()) => (),
+            Err(error) if is_in_progress(&error) => (),
+            Err(error) => return Err(Error::Write(error)),
+        }
+
+        if self.pending.len() >= MAX_PENDING_CONNECTIONS {
+            self.pending.remove(0);
+        }
+        self.pending.push(socket);
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+    }
+}
+
+fn is_in_progress(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    if error.raw_os_error() == Some(libc::EINPROGRESS) {
+        return true;
+    }
+    error.kind() == io::ErrorKind::WouldBlock
+}
@@ -0,0 +1,636 @@
+//! Userspace WireGuard implementation built on boringtun.
+//!
+//! Packets are read from the tunnel device and from the UDP sockets by a single task that drives
+//! one [`Tunn`] per peer. Routing between peers follows the allowed IPs of each peer, which is
+//! what makes multihop work: traffic to the exit peer's endpoint is routed into the tunnel device
+//! and ends up encapsulated for the entry peer.
+
+use super::{
+    stats::{Stats, StatsMap},
+    CloseMsg, Config, Error, Tunnel, TunnelError,
+};
+use boringtun::{
+    noise::{errors::WireGuardError, Tunn, TunnResult},
+    x25519::{PublicKey, StaticSecret},
+};
+use ipnetwork::IpNetwork;
+use parking_lot::Mutex;
+use std::{
+    convert::Infallible,
+    fs::File,
+    future::Future,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::unix::io::{AsRawFd, FromRawFd},
+    pin::Pin,
+    sync::{mpsc as sync_mpsc, Arc},
+    time::Duration,
+};
+use talpid_tunnel::tun_provider::{Tun, TunConfig, TunProvider};
+use tokio::{io::unix::AsyncFd, net::UdpSocket, task::JoinHandle};
+
+type Result<T> = std::result::Result<T, TunnelError>;
+
+/// Large enough for any IP packet plus the WireGuard overhead.
+const MAX_PACKET_SIZE: usize = u16::MAX as usize;
+/// How often boringtun timers are updated. This matches the interval used by boringtun itself.
+const TIMER_INTERVAL: Duration = Duration::from_millis(250);
+/// The tunnel device is opened with packet information, which prefixes each packet with flags and
+/// the protocol of the packet.
+const PACKET_INFO_LEN: usize = 4;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+
+pub struct BoringTunTunnel {
+    interface_name: String,
+    device: Arc<Mutex<Device>>,
+    task: Option<JoinHandle<()>>,
+    // holding on to the tunnel device ensures that the interface lives as long as the tunnel
+    _tunnel_device: Tun,
+}
+
+impl BoringTunTunnel {
+    pub fn start_tunnel(
+        runtime: tokio::runtime::Handle,
+        config: &Config,
+        tun_provider: Arc<std::sync::Mutex<TunProvider>>,
+        routes: impl Iterator<Item = IpNetwork>,
+        close_msg_sender: sync_mpsc::Sender<CloseMsg>,
+    ) -> Result<Self> {
+        let tunnel_device = tun_provider
+            .lock()
+            .unwrap()
+            .get_tun(Self::create_tunnel_config(config, routes))
+            .map_err(TunnelError::SetupTunnelDevice)?;
+        let interface_name = tunnel_device.interface_name().to_string();
+
+        // The tun provider leaves the MTU at its default
+        super::set_mtu(&interface_name, config.mtu).map_err(TunnelError::SetupSocket)?;
+
+        let tunnel_fd =
+            nix::unistd::dup(tunnel_device.as_raw_fd()).map_err(TunnelError::FdDuplicationError)?;
+        // SAFETY: The descriptor was just duplicated and is not owned by anything else
+        let tun = unsafe { File::from_raw_fd(tunnel_fd) };
+
+        let _guard = runtime.enter();
+        let tun = AsyncFd::new(tun).map_err(TunnelError::SetupSocket)?;
+        let sockets = Sockets::new(config).map_err(TunnelError::SetupSocket)?;
+
+        let device = Arc::new(Mutex::new(Device::new(config)));
+        let moved_device = device.clone();
+        let task = runtime.spawn(async move {
+            // This is safe to unwrap because the future resolves to `Result<Infallible, E>`.
+            let error = run_device(moved_device, tun, sockets).await.unwrap_err();
+            log::error!("Failed to poll tunnel device: {error}");
+            // Tear down the tunnel, since no traffic can pass through it anymore
+            let _ = close_msg_sender.send(CloseMsg::TunnelFailed(Error::TunnelError(
+                TunnelError::PollTunnelDevice(error),
+            )));
+        });
+
+        Ok(BoringTunTunnel {
+            interface_name,
+            device,
+            task: Some(task),
+            _tunnel_device: tunnel_device,
+        })
+    }
+
+    fn create_tunnel_config(config: &Config, routes: impl Iterator<Item = IpNetwork>) -> TunConfig {
+        let mut dns_servers = vec![IpAddr::V4(config.ipv4_gateway)];
+        dns_servers.extend(config.ipv6_gateway.map(IpAddr::V6));
+
+        TunConfig {
+            addresses: config.tunnel.addresses.clone(),
+            dns_servers,
+            routes: routes.collect(),
+            mtu: config.mtu,
+        }
+    }
+
+    fn stop_tunnel(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for BoringTunTunnel {
+    fn drop(&mut self) {
+        self.stop_tunnel();
+    }
+}
+
+impl Tunnel for BoringTunTunnel {
+    fn get_interface_name(&self) -> String {
+        self.interface_name.clone()
+    }
+
+    fn get_tunnel_stats(&self) -> Result<StatsMap> {
+        Ok(self.device.lock().stats())
+    }
+
+    fn stop(mut self: Box<Self>) -> Result<()> {
+        self.stop_tunnel();
+        Ok(())
+    }
+
+    fn set_config(
+        &self,
+        config: Config,
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send>> {
+        self.device.lock().set_config(&config);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+struct Device {
+    private_key: [u8; 32],
+    peers: Vec<Peer>,
+    next_index: u32,
+}
+
+struct Peer {
+    public_key: [u8; 32],
+    psk: Option<[u8; 32]>,
+    endpoint: SocketAddr,
+    allowed_ips: Vec<IpNetwork>,
+    tunnel: Tunn,
+    /// Traffic counted by earlier sessions with this peer. The counters of a [`Tunn`] start over
+    /// when it is replaced, e.g. when a new PSK is applied.
+    stats_offset: Stats,
+}
+
+impl Device {
+    fn new(config: &Config) -> Self {
+        let mut device = Device {
+            private_key: config.tunnel.private_key.to_bytes(),
+            peers: vec![],
+            next_index: 0,
+        };
+        device.set_config(config);
+        device
+    }
+
+    /// Applies a new config. Sessions with peers whose keys are unchanged are kept.
+    fn set_config(&mut self, config: &Config) {
+        let private_key = config.tunnel.private_key.to_bytes();
+        let key_changed = private_key != self.private_key;
+        self.private_key = private_key;
+
+        let mut old_peers = std::mem::take(&mut self.peers);
+        for peer_config in config.peers() {
+            let public_key = *peer_config.public_key.as_bytes();
+            let psk = peer_config.psk.as_ref().map(|psk| *psk.as_bytes());
+
+            let old_peer = old_peers
+                .iter()
+                .position(|peer| peer.public_key == public_key)
+                .map(|index| old_peers.swap_remove(index));
+
+            let peer = match old_peer {
+                Some(mut peer) if !key_changed && peer.psk == psk => {
+                    peer.endpoint = peer_config.endpoint;
+                    peer.allowed_ips = peer_config.allowed_ips.clone();
+                    peer
+                }
+                old_peer => Peer {
+                    public_key,
+                    psk,
+                    endpoint: peer_config.endpoint,
+                    allowed_ips: peer_config.allowed_ips.clone(),
+                    tunnel: self.new_session(public_key, psk),
+                    stats_offset: old_peer.map(|peer| peer.stats()).unwrap_or_default(),
+                },
+            };
+            self.peers.push(peer);
+        }
+    }
+
+    fn new_session(&mut self, public_key: [u8; 32], psk: Option<[u8; 32]>) -> Tunn {
+        let index = self.next_index;
+        self.next_index = self.next_index.wrapping_add(1);
+        Tunn::new(
+            StaticSecret::from(self.private_key),
+            PublicKey::from(public_key),
+            psk,
+            None,
+            index,
+            None,
+        )
+    }
+
+    fn stats(&self) -> StatsMap {
+        self.peers
+            .iter()
+            .map(|peer| (peer.public_key, peer.stats()))
+            .collect()
+    }
+
+    /// Returns the peer that should receive traffic for `destination`, i.e. the peer with the
+    /// most specific matching allowed IP.
+    fn route(&mut self, destination: IpAddr) -> Option<&mut Peer> {
+        self.peers
+            .iter_mut()
+            .filter_map(|peer| {
+                let prefix = peer
+                    .allowed_ips
+                    .iter()
+                    .filter(|network| network.contains(destination))
+                    .map(|network| network.prefix())
+                    .max()?;
+                Some((prefix, peer))
+            })
+            .max_by_key(|(prefix, _)| *prefix)
+            .map(|(_, peer)| peer)
+    }
+}
+
+impl Peer {
+    fn stats(&self) -> Stats {
+        let (_, tx_bytes, rx_bytes, _, _) = self.tunnel.stats();
+        Stats {
+            tx_bytes: self.stats_offset.tx_bytes + tx_bytes as u64,
+            rx_bytes: self.stats_offset.rx_bytes + rx_bytes as u64,
+        }
+    }
+
+    fn is_allowed_source(&self, source: IpAddr) -> bool {
+        self.allowed_ips
+            .iter()
+            .any(|network| network.contains(source))
+    }
+}
+
+struct Sockets {
+    v4: UdpSocket,
+    v6: Option<UdpSocket>,
+}
+
+impl Sockets {
+    fn new(config: &Config) -> io::Result<Self> {
+        let v4 = bind_socket(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)), config.fwmark)?;
+        let v6 = match bind_socket(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)), config.fwmark) {
+            Ok(socket) => Some(socket),
+            Err(error) => {
+                log::debug!("Not using IPv6 for WireGuard traffic: {error}");
+                None
+            }
+        };
+        Ok(Sockets { v4, v6 })
+    }
+
+    fn send_to(&self, packet: &[u8], endpoint: SocketAddr) {
+        let socket = match endpoint {
+            SocketAddr::V4(_) => Some(&self.v4),
+            SocketAddr::V6(_) => self.v6.as_ref(),
+        };
+        let Some(socket) = socket else {
+            log::trace!("No socket available for {endpoint}");
+            return;
+        };
+        if let Err(error) = socket.try_send_to(packet, endpoint) {
+            log::trace!("Failed to send packet to {endpoint}: {error}");
+        }
+    }
+}
+
+fn bind_socket(address: SocketAddr, fwmark: Option<u32>) -> io::Result<UdpSocket> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(address),
+        socket2::Type::DGRAM,
+        Some(socket2::Protocol::UDP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    if let Some(fwmark) = fwmark {
+        socket.set_mark(fwmark)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Forwards packets between the tunnel device and the peers. Only returns if the tunnel device
+/// can no longer be polled.
+async fn run_device(
+    device: Arc<Mutex<Device>>,
+    tun: AsyncFd<File>,
+    sockets: Sockets,
+) -> io::Result<Infallible> {
+    let mut timer = tokio::time::interval(TIMER_INTERVAL);
+    let mut tun_buf = vec![0u8; MAX_PACKET_SIZE];
+    let mut udp_v4_buf = vec![0u8; MAX_PACKET_SIZE];
+    let mut udp_v6_buf = vec![0u8; MAX_PACKET_SIZE];
+    let mut out_buf = vec![0u8; MAX_PACKET_SIZE + PACKET_INFO_LEN];
+
+    loop {
+        tokio::select! {
+            result = tun.readable() => {
+                let mut guard = result?;
+                match guard.try_io(|tun| tun.get_ref().read(&mut tun_buf)) {
+                    Ok(Ok(len)) => handle_tun_packet(
+                        &mut device.lock(),
+                        &sockets,
+                        &tun_buf[..len],
+                        &mut out_buf,
+                    ),
+                    Ok(Err(error)) => log::trace!("Failed to read from tunnel device: {error}"),
+                    Err(_would_block) => (),
+                }
+            }
+            result = sockets.v4.recv_from(&mut udp_v4_buf) => {
+                match result {
+                    Ok((len, source)) => handle_udp_packet(
+                        &mut device.lock(),
+                        &sockets,
+                        tun.get_ref(),
+                        source,
+                        &udp_v4_buf[..len],
+                        &mut out_buf,
+                    ),
+                    Err(error) => log::trace!("Failed to receive on IPv4 socket: {error}"),
+                }
+            }
+            Some(result) = recv_optional(sockets.v6.as_ref(), &mut udp_v6_buf) => {
+                match result {
+                    Ok((len, source)) => handle_udp_packet(
+                        &mut device.lock(),
+                        &sockets,
+                        tun.get_ref(),
+                        source,
+                        &udp_v6_buf[..len],
+                        &mut out_buf,
+                    ),
+                    Err(error) => log::trace!("Failed to receive on IPv6 socket: {error}"),
+                }
+            }
+            _ = timer.tick() => update_timers(&mut device.lock(), &sockets, &mut out_buf),
+        }
+    }
+}
+
+async fn recv_optional(
+    socket: Option<&UdpSocket>,
+    buf: &mut [u8],
+) -> Option<io::Result<(usize, SocketAddr)>> {
+    match socket {
+        Some(socket) => Some(socket.recv_from(buf).await),
+        None => futures::future::pending().await,
+    }
+}
+
+/// Encapsulates a packet read from the tunnel device and sends it to the peer it is routed to.
+fn handle_tun_packet(device: &mut Device, sockets: &Sockets, packet: &[u8], out_buf: &mut [u8]) {
+    let Some(packet) = packet.get(PACKET_INFO_LEN..) else {
+        return;
+    };
+    let Some(destination) = destination_address(packet) else {
+        return;
+    };
+    let Some(peer) = device.route(destination) else {
+        log::trace!("No peer for destination {destination}");
+        return;
+    };
+    match peer.tunnel.encapsulate(packet, out_buf) {
+        TunnResult::WriteToNetwork(packet) => sockets.send_to(packet, peer.endpoint),
+        TunnResult::Err(error) => log::trace!("Failed to encapsulate packet: {error:?}"),
+        _ => (),
+    }
+}
+
+/// Decapsulates a packet received from a peer and writes the result to the tunnel device.
+fn handle_udp_packet(
+    device: &mut Device,
+    sockets: &Sockets,
+    tun: &File,
+    source: SocketAddr,
+    packet: &[u8],
+    out_buf: &mut [u8],
+) {
+    let Some(peer) = device.peers.iter_mut().find(|peer| peer.endpoint == source) else {
+        log::trace!("Dropping packet from unknown endpoint {source}");
+        return;
+    };
+
+    let payload_buf = &mut out_buf[PACKET_INFO_LEN..];
+    let (protocol, len) = match peer
+        .tunnel
+        .decapsulate(Some(source.ip()), packet, payload_buf)
+    {
+        TunnResult::WriteToNetwork(packet) => {
+            sockets.send_to(packet, peer.endpoint);
+            // Send any packets that were queued while waiting for the handshake
+            while let TunnResult::WriteToNetwork(packet) =
+                peer.tunnel
+                    .decapsulate(None, &[], &mut out_buf[PACKET_INFO_LEN..])
+            {
+                sockets.send_to(packet, peer.endpoint);
+            }
+            return;
+        }
+        TunnResult::WriteToTunnelV4(packet, address)
+            if peer.is_allowed_source(IpAddr::V4(address)) =>
+        {
+            (ETH_P_IP, packet.len())
+        }
+        TunnResult::WriteToTunnelV6(packet, address)
+            if peer.is_allowed_source(IpAddr::V6(address)) =>
+        {
+            (ETH_P_IPV6, packet.len())
+        }
+        TunnResult::WriteToTunnelV4(..) | TunnResult::WriteToTunnelV6(..) => {
+            log::trace!("Dropping packet from a source that is not allowed for the peer");
+            return;
+        }
+        TunnResult::Err(error) => {
+            log::trace!("Failed to decapsulate packet: {error:?}");
+            return;
+        }
+        TunnResult::Done => return,
+    };
+
+    out_buf[..2].copy_from_slice(&[0, 0]);
+    out_buf[2..PACKET_INFO_LEN].copy_from_slice(&protocol.to_be_bytes());
+    let mut tun = tun;
+    if let Err(error) = tun.write_all(&out_buf[..PACKET_INFO_LEN + len]) {
+        log::trace!("Failed to write to tunnel device: {error}");
+    }
+}
+
+fn update_timers(device: &mut Device, sockets: &Sockets, out_buf: &mut [u8]) {
+    for peer in &mut device.peers {
+        match peer.tunnel.update_timers(out_buf) {
+            TunnResult::WriteToNetwork(packet) => sockets.send_to(packet, peer.endpoint),
+            TunnResult::Err(WireGuardError::ConnectionExpired) => {
+                log::trace!("Session with peer expired")
+            }
+            TunnResult::Err(error) => log::trace!("Failed to update timers: {error:?}"),
+            _ => (),
+        }
+    }
+}
+
+fn destination_address(packet: &[u8]) -> Option<IpAddr> {
+    match packet.first()? >> 4 {
+        4 => {
+            let octets: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        6 => {
+            let octets: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::wireguard::{PeerConfig, PresharedKey, PrivateKey, TunnelConfig};
+
+    const ENTRY_KEY: [u8; 32] = [2; 32];
+    const EXIT_KEY: [u8; 32] = [3; 32];
+
+    fn peer_config(public_key: [u8; 32], allowed_ips: &[&str], endpoint: &str) -> PeerConfig {
+        PeerConfig {
+            public_key: public_key.into(),
+            allowed_ips: allowed_ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            endpoint: endpoint.parse().unwrap(),
+            psk: None,
+        }
+    }
+
+    /// Returns a multihop config, where only the exit relay is reached through the entry relay.
+    fn multihop_config() -> Config {
+        Config {
+            tunnel: TunnelConfig {
+                private_key: PrivateKey::from([1; 32]),
+                addresses: vec!["10.64.0.2".parse().unwrap()],
+            },
+            entry_peer: peer_config(ENTRY_KEY, &["192.0.2.2/32"], "192.0.2.1:51820"),
+            exit_peer: Some(peer_config(
+                EXIT_KEY,
+                &["0.0.0.0/0", "::/0"],
+                "192.0.2.2:51820",
+            )),
+            ipv4_gateway: "10.64.0.1".parse().unwrap(),
+            ipv6_gateway: None,
+            mtu: 1380,
+            mtu_discovery: false,
+            fwmark: None,
+            enable_ipv6: true,
+            obfuscator_config: None,
+            connectivity_check: Default::default(),
+        }
+    }
+
+    fn peer(device: &Device, public_key: [u8; 32]) -> &Peer {
+        device
+            .peers
+            .iter()
+            .find(|peer| peer.public_key == public_key)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_set_config_keeps_sessions() {
+        let mut config = multihop_config();
+        let mut device = Device::new(&config);
+        assert_eq!(device.next_index, 2);
+
+        // Moving a peer does not require a new handshake
+        config.entry_peer.endpoint = "192.0.2.3:51820".parse().unwrap();
+        device.set_config(&config);
+        assert_eq!(device.next_index, 2);
+        assert_eq!(
+            peer(&device, ENTRY_KEY).endpoint,
+            config.entry_peer.endpoint
+        );
+    }
+
+    #[test]
+    fn test_set_config_replaces_session_on_psk_change() {
+        let mut config = multihop_config();
+        let mut device = Device::new(&config);
+        let offset = Stats {
+            tx_bytes: 100,
+            rx_bytes: 200,
+        };
+        device
+            .peers
+            .iter_mut()
+            .find(|peer| peer.public_key == EXIT_KEY)
+            .unwrap()
+            .stats_offset = offset;
+
+        config.exit_peer.as_mut().unwrap().psk = Some(PresharedKey::from(Box::new([4; 32])));
+        device.set_config(&config);
+
+        // Only the session with the exit peer is replaced, and its traffic is still counted
+        assert_eq!(device.next_index, 3);
+        let exit = peer(&device, EXIT_KEY);
+        assert_eq!(exit.psk, Some([4; 32]));
+        assert_eq!(exit.stats_offset, offset);
+        assert_eq!(device.stats()[&EXIT_KEY], offset);
+        assert_eq!(peer(&device, ENTRY_KEY).stats_offset, Stats::default());
+    }
+
+    #[test]
+    fn test_set_config_replaces_sessions_on_key_change() {
+        let mut config = multihop_config();
+        let mut device = Device::new(&config);
+        config.tunnel.private_key = PrivateKey::from([5; 32]);
+        device.set_config(&config);
+        assert_eq!(device.next_index, 4);
+    }
+
+    #[test]
+    fn test_route_longest_prefix() {
+        let mut device = Device::new(&multihop_config());
+        let mut route = |destination: &str| {
+            device
+                .route(destination.parse().unwrap())
+                .map(|peer| peer.public_key)
+        };
+
+        // Traffic to the exit relay is encapsulated for the entry relay
+        assert_eq!(route("192.0.2.2"), Some(ENTRY_KEY));
+        assert_eq!(route("192.0.2.3"), Some(EXIT_KEY));
+        assert_eq!(route("2001:db8::1"), Some(EXIT_KEY));
+    }
+
+    #[test]
+    fn test_route_without_matching_peer() {
+        let mut config = multihop_config();
+        config.exit_peer = None;
+        let mut device = Device::new(&config);
+        assert!(device.route("192.0.2.3".parse().unwrap()).is_none());
+        assert!(device.route("2001:db8::1".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_destination_address() {
+        let mut ipv4 = [0u8; 20];
+        ipv4[0] = 0x45;
+        ipv4[16..20].copy_from_slice(&[192, 0, 2, 2]);
+        assert_eq!(
+            destination_address(&ipv4),
+            Some(IpAddr::from([192, 0, 2, 2]))
+        );
+
+        let mut ipv6 = [0u8; 40];
+        ipv6[0] = 0x60;
+        let destination: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        ipv6[24..40].copy_from_slice(&destination.octets());
+        assert_eq!(destination_address(&ipv6), Some(IpAddr::V6(destination)));
+
+        // Truncated packets and unknown versions are dropped
+        assert_eq!(destination_address(&ipv4[..19]), None);
+        assert_eq!(destination_address(&ipv6[..39]), None);
+        assert_eq!(destination_address(&[0x50; 40]), None);
+        assert_eq!(destination_address(&[]), None);
+    }
+}
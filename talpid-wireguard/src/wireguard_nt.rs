@@ -1016,6 +1016,7 @@ mod tests {
         ipv6_gateway: None,
         mtu: 0,
         obfuscator_config: None,
+        connectivity_check: Default::default(),
     });
 
     static WG_STRUCT_CONFIG: Lazy<Interface> = Lazy::new(|| Interface {
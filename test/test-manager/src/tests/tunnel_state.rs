@@ -299,6 +299,7 @@ pub async fn test_connected_state(
                     obfuscation: None,
                     entry_endpoint: None,
                     tunnel_interface: _,
+                    discovered_mtu: _,
                 },
             ..
         } => {
@@ -0,0 +1,153 @@
+//! Minimal stand-in for the Mullvad REST API. It answers just enough requests for the daemon to
+//! stay logged in and keep using the fake relay list.
+
+use crate::{netns::Namespace, relay, Error, Result};
+use chrono::{Duration, Utc};
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tokio::task::JoinHandle;
+
+/// Port that the API stand-in listens on. The daemon talks to it using plain HTTP.
+pub const API_PORT: u16 = 80;
+
+/// Account number that the device is logged in to.
+pub const ACCOUNT_NUMBER: &str = "1234123412341234";
+/// ID of the logged-in device.
+pub const DEVICE_ID: &str = "b2f5a8d6-6c2b-4b68-8e6c-3b0e6b1f2e01";
+/// Name of the logged-in device.
+pub const DEVICE_NAME: &str = "testing netns";
+
+const ACCESS_TOKEN: &str = "test-netns-token";
+
+/// Runs the API stand-in until dropped.
+pub struct ApiServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ApiServer {
+    /// Starts the API stand-in in `namespace`, listening on `addr`.
+    pub fn start(namespace: &Namespace, addr: IpAddr) -> Result<Self> {
+        let addr = SocketAddr::new(addr, API_PORT);
+        let listener = namespace.bind_tcp(addr)?;
+        let state = Arc::new(ApiState {
+            created: Utc::now(),
+        });
+
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(state.handle(request)) }
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener)
+            .map_err(Error::StartApi)?
+            .serve(make_service);
+
+        let task = tokio::spawn(async move {
+            if let Err(error) = server.await {
+                log::error!("API stand-in stopped: {error}");
+            }
+        });
+
+        Ok(ApiServer { addr, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct ApiState {
+    created: chrono::DateTime<Utc>,
+}
+
+impl ApiState {
+    fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let path = request.uri().path().trim_start_matches('/');
+        log::debug!("API stand-in: {} {path}", request.method());
+
+        match (request.method(), path) {
+            (&Method::GET, "app/v1/relays") => {
+                // Only the cached relay list is used, so it is always up to date
+                if request.headers().contains_key(header::IF_NONE_MATCH) {
+                    status(StatusCode::NOT_MODIFIED)
+                } else {
+                    status(StatusCode::SERVICE_UNAVAILABLE)
+                }
+            }
+            (&Method::GET | &Method::HEAD, "app/v1/api-addrs") => json(serde_json::json!([])),
+            (&Method::GET, path) if path.starts_with("app/v1/releases/") => {
+                let version = path.rsplit('/').next().unwrap_or_default();
+                json(serde_json::json!({
+                    "supported": true,
+                    "latest": version,
+                    "latest_stable": version,
+                    "latest_beta": version,
+                }))
+            }
+            (&Method::POST, "auth/v1/token") => json(serde_json::json!({
+                "access_token": ACCESS_TOKEN,
+                "expiry": Utc::now() + Duration::hours(1),
+            })),
+            (&Method::GET, "accounts/v1/accounts/me") => json(serde_json::json!({
+                "id": "test-netns-account",
+                "expiry": Utc::now() + Duration::days(30),
+            })),
+            (&Method::GET, path) if path == format!("accounts/v1/devices/{DEVICE_ID}") => {
+                json(self.device())
+            }
+            (&Method::GET, "accounts/v1/devices") => json(serde_json::json!([self.device()])),
+            _ => {
+                log::warn!(
+                    "API stand-in: unhandled request: {} {path}",
+                    request.method()
+                );
+                status(StatusCode::NOT_FOUND)
+            }
+        }
+    }
+
+    fn device(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": DEVICE_ID,
+            "name": DEVICE_NAME,
+            "pubkey": relay::device_private_key().public_key(),
+            "ipv4_address": format!("{}/32", relay::DEVICE_TUN_ADDR),
+            "ipv6_address": format!("{}/128", relay::DEVICE_TUN_ADDR_V6),
+            "hijack_dns": false,
+            "created": self.created,
+        })
+    }
+}
+
+fn json(value: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap()
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
@@ -0,0 +1,185 @@
+//! Spawns `mullvad-daemon` and `test-runner` inside the client namespace.
+
+use crate::{api, netns::Namespace, relay, Error, Result};
+use mullvad_management_interface::{ManagementServiceClient, MullvadProxyClient};
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    process::Child,
+};
+
+/// How long to wait for the daemon to open its management interface.
+const DAEMON_START_TIMEOUT: Duration = Duration::from_secs(20);
+/// How long to wait for the test runner to connect.
+const RUNNER_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Directories used by a single daemon instance. They are removed together with `root`.
+pub struct DaemonDirs {
+    root: tempfile::TempDir,
+}
+
+impl DaemonDirs {
+    pub fn new() -> Result<Self> {
+        let root = tempfile::Builder::new()
+            .prefix("mullvad-netns-")
+            .tempdir()
+            .map_err(Error::CreateDaemonFiles)?;
+        let dirs = DaemonDirs { root };
+        for dir in [
+            dirs.settings_dir(),
+            dirs.cache_dir(),
+            dirs.log_dir(),
+            dirs.resource_dir(),
+        ] {
+            fs::create_dir(dir).map_err(Error::CreateDaemonFiles)?;
+        }
+        Ok(dirs)
+    }
+
+    pub fn settings_dir(&self) -> PathBuf {
+        self.root.path().join("settings")
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.root.path().join("cache")
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        self.root.path().join("log")
+    }
+
+    pub fn resource_dir(&self) -> PathBuf {
+        self.root.path().join("resources")
+    }
+
+    pub fn rpc_socket_path(&self) -> PathBuf {
+        self.root.path().join("mullvad-vpn.sock")
+    }
+
+    pub fn runner_socket_path(&self) -> PathBuf {
+        self.root.path().join("test-runner.sock")
+    }
+
+    /// Writes the relay list cache and a logged-in device, so that the daemon can connect
+    /// without creating an account or fetching relays.
+    pub fn write_initial_state(&self, relay_addr: std::net::Ipv4Addr) -> Result<()> {
+        let relay_list = serde_json::to_vec_pretty(&relay::relay_list(relay_addr))
+            .map_err(Error::SerializeDaemonFiles)?;
+        fs::write(self.cache_dir().join("relays.json"), relay_list)
+            .map_err(Error::CreateDaemonFiles)?;
+
+        let now = chrono::Utc::now();
+        let device = serde_json::json!({
+            "logged_in": {
+                "account_token": api::ACCOUNT_NUMBER,
+                "device": {
+                    "id": api::DEVICE_ID,
+                    "name": api::DEVICE_NAME,
+                    "wg_data": {
+                        "private_key": relay::device_private_key(),
+                        "addresses": {
+                            "ipv4_address": format!("{}/32", relay::DEVICE_TUN_ADDR),
+                            "ipv6_address": format!("{}/128", relay::DEVICE_TUN_ADDR_V6),
+                        },
+                        "created": now,
+                    },
+                    "hijack_dns": false,
+                    "created": now,
+                }
+            }
+        });
+        let device = serde_json::to_vec_pretty(&device).map_err(Error::SerializeDaemonFiles)?;
+        fs::write(self.settings_dir().join("device.json"), device).map_err(Error::CreateDaemonFiles)
+    }
+}
+
+/// Starts the daemon in `namespace` and waits for its management interface to come up.
+pub async fn spawn_daemon(
+    namespace: &Namespace,
+    daemon_path: &Path,
+    dirs: &DaemonDirs,
+    api_addr: SocketAddr,
+) -> Result<(Child, MullvadProxyClient)> {
+    let mut cmd = namespace.command(daemon_path);
+    cmd.args(["-vv", "--disable-stdout-timestamps"])
+        .env("MULLVAD_SETTINGS_DIR", dirs.settings_dir())
+        .env("MULLVAD_CACHE_DIR", dirs.cache_dir())
+        .env("MULLVAD_LOG_DIR", dirs.log_dir())
+        .env("MULLVAD_RESOURCE_DIR", dirs.resource_dir())
+        .env("MULLVAD_RPC_SOCKET_PATH", dirs.rpc_socket_path())
+        .env("MULLVAD_API_HOST", api_addr.ip().to_string())
+        .env("MULLVAD_API_ADDR", api_addr.to_string())
+        .env("MULLVAD_API_DISABLE_TLS", "1")
+        .env("TALPID_DNS_MODULE", "static-file")
+        .env("TALPID_DISABLE_OFFLINE_MONITOR", "1")
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    let child = cmd.spawn().map_err(Error::SpawnDaemon)?;
+
+    let socket_path = dirs.rpc_socket_path();
+    let client = tokio::time::timeout(DAEMON_START_TIMEOUT, async {
+        loop {
+            match connect_daemon(socket_path.clone()).await {
+                Ok(client) => return client,
+                Err(error) => {
+                    log::trace!("Daemon is not up yet: {error}");
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+            }
+        }
+    })
+    .await
+    .map_err(|_| Error::DaemonTimeout)?;
+
+    Ok((child, client))
+}
+
+async fn connect_daemon(socket_path: PathBuf) -> Result<MullvadProxyClient> {
+    // The URI will be ignored
+    let channel = tonic::transport::Endpoint::from_static("lttp://[::]:50051")
+        .connect_with_connector(tower::service_fn(move |_| {
+            UnixStream::connect(socket_path.clone())
+        }))
+        .await
+        .map_err(Error::ConnectDaemon)?;
+    Ok(MullvadProxyClient::from_rpc_client(
+        ManagementServiceClient::new(channel),
+    ))
+}
+
+/// Starts the test runner in `namespace` and waits for it to connect back over a Unix socket.
+pub async fn spawn_runner(
+    namespace: &Namespace,
+    runner_path: &Path,
+    dirs: &DaemonDirs,
+) -> Result<(Child, UnixStream)> {
+    let socket_path = dirs.runner_socket_path();
+    let listener = UnixListener::bind(&socket_path).map_err(Error::RunnerListener)?;
+
+    let mut cmd = namespace.command(runner_path);
+    cmd.arg(format!("unix:{}", socket_path.display()))
+        .env("MULLVAD_RPC_SOCKET_PATH", dirs.rpc_socket_path())
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    let child = cmd.spawn().map_err(Error::SpawnRunner)?;
+
+    let (stream, _) = tokio::time::timeout(RUNNER_START_TIMEOUT, listener.accept())
+        .await
+        .map_err(|_| Error::RunnerTimeout)?
+        .map_err(Error::RunnerListener)?;
+
+    Ok((child, stream))
+}
+
+/// Kills `child` and waits for it to exit.
+pub async fn stop(name: &str, mut child: Child) {
+    if let Err(error) = child.kill().await {
+        log::error!("Failed to stop {name}: {error}");
+    }
+}
@@ -0,0 +1,250 @@
+//! Integration test harness that runs `mullvad-daemon` in throwaway Linux network namespaces,
+//! without the need for a VM.
+//!
+//! The harness sets up the following topology:
+//!
+//! ```text
+//!  client namespace                     internet namespace
+//! +----------------------------+       +--------------------------------+
+//! | mullvad-daemon             |       | WireGuard relay (wg-relay0)    |
+//! | test-runner                | veth0 | REST API stand-in (port 80)    |
+//! |               10.99.0.2/24 +-------+ 10.99.0.1/24                   |
+//! +----------------------------+       +--------------------------------+
+//! ```
+//!
+//! The daemon is started with a cached relay list that only contains the local relay, and with a
+//! device that is already logged in. The test runner is started next to the daemon, which makes
+//! the checks in `test-rpc` (`send_tcp`, `send_ping`, `resolve_hostname`, etc.) available through
+//! [`Harness::runner`].
+//!
+//! Creating namespaces requires root. `mullvad-daemon` must be built with the `api-override`
+//! feature, so that it can be pointed at the API stand-in.
+#![cfg(target_os = "linux")]
+
+mod api;
+mod daemon;
+mod netns;
+mod relay;
+
+pub use api::{ACCOUNT_NUMBER, DEVICE_ID};
+pub use netns::Namespace;
+pub use relay::{
+    DEVICE_TUN_ADDR, DEVICE_TUN_ADDR_V6, RELAY_HOSTNAME, RELAY_PORT, RELAY_TUN_ADDR,
+    RELAY_TUN_ADDR_V6,
+};
+
+use ipnetwork::IpNetwork;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::relay_constraints::{
+    Constraint, GeographicLocationConstraint, LocationConstraint, RelayConstraints, RelaySettings,
+};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+};
+use talpid_types::net::TunnelType;
+use test_rpc::{transport::GrpcForwarder, ServiceClient};
+use tokio::{process::Child, task::JoinHandle};
+
+/// Address of the internet namespace on the veth segment. The relay and the API stand-in are
+/// reachable here.
+pub const INTERNET_ADDR: Ipv4Addr = Ipv4Addr::new(10, 99, 0, 1);
+/// Address of the client namespace on the veth segment.
+pub const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 99, 0, 2);
+/// Prefix length of the veth segment.
+const SEGMENT_PREFIX: u8 = 24;
+/// Name of the veth interface in both namespaces.
+pub const CLIENT_INTERFACE: &str = "veth0";
+
+/// Environment variable that points out the `mullvad-daemon` binary.
+pub const DAEMON_PATH_VAR: &str = "NETNS_MULLVAD_DAEMON";
+/// Environment variable that points out the `test-runner` binary.
+pub const RUNNER_PATH_VAR: &str = "NETNS_TEST_RUNNER";
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Failed to start 'ip'")]
+    IpStart(io::Error),
+    #[error(display = "'ip' command failed: {}", _0)]
+    IpFailed(i32),
+    #[error(display = "Failed to start '{}'", _0)]
+    ExecStart(String, #[error(source)] io::Error),
+    #[error(display = "'{}' failed: {}", _0, _1)]
+    ExecFailed(String, i32),
+    #[error(display = "Failed to create files for network namespace")]
+    CreateNamespaceFiles(io::Error),
+    #[error(display = "Failed to enter network namespace")]
+    EnterNamespace(io::Error),
+    #[error(display = "Failed to bind TCP listener")]
+    BindListener(io::Error),
+    #[error(display = "Failed to write WireGuard config")]
+    CreateWireguardConfig(io::Error),
+    #[error(display = "Failed to start API stand-in")]
+    StartApi(hyper::Error),
+    #[error(display = "Failed to create daemon files")]
+    CreateDaemonFiles(io::Error),
+    #[error(display = "Failed to serialize daemon files")]
+    SerializeDaemonFiles(serde_json::Error),
+    #[error(display = "Failed to spawn mullvad-daemon")]
+    SpawnDaemon(io::Error),
+    #[error(display = "Failed to connect to mullvad-daemon")]
+    ConnectDaemon(tonic::transport::Error),
+    #[error(display = "Timed out waiting for mullvad-daemon to start")]
+    DaemonTimeout,
+    #[error(display = "Failed to configure mullvad-daemon")]
+    ConfigureDaemon(mullvad_management_interface::Error),
+    #[error(display = "Failed to spawn test-runner")]
+    SpawnRunner(io::Error),
+    #[error(display = "Failed to accept connection from test-runner")]
+    RunnerListener(io::Error),
+    #[error(display = "Timed out waiting for test-runner to connect")]
+    RunnerTimeout,
+    #[error(display = "Failed to set up test-runner transport")]
+    RunnerTransport(test_rpc::Error),
+    #[error(display = "Environment variable {} must be set", _0)]
+    MissingPath(&'static str),
+}
+
+/// Paths to the binaries that the harness runs.
+#[derive(Debug, Clone)]
+pub struct HarnessConfig {
+    /// `mullvad-daemon`, built with the `api-override` feature.
+    pub daemon_path: PathBuf,
+    /// `test-runner`.
+    pub runner_path: PathBuf,
+}
+
+impl HarnessConfig {
+    /// Reads the paths from [`DAEMON_PATH_VAR`] and [`RUNNER_PATH_VAR`].
+    pub fn from_env() -> Result<Self> {
+        let path = |var| {
+            std::env::var_os(var)
+                .map(PathBuf::from)
+                .ok_or(Error::MissingPath(var))
+        };
+        Ok(HarnessConfig {
+            daemon_path: path(DAEMON_PATH_VAR)?,
+            runner_path: path(RUNNER_PATH_VAR)?,
+        })
+    }
+}
+
+/// A running daemon and test runner in their own namespaces. Everything is torn down when this is
+/// dropped.
+pub struct Harness {
+    runner: ServiceClient,
+    mullvad_client: MullvadProxyClient,
+    runner_process: Child,
+    daemon_process: Child,
+    // Kept alive for as long as the runner connection is used
+    _daemon_forwarder: GrpcForwarder,
+    _runner_forwarder: JoinHandle<()>,
+    _api: api::ApiServer,
+    _dirs: daemon::DaemonDirs,
+    client_ns: Namespace,
+    internet_ns: Namespace,
+}
+
+impl Harness {
+    /// Creates the namespaces, the relay and the API stand-in, and starts the daemon and the test
+    /// runner. The daemon is configured to use the local relay, but is not connected.
+    pub async fn start(config: HarnessConfig) -> Result<Self> {
+        let internet_ns = Namespace::new("inet").await?;
+        let client_ns = Namespace::new("client").await?;
+
+        netns::create_veth_pair(
+            CLIENT_INTERFACE,
+            &internet_ns,
+            IpNetwork::new(IpAddr::V4(INTERNET_ADDR), SEGMENT_PREFIX).unwrap(),
+            &client_ns,
+            IpNetwork::new(IpAddr::V4(CLIENT_ADDR), SEGMENT_PREFIX).unwrap(),
+        )
+        .await?;
+        client_ns
+            .ip(["route", "add", "default", "via", &INTERNET_ADDR.to_string()])
+            .await?;
+
+        relay::create_relay(&internet_ns).await?;
+        let api = api::ApiServer::start(&internet_ns, IpAddr::V4(INTERNET_ADDR))?;
+
+        let dirs = daemon::DaemonDirs::new()?;
+        dirs.write_initial_state(INTERNET_ADDR)?;
+
+        let (daemon_process, mut mullvad_client) =
+            daemon::spawn_daemon(&client_ns, &config.daemon_path, &dirs, api.addr()).await?;
+
+        mullvad_client
+            .set_relay_settings(RelaySettings::Normal(RelayConstraints {
+                location: Constraint::Only(LocationConstraint::from(
+                    GeographicLocationConstraint::Hostname(
+                        "xx".to_owned(),
+                        "tst".to_owned(),
+                        RELAY_HOSTNAME.to_owned(),
+                    ),
+                )),
+                tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+                ..RelayConstraints::default()
+            }))
+            .await
+            .map_err(Error::ConfigureDaemon)?;
+
+        let (runner_process, runner_stream) =
+            daemon::spawn_runner(&client_ns, &config.runner_path, &dirs).await?;
+        let (runner_transport, daemon_forwarder, mut connection_handle, runner_forwarder) =
+            test_rpc::transport::create_client_transports(runner_stream)
+                .map_err(Error::RunnerTransport)?;
+        connection_handle
+            .wait_for_server()
+            .await
+            .map_err(Error::RunnerTransport)?;
+        let runner = ServiceClient::new(connection_handle, runner_transport);
+
+        Ok(Harness {
+            runner,
+            mullvad_client,
+            runner_process,
+            daemon_process,
+            _daemon_forwarder: daemon_forwarder,
+            _runner_forwarder: runner_forwarder,
+            _api: api,
+            _dirs: dirs,
+            client_ns,
+            internet_ns,
+        })
+    }
+
+    /// Client for the test runner, which runs in the client namespace.
+    pub fn runner(&self) -> &ServiceClient {
+        &self.runner
+    }
+
+    /// Client for the daemon's management interface.
+    pub fn mullvad_client(&mut self) -> &mut MullvadProxyClient {
+        &mut self.mullvad_client
+    }
+
+    /// Namespace that the daemon runs in.
+    pub fn client_namespace(&self) -> &Namespace {
+        &self.client_ns
+    }
+
+    /// Namespace that the relay and the API stand-in runs in.
+    pub fn internet_namespace(&self) -> &Namespace {
+        &self.internet_ns
+    }
+
+    /// Stops the daemon and the test runner, and then tears down the namespaces.
+    pub async fn stop(self) {
+        let Harness {
+            runner_process,
+            daemon_process,
+            ..
+        } = self;
+        daemon::stop("test-runner", runner_process).await;
+        daemon::stop("mullvad-daemon", daemon_process).await;
+    }
+}
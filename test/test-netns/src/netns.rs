@@ -0,0 +1,190 @@
+use crate::{Error, Result};
+use std::{
+    ffi::OsStr,
+    fs,
+    net::{SocketAddr, TcpListener},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+use tokio::process::Command;
+
+/// Directory in which `ip netns` keeps the namespace handles.
+const NETNS_RUN_DIR: &str = "/var/run/netns";
+/// Directory from which `ip netns exec` bind mounts per-namespace files over `/etc`.
+const NETNS_ETC_DIR: &str = "/etc/netns";
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// A named network namespace that is deleted when dropped.
+pub struct Namespace {
+    name: String,
+}
+
+impl Namespace {
+    /// Creates a new network namespace. `role` is used to give the namespace a recognizable name.
+    pub async fn new(role: &str) -> Result<Self> {
+        let name = format!(
+            "mlvd-{role}-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        run_ip_cmd(["netns", "add", &name]).await?;
+
+        let namespace = Namespace { name };
+        namespace.ip(["link", "set", "lo", "up"]).await?;
+
+        // Give the namespace its own resolv.conf, so that whatever the daemon does to it does not
+        // affect the host
+        let etc_dir = namespace.etc_dir();
+        fs::create_dir_all(&etc_dir).map_err(Error::CreateNamespaceFiles)?;
+        fs::write(etc_dir.join("resolv.conf"), "").map_err(Error::CreateNamespaceFiles)?;
+
+        Ok(namespace)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs `ip` with the given arguments in this namespace.
+    pub async fn ip<I, S>(&self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut ns_args: Vec<&OsStr> = vec!["-n".as_ref(), self.name.as_ref()];
+        let args: Vec<S> = args.into_iter().collect();
+        ns_args.extend(args.iter().map(AsRef::as_ref));
+        run_ip_cmd(ns_args).await
+    }
+
+    /// Returns a command that runs `program` inside this namespace.
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        let mut cmd = Command::new("ip");
+        cmd.args(["netns", "exec", &self.name]);
+        cmd.arg(program);
+        cmd
+    }
+
+    /// Runs `program` inside this namespace and waits for it to succeed.
+    pub async fn exec<I, S>(&self, program: &str, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self
+            .command(program)
+            .args(args)
+            .output()
+            .await
+            .map_err(|error| Error::ExecStart(program.to_owned(), error))?;
+        if !output.status.success() {
+            log::error!(
+                "'{program}' failed in {}: {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Err(Error::ExecFailed(
+                program.to_owned(),
+                output.status.code().unwrap_or(-1),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Binds a TCP listener inside this namespace. The socket stays in the namespace even though
+    /// it is used from the host.
+    pub fn bind_tcp(&self, addr: SocketAddr) -> Result<TcpListener> {
+        let ns_path = Path::new(NETNS_RUN_DIR).join(&self.name);
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let ns_file = fs::File::open(&ns_path).map_err(Error::EnterNamespace)?;
+                    nix::sched::setns(ns_file.as_raw_fd(), nix::sched::CloneFlags::CLONE_NEWNET)
+                        .map_err(|error| Error::EnterNamespace(error.into()))?;
+                    let listener = TcpListener::bind(addr).map_err(Error::BindListener)?;
+                    listener
+                        .set_nonblocking(true)
+                        .map_err(Error::BindListener)?;
+                    Ok(listener)
+                })
+                .join()
+                .expect("namespace thread panicked")
+        })
+    }
+
+    fn etc_dir(&self) -> PathBuf {
+        Path::new(NETNS_ETC_DIR).join(&self.name)
+    }
+}
+
+impl Drop for Namespace {
+    fn drop(&mut self) {
+        // Deleting the namespace also destroys the interfaces in it
+        let result = std::process::Command::new("ip")
+            .args(["netns", "delete", &self.name])
+            .output();
+        match result {
+            Ok(output) if output.status.success() => (),
+            Ok(output) => log::error!(
+                "Failed to delete namespace {}: {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(error) => log::error!("Failed to run 'ip netns delete': {error}"),
+        }
+        if let Err(error) = fs::remove_dir_all(self.etc_dir()) {
+            log::error!(
+                "Failed to remove files for namespace {}: {error}",
+                self.name
+            );
+        }
+    }
+}
+
+/// Connects two namespaces using a veth pair. The interfaces are named `name` in both namespaces.
+pub async fn create_veth_pair(
+    name: &str,
+    first: &Namespace,
+    first_addr: ipnetwork::IpNetwork,
+    second: &Namespace,
+    second_addr: ipnetwork::IpNetwork,
+) -> Result<()> {
+    let peer_name = format!("{name}-peer");
+    first
+        .ip([
+            "link", "add", name, "type", "veth", "peer", "name", &peer_name,
+        ])
+        .await?;
+    first
+        .ip(["link", "set", &peer_name, "netns", second.name()])
+        .await?;
+    second.ip(["link", "set", &peer_name, "name", name]).await?;
+
+    for (namespace, addr) in [(first, first_addr), (second, second_addr)] {
+        namespace
+            .ip(["addr", "add", &addr.to_string(), "dev", name])
+            .await?;
+        namespace.ip(["link", "set", name, "up"]).await?;
+    }
+    Ok(())
+}
+
+async fn run_ip_cmd<I, S>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut cmd = Command::new("ip");
+    cmd.args(args);
+    let output = cmd.output().await.map_err(Error::IpStart)?;
+    if !output.status.success() {
+        log::error!(
+            "'ip' failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Err(Error::IpFailed(output.status.code().unwrap_or(-1)));
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! A local WireGuard peer that stands in for a Mullvad relay, and a relay list that contains only
+//! that relay.
+
+use crate::{netns::Namespace, Error, Result};
+use mullvad_types::relay_list::{
+    BridgeEndpointData, OpenVpnEndpointData, Relay, RelayEndpointData, RelayList, RelayListCity,
+    RelayListCountry, WireguardEndpointData, WireguardRelayEndpointData,
+};
+use std::{
+    io::Write,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+use talpid_types::net::wireguard;
+
+// Private key of the relay peer.
+const RELAY_PRIVKEY: &str = "gLvQuyqazziyf+pUCAFUgTnWIwn6fPE5MOReOqPEGHU=";
+// Public key of the relay peer.
+const RELAY_PUBKEY: &str = "7svBwGBefP7KVmH/yes+pZCfO6uSOYeGieYYa1+kZ0E=";
+// Private key of the device, i.e. the daemon.
+const DEVICE_PRIVKEY: &str = "mPue6Xt0pdz4NRAhfQSp/SLKo7kV7DW+2zvBq0N9iUI=";
+// Public key of the device, i.e. the daemon.
+const DEVICE_PUBKEY: &str = "h6elqt3dfamtS/p9jxJ8bIYs8UW9YHfTFhvx0fabTFo=";
+
+/// Port that the relay peer listens on.
+pub const RELAY_PORT: u16 = 51820;
+/// Tunnel address of the relay, and gateway of the tunnel.
+pub const RELAY_TUN_ADDR: Ipv4Addr = Ipv4Addr::new(10, 64, 0, 1);
+/// IPv6 tunnel address of the relay, and gateway of the tunnel.
+pub const RELAY_TUN_ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xfc00, 0xbbbb, 0xbbbb, 0xbb01, 0, 0, 0, 1);
+/// Tunnel address assigned to the device.
+pub const DEVICE_TUN_ADDR: Ipv4Addr = Ipv4Addr::new(10, 64, 0, 2);
+/// IPv6 tunnel address assigned to the device.
+pub const DEVICE_TUN_ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xfc00, 0xbbbb, 0xbbbb, 0xbb01, 0, 0, 0, 2);
+
+/// Hostname of the only relay in the relay list.
+pub const RELAY_HOSTNAME: &str = "xx-tst-wg-001";
+/// ETag of the relay list. The API stand-in reports that this version is up to date.
+pub const RELAY_LIST_ETAG: &str = "\"test-netns\"";
+
+/// Name of the WireGuard interface in the internet namespace.
+const RELAY_INTERFACE_NAME: &str = "wg-relay0";
+
+pub fn relay_public_key() -> wireguard::PublicKey {
+    wireguard::PublicKey::from_base64(RELAY_PUBKEY).unwrap()
+}
+
+pub fn device_private_key() -> wireguard::PrivateKey {
+    wireguard::PrivateKey::from_base64(DEVICE_PRIVKEY).unwrap()
+}
+
+/// Sets up the relay peer in `namespace`, listening on [`RELAY_PORT`] on all addresses.
+pub async fn create_relay(namespace: &Namespace) -> Result<()> {
+    namespace
+        .ip([
+            "link",
+            "add",
+            "dev",
+            RELAY_INTERFACE_NAME,
+            "type",
+            "wireguard",
+        ])
+        .await?;
+    namespace
+        .ip([
+            "addr",
+            "add",
+            "dev",
+            RELAY_INTERFACE_NAME,
+            &RELAY_TUN_ADDR.to_string(),
+            "peer",
+            &DEVICE_TUN_ADDR.to_string(),
+        ])
+        .await?;
+    namespace
+        .ip([
+            "-6",
+            "addr",
+            "add",
+            "dev",
+            RELAY_INTERFACE_NAME,
+            &RELAY_TUN_ADDR_V6.to_string(),
+            "peer",
+            &DEVICE_TUN_ADDR_V6.to_string(),
+            "nodad",
+        ])
+        .await?;
+
+    let mut config = tempfile::NamedTempFile::new().map_err(Error::CreateWireguardConfig)?;
+    write!(
+        config,
+        "
+[Interface]
+PrivateKey = {RELAY_PRIVKEY}
+ListenPort = {RELAY_PORT}
+
+[Peer]
+PublicKey = {DEVICE_PUBKEY}
+AllowedIPs = {DEVICE_TUN_ADDR}/32,{DEVICE_TUN_ADDR_V6}/128
+"
+    )
+    .map_err(Error::CreateWireguardConfig)?;
+
+    namespace
+        .exec(
+            "wg",
+            [
+                "setconf".as_ref(),
+                RELAY_INTERFACE_NAME.as_ref(),
+                config.path().as_os_str(),
+            ],
+        )
+        .await?;
+    namespace
+        .ip(["link", "set", "dev", RELAY_INTERFACE_NAME, "up"])
+        .await
+}
+
+/// Returns a relay list containing only the local relay, reachable at `relay_addr`.
+pub fn relay_list(relay_addr: Ipv4Addr) -> RelayList {
+    RelayList {
+        etag: Some(RELAY_LIST_ETAG.to_owned()),
+        countries: vec![RelayListCountry {
+            name: "Testland".to_owned(),
+            code: "xx".to_owned(),
+            cities: vec![RelayListCity {
+                name: "Testville".to_owned(),
+                code: "tst".to_owned(),
+                latitude: 0.0,
+                longitude: 0.0,
+                relays: vec![Relay {
+                    hostname: RELAY_HOSTNAME.to_owned(),
+                    ipv4_addr_in: relay_addr,
+                    ipv6_addr_in: None,
+                    include_in_country: true,
+                    active: true,
+                    owned: true,
+                    provider: "test-netns".to_owned(),
+                    weight: 1,
+                    endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                        public_key: relay_public_key(),
+                        port: None,
+                    }),
+                    location: None,
+                }],
+            }],
+        }],
+        openvpn: OpenVpnEndpointData::default(),
+        bridge: BridgeEndpointData::default(),
+        wireguard: WireguardEndpointData {
+            port_ranges: vec![(RELAY_PORT, RELAY_PORT)],
+            ipv4_gateway: RELAY_TUN_ADDR,
+            ipv6_gateway: RELAY_TUN_ADDR_V6,
+            udp2tcp_ports: vec![],
+        },
+    }
+}
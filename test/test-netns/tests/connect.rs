@@ -0,0 +1,85 @@
+//! These tests require root, and are skipped unless `NETNS_MULLVAD_DAEMON` and
+//! `NETNS_TEST_RUNNER` point out the binaries to test.
+#![cfg(target_os = "linux")]
+
+use mullvad_types::states::TunnelState;
+use std::{net::IpAddr, time::Duration};
+use test_netns::{Harness, HarnessConfig, CLIENT_INTERFACE, INTERNET_ADDR, RELAY_TUN_ADDR};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+async fn start_harness() -> Option<Harness> {
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("Skipping test since it must run as root");
+        return None;
+    }
+    let config = match HarnessConfig::from_env() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Skipping test: {error}");
+            return None;
+        }
+    };
+    let _ = env_logger::builder().is_test(true).try_init();
+    Some(
+        Harness::start(config)
+            .await
+            .expect("failed to start harness"),
+    )
+}
+
+async fn wait_for_state(harness: &mut Harness, predicate: impl Fn(&TunnelState) -> bool) {
+    tokio::time::timeout(CONNECT_TIMEOUT, async {
+        loop {
+            let state = harness.mullvad_client().get_tunnel_state().await.unwrap();
+            if predicate(&state) {
+                return;
+            }
+            if let TunnelState::Error(error) = state {
+                panic!("unexpected error state: {error:?}");
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for tunnel state");
+}
+
+/// Connects to the local relay and checks that traffic goes through the tunnel, and that nothing
+/// leaks outside of it.
+#[tokio::test]
+async fn test_connect_to_local_relay() {
+    let Some(mut harness) = start_harness().await else {
+        return;
+    };
+
+    // Nothing is blocked while disconnected
+    harness
+        .runner()
+        .send_ping(Some(CLIENT_INTERFACE.to_owned()), IpAddr::V4(INTERNET_ADDR))
+        .await
+        .expect("failed to ping while disconnected");
+
+    harness.mullvad_client().connect_tunnel().await.unwrap();
+    wait_for_state(&mut harness, TunnelState::is_connected).await;
+
+    harness
+        .runner()
+        .send_ping(None, IpAddr::V4(RELAY_TUN_ADDR))
+        .await
+        .expect("failed to ping relay gateway through tunnel");
+
+    let leak_result = harness
+        .runner()
+        .send_ping(Some(CLIENT_INTERFACE.to_owned()), IpAddr::V4(INTERNET_ADDR))
+        .await;
+    assert!(
+        leak_result.is_err(),
+        "ICMP traffic leaked outside the tunnel"
+    );
+
+    harness.mullvad_client().disconnect_tunnel().await.unwrap();
+    wait_for_state(&mut harness, TunnelState::is_disconnected).await;
+
+    harness.stop().await;
+}
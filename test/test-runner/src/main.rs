@@ -3,7 +3,7 @@ use logging::LOGGER;
 use std::{
     collections::{BTreeMap, HashMap},
     net::{IpAddr, SocketAddr},
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 
 use tarpc::context;
@@ -293,12 +293,20 @@ impl Service for TestServer {
 }
 
 fn get_pipe_status() -> ServiceStatus {
-    match Path::new(SOCKET_PATH).exists() {
+    match daemon_socket_path().exists() {
         true => ServiceStatus::Running,
         false => ServiceStatus::NotRunning,
     }
 }
 
+/// Returns the path to the management interface of the daemon. This can be overridden using
+/// `MULLVAD_RPC_SOCKET_PATH`, just like for the daemon itself.
+fn daemon_socket_path() -> PathBuf {
+    std::env::var_os("MULLVAD_RPC_SOCKET_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(SOCKET_PATH))
+}
+
 /// The baud rate of the serial connection between the test manager and the test runner.
 /// There is a known issue with setting a baud rate at all or macOS, and the workaround
 /// is to set it to zero: https://github.com/serialport/serialport-rs/pull/58
@@ -318,15 +326,27 @@ async fn main() -> Result<(), Error> {
 
     let mut args = std::env::args();
     let _ = args.next();
-    let path = args.next().expect("serial/COM path must be provided");
+    let path = args
+        .next()
+        .expect("serial/COM path or unix:<socket path> must be provided");
 
     loop {
         log::info!("Connecting to {}", path);
 
-        let serial_stream =
-            tokio_serial::SerialStream::open(&tokio_serial::new(&path, BAUD)).unwrap();
         let (runner_transport, mullvad_daemon_transport, _completion_handle) =
-            test_rpc::transport::create_server_transports(serial_stream);
+            match path.strip_prefix("unix:") {
+                // Used when the test manager runs on the same host, e.g. in a network namespace
+                #[cfg(unix)]
+                Some(socket_path) => {
+                    let stream = tokio::net::UnixStream::connect(socket_path).await.unwrap();
+                    test_rpc::transport::create_server_transports(stream)
+                }
+                _ => {
+                    let serial_stream =
+                        tokio_serial::SerialStream::open(&tokio_serial::new(&path, BAUD)).unwrap();
+                    test_rpc::transport::create_server_transports(serial_stream)
+                }
+            };
 
         log::info!("Running server");
 
@@ -370,7 +390,7 @@ async fn forward_to_mullvad_daemon_interface(proxy_transport: GrpcForwarder) {
         log::info!("mullvad daemon: connecting");
 
         let mut daemon_socket_endpoint =
-            match parity_tokio_ipc::Endpoint::connect(SOCKET_PATH).await {
+            match parity_tokio_ipc::Endpoint::connect(daemon_socket_path()).await {
                 Ok(uds_endpoint) => uds_endpoint,
                 Err(error) => {
                     log::error!("mullvad daemon: failed to connect: {error}");